@@ -0,0 +1,198 @@
+//! Benchmarks of unit creation, signature verification and ordering, run with
+//! `cargo bench --features benchmarks`. Everything is in memory, with a mock keychain.
+use aleph_bft::{
+    benchmarks::{order_dag, send_catch_up, UnitCreation},
+    DefaultMultiKeychain, Hasher, Index, KeyBox as KeyBoxT, MultiKeychain, NodeCount, NodeIndex,
+    PartialMultisignature, UnitBatchingConfig,
+};
+use async_trait::async_trait;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures::executor::block_on;
+use std::{collections::hash_map::DefaultHasher, hash::Hasher as StdHasher, time::Duration};
+
+const SIZES: [usize; 4] = [4, 16, 64, 128];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Hasher64;
+
+impl Hasher for Hasher64 {
+    type Hash = [u8; 8];
+
+    fn hash(x: &[u8]) -> Self::Hash {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(x);
+        hasher.finish().to_ne_bytes()
+    }
+}
+
+// Signatures are hashes of the message and the signer, so that verifying them still costs something.
+#[derive(Clone)]
+struct KeyBox {
+    count: NodeCount,
+    index: NodeIndex,
+}
+
+impl KeyBox {
+    fn signature(msg: &[u8], index: NodeIndex) -> [u8; 8] {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(msg);
+        hasher.write_usize(index.0);
+        hasher.finish().to_ne_bytes()
+    }
+}
+
+impl Index for KeyBox {
+    fn index(&self) -> NodeIndex {
+        self.index
+    }
+}
+
+#[async_trait]
+impl KeyBoxT for KeyBox {
+    type Signature = [u8; 8];
+
+    fn node_count(&self) -> NodeCount {
+        self.count
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Self::Signature {
+        KeyBox::signature(msg, self.index)
+    }
+
+    fn verify(&self, msg: &[u8], sgn: &Self::Signature, index: NodeIndex) -> bool {
+        KeyBox::signature(msg, index) == *sgn
+    }
+}
+
+fn keychains(n_members: usize) -> Vec<DefaultMultiKeychain<KeyBox>> {
+    (0..n_members)
+        .map(|index| {
+            DefaultMultiKeychain::new(KeyBox {
+                count: NodeCount(n_members),
+                index: NodeIndex(index),
+            })
+        })
+        .collect()
+}
+
+fn create_unit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create_unit");
+    for n_members in SIZES {
+        let creation = UnitCreation::<Hasher64>::new(NodeCount(n_members), 10);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(n_members),
+            &creation,
+            |b, creation| b.iter(|| black_box(creation.create_unit())),
+        );
+    }
+    group.finish();
+}
+
+fn verify_multisignature(c: &mut Criterion) {
+    let msg = b"an alert to multisign".to_vec();
+    let mut group = c.benchmark_group("verify_multisignature");
+    for n_members in SIZES {
+        let keychains = keychains(n_members);
+        let keychain = &keychains[0];
+        let mut signatures = keychains
+            .iter()
+            .map(|keychain| (keychain.index(), block_on(keychain.sign(&msg))));
+        let (index, signature) = signatures.next().expect("there are some members");
+        let multisignature = signatures.fold(
+            keychain.from_signature(&signature, index),
+            |partial, (index, signature)| partial.add_signature(&signature, index),
+        );
+        assert!(keychain.is_complete(&msg, &multisignature));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(n_members),
+            &multisignature,
+            |b, multisignature| b.iter(|| black_box(keychain.is_complete(&msg, multisignature))),
+        );
+    }
+    group.finish();
+}
+
+// Like verifying the signatures of a whole round of units at once.
+fn verify_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_batch");
+    for n_members in SIZES {
+        let keychains = keychains(n_members);
+        let signed: Vec<_> = keychains
+            .iter()
+            .map(|keychain| {
+                let msg = format!("the unit of {:?}", keychain.index()).into_bytes();
+                let signature = block_on(keychain.sign(&msg));
+                (msg, signature, keychain.index())
+            })
+            .collect();
+        let batch: Vec<_> = signed
+            .iter()
+            .map(|(msg, signature, index)| (&msg[..], signature, *index))
+            .collect();
+        let keychain = &keychains[0];
+        assert!(keychain.verify_batch(&batch));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(n_members),
+            &batch,
+            |b, batch| b.iter(|| black_box(keychain.verify_batch(batch))),
+        );
+    }
+    group.finish();
+}
+
+fn order(c: &mut Criterion) {
+    let rounds = 20;
+    let mut group = c.benchmark_group("order_dag");
+    group.sample_size(10);
+    for n_members in [4, 16, 64] {
+        let units = n_members * rounds as usize;
+        group.bench_with_input(
+            BenchmarkId::new("units", units),
+            &n_members,
+            |b, n_members| {
+                b.iter(|| black_box(order_dag::<Hasher64>(NodeCount(*n_members), rounds)))
+            },
+        );
+    }
+    group.finish();
+}
+
+// Sending a member all the units it missed, one message per unit or in batches. The parameter
+// of a benchmark is the number of messages on the wire.
+fn catch_up(c: &mut Criterion) {
+    let n_members = NodeCount(16);
+    let rounds = 20;
+    let unit_batching = UnitBatchingConfig {
+        window: Duration::from_millis(10),
+        max_batch_size: 64,
+    };
+    let mut group = c.benchmark_group("catch_up");
+    let single = send_catch_up::<Hasher64>(n_members, rounds, None);
+    let batched = send_catch_up::<Hasher64>(n_members, rounds, Some(unit_batching));
+    assert!(batched < single);
+    group.bench_with_input(
+        BenchmarkId::new("single", single),
+        &None,
+        |b, unit_batching| {
+            b.iter(|| black_box(send_catch_up::<Hasher64>(n_members, rounds, *unit_batching)))
+        },
+    );
+    group.bench_with_input(
+        BenchmarkId::new("batched", batched),
+        &Some(unit_batching),
+        |b, unit_batching| {
+            b.iter(|| black_box(send_catch_up::<Hasher64>(n_members, rounds, *unit_batching)))
+        },
+    );
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    create_unit,
+    verify_multisignature,
+    verify_batch,
+    order,
+    catch_up
+);
+criterion_main!(benches);
@@ -1,4 +1,7 @@
-use futures::{channel::oneshot, StreamExt};
+use futures::{
+    channel::{mpsc, oneshot},
+    StreamExt,
+};
 use log::{debug, info};
 
 use aleph_bft::{run_session, NodeIndex};
@@ -102,8 +105,20 @@ async fn main() {
             count: n_members,
             index: my_id.into(),
         };
-        let config = aleph_bft::default_config(n_members.into(), my_id.into(), 0);
-        run_session(config, network, data_io, keybox, Spawner {}, exit).await
+        let config = aleph_bft::ConfigBuilder::new(n_members.into(), my_id.into())
+            .build()
+            .expect("The config should be valid.");
+        let (_, creator_control) = mpsc::unbounded();
+        run_session(
+            config,
+            network,
+            data_io,
+            keybox,
+            Spawner {},
+            creator_control,
+            exit,
+        )
+        .await
     });
 
     let mut max_block_finalized = 0;
@@ -187,7 +187,7 @@ impl NetworkBehaviourEventProcess<MdnsEvent> for Behaviour {
         if let MdnsEvent::Discovered(list) = event {
             let auth_message = Message::Auth(self.node_ix).encode();
             for (peer, _) in list {
-                if self.peers.iter().any(|p| *p == peer) {
+                if self.peers.contains(&peer) {
                     continue;
                 }
                 self.peers.push(peer);
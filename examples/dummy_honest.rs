@@ -70,8 +70,20 @@ async fn main() {
             count: n_members,
             index: my_id.into(),
         };
-        let config = aleph_bft::default_config(n_members.into(), my_id.into(), 0);
-        run_session(config, network, data_io, keybox, Spawner {}, exit).await
+        let config = aleph_bft::ConfigBuilder::new(n_members.into(), my_id.into())
+            .build()
+            .expect("The config should be valid.");
+        let (_, creator_control) = mpsc::unbounded();
+        run_session(
+            config,
+            network,
+            data_io,
+            keybox,
+            Spawner {},
+            creator_control,
+            exit,
+        )
+        .await
     });
 
     let mut finalized = HashSet::new();
@@ -234,7 +246,7 @@ impl NetworkBehaviourEventProcess<MdnsEvent> for Behaviour {
     fn inject_event(&mut self, event: MdnsEvent) {
         if let MdnsEvent::Discovered(list) = event {
             for (peer, _) in list {
-                if self.peers.iter().any(|p| *p == peer) {
+                if self.peers.contains(&peer) {
                     continue;
                 }
                 self.peers.push(peer);
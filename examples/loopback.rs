@@ -0,0 +1,232 @@
+use aleph_bft::{
+    run_session, ConfigBuilder, FileBackup, LoopbackNetwork, NodeCount, NodeIndex, OrderedBatch,
+    TaskHandle,
+};
+use async_trait::async_trait;
+use codec::{Decode, Encode};
+use futures::{
+    channel::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    future, Future, StreamExt,
+};
+use log::info;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::Hasher as StdHasher,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+const USAGE_MSG: &str = "Usage
+    cargo run --example loopback --features network-loopback [n_members] [n_finalized] [backup_dir]
+
+    n_members -- size of the committee, 4 by default
+    n_finalized -- number of data items to be finalized, 100 by default
+    backup_dir -- if given, every member saves its backup there, as member<index>.backup";
+
+fn parse_arg(n: usize, default: usize) -> usize {
+    match std::env::args().nth(n) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| panic!("{}", USAGE_MSG)),
+        None => default,
+    }
+}
+
+// Runs a whole committee in a single process, connected by a `LoopbackNetwork`, until the first
+// member finalized the requested number of data items.
+#[tokio::main]
+async fn main() {
+    env_logger::builder()
+        .filter_module("loopback", log::LevelFilter::Info)
+        .init();
+
+    let n_members = parse_arg(1, 4);
+    let n_finalized = parse_arg(2, 100) as u64;
+    let backup_dir = std::env::args().nth(3).map(PathBuf::from);
+
+    let mut exits = Vec::new();
+    let mut sessions = Vec::new();
+    let mut finalized = Vec::new();
+    let networks: Vec<LoopbackNetwork<Hasher64, Data, Signature, PartialMultisignature>> =
+        LoopbackNetwork::connected(NodeCount(n_members));
+    for network in networks {
+        let index = network.index();
+        let (data_io, finalized_rx) = DataIO::new(index);
+        let mut config =
+            ConfigBuilder::new(NodeCount(n_members), index).allow_small_committee(true);
+        if let Some(backup_dir) = &backup_dir {
+            let backup = Arc::new(FileBackup::new(
+                backup_dir.join(format!("member{}.backup", index.0)),
+            ));
+            config = config.backup(backup.clone(), backup);
+        }
+        let config = config.build().expect("The config should be valid.");
+        let keybox = KeyBox {
+            count: n_members,
+            index,
+        };
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (_, creator_control) = mpsc::unbounded();
+        sessions.push(tokio::spawn(run_session(
+            config,
+            network,
+            data_io,
+            keybox,
+            Spawner,
+            creator_control,
+            exit_rx,
+        )));
+        exits.push(exit_tx);
+        finalized.push(finalized_rx);
+    }
+
+    // Every member finalizes the same data in the same order, so following one is enough.
+    let mut n_seen = 0;
+    while let Some(batch) = finalized[0].next().await {
+        for (creator, number) in &batch {
+            info!(target: "loopback", "Finalized data item {} of member {}.", number, creator);
+        }
+        n_seen += batch.len() as u64;
+        if n_seen >= n_finalized {
+            break;
+        }
+    }
+    info!(target: "loopback", "Finalized {} data items, stopping.", n_seen);
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    future::join_all(sessions).await;
+}
+
+// This is not cryptographically secure, mocked only for demonstration purposes
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Hasher64;
+
+impl aleph_bft::Hasher for Hasher64 {
+    type Hash = [u8; 8];
+
+    fn hash(x: &[u8]) -> Self::Hash {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(x);
+        hasher.finish().to_ne_bytes()
+    }
+}
+
+// The creator of the data item, together with its number among the items of the creator.
+type Data = (u64, u64);
+
+struct DataIO {
+    index: NodeIndex,
+    next_data: Arc<AtomicU64>,
+    finalized_tx: UnboundedSender<OrderedBatch<Data>>,
+}
+
+impl aleph_bft::DataIO<Data> for DataIO {
+    type Error = ();
+    fn get_data(&self) -> Data {
+        (
+            self.index.0 as u64,
+            self.next_data.fetch_add(1, Ordering::Relaxed),
+        )
+    }
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), Self::Error> {
+        // The receiver is dropped once we saw enough, which is fine.
+        let _ = self.finalized_tx.unbounded_send(data);
+        Ok(())
+    }
+}
+
+impl DataIO {
+    fn new(index: NodeIndex) -> (Self, UnboundedReceiver<OrderedBatch<Data>>) {
+        let (finalized_tx, finalized_rx) = mpsc::unbounded();
+        (
+            DataIO {
+                index,
+                next_data: Arc::new(AtomicU64::new(0)),
+                finalized_tx,
+            },
+            finalized_rx,
+        )
+    }
+}
+
+// This is not cryptographically secure, mocked only for demonstration purposes
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+struct Signature;
+
+// This is not cryptographically secure, mocked only for demonstration purposes
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+struct PartialMultisignature {
+    signed_by: Vec<NodeIndex>,
+}
+
+impl aleph_bft::PartialMultisignature for PartialMultisignature {
+    type Signature = Signature;
+    fn add_signature(self, _: &Self::Signature, index: NodeIndex) -> Self {
+        let Self { mut signed_by } = self;
+        if !signed_by.contains(&index) {
+            signed_by.push(index);
+        }
+        Self { signed_by }
+    }
+}
+
+// This is not cryptographically secure, mocked only for demonstration purposes
+#[derive(Clone)]
+struct KeyBox {
+    count: usize,
+    index: NodeIndex,
+}
+
+#[async_trait]
+impl aleph_bft::KeyBox for KeyBox {
+    type Signature = Signature;
+
+    fn node_count(&self) -> NodeCount {
+        self.count.into()
+    }
+
+    async fn sign(&self, _msg: &[u8]) -> Self::Signature {
+        Signature {}
+    }
+    fn verify(&self, _msg: &[u8], _sgn: &Self::Signature, _index: NodeIndex) -> bool {
+        true
+    }
+}
+
+impl aleph_bft::MultiKeychain for KeyBox {
+    type PartialMultisignature = PartialMultisignature;
+    fn from_signature(&self, _: &Self::Signature, index: NodeIndex) -> Self::PartialMultisignature {
+        let signed_by = vec![index];
+        PartialMultisignature { signed_by }
+    }
+    fn is_complete(&self, _: &[u8], partial: &Self::PartialMultisignature) -> bool {
+        (self.count * 2) / 3 < partial.signed_by.len()
+    }
+}
+
+impl aleph_bft::Index for KeyBox {
+    fn index(&self) -> NodeIndex {
+        self.index
+    }
+}
+
+#[derive(Clone)]
+struct Spawner;
+
+impl aleph_bft::SpawnHandle for Spawner {
+    fn spawn(&self, _: &str, task: impl Future<Output = ()> + Send + 'static) {
+        tokio::spawn(task);
+    }
+    fn spawn_essential(
+        &self,
+        _: &str,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> TaskHandle {
+        Box::pin(async move { tokio::spawn(task).await.map_err(|_| ()) })
+    }
+}
@@ -0,0 +1,58 @@
+use aleph_bft::{replay_backup, FileBackup};
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use std::{collections::hash_map::DefaultHasher, hash::Hasher as StdHasher};
+
+const USAGE_MSG: &str = "Usage
+    cargo run --example replay_backup backup_path
+
+    backup_path -- a backup saved by a member of the loopback example";
+
+// Prints the units in the order the member which saved the backup finalized them, one per line,
+// so that the outputs for two members can be compared with diff.
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| panic!("{}", USAGE_MSG));
+    let mut units = Box::pin(replay_backup::<Hasher64, Data, Signature>(FileBackup::new(
+        path,
+    )));
+    while let Some(unit) = units.next().await {
+        println!(
+            "round {} creator {} hash {}",
+            unit.round(),
+            unit.creator().0,
+            hex(&unit.hash())
+        );
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The types below have to match the ones of the loopback example, so that its backups decode.
+
+// This is not cryptographically secure, mocked only for demonstration purposes
+#[derive(PartialEq, Eq, Clone, Debug)]
+struct Hasher64;
+
+impl aleph_bft::Hasher for Hasher64 {
+    type Hash = [u8; 8];
+
+    fn hash(x: &[u8]) -> Self::Hash {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(x);
+        hasher.finish().to_ne_bytes()
+    }
+}
+
+// The creator of the data item, together with its number among the items of the creator.
+type Data = (u64, u64);
+
+// This is not cryptographically secure, mocked only for demonstration purposes
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+struct Signature;
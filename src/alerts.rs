@@ -1,28 +1,77 @@
 use crate::{
+    clock::Clock,
+    config::DelaySchedule,
     network::Recipient,
     nodes::NodeCount,
     rmc,
-    rmc::{DoublingDelayScheduler, ReliableMulticast},
+    rmc::{DelayScheduler, MultisignatureProgress, ReliableMulticast},
     signed::{Multisigned, PartialMultisignature, Signable, Signature, Signed, UncheckedSigned},
+    terminator::Terminator,
     units::UncheckedSignedUnit,
-    Data, Hasher, Index, MultiKeychain, NodeIndex, Receiver, Sender, SessionId,
+    Data, Hasher, Index, MultiKeychain, NodeIndex, Receiver, Round, Sender, SessionId,
+    SessionStatus,
 };
 use codec::{Decode, Encode};
 use derivative::Derivative;
-use futures::{
-    channel::{mpsc, oneshot},
-    FutureExt, StreamExt,
-};
+use futures::{channel::mpsc, FutureExt, StreamExt};
 use log::{debug, error, info, trace, warn};
 use parking_lot::RwLock;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash as StdHash,
     ops::Deref,
+    sync::Arc,
     time,
 };
 
 pub(crate) type ForkProof<H, D, S> = (UncheckedSignedUnit<H, D, S>, UncheckedSignedUnit<H, D, S>);
 
+/// One of the two conflicting units proving a fork.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForkingUnit {
+    /// The encoding of the unit. The forker signed its hash, computed with the [`Hasher`] of the
+    /// session.
+    pub encoded_unit: Vec<u8>,
+    /// The encoding of the forker's signature of the unit.
+    pub encoded_signature: Vec<u8>,
+}
+
+/// Evidence that a member of the committee created two different units of the same round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForkEvidence {
+    pub forker: NodeIndex,
+    pub round: Round,
+    pub units: [ForkingUnit; 2],
+}
+
+impl ForkEvidence {
+    pub(crate) fn from_proof<H: Hasher, D: Data, S: Signature>(proof: &ForkProof<H, D, S>) -> Self {
+        let unit = |unit: &UncheckedSignedUnit<H, D, S>| ForkingUnit {
+            encoded_unit: unit.as_signable().encode(),
+            encoded_signature: unit.signature().encode(),
+        };
+        ForkEvidence {
+            forker: proof.0.as_signable().creator(),
+            round: proof.0.as_signable().round(),
+            units: [unit(&proof.0), unit(&proof.1)],
+        }
+    }
+}
+
+/// Learns about the forks detected during the session, for example to punish the forkers.
+pub trait ForkHandler: Send + Sync + 'static {
+    /// Called once for every forker we learn about, either by detecting the fork ourselves or
+    /// from an alert of another member.
+    fn on_fork(&self, evidence: ForkEvidence);
+}
+
+/// A [`ForkHandler`] which ignores the forks, used by default.
+pub struct IgnoreForks;
+
+impl ForkHandler for IgnoreForks {
+    fn on_fork(&self, _evidence: ForkEvidence) {}
+}
+
 #[derive(Debug, Decode, Encode, Derivative)]
 #[derivative(PartialEq, Eq, Hash)]
 pub(crate) struct Alert<H: Hasher, D: Data, S: Signature> {
@@ -88,6 +137,13 @@ impl<H: Hasher, D: Data, S: Signature> Alert<H, D, S> {
             .map(|uu| uu.as_signable().data().clone())
             .collect()
     }
+
+    /// All the units in the alert, including the fork proof.
+    pub(crate) fn units(&self) -> impl Iterator<Item = &UncheckedSignedUnit<H, D, S>> {
+        std::iter::once(&self.proof.0)
+            .chain(std::iter::once(&self.proof.1))
+            .chain(self.legit_units.iter())
+    }
 }
 
 impl<H: Hasher, D: Data, S: Signature> Index for Alert<H, D, S> {
@@ -122,6 +178,24 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> AlertMessage<H
             Self::AlertRequest(_, _) => Vec::new(),
         }
     }
+
+    pub(crate) fn units(&self) -> Vec<&UncheckedSignedUnit<H, D, S>> {
+        match self {
+            Self::ForkAlert(unchecked_alert) => unchecked_alert.as_signable().units().collect(),
+            Self::RmcMessage(_, _) => Vec::new(),
+            Self::AlertRequest(_, _) => Vec::new(),
+        }
+    }
+
+    /// The node that sent this message, if the message identifies it. Fork alerts are also
+    /// forwarded by other nodes, so they do not.
+    pub(crate) fn sender(&self) -> Option<NodeIndex> {
+        match self {
+            Self::ForkAlert(_) => None,
+            Self::RmcMessage(sender, _) => Some(*sender),
+            Self::AlertRequest(sender, _) => Some(*sender),
+        }
+    }
 }
 
 // Notifications being sent to consensus, so that it can learn about proven forkers and receive
@@ -149,15 +223,68 @@ struct Alerter<'a, H: Hasher, D: Data, MK: MultiKeychain> {
     known_forkers: HashMap<NodeIndex, ForkProof<H, D, MK::Signature>>,
     known_alerts: HashMap<H::Hash, Signed<'a, Alert<H, D, MK::Signature>, MK>>,
     known_rmcs: HashMap<(NodeIndex, NodeIndex), H::Hash>,
+    alert_timeout: Option<time::Duration>,
+    // When we stop retransmitting our signatures of the alerts, ordered by the time.
+    rmc_deadlines: VecDeque<(time::Instant, H::Hash)>,
+    resent_alerts: ResendLimiter<(H::Hash, NodeIndex)>,
+    alert_requests: ResendLimiter<(H::Hash, NodeIndex)>,
+    clock: Arc<dyn Clock>,
+    status: SessionStatus,
     rmc: ReliableMulticast<'a, H::Hash, MK>,
     messages_from_rmc: Receiver<rmc::Message<H::Hash, MK::Signature, MK::PartialMultisignature>>,
     messages_for_rmc: Sender<rmc::Message<H::Hash, MK::Signature, MK::PartialMultisignature>>,
+    multisignature_progress: Receiver<MultisignatureProgress<H::Hash>>,
     exiting: bool,
 }
 
 pub(crate) struct AlertConfig {
     pub n_members: NodeCount,
     pub session_id: SessionId,
+    pub resend_interval: time::Duration,
+    pub rmc_retry_delay: DelaySchedule,
+    pub alert_timeout: Option<time::Duration>,
+    pub clock: Arc<dyn Clock>,
+    pub status: SessionStatus,
+}
+
+/// Remembers which messages were sent recently, so that we send each of them at most once per
+/// `interval`. A zero interval disables the limit.
+struct ResendLimiter<K> {
+    interval: time::Duration,
+    recent: HashSet<K>,
+    // Ordered by the time of sending, unless the clock went back.
+    sent: VecDeque<(time::Instant, K)>,
+}
+
+impl<K: Clone + Eq + StdHash> ResendLimiter<K> {
+    fn new(interval: time::Duration) -> Self {
+        ResendLimiter {
+            interval,
+            recent: HashSet::new(),
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Whether the message identified by `key` may be sent at `now`. If so, it is recorded as sent.
+    fn allow(&mut self, key: K, now: time::Instant) -> bool {
+        if self.interval.is_zero() {
+            return true;
+        }
+        while let Some((sent_at, _)) = self.sent.front() {
+            // After the clock went back we cannot tell how long ago anything was sent, so we
+            // forget the messages rather than holding them back for as long as the clock jumped.
+            if *sent_at <= now && now.duration_since(*sent_at) < self.interval {
+                break;
+            }
+            let (_, key) = self.sent.pop_front().expect("the element was peeked");
+            self.recent.remove(&key);
+        }
+        if !self.recent.insert(key.clone()) {
+            return false;
+        }
+        self.sent.push_back((now, key));
+        true
+    }
 }
 
 impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
@@ -176,6 +303,7 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
     ) -> Self {
         let (messages_for_rmc, messages_from_us) = mpsc::unbounded();
         let (messages_for_us, messages_from_rmc) = mpsc::unbounded();
+        let (progress_for_us, multisignature_progress) = mpsc::unbounded();
         Self {
             session_id: config.session_id,
             keychain,
@@ -186,15 +314,23 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
             known_forkers: HashMap::new(),
             known_alerts: HashMap::new(),
             known_rmcs: HashMap::new(),
+            alert_timeout: config.alert_timeout,
+            rmc_deadlines: VecDeque::new(),
+            resent_alerts: ResendLimiter::new(config.resend_interval),
+            alert_requests: ResendLimiter::new(config.resend_interval),
             rmc: ReliableMulticast::new(
                 messages_from_us,
                 messages_for_us,
                 keychain,
                 config.n_members,
-                DoublingDelayScheduler::new(time::Duration::from_millis(500)),
-            ),
+                DelayScheduler::new(config.rmc_retry_delay, config.clock.clone()),
+            )
+            .with_progress(progress_for_us),
+            clock: config.clock,
+            status: config.status,
             messages_from_rmc,
             messages_for_rmc,
+            multisignature_progress,
             exiting: false,
         }
     }
@@ -210,6 +346,7 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
     fn on_new_forker_detected(&mut self, forker: NodeIndex, proof: ForkProof<H, D, MK::Signature>) {
         use ForkingNotification::Forker;
         self.known_forkers.insert(forker, proof.clone());
+        self.status.on_fork_detected();
         self.send_notification_for_units(Forker(proof));
     }
 
@@ -225,15 +362,12 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
         forker: NodeIndex,
         units: &[UncheckedSignedUnit<H, D, MK::Signature>],
     ) -> bool {
+        if !UncheckedSigned::are_signatures_valid(units, self.keychain) {
+            warn!(target: "AlephBFT-alerter", "{:?} One of the units is incorrectly signed.", self.index());
+            return false;
+        }
         let mut rounds = HashSet::new();
         for u in units {
-            let u = match u.clone().check(self.keychain) {
-                Ok(u) => u,
-                Err(_) => {
-                    warn!(target: "AlephBFT-alerter", "{:?} One of the units is incorrectly signed.", self.index());
-                    return false;
-                }
-            };
             let full_unit = u.as_signable();
             if full_unit.creator() != forker {
                 warn!(target: "AlephBFT-alerter", "{:?} One of the units {:?} has wrong creator.", self.index(), full_unit);
@@ -250,17 +384,10 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
 
     fn who_is_forking(&self, proof: &ForkProof<H, D, MK::Signature>) -> Option<NodeIndex> {
         let (u1, u2) = proof;
-        let (u1, u2) = {
-            let u1 = u1.clone().check(self.keychain);
-            let u2 = u2.clone().check(self.keychain);
-            match (u1, u2) {
-                (Ok(u1), Ok(u2)) => (u1, u2),
-                _ => {
-                    warn!(target: "AlephBFT-alerter", "{:?} Invalid signatures in a proof.", self.index());
-                    return None;
-                }
-            }
-        };
+        if !UncheckedSigned::are_signatures_valid([u1, u2], self.keychain) {
+            warn!(target: "AlephBFT-alerter", "{:?} Invalid signatures in a proof.", self.index());
+            return None;
+        }
         let full_unit1 = u1.as_signable();
         let full_unit2 = u2.as_signable();
         if full_unit1.session_id() != self.session_id || full_unit2.session_id() != self.session_id
@@ -293,11 +420,47 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
             .insert((alert.as_signable().sender, forker), hash);
         self.known_alerts.insert(hash, alert);
         self.rmc.start_rmc(hash).await;
+        if let Some(deadline) = self
+            .alert_timeout
+            .and_then(|timeout| self.clock.now().checked_add(timeout))
+        {
+            self.rmc_deadlines.push_back((deadline, hash));
+        }
+    }
+
+    fn on_rmc_deadlines(&mut self) {
+        let now = self.clock.now();
+        while let Some((deadline, hash)) = self.rmc_deadlines.front().copied() {
+            if deadline > now {
+                break;
+            }
+            self.rmc_deadlines.pop_front();
+            if self.rmc.get_multisigned(&hash).is_some() {
+                continue;
+            }
+            match self.known_alerts.get(&hash) {
+                Some(alert) => {
+                    let alert = alert.as_signable();
+                    warn!(target: "AlephBFT-alerter", "{:?} Fork alert by {:?} about {:?} unresolved after {:?}, no longer retransmitting our signature.", self.index(), alert.sender, alert.forker(), self.alert_timeout);
+                }
+                None => {
+                    warn!(target: "AlephBFT-alerter", "{:?} Fork alert {:?} unresolved, no longer retransmitting our signature.", self.index(), hash);
+                }
+            }
+            self.rmc.stop_rmc(&hash);
+        }
     }
 
     async fn on_own_alert(&mut self, alert: Alert<H, D, MK::Signature>) {
         let forker = alert.forker();
-        self.known_forkers.insert(forker, alert.proof.clone());
+        if self
+            .known_forkers
+            .insert(forker, alert.proof.clone())
+            .is_none()
+        {
+            self.status.on_fork_detected();
+        }
+        self.status.on_alert_originated();
         let alert = Signed::sign(alert, self.keychain).await;
         self.send_message_for_network(
             AlertMessage::ForkAlert(alert.clone().into()),
@@ -328,6 +491,7 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
                 // We learn about this forker for the first time, need to send our own alert
                 self.on_new_forker_detected(forker, contents.proof.clone());
             }
+            self.status.on_alert_forwarded();
             self.rmc_alert(forker, alert).await;
         } else {
             warn!(target: "AlephBFT-alerter","{:?} We have received an incorrect forking proof from {:?}.", self.index(), alert.as_signable().sender);
@@ -342,6 +506,10 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
                 return;
             }
         };
+        if !self.resent_alerts.allow((hash, node), self.clock.now()) {
+            trace!(target: "AlephBFT-alerter", "{:?} Not sending the alert {:?} to {:?} again so soon.", self.index(), hash, node);
+            return;
+        }
         self.send_message_for_network(AlertMessage::ForkAlert(alert.into()), Recipient::Node(node));
     }
 
@@ -360,7 +528,7 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
                     self.exiting = true;
                 }
             }
-        } else {
+        } else if self.alert_requests.allow((*hash, sender), self.clock.now()) {
             self.send_message_for_network(
                 AlertMessage::AlertRequest(self.index(), *hash),
                 Recipient::Node(sender),
@@ -391,6 +559,13 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
                 return;
             }
         };
+        // The progress arrives before the multisignature completes, taking it in first keeps the
+        // complete multisignature from showing up as still in progress.
+        while let Some(Some(progress)) = self.multisignature_progress.next().now_or_never() {
+            self.status.on_multisignature_progress(progress);
+        }
+        self.status
+            .on_multisignature_collected(multisigned.as_signable().as_ref());
         let forker = alert.proof.0.as_signable().creator();
         self.known_rmcs.insert((alert.sender, forker), alert.hash());
         if !self.correct_commitment(forker, &alert.legit_units) {
@@ -440,8 +615,15 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
         }
     }
 
-    async fn run(&mut self, mut exit: oneshot::Receiver<()>) {
+    async fn run(&mut self, mut terminator: Terminator) {
         loop {
+            let mut rmc_deadline = match self.rmc_deadlines.front() {
+                Some((deadline, _)) => self
+                    .clock
+                    .sleep(deadline.saturating_duration_since(self.clock.now()))
+                    .fuse(),
+                None => futures::future::Fuse::terminated(),
+            };
             futures::select! {
                 message = self.messages_from_network.next() => match message {
                     Some(message) => self.on_message(message).await,
@@ -464,9 +646,17 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
                         break;
                     }
                 },
+                progress = self.multisignature_progress.next() => if let Some(progress) = progress {
+                    self.status.on_multisignature_progress(progress);
+                },
                 multisigned = self.rmc.next_multisigned_hash().fuse() => self.alert_confirmed(multisigned),
-                _ = &mut exit => {
+                _ = rmc_deadline => self.on_rmc_deadlines(),
+                _ = terminator.get_exit() => {
                     info!(target: "AlephBFT-alerter", "{:?} received exit signal", self.index());
+                    // Our alerts still go out, as we might be stopping because of the fork.
+                    while let Ok(alert) = self.alerts_from_units.try_recv() {
+                        self.on_own_alert(alert).await;
+                    }
                     self.exiting = true;
                 },
             }
@@ -475,6 +665,7 @@ impl<'a, H: Hasher, D: Data, MK: MultiKeychain> Alerter<'a, H, D, MK> {
                 break;
             }
         }
+        terminator.terminate_sync().await;
     }
 }
 
@@ -488,7 +679,7 @@ pub(crate) async fn run<H: Hasher, D: Data, MK: MultiKeychain>(
     notifications_for_units: Sender<ForkingNotification<H, D, MK::Signature>>,
     alerts_from_units: Receiver<Alert<H, D, MK::Signature>>,
     config: AlertConfig,
-    exit: oneshot::Receiver<()>,
+    terminator: Terminator,
 ) {
     Alerter::new(
         &keychain,
@@ -498,6 +689,6 @@ pub(crate) async fn run<H: Hasher, D: Data, MK: MultiKeychain>(
         alerts_from_units,
         config,
     )
-    .run(exit)
+    .run(terminator)
     .await
 }
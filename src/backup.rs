@@ -0,0 +1,1386 @@
+use crate::{
+    alerts::ForkProof,
+    nodes::NodeMap,
+    units::{ControlHash, FullUnit, UncheckedSignedUnit, UnitCoord},
+    Data, Hasher, NodeIndex, Round, SessionId, Signature,
+};
+use codec::{Decode, Encode};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use log::{info, warn};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "compression")]
+pub use compression::CompressedBackup;
+
+/// Where the units of the session are saved, so that a restarted node can recover them instead of
+/// creating different units for the rounds it already created units for, which would be a fork.
+///
+/// Units are appended in the order they are passed to the consensus, together with the proofs of
+/// the forks we learn about, which are passed to the [`ForkHandler`](crate::ForkHandler) again
+/// after a restart. Every unit and proof is a separate record, framed with its length and checksum,
+/// so that a record damaged by a crash in the middle of a write is detected when loading.
+#[async_trait::async_trait]
+pub trait BackupSink: Send + Sync + 'static {
+    /// Appends the encoded records to the backup.
+    async fn append(&self, data: &[u8]) -> io::Result<()>;
+    /// Makes sure that all the appended data is persisted. We call it before sending out our own
+    /// units.
+    async fn flush(&self) -> io::Result<()>;
+    /// Atomically replaces the whole backup with `data` and persists it. We call it to cut off
+    /// damaged records, so that new records are not appended after them.
+    async fn replace(&self, data: &[u8]) -> io::Result<()>;
+}
+
+/// The source of the data saved by a [`BackupSink`] in a previous run of the session.
+#[async_trait::async_trait]
+pub trait BackupSource: Send + Sync + 'static {
+    /// Returns everything appended to the backup so far.
+    async fn load(&self) -> io::Result<Vec<u8>>;
+
+    /// Returns everything appended to the backup so far in consecutive chunks. By default it is
+    /// a single chunk returned by [`BackupSource::load`]. Sources of large backups should read
+    /// them gradually instead, as recovering decodes the records as they come and keeps little
+    /// more than the current chunk in memory.
+    fn load_chunks(&self) -> BoxStream<'_, io::Result<Vec<u8>>> {
+        stream::once(self.load()).boxed()
+    }
+}
+
+/// A backup that saves nothing, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoBackup;
+
+#[async_trait::async_trait]
+impl BackupSink for NoBackup {
+    async fn append(&self, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn replace(&self, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupSource for NoBackup {
+    async fn load(&self) -> io::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A backup kept in memory, mainly for tests. Clones share the same contents.
+#[derive(Clone, Default)]
+pub struct MemoryBackup {
+    contents: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemoryBackup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything appended so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.contents.lock().clone()
+    }
+}
+
+impl Debug for MemoryBackup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("MemoryBackup")
+            .field("len", &self.contents.lock().len())
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupSink for MemoryBackup {
+    async fn append(&self, data: &[u8]) -> io::Result<()> {
+        self.contents.lock().extend_from_slice(data);
+        Ok(())
+    }
+
+    async fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn replace(&self, data: &[u8]) -> io::Result<()> {
+        *self.contents.lock() = data.to_vec();
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupSource for MemoryBackup {
+    async fn load(&self) -> io::Result<Vec<u8>> {
+        Ok(self.contents())
+    }
+}
+
+/// A backup in a local file, created when the first units are saved. The file operations block,
+/// which is fine as long as the disk is local. The file is read a megabyte at a time when
+/// recovering.
+#[derive(Debug)]
+pub struct FileBackup {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileBackup {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileBackup {
+            path: path.into(),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// The backup of the session `session_id`, kept in the directory `dir` as
+    /// `session<session_id>.backup`, so that the sessions running at the same time do not
+    /// share a backup.
+    pub fn for_session(dir: impl AsRef<Path>, session_id: SessionId) -> Self {
+        FileBackup::new(dir.as_ref().join(format!("session{}.backup", session_id)))
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupSink for FileBackup {
+    async fn append(&self, data: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock();
+        if file.is_none() {
+            *file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+        file.as_mut()
+            .expect("the file was just opened")
+            .write_all(data)
+    }
+
+    async fn flush(&self) -> io::Result<()> {
+        match self.file.lock().as_mut() {
+            Some(file) => file.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    async fn replace(&self, data: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock();
+        // Write the new contents next to the backup and rename it over the backup, so that a
+        // crash leaves either the old or the new contents.
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(data)?;
+            tmp.sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+        *file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl BackupSource for FileBackup {
+    async fn load(&self) -> io::Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        match File::open(&self.path) {
+            Ok(mut file) => {
+                file.read_to_end(&mut contents)?;
+            }
+            // There is no backup before the first run.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(contents)
+    }
+
+    fn load_chunks(&self) -> BoxStream<'_, io::Result<Vec<u8>>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return stream::empty().boxed(),
+            Err(e) => return stream::once(future::ready(Err(e))).boxed(),
+        };
+        stream::unfold(Some(file), |file| async move {
+            let mut file = file?;
+            let mut chunk = Vec::new();
+            match (&mut file)
+                .take(READ_CHUNK_LEN as u64)
+                .read_to_end(&mut chunk)
+            {
+                Ok(0) => None,
+                Ok(_) => Some((Ok(chunk), Some(file))),
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+        .boxed()
+    }
+}
+
+// The size of the chunks in which `FileBackup` reads the backup.
+const READ_CHUNK_LEN: usize = 1 << 20;
+
+/// Errors that may occur when restoring units from a backup.
+#[derive(Debug)]
+pub(crate) enum LoadError {
+    Io(io::Error),
+    Decode(codec::Error),
+    UnsupportedVersion(u8),
+    Compressed,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            LoadError::Io(e) => write!(f, "reading the backup failed: {}", e),
+            LoadError::Decode(e) => write!(f, "the backup is malformed: {}", e),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "the backup has format version {}, but this version of the crate reads versions up to {}",
+                version, FORMAT_VERSION
+            ),
+            LoadError::Compressed => write!(
+                f,
+                "the backup is compressed, it has to be loaded through a CompressedBackup"
+            ),
+        }
+    }
+}
+
+// The backup starts with the magic bytes followed by the version of the format. Backups written
+// before the format was versioned have no header, they are of version 0. Their first four bytes
+// are the length of the first record, which cannot be equal to the magic bytes, as no unit is
+// that large. The records of versions 0 and 1 are units, since version 2 they are `Record`s.
+// Version 3 added the `Record::Delivered` records, which older versions cannot decode, and
+// version 4 the `Record::SessionTag` records.
+const MAGIC: &[u8; 4] = b"ABFT";
+const FORMAT_VERSION: u8 = 4;
+const FIRST_VERSION_WITH_RECORD_TYPES: u8 = 2;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+// The header of a backup written through a `CompressedBackup`, which cannot be read directly.
+const COMPRESSED_MAGIC: &[u8; 4] = b"ABFZ";
+
+fn header() -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    header.push(FORMAT_VERSION);
+    header
+}
+
+// A record consists of the length of the payload and its checksum, both as u32 in little endian,
+// followed by the payload.
+const RECORD_HEADER_LEN: usize = 8;
+
+fn encode_record(payload: &[u8], data: &mut Vec<u8>) {
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    data.extend_from_slice(payload);
+}
+
+/// Splits the beginning of `input` into the payload of the first record and the rest. Returns
+/// `None` if the record is incomplete or does not match its checksum.
+fn decode_record(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    if input.len() < RECORD_HEADER_LEN {
+        return None;
+    }
+    let (header, rest) = input.split_at(RECORD_HEADER_LEN);
+    let len = u32::from_le_bytes(header[..4].try_into().expect("the slice has 4 bytes")) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().expect("the slice has 4 bytes"));
+    if rest.len() < len {
+        return None;
+    }
+    let (payload, rest) = rest.split_at(len);
+    if crc32fast::hash(payload) != checksum {
+        return None;
+    }
+    Some((payload, rest))
+}
+
+/// A single entry of the backup.
+#[derive(Clone, Debug, Decode, Encode, PartialEq, Eq)]
+pub(crate) enum Record<H: Hasher, D: Data, S: Signature> {
+    Unit(UncheckedSignedUnit<H, D, S>),
+    ForkProof(ForkProof<H, D, S>),
+    /// The number of finalized units passed to the finalization handler so far, and the hash of
+    /// the last of them.
+    Delivered {
+        count: u64,
+        last: H::Hash,
+    },
+    /// The [`Config::session_tag`](crate::Config::session_tag) of the session that saved the
+    /// backup, always the first record.
+    SessionTag(Vec<u8>),
+}
+
+fn encode_records<H: Hasher, D: Data, S: Signature>(records: &[Record<H, D, S>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for record in records {
+        encode_record(&record.encode(), &mut data);
+    }
+    data
+}
+
+fn decode_payload<H: Hasher, D: Data, S: Signature>(
+    mut payload: &[u8],
+    version: u8,
+) -> Result<Record<H, D, S>, LoadError> {
+    let record = if version < FIRST_VERSION_WITH_RECORD_TYPES {
+        UncheckedSignedUnit::decode(&mut payload).map(Record::Unit)
+    } else {
+        Record::decode(&mut payload)
+    }
+    .map_err(LoadError::Decode)?;
+    if !payload.is_empty() {
+        return Err(LoadError::Decode("trailing bytes in a record".into()));
+    }
+    Ok(record)
+}
+
+/// Appends the records to the backup and waits until they are persisted. Returns the number of
+/// bytes appended.
+pub(crate) async fn save_records<H: Hasher, D: Data, S: Signature>(
+    sink: &dyn BackupSink,
+    records: &[Record<H, D, S>],
+) -> io::Result<usize> {
+    if records.is_empty() {
+        return Ok(0);
+    }
+    let data = encode_records(records);
+    sink.append(&data).await?;
+    sink.flush().await?;
+    Ok(data.len())
+}
+
+/// Reads the records of a backup one by one, as the chunks of the backup come from the
+/// [`BackupSource::load_chunks`], keeping only the data of the current chunk and record in
+/// memory.
+///
+/// The backup ends with a damaged record when we crashed while writing it. In that case the
+/// records end with the last intact one. An intact record that does not decode is an error, as it
+/// was not caused by a crash. So is a backup written in a format newer than we know.
+pub(crate) struct RecordReader<'a, H: Hasher, D: Data, S: Signature> {
+    chunks: BoxStream<'a, io::Result<Vec<u8>>>,
+    chunks_ended: bool,
+    // The data read but not decoded yet starts at `start`.
+    buffer: Vec<u8>,
+    start: usize,
+    version: Option<u8>,
+    read_len: usize,
+    // The length of the header and of the records decoded so far.
+    intact_len: usize,
+    n_records: usize,
+    damaged: bool,
+    ended: bool,
+    _phantom: PhantomData<(H, D, S)>,
+}
+
+impl<'a, H: Hasher, D: Data, S: Signature> RecordReader<'a, H, D, S> {
+    pub(crate) fn new(source: &'a dyn BackupSource) -> Self {
+        RecordReader {
+            chunks: source.load_chunks(),
+            chunks_ended: false,
+            buffer: Vec::new(),
+            start: 0,
+            version: None,
+            read_len: 0,
+            intact_len: 0,
+            n_records: 0,
+            damaged: false,
+            ended: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The records of the backup, in the order of saving.
+    pub(crate) fn records(&mut self) -> BoxStream<'_, Result<Record<H, D, S>, LoadError>> {
+        stream::unfold(self, |reader| async move {
+            let record = reader.next_record().await?;
+            Some((record, reader))
+        })
+        .boxed()
+    }
+
+    pub(crate) async fn next_record(&mut self) -> Option<Result<Record<H, D, S>, LoadError>> {
+        let result = self.read_next_record().await;
+        if !matches!(result, Some(Ok(_))) {
+            self.ended = true;
+        }
+        result
+    }
+
+    async fn read_next_record(&mut self) -> Option<Result<Record<H, D, S>, LoadError>> {
+        if self.ended {
+            return None;
+        }
+        loop {
+            let version = match self.version {
+                Some(version) => version,
+                None if self.buffer.len() < HEADER_LEN && !self.chunks_ended => {
+                    if let Err(e) = self.read_chunk().await {
+                        return Some(Err(e));
+                    }
+                    continue;
+                }
+                None => match self.read_header() {
+                    Ok(version) => version,
+                    Err(e) => return Some(Err(e)),
+                },
+            };
+            let input = &self.buffer[self.start..];
+            match record_len(input) {
+                Some(len) if input.len() >= len => {
+                    let (payload, _) = match decode_record(input) {
+                        Some(record) => record,
+                        None => {
+                            self.damaged = true;
+                            return self.skip_damaged().await.err().map(Err);
+                        }
+                    };
+                    let record = decode_payload(payload, version);
+                    self.start += len;
+                    self.intact_len += len;
+                    self.n_records += 1;
+                    return Some(record);
+                }
+                _ if !self.chunks_ended => {
+                    if let Err(e) = self.read_chunk().await {
+                        return Some(Err(e));
+                    }
+                }
+                _ => {
+                    self.damaged = !input.is_empty();
+                    return None;
+                }
+            }
+        }
+    }
+
+    // The backup starts with the header, unless it is of version 0.
+    fn read_header(&mut self) -> Result<u8, LoadError> {
+        if self.buffer.starts_with(COMPRESSED_MAGIC) {
+            return Err(LoadError::Compressed);
+        }
+        let version = if self.buffer.is_empty() {
+            FORMAT_VERSION
+        } else if self.buffer.starts_with(MAGIC) {
+            match self.buffer.get(MAGIC.len()) {
+                Some(version) => {
+                    self.start = HEADER_LEN;
+                    *version
+                }
+                // The header was damaged when writing it, so there are no records yet.
+                None => {
+                    self.start = MAGIC.len();
+                    FORMAT_VERSION
+                }
+            }
+        } else {
+            0
+        };
+        if version > FORMAT_VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+        self.version = Some(version);
+        self.intact_len = self.start;
+        Ok(version)
+    }
+
+    async fn read_chunk(&mut self) -> Result<(), LoadError> {
+        match self.chunks.next().await {
+            Some(Ok(chunk)) => {
+                self.buffer.drain(..self.start);
+                self.start = 0;
+                self.read_len += chunk.len();
+                self.buffer.extend_from_slice(&chunk);
+            }
+            Some(Err(e)) => return Err(LoadError::Io(e)),
+            None => self.chunks_ended = true,
+        }
+        Ok(())
+    }
+
+    // Reads the rest of the backup after a damaged record only to learn its length.
+    async fn skip_damaged(&mut self) -> Result<(), LoadError> {
+        self.buffer.clear();
+        self.start = 0;
+        while !self.chunks_ended {
+            self.read_chunk().await?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Once all the records are read, cuts the damaged records off the backup and upgrades it to
+    /// the current format if needed, so that it is ready for appending records. Returns the size
+    /// of the intact part of the backup in bytes.
+    pub(crate) async fn finish(
+        self,
+        source: &dyn BackupSource,
+        sink: &dyn BackupSink,
+    ) -> Result<usize, LoadError> {
+        let version = self.version.unwrap_or(FORMAT_VERSION);
+        if self.damaged {
+            warn!(target: "AlephBFT-backup", "The backup is damaged after {} records, discarding the last {} bytes.", self.n_records, self.read_len - self.intact_len);
+        }
+        if version < FORMAT_VERSION {
+            info!(target: "AlephBFT-backup", "Upgrading the backup from format version {} to {}.", version, FORMAT_VERSION);
+        }
+        // The records of version 2 are the same in the later versions, only the header differs,
+        // but rewriting them does not hurt.
+        if self.damaged || self.read_len < HEADER_LEN || version < FORMAT_VERSION {
+            return rewrite_records::<H, D, S>(source, sink, |_| true).await;
+        }
+        Ok(self.intact_len)
+    }
+}
+
+/// The records of the backup, in the order of saving, see [`RecordReader`].
+pub(crate) fn read_records<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+) -> BoxStream<'_, Result<Record<H, D, S>, LoadError>> {
+    stream::unfold(RecordReader::new(source), |mut reader| async move {
+        let record = reader.next_record().await?;
+        Some((record, reader))
+    })
+    .boxed()
+}
+
+// The length of the first record of the input, if its header is complete.
+fn record_len(input: &[u8]) -> Option<usize> {
+    let len = input.get(..4)?;
+    Some(
+        RECORD_HEADER_LEN
+            + u32::from_le_bytes(len.try_into().expect("the slice has 4 bytes")) as usize,
+    )
+}
+
+// The backup in the current format with the records of the source which are kept.
+async fn encode_kept_records<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+    mut keep: impl FnMut(&Record<H, D, S>) -> bool,
+) -> Result<Vec<u8>, LoadError> {
+    let mut data = header();
+    let mut records = read_records(source);
+    while let Some(record) = records.next().await {
+        let record = record?;
+        if keep(&record) {
+            encode_record(&record.encode(), &mut data);
+        }
+    }
+    Ok(data)
+}
+
+/// Replaces the backup with the records of the source which are kept, in the current format.
+/// Returns the size of the rewritten backup in bytes. This is the only time we need memory for
+/// the whole backup, as the sink replaces all of it at once.
+async fn rewrite_records<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+    sink: &dyn BackupSink,
+    keep: impl FnMut(&Record<H, D, S>) -> bool,
+) -> Result<usize, LoadError> {
+    let data = encode_kept_records(source, keep).await?;
+    sink.replace(&data).await.map_err(LoadError::Io)?;
+    Ok(data.len())
+}
+
+/// Loads all the records saved in the backup, in the order of saving, together with the size of
+/// the intact part of the backup in bytes, see [`RecordReader`]. Afterwards the backup is in the
+/// current format, ready for appending records.
+pub(crate) async fn load_records<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+    sink: &dyn BackupSink,
+) -> Result<(Vec<Record<H, D, S>>, usize), LoadError> {
+    let mut reader = RecordReader::new(source);
+    let records = reader.records().try_collect().await?;
+    let size = reader.finish(source, sink).await?;
+    Ok((records, size))
+}
+
+/// The lowest round at which the units of a backup break the structure of the Dag, which they
+/// only do if the backup was tampered with or written by a buggy version. The units of the lower
+/// rounds are consistent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Inconsistency {
+    pub(crate) round: Round,
+    pub(crate) reason: String,
+}
+
+/// Checks that we have at most one unit of every round, and that the parents of every unit,
+/// which are the units of the previous round, were saved before it and match its control hash.
+///
+/// A compacted backup lacks the old units of other members, so the parents are only required
+/// in the rounds with some unit of another member saved before. When a parent was created by a
+/// known forker, we do not know which of its variants is meant, and do not check the parents.
+/// The records are checked one by one, keeping only the hashes of the units, and a member might
+/// turn out to be a forker only after the units with its units as parents, so the inconsistencies
+/// involving parents are only suspected until the end.
+pub(crate) struct UnitChecker<H: Hasher> {
+    own_index: NodeIndex,
+    variants: HashMap<UnitCoord, Vec<H::Hash>>,
+    rounds_of_others: HashSet<Round>,
+    forkers: HashSet<NodeIndex>,
+    suspected: Vec<(Inconsistency, Vec<NodeIndex>)>,
+}
+
+impl<H: Hasher> UnitChecker<H> {
+    pub(crate) fn new(own_index: NodeIndex) -> Self {
+        UnitChecker {
+            own_index,
+            variants: HashMap::new(),
+            rounds_of_others: HashSet::new(),
+            forkers: HashSet::new(),
+            suspected: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add<D: Data, S: Signature>(&mut self, record: &Record<H, D, S>) {
+        let full_unit = match record {
+            Record::Unit(unit) => unit.as_signable(),
+            Record::ForkProof((unit, _)) => {
+                self.forkers.insert(unit.as_signable().creator());
+                return;
+            }
+            Record::Delivered { .. } | Record::SessionTag(_) => return,
+        };
+        let hash = full_unit.hash();
+        if let Some((reason, parents)) = self.unit_inconsistency(full_unit, &hash) {
+            let inconsistency = Inconsistency {
+                round: full_unit.round(),
+                reason,
+            };
+            self.suspected.push((inconsistency, parents));
+        }
+        if self.is_new_variant(full_unit.coord(), &hash) {
+            self.forkers.insert(full_unit.creator());
+        }
+        let unit_variants = self.variants.entry(full_unit.coord()).or_default();
+        if !unit_variants.contains(&hash) {
+            unit_variants.push(hash);
+        }
+        if full_unit.creator() != self.own_index {
+            self.rounds_of_others.insert(full_unit.round());
+        }
+    }
+
+    /// The lowest inconsistency of the units added, if any.
+    pub(crate) fn finish(self) -> Result<(), Inconsistency> {
+        let mut lowest: Option<Inconsistency> = None;
+        let UnitChecker {
+            suspected, forkers, ..
+        } = self;
+        for (inconsistency, parents) in suspected {
+            if parents.iter().any(|parent| forkers.contains(parent)) {
+                continue;
+            }
+            if lowest
+                .as_ref()
+                .is_none_or(|lowest| inconsistency.round < lowest.round)
+            {
+                lowest = Some(inconsistency);
+            }
+        }
+        match lowest {
+            Some(inconsistency) => Err(inconsistency),
+            None => Ok(()),
+        }
+    }
+
+    // Whether we already have another unit at the coord.
+    fn is_new_variant(&self, coord: UnitCoord, hash: &H::Hash) -> bool {
+        self.variants
+            .get(&coord)
+            .is_some_and(|variants| !variants.contains(hash))
+    }
+
+    // The inconsistency of the unit, together with the parents it does not count for if any of
+    // them is a forker.
+    fn unit_inconsistency<D: Data>(
+        &self,
+        full_unit: &FullUnit<H, D>,
+        hash: &H::Hash,
+    ) -> Option<(String, Vec<NodeIndex>)> {
+        let (round, creator) = (full_unit.round(), full_unit.creator());
+        if creator == self.own_index && self.is_new_variant(full_unit.coord(), hash) {
+            let reason = format!("there are two different units of ours of round {}", round);
+            return Some((reason, Vec::new()));
+        }
+        if round == 0 {
+            return None;
+        }
+        let control_hash = full_unit.control_hash();
+        let parent_ids: Vec<_> = control_hash.parents().collect();
+        let mut parents = NodeMap::with_size(control_hash.n_members());
+        for &parent in &parent_ids {
+            if self.forkers.contains(&parent) {
+                return None;
+            }
+            match self
+                .variants
+                .get(&UnitCoord::new(round - 1, parent))
+                .map(Vec::as_slice)
+            {
+                Some([parent_hash]) => parents.insert(parent, *parent_hash),
+                Some(_) => return None,
+                None if parent != self.own_index
+                    && !self.rounds_of_others.contains(&(round - 1)) =>
+                {
+                    return None
+                }
+                None => {
+                    let reason = format!(
+                        "the parent of {:?} of the unit of {:?} of round {} was not saved before it",
+                        parent, creator, round
+                    );
+                    return Some((reason, parent_ids));
+                }
+            }
+        }
+        if ControlHash::<H>::combine_hashes(&parents) != control_hash.combined_hash {
+            let reason = format!(
+                "the parents of the unit of {:?} of round {} do not match its control hash",
+                creator, round
+            );
+            return Some((reason, parent_ids));
+        }
+        None
+    }
+}
+
+/// Checks the units of the records, see [`UnitChecker`].
+#[cfg(test)]
+pub(crate) fn check_units<H: Hasher, D: Data, S: Signature>(
+    records: &[Record<H, D, S>],
+    own_index: NodeIndex,
+) -> Result<(), Inconsistency> {
+    let mut checker = UnitChecker::new(own_index);
+    for record in records {
+        checker.add(record);
+    }
+    checker.finish()
+}
+
+/// Whether the record remains in the backup truncated from the given round, see
+/// [`truncate_units`].
+pub(crate) fn survives_truncation<H: Hasher, D: Data, S: Signature>(
+    record: &Record<H, D, S>,
+    round: Round,
+) -> bool {
+    match record {
+        Record::Unit(unit) => unit.as_signable().round() < round,
+        Record::ForkProof(_) | Record::SessionTag(_) => true,
+        Record::Delivered { .. } => false,
+    }
+}
+
+/// Rewrites the backup without the units of the given round and above, so that it is consistent
+/// again. The records of the delivered units are dropped too, as they might refer to removed
+/// units, so all the units are delivered again after recovering. Returns the size of the backup
+/// in bytes.
+pub(crate) async fn truncate_units<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+    sink: &dyn BackupSink,
+    round: Round,
+) -> Result<usize, LoadError> {
+    rewrite_records::<H, D, S>(source, sink, |record| survives_truncation(record, round)).await
+}
+
+/// How many rounds below the last finalized round we keep the units of other members in a
+/// compacted backup. These are the units most likely to be still needed by the consensus, so
+/// keeping them makes recovering from a compacted backup faster.
+const COMPACTION_ROUND_MARGIN: Round = 10;
+
+/// Rewrites the backup keeping only our own units, the units of rounds close to the last
+/// finalized round or above it, all the fork proofs, the session tag and the last record of delivered
+/// units.
+/// Returns the size of the compacted backup in bytes.
+///
+/// The consensus recovers from a backup by replaying the whole Dag, so the removed units are
+/// fetched from other members after a restart. Our own units are never removed: nobody else is
+/// obliged to keep them, and recovering them is what keeps us from forking. Thus if every member
+/// keeps a backup, every unit of an honest member can be recovered by someone.
+pub(crate) async fn compact_units<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+    sink: &dyn BackupSink,
+    own_index: NodeIndex,
+    finalized_round: Round,
+) -> Result<usize, LoadError> {
+    let first_kept_round = finalized_round.saturating_sub(COMPACTION_ROUND_MARGIN);
+    let mut delivered = None;
+    let mut data = encode_kept_records::<H, D, S>(source, |record| match record {
+        Record::Unit(unit) => {
+            let full_unit = unit.as_signable();
+            full_unit.creator() == own_index || full_unit.round() >= first_kept_round
+        }
+        Record::ForkProof(_) | Record::SessionTag(_) => true,
+        Record::Delivered { .. } => {
+            delivered = Some(record.clone());
+            false
+        }
+    })
+    .await?;
+    if let Some(delivered) = delivered {
+        encode_record(&delivered.encode(), &mut data);
+    }
+    sink.replace(&data).await.map_err(LoadError::Io)?;
+    Ok(data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nodes::{NodeCount, NodeIndex, NodeMap},
+        testing::mock::{Data, Hasher64, Signature},
+        units::{ControlHash, FullUnit, PreUnit, UnitCoord},
+        UncheckedSigned,
+    };
+
+    type UncheckedUnit = UncheckedSignedUnit<Hasher64, Data, Signature>;
+    type TestRecord = Record<Hasher64, Data, Signature>;
+
+    fn unit_records(units: &[UncheckedUnit]) -> Vec<TestRecord> {
+        units.iter().cloned().map(Record::Unit).collect()
+    }
+
+    async fn save_units(sink: &dyn BackupSink, units: &[UncheckedUnit]) -> io::Result<usize> {
+        save_records(sink, &unit_records(units)).await
+    }
+
+    async fn load_units(
+        source: &dyn BackupSource,
+        sink: &dyn BackupSink,
+    ) -> Result<(Vec<UncheckedUnit>, usize), LoadError> {
+        let (records, size) = load_records(source, sink).await?;
+        let units = records
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Unit(unit) => Some(unit),
+                Record::ForkProof(_) | Record::Delivered { .. } | Record::SessionTag(_) => None,
+            })
+            .collect();
+        Ok((units, size))
+    }
+
+    // The records of the format versions before 2.
+    fn encode_units(units: &[UncheckedUnit]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for unit in units {
+            encode_record(&unit.encode(), &mut data);
+        }
+        data
+    }
+
+    fn unit(round: u16) -> UncheckedUnit {
+        unit_of(NodeIndex(0), round)
+    }
+
+    fn unit_of(creator: NodeIndex, round: u16) -> UncheckedUnit {
+        variant_of(creator, round, 0)
+    }
+
+    fn variant_of(creator: NodeIndex, round: u16, variant: u32) -> UncheckedUnit {
+        let control_hash = ControlHash::new(&NodeMap::with_size(NodeCount(4)));
+        let full_unit = FullUnit::new(
+            PreUnit::new(creator, round, control_hash),
+            Data::new(UnitCoord::new(round, creator), variant),
+            0,
+        );
+        UncheckedSigned::new(full_unit, Signature {})
+    }
+
+    fn fork_proof(forker: NodeIndex, round: u16) -> TestRecord {
+        Record::ForkProof((variant_of(forker, round, 0), variant_of(forker, round, 1)))
+    }
+
+    // A backup as it is after the first load, that is with the header.
+    async fn empty_backup() -> MemoryBackup {
+        let backup = MemoryBackup::new();
+        let (loaded, size): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert!(loaded.is_empty());
+        assert_eq!(size, HEADER_LEN);
+        backup
+    }
+
+    #[tokio::test]
+    async fn loads_saved_units() {
+        let backup = empty_backup().await;
+        let units: Vec<_> = (0..5).map(unit).collect();
+        save_units(&backup, &units[..2])
+            .await
+            .expect("saving works");
+        save_units(&backup, &units[2..])
+            .await
+            .expect("saving works");
+        let (loaded, size): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, units);
+        assert_eq!(size, backup.contents().len());
+    }
+
+    #[tokio::test]
+    async fn loads_fork_proofs_among_units() {
+        let backup = empty_backup().await;
+        let records = vec![
+            Record::Unit(unit(0)),
+            fork_proof(NodeIndex(2), 0),
+            Record::Unit(unit(1)),
+        ];
+        save_records(&backup, &records).await.expect("saving works");
+        let (loaded, size) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, records);
+        assert_eq!(size, backup.contents().len());
+    }
+
+    #[tokio::test]
+    async fn file_backup_survives_reopening() {
+        let path = std::env::temp_dir().join(format!("aleph-bft-backup-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let units: Vec<_> = (0..3).map(unit).collect();
+        {
+            let backup = FileBackup::new(&path);
+            let (loaded, _): (Vec<UncheckedUnit>, _) =
+                load_units(&backup, &backup).await.expect("loading works");
+            assert!(loaded.is_empty());
+            save_units(&backup, &units).await.expect("saving works");
+        }
+        let backup = FileBackup::new(&path);
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, units);
+
+        backup.append(&[7; 5]).await.expect("appending works");
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, units);
+        save_units(&backup, &[unit(3)]).await.expect("saving works");
+        let backup = FileBackup::new(&path);
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded.len(), 4);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    async fn assert_recovers(backup: MemoryBackup, units: &[UncheckedUnit], intact_len: usize) {
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, units);
+        assert_eq!(backup.contents().len(), intact_len);
+
+        // New units are not lost behind the damaged record.
+        let more: Vec<_> = (units.len() as u16..10).map(unit).collect();
+        save_units(&backup, &more).await.expect("saving works");
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded[..units.len()], units[..]);
+        assert_eq!(loaded[units.len()..], more[..]);
+    }
+
+    #[tokio::test]
+    async fn discards_truncated_record() {
+        let backup = empty_backup().await;
+        let units: Vec<_> = (0..3).map(unit).collect();
+        save_units(&backup, &units).await.expect("saving works");
+        let intact_len = backup.contents().len();
+        let mut damaged = Vec::new();
+        encode_record(&unit(3).encode(), &mut damaged);
+        backup
+            .append(&damaged[..damaged.len() - 5])
+            .await
+            .expect("appending works");
+        assert_recovers(backup, &units, intact_len).await;
+    }
+
+    #[tokio::test]
+    async fn discards_garbled_record() {
+        let backup = empty_backup().await;
+        let units: Vec<_> = (0..3).map(unit).collect();
+        save_units(&backup, &units).await.expect("saving works");
+        let intact_len = backup.contents().len();
+        let mut damaged = Vec::new();
+        encode_record(&unit(3).encode(), &mut damaged);
+        let last = damaged.len() - 1;
+        damaged[last] ^= 1;
+        backup.append(&damaged).await.expect("appending works");
+        assert_recovers(backup, &units, intact_len).await;
+    }
+
+    #[tokio::test]
+    async fn rejects_intact_record_that_does_not_decode() {
+        let backup = empty_backup().await;
+        let mut data = Vec::new();
+        encode_record(&[1, 2, 3], &mut data);
+        backup.append(&data).await.expect("appending works");
+        let result: Result<(Vec<UncheckedUnit>, _), _> = load_units(&backup, &backup).await;
+        assert!(matches!(result, Err(LoadError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_own_and_recent_units() {
+        let backup = empty_backup().await;
+        let units: Vec<_> = (0..30)
+            .flat_map(|round| (0..4).map(move |creator| unit_of(NodeIndex(creator), round)))
+            .collect();
+        save_units(&backup, &units).await.expect("saving works");
+        let proof = fork_proof(NodeIndex(3), 2);
+        save_records(&backup, std::slice::from_ref(&proof))
+            .await
+            .expect("saving works");
+        let size = compact_units::<Hasher64, Data, Signature>(&backup, &backup, NodeIndex(1), 25)
+            .await
+            .expect("compaction works");
+        assert_eq!(size, backup.contents().len());
+
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        let expected: Vec<_> = units
+            .into_iter()
+            .filter(|unit| {
+                let full_unit = unit.as_signable();
+                full_unit.creator() == NodeIndex(1) || full_unit.round() >= 15
+            })
+            .collect();
+        assert_eq!(loaded, expected);
+        let (records, _) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(records.last(), Some(&proof));
+    }
+
+    #[tokio::test]
+    async fn compaction_keeps_last_delivered_record() {
+        let backup = empty_backup().await;
+        let delivered = |count| TestRecord::Delivered {
+            count,
+            last: Hasher64::hash(&count.to_le_bytes()),
+        };
+        let mut records = unit_records(&[unit(0), unit(1)]);
+        records.insert(1, delivered(1));
+        records.push(delivered(2));
+        save_records(&backup, &records).await.expect("saving works");
+        compact_units::<Hasher64, Data, Signature>(&backup, &backup, NodeIndex(1), 25)
+            .await
+            .expect("compaction works");
+        let (records, _) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(records, vec![delivered(2)]);
+    }
+
+    #[tokio::test]
+    async fn compaction_and_truncation_keep_session_tag() {
+        let backup = empty_backup().await;
+        let tag = TestRecord::SessionTag(b"epoch 7".to_vec());
+        let mut records = vec![tag.clone()];
+        records.extend(unit_records(&[unit(0), unit(1)]));
+        save_records(&backup, &records).await.expect("saving works");
+        compact_units::<Hasher64, Data, Signature>(&backup, &backup, NodeIndex(1), 25)
+            .await
+            .expect("compaction works");
+        let (records, _) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(records, vec![tag.clone()]);
+        save_records(&backup, &unit_records(&[unit(0)]))
+            .await
+            .expect("saving works");
+        truncate_units::<Hasher64, Data, Signature>(&backup, &backup, 0)
+            .await
+            .expect("truncation works");
+        let (records, _) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(records, vec![tag]);
+    }
+
+    #[tokio::test]
+    async fn upgrades_backup_without_version() {
+        let backup = MemoryBackup::new();
+        let units: Vec<_> = (0..3).map(unit).collect();
+        backup
+            .append(&encode_units(&units))
+            .await
+            .expect("appending works");
+        let (loaded, size): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, units);
+        assert!(backup.contents().starts_with(&header()));
+        assert_eq!(size, backup.contents().len());
+
+        save_units(&backup, &[unit(3)]).await.expect("saving works");
+        let (loaded, _): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn upgrades_backup_of_units_only() {
+        let backup = MemoryBackup::new();
+        let units: Vec<_> = (0..3).map(unit).collect();
+        let mut data = MAGIC.to_vec();
+        data.push(1);
+        data.extend_from_slice(&encode_units(&units));
+        backup.append(&data).await.expect("appending works");
+        let (loaded, size): (Vec<UncheckedUnit>, _) =
+            load_units(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, units);
+        assert!(backup.contents().starts_with(&header()));
+        assert_eq!(size, backup.contents().len());
+
+        save_records(&backup, &[fork_proof(NodeIndex(2), 1)])
+            .await
+            .expect("saving works");
+        let (records, _) = load_records::<Hasher64, Data, Signature>(&backup, &backup)
+            .await
+            .expect("loading works");
+        assert_eq!(records.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn upgrades_backup_of_version_two() {
+        let backup = MemoryBackup::new();
+        let records = unit_records(&[unit(0), unit(1)]);
+        let mut data = MAGIC.to_vec();
+        data.push(2);
+        data.extend_from_slice(&encode_records(&records));
+        backup.append(&data).await.expect("appending works");
+        let (loaded, size) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, records);
+        assert!(backup.contents().starts_with(&header()));
+        assert_eq!(size, backup.contents().len());
+    }
+
+    #[tokio::test]
+    async fn rejects_backup_of_unknown_version() {
+        let backup = MemoryBackup::new();
+        let mut data = MAGIC.to_vec();
+        data.push(FORMAT_VERSION + 1);
+        data.extend_from_slice(&encode_units(&[unit(0)]));
+        backup.append(&data).await.expect("appending works");
+        let result: Result<(Vec<UncheckedUnit>, _), _> = load_units(&backup, &backup).await;
+        assert!(matches!(
+            result,
+            Err(LoadError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+        assert_eq!(backup.contents(), data);
+    }
+
+    // A unit of the given round with the given units of the previous round as parents.
+    fn unit_with_parents(
+        creator: NodeIndex,
+        round: u16,
+        parents: &[&UncheckedUnit],
+    ) -> UncheckedUnit {
+        let mut parent_map = NodeMap::with_size(NodeCount(4));
+        for parent in parents {
+            let parent = parent.as_signable();
+            parent_map.insert(parent.creator(), parent.hash());
+        }
+        let full_unit = FullUnit::new(
+            PreUnit::new(creator, round, ControlHash::new(&parent_map)),
+            Data::new(UnitCoord::new(round, creator), 0),
+            0,
+        );
+        UncheckedSigned::new(full_unit, Signature {})
+    }
+
+    fn round_zero() -> Vec<UncheckedUnit> {
+        (0..4)
+            .map(|creator| unit_of(NodeIndex(creator), 0))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_consistent_units() {
+        let round_zero = round_zero();
+        let parents: Vec<_> = round_zero.iter().collect();
+        let mut units = round_zero.clone();
+        units.push(unit_with_parents(NodeIndex(0), 1, &parents));
+        units.push(unit_with_parents(NodeIndex(1), 1, &parents[1..]));
+        // Other members might fork.
+        units.push(variant_of(NodeIndex(2), 0, 1));
+        assert_eq!(check_units(&unit_records(&units), NodeIndex(0)), Ok(()));
+    }
+
+    #[test]
+    fn detects_own_fork() {
+        let mut units = round_zero();
+        units.push(variant_of(NodeIndex(0), 0, 1));
+        let inconsistency =
+            check_units(&unit_records(&units), NodeIndex(0)).expect_err("we forked");
+        assert_eq!(inconsistency.round, 0);
+    }
+
+    #[test]
+    fn detects_missing_parents() {
+        let round_zero = round_zero();
+        let parents: Vec<_> = round_zero.iter().collect();
+        let unit = unit_with_parents(NodeIndex(0), 1, &parents);
+        // Our own parent is never removed.
+        let units = vec![
+            round_zero[1].clone(),
+            round_zero[2].clone(),
+            round_zero[3].clone(),
+            unit.clone(),
+        ];
+        let inconsistency =
+            check_units(&unit_records(&units), NodeIndex(0)).expect_err("a parent is missing");
+        assert_eq!(inconsistency.round, 1);
+        // Neither are the parents of rounds with other units.
+        let units = vec![round_zero[0].clone(), round_zero[1].clone(), unit.clone()];
+        assert!(check_units(&unit_records(&units), NodeIndex(0)).is_err());
+        // But the units of others of rounds with none of them were compacted.
+        let units = vec![round_zero[0].clone(), unit];
+        assert_eq!(check_units(&unit_records(&units), NodeIndex(0)), Ok(()));
+    }
+
+    #[test]
+    fn detects_parents_not_matching_control_hash() {
+        let round_zero = round_zero();
+        let mut parents: Vec<_> = round_zero.iter().collect();
+        let other_variant = variant_of(NodeIndex(1), 0, 1);
+        parents[1] = &other_variant;
+        let mut units = round_zero.clone();
+        units.push(unit_with_parents(NodeIndex(2), 1, &parents));
+        // Only the lowest inconsistency is reported, here our missing unit of round 1.
+        let above = unit_with_parents(NodeIndex(0), 2, &[&units[4]]);
+        units.push(above);
+        let inconsistency =
+            check_units(&unit_records(&units), NodeIndex(0)).expect_err("the hash is wrong");
+        assert_eq!(inconsistency.round, 1);
+    }
+
+    #[tokio::test]
+    async fn truncates_units_from_round() {
+        let backup = empty_backup().await;
+        let records = vec![
+            Record::Unit(unit(0)),
+            fork_proof(NodeIndex(2), 1),
+            Record::Unit(unit(1)),
+            Record::Delivered {
+                count: 1,
+                last: unit(0).as_signable().hash(),
+            },
+        ];
+        save_records(&backup, &records).await.expect("saving works");
+        let size = truncate_units::<Hasher64, Data, Signature>(&backup, &backup, 1)
+            .await
+            .expect("truncating works");
+        assert_eq!(size, backup.contents().len());
+        let (loaded, _) = load_records(&backup, &backup).await.expect("loading works");
+        assert_eq!(loaded, records[..2]);
+        assert!(records
+            .iter()
+            .all(|record| loaded.contains(record) == survives_truncation(record, 1)));
+    }
+
+    #[test]
+    fn ignores_parents_of_forkers_proven_later() {
+        let round_zero = round_zero();
+        let mut parents: Vec<_> = round_zero.iter().collect();
+        let other_variant = variant_of(NodeIndex(1), 0, 1);
+        parents[1] = &other_variant;
+        let mut records = unit_records(&round_zero);
+        records.push(Record::Unit(unit_with_parents(NodeIndex(2), 1, &parents)));
+        assert!(check_units(&records, NodeIndex(0)).is_err());
+        records.push(fork_proof(NodeIndex(1), 0));
+        assert_eq!(check_units(&records, NodeIndex(0)), Ok(()));
+    }
+
+    // Returns the contents of the wrapped backup in chunks of the given length, counting the
+    // chunks taken.
+    struct ChunkedBackup {
+        backup: MemoryBackup,
+        chunk_len: usize,
+        taken: Arc<Mutex<usize>>,
+    }
+
+    impl ChunkedBackup {
+        fn new(backup: MemoryBackup, chunk_len: usize) -> Self {
+            ChunkedBackup {
+                backup,
+                chunk_len,
+                taken: Arc::new(Mutex::new(0)),
+            }
+        }
+
+        fn taken(&self) -> usize {
+            *self.taken.lock()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl BackupSource for ChunkedBackup {
+        async fn load(&self) -> io::Result<Vec<u8>> {
+            Ok(self.backup.contents())
+        }
+
+        fn load_chunks(&self) -> BoxStream<'_, io::Result<Vec<u8>>> {
+            let chunks: Vec<_> = self
+                .backup
+                .contents()
+                .chunks(self.chunk_len)
+                .map(<[u8]>::to_vec)
+                .collect();
+            stream::iter(chunks)
+                .map(move |chunk| {
+                    *self.taken.lock() += 1;
+                    Ok(chunk)
+                })
+                .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_records_across_chunks() {
+        let backup = empty_backup().await;
+        let records = vec![
+            Record::Unit(unit(0)),
+            fork_proof(NodeIndex(2), 0),
+            Record::Unit(unit(1)),
+        ];
+        save_records(&backup, &records).await.expect("saving works");
+        let intact_len = backup.contents().len();
+        backup.append(&[7; 5]).await.expect("appending works");
+        for chunk_len in [1, 3, 7, 100, 10_000] {
+            let chunked = ChunkedBackup::new(backup.clone(), chunk_len);
+            let (loaded, size) = load_records(&chunked, &NoBackup)
+                .await
+                .expect("loading works");
+            assert_eq!(loaded, records);
+            assert_eq!(size, intact_len);
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_records_lazily() {
+        let backup = empty_backup().await;
+        let units: Vec<_> = (0..100).map(unit).collect();
+        save_units(&backup, &units).await.expect("saving works");
+        let chunk_len = 16;
+        let chunked = ChunkedBackup::new(backup.clone(), chunk_len);
+        let mut records = read_records::<Hasher64, Data, Signature>(&chunked);
+        let first = records.next().await.expect("there are records");
+        assert_eq!(first.expect("the record decodes"), Record::Unit(unit(0)));
+        let mut first_record = Vec::new();
+        encode_record(&Record::Unit(unit(0)).encode(), &mut first_record);
+        assert!(chunked.taken() <= (HEADER_LEN + first_record.len()) / chunk_len + 1);
+        assert_eq!(records.count().await, units.len() - 1);
+        assert_eq!(chunked.taken(), backup.contents().len().div_ceil(chunk_len));
+    }
+}
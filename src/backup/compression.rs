@@ -0,0 +1,287 @@
+use super::{decode_record, encode_record, BackupSink, BackupSource, COMPRESSED_MAGIC};
+use log::warn;
+use parking_lot::Mutex;
+use std::{
+    convert::TryInto,
+    fmt::{Debug, Formatter, Result as FmtResult},
+    io,
+    sync::Arc,
+};
+use zstd::block::{Compressor, Decompressor};
+
+const LEVEL: i32 = 3;
+/// How much of the preceding uncompressed data is used as the dictionary when compressing an
+/// append. The appends are usually a few units, too small to compress well on their own, but
+/// they resemble the units before them.
+const WINDOW: usize = 32 * 1024;
+// A frame starts with one of these, followed by the data as it is or by its length as u32 in
+// little endian and the data compressed with zstd against the `WINDOW` bytes of uncompressed data
+// preceding it.
+const RAW: u8 = 0;
+const ZSTD: u8 = 1;
+
+/// A backup compressing everything written to the wrapped backup with zstd, available with the
+/// `compression` feature. Use it as both the sink and the source of the session.
+///
+/// Every append is compressed separately and framed with its length and checksum, like the
+/// records of the backup, so that a frame damaged by a crash is cut off when loading. The
+/// compressed backup starts with its own header, so that it is never mistaken for an
+/// uncompressed one, and an uncompressed backup is compressed when it is first loaded through the
+/// wrapper. Since every append is compressed against the data before it, the backup has to be
+/// loaded before appending to it, which the session always does, and it should not be written
+/// other than through the wrapper and its clones. Note that
+/// [`Config::backup_compaction_threshold`](crate::Config::backup_compaction_threshold) counts the
+/// bytes before compression.
+#[derive(Clone)]
+pub struct CompressedBackup<B> {
+    inner: B,
+    // The end of the uncompressed data, `None` until the backup is loaded.
+    history: Arc<Mutex<Option<Vec<u8>>>>,
+}
+
+impl<B> CompressedBackup<B> {
+    pub fn new(inner: B) -> Self {
+        CompressedBackup {
+            inner,
+            history: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The wrapped backup.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    fn set_history(&self, data: &[u8]) {
+        *self.history.lock() = Some(data[data.len().saturating_sub(WINDOW)..].to_vec());
+    }
+}
+
+impl<B: Debug> Debug for CompressedBackup<B> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("CompressedBackup")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+fn encode_frame(data: &[u8], dict: Vec<u8>, frames: &mut Vec<u8>) {
+    let payload = match Compressor::with_dict(dict).compress(data, LEVEL) {
+        Ok(compressed) if compressed.len() + 4 < data.len() => {
+            let mut payload = Vec::with_capacity(compressed.len() + 5);
+            payload.push(ZSTD);
+            payload.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&compressed);
+            payload
+        }
+        _ => {
+            let mut payload = Vec::with_capacity(data.len() + 1);
+            payload.push(RAW);
+            payload.extend_from_slice(data);
+            payload
+        }
+    };
+    encode_record(&payload, frames);
+}
+
+/// Appends the data of the frame to `data`, which holds all the data of the preceding frames.
+fn decode_frame(payload: &[u8], data: &mut Vec<u8>) -> io::Result<()> {
+    let malformed = || io::Error::new(io::ErrorKind::InvalidData, "a malformed compressed frame");
+    match payload.split_first() {
+        Some((&RAW, raw)) => data.extend_from_slice(raw),
+        Some((&ZSTD, rest)) if rest.len() >= 4 => {
+            let (len, compressed) = rest.split_at(4);
+            let len = u32::from_le_bytes(len.try_into().expect("the slice has 4 bytes")) as usize;
+            let dict = data[data.len().saturating_sub(WINDOW)..].to_vec();
+            let decompressed = Decompressor::with_dict(dict).decompress(compressed, len)?;
+            if decompressed.len() != len {
+                return Err(malformed());
+            }
+            data.extend_from_slice(&decompressed);
+        }
+        _ => return Err(malformed()),
+    }
+    Ok(())
+}
+
+fn compressed(data: &[u8]) -> Vec<u8> {
+    let mut contents = COMPRESSED_MAGIC.to_vec();
+    encode_frame(data, Vec::new(), &mut contents);
+    contents
+}
+
+#[async_trait::async_trait]
+impl<B: BackupSink + BackupSource> BackupSink for CompressedBackup<B> {
+    async fn append(&self, data: &[u8]) -> io::Result<()> {
+        let dict = self.history.lock().clone().ok_or_else(|| {
+            io::Error::other("the compressed backup has to be loaded before appending to it")
+        })?;
+        let mut frame = Vec::new();
+        encode_frame(data, dict.clone(), &mut frame);
+        self.inner.append(&frame).await?;
+        let mut history = dict;
+        history.extend_from_slice(data);
+        self.set_history(&history);
+        Ok(())
+    }
+
+    async fn flush(&self) -> io::Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn replace(&self, data: &[u8]) -> io::Result<()> {
+        self.inner.replace(&compressed(data)).await?;
+        self.set_history(data);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<B: BackupSink + BackupSource> BackupSource for CompressedBackup<B> {
+    async fn load(&self) -> io::Result<Vec<u8>> {
+        let contents = self.inner.load().await?;
+        if contents.len() < COMPRESSED_MAGIC.len() && COMPRESSED_MAGIC.starts_with(&contents) {
+            // Empty, or the header was damaged when writing it, so there is nothing yet.
+            self.set_history(&[]);
+            return Ok(Vec::new());
+        }
+        if !contents.starts_with(COMPRESSED_MAGIC) {
+            // Written before the backup was compressed, compress it before anything is appended.
+            self.replace(&contents).await?;
+            return Ok(contents);
+        }
+        let frames = &contents[COMPRESSED_MAGIC.len()..];
+        let mut input = frames;
+        let mut data = Vec::new();
+        let mut n_frames = 0;
+        while let Some((payload, rest)) = decode_record(input) {
+            decode_frame(payload, &mut data)?;
+            n_frames += 1;
+            input = rest;
+        }
+        if !input.is_empty() {
+            warn!(target: "AlephBFT-backup", "The compressed backup is damaged after {} frames, discarding the last {} bytes.", n_frames, input.len());
+            let intact = COMPRESSED_MAGIC.len() + frames.len() - input.len();
+            self.inner.replace(&contents[..intact]).await?;
+        }
+        self.set_history(&data);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressedBackup;
+    use crate::{
+        backup::{load_records, save_records, LoadError, Record},
+        nodes::NodeMap,
+        testing::mock::{Data, Hasher64, Signature},
+        units::{ControlHash, FullUnit, PreUnit, UnitCoord},
+        BackupSink, MemoryBackup, NodeCount, UncheckedSigned,
+    };
+
+    type TestRecord = Record<Hasher64, Data, Signature>;
+
+    fn records(round: u16) -> Vec<TestRecord> {
+        NodeCount(4)
+            .all_indices()
+            .map(|creator| {
+                let control_hash = ControlHash::new(&NodeMap::with_size(NodeCount(4)));
+                let full_unit = FullUnit::new(
+                    PreUnit::new(creator, round, control_hash),
+                    Data::new(UnitCoord::new(round, creator), 0),
+                    0,
+                );
+                Record::Unit(UncheckedSigned::new(full_unit, Signature {}))
+            })
+            .collect()
+    }
+
+    async fn load(backup: &CompressedBackup<MemoryBackup>) -> Vec<TestRecord> {
+        let (loaded, _) = load_records(backup, backup).await.expect("loading works");
+        loaded
+    }
+
+    #[tokio::test]
+    async fn loads_compressed_records() {
+        let backup = CompressedBackup::new(MemoryBackup::new());
+        let plain = MemoryBackup::new();
+        assert!(load(&backup).await.is_empty());
+        load_records::<Hasher64, Data, Signature>(&plain, &plain)
+            .await
+            .expect("loading works");
+        let mut saved = Vec::new();
+        for round in 0..10 {
+            let records = records(round);
+            save_records(&backup, &records).await.expect("saving works");
+            save_records(&plain, &records).await.expect("saving works");
+            saved.extend(records);
+        }
+        assert_eq!(load(&backup).await, saved);
+        assert!(backup.inner().contents().len() < plain.contents().len());
+    }
+
+    #[tokio::test]
+    async fn compresses_uncompressed_backup() {
+        let plain = MemoryBackup::new();
+        load_records::<Hasher64, Data, Signature>(&plain, &plain)
+            .await
+            .expect("loading works");
+        save_records(&plain, &records(0))
+            .await
+            .expect("saving works");
+        let backup = CompressedBackup::new(plain.clone());
+        assert_eq!(load(&backup).await, records(0));
+        save_records(&backup, &records(1))
+            .await
+            .expect("saving works");
+        let mut saved = records(0);
+        saved.extend(records(1));
+        assert_eq!(load(&backup).await, saved);
+    }
+
+    #[tokio::test]
+    async fn cuts_off_damaged_frame() {
+        let backup = CompressedBackup::new(MemoryBackup::new());
+        load(&backup).await;
+        save_records(&backup, &records(0))
+            .await
+            .expect("saving works");
+        let intact = backup.inner().contents();
+        save_records(&backup, &records(1))
+            .await
+            .expect("saving works");
+        let mut contents = backup.inner().contents();
+        contents.truncate(contents.len() - 3);
+        backup
+            .inner()
+            .replace(&contents)
+            .await
+            .expect("replacing works");
+        assert_eq!(load(&backup).await, records(0));
+        assert_eq!(backup.inner().contents(), intact);
+    }
+
+    #[tokio::test]
+    async fn rejects_compressed_backup_loaded_without_decompressing() {
+        let backup = CompressedBackup::new(MemoryBackup::new());
+        load(&backup).await;
+        save_records(&backup, &records(0))
+            .await
+            .expect("saving works");
+        let contents = backup.inner().contents();
+        let plain = backup.inner().clone();
+        assert!(matches!(
+            load_records::<Hasher64, Data, Signature>(&plain, &plain).await,
+            Err(LoadError::Compressed)
+        ));
+        assert_eq!(plain.contents(), contents);
+    }
+
+    #[tokio::test]
+    async fn refuses_appending_before_loading() {
+        let backup = CompressedBackup::new(MemoryBackup::new());
+        assert!(save_records(&backup, &records(0)).await.is_err());
+        assert!(backup.inner().contents().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Entry points to the internal components measured by the benchmarks in `benches/`.
+//! Not a part of the public API, only available with the `benchmarks` feature.
+use crate::{
+    creation::Creator,
+    extender::{Extender, ExtenderUnit},
+    member::UnitMessage,
+    network::NetworkHub,
+    nodes::{NodeCount, NodeIndex, NodeMap, NodeWeights},
+    signed::SignatureSet,
+    terminator::Terminator,
+    units::{ControlHash, FullUnit, PreUnit, Unit},
+    AllAvailableParents, Hasher, Network, NetworkData, RealClock, Recipient, Round, SessionStatus,
+    UncheckedSigned, UnitBatchingConfig,
+};
+use codec::Encode;
+use futures::{
+    channel::{mpsc, oneshot},
+    executor::block_on,
+    future::join,
+    StreamExt,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+fn unit_hash<H: Hasher>(creator: NodeIndex, round: Round) -> H::Hash {
+    H::hash(&(round, creator.0 as u64).encode())
+}
+
+// Every unit of a non-zero round has all the units of the previous round as parents.
+fn parents<H: Hasher>(round: Round, n_members: NodeCount) -> NodeMap<H::Hash> {
+    let mut parents = NodeMap::with_size(n_members);
+    if round > 0 {
+        for creator in n_members.all_indices() {
+            parents.insert(creator, unit_hash::<H>(creator, round - 1));
+        }
+    }
+    parents
+}
+
+/// A creator of the first member knowing all the units of a full Dag below the given round.
+pub struct UnitCreation<H: Hasher> {
+    creator: Creator<H>,
+    round: Round,
+}
+
+impl<H: Hasher> UnitCreation<H> {
+    pub fn new(n_members: NodeCount, round: Round) -> Self {
+        let mut creator = Creator::new(
+            NodeIndex(0),
+            NodeWeights::uniform(n_members),
+            Arc::new(AllAvailableParents),
+        );
+        for unit_round in 0..round {
+            let control_hash = ControlHash::new(&parents::<H>(unit_round, n_members));
+            for creator_id in n_members.all_indices() {
+                let pre_unit = PreUnit::new(creator_id, unit_round, control_hash.clone());
+                let unit = Unit::new(pre_unit, unit_hash::<H>(creator_id, unit_round));
+                creator.add_unit(&unit);
+            }
+        }
+        UnitCreation { creator, round }
+    }
+
+    /// Creates our unit of the round, returning the hashes of its parents.
+    pub fn create_unit(&self) -> Option<Vec<H::Hash>> {
+        self.creator
+            .create_unit(self.round)
+            .map(|(_, parent_hashes)| parent_hashes)
+    }
+}
+
+/// Orders a full Dag with the given number of rounds, returning the number of batches.
+pub fn order_dag<H: Hasher>(n_members: NodeCount, rounds: Round) -> usize {
+    let (electors_tx, electors_rx) = mpsc::unbounded();
+    let (batch_tx, mut batch_rx) = mpsc::unbounded();
+    let (finalized_rounds_tx, mut finalized_rounds_rx) = mpsc::unbounded();
+    let mut extender = Extender::<H>::new(
+        NodeIndex(0),
+        NodeWeights::uniform(n_members),
+        None,
+        electors_rx,
+        batch_tx,
+        finalized_rounds_tx,
+        SessionStatus::new(),
+    );
+    for round in 0..rounds {
+        for creator in n_members.all_indices() {
+            let unit = ExtenderUnit::new(
+                creator,
+                round,
+                unit_hash::<H>(creator, round),
+                parents::<H>(round, n_members),
+            );
+            electors_tx
+                .unbounded_send(unit)
+                .expect("the extender owns the receiver");
+        }
+    }
+    let (exit_tx, exit_rx) = oneshot::channel();
+    // A round is decided once there are units four rounds above it.
+    let all_decided = async move {
+        for _ in 0..rounds.saturating_sub(4) {
+            finalized_rounds_rx.next().await;
+        }
+        let _ = exit_tx.send(());
+    };
+    let terminator = Terminator::create_root(exit_rx, "AlephBFT-extender");
+    block_on(join(extender.extend(terminator), all_decided));
+    let mut n_batches = 0;
+    while batch_rx.try_recv().is_ok() {
+        n_batches += 1;
+    }
+    n_batches
+}
+
+type CatchUpSignature = [u8; 8];
+
+// Counts the messages sent, encoding them like a network sending bytes would.
+struct CountingNetwork(Arc<AtomicUsize>);
+
+#[async_trait::async_trait]
+impl<H: Hasher> Network<H, u64, CatchUpSignature, SignatureSet<CatchUpSignature>>
+    for CountingNetwork
+{
+    fn send(
+        &self,
+        data: NetworkData<H, u64, CatchUpSignature, SignatureSet<CatchUpSignature>>,
+        _: Recipient,
+    ) {
+        std::hint::black_box(data.encode());
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    async fn next_event(
+        &mut self,
+    ) -> Option<NetworkData<H, u64, CatchUpSignature, SignatureSet<CatchUpSignature>>> {
+        futures::future::pending().await
+    }
+}
+
+/// Sends the units of a full Dag with the given number of rounds to a single member through the
+/// network hub, one response each, like when the member catches up. Returns the number of
+/// messages sent.
+pub fn send_catch_up<H: Hasher>(
+    n_members: NodeCount,
+    rounds: Round,
+    unit_batching: Option<UnitBatchingConfig>,
+) -> usize {
+    let n_messages = Arc::new(AtomicUsize::new(0));
+    let (_, units_to_send) = mpsc::unbounded();
+    let (units_received, _) = mpsc::unbounded();
+    let (_, alerts_to_send) = mpsc::unbounded();
+    let (alerts_received, _) = mpsc::unbounded();
+    let mut hub = NetworkHub::<H, u64, CatchUpSignature, SignatureSet<CatchUpSignature>, _>::new(
+        CountingNetwork(n_messages.clone()),
+        units_to_send,
+        units_received,
+        alerts_to_send,
+        alerts_received,
+        None,
+        None,
+        unit_batching,
+        Arc::new(RealClock),
+    );
+    for round in 0..rounds {
+        let control_hash = ControlHash::new(&parents::<H>(round, n_members));
+        for creator in n_members.all_indices() {
+            let pre_unit = PreUnit::new(creator, round, control_hash.clone());
+            let full_unit = FullUnit::new(pre_unit, round as u64, 0);
+            let unit = UncheckedSigned::new(full_unit, [0; 8]);
+            hub.send_units(
+                UnitMessage::ResponseCoord(unit),
+                Recipient::Node(NodeIndex(1)),
+            );
+        }
+    }
+    hub.flush_batches();
+    n_messages.load(Ordering::Relaxed)
+}
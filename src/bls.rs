@@ -0,0 +1,405 @@
+use crate::{
+    nodes::{NodeCount, NodeIndex, NodeSubset, NodeWeights},
+    Index, KeyBox, MultiKeychain, PartialMultisignature,
+};
+use async_trait::async_trait;
+use bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve},
+    multi_miller_loop, pairing, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective, Gt,
+    Scalar,
+};
+use codec::{Decode, Encode};
+use rand::RngCore;
+use std::{fmt, sync::Arc};
+
+// Domain separation tags of the proof of possession scheme with signatures in G1, as in the
+// IETF BLS signature draft.
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+const POSSESSION_DST: &[u8] = b"BLS_POP_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+
+fn hash_to_g1(msg: &[u8], dst: &[u8]) -> G1Projective {
+    <G1Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(msg, dst)
+}
+
+// Checks e(signature, g2) == e(H(msg), public_key).
+fn verify_point(msg: &[u8], dst: &[u8], signature: &G1Affine, public_key: &G2Affine) -> bool {
+    let hash = G1Affine::from(hash_to_g1(msg, dst));
+    pairing(signature, &G2Affine::generator()) == pairing(&hash, public_key)
+}
+
+/// A secret key of a member used for BLS signatures.
+#[derive(Clone)]
+pub struct BlsSecretKey(Scalar);
+
+impl BlsSecretKey {
+    /// Generates a random secret key.
+    pub fn generate<R: RngCore>(rng: &mut R) -> Self {
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        Self::from_bytes_wide(&bytes)
+    }
+
+    /// Derives a secret key from 64 uniformly random bytes.
+    pub fn from_bytes_wide(bytes: &[u8; 64]) -> Self {
+        BlsSecretKey(Scalar::from_bytes_wide(bytes))
+    }
+
+    /// The public key corresponding to this secret key.
+    pub fn public_key(&self) -> BlsPublicKey {
+        BlsPublicKey((G2Affine::generator() * self.0).into())
+    }
+
+    /// A proof that the holder of the public key knows this secret key, to be verified with
+    /// [`BlsPublicKey::verify_possession`] before the public key is trusted.
+    pub fn prove_possession(&self) -> BlsSignature {
+        let msg = self.public_key().to_bytes();
+        self.sign_with_dst(&msg, POSSESSION_DST)
+    }
+
+    fn sign_with_dst(&self, msg: &[u8], dst: &[u8]) -> BlsSignature {
+        BlsSignature::from_point(&(hash_to_g1(msg, dst) * self.0).into())
+    }
+}
+
+impl fmt::Debug for BlsSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BlsSecretKey(..)")
+    }
+}
+
+/// A public key of a member used for BLS signatures, a point of G2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlsPublicKey(G2Affine);
+
+impl BlsPublicKey {
+    /// The compressed encoding of the key.
+    pub fn to_bytes(&self) -> [u8; 96] {
+        self.0.to_compressed()
+    }
+
+    /// Decodes a key from the compressed encoding, returns `None` if it is not a valid point.
+    pub fn from_bytes(bytes: &[u8; 96]) -> Option<Self> {
+        Option::from(G2Affine::from_compressed(bytes)).map(BlsPublicKey)
+    }
+
+    /// Verifies a proof created with [`BlsSecretKey::prove_possession`].
+    pub fn verify_possession(&self, proof: &BlsSignature) -> bool {
+        match proof.to_point() {
+            Some(point) => verify_point(&self.to_bytes(), POSSESSION_DST, &point, &self.0),
+            None => false,
+        }
+    }
+}
+
+/// A BLS signature, a point of G1 in the compressed form.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct BlsSignature([u8; 48]);
+
+impl BlsSignature {
+    fn from_point(point: &G1Affine) -> Self {
+        BlsSignature(point.to_compressed())
+    }
+
+    fn to_point(&self) -> Option<G1Affine> {
+        Option::from(G1Affine::from_compressed(&self.0))
+    }
+}
+
+/// Signatures of a single message aggregated into one point, together with the set of members
+/// whose signatures were added.
+///
+/// Apart from a single bit per member, the size does not depend on the number of signatures.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct BlsMultisignature {
+    signers: NodeSubset,
+    aggregate: BlsSignature,
+}
+
+impl PartialMultisignature for BlsMultisignature {
+    type Signature = BlsSignature;
+
+    fn add_signature(mut self, signature: &BlsSignature, index: NodeIndex) -> Self {
+        if index.0 >= self.signers.size() || self.signers[index] {
+            return self;
+        }
+        let (aggregate, signature) = match (self.aggregate.to_point(), signature.to_point()) {
+            (Some(aggregate), Some(signature)) => (aggregate, signature),
+            _ => return self,
+        };
+        self.aggregate =
+            BlsSignature::from_point(&(G1Projective::from(aggregate) + signature).into());
+        self.signers.insert(index);
+        self
+    }
+}
+
+/// A [`MultiKeychain`] using BLS signatures over the BLS12-381 curve, with multisignatures
+/// aggregated into a [`BlsMultisignature`].
+///
+/// As with [`DefaultMultiKeychain`], a multisignature is complete once it contains signatures
+/// of more than 2/3 of the members, or of their total weight, see [`BlsKeychain::with_weights`].
+/// Aggregation is vulnerable to rogue key attacks, so the public
+/// keys must only be accepted together with a valid proof of possession, see
+/// [`BlsSecretKey::prove_possession`].
+#[derive(Clone)]
+pub struct BlsKeychain {
+    index: NodeIndex,
+    secret_key: BlsSecretKey,
+    public_keys: Arc<Vec<BlsPublicKey>>,
+    node_weights: NodeWeights,
+}
+
+impl BlsKeychain {
+    /// Creates the keychain of the member with the given index and secret key. The public keys
+    /// of all members are given in the order of their indices.
+    pub fn new(index: NodeIndex, secret_key: BlsSecretKey, public_keys: Vec<BlsPublicKey>) -> Self {
+        assert!(
+            public_keys.get(index.0) == Some(&secret_key.public_key()),
+            "The secret key should match the public key of the member."
+        );
+        let node_weights = NodeWeights::uniform(public_keys.len().into());
+        BlsKeychain {
+            index,
+            secret_key,
+            public_keys: Arc::new(public_keys),
+            node_weights,
+        }
+    }
+
+    /// Makes multisignatures complete once their signers carry more than 2/3 of the total of the
+    /// given weights, instead of being more than 2/3 of the members.
+    pub fn with_weights(mut self, node_weights: NodeWeights) -> Self {
+        assert_eq!(
+            node_weights.n_members(),
+            self.node_count(),
+            "There should be a weight for every member."
+        );
+        self.node_weights = node_weights;
+        self
+    }
+}
+
+impl Index for BlsKeychain {
+    fn index(&self) -> NodeIndex {
+        self.index
+    }
+}
+
+#[async_trait]
+impl KeyBox for BlsKeychain {
+    type Signature = BlsSignature;
+
+    fn node_count(&self) -> NodeCount {
+        self.public_keys.len().into()
+    }
+
+    async fn sign(&self, msg: &[u8]) -> BlsSignature {
+        self.secret_key.sign_with_dst(msg, SIGNATURE_DST)
+    }
+
+    fn verify(&self, msg: &[u8], sgn: &BlsSignature, index: NodeIndex) -> bool {
+        match (self.public_keys.get(index.0), sgn.to_point()) {
+            (Some(public_key), Some(point)) => {
+                verify_point(msg, SIGNATURE_DST, &point, &public_key.0)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl MultiKeychain for BlsKeychain {
+    type PartialMultisignature = BlsMultisignature;
+
+    fn from_signature(&self, signature: &BlsSignature, index: NodeIndex) -> BlsMultisignature {
+        let mut signers = NodeSubset::with_size(self.node_count());
+        signers.insert(index);
+        BlsMultisignature {
+            signers,
+            aggregate: signature.clone(),
+        }
+    }
+
+    fn is_complete(&self, msg: &[u8], partial: &BlsMultisignature) -> bool {
+        let node_count = self.node_count();
+        if partial.signers.size() != node_count.0 {
+            return false;
+        }
+        let signers: Vec<_> = partial.signers.elements().collect();
+        if !self.node_weights.is_quorum(signers.iter().copied()) {
+            return false;
+        }
+        let aggregate = match partial.aggregate.to_point() {
+            Some(aggregate) => aggregate,
+            None => return false,
+        };
+        let public_key = signers
+            .iter()
+            .fold(G2Projective::identity(), |sum, signer| {
+                sum + self.public_keys[signer.0].0
+            });
+        verify_point(msg, SIGNATURE_DST, &aggregate, &public_key.into())
+    }
+
+    fn verify_batch(&self, batch: &[(&[u8], &BlsSignature, NodeIndex)]) -> bool {
+        // Checks e(sum r_i * s_i, g2) == prod e(r_i * H(m_i), pk_i) with random r_i, so that
+        // invalid signatures cannot cancel each other out. Only a single final exponentiation
+        // is needed for the whole batch.
+        let mut rng = rand::thread_rng();
+        let mut aggregate = G1Projective::identity();
+        let mut terms = Vec::with_capacity(batch.len() + 1);
+        for (msg, sgn, index) in batch {
+            let (public_key, signature) = match (self.public_keys.get(index.0), sgn.to_point()) {
+                (Some(public_key), Some(signature)) => (public_key, signature),
+                _ => return false,
+            };
+            let coefficient = Scalar::from(rng.next_u64());
+            aggregate += signature * coefficient;
+            let hash = G1Affine::from(hash_to_g1(msg, SIGNATURE_DST) * coefficient);
+            terms.push((hash, G2Prepared::from(public_key.0)));
+        }
+        terms.push((
+            G1Affine::from(-aggregate),
+            G2Prepared::from(G2Affine::generator()),
+        ));
+        let terms: Vec<_> = terms.iter().map(|(p, q)| (p, q)).collect();
+        multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+    use futures::executor::block_on;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn keychains(n_members: usize) -> Vec<BlsKeychain> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let secret_keys: Vec<_> = (0..n_members)
+            .map(|_| BlsSecretKey::generate(&mut rng))
+            .collect();
+        let public_keys: Vec<_> = secret_keys.iter().map(BlsSecretKey::public_key).collect();
+        secret_keys
+            .into_iter()
+            .enumerate()
+            .map(|(i, secret_key)| BlsKeychain::new(i.into(), secret_key, public_keys.clone()))
+            .collect()
+    }
+
+    fn multisign(keychains: &[BlsKeychain], msg: &[u8], signers: usize) -> BlsMultisignature {
+        let signature = block_on(keychains[0].sign(msg));
+        let mut partial = keychains[0].from_signature(&signature, 0.into());
+        for keychain in keychains.iter().take(signers).skip(1) {
+            let signature = block_on(keychain.sign(msg));
+            partial = partial.add_signature(&signature, keychain.index());
+        }
+        partial
+    }
+
+    #[test]
+    fn verifies_signatures() {
+        let keychains = keychains(4);
+        let msg = b"message";
+        let signature = block_on(keychains[1].sign(msg));
+        assert!(keychains[0].verify(msg, &signature, 1.into()));
+        assert!(!keychains[0].verify(msg, &signature, 2.into()));
+        assert!(!keychains[0].verify(b"other message", &signature, 1.into()));
+        assert!(!keychains[0].verify(msg, &signature, 4.into()));
+        assert!(!keychains[0].verify(msg, &BlsSignature([0; 48]), 1.into()));
+    }
+
+    #[test]
+    fn completes_after_more_than_two_thirds_of_signatures() {
+        let keychains = keychains(7);
+        let msg = b"message";
+        assert!(!keychains[3].is_complete(msg, &multisign(&keychains, msg, 4)));
+        assert!(keychains[3].is_complete(msg, &multisign(&keychains, msg, 5)));
+        assert!(keychains[3].is_complete(msg, &multisign(&keychains, msg, 7)));
+        assert!(!keychains[3].is_complete(b"other message", &multisign(&keychains, msg, 7)));
+    }
+
+    #[test]
+    fn completes_after_more_than_two_thirds_of_weight() {
+        let weights = NodeWeights::new(vec![5, 1, 1, 1]);
+        let keychains: Vec<_> = keychains(4)
+            .into_iter()
+            .map(|keychain| keychain.with_weights(weights.clone()))
+            .collect();
+        let msg = b"message";
+        assert!(!keychains[3].is_complete(msg, &multisign(&keychains, msg, 1)));
+        assert!(keychains[3].is_complete(msg, &multisign(&keychains, msg, 2)));
+    }
+
+    #[test]
+    fn adding_a_signature_twice_does_not_count() {
+        let keychains = keychains(4);
+        let msg = b"message";
+        let mut partial = multisign(&keychains, msg, 2);
+        let signature = block_on(keychains[1].sign(msg));
+        partial = partial.add_signature(&signature, 1.into());
+        assert!(!keychains[0].is_complete(msg, &partial));
+        let signature = block_on(keychains[2].sign(msg));
+        partial = partial.add_signature(&signature, 2.into());
+        assert!(keychains[0].is_complete(msg, &partial));
+    }
+
+    #[test]
+    fn rejects_multisignature_with_invalid_signature() {
+        let keychains = keychains(4);
+        let msg = b"message";
+        let partial = multisign(&keychains, msg, 2);
+        let signature = block_on(keychains[2].sign(b"other message"));
+        let partial = partial.add_signature(&signature, 2.into());
+        assert!(!keychains[0].is_complete(msg, &partial));
+    }
+
+    #[test]
+    fn verifies_batches_of_signatures() {
+        let keychains = keychains(4);
+        let messages: Vec<&[u8]> = vec![b"first", b"second", b"third"];
+        let signatures: Vec<_> = messages
+            .iter()
+            .zip(keychains.iter())
+            .map(|(msg, keychain)| block_on(keychain.sign(msg)))
+            .collect();
+        let mut batch: Vec<_> = messages
+            .iter()
+            .zip(signatures.iter())
+            .enumerate()
+            .map(|(i, (msg, sgn))| (*msg, sgn, NodeIndex(i)))
+            .collect();
+        assert!(keychains[3].verify_batch(&batch));
+        assert!(keychains[3].verify_batch(&[]));
+        batch[1].2 = NodeIndex(2);
+        assert!(!keychains[3].verify_batch(&batch));
+        batch[1].2 = NodeIndex(1);
+        batch[2].0 = b"other message";
+        assert!(!keychains[3].verify_batch(&batch));
+    }
+
+    #[test]
+    fn multisignature_size_does_not_grow_with_signatures() {
+        let keychains = keychains(7);
+        let msg = b"message";
+        let small = multisign(&keychains, msg, 1);
+        let large = multisign(&keychains, msg, 7);
+        assert_eq!(small.encode().len(), large.encode().len());
+        let decoded =
+            BlsMultisignature::decode(&mut large.encode().as_slice()).expect("should decode");
+        assert!(keychains[0].is_complete(msg, &decoded));
+    }
+
+    #[test]
+    fn verifies_proofs_of_possession() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let secret_key = BlsSecretKey::generate(&mut rng);
+        let other_key = BlsSecretKey::generate(&mut rng);
+        let public_key = secret_key.public_key();
+        assert!(public_key.verify_possession(&secret_key.prove_possession()));
+        assert!(!public_key.verify_possession(&other_key.prove_possession()));
+        assert_eq!(
+            BlsPublicKey::from_bytes(&public_key.to_bytes()),
+            Some(public_key)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    backup::{BackupSink, BackupSource},
+    creation::CreatorCommand,
+    member::{run_session_with_status, spawn_session_task},
+    rotation::NewKeychain,
+    Config, Data, DataIOAdapter, EventForwarder, Hasher, MultiKeychain, Network, Receiver,
+    SessionError, SessionEvents, SessionOutcome, SessionStatus, SpawnHandle, TaskHandle,
+    UnitFinalizationHandler,
+};
+use futures::channel::{mpsc, oneshot};
+use std::sync::Arc;
+
+/// Marks a required component of a [`SessionBuilder`] that is not set yet.
+pub struct Missing;
+
+/// Assembles a session from named components, as an alternative to the positional arguments of
+/// [`run_session`](crate::run_session) and its variants.
+///
+/// The network, the data provider or finalization handler, the keychain and the spawner are
+/// required, and the session can only be run once all of them are set, so forgetting one is a
+/// compile error. Without a creator control channel the creator is never paused, without an exit
+/// channel the session runs until it finishes on its own, and without key rotation or network
+/// replacement channels the keychain and the network are used for the whole session. The fork
+/// handler and the backup are taken from the
+/// [`Config`], unless set here.
+pub struct SessionBuilder<N = Missing, DP = Missing, MK = Missing, SH = Missing> {
+    config: Config,
+    network: N,
+    data_io: DP,
+    keychain: MK,
+    spawn_handle: SH,
+    creator_control: Option<Receiver<CreatorCommand>>,
+    new_keychains: Option<Receiver<NewKeychain<MK>>>,
+    new_networks: Option<Receiver<N>>,
+    exit: Option<oneshot::Receiver<()>>,
+    status: SessionStatus,
+}
+
+impl SessionBuilder {
+    pub fn new(config: Config) -> Self {
+        SessionBuilder {
+            config,
+            network: Missing,
+            data_io: Missing,
+            keychain: Missing,
+            spawn_handle: Missing,
+            creator_control: None,
+            new_keychains: None,
+            new_networks: None,
+            exit: None,
+            status: SessionStatus::new(),
+        }
+    }
+}
+
+impl<N, DP, MK, SH> SessionBuilder<N, DP, MK, SH> {
+    /// Sets the network, dropping the networks scheduled with
+    /// [`SessionBuilder::network_replacements`] for the previous one.
+    pub fn network<N2>(self, network: N2) -> SessionBuilder<N2, DP, MK, SH> {
+        SessionBuilder {
+            config: self.config,
+            network,
+            data_io: self.data_io,
+            keychain: self.keychain,
+            spawn_handle: self.spawn_handle,
+            creator_control: self.creator_control,
+            new_keychains: self.new_keychains,
+            new_networks: None,
+            exit: self.exit,
+            status: self.status,
+        }
+    }
+
+    /// The [`DataIO`](crate::DataIO) providing the data and taking the ordered batches.
+    pub fn data_io<DP2>(self, data_io: DP2) -> SessionBuilder<N, DataIOAdapter<DP2>, MK, SH> {
+        self.unit_finalization_handler(DataIOAdapter(data_io))
+    }
+
+    /// Like [`SessionBuilder::data_io`], for a data provider that learns which units the ordered
+    /// data comes from, see [`run_session_with_unit_handler`](crate::run_session_with_unit_handler).
+    pub fn unit_finalization_handler<DP2>(self, data_io: DP2) -> SessionBuilder<N, DP2, MK, SH> {
+        SessionBuilder {
+            config: self.config,
+            network: self.network,
+            data_io,
+            keychain: self.keychain,
+            spawn_handle: self.spawn_handle,
+            creator_control: self.creator_control,
+            new_keychains: self.new_keychains,
+            new_networks: self.new_networks,
+            exit: self.exit,
+            status: self.status,
+        }
+    }
+
+    /// Sets the keychain, dropping the keychains scheduled with [`SessionBuilder::key_rotation`]
+    /// for the previous one.
+    pub fn keychain<MK2>(self, keychain: MK2) -> SessionBuilder<N, DP, MK2, SH> {
+        SessionBuilder {
+            config: self.config,
+            network: self.network,
+            data_io: self.data_io,
+            keychain,
+            spawn_handle: self.spawn_handle,
+            creator_control: self.creator_control,
+            new_keychains: None,
+            new_networks: self.new_networks,
+            exit: self.exit,
+            status: self.status,
+        }
+    }
+
+    pub fn spawn_handle<SH2>(self, spawn_handle: SH2) -> SessionBuilder<N, DP, MK, SH2> {
+        SessionBuilder {
+            config: self.config,
+            network: self.network,
+            data_io: self.data_io,
+            keychain: self.keychain,
+            spawn_handle,
+            creator_control: self.creator_control,
+            new_keychains: self.new_keychains,
+            new_networks: self.new_networks,
+            exit: self.exit,
+            status: self.status,
+        }
+    }
+
+    /// The networks to switch to during the session, e.g. after the connection behind the current
+    /// one breaks. Each of them replaces the previous network without restarting the session: the
+    /// messages the other components are about to send wait for the new network, and the messages
+    /// in flight through the old one may be lost, which the protocol recovers from.
+    pub fn network_replacements(mut self, new_networks: Receiver<N>) -> Self {
+        self.new_networks = Some(new_networks);
+        self
+    }
+
+    pub fn creator_control(mut self, creator_control: Receiver<CreatorCommand>) -> Self {
+        self.creator_control = Some(creator_control);
+        self
+    }
+
+    /// The session stops once `exit` gets a message or its sender is dropped.
+    pub fn exit(mut self, exit: oneshot::Receiver<()>) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+
+    /// Sets [`Config::backup_sink`] and [`Config::backup_source`].
+    pub fn backup(
+        mut self,
+        backup_sink: Arc<dyn BackupSink>,
+        backup_source: Arc<dyn BackupSource>,
+    ) -> Self {
+        self.config.backup_sink = backup_sink;
+        self.config.backup_source = backup_source;
+        self
+    }
+
+    /// A view of the progress of the session, which is going to be run by this builder.
+    pub fn status(&self) -> SessionStatus {
+        self.status.clone()
+    }
+
+    /// Makes the session report its events to the returned stream, see
+    /// [`SessionEvents::attach`]. The data provider or finalization handler has to be set before,
+    /// as it gets wrapped.
+    pub fn events<H: Hasher, D: Data>(
+        mut self,
+    ) -> (
+        SessionBuilder<N, EventForwarder<H, D, DP>, MK, SH>,
+        SessionEvents<H, D>,
+    )
+    where
+        DP: UnitFinalizationHandler<H, D>,
+    {
+        let (data_io, events) = SessionEvents::attach(&mut self.config, self.data_io);
+        let builder = SessionBuilder {
+            config: self.config,
+            network: self.network,
+            data_io,
+            keychain: self.keychain,
+            spawn_handle: self.spawn_handle,
+            creator_control: self.creator_control,
+            new_keychains: self.new_keychains,
+            new_networks: self.new_networks,
+            exit: self.exit,
+            status: self.status,
+        };
+        (builder, events)
+    }
+}
+
+impl<N, DP, MK: MultiKeychain, SH> SessionBuilder<N, DP, MK, SH> {
+    /// The keychains to switch to during the session, see
+    /// [`run_session_with_key_rotation`](crate::run_session_with_key_rotation).
+    pub fn key_rotation(mut self, new_keychains: Receiver<NewKeychain<MK>>) -> Self {
+        self.new_keychains = Some(new_keychains);
+        self
+    }
+}
+
+impl<N, DP, MK: MultiKeychain, SH: SpawnHandle> SessionBuilder<N, DP, MK, SH> {
+    /// Runs the session, see [`run_session`](crate::run_session).
+    pub async fn run<H: Hasher, D: Data>(self) -> Result<SessionOutcome, SessionError>
+    where
+        N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+        DP: UnitFinalizationHandler<H, D>,
+    {
+        // Kept until the session ends, so that it does not stop right away without an exit channel.
+        let (_exit_tx, exit) = oneshot::channel();
+        run_session_with_status(
+            self.config,
+            self.network,
+            self.data_io,
+            self.keychain,
+            self.spawn_handle,
+            self.creator_control.unwrap_or_else(|| mpsc::unbounded().1),
+            self.new_keychains.unwrap_or_else(|| mpsc::unbounded().1),
+            self.new_networks.unwrap_or_else(|| mpsc::unbounded().1),
+            self.status,
+            self.exit.unwrap_or(exit),
+        )
+        .await
+    }
+
+    /// Spawns the session as an essential task, see [`spawn_session`](crate::spawn_session).
+    pub fn spawn<H: Hasher, D: Data>(self) -> (TaskHandle, SessionStatus)
+    where
+        N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+        DP: UnitFinalizationHandler<H, D> + Send + 'static,
+    {
+        let spawn_handle = self.spawn_handle.clone();
+        let status = self.status.clone();
+        (spawn_session_task(&spawn_handle, self.run()), status)
+    }
+}
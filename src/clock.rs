@@ -0,0 +1,155 @@
+use futures::{channel::oneshot, future::BoxFuture, FutureExt};
+use log::warn;
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// A source of time for the timers of the consensus.
+pub trait Clock: Send + Sync + 'static {
+    /// The current time. It should never go back, like [`Instant::now`], so a clock following
+    /// the system time has to smooth over its corrections. If it goes back anyway, the time in
+    /// between counts as zero.
+    fn now(&self) -> Instant;
+    /// Returns a future completing once `duration` has passed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// The wall clock, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        futures_timer::Delay::new(duration).boxed()
+    }
+}
+
+/// The time from `earlier` to `later`, both coming from a [`Clock`]. Zero if the clock went back
+/// in between, which is logged.
+pub(crate) fn time_between(earlier: Instant, later: Instant) -> Duration {
+    later.checked_duration_since(earlier).unwrap_or_else(|| {
+        warn!(target: "AlephBFT-clock", "The clock went back by {:?}, counting it as no time passing.", earlier - later);
+        Duration::ZERO
+    })
+}
+
+struct MockTime {
+    now: Instant,
+    sleepers: Vec<(Instant, oneshot::Sender<()>)>,
+}
+
+/// A clock that stands still until it is advanced with [`MockClock::advance`], so that tests can
+/// simulate long periods of time instantly. Clones share the same time.
+#[derive(Clone)]
+pub struct MockClock {
+    time: Arc<Mutex<MockTime>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock {
+            time: Arc::new(Mutex::new(MockTime {
+                now: Instant::now(),
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Moves the time forward by `duration`, completing all the sleeps that end by then.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.time.lock();
+        time.now += duration;
+        let now = time.now;
+        let (finished, pending) = time
+            .sleepers
+            .drain(..)
+            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+        time.sleepers = pending
+            .into_iter()
+            .filter(|(_, sender)| !sender.is_canceled())
+            .collect();
+        for (_, sender) in finished {
+            let _ = sender.send(());
+        }
+    }
+
+    /// How long until the earliest pending sleep completes, if there is any.
+    pub fn time_to_next_wakeup(&self) -> Option<Duration> {
+        let time = self.time.lock();
+        time.sleepers
+            .iter()
+            .filter(|(_, sender)| !sender.is_canceled())
+            .map(|(deadline, _)| deadline.saturating_duration_since(time.now))
+            .min()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.time.lock().now
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        if duration.is_zero() {
+            return futures::future::ready(()).boxed();
+        }
+        let mut time = self.time.lock();
+        let deadline = time.now + duration;
+        let (sender, receiver) = oneshot::channel();
+        time.sleepers.push((deadline, sender));
+        async move {
+            if receiver.await.is_err() {
+                // The clock is gone, so the time will never come.
+                futures::future::pending::<()>().await;
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    #[test]
+    fn time_between_clamps_going_back() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(3);
+        assert_eq!(time_between(start, later), Duration::from_secs(3));
+        assert_eq!(time_between(later, start), Duration::ZERO);
+    }
+
+    #[test]
+    fn mock_clock_wakes_sleepers_when_advanced() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let mut short = clock.sleep(Duration::from_secs(1));
+        let mut long = clock.sleep(Duration::from_secs(10));
+        assert!(clock.sleep(Duration::from_secs(0)).now_or_never().is_some());
+        assert_eq!(clock.time_to_next_wakeup(), Some(Duration::from_secs(1)));
+        assert!((&mut short).now_or_never().is_none());
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(clock.now() - start, Duration::from_secs(2));
+        assert!((&mut short).now_or_never().is_some());
+        assert!((&mut long).now_or_never().is_none());
+        assert_eq!(clock.time_to_next_wakeup(), Some(Duration::from_secs(8)));
+
+        clock.advance(Duration::from_secs(8));
+        assert!(long.now_or_never().is_some());
+        assert_eq!(clock.time_to_next_wakeup(), None);
+    }
+}
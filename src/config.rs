@@ -1,25 +1,175 @@
-use crate::{Round, SessionId};
-use std::{sync::Arc, time::Duration};
+use crate::{
+    alerts::{ForkHandler, IgnoreForks},
+    backup::{BackupSink, BackupSource, NoBackup},
+    clock::{Clock, RealClock},
+    creation::{AllAvailableParents, CreationLatencies, ParentSelector},
+    Round, Sender, SessionId, UnitRejection,
+};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use std::{
+    convert::{TryFrom, TryInto},
+    sync::Arc,
+    time::Duration,
+};
 
-use crate::nodes::{NodeCount, NodeIndex};
+use crate::nodes::{NodeCount, NodeIndex, NodeWeights};
 
 pub type DelaySchedule = Arc<dyn Fn(usize) -> Duration + Sync + Send + 'static>;
 
+/// What a [`ContextDelaySchedule`] gets to decide on the delay of a unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CreationContext {
+    /// The round of the unit about to be created.
+    pub round: Round,
+    /// The members, including us, with a unit of one of the two rounds below `round` known to us
+    /// when we start waiting for the delay.
+    pub active_members: NodeCount,
+    /// The number of members of the committee.
+    pub n_members: NodeCount,
+}
+
+/// A unit creation delay depending not only on the round, see
+/// [`DelayConfig::context_creation_delay`].
+pub type ContextDelaySchedule = Arc<dyn Fn(CreationContext) -> Duration + Sync + Send + 'static>;
+
+/// Creates the random number generator of a session, called once when the session starts.
+pub type RngFactory = Arc<dyn Fn() -> Box<dyn RngCore + Send> + Sync + Send + 'static>;
+
 /// Configuration of several parameters related to delaying various tasks.
 #[derive(Clone)]
 pub struct DelayConfig {
     /// Tick frequency of the Member. Governs internal task queue of the Member.
     pub tick_interval: Duration,
-    /// After what delay, we repeat a request for a coord or parents.
-    pub requests_interval: Duration,
     /// DelaySchedule(k) represents the delay between the kth and (k+1)th broadcast.
     pub unit_broadcast_delay: DelaySchedule,
     /// DelaySchedule(k) represents the delay between creating the (k-1)th and kth unit.
     pub unit_creation_delay: DelaySchedule,
+    /// If set, it is used instead of `unit_creation_delay`, so that the delay can also depend on
+    /// how many members are active, e.g. to create units faster when most of them are and the
+    /// parents come quickly. The delay only decides when we start trying to create a unit, which
+    /// still needs the parents the protocol requires, so the schedule cannot break the protocol.
+    pub context_creation_delay: Option<ContextDelaySchedule>,
+    /// How often we warn that a unit could not be created, even though the creation delay passed.
+    pub stall_warning_delay: Duration,
+    /// If set, the unit creation delay is multiplied by `creation_backpressure_factor` for units
+    /// more than this many rounds ahead of the last finalized round.
+    pub creation_backpressure_threshold: Option<Round>,
+    /// The factor by which the unit creation delay is multiplied when finalization lags behind.
+    /// It has to be between 1 and [`MAX_CREATION_BACKPRESSURE_FACTOR`], which [`ConfigBuilder`]
+    /// checks, and other values are brought into that range.
+    pub creation_backpressure_factor: f64,
+    /// If set, we stop waiting for [`DataIO::get_data_async`](crate::DataIO::get_data_async)
+    /// after this long and use [`DataIO::fallback_data`](crate::DataIO::fallback_data) instead.
+    pub data_timeout: Option<Duration>,
+    /// We send an alert, or a request for an alert, to the same node at most once per this
+    /// interval, no matter how often it asks for it. Zero disables the limit.
+    pub alert_resend_interval: Duration,
+    /// DelaySchedule(k) represents the delay between the kth and (k+1)th retransmission of a
+    /// message of the reliable multicast, which collects multisignatures for alerts.
+    pub rmc_retry_delay: DelaySchedule,
+    /// If set, we stop retransmitting our signature of an alert once this long passed since we
+    /// started collecting the multisignature for it, and warn that the fork alert is unresolved.
+    /// The alert and the signatures collected so far are kept, so the multisignature can still be
+    /// completed by the others. Otherwise we retransmit according to `rmc_retry_delay` until the
+    /// multisignature is complete.
+    pub alert_timeout: Option<Duration>,
+    /// Once enough parents for a unit are available, we wait up to this long for the parents of
+    /// the remaining members, so that slow but honest members still get included. The unit is
+    /// created right away once all the parents are there, or if we are behind. Zero, the
+    /// default, disables waiting.
+    pub parents_grace_period: Duration,
+}
+
+/// How we ask other members for the units we are missing.
+#[derive(Clone)]
+pub struct RequestConfig {
+    /// How many members we ask at once. The first time we ask the creator of the unit, if we
+    /// know it, together with random other members, later only random members. Capped at the
+    /// number of other members.
+    pub fanout: usize,
+    /// DelaySchedule(k) represents the delay between asking for the parents of the same unit for
+    /// the kth and (k+1)th time, when they do not match its control hash.
+    pub interval: DelaySchedule,
+    /// DelaySchedule(k) represents the delay between asking for the same missing parent, i.e. a
+    /// unit we only know by its round and creator, for the kth and (k+1)th time. Backing off
+    /// exponentially, we soon ask again when a response got lost, but do not flood the others
+    /// with requests for a unit none of them has yet.
+    pub missing_parent_request_interval: DelaySchedule,
+}
+
+/// How we send the unit messages in batches, see [`Config::unit_batching`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnitBatchingConfig {
+    /// How long a message waits for more messages to the same recipient before it is sent.
+    pub window: Duration,
+    /// The most messages sent in one batch. A full batch is sent right away.
+    pub max_batch_size: usize,
+}
+
+/// How many units we send in responses to the requests of the same member, see
+/// [`Config::response_limit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResponseLimitConfig {
+    /// The most units we send in responses to the same member within `window`.
+    pub max_units: usize,
+    /// How long the units we sent count towards the limit.
+    pub window: Duration,
+}
+
+/// When the session counts as wedged, see [`Config::liveness`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LivenessConfig {
+    /// How long we may go without finalizing any unit while the network is active.
+    pub stall_threshold: Duration,
+    /// A member counts as active if we saw its unit of a new round at most this long ago, see
+    /// [`SessionStatus::member_activity`](crate::SessionStatus::member_activity).
+    pub activity_window: Duration,
+    /// How many other members have to be active for the network to count as active. If `None`,
+    /// the active members together with us have to carry more than two thirds of the weight, see
+    /// [`Config::node_weights`], which is what the consensus needs to make progress.
+    pub min_active_members: Option<NodeCount>,
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        LivenessConfig {
+            stall_threshold: Duration::from_secs(120),
+            activity_window: Duration::from_secs(30),
+            min_active_members: None,
+        }
+    }
+}
+
+/// When we count as catching up with the rest of the committee, see [`Config::catch_up`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CatchUpConfig {
+    /// We start catching up once the last finalized round is more than this many rounds below
+    /// the highest round we have seen.
+    pub behind_rounds: Round,
+    /// We stop catching up once the last finalized round is at most this many rounds below the
+    /// highest round. It has to be lower than `behind_rounds`, so that a node near the boundary
+    /// does not switch back and forth. It is also how close we have to get for the first time
+    /// to count as caught up, see [`SessionStatus::caught_up`](crate::SessionStatus::caught_up).
+    pub caught_up_rounds: Round,
+    /// How many members we ask at once for the units we are missing while catching up, instead
+    /// of [`RequestConfig::fanout`].
+    pub fanout: usize,
+}
+
+impl Default for CatchUpConfig {
+    fn default() -> Self {
+        CatchUpConfig {
+            behind_rounds: 20,
+            caught_up_rounds: 6,
+            fanout: 3,
+        }
+    }
 }
 
 /// Main configuration of the consensus. We refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/aleph_bft_api.html
 /// Section 3.4 for a discussion of some of these parameters and their significance.
+///
+/// The recommended way of obtaining a config is [`ConfigBuilder`], which validates the parameters.
 #[derive(Clone)]
 pub struct Config {
     /// Identification number of the Member=0,..,(n_members-1).
@@ -28,10 +178,208 @@ pub struct Config {
     pub session_id: SessionId,
     /// The size of the committee running the consensus.
     pub n_members: NodeCount,
+    /// The weights of the members, e.g. their stakes. The units need parents, and the
+    /// multisignatures signers, of more than two thirds of the total weight. Uniform by default.
+    pub node_weights: NodeWeights,
     /// Configuration of several parameters related to delaying various tasks.
     pub delay_config: DelayConfig,
-    /// Maximum allowable round of a unit.
+    /// How we ask other members for the units we are missing.
+    pub request_config: RequestConfig,
+    /// Maximum allowable round of a unit. It can be raised during the session using
+    /// [`CreatorCommand::ExtendMaxRound`](crate::CreatorCommand::ExtendMaxRound), but never above
+    /// [`MAX_MAX_ROUND`].
     pub max_round: Round,
+    /// We report when our units get within this many rounds of `max_round`, see
+    /// [`SessionStatus::max_round_approached`](crate::SessionStatus::max_round_approached).
+    pub max_round_warning_margin: Round,
+    /// What we do once our units reach `max_round`.
+    pub max_round_policy: MaxRoundPolicy,
+    /// If set, we do not create units more than this many rounds above the last finalized round,
+    /// until finalization catches up. Deciding a round takes units of a few rounds above it, so
+    /// the bound has to be at least [`MIN_MAX_ROUNDS_AHEAD`].
+    pub max_rounds_ahead: Option<Round>,
+    /// The finalized batches are passed to the finalization handler one at a time, and the next
+    /// ones wait until it is done with the previous one. Once the waiting batches span more than
+    /// this many rounds, we stop passing new units to the consensus, so that it does not order
+    /// more of them, and consequently the creator runs out of parents, until the handler catches
+    /// up. A slow handler thus eventually slows down the creation of our units. See
+    /// [`SessionStatus::is_finalization_lagging`](crate::SessionStatus::is_finalization_lagging).
+    pub max_undelivered_rounds: Round,
+    /// We warn when more than this many units are waiting to be received by the creator.
+    pub parents_backlog_high_water: usize,
+    /// The strategy of choosing parents for our units.
+    pub parent_selector: Arc<dyn ParentSelector>,
+    /// If set, we wait for at least this many parents before creating a unit, unless this takes
+    /// longer than another unit creation delay. The protocol minimum of parents of more than two
+    /// thirds of the total weight always applies.
+    pub min_parents: Option<NodeCount>,
+    /// How many hashes of recently processed units we remember, so that we do not verify the
+    /// signatures of units we receive multiple times. Zero disables this.
+    pub processed_units_cache_size: usize,
+    /// How many different units of the same creator and round we verify and process, the rest
+    /// is dropped. Two already prove a fork, so a flooding forker cannot make us verify more.
+    /// The units waiting for one of the [`Config::unit_verification_workers`] count as well, so
+    /// a badly signed unit holds a place until it is verified. Units coming with alerts are
+    /// always processed. We forget the variants of a unit once it is pruned, see
+    /// [`Config::max_stored_units`], otherwise we remember them as long as the units themselves.
+    /// At least [`MIN_MAX_UNIT_VARIANTS`].
+    pub max_unit_variants: usize,
+    /// If set, we warn once our units are included by too few members, i.e. in less than this
+    /// fraction of the recent rounds more than two thirds of the members used our unit as a
+    /// parent in the next round, see [`DagShape::own_unit_inclusion`](crate::DagShape::own_unit_inclusion).
+    /// Such a node is running, but hardly contributes. Between 0 and 1.
+    pub min_own_unit_inclusion: Option<f64>,
+    /// If set, we warn once the members' units are used as parents very unevenly, i.e. the units
+    /// of the least used member are used less than this fraction as often as the ones of the most
+    /// used member, see [`DagShape::parent_inclusion_balance`](crate::DagShape::parent_inclusion_balance).
+    /// Such a committee depends on the few favoured members, e.g. because of the
+    /// [`Config::parent_selector`]. Between 0 and 1.
+    pub min_parent_inclusion_balance: Option<f64>,
+    /// If nonzero, the signatures of incoming units are verified by this many parallel tasks
+    /// instead of inline. The units are still processed in the order they were received.
+    pub unit_verification_workers: usize,
+    /// If set, the task verifying the signatures of units lets the other tasks run, through
+    /// [`SpawnHandle::yield_now`](crate::SpawnHandle::yield_now), after verifying this many of
+    /// them. Catching up verifies lots of units at once, which otherwise could keep the other
+    /// work of the node waiting for long. This applies to each of the
+    /// [`Config::unit_verification_workers`], or to the member itself if there are none.
+    pub verification_budget: Option<usize>,
+    /// How many notifications of the creator, like the units it created, can wait to be processed.
+    /// Once that many are waiting, the creator waits too, so it slows down when the rest of the
+    /// member cannot keep up, instead of accumulating units in memory.
+    pub creator_notification_capacity: usize,
+    /// If set, the units finalized at once are passed to the [`DataIO`](crate::DataIO) in batches
+    /// of at most this many, so that catching up does not produce one enormous batch.
+    pub max_batch_size: Option<usize>,
+    /// If set, a soft bound on the number of units we keep in memory. Above it we forget the
+    /// units which were already finalized, apart from the newest unit of every member, and while
+    /// it is still exceeded we create units more slowly, by `creation_backpressure_factor`. The
+    /// forgotten units are saved in the backup, but we no longer send them to members asking
+    /// for them, so they have to fetch them from someone else. We also ignore any other unit of
+    /// the round and creator of a forgotten unit, so a fork with such a unit goes unnoticed by us,
+    /// until another member alerts about it. See
+    /// [`SessionStatus::n_store_overloads`](crate::SessionStatus::n_store_overloads).
+    pub max_stored_units: Option<usize>,
+    /// If set, the maximum size of the encoded data of a unit in bytes. Messages with units
+    /// carrying more data are dropped as soon as we receive them, and the session fails if the
+    /// [`DataIO`](crate::DataIO) gives us more data for our own unit.
+    pub max_data_size: Option<usize>,
+    /// How many calls to [`DataIO::get_data_async`](crate::DataIO::get_data_async) may be
+    /// outstanding at once. With the default of one, the data of a unit is requested only once we
+    /// create it. With more, we also request the data of that many minus one next units right
+    /// away, so that it is ready when we create them. The calls are made one after another and
+    /// the data is put in our units in the order of the calls, no matter in which order it
+    /// arrives. A call we stop waiting for after [`DelayConfig::data_timeout`] is dropped.
+    pub data_pipeline_depth: usize,
+    /// If set, the unit messages we send to the same recipient within a short window are sent
+    /// together, as a single message, which saves a lot of messages when someone catches up.
+    /// Every member handles batches, but versions from before batching was introduced cannot
+    /// decode them, so it should only be enabled once all the members are upgraded.
+    pub unit_batching: Option<UnitBatchingConfig>,
+    /// If set, we answer the requests for units of the same member with at most that many units
+    /// per window, and ignore the rest of its requests until the window passes. The member asks
+    /// again later, according to [`RequestConfig::interval`], so serving a member catching up
+    /// takes longer, but does not flood the network and our memory with units to send.
+    pub response_limit: Option<ResponseLimitConfig>,
+    /// The source of time for the timers of the consensus. Replaceable mainly for testing.
+    pub clock: Arc<dyn Clock>,
+    /// If set, the latencies of creating our units are pushed here after every unit we create.
+    /// Nothing is measured otherwise.
+    pub creation_latency_sink: Option<Sender<CreationLatencies>>,
+    /// The upper bounds of the buckets of the histograms in [`CreationLatencies`], sorted.
+    pub creation_latency_buckets: Vec<Duration>,
+    /// If set, every round is pushed here once units of a quorum of members of that round reach
+    /// our Dag for the first time, so that it can serve as the parents of the next round. The rounds
+    /// come in order, usually a few rounds ahead of finalization.
+    pub quorum_round_sink: Option<Sender<Round>>,
+    /// If set, every incoming unit we reject as invalid is pushed here together with the reason,
+    /// on top of the warning in the logs. Units we merely ignore, like the ones we already have,
+    /// are not reported.
+    pub unit_rejection_sink: Option<Sender<UnitRejection>>,
+    /// If set, the random choices of the member, like the peers asked again for missing units,
+    /// are derived from this seed and `node_ix`, so that runs can be reproduced. Meant for
+    /// testing, as the choices become predictable.
+    pub random_seed: Option<u64>,
+    /// If set, the random choices of the member are drawn from the generator this creates
+    /// instead, and `random_seed` is ignored. Every session gets its own generator, so sessions
+    /// running at the same time do not affect each other.
+    pub rng: Option<RngFactory>,
+    /// Receives the evidence of the forks we learn about.
+    pub fork_handler: Arc<dyn ForkHandler>,
+    /// What we do on top of alerting the others once we detect a fork or learn about it from an
+    /// alert.
+    pub fork_policy: ForkPolicy,
+    /// Where we save the units of the session.
+    pub backup_sink: Arc<dyn BackupSink>,
+    /// The units saved by a previous run of this session, which we recover before starting.
+    pub backup_source: Arc<dyn BackupSource>,
+    /// When the backup grows above this many bytes, we rewrite it without the units of other
+    /// members from rounds well below the finalized ones. Zero disables this. After restarting
+    /// with a compacted backup, the removed units are fetched from other members again, so it
+    /// should only be enabled when every member keeps a backup.
+    pub backup_compaction_threshold: usize,
+    /// If set, we stop recovering the units from the backup once this much time passed since we
+    /// started reading it, log how far we got, and catch up with the other members on the rest,
+    /// as if we restarted without a backup from there on. We still start creating units above
+    /// the highest round of our units in the backup, seen while checking it, so that we never
+    /// create a second unit of a round. Checking the backup before is not interrupted, only
+    /// loading the units into the Dag, which is the slow part, as their signatures are verified.
+    /// See
+    /// [`SessionStatus::recovery_timed_out`](crate::SessionStatus::recovery_timed_out).
+    pub recovery_timeout: Option<Duration>,
+    /// If set, we save in the backup how many finalized units were passed to the
+    /// [`UnitFinalizationHandler`](crate::UnitFinalizationHandler), and after recovering from the
+    /// backup we do not pass them again. Otherwise all the units ordered before a restart are
+    /// passed again, which applications keeping their state only in memory rely on. A batch
+    /// passed right before a crash, before we saved that it was passed, is still passed again.
+    pub skip_delivered_after_recovery: bool,
+    /// The round up to which the order of the session is known to be committed elsewhere, e.g.
+    /// by a chain finalizing the data of a previous run. After a restart, the batches whose head
+    /// is of this round or below are not passed to the
+    /// [`UnitFinalizationHandler`](crate::UnitFinalizationHandler) again. We only trust it up to
+    /// the highest round of the units recovered from the backup, as we cannot check the order
+    /// above them against our Dag, so the batches above are passed again.
+    pub externally_finalized_round: Option<Round>,
+    /// The hashes of units known to be finalized, as bytes, e.g. taken from
+    /// [`SessionStatus::finalized_unit_hashes`](crate::SessionStatus::finalized_unit_hashes)
+    /// before a restart or from a snapshot. Such units are dropped without verifying them when
+    /// other members send them again while we catch up, unless we are missing them, and their
+    /// signatures are not verified again when recovering them from the backup. Hashes of a wrong
+    /// length are ignored.
+    pub known_unit_hashes: Vec<Vec<u8>>,
+    /// If set, a backup breaking the structure of the Dag, e.g. containing two of our units of the
+    /// same round, is truncated to the units below the lowest round affected, instead of failing
+    /// the session with [`SessionError::InconsistentBackup`](crate::SessionError::InconsistentBackup).
+    /// Our removed units are created again, which is a fork if the other members saw them,
+    /// unless they tell us about them while we catch up.
+    pub truncate_inconsistent_backup: bool,
+    /// An opaque tag of the session, e.g. an encoded epoch number and chain id, saved as the
+    /// first record of a new backup. Recovering from a backup with a different tag fails the
+    /// session with [`SessionError::BackupOfOtherSession`](crate::SessionError::BackupOfOtherSession),
+    /// which catches backups mixed up between sessions. A backup saved without a tag, e.g. by an
+    /// older version, only matches the empty tag, which is the default.
+    pub session_tag: Vec<u8>,
+    /// If set, the [`Hasher`](crate::Hasher) is tested with [`check_hasher`](crate::check_hasher)
+    /// before starting, and the session fails right away if it does not pass.
+    pub check_hasher: bool,
+    /// What we do once the [`DataIO`](crate::DataIO) is closed.
+    pub data_channel_policy: DataChannelPolicy,
+    /// If set, we follow the consensus and pass on the finalized data, but never create units, e.g.
+    /// for an indexer or a monitoring node. The data is never requested from the
+    /// [`DataIO`](crate::DataIO), and creator commands other than raising the maximum round are
+    /// ignored. The other members cannot tell that we are an observer, so our weight in
+    /// [`Config::node_weights`] should be zero for us not to count towards quorums.
+    pub observer: bool,
+    /// What happens when one of the tasks of the session panics.
+    pub task_panic_policy: TaskPanicPolicy,
+    /// When we count as wedged, i.e. not finalizing anything even though the network is active,
+    /// see [`SessionStatus::wedged`](crate::SessionStatus::wedged).
+    pub liveness: LivenessConfig,
+    /// When we ask more members at once for the units we are missing, because we are far behind
+    /// the others. Even a node which is up to date finalizes rounds a few rounds below the
+    /// highest one, and on a high-latency network the gap grows, so the thresholds might need to
+    /// be raised for such a node not to flood its peers with requests.
+    pub catch_up: CatchUpConfig,
 }
 
 pub fn exponential_slowdown(
@@ -54,6 +402,104 @@ pub fn exponential_slowdown(
     Duration::from_millis(delay)
 }
 
+/// A schedule returning the same delay for every argument.
+pub fn constant_schedule(delay: Duration) -> DelaySchedule {
+    Arc::new(move |_| delay)
+}
+
+/// A schedule starting with `initial` for the argument 0 and multiplying the delay by `factor`
+/// with every step, but never exceeding `max`. The delays that would be negative, for a negative
+/// `factor`, are zero instead, so [`ConfigBuilder`] rejects such a schedule.
+pub fn exponential_schedule(initial: Duration, factor: f64, max: Duration) -> DelaySchedule {
+    Arc::new(move |t| {
+        let delay = initial.as_secs_f64() * factor.powf(t as f64);
+        // This also covers the delay overflowing to infinity, and being NaN.
+        if delay < max.as_secs_f64() {
+            Duration::from_secs_f64(delay.max(0.0))
+        } else {
+            max
+        }
+    })
+}
+
+/// A schedule changing its delay at the given rounds: the delay for an argument is the one of the
+/// last step starting at or below it, or of the first step for arguments below all of them.
+/// The steps have to be non-empty and sorted by their rounds, with no round repeated.
+///
+/// ```
+/// use aleph_bft::stepped_schedule;
+/// use std::time::Duration;
+///
+/// // Fast creation while bootstrapping, then a slower steady cadence.
+/// let schedule = stepped_schedule(vec![
+///     (0, Duration::from_millis(50)),
+///     (100, Duration::from_millis(500)),
+/// ])
+/// .expect("the steps are valid");
+/// assert_eq!(schedule(99), Duration::from_millis(50));
+/// assert_eq!(schedule(100), Duration::from_millis(500));
+/// ```
+pub fn stepped_schedule(steps: Vec<(Round, Duration)>) -> Result<DelaySchedule, ConfigError> {
+    if steps.is_empty() {
+        return Err(ConfigError::NoSteps);
+    }
+    if steps.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err(ConfigError::UnsortedSteps);
+    }
+    Ok(Arc::new(move |t| {
+        let n_started = steps.partition_point(|(round, _)| (*round as usize) <= t);
+        steps[n_started.saturating_sub(1)].1
+    }))
+}
+
+/// Adds a random offset of at most `max_jitter` to every delay of the `base` schedule, so that
+/// nodes using the same schedule do not act in lockstep. The offset is never negative, so the
+/// delays never drop below the ones of `base`. The offsets are reproducible, as they only depend
+/// on `node_ix` and the argument of the schedule.
+pub fn with_jitter(base: DelaySchedule, max_jitter: Duration, node_ix: NodeIndex) -> DelaySchedule {
+    let seed = (node_ix.0 as u64) << 32;
+    Arc::new(move |t| {
+        let mut rng = StdRng::seed_from_u64(seed ^ t as u64);
+        base(t).saturating_add(max_jitter.mul_f64(rng.gen_range(0.0..=1.0)))
+    })
+}
+
+// 3000, 6000, 12000, 24000, 24000, ...
+fn default_missing_parent_request_interval() -> DelaySchedule {
+    exponential_schedule(Duration::from_millis(3000), 2.0, Duration::from_secs(24))
+}
+
+const DEFAULT_ALERT_RESEND_INTERVAL: Duration = Duration::from_millis(1000);
+
+// 500, 1000, 2000, 4000, ...
+fn default_rmc_retry_delay() -> DelaySchedule {
+    Arc::new(|t| exponential_slowdown(t, 500.0, 0, 2.0))
+}
+
+// 10ms, 25ms, 50ms, 100ms, 250ms, ..., 10s
+fn default_creation_latency_buckets() -> Vec<Duration> {
+    [10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000]
+        .iter()
+        .map(|millis| Duration::from_millis(*millis))
+        .collect()
+}
+
+/// A random number generator for the member `node_ix`, seeded with `seed` if one is given.
+fn member_rng(seed: Option<u64>, node_ix: NodeIndex) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed ^ ((node_ix.0 as u64) << 32)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// The random number generator of a session, see [`Config::rng`] and [`Config::random_seed`].
+pub(crate) fn session_rng(config: &Config) -> Box<dyn RngCore + Send> {
+    match &config.rng {
+        Some(rng) => rng(),
+        None => Box::new(member_rng(config.random_seed, config.node_ix)),
+    }
+}
+
 /// A default configuration of what the creators of this package see as optimal parameters.
 pub fn default_config(n_members: NodeCount, node_ix: NodeIndex, session_id: SessionId) -> Config {
     let unit_creation_delay = Arc::new(|t| {
@@ -65,17 +511,1576 @@ pub fn default_config(n_members: NodeCount, node_ix: NodeIndex, session_id: Sess
     });
     let delay_config = DelayConfig {
         tick_interval: Duration::from_millis(100),
-        requests_interval: Duration::from_millis(3000),
         unit_broadcast_delay: Arc::new(|t| exponential_slowdown(t, 4000.0, 0, 2.0)),
         // 4000, 8000, 16000, 32000, ...
         unit_creation_delay,
         // 5000, 500, 500, 500, ... (till step 3000), 500, 500*1.005, 500*(1.005)^2, 500*(1.005)^3, ..., 10742207 (last step)
+        context_creation_delay: None,
+        stall_warning_delay: Duration::from_secs(30 * 60),
+        creation_backpressure_threshold: None,
+        creation_backpressure_factor: 2.0,
+        data_timeout: None,
+        alert_resend_interval: DEFAULT_ALERT_RESEND_INTERVAL,
+        rmc_retry_delay: default_rmc_retry_delay(),
+        alert_timeout: None,
+        parents_grace_period: Duration::ZERO,
     };
     Config {
         node_ix,
         session_id,
         n_members,
+        node_weights: NodeWeights::uniform(n_members),
         delay_config,
+        request_config: RequestConfig {
+            fanout: 1,
+            interval: constant_schedule(Duration::from_millis(3000)),
+            missing_parent_request_interval: default_missing_parent_request_interval(),
+        },
         max_round: 5000,
+        max_round_warning_margin: 100,
+        max_round_policy: MaxRoundPolicy::Wait,
+        max_rounds_ahead: None,
+        max_undelivered_rounds: 50,
+        parents_backlog_high_water: 1000,
+        parent_selector: Arc::new(AllAvailableParents),
+        min_parents: None,
+        processed_units_cache_size: 4096,
+        max_unit_variants: MIN_MAX_UNIT_VARIANTS,
+        min_own_unit_inclusion: None,
+        min_parent_inclusion_balance: None,
+        unit_verification_workers: 0,
+        verification_budget: None,
+        creator_notification_capacity: 64,
+        max_batch_size: None,
+        max_stored_units: None,
+        max_data_size: None,
+        data_pipeline_depth: 1,
+        unit_batching: None,
+        response_limit: None,
+        clock: Arc::new(RealClock),
+        creation_latency_sink: None,
+        creation_latency_buckets: default_creation_latency_buckets(),
+        quorum_round_sink: None,
+        unit_rejection_sink: None,
+        random_seed: None,
+        rng: None,
+        fork_handler: Arc::new(IgnoreForks),
+        fork_policy: ForkPolicy::Alert,
+        backup_sink: Arc::new(NoBackup),
+        backup_source: Arc::new(NoBackup),
+        backup_compaction_threshold: 0,
+        recovery_timeout: None,
+        skip_delivered_after_recovery: false,
+        externally_finalized_round: None,
+        known_unit_hashes: Vec::new(),
+        truncate_inconsistent_backup: false,
+        session_tag: Vec::new(),
+        check_hasher: false,
+        data_channel_policy: DataChannelPolicy::ProduceEmptyUnits,
+        observer: false,
+        task_panic_policy: TaskPanicPolicy::Abort,
+        liveness: LivenessConfig::default(),
+        catch_up: CatchUpConfig::default(),
+    }
+}
+
+/// The lowest value of [`Config::max_rounds_ahead`] accepted by [`ConfigBuilder`]. A round can be
+/// decided once there are units three rounds above it, but it might take a few more if the votes of
+/// the members differ, so that many rounds have to remain available.
+pub const MIN_MAX_ROUNDS_AHEAD: Round = 8;
+
+/// The highest value of [`Config::max_round`], also when extended during the session. Leaves room
+/// for the round following any unit, so that the round arithmetic never overflows. A session whose
+/// units reach it ends with [`SessionError::RoundLimitReached`](crate::SessionError::RoundLimitReached).
+pub const MAX_MAX_ROUND: Round = Round::MAX - 1;
+
+/// The highest allowed [`DelayConfig::creation_backpressure_factor`]. Backpressure is meant to
+/// slow the creation down, not to stop it, and scaling the delays too much overflows them.
+pub const MAX_CREATION_BACKPRESSURE_FACTOR: f64 = 1000.0;
+
+/// The smallest allowed [`Config::max_unit_variants`], as two variants are needed to prove a fork.
+pub const MIN_MAX_UNIT_VARIANTS: usize = 2;
+
+/// The number of initial arguments for which we check that a delay schedule is positive.
+const CHECKED_SCHEDULE_STEPS: usize = 10;
+
+/// What we do once the [`DataIO`](crate::DataIO) is closed, see
+/// [`DataIO::is_closed`](crate::DataIO::is_closed).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataChannelPolicy {
+    /// Keep taking part in the consensus, with our units carrying
+    /// [`DataIO::fallback_data`](crate::DataIO::fallback_data). If there is no fallback data, we
+    /// still ask the data provider for data.
+    ProduceEmptyUnits,
+    /// Stop the session, which then returns [`SessionOutcome::DataClosed`](crate::SessionOutcome::DataClosed).
+    ShutdownSession,
+}
+
+/// What we do once our units reach [`Config::max_round`], so that we cannot create any more of
+/// them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaxRoundPolicy {
+    /// Keep following the consensus without creating units, until the maximum round is raised
+    /// with [`CreatorCommand::ExtendMaxRound`](crate::CreatorCommand::ExtendMaxRound).
+    Wait,
+    /// Raise the maximum round by the given number of rounds, as if
+    /// [`CreatorCommand::ExtendMaxRound`](crate::CreatorCommand::ExtendMaxRound) was sent.
+    Extend(Round),
+    /// Stop the session, which then returns
+    /// [`SessionOutcome::Completed`](crate::SessionOutcome::Completed). Our units are sent out
+    /// before, but the ones of the last few rounds are never finalized, as the rounds above
+    /// them are never created.
+    ShutdownSession,
+}
+
+/// What happens when one of the tasks of a session panics, see [`Config::task_panic_policy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskPanicPolicy {
+    /// The panic is not caught, so it is handled by the [`SpawnHandle`](crate::SpawnHandle), or
+    /// unwinds through [`run_session`](crate::run_session) for the parts of the session running
+    /// within it. Spawners reporting the panic with the handle of the task, like
+    /// [`TokioSpawner`](crate::TokioSpawner), make the whole session stop with an error.
+    Abort,
+    /// The panic is caught within the task, and the session stops all the other tasks and
+    /// returns [`SessionError::TaskPanicked`](crate::SessionError::TaskPanicked) with the name of
+    /// the task and the message of the panic, so that a supervisor can restart it cleanly.
+    Report,
+}
+
+/// How we respond to a proven fork, see [`Config::fork_policy`]. Whatever the policy, we alert
+/// the other members, and the units of the forker only reach the Dag when legitimized by alerts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForkPolicy {
+    /// Keep running the session as usual, leaving the rest to the
+    /// [`ForkHandler`](crate::ForkHandler).
+    Alert,
+    /// Also cut the forker off: its requests for units are no longer answered, so it cannot
+    /// catch up with our help.
+    Isolate,
+    /// Stop the session with [`SessionError::ForkDetected`](crate::SessionError::ForkDetected),
+    /// for a deployment to investigate before going on. A fork found in the backup stops the
+    /// session again when recovering.
+    Shutdown,
+}
+
+/// Presets of the timing parameters for common kinds of deployments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Short delays for small committees on fast networks, where quick finalization matters
+    /// more than the amount of traffic.
+    LowLatency,
+    /// Longer delays and finalization backpressure, so that every unit carries more data and
+    /// the DAG does not run far ahead of finalization.
+    HighThroughput,
+    /// Very short delays, suitable for integration tests running all the nodes locally.
+    Testing,
+}
+
+impl Config {
+    /// A config for the given node with the timing parameters of `profile` and session 0.
+    pub fn with_profile(node_ix: NodeIndex, n_members: NodeCount, profile: Profile) -> Config {
+        let mut config = default_config(n_members, node_ix, 0);
+        let delay_config = &mut config.delay_config;
+        match profile {
+            Profile::LowLatency => {
+                delay_config.tick_interval = Duration::from_millis(20);
+                config.request_config.interval = constant_schedule(Duration::from_millis(1000));
+                // 1000, 2000, 4000, 8000, 8000, ...
+                config.request_config.missing_parent_request_interval =
+                    exponential_schedule(Duration::from_millis(1000), 2.0, Duration::from_secs(8));
+                // 1000, 2000, 4000, 8000, ...
+                delay_config.unit_broadcast_delay =
+                    Arc::new(|t| exponential_slowdown(t, 1000.0, 0, 2.0));
+                // 1000, 200, 200, 200, ... (till step 3000), 200, 200*1.005, 200*(1.005)^2, ...
+                delay_config.unit_creation_delay = Arc::new(|t| {
+                    if t == 0 {
+                        Duration::from_millis(1000)
+                    } else {
+                        exponential_slowdown(t, 200.0, 3000, 1.005)
+                    }
+                });
+            }
+            Profile::HighThroughput => {
+                // 5000, 1000, 1000, 1000, ... (till step 3000), 1000, 1000*1.005, ...
+                delay_config.unit_creation_delay = Arc::new(|t| {
+                    if t == 0 {
+                        Duration::from_millis(5000)
+                    } else {
+                        exponential_slowdown(t, 1000.0, 3000, 1.005)
+                    }
+                });
+                delay_config.creation_backpressure_threshold = Some(10);
+                config.parents_backlog_high_water = 5000;
+            }
+            Profile::Testing => {
+                delay_config.tick_interval = Duration::from_millis(5);
+                config.request_config.interval = constant_schedule(Duration::from_millis(50));
+                // 50, 100, 200, 400, 400, ...
+                config.request_config.missing_parent_request_interval = exponential_schedule(
+                    Duration::from_millis(50),
+                    2.0,
+                    Duration::from_millis(400),
+                );
+                // 100, 100, 300, 900, 2700, ...
+                delay_config.unit_broadcast_delay =
+                    Arc::new(|t| exponential_slowdown(t, 100.0, 1, 3.0));
+                // 50, 50, 50, 50, ...
+                delay_config.unit_creation_delay = constant_schedule(Duration::from_millis(50));
+            }
+        }
+        config
+    }
+}
+
+/// Error type returned when a [`ConfigBuilder`] is given invalid parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The committee has no members.
+    NoMembers,
+    /// The index of the node is not smaller than the size of the committee.
+    NodeIndexOutOfRange {
+        node_ix: NodeIndex,
+        n_members: NodeCount,
+    },
+    /// The maximum round is zero.
+    ZeroMaxRound,
+    /// The maximum round is above [`MAX_MAX_ROUND`].
+    MaxRoundTooHigh(Round),
+    /// The maximum round is to be raised by zero rounds once reached.
+    ZeroMaxRoundExtension,
+    /// The delay with the given name is zero, at least for some initial arguments in case of a
+    /// schedule.
+    ZeroDelay(&'static str),
+    /// We are supposed to ask no members for missing units.
+    ZeroRequestFanout,
+    /// The maximum size of batches is zero.
+    ZeroMaxBatchSize,
+    /// The bound on the number of stored units is zero.
+    ZeroMaxStoredUnits,
+    /// The batches waiting for the finalization handler may span at most zero rounds.
+    ZeroMaxUndeliveredRounds,
+    /// The maximum size of the data of a unit is zero.
+    ZeroMaxDataSize,
+    /// We are supposed to request the data for our units with at most zero calls at once.
+    ZeroDataPipelineDepth,
+    /// The unit messages are to be sent in batches of at most zero messages.
+    ZeroUnitBatchSize,
+    /// We are supposed to answer requests with at most zero units per window.
+    ZeroResponseLimit,
+    /// We are supposed to let the other tasks run after verifying zero units.
+    ZeroVerificationBudget,
+    /// A stepped schedule was given no steps.
+    NoSteps,
+    /// The steps of a stepped schedule are not sorted by their rounds, or some round repeats.
+    UnsortedSteps,
+    /// The bounds of the creation latency buckets are not sorted, or some bound repeats.
+    UnsortedLatencyBuckets,
+    /// The number of rounds we may run ahead of finalization is below [`MIN_MAX_ROUNDS_AHEAD`].
+    MaxRoundsAheadTooLow(Round),
+    /// The number of units of the same creator and round we process is below
+    /// [`MIN_MAX_UNIT_VARIANTS`], so we could not notice forks.
+    MaxUnitVariantsTooLow(usize),
+    /// The fraction of rounds below which our units count as rarely included is not between 0 and 1.
+    OwnUnitInclusionOutOfRange,
+    /// The balance of parent inclusion below which we warn is not between 0 and 1.
+    ParentInclusionBalanceOutOfRange,
+    /// The creation backpressure factor is not between 1 and
+    /// [`MAX_CREATION_BACKPRESSURE_FACTOR`].
+    BackpressureFactorOutOfRange,
+    /// The committee has fewer than [`MIN_FAULT_TOLERANT_MEMBERS`] members. The consensus needs
+    /// more than two thirds of the members, which in such a committee are all of them, so a
+    /// single crashed or malicious member stops it. Allowed with
+    /// [`ConfigBuilder::allow_small_committee`], e.g. for testing on a single node.
+    SmallCommittee(NodeCount),
+    /// The weights are given for a committee of a different size.
+    WrongNumberOfWeights(NodeCount),
+    /// The weights of all the members are zero.
+    ZeroTotalWeight,
+    /// More other members have to be active for the network to count as active than there are,
+    /// see [`LivenessConfig::min_active_members`].
+    TooManyActiveMembers(NodeCount),
+    /// The number of rounds at which we stop catching up is not below the number at which we
+    /// start, see [`CatchUpConfig::caught_up_rounds`].
+    CatchUpThresholdsNotOrdered {
+        caught_up_rounds: Round,
+        behind_rounds: Round,
+    },
+}
+
+/// The smallest committee tolerating a faulty member. In general a committee of `n` members
+/// tolerates `(n - 1) / 3` faulty ones.
+pub const MIN_FAULT_TOLERANT_MEMBERS: NodeCount = NodeCount(4);
+
+/// A builder of a [`Config`], using the parameters of [`default_config`] unless set otherwise.
+/// Checks that the parameters make sense before building the config.
+///
+/// ```
+/// use aleph_bft::{ConfigBuilder, NodeCount, NodeIndex};
+///
+/// let config = ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+///     .session_id(7)
+///     .max_round(1000)
+///     .build()
+///     .expect("the parameters are valid");
+/// assert_eq!(config.max_round, 1000);
+/// ```
+pub struct ConfigBuilder {
+    config: Config,
+    allow_small_committee: bool,
+}
+
+impl ConfigBuilder {
+    /// Starts building a config for the member with the given index in a committee of the given size.
+    pub fn new(n_members: NodeCount, node_ix: NodeIndex) -> Self {
+        ConfigBuilder {
+            config: default_config(n_members, node_ix, 0),
+            allow_small_committee: false,
+        }
+    }
+
+    /// Starts building a config for a committee of a single member, for running AlephBFT locally
+    /// during development. The member orders its own units as soon as it creates them, as there
+    /// is nobody to agree with, so there is no fault tolerance whatsoever. Like any other
+    /// committee that small, it has to be allowed explicitly.
+    ///
+    /// ```
+    /// use aleph_bft::{ConfigBuilder, ConfigError, NodeCount};
+    ///
+    /// assert_eq!(
+    ///     ConfigBuilder::single_node().build().err(),
+    ///     Some(ConfigError::SmallCommittee(NodeCount(1)))
+    /// );
+    /// let config = ConfigBuilder::single_node()
+    ///     .allow_small_committee(true)
+    ///     .build()
+    ///     .expect("a single node is allowed");
+    /// assert_eq!(config.n_members, NodeCount(1));
+    /// ```
+    pub fn single_node() -> Self {
+        Self::new(NodeCount(1), NodeIndex(0))
+    }
+
+    pub fn session_id(mut self, session_id: SessionId) -> Self {
+        self.config.session_id = session_id;
+        self
+    }
+
+    /// The weights of the members, in the order of their indices, see [`Config::node_weights`].
+    pub fn node_weights(mut self, weights: NodeWeights) -> Self {
+        self.config.node_weights = weights;
+        self
+    }
+
+    pub fn delay_config(mut self, delay_config: DelayConfig) -> Self {
+        self.config.delay_config = delay_config;
+        self
+    }
+
+    pub fn request_config(mut self, request_config: RequestConfig) -> Self {
+        self.config.request_config = request_config;
+        self
+    }
+
+    pub fn max_round(mut self, max_round: Round) -> Self {
+        self.config.max_round = max_round;
+        self
+    }
+
+    pub fn max_round_warning_margin(mut self, margin: Round) -> Self {
+        self.config.max_round_warning_margin = margin;
+        self
+    }
+
+    pub fn max_round_policy(mut self, policy: MaxRoundPolicy) -> Self {
+        self.config.max_round_policy = policy;
+        self
+    }
+
+    pub fn max_rounds_ahead(mut self, max_rounds_ahead: Round) -> Self {
+        self.config.max_rounds_ahead = Some(max_rounds_ahead);
+        self
+    }
+
+    pub fn max_undelivered_rounds(mut self, max_undelivered_rounds: Round) -> Self {
+        self.config.max_undelivered_rounds = max_undelivered_rounds;
+        self
+    }
+
+    pub fn parents_backlog_high_water(mut self, high_water: usize) -> Self {
+        self.config.parents_backlog_high_water = high_water;
+        self
+    }
+
+    pub fn parent_selector(mut self, parent_selector: Arc<dyn ParentSelector>) -> Self {
+        self.config.parent_selector = parent_selector;
+        self
+    }
+
+    pub fn min_parents(mut self, min_parents: NodeCount) -> Self {
+        self.config.min_parents = Some(min_parents);
+        self
+    }
+
+    pub fn processed_units_cache_size(mut self, size: usize) -> Self {
+        self.config.processed_units_cache_size = size;
+        self
+    }
+
+    pub fn max_unit_variants(mut self, max_unit_variants: usize) -> Self {
+        self.config.max_unit_variants = max_unit_variants;
+        self
+    }
+
+    pub fn min_own_unit_inclusion(mut self, fraction: f64) -> Self {
+        self.config.min_own_unit_inclusion = Some(fraction);
+        self
+    }
+
+    pub fn min_parent_inclusion_balance(mut self, balance: f64) -> Self {
+        self.config.min_parent_inclusion_balance = Some(balance);
+        self
+    }
+
+    pub fn unit_verification_workers(mut self, workers: usize) -> Self {
+        self.config.unit_verification_workers = workers;
+        self
+    }
+
+    pub fn verification_budget(mut self, n_units: usize) -> Self {
+        self.config.verification_budget = Some(n_units);
+        self
+    }
+
+    pub fn creator_notification_capacity(mut self, capacity: usize) -> Self {
+        self.config.creator_notification_capacity = capacity;
+        self
+    }
+
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.config.max_batch_size = Some(max_batch_size);
+        self
+    }
+
+    pub fn max_stored_units(mut self, max_stored_units: usize) -> Self {
+        self.config.max_stored_units = Some(max_stored_units);
+        self
+    }
+
+    pub fn max_data_size(mut self, max_data_size: usize) -> Self {
+        self.config.max_data_size = Some(max_data_size);
+        self
+    }
+
+    pub fn data_pipeline_depth(mut self, depth: usize) -> Self {
+        self.config.data_pipeline_depth = depth;
+        self
+    }
+
+    pub fn unit_batching(mut self, unit_batching: UnitBatchingConfig) -> Self {
+        self.config.unit_batching = Some(unit_batching);
+        self
+    }
+
+    pub fn response_limit(mut self, response_limit: ResponseLimitConfig) -> Self {
+        self.config.response_limit = Some(response_limit);
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.config.clock = clock;
+        self
+    }
+
+    pub fn creation_latency_sink(mut self, sink: Sender<CreationLatencies>) -> Self {
+        self.config.creation_latency_sink = Some(sink);
+        self
+    }
+
+    pub fn creation_latency_buckets(mut self, buckets: Vec<Duration>) -> Self {
+        self.config.creation_latency_buckets = buckets;
+        self
+    }
+
+    pub fn quorum_round_sink(mut self, sink: Sender<Round>) -> Self {
+        self.config.quorum_round_sink = Some(sink);
+        self
+    }
+
+    pub fn unit_rejection_sink(mut self, sink: Sender<UnitRejection>) -> Self {
+        self.config.unit_rejection_sink = Some(sink);
+        self
+    }
+
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.config.random_seed = Some(seed);
+        self
+    }
+
+    pub fn rng(mut self, rng: RngFactory) -> Self {
+        self.config.rng = Some(rng);
+        self
+    }
+
+    pub fn fork_handler(mut self, fork_handler: Arc<dyn ForkHandler>) -> Self {
+        self.config.fork_handler = fork_handler;
+        self
+    }
+
+    pub fn fork_policy(mut self, policy: ForkPolicy) -> Self {
+        self.config.fork_policy = policy;
+        self
+    }
+
+    pub fn backup(
+        mut self,
+        backup_sink: Arc<dyn BackupSink>,
+        backup_source: Arc<dyn BackupSource>,
+    ) -> Self {
+        self.config.backup_sink = backup_sink;
+        self.config.backup_source = backup_source;
+        self
+    }
+
+    pub fn backup_compaction_threshold(mut self, threshold: usize) -> Self {
+        self.config.backup_compaction_threshold = threshold;
+        self
+    }
+
+    pub fn recovery_timeout(mut self, timeout: Duration) -> Self {
+        self.config.recovery_timeout = Some(timeout);
+        self
+    }
+
+    pub fn skip_delivered_after_recovery(mut self, skip: bool) -> Self {
+        self.config.skip_delivered_after_recovery = skip;
+        self
+    }
+
+    pub fn externally_finalized_round(mut self, round: Round) -> Self {
+        self.config.externally_finalized_round = Some(round);
+        self
+    }
+
+    pub fn known_unit_hashes<T: AsRef<[u8]>>(
+        mut self,
+        hashes: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.config.known_unit_hashes = hashes
+            .into_iter()
+            .map(|hash| hash.as_ref().to_vec())
+            .collect();
+        self
+    }
+
+    pub fn truncate_inconsistent_backup(mut self, truncate: bool) -> Self {
+        self.config.truncate_inconsistent_backup = truncate;
+        self
+    }
+
+    pub fn session_tag(mut self, session_tag: Vec<u8>) -> Self {
+        self.config.session_tag = session_tag;
+        self
+    }
+
+    pub fn check_hasher(mut self, check: bool) -> Self {
+        self.config.check_hasher = check;
+        self
+    }
+
+    pub fn data_channel_policy(mut self, policy: DataChannelPolicy) -> Self {
+        self.config.data_channel_policy = policy;
+        self
+    }
+
+    pub fn observer(mut self, observer: bool) -> Self {
+        self.config.observer = observer;
+        self
+    }
+
+    pub fn task_panic_policy(mut self, policy: TaskPanicPolicy) -> Self {
+        self.config.task_panic_policy = policy;
+        self
+    }
+
+    pub fn liveness(mut self, liveness: LivenessConfig) -> Self {
+        self.config.liveness = liveness;
+        self
+    }
+
+    pub fn catch_up(mut self, catch_up: CatchUpConfig) -> Self {
+        self.config.catch_up = catch_up;
+        self
+    }
+
+    /// Allows committees of fewer than [`MIN_FAULT_TOLERANT_MEMBERS`] members, which tolerate no
+    /// faulty member, see [`ConfigError::SmallCommittee`].
+    pub fn allow_small_committee(mut self, allow: bool) -> Self {
+        self.allow_small_committee = allow;
+        self
+    }
+
+    /// Validates the parameters and builds the config.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let config = self.config;
+        if config.n_members.0 == 0 {
+            return Err(ConfigError::NoMembers);
+        }
+        if config.node_ix.0 >= config.n_members.0 {
+            return Err(ConfigError::NodeIndexOutOfRange {
+                node_ix: config.node_ix,
+                n_members: config.n_members,
+            });
+        }
+        if config.n_members < MIN_FAULT_TOLERANT_MEMBERS && !self.allow_small_committee {
+            return Err(ConfigError::SmallCommittee(config.n_members));
+        }
+        if config.node_weights.n_members() != config.n_members {
+            return Err(ConfigError::WrongNumberOfWeights(
+                config.node_weights.n_members(),
+            ));
+        }
+        if config.node_weights.total() == 0 {
+            return Err(ConfigError::ZeroTotalWeight);
+        }
+        if config.max_round == 0 {
+            return Err(ConfigError::ZeroMaxRound);
+        }
+        if config.max_round > MAX_MAX_ROUND {
+            return Err(ConfigError::MaxRoundTooHigh(config.max_round));
+        }
+        if config.max_round_policy == MaxRoundPolicy::Extend(0) {
+            return Err(ConfigError::ZeroMaxRoundExtension);
+        }
+        if let Some(max_rounds_ahead) = config.max_rounds_ahead {
+            if max_rounds_ahead < MIN_MAX_ROUNDS_AHEAD {
+                return Err(ConfigError::MaxRoundsAheadTooLow(max_rounds_ahead));
+            }
+        }
+        if config.max_unit_variants < MIN_MAX_UNIT_VARIANTS {
+            return Err(ConfigError::MaxUnitVariantsTooLow(config.max_unit_variants));
+        }
+        if let Some(fraction) = config.min_own_unit_inclusion {
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(ConfigError::OwnUnitInclusionOutOfRange);
+            }
+        }
+        if let Some(balance) = config.min_parent_inclusion_balance {
+            if !(0.0..=1.0).contains(&balance) {
+                return Err(ConfigError::ParentInclusionBalanceOutOfRange);
+            }
+        }
+        if config.request_config.fanout == 0 || config.catch_up.fanout == 0 {
+            return Err(ConfigError::ZeroRequestFanout);
+        }
+        let CatchUpConfig {
+            behind_rounds,
+            caught_up_rounds,
+            ..
+        } = config.catch_up;
+        if caught_up_rounds >= behind_rounds {
+            return Err(ConfigError::CatchUpThresholdsNotOrdered {
+                caught_up_rounds,
+                behind_rounds,
+            });
+        }
+        if config.max_batch_size == Some(0) {
+            return Err(ConfigError::ZeroMaxBatchSize);
+        }
+        if config.max_stored_units == Some(0) {
+            return Err(ConfigError::ZeroMaxStoredUnits);
+        }
+        if config.max_undelivered_rounds == 0 {
+            return Err(ConfigError::ZeroMaxUndeliveredRounds);
+        }
+        if config.max_data_size == Some(0) {
+            return Err(ConfigError::ZeroMaxDataSize);
+        }
+        if config.verification_budget == Some(0) {
+            return Err(ConfigError::ZeroVerificationBudget);
+        }
+        if config.data_pipeline_depth == 0 {
+            return Err(ConfigError::ZeroDataPipelineDepth);
+        }
+        if let Some(unit_batching) = config.unit_batching {
+            if unit_batching.max_batch_size == 0 {
+                return Err(ConfigError::ZeroUnitBatchSize);
+            }
+            if unit_batching.window.is_zero() {
+                return Err(ConfigError::ZeroDelay("unit_batching_window"));
+            }
+        }
+        if let Some(response_limit) = config.response_limit {
+            if response_limit.max_units == 0 {
+                return Err(ConfigError::ZeroResponseLimit);
+            }
+            if response_limit.window.is_zero() {
+                return Err(ConfigError::ZeroDelay("response_limit_window"));
+            }
+        }
+        if config
+            .recovery_timeout
+            .is_some_and(|timeout| timeout.is_zero())
+        {
+            return Err(ConfigError::ZeroDelay("recovery_timeout"));
+        }
+        if config
+            .creation_latency_buckets
+            .windows(2)
+            .any(|pair| pair[0] >= pair[1])
+        {
+            return Err(ConfigError::UnsortedLatencyBuckets);
+        }
+        let delay_config = &config.delay_config;
+        if !(1.0..=MAX_CREATION_BACKPRESSURE_FACTOR)
+            .contains(&delay_config.creation_backpressure_factor)
+        {
+            return Err(ConfigError::BackpressureFactorOutOfRange);
+        }
+        if delay_config.tick_interval.is_zero() {
+            return Err(ConfigError::ZeroDelay("tick_interval"));
+        }
+        if delay_config.stall_warning_delay.is_zero() {
+            return Err(ConfigError::ZeroDelay("stall_warning_delay"));
+        }
+        if delay_config.data_timeout == Some(Duration::ZERO) {
+            return Err(ConfigError::ZeroDelay("data_timeout"));
+        }
+        if delay_config.alert_timeout == Some(Duration::ZERO) {
+            return Err(ConfigError::ZeroDelay("alert_timeout"));
+        }
+        if config.liveness.stall_threshold.is_zero() {
+            return Err(ConfigError::ZeroDelay("liveness_stall_threshold"));
+        }
+        if config.liveness.activity_window.is_zero() {
+            return Err(ConfigError::ZeroDelay("liveness_activity_window"));
+        }
+        if let Some(min_active_members) = config.liveness.min_active_members {
+            if min_active_members >= config.n_members {
+                return Err(ConfigError::TooManyActiveMembers(min_active_members));
+            }
+        }
+        let schedules = [
+            ("unit_broadcast_delay", &delay_config.unit_broadcast_delay),
+            ("unit_creation_delay", &delay_config.unit_creation_delay),
+            ("rmc_retry_delay", &delay_config.rmc_retry_delay),
+            ("request_interval", &config.request_config.interval),
+            (
+                "missing_parent_request_interval",
+                &config.request_config.missing_parent_request_interval,
+            ),
+        ];
+        for (name, schedule) in schedules {
+            if (0..CHECKED_SCHEDULE_STEPS).any(|t| schedule(t).is_zero()) {
+                return Err(ConfigError::ZeroDelay(name));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A description of a [`DelaySchedule`], which unlike the schedule itself can be serialized.
+/// All the delays are in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum DelayScheduleSpec {
+    /// See [`constant_schedule`].
+    Constant { delay_ms: u64 },
+    /// See [`exponential_schedule`].
+    Exponential {
+        initial_ms: u64,
+        factor: f64,
+        max_ms: u64,
+    },
+    /// See [`stepped_schedule`], with every step given as the round it starts at and its delay.
+    Stepped { steps: Vec<(Round, u64)> },
+}
+
+impl TryFrom<DelayScheduleSpec> for DelaySchedule {
+    type Error = ConfigError;
+
+    fn try_from(spec: DelayScheduleSpec) -> Result<Self, Self::Error> {
+        Ok(match spec {
+            DelayScheduleSpec::Constant { delay_ms } => {
+                constant_schedule(Duration::from_millis(delay_ms))
+            }
+            DelayScheduleSpec::Exponential {
+                initial_ms,
+                factor,
+                max_ms,
+            } => exponential_schedule(
+                Duration::from_millis(initial_ms),
+                factor,
+                Duration::from_millis(max_ms),
+            ),
+            DelayScheduleSpec::Stepped { steps } => stepped_schedule(
+                steps
+                    .into_iter()
+                    .map(|(round, delay_ms)| (round, Duration::from_millis(delay_ms)))
+                    .collect(),
+            )?,
+        })
+    }
+}
+
+/// A serializable description of a [`DelayConfig`], with all the delays in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DelayConfigSpec {
+    pub tick_interval_ms: u64,
+    pub stall_warning_delay_ms: u64,
+    pub creation_backpressure_threshold: Option<Round>,
+    pub creation_backpressure_factor: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub data_timeout_ms: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alert_resend_interval_ms: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alert_timeout_ms: Option<u64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub parents_grace_period_ms: Option<u64>,
+    // The schedules come last, as TOML requires tables to follow plain values.
+    pub unit_broadcast_delay: DelayScheduleSpec,
+    pub unit_creation_delay: DelayScheduleSpec,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rmc_retry_delay: Option<DelayScheduleSpec>,
+}
+
+impl TryFrom<DelayConfigSpec> for DelayConfig {
+    type Error = ConfigError;
+
+    fn try_from(spec: DelayConfigSpec) -> Result<Self, Self::Error> {
+        Ok(DelayConfig {
+            tick_interval: Duration::from_millis(spec.tick_interval_ms),
+            unit_broadcast_delay: spec.unit_broadcast_delay.try_into()?,
+            unit_creation_delay: spec.unit_creation_delay.try_into()?,
+            // A closure cannot be described in a spec.
+            context_creation_delay: None,
+            stall_warning_delay: Duration::from_millis(spec.stall_warning_delay_ms),
+            creation_backpressure_threshold: spec.creation_backpressure_threshold,
+            creation_backpressure_factor: spec.creation_backpressure_factor,
+            data_timeout: spec.data_timeout_ms.map(Duration::from_millis),
+            alert_resend_interval: spec
+                .alert_resend_interval_ms
+                .map_or(DEFAULT_ALERT_RESEND_INTERVAL, Duration::from_millis),
+            rmc_retry_delay: match spec.rmc_retry_delay {
+                Some(schedule) => schedule.try_into()?,
+                None => default_rmc_retry_delay(),
+            },
+            alert_timeout: spec.alert_timeout_ms.map(Duration::from_millis),
+            parents_grace_period: spec
+                .parents_grace_period_ms
+                .map_or(Duration::ZERO, Duration::from_millis),
+        })
+    }
+}
+
+/// A serializable description of a [`RequestConfig`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RequestConfigSpec {
+    pub fanout: usize,
+    pub interval: DelayScheduleSpec,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub missing_parent_request_interval: Option<DelayScheduleSpec>,
+}
+
+impl TryFrom<RequestConfigSpec> for RequestConfig {
+    type Error = ConfigError;
+
+    fn try_from(spec: RequestConfigSpec) -> Result<Self, Self::Error> {
+        Ok(RequestConfig {
+            fanout: spec.fanout,
+            interval: spec.interval.try_into()?,
+            missing_parent_request_interval: match spec.missing_parent_request_interval {
+                Some(schedule) => schedule.try_into()?,
+                None => default_missing_parent_request_interval(),
+            },
+        })
+    }
+}
+
+/// A serializable description of a [`Config`]. The parameters which are not set are taken from
+/// [`default_config`], and the resulting config is validated as in [`ConfigBuilder`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfigSpec {
+    pub node_ix: NodeIndex,
+    pub n_members: NodeCount,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub session_id: SessionId,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_round: Option<Round>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub allow_small_committee: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub delay_config: Option<DelayConfigSpec>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub request_config: Option<RequestConfigSpec>,
+}
+
+impl TryFrom<ConfigSpec> for Config {
+    type Error = ConfigError;
+
+    fn try_from(spec: ConfigSpec) -> Result<Self, Self::Error> {
+        let mut builder = ConfigBuilder::new(spec.n_members, spec.node_ix)
+            .session_id(spec.session_id)
+            .allow_small_committee(spec.allow_small_committee);
+        if let Some(max_round) = spec.max_round {
+            builder = builder.max_round(max_round);
+        }
+        if let Some(delay_config) = spec.delay_config {
+            builder = builder.delay_config(delay_config.try_into()?);
+        }
+        if let Some(request_config) = spec.request_config {
+            builder = builder.request_config(request_config.try_into()?);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_default_config() {
+        let config = ConfigBuilder::new(NodeCount(4), NodeIndex(3))
+            .build()
+            .expect("the parameters are valid");
+        assert_eq!(config.n_members, NodeCount(4));
+        assert_eq!(config.node_ix, NodeIndex(3));
+    }
+
+    #[test]
+    fn profiles_are_valid() {
+        for profile in [
+            Profile::LowLatency,
+            Profile::HighThroughput,
+            Profile::Testing,
+        ] {
+            let config = Config::with_profile(NodeIndex(1), NodeCount(4), profile);
+            assert_eq!(config.node_ix, NodeIndex(1));
+            assert_eq!(config.n_members, NodeCount(4));
+            ConfigBuilder::new(NodeCount(4), NodeIndex(1))
+                .delay_config(config.delay_config)
+                .max_round(config.max_round)
+                .build()
+                .expect("the profile parameters are valid");
+        }
+        let testing = Config::with_profile(NodeIndex(0), NodeCount(4), Profile::Testing);
+        let default = default_config(NodeCount(4), NodeIndex(0), 0);
+        assert!(
+            (testing.delay_config.unit_creation_delay)(1)
+                < (default.delay_config.unit_creation_delay)(1)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_parameters() {
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(0), NodeIndex(0)).build().err(),
+            Some(ConfigError::NoMembers)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(4)).build().err(),
+            Some(ConfigError::NodeIndexOutOfRange {
+                node_ix: NodeIndex(4),
+                n_members: NodeCount(4)
+            })
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .node_weights(NodeWeights::new(vec![1, 2, 3]))
+                .build()
+                .err(),
+            Some(ConfigError::WrongNumberOfWeights(NodeCount(3)))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .node_weights(NodeWeights::new(vec![0; 4]))
+                .build()
+                .err(),
+            Some(ConfigError::ZeroTotalWeight)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_round(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroMaxRound)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_round(Round::MAX)
+                .build()
+                .err(),
+            Some(ConfigError::MaxRoundTooHigh(Round::MAX))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_round_policy(MaxRoundPolicy::Extend(0))
+                .build()
+                .err(),
+            Some(ConfigError::ZeroMaxRoundExtension)
+        );
+        assert!(ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+            .max_round(MAX_MAX_ROUND)
+            .build()
+            .is_ok());
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_batch_size(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroMaxBatchSize)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_stored_units(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroMaxStoredUnits)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_undelivered_rounds(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroMaxUndeliveredRounds)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_data_size(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroMaxDataSize)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .data_pipeline_depth(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDataPipelineDepth)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .unit_batching(UnitBatchingConfig {
+                    window: Duration::from_millis(10),
+                    max_batch_size: 0,
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroUnitBatchSize)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .response_limit(ResponseLimitConfig {
+                    max_units: 0,
+                    window: Duration::from_secs(1),
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroResponseLimit)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .verification_budget(0)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroVerificationBudget)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .liveness(LivenessConfig {
+                    min_active_members: Some(NodeCount(4)),
+                    ..LivenessConfig::default()
+                })
+                .build()
+                .err(),
+            Some(ConfigError::TooManyActiveMembers(NodeCount(4)))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .catch_up(CatchUpConfig {
+                    behind_rounds: 6,
+                    caught_up_rounds: 6,
+                    fanout: 3,
+                })
+                .build()
+                .err(),
+            Some(ConfigError::CatchUpThresholdsNotOrdered {
+                caught_up_rounds: 6,
+                behind_rounds: 6
+            })
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .catch_up(CatchUpConfig {
+                    fanout: 0,
+                    ..CatchUpConfig::default()
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroRequestFanout)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .creation_latency_buckets(vec![
+                    Duration::from_millis(100),
+                    Duration::from_millis(100),
+                ])
+                .build()
+                .err(),
+            Some(ConfigError::UnsortedLatencyBuckets)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_rounds_ahead(MIN_MAX_ROUNDS_AHEAD - 1)
+                .build()
+                .err(),
+            Some(ConfigError::MaxRoundsAheadTooLow(MIN_MAX_ROUNDS_AHEAD - 1))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .max_unit_variants(1)
+                .build()
+                .err(),
+            Some(ConfigError::MaxUnitVariantsTooLow(1))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .min_own_unit_inclusion(1.5)
+                .build()
+                .err(),
+            Some(ConfigError::OwnUnitInclusionOutOfRange)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .min_parent_inclusion_balance(-0.5)
+                .build()
+                .err(),
+            Some(ConfigError::ParentInclusionBalanceOutOfRange)
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .request_config(RequestConfig {
+                    fanout: 0,
+                    interval: constant_schedule(Duration::from_millis(100)),
+                    missing_parent_request_interval: constant_schedule(Duration::from_millis(100)),
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroRequestFanout)
+        );
+    }
+
+    #[test]
+    fn rejects_zero_delays() {
+        let mut delay_config = default_config(NodeCount(4), NodeIndex(0), 0).delay_config;
+        delay_config.unit_creation_delay =
+            Arc::new(|t| Duration::from_millis(if t == 5 { 0 } else { 500 }));
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .delay_config(delay_config.clone())
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("unit_creation_delay"))
+        );
+        delay_config.unit_creation_delay = constant_schedule(Duration::from_millis(500));
+        delay_config.tick_interval = Duration::ZERO;
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .delay_config(delay_config.clone())
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("tick_interval"))
+        );
+        delay_config.tick_interval = Duration::from_millis(100);
+        delay_config.data_timeout = Some(Duration::ZERO);
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .delay_config(delay_config.clone())
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("data_timeout"))
+        );
+        delay_config.data_timeout = None;
+        delay_config.alert_timeout = Some(Duration::ZERO);
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .delay_config(delay_config)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("alert_timeout"))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .unit_batching(UnitBatchingConfig {
+                    window: Duration::ZERO,
+                    max_batch_size: 16,
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("unit_batching_window"))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .response_limit(ResponseLimitConfig {
+                    max_units: 100,
+                    window: Duration::ZERO,
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("response_limit_window"))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .recovery_timeout(Duration::ZERO)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("recovery_timeout"))
+        );
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .liveness(LivenessConfig {
+                    activity_window: Duration::ZERO,
+                    ..LivenessConfig::default()
+                })
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("liveness_activity_window"))
+        );
+    }
+
+    #[test]
+    fn rejects_small_committee_unless_allowed() {
+        for n_members in 1..4 {
+            assert_eq!(
+                ConfigBuilder::new(NodeCount(n_members), NodeIndex(0))
+                    .build()
+                    .err(),
+                Some(ConfigError::SmallCommittee(NodeCount(n_members)))
+            );
+            ConfigBuilder::new(NodeCount(n_members), NodeIndex(0))
+                .allow_small_committee(true)
+                .build()
+                .expect("small committees are allowed");
+        }
+    }
+
+    #[test]
+    fn stepped_spec_changes_delay_at_given_rounds() {
+        let schedule: DelaySchedule = DelayScheduleSpec::Stepped {
+            steps: vec![(0, 5000), (10, 500)],
+        }
+        .try_into()
+        .expect("the steps are valid");
+        assert_eq!(schedule(0), Duration::from_millis(5000));
+        assert_eq!(schedule(9), Duration::from_millis(5000));
+        assert_eq!(schedule(10), Duration::from_millis(500));
+        assert_eq!(schedule(100), Duration::from_millis(500));
+        let unsorted = DelayScheduleSpec::Stepped {
+            steps: vec![(10, 500), (0, 5000)],
+        };
+        assert_eq!(
+            DelaySchedule::try_from(unsorted).err(),
+            Some(ConfigError::UnsortedSteps)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_backpressure_factor() {
+        for factor in [0.5, -2.0, f64::NAN, f64::INFINITY, 1e20] {
+            let mut delay_config = default_config(NodeCount(4), NodeIndex(0), 0).delay_config;
+            delay_config.creation_backpressure_factor = factor;
+            assert_eq!(
+                ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                    .delay_config(delay_config)
+                    .build()
+                    .err(),
+                Some(ConfigError::BackpressureFactorOutOfRange)
+            );
+        }
+        let spec = ConfigSpec {
+            node_ix: NodeIndex(1),
+            n_members: NodeCount(4),
+            session_id: 0,
+            max_round: None,
+            allow_small_committee: false,
+            delay_config: Some(DelayConfigSpec {
+                tick_interval_ms: 100,
+                unit_broadcast_delay: DelayScheduleSpec::Constant { delay_ms: 4000 },
+                unit_creation_delay: DelayScheduleSpec::Constant { delay_ms: 500 },
+                stall_warning_delay_ms: 60_000,
+                creation_backpressure_threshold: Some(10),
+                creation_backpressure_factor: -1.0,
+                data_timeout_ms: None,
+                alert_resend_interval_ms: None,
+                alert_timeout_ms: None,
+                parents_grace_period_ms: None,
+                rmc_retry_delay: None,
+            }),
+            request_config: None,
+        };
+        assert_eq!(
+            Config::try_from(spec).err(),
+            Some(ConfigError::BackpressureFactorOutOfRange)
+        );
+    }
+
+    #[test]
+    fn rejects_spec_with_empty_steps() {
+        let mut spec = ConfigSpec {
+            node_ix: NodeIndex(1),
+            n_members: NodeCount(4),
+            session_id: 0,
+            max_round: None,
+            allow_small_committee: false,
+            delay_config: Some(DelayConfigSpec {
+                tick_interval_ms: 100,
+                unit_broadcast_delay: DelayScheduleSpec::Constant { delay_ms: 4000 },
+                unit_creation_delay: DelayScheduleSpec::Stepped { steps: vec![] },
+                stall_warning_delay_ms: 60_000,
+                creation_backpressure_threshold: None,
+                creation_backpressure_factor: 2.0,
+                data_timeout_ms: None,
+                alert_resend_interval_ms: None,
+                alert_timeout_ms: None,
+                parents_grace_period_ms: None,
+                rmc_retry_delay: None,
+            }),
+            request_config: None,
+        };
+        assert_eq!(
+            Config::try_from(spec.clone()).err(),
+            Some(ConfigError::NoSteps)
+        );
+        spec.delay_config = None;
+        assert!(Config::try_from(spec).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn loads_config_from_toml() {
+        let toml_config = r#"
+            node_ix = 2
+            n_members = 7
+            session_id = 3
+            max_round = 1000
+
+            [delay_config]
+            tick_interval_ms = 100
+            stall_warning_delay_ms = 60000
+            creation_backpressure_factor = 2.0
+
+            [delay_config.unit_broadcast_delay]
+            kind = "exponential"
+            initial_ms = 4000
+            factor = 2.0
+            max_ms = 60000
+
+            [delay_config.unit_creation_delay]
+            kind = "stepped"
+            steps = [[0, 5000], [1, 500]]
+
+            [delay_config.rmc_retry_delay]
+            kind = "constant"
+            delay_ms = 2000
+
+            [request_config]
+            fanout = 3
+
+            [request_config.interval]
+            kind = "exponential"
+            initial_ms = 500
+            factor = 2.0
+            max_ms = 8000
+
+            [request_config.missing_parent_request_interval]
+            kind = "exponential"
+            initial_ms = 200
+            factor = 3.0
+            max_ms = 5000
+        "#;
+        let spec: ConfigSpec = toml::from_str(toml_config).expect("the config should parse");
+        let serialized = toml::to_string(&spec).expect("the config should serialize");
+        let deserialized: ConfigSpec =
+            toml::from_str(&serialized).expect("the serialized config should parse");
+        assert_eq!(deserialized, spec);
+
+        let config = Config::try_from(spec).expect("the config should be valid");
+        assert_eq!(config.node_ix, NodeIndex(2));
+        assert_eq!(config.n_members, NodeCount(7));
+        assert_eq!(config.session_id, 3);
+        assert_eq!(config.max_round, 1000);
+        let delay_config = &config.delay_config;
+        assert_eq!(delay_config.creation_backpressure_threshold, None);
+        assert_eq!(
+            (delay_config.unit_broadcast_delay)(1),
+            Duration::from_millis(8000)
+        );
+        assert_eq!(
+            (delay_config.unit_creation_delay)(0),
+            Duration::from_millis(5000)
+        );
+        assert_eq!(
+            (delay_config.unit_creation_delay)(7),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            (delay_config.rmc_retry_delay)(5),
+            Duration::from_millis(2000)
+        );
+        assert_eq!(config.request_config.fanout, 3);
+        assert_eq!(
+            (config.request_config.interval)(2),
+            Duration::from_millis(2000)
+        );
+        let missing_parent_request_interval =
+            &config.request_config.missing_parent_request_interval;
+        assert_eq!(
+            missing_parent_request_interval(2),
+            Duration::from_millis(1800)
+        );
+        assert_eq!(
+            missing_parent_request_interval(4),
+            Duration::from_millis(5000)
+        );
+    }
+
+    #[test]
+    fn seeded_member_rng_is_reproducible() {
+        let draw = |seed, node_ix| member_rng(seed, NodeIndex(node_ix)).gen::<u64>();
+        assert_eq!(draw(Some(7), 1), draw(Some(7), 1));
+        assert_ne!(draw(Some(7), 1), draw(Some(7), 2));
+        assert_ne!(draw(Some(7), 1), draw(Some(8), 1));
+    }
+
+    #[test]
+    fn session_rng_comes_from_the_factory() {
+        let seeded = ConfigBuilder::new(NodeCount(4), NodeIndex(1))
+            .random_seed(7)
+            .build()
+            .expect("the parameters are valid");
+        let injected = ConfigBuilder::new(NodeCount(4), NodeIndex(1))
+            .random_seed(7)
+            .rng(Arc::new(|| Box::new(StdRng::seed_from_u64(3))))
+            .build()
+            .expect("the parameters are valid");
+        assert_eq!(
+            session_rng(&seeded).gen::<u64>(),
+            member_rng(Some(7), NodeIndex(1)).gen::<u64>()
+        );
+        assert_eq!(
+            session_rng(&injected).gen::<u64>(),
+            StdRng::seed_from_u64(3).gen::<u64>()
+        );
+        // Every session gets a fresh generator.
+        assert_eq!(
+            session_rng(&injected).gen::<u64>(),
+            session_rng(&injected).gen::<u64>()
+        );
+    }
+
+    #[test]
+    fn constant_schedule_is_constant() {
+        let delay = Duration::from_millis(300);
+        let schedule = constant_schedule(delay);
+        for t in [0, 1, 10, usize::MAX] {
+            assert_eq!(schedule(t), delay);
+        }
+    }
+
+    #[test]
+    fn exponential_schedule_grows_up_to_max() {
+        let schedule =
+            exponential_schedule(Duration::from_millis(100), 2.0, Duration::from_millis(1000));
+        assert_eq!(schedule(0), Duration::from_millis(100));
+        assert_eq!(schedule(1), Duration::from_millis(200));
+        assert_eq!(schedule(3), Duration::from_millis(800));
+        assert_eq!(schedule(4), Duration::from_millis(1000));
+        assert_eq!(schedule(usize::MAX), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn exponential_schedule_with_negative_factor_is_rejected() {
+        let schedule = exponential_schedule(
+            Duration::from_millis(100),
+            -2.0,
+            Duration::from_millis(1000),
+        );
+        assert_eq!(schedule(0), Duration::from_millis(100));
+        assert_eq!(schedule(1), Duration::ZERO);
+        assert_eq!(schedule(2), Duration::from_millis(400));
+        let spec = DelayScheduleSpec::Exponential {
+            initial_ms: 100,
+            factor: -2.0,
+            max_ms: 1000,
+        };
+        let mut delay_config = default_config(NodeCount(4), NodeIndex(0), 0).delay_config;
+        delay_config.unit_creation_delay = spec.try_into().expect("the spec is valid");
+        assert_eq!(
+            ConfigBuilder::new(NodeCount(4), NodeIndex(0))
+                .delay_config(delay_config)
+                .build()
+                .err(),
+            Some(ConfigError::ZeroDelay("unit_creation_delay"))
+        );
+    }
+
+    #[test]
+    fn stepped_schedule_uses_last_started_step() {
+        let schedule = stepped_schedule(vec![
+            (2, Duration::from_millis(10)),
+            (5, Duration::from_millis(100)),
+            (9, Duration::from_millis(1000)),
+        ])
+        .expect("the steps are valid");
+        assert_eq!(schedule(0), Duration::from_millis(10));
+        assert_eq!(schedule(4), Duration::from_millis(10));
+        assert_eq!(schedule(5), Duration::from_millis(100));
+        assert_eq!(schedule(8), Duration::from_millis(100));
+        assert_eq!(schedule(9), Duration::from_millis(1000));
+        assert_eq!(schedule(usize::MAX), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn stepped_schedule_rejects_invalid_steps() {
+        let delay = Duration::from_millis(10);
+        assert_eq!(stepped_schedule(vec![]).err(), Some(ConfigError::NoSteps));
+        assert_eq!(
+            stepped_schedule(vec![(5, delay), (2, delay)]).err(),
+            Some(ConfigError::UnsortedSteps)
+        );
+        assert_eq!(
+            stepped_schedule(vec![(2, delay), (2, delay)]).err(),
+            Some(ConfigError::UnsortedSteps)
+        );
+    }
+
+    #[test]
+    fn jitter_is_bounded_and_reproducible() {
+        let base: DelaySchedule = Arc::new(|t| Duration::from_millis(10 * t as u64));
+        let max_jitter = Duration::from_millis(100);
+        let schedule = with_jitter(base.clone(), max_jitter, NodeIndex(3));
+        let same_schedule = with_jitter(base.clone(), max_jitter, NodeIndex(3));
+        for t in 0..100 {
+            assert!(schedule(t) >= base(t));
+            assert!(schedule(t) <= base(t) + max_jitter);
+            assert_eq!(schedule(t), same_schedule(t));
+        }
+    }
+
+    #[test]
+    fn jitter_differs_between_nodes() {
+        let base: DelaySchedule = Arc::new(|_| Duration::from_millis(500));
+        let max_jitter = Duration::from_millis(100);
+        let first = with_jitter(base.clone(), max_jitter, NodeIndex(0));
+        let second = with_jitter(base, max_jitter, NodeIndex(1));
+        assert!((0..100).any(|t| first(t) != second(t)));
     }
 }
@@ -1,63 +1,125 @@
 use futures::{
     channel::{mpsc, oneshot},
-    future::FusedFuture,
+    future::Fuse,
     FutureExt,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use crate::{
     config::Config,
-    creation,
+    creation::{self, CreatorCommand},
     extender::Extender,
     runway::{NotificationIn, NotificationOut},
     terminal::Terminal,
-    Hasher, OrderedBatch, Receiver, Round, Sender, SpawnHandle,
+    terminator::Terminator,
+    trace::in_span,
+    units::Unit,
+    Hasher, OrderedBatch, Receiver, Round, Sender, SessionStatus, SpawnHandle,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn run<H: Hasher + 'static>(
     conf: Config,
     incoming_notifications: Receiver<NotificationIn<H>>,
     outgoing_notifications: Sender<NotificationOut<H>>,
-    ordered_batch_tx: Sender<OrderedBatch<H::Hash>>,
+    creator_notifications: mpsc::Sender<NotificationOut<H>>,
+    ordered_batch_tx: Sender<(Round, OrderedBatch<H::Hash>)>,
     spawn_handle: impl SpawnHandle,
     starting_round: oneshot::Receiver<Round>,
-    mut exit: oneshot::Receiver<()>,
+    recovered_units: oneshot::Receiver<Vec<Unit<H>>>,
+    creator_control: Receiver<CreatorCommand>,
+    store_overloaded: Arc<AtomicBool>,
+    status: SessionStatus,
+    mut terminator: Terminator,
 ) {
     info!(target: "AlephBFT", "{:?} Starting all services...", conf.node_ix);
 
-    let n_members = conf.n_members;
     let index = conf.node_ix;
+    let session_id = conf.session_id;
+    let observer = conf.observer;
 
     let (electors_tx, electors_rx) = mpsc::unbounded();
-    let mut extender = Extender::<H>::new(index, n_members, electors_rx, ordered_batch_tx);
-    let (extender_exit, exit_rx) = oneshot::channel();
+    let (finalized_rounds_tx, finalized_rounds_rx) = mpsc::unbounded();
+    let mut extender = Extender::<H>::new(
+        index,
+        conf.node_weights.clone(),
+        conf.max_batch_size,
+        electors_rx,
+        ordered_batch_tx,
+        finalized_rounds_tx,
+        status.clone(),
+    );
+    let extender_terminator = terminator.add_offspring_connection("consensus/extender");
     let mut extender_handle = spawn_handle
-        .spawn_essential("consensus/extender", async move {
-            extender.extend(exit_rx).await
-        })
+        .spawn_essential(
+            "consensus/extender",
+            in_span("extender", index, session_id, async move {
+                extender.extend(extender_terminator).await
+            }),
+        )
         .fuse();
 
     let (parents_for_creator, parents_from_terminal) = mpsc::unbounded();
+    // Shared with the creator, which takes the units off the backlog.
+    let parents_backlog = status.parents_backlog_counter();
 
-    let (creator_exit, exit_rx) = oneshot::channel();
-    let io = creation::IO {
-        outgoing_units: outgoing_notifications.clone(),
-        incoming_parents: parents_from_terminal,
+    // An observer never creates units, so the creator is not started at all.
+    let mut creator_handle = if observer {
+        info!(target: "AlephBFT", "{:?} Running as an observer, no units will be created.", index);
+        // Only the creator needs the finalized rounds, the extender does not mind nobody listening.
+        drop(finalized_rounds_rx);
+        Fuse::terminated()
+    } else {
+        // The delay schedule is not replaced during a session.
+        let (_, delay_schedules) = mpsc::unbounded();
+        let creator_terminator = terminator.add_offspring_connection("consensus/creation");
+        let io = creation::IO {
+            outgoing_units: creator_notifications,
+            incoming_parents: parents_from_terminal,
+            finalized_rounds: finalized_rounds_rx,
+            metrics: None,
+            latencies: conf.creation_latency_sink.clone().map(|sink| {
+                creation::LatencyRecorder::new(sink, conf.creation_latency_buckets.clone())
+            }),
+            control: creator_control,
+            delay_schedules,
+            state_sink: None,
+            parents_backlog: Some(parents_backlog.clone()),
+            store_overloaded: Some(store_overloaded),
+            recovered_units: Some(recovered_units),
+        };
+        spawn_handle
+            .spawn_essential(
+                "consensus/creation",
+                in_span("creator", index, session_id, async move {
+                    creation::run(
+                        conf.clone().into(),
+                        io,
+                        starting_round,
+                        None,
+                        creator_terminator,
+                    )
+                    .await;
+                }),
+            )
+            .fuse()
     };
-    let mut creator_handle = spawn_handle
-        .spawn_essential("consensus/creation", async move {
-            creation::run(conf.clone().into(), io, starting_round, exit_rx).await;
-        })
-        .fuse();
 
     let mut terminal = Terminal::new(index, incoming_notifications, outgoing_notifications);
 
     // send a new parent candidate to the creator
-    terminal.register_post_insert_hook(Box::new(move |u| {
-        parents_for_creator
-            .unbounded_send(u.into())
-            .expect("Channel to creator should be open.");
-    }));
+    if !observer {
+        terminal.register_post_insert_hook(Box::new(move |u| {
+            parents_backlog.fetch_add(1, Ordering::Relaxed);
+            parents_for_creator
+                .unbounded_send(u.into())
+                .expect("Channel to creator should be open.");
+        }));
+    }
     // try to extend the partial order after adding a unit to the dag
     terminal.register_post_insert_hook(Box::new(move |u| {
         electors_tx
@@ -65,17 +127,19 @@ pub(crate) async fn run<H: Hasher + 'static>(
             .expect("Channel to extender should be open.")
     }));
 
-    let (terminal_exit, exit_rx) = oneshot::channel();
+    let terminal_terminator = terminator.add_offspring_connection("consensus/terminal");
     let mut terminal_handle = spawn_handle
         .spawn_essential(
             "consensus/terminal",
-            async move { terminal.run(exit_rx).await },
+            in_span("terminal", index, session_id, async move {
+                terminal.run(terminal_terminator).await
+            }),
         )
         .fuse();
     info!(target: "AlephBFT", "{:?} All services started.", index);
 
     futures::select! {
-        _ = exit => {},
+        _ = terminator.get_exit() => {},
         _ = terminal_handle => {
             debug!(target: "AlephBFT-consensus", "{:?} terminal task terminated early.", index);
         },
@@ -87,27 +151,18 @@ pub(crate) async fn run<H: Hasher + 'static>(
         }
     }
 
-    // we stop no matter if received Ok or Err
-    if terminal_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-consensus", "{:?} terminal already stopped.", index);
-    }
-    if !terminal_handle.is_terminated() {
-        terminal_handle.await.unwrap();
-    }
-
-    if creator_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-consensus", "{:?} creator already stopped.", index);
-    }
-    if !creator_handle.is_terminated() {
-        creator_handle.await.unwrap();
-    }
-
-    if extender_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-consensus", "{:?} extender already stopped.", index);
-    }
-    if !extender_handle.is_terminated() {
-        extender_handle.await.unwrap();
+    // We stop no matter if received Ok or Err. The creator goes first, so that it does not
+    // create units nobody processes anymore.
+    for (name, handle) in [
+        ("consensus/creation", &mut creator_handle),
+        ("consensus/terminal", &mut terminal_handle),
+        ("consensus/extender", &mut extender_handle),
+    ] {
+        if let Some(Err(())) = terminator.terminate_offspring(name, handle).await {
+            warn!(target: "AlephBFT-consensus", "{:?} {} finished with an error.", index, name);
+        }
     }
+    terminator.terminate_sync().await;
 
     info!(target: "AlephBFT", "{:?} All services stopped.", index);
 }
@@ -1,24 +1,122 @@
 use crate::{
-    nodes::{NodeCount, NodeIndex, NodeMap},
-    units::{ControlHash, PreUnit, Unit},
+    config::CreationContext,
+    nodes::{NodeCount, NodeIndex, NodeMap, NodeWeights},
+    units::{ControlHash, ParentsError, PreUnit, Unit},
     Hasher, Round,
 };
-use log::trace;
+use codec::{Decode, Encode};
+use log::{trace, warn};
+use std::sync::Arc;
 
-pub(super) struct Creator<H: Hasher> {
+/// A strategy for choosing the parents of newly created units.
+///
+/// The selection is always validated by the creator: the chosen parents have to be a subset of the
+/// candidates, include our own unit from the previous round and carry more than 2/3 of the total
+/// weight of the members. If any of these is violated, the selection is rejected and all the candidates are used.
+pub trait ParentSelector: Send + Sync + 'static {
+    /// Chooses the parents for our unit of the given round out of the creators of candidate units
+    /// of the previous round. The candidates are in increasing order of their indices, whatever
+    /// order their units arrived in, so a selector breaking ties by their position chooses the
+    /// same parents on every node knowing the same units.
+    fn select(&self, candidates: &[NodeIndex], round: Round) -> Vec<NodeIndex>;
+}
+
+/// The default strategy, choosing all the available candidates as parents.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AllAvailableParents;
+
+impl ParentSelector for AllAvailableParents {
+    fn select(&self, candidates: &[NodeIndex], _round: Round) -> Vec<NodeIndex> {
+        candidates.to_vec()
+    }
+}
+
+/// A snapshot of the internal state of the creator, useful for monitoring.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreatorStatus {
+    /// The round of the unit the creator is trying to create.
+    pub round: Round,
+    /// The number of candidates for parents collected so far, indexed by round.
+    pub parents_by_round: Vec<NodeCount>,
+    /// The smallest number of parents which might be enough to create a unit of a non-zero round.
+    /// Their weights decide, with uniform weights this is the number of parents required.
+    pub parents_needed: NodeCount,
+    /// The highest round for which a unit could be created right now.
+    pub highest_creatable_round: Round,
+    /// Whether we know of units of a round higher than `round`, i.e. other nodes are ahead of us.
+    pub is_behind: bool,
+}
+
+/// A snapshot of the units known to the creator, allowing it to resume creation after a restart
+/// without collecting all the parents again.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct CreatorState<H: Hasher> {
+    round: Round,
+    candidates_by_round: Vec<NodeMap<H::Hash>>,
+}
+
+impl<H: Hasher> CreatorState<H> {
+    /// The round of the unit the creator was about to create, all our units of lower rounds were
+    /// already created.
+    pub fn round(&self) -> Round {
+        self.round
+    }
+}
+
+pub(crate) struct Creator<H: Hasher> {
     node_id: NodeIndex,
     n_members: NodeCount,
+    node_weights: NodeWeights,
     candidates_by_round: Vec<NodeMap<H::Hash>>,
     n_candidates_by_round: Vec<NodeCount>, // len of this - 1 is the highest round number of all known units
+    parent_selector: Arc<dyn ParentSelector>,
 }
 
 impl<H: Hasher> Creator<H> {
-    pub(super) fn new(node_id: NodeIndex, n_members: NodeCount) -> Self {
+    pub(crate) fn new(
+        node_id: NodeIndex,
+        node_weights: NodeWeights,
+        parent_selector: Arc<dyn ParentSelector>,
+    ) -> Self {
+        let n_members = node_weights.n_members();
         Creator {
             node_id,
             n_members,
+            node_weights,
             candidates_by_round: vec![NodeMap::with_size(n_members)],
             n_candidates_by_round: vec![NodeCount(0)],
+            parent_selector,
+        }
+    }
+
+    /// Rehydrates a creator from a snapshot taken by [`Creator::state`].
+    pub(super) fn from_state(
+        node_id: NodeIndex,
+        node_weights: NodeWeights,
+        parent_selector: Arc<dyn ParentSelector>,
+        state: CreatorState<H>,
+    ) -> Self {
+        let mut creator = Creator::new(node_id, node_weights, parent_selector);
+        for (round, candidates) in state.candidates_by_round.into_iter().enumerate() {
+            for (pid, hash) in candidates.into_iter() {
+                creator.add_candidate(round as Round, pid, hash);
+            }
+        }
+        creator
+    }
+
+    /// Adds units known from elsewhere, e.g. recovered from the backup, as candidates for parents.
+    pub(super) fn add_known_units(&mut self, units: &[Unit<H>]) {
+        for unit in units {
+            self.add_unit(unit);
+        }
+    }
+
+    /// Takes a snapshot of the known units, given the round of the unit we are about to create.
+    pub(super) fn state(&self, round: Round) -> CreatorState<H> {
+        CreatorState {
+            round,
+            candidates_by_round: self.candidates_by_round.clone(),
         }
     }
 
@@ -29,7 +127,7 @@ impl<H: Hasher> Creator<H> {
     // initializes the vectors corresponding to the given round (and all between if not there)
     fn init_round(&mut self, round: Round) {
         if round > self.current_round() {
-            let new_size = (round + 1).into();
+            let new_size = usize::from(round) + 1;
             self.candidates_by_round
                 .resize(new_size, NodeMap::with_size(self.n_members));
             self.n_candidates_by_round.resize(new_size, NodeCount(0));
@@ -37,17 +135,27 @@ impl<H: Hasher> Creator<H> {
     }
 
     /// Returns `None` if a unit cannot be created.
-    /// To create a new unit, we need to have at least floor(2*N/3) + 1 parents available in previous round.
-    /// Additionally, our unit from previous round must be available.
-    pub(super) fn create_unit(&self, round: Round) -> Option<(PreUnit<H>, Vec<H::Hash>)> {
-        if !self.can_create(round) {
+    /// To create a new unit, we need parents of more than 2/3 of the total weight available in
+    /// previous round, with uniform weights at least floor(2*N/3) + 1 of them. Additionally, our
+    /// unit from previous round must be available.
+    pub(crate) fn create_unit(&self, round: Round) -> Option<(PreUnit<H>, Vec<H::Hash>)> {
+        self.create_unit_with_min_parents(round, NodeCount(0))
+    }
+
+    /// Like `create_unit`, but additionally requires at least `min_parents` parents available.
+    pub(super) fn create_unit_with_min_parents(
+        &self,
+        round: Round,
+        min_parents: NodeCount,
+    ) -> Option<(PreUnit<H>, Vec<H::Hash>)> {
+        if !self.can_create(round, min_parents) {
             return None;
         }
         let parents = {
             if round == 0 {
                 NodeMap::with_size(self.n_members)
             } else {
-                self.candidates_by_round[(round - 1) as usize].clone()
+                self.select_parents(round)
             }
         };
 
@@ -59,10 +167,55 @@ impl<H: Hasher> Creator<H> {
         Some((new_preunit, parent_hashes))
     }
 
-    pub(super) fn add_unit(&mut self, unit: &Unit<H>) {
-        let round = unit.round();
-        let pid = unit.creator();
-        let hash = unit.hash();
+    /// Creates a unit of the given round with exactly the given parents, instead of choosing them
+    /// among the candidates, e.g. to recreate a unit created before. The parents have to satisfy
+    /// the constraints of the protocol, which is checked except for them being units of the
+    /// previous round, as the creator does not need to know them.
+    pub(crate) fn create_unit_with_parents(
+        &self,
+        round: Round,
+        parents: NodeMap<H::Hash>,
+    ) -> Result<(PreUnit<H>, Vec<H::Hash>), ParentsError> {
+        let control_hash = ControlHash::new(&parents);
+        let new_preunit = PreUnit::new(self.node_id, round, control_hash);
+        new_preunit.check_parents(&self.node_weights)?;
+        let parent_hashes = parents.into_values().collect();
+        trace!(target: "AlephBFT-creator", "Created a new unit {:?} at round {:?} with the given parents.", new_preunit, round);
+        Ok((new_preunit, parent_hashes))
+    }
+
+    // Assumes that a unit of the given, non-zero round can be created.
+    fn select_parents(&self, round: Round) -> NodeMap<H::Hash> {
+        let candidates = &self.candidates_by_round[(round - 1) as usize];
+        // There is a single candidate of every creator, so this is the order of `unit_order`.
+        let candidate_ids: Vec<_> = candidates.iter().map(|(id, _)| id).collect();
+        let mut parents = NodeMap::with_size(self.n_members);
+        let mut n_parents = NodeCount(0);
+        for id in self.parent_selector.select(&candidate_ids, round) {
+            match (candidates.get(id), parents.get(id)) {
+                (Some(hash), None) => {
+                    parents.insert(id, *hash);
+                    n_parents += NodeCount(1);
+                }
+                (Some(_), Some(_)) => {}
+                (None, _) => {
+                    warn!(target: "AlephBFT-creator", "Parent selector chose {:?}, which is not a candidate for a parent at round {}. Using all the candidates.", id, round);
+                    return candidates.clone();
+                }
+            }
+        }
+        if parents.get(self.node_id).is_none() || !self.is_quorum(&parents) {
+            warn!(target: "AlephBFT-creator", "Parent selector chose {:?} parents, not including our own unit or too few for round {}. Using all the candidates.", n_parents, round);
+            return candidates.clone();
+        }
+        parents
+    }
+
+    pub(crate) fn add_unit(&mut self, unit: &Unit<H>) {
+        self.add_candidate(unit.round(), unit.creator(), unit.hash());
+    }
+
+    fn add_candidate(&mut self, round: Round, pid: NodeIndex, hash: H::Hash) {
         self.init_round(round);
         if self.candidates_by_round[round as usize].get(pid).is_none() {
             // passing the check above means that we do not have any unit for the pair (round, pid) yet
@@ -71,16 +224,80 @@ impl<H: Hasher> Creator<H> {
         }
     }
 
-    fn can_create(&self, round: Round) -> bool {
+    /// The number of units of the previous round that are available as parents for a unit of the given round.
+    pub(super) fn n_parents_available(&self, round: Round) -> NodeCount {
+        if round == 0 {
+            return NodeCount(0);
+        }
+        self.n_candidates_by_round
+            .get((round - 1) as usize)
+            .cloned()
+            .unwrap_or(NodeCount(0))
+    }
+
+    /// Whether the units of all the members of the previous round are available as parents for a
+    /// unit of the given round.
+    pub(super) fn has_all_parents(&self, round: Round) -> bool {
+        self.n_parents_available(round) == self.n_members
+    }
+
+    /// The context of the creation delay of our unit of the given round, with the members active
+    /// in the two rounds below it.
+    pub(super) fn creation_context(&self, round: Round) -> CreationContext {
+        let recent_candidates: Vec<_> = (1..=2)
+            .filter_map(|back| round.checked_sub(back))
+            .filter_map(|round| self.candidates_by_round.get(usize::from(round)))
+            .collect();
+        let active_members = self
+            .n_members
+            .all_indices()
+            .filter(|id| {
+                recent_candidates
+                    .iter()
+                    .any(|candidates| candidates.get(*id).is_some())
+            })
+            .count();
+        CreationContext {
+            round,
+            active_members: NodeCount(active_members),
+            n_members: self.n_members,
+        }
+    }
+
+    /// The smallest number of parents which might be enough for a unit of non-zero round.
+    pub(super) fn parents_needed(&self) -> NodeCount {
+        self.node_weights.min_quorum_size()
+    }
+
+    fn is_quorum(&self, parents: &NodeMap<H::Hash>) -> bool {
+        self.node_weights
+            .is_quorum(parents.iter().map(|(id, _)| id))
+    }
+
+    /// Reports the state of the creator while it is trying to create a unit of the given round.
+    pub(super) fn status(&self, round: Round) -> CreatorStatus {
+        let highest_creatable_round = (0..=self.current_round().saturating_add(1))
+            .rev()
+            .find(|round| self.can_create(*round, NodeCount(0)))
+            .unwrap_or(0);
+        CreatorStatus {
+            round,
+            parents_by_round: self.n_candidates_by_round.clone(),
+            parents_needed: self.parents_needed(),
+            highest_creatable_round,
+            is_behind: self.current_round() > round,
+        }
+    }
+
+    fn can_create(&self, round: Round, min_parents: NodeCount) -> bool {
         if round == 0 {
             return true;
         }
         let prev_round = (round - 1).into();
 
-        let threshold = (self.n_members * 2) / 3 + NodeCount(1);
-
         self.n_candidates_by_round.len() > prev_round
-            && self.n_candidates_by_round[prev_round] >= threshold
+            && self.is_quorum(&self.candidates_by_round[prev_round])
+            && self.n_candidates_by_round[prev_round] >= min_parents
             && self.candidates_by_round[prev_round]
                 .get(self.node_id)
                 .is_some()
@@ -89,17 +306,23 @@ impl<H: Hasher> Creator<H> {
 
 #[cfg(test)]
 mod tests {
-    use super::Creator as GenericCreator;
+    use super::{
+        AllAvailableParents, Creator as GenericCreator, CreatorState as GenericCreatorState,
+        CreatorStatus, ParentSelector, ParentsError,
+    };
     use crate::{
+        nodes::NodeMap,
         testing::mock::{Data, Hasher64},
         units::{
             FullUnit as GenericFullUnit, PreUnit as GenericPreUnit, Unit as GenericUnit, UnitCoord,
         },
-        Hasher, NodeCount, NodeIndex, Round,
+        Hasher, NodeCount, NodeIndex, NodeWeights, Round,
     };
-    use std::collections::HashSet;
+    use codec::{Decode, Encode};
+    use std::{collections::HashSet, sync::Arc};
 
     type Creator = GenericCreator<Hasher64>;
+    type CreatorState = GenericCreatorState<Hasher64>;
     type PreUnit = GenericPreUnit<Hasher64>;
     type Unit = GenericUnit<Hasher64>;
     type FullUnit = GenericFullUnit<Hasher64, Data>;
@@ -107,7 +330,11 @@ mod tests {
     fn creator_set(n_members: NodeCount) -> Vec<Creator> {
         let mut result = Vec::new();
         for i in 0..n_members.0 {
-            result.push(Creator::new(NodeIndex(i), n_members));
+            result.push(Creator::new(
+                NodeIndex(i),
+                NodeWeights::uniform(n_members),
+                Arc::new(AllAvailableParents),
+            ));
         }
         result
     }
@@ -147,7 +374,11 @@ mod tests {
     fn creates_initial_unit() {
         let n_members = NodeCount(7);
         let round = 0;
-        let creator = Creator::new(NodeIndex(0), n_members);
+        let creator = Creator::new(
+            NodeIndex(0),
+            NodeWeights::uniform(n_members),
+            Arc::new(AllAvailableParents),
+        );
         assert_eq!(creator.current_round(), round);
         let (preunit, parent_hashes) = creator
             .create_unit(round)
@@ -156,6 +387,26 @@ mod tests {
         assert_eq!(parent_hashes.len(), 0);
     }
 
+    #[test]
+    fn creation_context_counts_recently_active_members() {
+        let n_members = NodeCount(4);
+        let mut creators = creator_set(n_members);
+        let new_units: Vec<_> = create_units(creators.iter(), 0)
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let creator = &mut creators[0];
+        assert_eq!(creator.creation_context(1).active_members, NodeCount(0));
+        add_units(creator, &new_units[..3]);
+        let context = creator.creation_context(1);
+        assert_eq!(context.round, 1);
+        assert_eq!(context.active_members, NodeCount(3));
+        assert_eq!(context.n_members, n_members);
+        // Round 0 is still one of the two rounds below round 2, but not below round 3.
+        assert_eq!(creator.creation_context(2).active_members, NodeCount(3));
+        assert_eq!(creator.creation_context(3).active_members, NodeCount(0));
+    }
+
     #[test]
     fn creates_unit_with_all_parents() {
         let n_members = NodeCount(7);
@@ -305,4 +556,258 @@ mod tests {
         let round = 1;
         assert!(creator.create_unit(round).is_none());
     }
+
+    #[test]
+    fn reports_status() {
+        let n_members = NodeCount(4);
+        let mut creators = creator_set(n_members);
+        let new_units = create_units(creators.iter().take(2), 0);
+        let new_units: Vec<_> = new_units
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let creator = &mut creators[0];
+        add_units(creator, &new_units);
+        assert_eq!(
+            creator.status(1),
+            CreatorStatus {
+                round: 1,
+                parents_by_round: vec![NodeCount(2)],
+                parents_needed: NodeCount(3),
+                highest_creatable_round: 0,
+                is_behind: false,
+            }
+        );
+        let (preunit, _) = create_units(creators.iter().skip(2).take(1), 0)
+            .pop()
+            .expect("A unit should be created.");
+        let creator = &mut creators[0];
+        creator.add_unit(&preunit_to_unit(preunit));
+        let status = creator.status(1);
+        assert_eq!(status.parents_by_round, vec![NodeCount(3)]);
+        assert_eq!(status.highest_creatable_round, 1);
+        assert!(!status.is_behind);
+        let (preunit, _) = creator.create_unit(1).expect("Creation should succeed.");
+        creator.add_unit(&preunit_to_unit(preunit));
+        assert!(creator.status(0).is_behind);
+    }
+
+    // Chooses the given number of candidates with the lowest indices.
+    struct LowestIndices(usize);
+
+    impl ParentSelector for LowestIndices {
+        fn select(&self, candidates: &[NodeIndex], _round: Round) -> Vec<NodeIndex> {
+            let mut candidates = candidates.to_vec();
+            candidates.sort_by_key(|id| id.0);
+            candidates.truncate(self.0);
+            candidates
+        }
+    }
+
+    fn create_unit_with_selector(
+        node_id: NodeIndex,
+        selector: LowestIndices,
+    ) -> Vec<<Hasher64 as Hasher>::Hash> {
+        let n_members = NodeCount(7);
+        let new_units = create_units(creator_set(n_members).iter(), 0);
+        let new_units: Vec<_> = new_units
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let mut creator =
+            Creator::new(node_id, NodeWeights::uniform(n_members), Arc::new(selector));
+        add_units(&mut creator, &new_units);
+        let (_, parent_hashes) = creator.create_unit(1).expect("Creation should succeed.");
+        parent_hashes
+    }
+
+    #[test]
+    fn creates_unit_with_selected_parents() {
+        assert_eq!(
+            create_unit_with_selector(NodeIndex(0), LowestIndices(5)).len(),
+            5
+        );
+    }
+
+    // Chooses the first candidates, relying on their order.
+    struct FirstCandidates(usize);
+
+    impl ParentSelector for FirstCandidates {
+        fn select(&self, candidates: &[NodeIndex], _round: Round) -> Vec<NodeIndex> {
+            candidates.iter().take(self.0).copied().collect()
+        }
+    }
+
+    #[test]
+    fn candidates_do_not_depend_on_arrival_order() {
+        let n_members = NodeCount(7);
+        let new_units: Vec<_> = create_units(creator_set(n_members).iter(), 0)
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let mut reversed = new_units.clone();
+        reversed.reverse();
+        let parents: Vec<_> = [new_units, reversed]
+            .iter()
+            .map(|units| {
+                let mut creator = Creator::new(
+                    NodeIndex(0),
+                    NodeWeights::uniform(n_members),
+                    Arc::new(FirstCandidates(5)),
+                );
+                add_units(&mut creator, units);
+                let (_, parent_hashes) = creator.create_unit(1).expect("Creation should succeed.");
+                parent_hashes
+            })
+            .collect();
+        assert_eq!(parents[0].len(), 5);
+        assert_eq!(parents[0], parents[1]);
+    }
+
+    #[test]
+    fn rejects_selection_without_own_unit() {
+        assert_eq!(
+            create_unit_with_selector(NodeIndex(6), LowestIndices(5)).len(),
+            7
+        );
+    }
+
+    #[test]
+    fn rejects_selection_below_threshold() {
+        assert_eq!(
+            create_unit_with_selector(NodeIndex(0), LowestIndices(4)).len(),
+            7
+        );
+    }
+
+    #[test]
+    fn restores_from_state() {
+        let n_members = NodeCount(7);
+        let mut creators = creator_set(n_members);
+        let new_units = create_units(creators.iter().skip(1), 0);
+        let new_units: Vec<_> = new_units
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let creator = &mut creators[0];
+        add_units(creator, &new_units);
+        let (preunit, _) = creator.create_unit(0).expect("Creation should succeed.");
+        creator.add_unit(&preunit_to_unit(preunit));
+        let (_, expected_hashes) = creator.create_unit(1).expect("Creation should succeed.");
+
+        let state = creator.state(1);
+        let encoded = state.encode();
+        let decoded =
+            CreatorState::decode(&mut encoded.as_slice()).expect("should decode correctly");
+        assert_eq!(decoded, state);
+        assert_eq!(decoded.round(), 1);
+        let restored = Creator::from_state(
+            NodeIndex(0),
+            NodeWeights::uniform(n_members),
+            Arc::new(AllAvailableParents),
+            decoded,
+        );
+        assert_eq!(restored.current_round(), creator.current_round());
+        assert_eq!(restored.status(1), creator.status(1));
+        let (_, parent_hashes) = restored.create_unit(1).expect("Creation should succeed.");
+        assert_eq!(parent_hashes, expected_hashes);
+    }
+
+    #[test]
+    fn recreates_unit_with_given_parents() {
+        let n_members = NodeCount(4);
+        let mut creators = creator_set(n_members);
+        let new_units: Vec<_> = create_units(creators.iter(), 0)
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let creator = &mut creators[0];
+        add_units(creator, &new_units[..3]);
+        let (preunit, parent_hashes) = creator.create_unit(1).expect("Creation should succeed.");
+
+        // Another creator does not need to know the parents.
+        let other = Creator::new(
+            NodeIndex(0),
+            NodeWeights::uniform(n_members),
+            Arc::new(AllAvailableParents),
+        );
+        let mut parents = NodeMap::with_size(n_members);
+        for unit in &new_units[..3] {
+            parents.insert(unit.creator(), unit.hash());
+        }
+        assert_eq!(
+            other.create_unit_with_parents(1, parents.clone()),
+            Ok((preunit, parent_hashes))
+        );
+        assert_eq!(
+            other.create_unit_with_parents(0, parents),
+            Err(ParentsError::ParentsOfInitialUnit)
+        );
+
+        let mut without_own = NodeMap::with_size(n_members);
+        let mut too_few = NodeMap::with_size(n_members);
+        for unit in &new_units[1..] {
+            without_own.insert(unit.creator(), unit.hash());
+        }
+        for unit in &new_units[..2] {
+            too_few.insert(unit.creator(), unit.hash());
+        }
+        assert_eq!(
+            other.create_unit_with_parents(1, without_own),
+            Err(ParentsError::MissingOwnParent)
+        );
+        assert_eq!(
+            other.create_unit_with_parents(1, too_few),
+            Err(ParentsError::NotQuorum)
+        );
+        assert_eq!(
+            other.create_unit_with_parents(1, NodeMap::with_size(NodeCount(5))),
+            Err(ParentsError::WrongCommitteeSize(NodeCount(5)))
+        );
+        assert!(other
+            .create_unit_with_parents(0, NodeMap::with_size(n_members))
+            .is_ok());
+    }
+
+    #[test]
+    fn waits_for_min_parents() {
+        let n_members = NodeCount(7);
+        let mut creators = creator_set(n_members);
+        let new_units = create_units(creators.iter(), 0);
+        let new_units: Vec<_> = new_units
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        let creator = &mut creators[0];
+        add_units(creator, &new_units[..6]);
+        assert!(creator.create_unit_with_min_parents(1, n_members).is_none());
+        assert!(creator.create_unit(1).is_some());
+        creator.add_unit(&new_units[6]);
+        let (_, parent_hashes) = creator
+            .create_unit_with_min_parents(1, n_members)
+            .expect("Creation should succeed.");
+        assert_eq!(parent_hashes.len(), 7);
+    }
+
+    #[test]
+    fn needs_parents_of_enough_weight() {
+        let weights = NodeWeights::new(vec![5, 1, 1, 1]);
+        let creators: Vec<_> = (0..4)
+            .map(|i| Creator::new(NodeIndex(i), weights.clone(), Arc::new(AllAvailableParents)))
+            .collect();
+        let new_units: Vec<_> = create_units(creators.iter(), 0)
+            .into_iter()
+            .map(|(pu, _)| preunit_to_unit(pu))
+            .collect();
+        // Three light members are not enough, even though they are the usual threshold.
+        let mut light = Creator::new(NodeIndex(1), weights.clone(), Arc::new(AllAvailableParents));
+        add_units(&mut light, &new_units[1..]);
+        assert!(light.create_unit(1).is_none());
+        // The heavy member and any other one are enough.
+        let mut heavy = Creator::new(NodeIndex(1), weights, Arc::new(AllAvailableParents));
+        add_units(&mut heavy, &new_units[..2]);
+        let (_, parent_hashes) = heavy.create_unit(1).expect("Creation should succeed.");
+        assert_eq!(parent_hashes.len(), 2);
+        assert_eq!(heavy.status(1).parents_needed, NodeCount(2));
+    }
 }
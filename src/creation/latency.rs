@@ -0,0 +1,169 @@
+use crate::{Round, Sender};
+use log::debug;
+use std::time::Duration;
+
+/// Counts of durations falling into buckets with the given upper bounds, and one more bucket for
+/// the durations above all of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    bounds: Vec<Duration>,
+    counts: Vec<u64>,
+    sum: Duration,
+}
+
+impl LatencyHistogram {
+    /// An empty histogram, the bounds have to be sorted.
+    pub fn new(bounds: Vec<Duration>) -> Self {
+        let counts = vec![0; bounds.len() + 1];
+        LatencyHistogram {
+            bounds,
+            counts,
+            sum: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn record(&mut self, duration: Duration) {
+        let bucket = self.bounds.partition_point(|bound| *bound < duration);
+        self.counts[bucket] += 1;
+        self.sum = self.sum.saturating_add(duration);
+    }
+
+    /// The upper bounds of the buckets with the number of durations in each of them, a duration
+    /// falls into the first bucket it does not exceed. The last bucket has no bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .copied()
+            .map(Some)
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+
+    /// The number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The sum of the recorded durations.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+}
+
+/// What we were waiting for before we could create a unit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Wait {
+    /// The unit could be created as soon as the creation delay passed.
+    Delay,
+    /// The creation delay passed, but there were not enough parents yet.
+    Parents,
+}
+
+/// The latencies of creating our units in a session, see
+/// [`Config::creation_latency_sink`](crate::Config::creation_latency_sink).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreationLatencies {
+    /// The round of the last unit we created.
+    pub round: Round,
+    /// How long we waited in the rounds in which the unit could be created as soon as the
+    /// creation delay passed.
+    pub delay_bound: LatencyHistogram,
+    /// How long we waited in the rounds in which we still had to wait for parents after the
+    /// creation delay passed.
+    pub parent_bound: LatencyHistogram,
+    /// How long creating the units took once we started creating them.
+    pub creation: LatencyHistogram,
+}
+
+impl CreationLatencies {
+    fn new(bounds: Vec<Duration>) -> Self {
+        CreationLatencies {
+            round: 0,
+            delay_bound: LatencyHistogram::new(bounds.clone()),
+            parent_bound: LatencyHistogram::new(bounds.clone()),
+            creation: LatencyHistogram::new(bounds),
+        }
+    }
+}
+
+/// Collects the latencies and pushes them to the sink after every created unit.
+pub(crate) struct LatencyRecorder {
+    sink: Sender<CreationLatencies>,
+    latencies: CreationLatencies,
+}
+
+impl LatencyRecorder {
+    pub(crate) fn new(sink: Sender<CreationLatencies>, bounds: Vec<Duration>) -> Self {
+        LatencyRecorder {
+            sink,
+            latencies: CreationLatencies::new(bounds),
+        }
+    }
+
+    /// Records the latencies of creating our unit of the given round, returns false if nobody
+    /// listens anymore.
+    pub(crate) fn record(
+        &mut self,
+        round: Round,
+        wait: Wait,
+        waited: Duration,
+        creation: Duration,
+    ) -> bool {
+        let latencies = &mut self.latencies;
+        latencies.round = round;
+        match wait {
+            Wait::Delay => latencies.delay_bound.record(waited),
+            Wait::Parents => latencies.parent_bound.record(waited),
+        }
+        latencies.creation.record(creation);
+        if self.sink.unbounded_send(latencies.clone()).is_err() {
+            debug!(target: "AlephBFT-creator", "Latency channel closed, no longer recording latencies.");
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LatencyHistogram, LatencyRecorder, Wait};
+    use futures::channel::mpsc;
+    use std::time::Duration;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn counts_durations_in_buckets() {
+        let mut histogram = LatencyHistogram::new(vec![ms(10), ms(100)]);
+        for duration in [ms(0), ms(10), ms(11), ms(100), ms(1000), ms(5000)] {
+            histogram.record(duration);
+        }
+        let buckets: Vec<_> = histogram.buckets().collect();
+        assert_eq!(
+            buckets,
+            vec![(Some(ms(10)), 2), (Some(ms(100)), 2), (None, 2)]
+        );
+        assert_eq!(histogram.count(), 6);
+        assert_eq!(histogram.sum(), ms(6121));
+    }
+
+    #[test]
+    fn records_rounds_by_what_they_waited_for() {
+        let (sink, mut reports) = mpsc::unbounded();
+        let mut recorder = LatencyRecorder::new(sink, vec![ms(50)]);
+        assert!(recorder.record(0, Wait::Delay, ms(40), ms(1)));
+        assert!(recorder.record(1, Wait::Parents, ms(70), ms(2)));
+        reports.try_recv().expect("the first round was recorded");
+        let latencies = reports.try_recv().expect("the second round was recorded");
+        assert_eq!(latencies.round, 1);
+        assert_eq!(latencies.delay_bound.count(), 1);
+        assert_eq!(latencies.delay_bound.sum(), ms(40));
+        assert_eq!(latencies.parent_bound.count(), 1);
+        assert_eq!(latencies.parent_bound.sum(), ms(70));
+        assert_eq!(latencies.creation.sum(), ms(3));
+        drop(reports);
+        assert!(!recorder.record(2, Wait::Delay, ms(40), ms(1)));
+    }
+}
@@ -1,76 +1,445 @@
 use crate::{
-    config::{Config as GeneralConfig, DelaySchedule},
-    nodes::{NodeCount, NodeIndex},
+    clock::{time_between, Clock},
+    config::{
+        Config as GeneralConfig, ContextDelaySchedule, CreationContext, DelaySchedule,
+        MAX_CREATION_BACKPRESSURE_FACTOR, MAX_MAX_ROUND,
+    },
+    nodes::{NodeCount, NodeIndex, NodeWeights},
     runway::NotificationOut,
+    terminator::Terminator,
     units::{PreUnit, Unit},
     Hasher, Receiver, Round, Sender,
 };
-use futures::{channel::oneshot, FutureExt, StreamExt};
-use futures_timer::Delay;
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, Fuse, FusedFuture},
+    FutureExt, StreamExt,
+};
 use log::{debug, error, info, warn};
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 mod creator;
+mod latency;
+
+pub(crate) use creator::Creator;
+pub use creator::{AllAvailableParents, CreatorState, CreatorStatus, ParentSelector};
+pub(crate) use latency::LatencyRecorder;
+use latency::Wait;
+pub use latency::{CreationLatencies, LatencyHistogram};
+
+/// The shortest delay between creating units, so that a schedule returning zero, usually by
+/// mistake, cannot make the creator spin.
+const MIN_CREATION_DELAY: Duration = Duration::from_millis(1);
 
-use creator::Creator;
+/// The schedule of the unit creation delay, depending either only on the round, or also on the
+/// members active around it.
+#[derive(Clone)]
+pub(crate) enum CreationDelay {
+    RoundOnly(DelaySchedule),
+    ContextAware(ContextDelaySchedule),
+}
+
+impl CreationDelay {
+    fn delay(&self, context: CreationContext) -> Duration {
+        match self {
+            CreationDelay::RoundOnly(schedule) => schedule(context.round.into()),
+            CreationDelay::ContextAware(schedule) => schedule(context),
+        }
+    }
+}
 
 /// The configuration needed for the process creating new units.
 pub struct Config {
     node_id: NodeIndex,
-    n_members: NodeCount,
-    create_lag: DelaySchedule,
+    node_weights: NodeWeights,
+    create_lag: CreationDelay,
+    stall_warning_delay: Duration,
+    backpressure_threshold: Option<Round>,
+    backpressure_factor: f64,
     max_round: Round,
+    max_round_warning_margin: Round,
+    max_rounds_ahead: Option<Round>,
+    parents_backlog_high_water: usize,
+    parent_selector: Arc<dyn ParentSelector>,
+    min_parents: Option<NodeCount>,
+    parents_grace_period: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl Config {
+    /// The delay after which a unit can be created in the given context. It is scaled by the
+    /// backpressure factor if we are too many rounds ahead of the last finalized round, or if
+    /// too many units are stored. It is never shorter than [`MIN_CREATION_DELAY`].
+    fn creation_delay(
+        &self,
+        context: CreationContext,
+        finalized_round: Round,
+        overloaded: bool,
+    ) -> Duration {
+        let round = context.round;
+        let delay = self.create_lag.delay(context).max(MIN_CREATION_DELAY);
+        match self.backpressure_threshold {
+            Some(threshold) if round.saturating_sub(finalized_round) > threshold => {
+                delay.mul_f64(self.backpressure_factor)
+            }
+            _ if overloaded => delay.mul_f64(self.backpressure_factor),
+            _ => delay,
+        }
+    }
+}
+
+// Brings the factor into the allowed range, as the config might not come from the builder, and
+// scaling the delay by a negative, infinite or enormous factor panics.
+fn backpressure_factor(factor: f64) -> f64 {
+    if factor.is_nan() {
+        return 1.0;
+    }
+    factor.clamp(1.0, MAX_CREATION_BACKPRESSURE_FACTOR)
 }
 
 impl From<GeneralConfig> for Config {
     fn from(conf: GeneralConfig) -> Self {
         Config {
             node_id: conf.node_ix,
-            n_members: conf.n_members,
-            create_lag: conf.delay_config.unit_creation_delay,
+            node_weights: conf.node_weights,
+            create_lag: match conf.delay_config.context_creation_delay {
+                Some(schedule) => CreationDelay::ContextAware(schedule),
+                None => CreationDelay::RoundOnly(conf.delay_config.unit_creation_delay),
+            },
+            stall_warning_delay: conf.delay_config.stall_warning_delay,
+            backpressure_threshold: conf.delay_config.creation_backpressure_threshold,
+            backpressure_factor: backpressure_factor(
+                conf.delay_config.creation_backpressure_factor,
+            ),
             max_round: conf.max_round,
+            max_round_warning_margin: conf.max_round_warning_margin,
+            max_rounds_ahead: conf.max_rounds_ahead,
+            parents_backlog_high_water: conf.parents_backlog_high_water,
+            parent_selector: conf.parent_selector,
+            min_parents: conf.min_parents,
+            parents_grace_period: conf.delay_config.parents_grace_period,
+            clock: conf.clock,
         }
     }
 }
 
+/// Commands controlling the process creating new units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreatorCommand {
+    /// Stop creating new units, while still collecting the incoming parents.
+    Pause,
+    /// Resume creating units from the round we stopped at.
+    Resume,
+    /// Raise the maximum round of units to the given value, lower values are ignored.
+    ExtendMaxRound(Round),
+}
+
 pub struct IO<H: Hasher> {
     pub(crate) incoming_parents: Receiver<Unit<H>>,
-    pub(crate) outgoing_units: Sender<NotificationOut<H>>,
+    /// Bounded, so that we wait with creating more units until the created ones are processed.
+    pub(crate) outgoing_units: mpsc::Sender<NotificationOut<H>>,
+    pub(crate) finalized_rounds: Receiver<Round>,
+    /// If present, the status of the creator is pushed here whenever it changes.
+    pub(crate) metrics: Option<Sender<CreatorStatus>>,
+    /// If present, the latencies of creating units are recorded and pushed here after every unit.
+    pub(crate) latencies: Option<LatencyRecorder>,
+    pub(crate) control: Receiver<CreatorCommand>,
+    /// Replacements of the unit creation delay schedule, each applies from the next round on.
+    /// Either kind of schedule can replace either kind.
+    pub(crate) delay_schedules: Receiver<CreationDelay>,
+    /// If present, a snapshot of the creator is sent here when it exits upon the exit signal.
+    pub(crate) state_sink: Option<oneshot::Sender<CreatorState<H>>>,
+    /// If present, counts the units sent via `incoming_parents` which were not received yet. The
+    /// senders have to increment it before sending a unit.
+    pub(crate) parents_backlog: Option<Arc<AtomicUsize>>,
+    /// If present, set whenever more units are stored than the configured bound allows.
+    pub(crate) store_overloaded: Option<Arc<AtomicBool>>,
+    /// If present, the units recovered from the backup are sent here before the starting round,
+    /// so that they can be parents right away instead of after they are added to the Dag again.
+    pub(crate) recovered_units: Option<oneshot::Receiver<Vec<Unit<H>>>>,
+}
+
+impl<H: Hasher> IO<H> {
+    // Returns the number of units still waiting to be received, after receiving one of them.
+    fn on_parent_received(&self) -> Option<usize> {
+        self.parents_backlog
+            .as_ref()
+            .map(|backlog| backlog.fetch_sub(1, Ordering::Relaxed).saturating_sub(1))
+    }
+
+    fn store_overloaded(&self) -> bool {
+        self.store_overloaded
+            .as_ref()
+            .is_some_and(|overloaded| overloaded.load(Ordering::Relaxed))
+    }
+
+    fn report_status(&mut self, creator: &Creator<H>, round: Round) {
+        if let Some(metrics) = &self.metrics {
+            if metrics.unbounded_send(creator.status(round)).is_err() {
+                debug!(target: "AlephBFT-creator", "Metrics channel closed, no longer reporting status.");
+                self.metrics = None;
+            }
+        }
+    }
+
+    // Waits until there is room for the notification, but not after the exit signal, as nobody
+    // might be processing the notifications anymore.
+    async fn notify(
+        &mut self,
+        notification: NotificationOut<H>,
+        exit: &mut oneshot::Receiver<()>,
+    ) -> Result<(), Interrupted> {
+        // The notification is only sent once there is room, so that it is not sent when we exit
+        // while waiting.
+        let ready = futures::select! {
+            ready = future::poll_fn(|cx| self.outgoing_units.poll_ready(cx)).fuse() => ready,
+            _ = &mut *exit => {
+                info!(target: "AlephBFT-creator", "Received exit signal.");
+                return Err(Interrupted::Exit);
+            },
+        };
+        ready
+            .and_then(|()| self.outgoing_units.start_send(notification))
+            .map_err(|e| {
+                warn!(target: "AlephBFT-creator", "Notification send error: {}. Exiting.", e);
+                Interrupted::ChannelClosed
+            })
+    }
+
+    fn send_state(&mut self, creator: &Creator<H>, round: Round) {
+        if let Some(state_sink) = self.state_sink.take() {
+            if state_sink.send(creator.state(round)).is_err() {
+                debug!(target: "AlephBFT-creator", "Creator state receiver dropped.");
+            }
+        }
+    }
+
+    fn record_latency(&mut self, round: Round, wait: Wait, waited: Duration, creation: Duration) {
+        if let Some(latencies) = &mut self.latencies {
+            if !latencies.record(round, wait, waited, creation) {
+                self.latencies = None;
+            }
+        }
+    }
+}
+
+/// Reasons for which we stopped waiting for a unit to be created.
+enum Interrupted {
+    Exit,
+    ChannelClosed,
+}
+
+// Whether we should stop, either because of the exit signal or because the parent is gone.
+fn exit_signaled(exit: &mut oneshot::Receiver<()>) -> bool {
+    !matches!(exit.try_recv(), Ok(None))
+}
+
+/// The part of the state of the creation process that is driven by the other components.
+struct ExternalState {
+    finalized_round: Round,
+    paused: bool,
+    max_round: Round,
+    max_rounds_ahead: Option<Round>,
+    lagging: bool,
+    pending_schedule: Option<CreationDelay>,
+    warned_short_delay: bool,
+}
+
+impl ExternalState {
+    fn new(max_round: Round, max_rounds_ahead: Option<Round>) -> Self {
+        ExternalState {
+            finalized_round: 0,
+            paused: false,
+            max_round,
+            max_rounds_ahead,
+            lagging: false,
+            pending_schedule: None,
+            warned_short_delay: false,
+        }
+    }
+
+    fn blocked(&self, round: Round) -> bool {
+        self.paused || round >= self.max_round || self.too_far_ahead(round)
+    }
+
+    fn too_far_ahead(&self, round: Round) -> bool {
+        self.max_rounds_ahead
+            .is_some_and(|max| round.saturating_sub(self.finalized_round) > max)
+    }
+
+    fn on_command(&mut self, command: CreatorCommand) {
+        match command {
+            CreatorCommand::Pause if !self.paused => {
+                info!(target: "AlephBFT-creator", "Pausing unit creation.");
+                self.paused = true;
+            }
+            CreatorCommand::Resume if self.paused => {
+                info!(target: "AlephBFT-creator", "Resuming unit creation.");
+                self.paused = false;
+            }
+            CreatorCommand::ExtendMaxRound(max_round) if max_round > self.max_round => {
+                if max_round > MAX_MAX_ROUND {
+                    warn!(target: "AlephBFT-creator", "Cannot extend the maximum round to {}, the limit is {}.", max_round, MAX_MAX_ROUND);
+                }
+                let max_round = max_round.min(MAX_MAX_ROUND);
+                info!(target: "AlephBFT-creator", "Extending the maximum round from {} to {}.", self.max_round, max_round);
+                self.max_round = max_round;
+            }
+            _ => {}
+        }
+    }
 }
 
 async fn create_unit<H: Hasher>(
     round: Round,
     creator: &mut Creator<H>,
-    create_lag: &DelaySchedule,
+    conf: &Config,
+    state: &mut ExternalState,
     mut can_create: bool,
-    incoming_parents: &mut Receiver<Unit<H>>,
+    io: &mut IO<H>,
     mut exit: &mut oneshot::Receiver<()>,
-) -> Result<(PreUnit<H>, Vec<H::Hash>), ()> {
-    let mut delay = Delay::new(create_lag(round.into())).fuse();
+) -> Result<(PreUnit<H>, Vec<H::Hash>), Interrupted> {
+    let stall_warning_delay = conf.stall_warning_delay;
+    let context = creator.creation_context(round);
+    if !state.warned_short_delay && conf.create_lag.delay(context) < MIN_CREATION_DELAY {
+        warn!(target: "AlephBFT-creator", "The unit creation delay of round {} is below {:?}, using {:?} instead. The delay schedule is likely misconfigured.", round, MIN_CREATION_DELAY, MIN_CREATION_DELAY);
+        state.warned_short_delay = true;
+    }
+    let mut delay = conf
+        .clock
+        .sleep(conf.creation_delay(context, state.finalized_round, io.store_overloaded()))
+        .fuse();
+    // We only wait for the additional parents if we are not catching up, and at most for another
+    // creation delay, after which the protocol minimum suffices.
+    let mut min_parents = if can_create { None } else { conf.min_parents };
+    let mut min_parents_timeout = Fuse::terminated();
+    // Similarly, once the unit can be created, we wait up to the grace period for the parents of
+    // the remaining members, started at most once.
+    let mut grace_period = if can_create || conf.parents_grace_period.is_zero() {
+        None
+    } else {
+        Some(conf.parents_grace_period)
+    };
+    let mut grace_timeout = Fuse::terminated();
+    // The time is only measured if someone listens for the latencies.
+    let started = io.latencies.as_ref().map(|_| conf.clock.now());
+    // Whether we already tried to create the unit, but there were not enough parents.
+    let mut waiting_for_parents = false;
+    let mut reported_too_far_ahead = false;
     loop {
-        if can_create {
-            if let Some(result) = creator.create_unit(round) {
+        io.report_status(creator, round);
+        if state.too_far_ahead(round) && !reported_too_far_ahead {
+            debug!(target: "AlephBFT-creator", "Round {} is too far ahead of the finalized round {}, waiting for finalization to catch up.", round, state.finalized_round);
+            reported_too_far_ahead = true;
+        }
+        if can_create && !state.blocked(round) {
+            let creating = started.map(|_| conf.clock.now());
+            let result = match min_parents {
+                Some(min_parents) => creator.create_unit_with_min_parents(round, min_parents),
+                None => creator.create_unit(round),
+            };
+            let all_parents = creator.has_all_parents(round);
+            if result.is_some() && !all_parents {
+                if let Some(grace_period) = grace_period.take() {
+                    debug!(target: "AlephBFT-creator", "Waiting up to {:?} for the remaining parents of a unit of round {}.", grace_period, round);
+                    grace_timeout = conf.clock.sleep(grace_period).fuse();
+                }
+            }
+            let in_grace_period = !grace_timeout.is_terminated() && !all_parents;
+            if let Some(result) = result.filter(|_| !in_grace_period) {
+                if let (Some(started), Some(creating)) = (started, creating) {
+                    let wait = if waiting_for_parents {
+                        Wait::Parents
+                    } else {
+                        Wait::Delay
+                    };
+                    let waited = time_between(started, creating);
+                    let creation = time_between(creating, conf.clock.now());
+                    io.record_latency(round, wait, waited, creation);
+                }
                 return Ok(result);
             }
+            waiting_for_parents = true;
         }
         futures::select! {
-            unit = incoming_parents.next() => match unit {
-                Some(unit) => creator.add_unit(&unit),
+            unit = io.incoming_parents.next() => match unit {
+                Some(unit) => {
+                    creator.add_unit(&unit);
+                    if !can_create && creator.current_round() > round {
+                        // Others are ahead of us, so there is no point in waiting for the delay.
+                        debug!(target: "AlephBFT-creator", "Fell behind to round {} while waiting to create a unit of round {}, catching up.", creator.current_round(), round);
+                        can_create = true;
+                        min_parents = None;
+                        grace_period = None;
+                        grace_timeout = Fuse::terminated();
+                        delay = conf.clock.sleep(stall_warning_delay).fuse();
+                    }
+                    if let Some(backlog) = io.on_parent_received() {
+                        if backlog > conf.parents_backlog_high_water && !state.lagging {
+                            debug!(target: "AlephBFT-creator", "{} units are waiting to be received.", backlog);
+                            state.lagging = true;
+                            let notification = NotificationOut::CreatorLagging { backlog };
+                            io.notify(notification, exit).await?;
+                        } else if backlog <= conf.parents_backlog_high_water / 2 {
+                            // Notify again only after catching up significantly.
+                            state.lagging = false;
+                        }
+                    }
+                }
+                // The channel also closes when the session stops, so it is only unexpected if
+                // the exit signal did not come as well.
+                None if exit_signaled(exit) => {
+                    info!(target: "AlephBFT-creator", "Received exit signal.");
+                    return Err(Interrupted::Exit);
+                }
                 None => {
-                    info!(target: "AlephBFT-creator", "Incoming parent channel closed, exiting.");
-                    return Err(());
+                    error!(target: "AlephBFT-creator", "Incoming parent channel closed unexpectedly, no units can be created anymore. Exiting.");
+                    return Err(Interrupted::ChannelClosed);
                 }
             },
+            finalized = io.finalized_rounds.next() => if let Some(finalized) = finalized {
+                state.finalized_round = finalized;
+            },
+            command = io.control.next() => if let Some(command) = command {
+                state.on_command(command);
+            },
+            schedule = io.delay_schedules.next() => if let Some(schedule) = schedule {
+                // The delay of the current round is already running, so we keep it.
+                debug!(target: "AlephBFT-creator", "Received a new creation delay schedule, applying it from round {}.", round + 1);
+                state.pending_schedule = Some(schedule);
+            },
             _ = &mut delay => {
-                if can_create {
-                    warn!(target: "AlephBFT-creator", "Delay passed despite us not waiting for it.");
+                if !can_create && min_parents.is_some() {
+                    min_parents_timeout = conf.clock.sleep(conf.creation_delay(context, state.finalized_round, io.store_overloaded())).fuse();
+                }
+                if can_create && !state.blocked(round) && grace_timeout.is_terminated() {
+                    debug!(target: "AlephBFT-creator", "More than {:?} passed since we were allowed to create a unit of round {}, but we still cannot create it.", stall_warning_delay, round);
+                    let notification = NotificationOut::CreatorStalled {
+                        round,
+                        parents_collected: creator.n_parents_available(round),
+                        parents_needed: creator.parents_needed(),
+                    };
+                    io.notify(notification, exit).await?;
                 }
                 can_create = true;
-                delay = Delay::new(Duration::from_secs(30 * 60)).fuse();
+                delay = conf.clock.sleep(stall_warning_delay).fuse();
+            },
+            _ = grace_timeout => {
+                debug!(target: "AlephBFT-creator", "Stopped waiting for the remaining parents of a unit of round {}.", round);
+            },
+            _ = min_parents_timeout => {
+                debug!(target: "AlephBFT-creator", "Stopped waiting for {:?} parents of a unit of round {}.", min_parents, round);
+                min_parents = None;
             },
             _ = exit => {
                 info!(target: "AlephBFT-creator", "Received exit signal.");
-                return Err(());
+                return Err(Interrupted::Exit);
             },
         }
     }
@@ -84,62 +453,192 @@ async fn create_unit<H: Hasher>(
 /// - one of U's parents is the (r-1)-round unit by U's creator,
 /// - U has > floor(2*N/3) parents.
 /// - U will appear in the channel only if all U's parents appeared there before
+///
 /// The currently implemented strategy creates the unit U according to a delay schedule and when enough
-/// candidates for parents are available for all the above constraints to be satisfied.
+/// candidates for parents are available for all the above constraints to be satisfied. The schedule
+/// depends on U's round, or, if `context_creation_delay` is configured, also on the members with
+/// units of the two rounds below U's known when the creator starts waiting for U. If `min_parents`
+/// is configured, the creator waits up to one more delay for that many parents to appear. Once U can
+/// be created, the creator additionally waits up to `parents_grace_period` for the parents of the
+/// members still missing, unless all of them arrive sooner. Optionally, the
+/// delay is scaled up when U's round is too far ahead of the last round received via the
+/// `finalized_rounds` channel. Creation can be paused and resumed via the `control` channel, the
+/// incoming parents are collected in the meantime. The same happens after reaching the maximum round,
+/// until it is extended via the `control` channel, and, if `max_rounds_ahead` is configured, when U's
+/// round is more than that many rounds above the last finalized round, until finalization catches
+/// up. The delay schedule, round-only or context-aware, can be replaced via the `delay_schedules`
+/// channel, the new one is used starting with the next round. While the channel for the outgoing
+/// units is full, the creator waits, so it does not get ahead of the processing of its units.
+///
+/// Once units of a round higher than U's round are known, also ones arriving while waiting for
+/// the delay, the creator is behind. It then creates its units up to the highest known round as
+/// fast as their parents allow, ignoring the delay schedule, `min_parents` and the grace period,
+/// and resumes the schedule afterwards. The burst is still limited by the channel for the
+/// outgoing units.
+///
+/// Upon reaching the maximum round, the creator reports it, and then waits for it to be extended.
+/// If the starting round is not below the maximum round, the creator reports it and exits right away.
+/// Once the maximum round reaches [`MAX_MAX_ROUND`], it cannot be extended anymore, so the creator
+/// reports reaching it and exits.
+/// It also exits if the exit signal comes, or the sender of the starting round is dropped, before
+/// the starting round is known. If the `incoming_parents` channel closes, the creator exits as
+/// well, as it cannot collect parents anymore. This is treated as a shutdown only when the exit
+/// signal came too, otherwise it is reported as an error, and no snapshot is sent to `state_sink`.
+///
+/// The creator can be restored from a snapshot sent to `state_sink` by a previous instance that
+/// exited upon the exit signal. Alternatively, the units recovered from the backup can be sent
+/// via `recovered_units`, before the starting round, so that creation resumes without waiting for
+/// all of them to come through `incoming_parents` again.
 ///
 /// We refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/internals.html
 /// Section 5.1 for a discussion of this component.
-pub async fn run<H: Hasher>(
+pub(crate) async fn run<H: Hasher>(
     conf: Config,
     io: IO<H>,
     starting_round: oneshot::Receiver<Round>,
-    mut exit: oneshot::Receiver<()>,
+    initial_state: Option<CreatorState<H>>,
+    mut terminator: Terminator,
 ) {
-    let Config {
-        node_id,
-        n_members,
-        create_lag,
-        max_round,
-    } = conf;
-    let mut creator = Creator::new(node_id, n_members);
-    let IO {
-        mut incoming_parents,
-        outgoing_units,
-    } = io;
-    let starting_round = match starting_round.await {
-        Ok(round) => round,
-        Err(e) => {
-            error!(target: "AlephBFT-creator", "Starting round not provided: {}", e);
-            return;
+    create_units(
+        conf,
+        io,
+        starting_round,
+        initial_state,
+        terminator.get_exit(),
+    )
+    .await;
+    terminator.terminate_sync().await;
+}
+
+async fn create_units<H: Hasher>(
+    mut conf: Config,
+    mut io: IO<H>,
+    starting_round: oneshot::Receiver<Round>,
+    initial_state: Option<CreatorState<H>>,
+    exit: &mut oneshot::Receiver<()>,
+) {
+    let parent_selector = conf.parent_selector.clone();
+    // All our units below the round of the snapshot were already created, so we must not create
+    // them again.
+    let (mut creator, restored_round) = match initial_state {
+        Some(initial_state) => {
+            let round = initial_state.round();
+            let creator = Creator::from_state(
+                conf.node_id,
+                conf.node_weights.clone(),
+                parent_selector,
+                initial_state,
+            );
+            (creator, round)
         }
+        None => (
+            Creator::new(conf.node_id, conf.node_weights.clone(), parent_selector),
+            0,
+        ),
+    };
+    let mut state = ExternalState::new(conf.max_round, conf.max_rounds_ahead);
+    // The runway only drops the sender when it stops before resolving the starting round, so
+    // there is nobody to wait for, and exiting makes the whole session stop.
+    let starting_round = futures::select! {
+        round = starting_round.fuse() => match round {
+            Ok(round) => round.max(restored_round),
+            Err(e) => {
+                error!(target: "AlephBFT-creator", "Starting round not provided: {}. Exiting.", e);
+                return;
+            }
+        },
+        _ = &mut *exit => {
+            info!(target: "AlephBFT-creator", "Received exit signal before the starting round.");
+            io.send_state(&creator, restored_round);
+            return;
+        },
     };
+    if let Some(recovered_units) = io.recovered_units.take() {
+        // The units are sent before the starting round, so they are already here, if at all.
+        if let Some(Ok(units)) = recovered_units.now_or_never() {
+            debug!(target: "AlephBFT-creator", "Adding {} units recovered from the backup.", units.len());
+            creator.add_known_units(&units);
+        }
+    }
+    if starting_round >= state.max_round {
+        error!(target: "AlephBFT-creator", "Starting round {} is not below the maximum round {}, no units can be created. The maximum round is likely misconfigured.", starting_round, state.max_round);
+        let notification = NotificationOut::StartingRoundTooHigh {
+            starting_round,
+            max_round: state.max_round,
+        };
+        let _ = io.notify(notification, exit).await;
+        return;
+    }
     debug!(target: "AlephBFT-creator", "Creator starting from round {}", starting_round);
-    for round in starting_round..max_round {
+    let mut warned_ceiling = None;
+    let mut reached_ceiling = None;
+    for round in starting_round.. {
+        if round >= MAX_MAX_ROUND {
+            error!(target: "AlephBFT-creator", "Reached the highest possible round {}, no more units can be created.", MAX_MAX_ROUND);
+            let _ = io.notify(NotificationOut::RoundLimitReached, exit).await;
+            return;
+        }
+        if round >= state.max_round && reached_ceiling != Some(state.max_round) {
+            info!(target: "AlephBFT-creator", "Maximum round {} reached. Not creating another unit until it is extended.", state.max_round);
+            reached_ceiling = Some(state.max_round);
+            let notification = NotificationOut::MaxRoundReached(state.max_round);
+            match io.notify(notification, exit).await {
+                Ok(()) => {}
+                Err(Interrupted::Exit) => {
+                    io.send_state(&creator, round);
+                    return;
+                }
+                Err(Interrupted::ChannelClosed) => return,
+            }
+        } else if round.saturating_add(conf.max_round_warning_margin) >= state.max_round
+            && warned_ceiling != Some(state.max_round)
+        {
+            info!(target: "AlephBFT-creator", "Approaching the maximum round {}, currently at round {}.", state.max_round, round);
+            warned_ceiling = Some(state.max_round);
+            let notification = NotificationOut::MaxRoundApproached(state.max_round);
+            match io.notify(notification, exit).await {
+                Ok(()) => {}
+                Err(Interrupted::Exit) => {
+                    io.send_state(&creator, round);
+                    return;
+                }
+                Err(Interrupted::ChannelClosed) => return,
+            }
+        }
+        if let Some(schedule) = state.pending_schedule.take() {
+            conf.create_lag = schedule;
+        }
         // Skip waiting if someone created a unit of a higher round.
         // In such a case at least 2/3 nodes created units from this round so we aren't skipping a
         // delay we should observe.
         let ignore_delay = creator.current_round() > round;
-        let (unit, parent_hashes) = match create_unit(
+        let result = match create_unit(
             round,
             &mut creator,
-            &create_lag,
+            &conf,
+            &mut state,
             ignore_delay,
-            &mut incoming_parents,
-            &mut exit,
+            &mut io,
+            exit,
         )
         .await
         {
-            Ok((u, ph)) => (u, ph),
-            Err(_) => {
-                return;
+            Ok((unit, parent_hashes)) => {
+                // This waits while the units we created before are not processed yet.
+                io.notify(NotificationOut::CreatedPreUnit(unit, parent_hashes), exit)
+                    .await
             }
+            Err(interrupted) => Err(interrupted),
         };
-        if let Err(e) =
-            outgoing_units.unbounded_send(NotificationOut::CreatedPreUnit(unit, parent_hashes))
-        {
-            warn!(target: "AlephBFT-creator", "Notification send error: {}. Exiting.", e);
-            return;
+        match result {
+            Ok(()) => {}
+            // A unit which we did not manage to pass on was never signed, so it can be created
+            // again after restoring the snapshot.
+            Err(Interrupted::Exit) => {
+                io.send_state(&creator, round);
+                return;
+            }
+            Err(Interrupted::ChannelClosed) => return,
         }
     }
-    warn!(target: "AlephBFT-creator", "Maximum round reached. Not creating another unit.");
 }
@@ -0,0 +1,180 @@
+use futures::{
+    channel::mpsc,
+    future::BoxFuture,
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use log::trace;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use crate::{
+    alerts::{ForkEvidence, ForkHandler},
+    Config, Data, DataIO, FinalizedUnit, Hasher, OrderedBatch, Round, Sender,
+    UnitFinalizationHandler, UnitRejection,
+};
+
+/// Something that happened during a session, see [`SessionEvents`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionEvent<H: Hasher, D> {
+    /// We created our unit of the round.
+    UnitCreated(Round),
+    /// Units of a quorum of members of the round reached our Dag, see
+    /// [`Config::quorum_round_sink`].
+    QuorumRound(Round),
+    /// We learned about a fork, see [`ForkHandler`].
+    Fork(ForkEvidence),
+    /// We rejected an incoming unit, see [`Config::unit_rejection_sink`].
+    UnitRejected(UnitRejection),
+    /// A batch was finalized, with the units just passed to the finalization handler.
+    Finalized(Vec<FinalizedUnit<H, D>>),
+}
+
+/// The events of a session as a single stream, for monitoring it without wiring up every sink
+/// and handler separately. Created with [`SessionEvents::attach`], or with
+/// [`SessionBuilder::events`](crate::SessionBuilder::events).
+///
+/// The events of every kind come in the order they happen, but the events of different kinds
+/// might be reordered a little, e.g. a batch might come before the quorum round above it. The
+/// stream ends once the session does.
+pub struct SessionEvents<H: Hasher, D> {
+    events: BoxStream<'static, SessionEvent<H, D>>,
+}
+
+impl<H: Hasher, D: Data> SessionEvents<H, D> {
+    /// Makes the session run with `config` and the returned handler report its events to the
+    /// returned stream. The creation latency, quorum round and unit rejection sinks of `config`
+    /// are replaced, so the events they would get only come out of the stream, while the fork
+    /// handler of `config` and `handler` keep getting everything as before. Creating units is
+    /// measured from now on, as if [`Config::creation_latency_sink`] was set.
+    pub fn attach<DP: UnitFinalizationHandler<H, D>>(
+        config: &mut Config,
+        handler: DP,
+    ) -> (EventForwarder<H, D, DP>, Self) {
+        let (events_tx, events_rx) = mpsc::unbounded();
+        let (latencies_tx, latencies_rx) = mpsc::unbounded();
+        let (quorum_rounds_tx, quorum_rounds_rx) = mpsc::unbounded();
+        let (rejections_tx, rejections_rx) = mpsc::unbounded();
+        config.creation_latency_sink = Some(latencies_tx);
+        config.quorum_round_sink = Some(quorum_rounds_tx);
+        config.unit_rejection_sink = Some(rejections_tx);
+        config.fork_handler = Arc::new(ForkForwarder {
+            fork_handler: config.fork_handler.clone(),
+            events: events_tx.clone(),
+        });
+        let events = stream::select_all([
+            events_rx.boxed(),
+            latencies_rx
+                .map(|latencies| SessionEvent::UnitCreated(latencies.round))
+                .boxed(),
+            quorum_rounds_rx.map(SessionEvent::QuorumRound).boxed(),
+            rejections_rx.map(SessionEvent::UnitRejected).boxed(),
+        ])
+        .boxed();
+        let handler = EventForwarder {
+            handler,
+            events: events_tx,
+        };
+        (handler, SessionEvents { events })
+    }
+}
+
+impl<H: Hasher, D> Stream for SessionEvents<H, D> {
+    type Item = SessionEvent<H, D>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+fn send_event<H: Hasher, D>(events: &Sender<SessionEvent<H, D>>, event: SessionEvent<H, D>) {
+    // Nobody might be listening anymore, which does not concern the session.
+    if events.unbounded_send(event).is_err() {
+        trace!(target: "AlephBFT-events", "Session event stream dropped.");
+    }
+}
+
+struct ForkForwarder<H: Hasher, D> {
+    fork_handler: Arc<dyn ForkHandler>,
+    events: Sender<SessionEvent<H, D>>,
+}
+
+impl<H: Hasher, D: Data> ForkHandler for ForkForwarder<H, D> {
+    fn on_fork(&self, evidence: ForkEvidence) {
+        send_event(&self.events, SessionEvent::Fork(evidence.clone()));
+        self.fork_handler.on_fork(evidence);
+    }
+}
+
+/// A [`UnitFinalizationHandler`] that reports the finalized batches to [`SessionEvents`] before
+/// passing them to the wrapped handler, see [`SessionEvents::attach`].
+pub struct EventForwarder<H: Hasher, D, DP> {
+    handler: DP,
+    events: Sender<SessionEvent<H, D>>,
+}
+
+impl<H: Hasher, D, DP> EventForwarder<H, D, DP> {
+    /// The wrapped handler.
+    pub fn inner(&self) -> &DP {
+        &self.handler
+    }
+}
+
+impl<H: Hasher, D: Data, DP: DataIO<D>> DataIO<D> for EventForwarder<H, D, DP> {
+    type Error = DP::Error;
+
+    fn get_data(&self) -> D {
+        self.handler.get_data()
+    }
+
+    fn get_data_async(&self) -> BoxFuture<'static, D> {
+        self.handler.get_data_async()
+    }
+
+    fn fallback_data(&self) -> Option<D> {
+        self.handler.fallback_data()
+    }
+
+    fn finalize_data(&self, data: D, round: Round) -> Option<D> {
+        self.handler.finalize_data(data, round)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.handler.is_closed()
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<D>) -> Result<(), Self::Error> {
+        self.handler.send_ordered_batch(data)
+    }
+
+    fn send_ordered_batch_async(
+        &mut self,
+        data: OrderedBatch<D>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        self.handler.send_ordered_batch_async(data)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.handler.flush()
+    }
+}
+
+impl<H: Hasher, D: Data, DP: UnitFinalizationHandler<H, D>> UnitFinalizationHandler<H, D>
+    for EventForwarder<H, D, DP>
+{
+    fn units_finalized(&mut self, units: Vec<FinalizedUnit<H, D>>) -> Result<(), Self::Error> {
+        send_event(&self.events, SessionEvent::Finalized(units.clone()));
+        self.handler.units_finalized(units)
+    }
+
+    fn units_finalized_async(
+        &mut self,
+        units: Vec<FinalizedUnit<H, D>>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        send_event(&self.events, SessionEvent::Finalized(units.clone()));
+        self.handler.units_finalized_async(units)
+    }
+}
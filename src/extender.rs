@@ -1,11 +1,13 @@
-use futures::{channel::oneshot, StreamExt};
-use std::collections::{HashMap, VecDeque};
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 use log::{debug, info, warn};
 
 use crate::{
-    nodes::{NodeCount, NodeIndex, NodeMap},
-    Hasher, Receiver, Round, Sender,
+    nodes::{NodeCount, NodeIndex, NodeMap, NodeWeights},
+    terminator::Terminator,
+    trace::unit_event,
+    Hasher, Receiver, Round, Sender, SessionStatus,
 };
 
 pub(crate) struct ExtenderUnit<H: Hasher> {
@@ -59,39 +61,52 @@ impl CacheState {
 /// to finalize subsequent rounds of the Dag. More specifically whenever a new unit is received
 /// this process checks whether a new round can be finalized and if so, it computes the batch of
 /// units that should be finalized, unwraps them (leaving only a block hash per unit) and pushes
-/// such a batch to a channel via the finalizer_tx endpoint.
+/// such a batch, together with the round of its head, to a channel via the finalizer_tx endpoint.
+/// A batch larger than `max_batch_size` is pushed in a few parts, each with the round of the head.
+/// The number of every finalized round is additionally pushed via the finalized_rounds_tx endpoint.
 ///
 /// We refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/internals.html
 /// Section 5.4 for a discussion of this component.
-
 pub(crate) struct Extender<H: Hasher> {
     node_id: NodeIndex,
     electors: Receiver<ExtenderUnit<H>>,
     state: CacheState,
     units: HashMap<H::Hash, ExtenderUnit<H>>,
     units_by_round: Vec<Vec<H::Hash>>,
-    n_members: NodeCount,
+    // The number of the units of `units` of every round, i.e. of the ones not ordered yet.
+    unordered_by_round: BTreeMap<Round, usize>,
+    node_weights: NodeWeights,
+    max_batch_size: Option<usize>,
     candidates: Vec<H::Hash>,
-    finalizer_tx: Sender<Vec<H::Hash>>,
+    finalizer_tx: Sender<(Round, Vec<H::Hash>)>,
+    finalized_rounds_tx: Sender<Round>,
+    status: SessionStatus,
     exiting: bool,
 }
 
 impl<H: Hasher> Extender<H> {
     pub(crate) fn new(
         node_id: NodeIndex,
-        n_members: NodeCount,
+        node_weights: NodeWeights,
+        max_batch_size: Option<usize>,
         electors: Receiver<ExtenderUnit<H>>,
-        finalizer_tx: Sender<Vec<H::Hash>>,
+        finalizer_tx: Sender<(Round, Vec<H::Hash>)>,
+        finalized_rounds_tx: Sender<Round>,
+        status: SessionStatus,
     ) -> Self {
         Extender {
             node_id,
             electors,
             finalizer_tx,
+            finalized_rounds_tx,
             state: CacheState::empty_dag_cache(),
             units: HashMap::new(),
             units_by_round: vec![vec![]],
-            n_members,
+            unordered_by_round: BTreeMap::new(),
+            node_weights,
+            max_batch_size,
             candidates: vec![],
+            status,
             exiting: false,
         }
     }
@@ -113,14 +128,30 @@ impl<H: Hasher> Extender<H> {
             }
             self.units_by_round[round as usize].push(u.hash);
         }
+        *self.unordered_by_round.entry(round).or_insert(0) += 1;
         self.units.insert(u.hash, u);
     }
 
+    fn remove_unit(&mut self, hash: &H::Hash) -> Option<ExtenderUnit<H>> {
+        let u = self.units.remove(hash)?;
+        if let Some(n_units) = self.unordered_by_round.get_mut(&u.round) {
+            *n_units -= 1;
+            if *n_units == 0 {
+                self.unordered_by_round.remove(&u.round);
+            }
+        }
+        Some(u)
+    }
+
     fn initialize_round(&mut self, round: Round) {
         // The clone below is necessary as we take "a snapshot" of the set of units at this round and never
         // go back and never update this list. From math it follows that each unit that is added to the Dag later
         // then the moment of round initialization will be decided as false, hence they can be ignored.
         self.candidates = self.units_by_round[round as usize].clone();
+        // Deliberately not `unit_order`: all the candidates are of the same round, so it would
+        // order them by creator and the members with low indices would be heads more often.
+        // Every node has to use the same order anyway, so changing it would break consensus with
+        // older versions.
         self.candidates.sort();
     }
 
@@ -139,11 +170,11 @@ impl<H: Hasher> Extender<H> {
     fn finalize_round(&mut self, round: Round, head: &H::Hash) {
         let mut batch = vec![];
         let mut queue = VecDeque::new();
-        queue.push_back(self.units.remove(head).unwrap());
+        queue.push_back(self.remove_unit(head).unwrap());
         while let Some(u) = queue.pop_front() {
             batch.push(u.hash);
             for u_hash in u.parents.into_values() {
-                if let Some(v) = self.units.remove(&u_hash) {
+                if let Some(v) = self.remove_unit(&u_hash) {
                     queue.push_back(v);
                 }
             }
@@ -152,12 +183,26 @@ impl<H: Hasher> Extender<H> {
 
         // We reverse for the batch to start with least recent units.
         batch.reverse();
-        if self.finalizer_tx.unbounded_send(batch).is_err() {
-            warn!(target: "AlephBFT-extender", "{:?} Channel for batches should be open", self.node_id);
-            self.exiting = true;
+        let max_batch_size = self.max_batch_size.unwrap_or(batch.len());
+        // Only the last part contains the head, so every part carries its round explicitly.
+        for chunk in batch.chunks(max_batch_size) {
+            if self
+                .finalizer_tx
+                .unbounded_send((round, chunk.to_vec()))
+                .is_err()
+            {
+                warn!(target: "AlephBFT-extender", "{:?} Channel for batches should be open", self.node_id);
+                self.exiting = true;
+                break;
+            }
+        }
+        if self.finalized_rounds_tx.unbounded_send(round).is_err() {
+            // The creator might have already reached the maximum round and exited.
+            debug!(target: "AlephBFT-extender", "{:?} Channel for finalized rounds closed.", self.node_id);
         }
 
         debug!(target: "AlephBFT-extender", "{:?} Finalized round {:?} with head {:?}.", self.node_id, round, head);
+        unit_event!(round, head = ?head, "round finalized");
         self.units_by_round[round as usize].clear();
     }
 
@@ -182,31 +227,33 @@ impl<H: Hasher> Extender<H> {
             );
         }
 
-        let mut n_votes_true = NodeCount(0);
-        let mut n_votes_false = NodeCount(0);
+        let mut voters_true = Vec::new();
+        let mut voters_false = Vec::new();
 
-        for p_hash in voter.parents.values() {
+        for (p_creator, p_hash) in voter.parents.iter() {
             let p = self.units.get(p_hash).unwrap();
             if p.vote {
-                n_votes_true += NodeCount(1);
+                voters_true.push(p_creator);
             } else {
-                n_votes_false += NodeCount(1);
+                voters_false.push(p_creator);
             }
         }
         let cv = self.common_vote(relative_round);
         let mut decision = None;
-        let threshold = (self.n_members * 2) / 3 + NodeCount(1);
-        assert!(n_votes_true + n_votes_false >= threshold);
+        assert!(self
+            .node_weights
+            .is_quorum(voters_true.iter().chain(voters_false.iter()).copied()));
 
         if relative_round >= 3
-            && ((cv && n_votes_true >= threshold) || (!cv && n_votes_false >= threshold))
+            && ((cv && self.node_weights.is_quorum(voters_true.iter().copied()))
+                || (!cv && self.node_weights.is_quorum(voters_false.iter().copied())))
         {
             decision = Some(cv);
         }
 
-        let vote = match (n_votes_false, n_votes_true) {
-            (NodeCount(0), _) => true,
-            (_, NodeCount(0)) => false,
+        let vote = match (voters_false.is_empty(), voters_true.is_empty()) {
+            (true, _) => true,
+            (_, true) => false,
             _ => cv,
         };
 
@@ -232,8 +279,24 @@ impl<H: Hasher> Extender<H> {
         None
     }
 
+    // A single member has nothing to vote on: its unit is the only candidate of its round, which
+    // the votes would decide to be the head anyway, only a few rounds later.
+    fn progress_alone(&mut self) {
+        while let Some(head) = self
+            .units_by_round
+            .get(self.state.current_round as usize)
+            .and_then(|units| units.first().copied())
+        {
+            self.finalize_round(self.state.current_round, &head);
+            self.state.current_round += 1;
+        }
+    }
+
     // Tries to make progress in extending the partial order after adding a new unit to the Dag.
     fn progress(&mut self, u_new_hash: H::Hash) {
+        if self.node_weights.n_members() == NodeCount(1) {
+            return self.progress_alone();
+        }
         loop {
             if !self.state.round_initialized {
                 if self.state.highest_round >= self.state.current_round + 3 {
@@ -297,17 +360,26 @@ impl<H: Hasher> Extender<H> {
         }
     }
 
-    pub(crate) async fn extend(&mut self, mut exit: oneshot::Receiver<()>) {
+    /// Adds a unit to the Dag, after all its parents, and finalizes the rounds it allows to.
+    pub(crate) fn add_elector(&mut self, u: ExtenderUnit<H>) {
+        let u_hash = u.hash;
+        self.add_unit(u);
+        self.progress(u_hash);
+        self.status.on_unordered_units(
+            self.units.len(),
+            self.unordered_by_round.keys().next().copied(),
+        );
+    }
+
+    pub(crate) async fn extend(&mut self, mut terminator: Terminator) {
         loop {
             futures::select! {
                 v = self.electors.next() => {
                     if let Some(v) = v {
-                        let v_hash = v.hash;
-                        self.add_unit(v);
-                        self.progress(v_hash)
+                        self.add_elector(v);
                     }
                 }
-                _ = &mut exit => {
+                _ = terminator.get_exit() => {
                     info!(target: "AlephBFT-extender", "{:?} received exit signal.", self.node_id);
                     self.exiting = true;
                 }
@@ -317,6 +389,7 @@ impl<H: Hasher> Extender<H> {
                 break;
             }
         }
+        terminator.terminate_sync().await;
     }
 }
 
@@ -324,7 +397,7 @@ impl<H: Hasher> Extender<H> {
 mod tests {
     use super::*;
     use crate::{nodes::NodeCount, testing::mock::Hasher64};
-    use futures::channel::mpsc;
+    use futures::channel::{mpsc, oneshot};
 
     fn coord_to_number(creator: NodeIndex, round: Round, n_members: NodeCount) -> u64 {
         (round as usize * n_members.0 + creator.0) as u64
@@ -337,7 +410,7 @@ mod tests {
     ) -> ExtenderUnit<Hasher64> {
         let mut parents = NodeMap::with_size(n_members);
         if round > 0 {
-            for i in n_members.into_iterator() {
+            for i in n_members.all_indices() {
                 parents.insert(i, coord_to_number(i, round - 1, n_members).to_ne_bytes());
             }
         }
@@ -356,24 +429,182 @@ mod tests {
         let rounds = 6;
         let (batch_tx, mut batch_rx) = mpsc::unbounded();
         let (electors_tx, electors_rx) = mpsc::unbounded();
-        let mut extender = Extender::<Hasher64>::new(0.into(), n_members, electors_rx, batch_tx);
+        let (finalized_rounds_tx, mut finalized_rounds_rx) = mpsc::unbounded();
+        let mut extender = Extender::<Hasher64>::new(
+            0.into(),
+            NodeWeights::uniform(n_members),
+            None,
+            electors_rx,
+            batch_tx,
+            finalized_rounds_tx,
+            SessionStatus::new(),
+        );
         let (exit_tx, exit_rx) = oneshot::channel();
-        let extender_handle = tokio::spawn(async move { extender.extend(exit_rx).await });
+        let extender_handle = tokio::spawn(async move {
+            extender
+                .extend(Terminator::create_root(exit_rx, "AlephBFT-extender"))
+                .await
+        });
 
         for round in 0..rounds {
-            for creator in n_members.into_iterator() {
+            for creator in n_members.all_indices() {
                 let unit = construct_unit(creator, round, n_members);
                 electors_tx
                     .unbounded_send(unit)
                     .expect("Channel should be open");
             }
         }
-        let batch_round_0 = batch_rx.next().await.unwrap();
+        let (round, batch_round_0) = batch_rx.next().await.unwrap();
+        assert_eq!(round, 0);
         assert!(!batch_round_0.is_empty());
 
-        let batch_round_1 = batch_rx.next().await.unwrap();
+        let (round, batch_round_1) = batch_rx.next().await.unwrap();
+        assert_eq!(round, 1);
         assert!(!batch_round_1.is_empty());
+        assert_eq!(finalized_rounds_rx.next().await, Some(0));
+        assert_eq!(finalized_rounds_rx.next().await, Some(1));
+        let _ = exit_tx.send(());
+        let _ = extender_handle.await;
+    }
+
+    // Finalizes the given number of rounds of a Dag in which everybody creates units with all the
+    // units of the previous round as parents, as if we were catching up on all of it at once.
+    async fn catch_up(
+        n_members: NodeCount,
+        rounds: Round,
+        max_batch_size: Option<usize>,
+    ) -> Vec<(Round, Vec<[u8; 8]>)> {
+        let (batch_tx, mut batch_rx) = mpsc::unbounded();
+        let (electors_tx, electors_rx) = mpsc::unbounded();
+        let (finalized_rounds_tx, mut finalized_rounds_rx) = mpsc::unbounded();
+        let mut extender = Extender::<Hasher64>::new(
+            0.into(),
+            NodeWeights::uniform(n_members),
+            max_batch_size,
+            electors_rx,
+            batch_tx,
+            finalized_rounds_tx,
+            SessionStatus::new(),
+        );
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let extender_handle = tokio::spawn(async move {
+            extender
+                .extend(Terminator::create_root(exit_rx, "AlephBFT-extender"))
+                .await
+        });
+
+        for round in 0..rounds {
+            for creator in n_members.all_indices() {
+                let unit = construct_unit(creator, round, n_members);
+                electors_tx
+                    .unbounded_send(unit)
+                    .expect("Channel should be open");
+            }
+        }
+        // A round is decided once there are units four rounds above it.
+        for round in 0..rounds - 4 {
+            assert_eq!(finalized_rounds_rx.next().await, Some(round));
+        }
         let _ = exit_tx.send(());
         let _ = extender_handle.await;
+        let mut batches = Vec::new();
+        while let Ok(batch) = batch_rx.try_recv() {
+            batches.push(batch);
+        }
+        batches
+    }
+
+    #[test]
+    fn finalizes_units_of_a_single_member_right_away() {
+        let n_members = NodeCount(1);
+        let (batch_tx, mut batch_rx) = mpsc::unbounded();
+        let (_, electors_rx) = mpsc::unbounded();
+        let (finalized_rounds_tx, mut finalized_rounds_rx) = mpsc::unbounded();
+        let mut extender = Extender::<Hasher64>::new(
+            0.into(),
+            NodeWeights::uniform(n_members),
+            None,
+            electors_rx,
+            batch_tx,
+            finalized_rounds_tx,
+            SessionStatus::new(),
+        );
+        for round in 0..5 {
+            let unit = construct_unit(0.into(), round, n_members);
+            let hash = unit.hash;
+            extender.add_elector(unit);
+            assert_eq!(batch_rx.try_recv().ok(), Some((round, vec![hash])));
+            assert_eq!(finalized_rounds_rx.try_recv().ok(), Some(round));
+        }
+    }
+
+    #[test]
+    fn reports_the_units_it_cannot_order_yet() {
+        let n_members = NodeCount(4);
+        let status = SessionStatus::new();
+        let (batch_tx, mut batch_rx) = mpsc::unbounded();
+        let (_, electors_rx) = mpsc::unbounded();
+        let (finalized_rounds_tx, _finalized_rounds_rx) = mpsc::unbounded();
+        let mut extender = Extender::<Hasher64>::new(
+            0.into(),
+            NodeWeights::uniform(n_members),
+            None,
+            electors_rx,
+            batch_tx,
+            finalized_rounds_tx,
+            status.clone(),
+        );
+        let mut add_rounds = |rounds| {
+            for round in rounds {
+                for creator in n_members.all_indices() {
+                    extender.add_elector(construct_unit(creator, round, n_members));
+                }
+            }
+        };
+        // Too few rounds to decide on any head.
+        add_rounds(0..3);
+        assert!(batch_rx.try_recv().is_err());
+        assert_eq!(status.n_unordered_units(), 12);
+        assert_eq!(status.oldest_unordered_round(), Some(0));
+
+        add_rounds(3..6);
+        let mut n_ordered = 0;
+        while let Ok((_, batch)) = batch_rx.try_recv() {
+            n_ordered += batch.len();
+        }
+        // The heads of rounds 0 and 1, with all the units of round 0 below them.
+        assert_eq!(n_ordered, 5);
+        assert_eq!(status.n_unordered_units(), 19);
+        assert_eq!(status.oldest_unordered_round(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn splits_large_batches_preserving_order() {
+        let n_members = NodeCount(7);
+        let rounds = 10;
+        let max_batch_size = 3;
+        let whole = catch_up(n_members, rounds, None).await;
+        let split = catch_up(n_members, rounds, Some(max_batch_size)).await;
+
+        assert!(whole.iter().any(|(_, batch)| batch.len() > max_batch_size));
+        assert!(split
+            .iter()
+            .all(|(_, batch)| !batch.is_empty() && batch.len() <= max_batch_size));
+        assert!(split.len() > whole.len());
+        let units = |batches: &[(Round, Vec<[u8; 8]>)]| -> Vec<[u8; 8]> {
+            batches
+                .iter()
+                .flat_map(|(_, batch)| batch.clone())
+                .collect()
+        };
+        assert_eq!(units(&whole), units(&split));
+        // Every part of a batch carries the round of its head, also the ones without the head.
+        let batch_rounds = |batches: &[(Round, Vec<[u8; 8]>)]| -> Vec<Round> {
+            let mut rounds: Vec<_> = batches.iter().map(|(round, _)| *round).collect();
+            rounds.dedup();
+            rounds
+        };
+        assert_eq!(batch_rounds(&whole), batch_rounds(&split));
+        assert_eq!(batch_rounds(&split), (0..rounds - 4).collect::<Vec<_>>());
     }
 }
@@ -0,0 +1,125 @@
+use crate::Hasher;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The inputs hashed by [`check_hasher`], differing in small ways, like the units do.
+fn inputs() -> Vec<Vec<u8>> {
+    let long: Vec<u8> = (0..=255).cycle().take(1024).collect();
+    let mut long_changed = long.clone();
+    long_changed[512] ^= 1;
+    vec![
+        Vec::new(),
+        vec![0],
+        vec![1],
+        vec![0, 0],
+        b"AlephBFT".to_vec(),
+        b"AlephBFU".to_vec(),
+        long,
+        long_changed,
+    ]
+}
+
+/// The way a [`Hasher`] failed [`check_hasher`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HasherError {
+    /// Hashing the input twice gave different hashes.
+    NotDeterministic { input: Vec<u8> },
+    /// The two different inputs have the same hash.
+    Collision { first: Vec<u8>, second: Vec<u8> },
+}
+
+impl Display for HasherError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            HasherError::NotDeterministic { input } => write!(
+                f,
+                "hashing {:?} twice gave different hashes, the hasher has to be deterministic",
+                input
+            ),
+            HasherError::Collision { first, second } => write!(
+                f,
+                "{:?} and {:?} have the same hash, the hasher has to be collision resistant",
+                first, second
+            ),
+        }
+    }
+}
+
+/// Hashes a few fixed inputs with the [`Hasher`], checking that every input always has the same
+/// hash and that different inputs have different hashes. Passing it does not make the hasher
+/// correct, but a hasher failing it breaks the consensus in ways hard to trace back to it. Run at
+/// the start of the session if [`Config::check_hasher`](crate::Config::check_hasher) is set.
+pub fn check_hasher<H: Hasher>() -> Result<(), HasherError> {
+    let inputs = inputs();
+    let mut hashes: Vec<(H::Hash, &Vec<u8>)> = Vec::with_capacity(inputs.len());
+    for input in &inputs {
+        let hash = H::hash(input);
+        if H::hash(input) != hash {
+            return Err(HasherError::NotDeterministic {
+                input: input.clone(),
+            });
+        }
+        if let Some((_, first)) = hashes.iter().find(|(other, _)| *other == hash) {
+            return Err(HasherError::Collision {
+                first: first.to_vec(),
+                second: input.clone(),
+            });
+        }
+        hashes.push((hash, input));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_hasher, HasherError};
+    use crate::{testing::mock::Hasher64, Hasher};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct ShortHasher;
+
+    impl Hasher for ShortHasher {
+        type Hash = [u8; 1];
+
+        fn hash(x: &[u8]) -> Self::Hash {
+            [x.len() as u8]
+        }
+    }
+
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct CountingHasher;
+
+    impl Hasher for CountingHasher {
+        type Hash = [u8; 8];
+
+        fn hash(_: &[u8]) -> Self::Hash {
+            CALLS.fetch_add(1, Ordering::Relaxed).to_le_bytes()
+        }
+    }
+
+    #[test]
+    fn accepts_correct_hasher() {
+        assert_eq!(check_hasher::<Hasher64>(), Ok(()));
+    }
+
+    #[test]
+    fn detects_collisions() {
+        assert_eq!(
+            check_hasher::<ShortHasher>(),
+            Err(HasherError::Collision {
+                first: vec![0],
+                second: vec![1],
+            })
+        );
+    }
+
+    #[test]
+    fn detects_nondeterminism() {
+        assert_eq!(
+            check_hasher::<CountingHasher>(),
+            Err(HasherError::NotDeterministic { input: Vec::new() })
+        );
+    }
+}
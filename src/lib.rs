@@ -1,34 +1,96 @@
 //! Implements the Aleph BFT Consensus protocol as a "finality gadget". The [Member] struct
 //! requires access to a network layer, a cryptographic primitive, and a data provider that
 //! gives appropriate access to the set of available data that we need to make consensus on.
+#![allow(clippy::type_complexity)]
 
 use codec::{Decode, Encode};
-use futures::{channel::mpsc, Future};
+use futures::{channel::mpsc, future::BoxFuture, Future, FutureExt};
 use std::{fmt::Debug, hash::Hash as StdHash, pin::Pin};
 
-use crate::nodes::NodeMap;
-
-pub use config::{default_config, exponential_slowdown, Config, DelayConfig};
-pub use member::run_session;
-pub use network::{Network, NetworkData, Recipient};
-pub use nodes::{NodeCount, NodeIndex};
+pub use aleph_bft_types::{Hasher, Round};
+pub use config::{
+    constant_schedule, default_config, exponential_schedule, exponential_slowdown,
+    stepped_schedule, with_jitter, CatchUpConfig, Config, ConfigBuilder, ConfigError, ConfigSpec,
+    ContextDelaySchedule, CreationContext, DataChannelPolicy, DelayConfig, DelayConfigSpec,
+    DelaySchedule, DelayScheduleSpec, ForkPolicy, LivenessConfig, MaxRoundPolicy, Profile,
+    RequestConfig, RequestConfigSpec, ResponseLimitConfig, RngFactory, TaskPanicPolicy,
+    UnitBatchingConfig, MAX_CREATION_BACKPRESSURE_FACTOR, MAX_MAX_ROUND,
+    MIN_FAULT_TOLERANT_MEMBERS, MIN_MAX_ROUNDS_AHEAD, MIN_MAX_UNIT_VARIANTS,
+};
+pub use creation::{
+    AllAvailableParents, CreationLatencies, CreatorCommand, LatencyHistogram, ParentSelector,
+};
+pub use member::{
+    run_session, run_session_with_key_rotation, run_session_with_unit_handler, spawn_session,
+    SessionError, SessionOutcome,
+};
+#[cfg(feature = "network-loopback")]
+pub use network::LoopbackNetwork;
+#[cfg(feature = "network-tcp")]
+pub use network::TcpNetwork;
+#[cfg(feature = "compression")]
+pub use network::{compress_payload, decompress_payload, DecompressionError};
+pub use network::{
+    MultiplexedNetwork, Network, NetworkCodec, NetworkData, PeerStatus, PeerStatusHandle,
+    PeerTrackingNetwork, RateLimitedNetwork, Recipient, ScaleCodec, SessionData,
+    SessionMultiplexer, SessionNetwork, UnitHeader,
+};
+pub use nodes::{NodeCount, NodeIndex, NodeWeights};
 
 mod alerts;
+pub use alerts::{ForkEvidence, ForkHandler, ForkingUnit, IgnoreForks};
+mod backup;
+mod builder;
+pub use builder::{Missing, SessionBuilder};
+#[cfg(feature = "benchmarks")]
+#[doc(hidden)]
+pub mod benchmarks;
+#[cfg(feature = "compression")]
+pub use backup::CompressedBackup;
+pub use backup::{BackupSink, BackupSource, FileBackup, MemoryBackup, NoBackup};
+#[cfg(feature = "bls")]
+mod bls;
+#[cfg(feature = "bls")]
+pub use bls::{BlsKeychain, BlsMultisignature, BlsPublicKey, BlsSecretKey, BlsSignature};
+mod clock;
+pub use clock::{Clock, MockClock, RealClock};
 mod consensus;
 mod creation;
+mod events;
+pub use events::{EventForwarder, SessionEvent, SessionEvents};
 mod extender;
+mod hasher;
+pub use hasher::{check_hasher, HasherError};
 mod member;
 mod network;
 mod nodes;
+mod panics;
 mod runway;
 mod signed;
 pub use signed::*;
+#[cfg(feature = "tokio")]
+mod spawner;
+#[cfg(feature = "tokio")]
+pub use spawner::TokioSpawner;
+mod status;
+pub use status::{CreatedUnitParents, CreatorStall, DagShape, MemberActivity, SessionStatus};
 mod config;
+mod replay;
 pub mod rmc;
+pub use replay::{replay_backup, replay_backup_with_weights};
+mod rotation;
+pub use rotation::NewKeychain;
 mod terminal;
+mod terminator;
 #[cfg(test)]
 pub mod testing;
+mod trace;
+#[cfg(feature = "trusted")]
+mod trusted;
+#[cfg(feature = "trusted")]
+pub use trusted::{TrustedKeychain, TrustedMultisignature, TrustedSignature};
 mod units;
+pub use units::{unit_order, ParentsError, Unit, UnitRejection, UnitRejectionReason};
 
 /// The number of a session for which the consensus is run.
 pub type SessionId = u64;
@@ -42,11 +104,169 @@ pub type SessionId = u64;
 /// We refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/aleph_bft_api.html for a discussion
 /// and examples of how this trait can be implemented.
 pub trait DataIO<Data> {
-    type Error: Debug + 'static;
+    type Error: Debug + Send + 'static;
     /// Outputs a new data item to be ordered
     fn get_data(&self) -> Data;
-    /// Takes a new ordered batch of data item.
+    /// Outputs a new data item to be ordered, for providers which need to wait for it. Our unit
+    /// waits for the data, while the session keeps running. If [`DelayConfig::data_timeout`] is
+    /// set and [`DataIO::fallback_data`] returns some data, the unit is not delayed longer than
+    /// that, otherwise it waits as long as the data takes. At most
+    /// [`Config::data_pipeline_depth`] calls are outstanding at once. By default it returns the
+    /// result of [`DataIO::get_data`].
+    fn get_data_async(&self) -> BoxFuture<'static, Data>
+    where
+        Data: Send + 'static,
+    {
+        futures::future::ready(self.get_data()).boxed()
+    }
+    /// The data placed in a unit when [`DataIO::get_data_async`] times out. If it returns `None`,
+    /// we keep waiting for the data.
+    fn fallback_data(&self) -> Option<Data> {
+        None
+    }
+    /// Checks the data right before it is put in our unit of the given round, and possibly
+    /// replaces it, e.g. when it became invalid while waiting for the unit. If it returns `None`,
+    /// the unit carries [`DataIO::fallback_data`] instead, and if there is no fallback data, we ask
+    /// for new data, which is checked again. By default the data is used as it is.
+    fn finalize_data(&self, data: Data, _round: Round) -> Option<Data> {
+        Some(data)
+    }
+    /// Whether the data provider is closed, e.g. because the application dropped its end of the
+    /// channel with the data. Checked before asking for the data of every unit, and once it
+    /// returns `true`, [`Config::data_channel_policy`] decides what we do.
+    fn is_closed(&self) -> bool {
+        false
+    }
+    /// Takes a new ordered batch of data item. All the data ordered in a single round of the
+    /// consensus arrives in one batch, so it can be persisted atomically, unless
+    /// [`Config::max_batch_size`] splits it.
     fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), Self::Error>;
+    /// Takes a new ordered batch of data items, for consumers which need time to process it. The
+    /// next batch is passed only once the returned future resolves, and once the waiting batches
+    /// span more than [`Config::max_undelivered_rounds`], the session stops ordering new ones, so
+    /// a slow consumer slows down the whole session, down to the creation of units, instead of
+    /// making the ordered batches pile up. By default it returns the result of
+    /// [`DataIO::send_ordered_batch`].
+    fn send_ordered_batch_async(
+        &mut self,
+        data: OrderedBatch<Data>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        futures::future::ready(self.send_ordered_batch(data)).boxed()
+    }
+    /// Called once when the session exits, after the last ordered batch has been sent. The
+    /// session does not end before this returns, so it is the place to persist everything.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A finalized data item together with the unit that carried it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FinalizedUnit<H: Hasher, Data> {
+    /// The creator of the unit.
+    pub creator: NodeIndex,
+    /// The round of the unit.
+    pub round: Round,
+    /// The hash of the unit.
+    pub hash: H::Hash,
+    /// The data carried by the unit.
+    pub data: Data,
+    /// The local wall-clock time at which the unit was finalized, only with the `timestamps`
+    /// feature.
+    #[cfg(feature = "timestamps")]
+    pub finalized_at: std::time::SystemTime,
+    /// The local wall-clock time at which we created the unit, known only for our own units
+    /// created since the session started, and only with the `timestamps` feature.
+    #[cfg(feature = "timestamps")]
+    pub created_at: Option<std::time::SystemTime>,
+}
+
+#[cfg(feature = "timestamps")]
+impl<H: Hasher, Data> FinalizedUnit<H, Data> {
+    /// How long the unit took from its creation to its finalization, if we know when it was
+    /// created. `None` also if the wall clock went back in between.
+    pub fn finalization_latency(&self) -> Option<std::time::Duration> {
+        self.finalized_at.duration_since(self.created_at?).ok()
+    }
+}
+
+/// A [`DataIO`] that additionally learns which units the ordered data comes from.
+///
+/// When running [`run_session_with_unit_handler`], AlephBFT calls [`UnitFinalizationHandler::units_finalized`]
+/// with the units of every new batch, in the order of finalization, instead of [`DataIO::send_ordered_batch`].
+pub trait UnitFinalizationHandler<H: Hasher, Data>: DataIO<Data> {
+    /// Takes a new ordered batch of data items, together with their units.
+    fn units_finalized(&mut self, units: Vec<FinalizedUnit<H, Data>>) -> Result<(), Self::Error>;
+    /// Takes a new ordered batch of data items, together with their units, for handlers which
+    /// need time to process it. Like [`DataIO::send_ordered_batch_async`], the session waits for
+    /// the returned future. By default it returns the result of
+    /// [`UnitFinalizationHandler::units_finalized`].
+    fn units_finalized_async(
+        &mut self,
+        units: Vec<FinalizedUnit<H, Data>>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        futures::future::ready(self.units_finalized(units)).boxed()
+    }
+}
+
+/// A [`UnitFinalizationHandler`] that passes only the data to the wrapped [`DataIO`].
+pub struct DataIOAdapter<DP>(pub DP);
+
+impl<Data, DP: DataIO<Data>> DataIO<Data> for DataIOAdapter<DP> {
+    type Error = DP::Error;
+
+    fn get_data(&self) -> Data {
+        self.0.get_data()
+    }
+
+    fn get_data_async(&self) -> BoxFuture<'static, Data>
+    where
+        Data: Send + 'static,
+    {
+        self.0.get_data_async()
+    }
+
+    fn fallback_data(&self) -> Option<Data> {
+        self.0.fallback_data()
+    }
+
+    fn finalize_data(&self, data: Data, round: Round) -> Option<Data> {
+        self.0.finalize_data(data, round)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), Self::Error> {
+        self.0.send_ordered_batch(data)
+    }
+
+    fn send_ordered_batch_async(
+        &mut self,
+        data: OrderedBatch<Data>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        self.0.send_ordered_batch_async(data)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+}
+
+impl<H: Hasher, Data, DP: DataIO<Data>> UnitFinalizationHandler<H, Data> for DataIOAdapter<DP> {
+    fn units_finalized(&mut self, units: Vec<FinalizedUnit<H, Data>>) -> Result<(), Self::Error> {
+        self.0
+            .send_ordered_batch(units.into_iter().map(|unit| unit.data).collect())
+    }
+
+    fn units_finalized_async(
+        &mut self,
+        units: Vec<FinalizedUnit<H, Data>>,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        self.0
+            .send_ordered_batch_async(units.into_iter().map(|unit| unit.data).collect())
+    }
 }
 
 /// Indicates that an implementor has been assigned some index.
@@ -54,32 +274,11 @@ pub trait Index {
     fn index(&self) -> NodeIndex;
 }
 
-/// A hasher, used for creating identifiers for blocks or units.
-pub trait Hasher: Eq + Clone + Send + Sync + Debug + 'static {
-    /// A hash, as an identifier for a block or unit.
-    type Hash: AsRef<[u8]>
-        + Eq
-        + Ord
-        + Copy
-        + Clone
-        + Send
-        + Sync
-        + Debug
-        + StdHash
-        + Encode
-        + Decode;
-
-    fn hash(s: &[u8]) -> Self::Hash;
-}
-
 /// Data type that we want to order.
 pub trait Data: Eq + Clone + Send + Sync + Debug + StdHash + Encode + Decode + 'static {}
 
 impl<T> Data for T where T: Eq + Clone + Send + Sync + Debug + StdHash + Encode + Decode + 'static {}
 
-/// An asynchronous round of the protocol.
-pub type Round = u16;
-
 /// Type for sending a new ordered batch of data items.
 pub type OrderedBatch<Data> = Vec<Data>;
 
@@ -97,6 +296,20 @@ pub trait SpawnHandle: Clone + Send + 'static {
         name: &'static str,
         task: impl Future<Output = ()> + Send + 'static,
     ) -> TaskHandle;
+    /// Lets the other tasks run before the calling one continues, which long CPU-bound work, like
+    /// verifying many signatures in a row, does from time to time. By default the calling task is
+    /// suspended once and woken up right away.
+    fn yield_now(&self) -> BoxFuture<'static, ()> {
+        let mut yielded = false;
+        Box::pin(futures::future::poll_fn(move |cx| {
+            if yielded {
+                return std::task::Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            std::task::Poll::Pending
+        }))
+    }
 }
 
 pub(crate) type Receiver<T> = mpsc::UnboundedReceiver<T>;
@@ -1,26 +1,32 @@
 use crate::{
-    config::Config,
-    network::{self, Recipient},
+    config::{session_rng, Config, TaskPanicPolicy, MAX_MAX_ROUND, MIN_FAULT_TOLERANT_MEMBERS},
+    creation::CreatorCommand,
+    hasher::{check_hasher, HasherError},
+    network::{self, NetworkHubIO, Recipient},
+    panics::{PanicCatchingSpawner, TaskPanic},
+    rotation::{self, NewKeychain, RotatingKeychain},
     runway::{self, Request, Response, RunwayIO, RunwayNotificationIn, RunwayNotificationOut},
-    signed::Signature,
+    signed::{PartialMultisignature, Signature},
+    terminator::Terminator,
+    trace::in_span,
     units::{UncheckedSignedUnit, UnitCoord},
-    Data, DataIO, Hasher, MultiKeychain, Network, NodeCount, NodeIndex, Receiver, Sender, Signable,
-    SpawnHandle, UncheckedSigned,
+    Data, DataIO, DataIOAdapter, Hasher, MultiKeychain, Network, NodeCount, NodeIndex, Receiver,
+    Round, Sender, SessionStatus, Signable, SpawnHandle, TaskHandle, UncheckedSigned,
+    UnitFinalizationHandler,
 };
 use codec::{Decode, Encode};
 use futures::{
     channel::{mpsc, oneshot},
-    future::FusedFuture,
-    pin_mut, FutureExt, StreamExt,
+    pin_mut, Future, FutureExt, StreamExt,
 };
-use futures_timer::Delay;
 use log::{debug, error, info, trace, warn};
-use rand::Rng;
+use rand::{seq::SliceRandom, RngCore};
 use std::{
     cmp::Ordering,
     collections::{BinaryHeap, HashSet},
     convert::TryInto,
-    fmt::Debug,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    panic::AssertUnwindSafe,
     time,
 };
 
@@ -85,6 +91,32 @@ impl<H: Hasher, D: Data, S: Signature> UnitMessage<H, D, S> {
                 .collect(),
         }
     }
+
+    pub(crate) fn units(&self) -> Vec<&UncheckedSignedUnit<H, D, S>> {
+        match self {
+            Self::NewUnit(uu) => vec![uu],
+            Self::RequestCoord(_, _) => Vec::new(),
+            Self::ResponseCoord(uu) => vec![uu],
+            Self::RequestParents(_, _) => Vec::new(),
+            Self::ResponseParents(_, units) => units.iter().collect(),
+            Self::RequestNewest(_, _) => Vec::new(),
+            Self::ResponseNewest(response) => response.as_signable().unit.iter().collect(),
+        }
+    }
+
+    /// The node that sent this message, if the message identifies it. New units are only
+    /// multicast by their creators, while responses with units might come from anyone.
+    pub(crate) fn sender(&self) -> Option<NodeIndex> {
+        match self {
+            Self::NewUnit(uu) => Some(uu.as_signable().creator()),
+            Self::RequestCoord(sender, _) => Some(*sender),
+            Self::ResponseCoord(_) => None,
+            Self::RequestParents(sender, _) => Some(*sender),
+            Self::ResponseParents(_, _) => None,
+            Self::RequestNewest(sender, _) => Some(*sender),
+            Self::ResponseNewest(response) => Some(response.as_signable().responder),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq)]
@@ -141,12 +173,14 @@ where
     not_resolved_parents: HashSet<H::Hash>,
     not_resolved_coords: HashSet<UnitCoord>,
     newest_unit_resolved: bool,
+    catching_up: bool,
     n_members: NodeCount,
     unit_messages_for_network: Sender<(UnitMessage<H, D, S>, Recipient)>,
     unit_messages_from_network: Receiver<UnitMessage<H, D, S>>,
     notifications_for_runway: Sender<RunwayNotificationIn<H, D, S>>,
     notifications_from_runway: Receiver<RunwayNotificationOut<H, D, S>>,
     resolved_requests: Receiver<Request<H>>,
+    rng: Box<dyn RngCore + Send>,
     exiting: bool,
 }
 
@@ -163,6 +197,7 @@ where
         notifications_for_runway: Sender<RunwayNotificationIn<H, D, S>>,
         notifications_from_runway: Receiver<RunwayNotificationOut<H, D, S>>,
         resolved_requests: Receiver<Request<H>>,
+        rng: Box<dyn RngCore + Send>,
     ) -> Self {
         let n_members = config.n_members;
         Self {
@@ -171,18 +206,20 @@ where
             not_resolved_parents: HashSet::new(),
             not_resolved_coords: HashSet::new(),
             newest_unit_resolved: false,
+            catching_up: false,
             n_members,
             unit_messages_for_network,
             unit_messages_from_network,
             notifications_for_runway,
             notifications_from_runway,
             resolved_requests,
+            rng,
             exiting: false,
         }
     }
 
     fn on_create(&mut self, u: UncheckedSignedUnit<H, D, S>) {
-        let curr_time = time::Instant::now();
+        let curr_time = self.config.clock.now();
         let task = ScheduledTask::new(Task::UnitMulticast(u), curr_time);
         self.task_queue.push(task);
     }
@@ -192,7 +229,7 @@ where
         if !self.not_resolved_coords.insert(coord) {
             return;
         }
-        let curr_time = time::Instant::now();
+        let curr_time = self.config.clock.now();
         let task = ScheduledTask::new(Task::CoordRequest(coord), curr_time);
         self.task_queue.push(task);
         self.trigger_tasks();
@@ -202,14 +239,27 @@ where
         if !self.not_resolved_parents.insert(u_hash) {
             return;
         }
-        let curr_time = time::Instant::now();
+        let curr_time = self.config.clock.now();
         let task = ScheduledTask::new(Task::ParentsRequest(u_hash, recipient), curr_time);
         self.task_queue.push(task);
         self.trigger_tasks();
     }
 
+    fn on_creator_stalled(
+        &mut self,
+        round: Round,
+        parents_collected: NodeCount,
+        parents_needed: NodeCount,
+    ) {
+        info!(target: "AlephBFT-member", "{:?} Creator stalled at round {}, collected {:?} parents out of {:?} needed.", self.index(), round, parents_collected.0, parents_needed.0);
+    }
+
+    fn on_creator_lagging(&mut self, backlog: usize) {
+        info!(target: "AlephBFT-member", "{:?} Creator is not keeping up with the Dag, {} units are waiting for it.", self.index(), backlog);
+    }
+
     fn on_request_newest(&mut self, salt: u64) {
-        let curr_time = time::Instant::now();
+        let curr_time = self.config.clock.now();
         let task = ScheduledTask::new(Task::RequestNewest(salt), curr_time);
         self.task_queue.push(task);
         self.trigger_tasks();
@@ -219,15 +269,17 @@ where
     // as long as they are scheduled at time <= curr_time
     fn trigger_tasks(&mut self) {
         while let Some(request) = self.task_queue.peek() {
-            let curr_time = time::Instant::now();
+            let curr_time = self.config.clock.now();
             if request.scheduled_time > curr_time {
                 break;
             }
             let mut request = self.task_queue.pop().expect("The element was peeked");
-            if let Some((message, recipient, delay)) =
+            if let Some((message, recipients, delay)) =
                 self.task_details(&request.task, request.counter)
             {
-                self.send_unit_message(message, recipient);
+                for recipient in recipients {
+                    self.send_unit_message(message.clone(), recipient);
+                }
                 request.scheduled_time += delay;
                 request.counter += 1;
                 self.task_queue.push(request);
@@ -235,10 +287,26 @@ where
         }
     }
 
-    fn random_peer(&self) -> NodeIndex {
-        rand::thread_rng()
-            .gen_range(0..self.n_members.into())
-            .into()
+    // The preferred peer, if any, followed by random other peers, as many as the request fanout
+    // says, or the catch up one while we are catching up, but never ourselves.
+    fn request_peers(&mut self, preferred: Option<NodeIndex>) -> Vec<Recipient> {
+        let me = self.index();
+        let preferred = preferred.filter(|peer| *peer != me);
+        let n_members: usize = self.n_members.into();
+        let fanout = if self.catching_up {
+            self.config.catch_up.fanout
+        } else {
+            self.config.request_config.fanout
+        };
+        let fanout = fanout.min(n_members - 1);
+        let mut peers: Vec<NodeIndex> = preferred.into_iter().collect();
+        let others: Vec<NodeIndex> = (0..n_members)
+            .map(NodeIndex)
+            .filter(|peer| *peer != me && Some(*peer) != preferred)
+            .collect();
+        let n_others = fanout.saturating_sub(peers.len());
+        peers.extend(others.choose_multiple(&mut self.rng, n_others).copied());
+        peers.into_iter().map(Recipient::Node).collect()
     }
 
     fn index(&self) -> NodeIndex {
@@ -257,37 +325,43 @@ where
     }
 
     /// Given a task and the number of times it was performed, returns `None` if the task is no longer active, or
-    /// `Some((message, recipient, delay))` if the task is active and the task is to send `message` to `recipient`
+    /// `Some((message, recipients, delay))` if the task is active and the task is to send `message` to `recipients`
     /// and should be rescheduled after `delay`.
     fn task_details(
         &mut self,
         task: &Task<H, D, S>,
         counter: usize,
-    ) -> Option<(UnitMessage<H, D, S>, Recipient, time::Duration)> {
+    ) -> Option<(UnitMessage<H, D, S>, Vec<Recipient>, time::Duration)> {
         // preferred_recipient is Everyone if the message is supposed to be broadcast,
-        // and Node(node_id) if the message should be send to one peer (node_id when
-        // the task is done for the first time or a random peer otherwise)
-        let (message, preferred_recipient) = match task {
+        // and Node(node_id) if the message should be send to some peers (node_id among them when
+        // the task is done for the first time, random peers otherwise)
+        let request_config = &self.config.request_config;
+        let broadcast_delay = &self.config.delay_config.unit_broadcast_delay;
+        let (message, preferred_recipient, schedule) = match task {
             Task::CoordRequest(coord) => {
                 if !self.not_resolved_coords.contains(coord) {
                     return None;
                 }
                 let message = UnitMessage::RequestCoord(self.index(), *coord);
                 let preferred_recipient = Recipient::Node(coord.creator());
-                (message, preferred_recipient)
+                let schedule = request_config.missing_parent_request_interval.clone();
+                (message, preferred_recipient, schedule)
             }
             Task::ParentsRequest(hash, preferred_recipient) => {
                 if !self.not_resolved_parents.contains(hash) {
                     return None;
                 }
                 let message = UnitMessage::RequestParents(self.index(), *hash);
-                let preferred_recipient = preferred_recipient.clone();
-                (message, preferred_recipient)
+                let schedule = match preferred_recipient {
+                    Recipient::Everyone => broadcast_delay.clone(),
+                    Recipient::Node(_) => request_config.interval.clone(),
+                };
+                (message, preferred_recipient.clone(), schedule)
             }
             Task::UnitMulticast(signed_unit) => {
                 let message = UnitMessage::NewUnit(signed_unit.clone());
                 let preferred_recipient = Recipient::Everyone;
-                (message, preferred_recipient)
+                (message, preferred_recipient, broadcast_delay.clone())
             }
             Task::RequestNewest(salt) => {
                 if self.newest_unit_resolved {
@@ -295,27 +369,21 @@ where
                 }
                 let message = UnitMessage::RequestNewest(self.index(), *salt);
                 let preferred_recipient = Recipient::Everyone;
-                (message, preferred_recipient)
+                (message, preferred_recipient, broadcast_delay.clone())
             }
         };
-        let (recipient, delay) = match preferred_recipient {
-            Recipient::Everyone => (
-                Recipient::Everyone,
-                (self.config.delay_config.unit_broadcast_delay)(counter),
-            ),
+        let recipients = match preferred_recipient {
+            Recipient::Everyone => vec![Recipient::Everyone],
             Recipient::Node(preferred_id) => {
-                let recipient = if counter == 0 {
-                    preferred_id
+                let preferred_id = if counter == 0 {
+                    Some(preferred_id)
                 } else {
-                    self.random_peer()
+                    None
                 };
-                (
-                    Recipient::Node(recipient),
-                    self.config.delay_config.requests_interval,
-                )
+                self.request_peers(preferred_id)
             }
         };
-        Some((message, recipient, delay))
+        Some((message, recipients, schedule(counter)))
     }
 
     fn on_unit_message_from_units(&mut self, message: RunwayNotificationOut<H, D, S>) {
@@ -341,12 +409,19 @@ where
                     self.send_unit_message(message, Recipient::Node(requester))
                 }
             },
+            RunwayNotificationOut::CreatorStalled {
+                round,
+                parents_collected,
+                parents_needed,
+            } => self.on_creator_stalled(round, parents_collected, parents_needed),
+            RunwayNotificationOut::CreatorLagging { backlog } => self.on_creator_lagging(backlog),
+            RunwayNotificationOut::CatchingUp(catching_up) => self.catching_up = catching_up,
         }
     }
 
-    async fn run(mut self, mut exit: oneshot::Receiver<()>) {
+    async fn run(mut self, mut terminator: Terminator) {
         let ticker_delay = self.config.delay_config.tick_interval;
-        let mut ticker = Delay::new(ticker_delay).fuse();
+        let mut ticker = self.config.clock.sleep(ticker_delay).fuse();
 
         loop {
             futures::select! {
@@ -387,10 +462,10 @@ where
 
                 _ = &mut ticker => {
                     self.trigger_tasks();
-                    ticker = Delay::new(ticker_delay).fuse();
+                    ticker = self.config.clock.sleep(ticker_delay).fuse();
                 },
 
-                _ = &mut exit => {
+                _ = terminator.get_exit() => {
                     info!(target: "AlephBFT-member", "{:?} received exit signal", self.index());
                     self.exiting = true;
                 },
@@ -400,6 +475,7 @@ where
                 break;
             }
         }
+        terminator.terminate_sync().await;
         debug!(target: "AlephBFT-member", "{:?} Member stopped.", self.index());
     }
 
@@ -415,8 +491,156 @@ where
     }
 }
 
+/// The way a session ended, if it did not fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionOutcome {
+    /// The session stopped upon the exit signal.
+    Exited,
+    /// The [`DataIO`] was closed and [`Config::data_channel_policy`] is
+    /// [`DataChannelPolicy::ShutdownSession`](crate::DataChannelPolicy::ShutdownSession).
+    DataClosed,
+    /// Our units reached [`Config::max_round`] and [`Config::max_round_policy`] is
+    /// [`MaxRoundPolicy::ShutdownSession`](crate::MaxRoundPolicy::ShutdownSession).
+    Completed,
+}
+
+/// The reason a session stopped without being asked to, see [`run_session`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    /// The network stopped, so we could not talk to the other members anymore.
+    Network,
+    /// Loading the backup failed, it contained invalid units, or saving to it failed.
+    Backup(String),
+    /// The units of the backup break the structure of the Dag from the given round on, so it was
+    /// tampered with or written by a buggy version, see
+    /// [`Config::truncate_inconsistent_backup`].
+    InconsistentBackup { round: Round, reason: String },
+    /// The backup was saved by a session with a different [`Config::session_tag`], so it
+    /// most likely belongs to another session.
+    BackupOfOtherSession { expected: Vec<u8>, found: Vec<u8> },
+    /// The creator was supposed to start at a round not below the maximum round, so no units
+    /// could be created. Usually the maximum round is misconfigured.
+    StartingRoundTooHigh {
+        starting_round: Round,
+        max_round: Round,
+    },
+    /// The [`DataIO`] gave us data for our unit of the given round larger than
+    /// [`Config::max_data_size`], so the other members would reject the unit.
+    DataTooLarge {
+        round: Round,
+        data_size: usize,
+        max_data_size: usize,
+    },
+    /// We received a unit of the given round signed with our key that we did not create, so
+    /// another process is running with our `NodeIndex`, or a previous run of ours without a
+    /// backup created units we do not know about. Continuing would make us fork.
+    DuplicateNodeIndex { round: Round },
+    /// The given member forked in the given round, and [`Config::fork_policy`] is
+    /// [`ForkPolicy::Shutdown`](crate::ForkPolicy::Shutdown).
+    ForkDetected { forker: NodeIndex, round: Round },
+    /// Our units reached [`MAX_MAX_ROUND`], so the maximum round cannot be extended any further
+    /// and no more units can be created. A new session has to be started.
+    RoundLimitReached,
+    /// The [`Hasher`] failed [`check_hasher`](crate::check_hasher), see
+    /// [`Config::check_hasher`].
+    InvalidHasher(HasherError),
+    /// The essential task with the given name panicked or was cancelled.
+    TaskFailed(&'static str),
+    /// The task with the given name panicked with the given message, see
+    /// [`TaskPanicPolicy::Report`].
+    TaskPanicked { task: &'static str, message: String },
+    /// The task with the given name stopped on its own, which should not happen.
+    TaskStopped(&'static str),
+}
+
+impl SessionError {
+    /// The error for an essential task with the given name which finished with `result`.
+    pub(crate) fn task_ended(name: &'static str, result: Result<(), ()>) -> Self {
+        match result {
+            Ok(()) => SessionError::TaskStopped(name),
+            Err(()) => SessionError::TaskFailed(name),
+        }
+    }
+
+    /// A task stopping is usually just a consequence of another task stopping, so we replace it
+    /// with a more specific error we learn about later. The same goes for the network, which
+    /// stops when the runway stops sending alerts.
+    pub(crate) fn or_cause(self, cause: SessionError) -> Self {
+        match (self, cause) {
+            (SessionError::TaskStopped(_), cause) => cause,
+            (SessionError::Network, SessionError::TaskStopped(_)) => SessionError::Network,
+            (SessionError::Network, cause) => cause,
+            (error, _) => error,
+        }
+    }
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SessionError::Network => write!(f, "the network stopped"),
+            SessionError::Backup(e) => write!(f, "the backup failed: {}", e),
+            SessionError::InconsistentBackup { round, reason } => write!(
+                f,
+                "the backup is inconsistent from round {}: {}",
+                round, reason
+            ),
+            SessionError::BackupOfOtherSession { expected, found } => write!(
+                f,
+                "the backup belongs to the session tagged {:?}, not {:?}",
+                found, expected
+            ),
+            SessionError::InvalidHasher(e) => write!(f, "the hasher is invalid: {}", e),
+            SessionError::StartingRoundTooHigh {
+                starting_round,
+                max_round,
+            } => write!(
+                f,
+                "the starting round {} is not below the maximum round {}",
+                starting_round, max_round
+            ),
+            SessionError::DataTooLarge {
+                round,
+                data_size,
+                max_data_size,
+            } => write!(
+                f,
+                "the data for our unit of round {} has {} bytes, more than the limit of {}",
+                round, data_size, max_data_size
+            ),
+            SessionError::DuplicateNodeIndex { round } => write!(
+                f,
+                "received a unit of round {} with our node index that we did not create",
+                round
+            ),
+            SessionError::ForkDetected { forker, round } => {
+                write!(f, "{:?} forked in round {}", forker, round)
+            }
+            SessionError::RoundLimitReached => write!(
+                f,
+                "our units reached the highest possible round {}",
+                MAX_MAX_ROUND
+            ),
+            SessionError::TaskFailed(name) => write!(f, "the task {} failed", name),
+            SessionError::TaskPanicked { task, message } => {
+                write!(f, "the task {} panicked: {}", task, message)
+            }
+            SessionError::TaskStopped(name) => write!(f, "the task {} stopped early", name),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
 /// Starts the consensus algorithm as an async task. It stops establishing consensus for new data items after
-/// reaching the threshold specified in [`Config::max_round`] or upon receiving a stop signal from `exit`.
+/// reaching the threshold specified in [`Config::max_round`], see [`Config::max_round_policy`], or upon
+/// receiving a stop signal from `exit`.
+/// The creation of new units can be temporarily paused and resumed via `creator_control`, without
+/// losing the state of the session. The same channel allows raising the maximum round.
+/// Returns [`SessionOutcome::Exited`] after stopping upon the exit signal,
+/// [`SessionOutcome::DataClosed`] after stopping because the [`DataIO`] was closed,
+/// [`SessionOutcome::Completed`] after stopping at the maximum round, and a
+/// [`SessionError`] telling why the session stopped otherwise.
 /// For a detailed description of the consensus implemented by `run_session` see
 /// [docs for devs](https://cardinal-cryptography.github.io/AlephBFT/index.html)
 /// or the [original paper](https://arxiv.org/abs/1908.05156).
@@ -433,11 +657,288 @@ pub async fn run_session<
     data_io: DP,
     keybox: MK,
     spawn_handle: SH,
-    mut exit: oneshot::Receiver<()>,
-) {
+    creator_control: Receiver<CreatorCommand>,
+    exit: oneshot::Receiver<()>,
+) -> Result<SessionOutcome, SessionError> {
+    run_session_with_unit_handler(
+        config,
+        network,
+        DataIOAdapter(data_io),
+        keybox,
+        spawn_handle,
+        creator_control,
+        exit,
+    )
+    .await
+}
+
+/// Works like [`run_session`], but passes the ordered data to `data_io` together with the units
+/// carrying it, see [`UnitFinalizationHandler`].
+pub async fn run_session_with_unit_handler<
+    H: Hasher,
+    D: Data,
+    DP: UnitFinalizationHandler<H, D>,
+    N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+    SH: SpawnHandle,
+    MK: MultiKeychain,
+>(
+    config: Config,
+    network: N,
+    data_io: DP,
+    keybox: MK,
+    spawn_handle: SH,
+    creator_control: Receiver<CreatorCommand>,
+    exit: oneshot::Receiver<()>,
+) -> Result<SessionOutcome, SessionError> {
+    run_session_with_status(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawn_handle,
+        creator_control,
+        mpsc::unbounded().1,
+        mpsc::unbounded().1,
+        SessionStatus::new(),
+        exit,
+    )
+    .await
+}
+
+/// Works like [`run_session`], but switches to the keychains coming from `new_keychains` at the
+/// rounds they are scheduled for, see [`NewKeychain`]. The units are always verified with the
+/// keychain of their round, so `keybox` has to be the keychain the session started with, also
+/// after a restart, and every later keychain has to be passed again.
+#[allow(clippy::too_many_arguments)]
+pub async fn run_session_with_key_rotation<
+    H: Hasher,
+    D: Data,
+    DP: DataIO<D>,
+    N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+    SH: SpawnHandle,
+    MK: MultiKeychain,
+>(
+    config: Config,
+    network: N,
+    data_io: DP,
+    keybox: MK,
+    spawn_handle: SH,
+    creator_control: Receiver<CreatorCommand>,
+    new_keychains: Receiver<NewKeychain<MK>>,
+    exit: oneshot::Receiver<()>,
+) -> Result<SessionOutcome, SessionError> {
+    run_session_with_status(
+        config,
+        network,
+        DataIOAdapter(data_io),
+        keybox,
+        spawn_handle,
+        creator_control,
+        new_keychains,
+        mpsc::unbounded().1,
+        SessionStatus::new(),
+        exit,
+    )
+    .await
+}
+
+/// Spawns [`run_session`] as an essential task via `spawn_handle`. Returns the handle of the task,
+/// together with a [`SessionStatus`] showing the progress of the session. The handle resolves to
+/// an error if the session ends with a [`SessionError`], which is logged.
+pub fn spawn_session<
+    H: Hasher,
+    D: Data,
+    DP: DataIO<D> + Send + 'static,
+    N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+    SH: SpawnHandle,
+    MK: MultiKeychain,
+>(
+    config: Config,
+    network: N,
+    data_io: DP,
+    keybox: MK,
+    spawn_handle: SH,
+    creator_control: Receiver<CreatorCommand>,
+    exit: oneshot::Receiver<()>,
+) -> (TaskHandle, SessionStatus) {
+    let status = SessionStatus::new();
+    let session = run_session_with_status(
+        config,
+        network,
+        DataIOAdapter(data_io),
+        keybox,
+        spawn_handle.clone(),
+        creator_control,
+        mpsc::unbounded().1,
+        mpsc::unbounded().1,
+        status.clone(),
+        exit,
+    );
+    (spawn_session_task(&spawn_handle, session), status)
+}
+
+/// Spawns `session` as an essential task, with a handle resolving to an error if it fails.
+pub(crate) fn spawn_session_task<SH: SpawnHandle>(
+    spawn_handle: &SH,
+    session: impl Future<Output = Result<SessionOutcome, SessionError>> + Send + 'static,
+) -> TaskHandle {
+    let (result_tx, result_rx) = oneshot::channel();
+    let task = spawn_handle.spawn_essential("member/session", async move {
+        let _ = result_tx.send(session.await);
+    });
+    Box::pin(async move {
+        task.await?;
+        match result_rx.await {
+            Ok(Ok(_)) => Ok(()),
+            // The result is only missing if the task did not finish, which the spawner should report.
+            _ => Err(()),
+        }
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run_session_with_status<
+    H: Hasher,
+    D: Data,
+    DP: UnitFinalizationHandler<H, D>,
+    N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+    SH: SpawnHandle,
+    MK: MultiKeychain,
+>(
+    config: Config,
+    network: N,
+    data_io: DP,
+    keybox: MK,
+    spawn_handle: SH,
+    creator_control: Receiver<CreatorCommand>,
+    new_keychains: Receiver<NewKeychain<MK>>,
+    new_networks: Receiver<N>,
+    status: SessionStatus,
+    exit: oneshot::Receiver<()>,
+) -> Result<SessionOutcome, SessionError> {
+    let catch_panics = config.task_panic_policy == TaskPanicPolicy::Report;
+    let spawn_handle = PanicCatchingSpawner::new(spawn_handle, catch_panics);
+    let session = run_session_tasks(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawn_handle.clone(),
+        creator_control,
+        new_keychains,
+        new_networks,
+        status,
+        exit,
+    );
+    if !catch_panics {
+        return session.await;
+    }
+    // The runway and the member run within the session itself, so their panics reach us here.
+    let result = match AssertUnwindSafe(session).catch_unwind().await {
+        Ok(result) => result,
+        Err(payload) => {
+            spawn_handle.on_panic("member/session", &*payload);
+            Err(SessionError::TaskStopped("member/session"))
+        }
+    };
+    // Whatever else went wrong was most likely caused by the panic.
+    match (result, spawn_handle.first_panic()) {
+        (Err(_), Some(TaskPanic { task, message })) => {
+            Err(SessionError::TaskPanicked { task, message })
+        }
+        (result, _) => result,
+    }
+}
+
+/// Spawns the network hub, which sends its channels back through the returned receiver once it
+/// stops, so that another hub can take over.
+#[allow(clippy::type_complexity)]
+fn spawn_network_hub<
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    N: Network<H, D, S, MS> + 'static,
+    SH: SpawnHandle,
+>(
+    spawn_handle: &SH,
+    config: &Config,
+    network: N,
+    network_io: NetworkHubIO<H, D, S, MS>,
+    terminator: Terminator,
+) -> (TaskHandle, oneshot::Receiver<NetworkHubIO<H, D, S, MS>>) {
+    let (network_io_tx, network_io_rx) = oneshot::channel();
+    let hub = network::run(
+        network,
+        network_io,
+        config.max_data_size,
+        config.unit_rejection_sink.clone(),
+        config.unit_batching,
+        config.clock.clone(),
+        terminator,
+    );
+    let handle = spawn_handle.spawn_essential(
+        "member/network",
+        in_span("network", config.node_ix, config.session_id, async move {
+            let _ = network_io_tx.send(hub.await);
+        }),
+    );
+    (handle, network_io_rx)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_session_tasks<
+    H: Hasher,
+    D: Data,
+    DP: UnitFinalizationHandler<H, D>,
+    N: Network<H, D, MK::Signature, MK::PartialMultisignature> + 'static,
+    SH: SpawnHandle,
+    MK: MultiKeychain,
+>(
+    mut config: Config,
+    network: N,
+    data_io: DP,
+    keybox: MK,
+    spawn_handle: SH,
+    creator_control: Receiver<CreatorCommand>,
+    new_keychains: Receiver<NewKeychain<MK>>,
+    new_networks: Receiver<N>,
+    status: SessionStatus,
+    exit: oneshot::Receiver<()>,
+) -> Result<SessionOutcome, SessionError> {
     let index = config.node_ix;
+    let mut terminator = Terminator::create_root(exit, "session");
+    if config.check_hasher {
+        if let Err(e) = check_hasher::<H>() {
+            error!(target: "AlephBFT-member", "{:?} The hasher is invalid: {}.", index, e);
+            return Err(SessionError::InvalidHasher(e));
+        }
+    }
+    if config.n_members == NodeCount(1) {
+        warn!(target: "AlephBFT-member", "{:?} Running as the only member, which orders its own data without any consensus. This is only meant for development.", index);
+    } else if config.n_members < MIN_FAULT_TOLERANT_MEMBERS {
+        warn!(target: "AlephBFT-member", "{:?} The committee has only {} members, so a single faulty member stops the consensus. Committees need at least {} members to tolerate faults.", index, config.n_members.0, MIN_FAULT_TOLERANT_MEMBERS.0);
+    }
+    // The config might not come from the builder, which rejects it.
+    if config.max_round > MAX_MAX_ROUND {
+        warn!(target: "AlephBFT-member", "{:?} The maximum round {} is above the limit of {}, using the limit instead.", index, config.max_round, MAX_MAX_ROUND);
+        config.max_round = MAX_MAX_ROUND;
+    }
+    #[cfg(feature = "trusted")]
+    warn!(target: "AlephBFT-member", "{:?} Built with the `trusted` feature, signatures are NOT verified. Anyone with access to the network can impersonate any member, never use it in a real network.", index);
     info!(target: "AlephBFT-member", "{:?} Spawning party for a session.", index);
 
+    let keybox = RotatingKeychain::new(keybox);
+    spawn_handle.spawn(
+        "member/rotation",
+        rotation::run(
+            keybox.clone(),
+            new_keychains,
+            config.session_id,
+            terminator.add_offspring_connection("member/rotation"),
+        ),
+    );
+
     let (alert_messages_for_alerter, alert_messages_from_network) = mpsc::unbounded();
     let (alert_messages_for_network, alert_messages_from_alerter) = mpsc::unbounded();
     let (unit_messages_for_units, unit_messages_from_network) = mpsc::unbounded();
@@ -447,31 +948,36 @@ pub async fn run_session<
     let (resolved_requests_tx, resolved_requests_rx) = mpsc::unbounded();
 
     info!(target: "AlephBFT-member", "{:?} Spawning network.", index);
-    let (network_exit, exit_stream) = oneshot::channel();
-
-    let network_handle = spawn_handle.spawn_essential("member/network", async move {
-        network::run(
-            network,
-            unit_messages_from_units,
-            unit_messages_for_units,
-            alert_messages_from_alerter,
-            alert_messages_for_alerter,
-            exit_stream,
-        )
-        .await
-    });
+    let network_io = NetworkHubIO {
+        units_to_send: unit_messages_from_units,
+        units_received: unit_messages_for_units,
+        alerts_to_send: alert_messages_from_alerter,
+        alerts_received: alert_messages_for_alerter,
+    };
+    let (network_handle, mut returned_network_io) = spawn_network_hub(
+        &spawn_handle,
+        &config,
+        network,
+        network_io,
+        terminator.add_offspring_connection("member/network"),
+    );
     let network_handle = network_handle.fuse();
     pin_mut!(network_handle);
+    let mut new_networks = new_networks.fuse();
     info!(target: "AlephBFT-member", "{:?} Network spawned.", index);
 
     info!(target: "AlephBFT-member", "{:?} Initializing Runway.", index);
-    let (runway_exit, exit_stream) = oneshot::channel();
+    // All the random choices of the session come from this one generator.
+    let mut rng = session_rng(&config);
+    let salt = rng.next_u64();
     let runway_io = RunwayIO {
         alert_messages_for_network,
         alert_messages_from_network,
         unit_messages_from_network: runway_messages_from_network,
         unit_messages_for_network: runway_messages_for_network,
         resolved_requests: resolved_requests_tx,
+        creator_control,
+        status,
     };
     let runway_handle = runway::run(
         config.clone(),
@@ -479,66 +985,310 @@ pub async fn run_session<
         data_io,
         spawn_handle.clone(),
         runway_io,
-        exit_stream,
+        salt,
+        terminator.add_offspring_connection("runway"),
     );
     let runway_handle = runway_handle.fuse();
     pin_mut!(runway_handle);
     info!(target: "AlephBFT-member", "{:?} Runway initialized.", index);
 
     info!(target: "AlephBFT-member", "{:?} Initializing Member.", index);
+    let session_id = config.session_id;
     let member = Member::new(
-        config,
+        config.clone(),
         unit_messages_for_network,
         unit_messages_from_network,
         runway_messages_for_runway,
         runway_messages_from_runway,
         resolved_requests_rx,
+        rng,
     );
-    let (member_exit, exit_stream) = oneshot::channel();
-    let member_handle = member.run(exit_stream).fuse();
+    let member_terminator = terminator.add_offspring_connection("member");
+    let member_handle = in_span("member", index, session_id, member.run(member_terminator)).fuse();
     pin_mut!(member_handle);
     info!(target: "AlephBFT-member", "{:?} Member initialized.", index);
 
-    futures::select! {
-        _ = network_handle => {
-            error!(target: "AlephBFT-member", "{:?} Network-hub terminated early.", index);
-        },
+    let mut result = loop {
+        futures::select! {
+            result = network_handle => {
+                error!(target: "AlephBFT-member", "{:?} Network-hub terminated early.", index);
+                break match result {
+                    Ok(()) => Err(SessionError::Network),
+                    Err(()) => Err(SessionError::TaskFailed("member/network")),
+                };
+            },
+
+            // Only the network hub is restarted, the other tasks keep their channels to it.
+            network = new_networks.next() => if let Some(network) = network {
+                info!(target: "AlephBFT-member", "{:?} Replacing the network.", index);
+                if let Some(Err(())) = terminator
+                    .terminate_offspring("member/network", &mut network_handle)
+                    .await
+                {
+                    error!(target: "AlephBFT-member", "{:?} Network-hub failed while being replaced.", index);
+                    break Err(SessionError::TaskFailed("member/network"));
+                }
+                let network_io = match (&mut returned_network_io).await {
+                    Ok(network_io) => network_io,
+                    Err(_) => {
+                        error!(target: "AlephBFT-member", "{:?} Network-hub stopped without returning its channels.", index);
+                        break Err(SessionError::TaskFailed("member/network"));
+                    }
+                };
+                let (handle, network_io) = spawn_network_hub(
+                    &spawn_handle,
+                    &config,
+                    network,
+                    network_io,
+                    terminator.add_offspring_connection("member/network"),
+                );
+                network_handle.set(handle.fuse());
+                returned_network_io = network_io;
+                info!(target: "AlephBFT-member", "{:?} Network replaced.", index);
+            },
 
-        _ = runway_handle => {
-            error!(target: "AlephBFT-member", "{:?} Runway terminated early.", index);
-        },
+            result = runway_handle => break match result {
+                Ok(SessionOutcome::DataClosed) => {
+                    info!(target: "AlephBFT-member", "{:?} Runway stopped, as the data provider closed.", index);
+                    Ok(SessionOutcome::DataClosed)
+                }
+                Ok(SessionOutcome::Completed) => {
+                    info!(target: "AlephBFT-member", "{:?} Runway stopped at the maximum round.", index);
+                    Ok(SessionOutcome::Completed)
+                }
+                result => {
+                    error!(target: "AlephBFT-member", "{:?} Runway terminated early.", index);
+                    Err(result.err().unwrap_or(SessionError::TaskStopped("runway")))
+                }
+            },
+
+            _ = member_handle => {
+                error!(target: "AlephBFT-member", "{:?} Member terminated early.", index);
+                break Err(SessionError::TaskStopped("member"));
+            },
 
-        _ = member_handle => {
-            error!(target: "AlephBFT-member", "{:?} Member terminated early.", index);
-        },
+            _ = terminator.get_exit() => {
+                info!(target: "AlephBFT-member", "{:?} exit channel was called.", index);
+                break Ok(SessionOutcome::Exited);
+            },
+        }
+    };
 
-        _ = &mut exit => {
-            info!(target: "AlephBFT-member", "{:?} exit channel was called.", index);
-        },
+    // The runway stops the creator first, and only then itself, so the last units make it to the
+    // backup, and the network hub stops last, so the others never find their channels closed.
+    match terminator
+        .terminate_offspring("runway", &mut runway_handle)
+        .await
+    {
+        Some(Err(error)) => result = result.map_err(|e| e.or_cause(error)),
+        // Stopping the runway on a closed data provider or at the maximum round takes down the
+        // other tasks, which may be noticed before the runway finishes.
+        Some(Ok(SessionOutcome::DataClosed)) => {
+            info!(target: "AlephBFT-member", "{:?} Runway stopped, as the data provider closed.", index);
+            result = Ok(SessionOutcome::DataClosed);
+        }
+        Some(Ok(SessionOutcome::Completed)) => {
+            info!(target: "AlephBFT-member", "{:?} Runway stopped at the maximum round.", index);
+            result = Ok(SessionOutcome::Completed);
+        }
+        Some(Ok(SessionOutcome::Exited)) | None => {}
     }
 
-    if runway_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-member", "{:?} Runway already stopped.", index);
+    terminator
+        .terminate_offspring("member", &mut member_handle)
+        .await;
+
+    let network_stopped = terminator.has_stopped("member/network");
+    if let Some(network_result) = terminator
+        .terminate_offspring("member/network", &mut network_handle)
+        .await
+    {
+        if let Err(()) = network_result {
+            warn!(target: "AlephBFT-member", "{:?} Network task stopped with an error", index);
+        }
+        if network_stopped || network_result.is_err() {
+            let error = match network_result {
+                Ok(()) => SessionError::Network,
+                Err(()) => SessionError::TaskFailed("member/network"),
+            };
+            result = result.map_err(|e| e.or_cause(error));
+        }
     }
-    if !runway_handle.is_terminated() {
-        runway_handle.await;
+    // Only the keychain rotation is left.
+    terminator.terminate_sync().await;
+
+    match &result {
+        Ok(_) => info!(target: "AlephBFT-member", "{:?} Run ended.", index),
+        Err(e) => error!(target: "AlephBFT-member", "{:?} Run ended: {}.", index, e),
     }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Member, SessionError, Task, UnitMessage};
+    use crate::{
+        config::{constant_schedule, exponential_schedule, session_rng, RequestConfig},
+        network::Recipient,
+        runway::{Request, RunwayNotificationOut},
+        testing::mock::{gen_config, Data, Hasher64, Signature},
+        units::UnitCoord,
+        Config, MockClock, NodeCount, NodeIndex,
+    };
+    use futures::channel::mpsc;
+    use std::{collections::HashSet, sync::Arc, time::Duration};
 
-    if member_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-member", "{:?} Member already stopped.", index);
+    type UnitMessages =
+        mpsc::UnboundedReceiver<(UnitMessage<Hasher64, Data, Signature>, Recipient)>;
+
+    fn member(fanout: usize) -> Member<Hasher64, Data, Signature> {
+        let mut config = gen_config(NodeIndex(0), NodeCount(7));
+        config.request_config = RequestConfig {
+            fanout,
+            interval: constant_schedule(Duration::from_millis(200)),
+            missing_parent_request_interval: constant_schedule(Duration::from_millis(200)),
+        };
+        member_with_config(config).0
     }
-    if !member_handle.is_terminated() {
-        member_handle.await;
+
+    fn member_with_config(config: Config) -> (Member<Hasher64, Data, Signature>, UnitMessages) {
+        let (unit_messages_for_network, unit_messages) = mpsc::unbounded();
+        let (_, unit_messages_from_network) = mpsc::unbounded();
+        let (notifications_for_runway, _) = mpsc::unbounded();
+        let (_, notifications_from_runway) = mpsc::unbounded();
+        let (_, resolved_requests) = mpsc::unbounded();
+        let rng = session_rng(&config);
+        let member = Member::new(
+            config,
+            unit_messages_for_network,
+            unit_messages_from_network,
+            notifications_for_runway,
+            notifications_from_runway,
+            resolved_requests,
+            rng,
+        );
+        (member, unit_messages)
     }
 
-    if network_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-member", "{:?} Network-hub already stopped.", index);
+    fn peers(recipients: Vec<Recipient>) -> Vec<NodeIndex> {
+        recipients
+            .into_iter()
+            .map(|recipient| match recipient {
+                Recipient::Node(peer) => peer,
+                Recipient::Everyone => panic!("a request should not be broadcast"),
+            })
+            .collect()
     }
-    if !network_handle.is_terminated() {
-        if let Err(()) = network_handle.await {
-            warn!(target: "AlephBFT-member", "{:?} Network task stopped with an error", index);
+
+    #[test]
+    fn requests_fan_out_to_other_members() {
+        let mut member = member(3);
+        let coord = UnitCoord::new(4, NodeIndex(5));
+        member.not_resolved_coords.insert(coord);
+        let task = Task::CoordRequest(coord);
+        for counter in 0..20 {
+            let (_, recipients, delay) = member
+                .task_details(&task, counter)
+                .expect("the coord is not resolved");
+            let peers = peers(recipients);
+            let distinct: HashSet<_> = peers.iter().collect();
+            assert_eq!(peers.len(), 3);
+            assert_eq!(distinct.len(), 3);
+            assert!(!peers.contains(&NodeIndex(0)));
+            if counter == 0 {
+                assert_eq!(peers[0], NodeIndex(5));
+            }
+            assert_eq!(delay, Duration::from_millis(200));
         }
     }
 
-    info!(target: "AlephBFT-member", "{:?} Run ended.", index);
+    #[test]
+    fn request_fanout_is_capped_at_other_members() {
+        let mut member = member(100);
+        let coord = UnitCoord::new(4, NodeIndex(5));
+        member.not_resolved_coords.insert(coord);
+        let (_, recipients, _) = member
+            .task_details(&Task::CoordRequest(coord), 1)
+            .expect("the coord is not resolved");
+        let peers: HashSet<_> = peers(recipients).into_iter().collect();
+        assert_eq!(peers, (1..7).map(NodeIndex).collect());
+    }
+
+    #[test]
+    fn requests_fan_out_wider_while_catching_up() {
+        let mut member = member(1);
+        member.config.catch_up.fanout = 4;
+        let coord = UnitCoord::new(4, NodeIndex(5));
+        member.not_resolved_coords.insert(coord);
+        let task = Task::CoordRequest(coord);
+        let n_peers = |member: &mut Member<_, _, _>| {
+            let (_, recipients, _) = member
+                .task_details(&task, 1)
+                .expect("the coord is not resolved");
+            recipients.len()
+        };
+        assert_eq!(n_peers(&mut member), 1);
+        member.on_unit_message_from_units(RunwayNotificationOut::CatchingUp(true));
+        assert_eq!(n_peers(&mut member), 4);
+        member.on_unit_message_from_units(RunwayNotificationOut::CatchingUp(false));
+        assert_eq!(n_peers(&mut member), 1);
+    }
+
+    #[test]
+    fn asks_again_for_a_missing_parent_backing_off() {
+        let clock = MockClock::new();
+        let mut config = gen_config(NodeIndex(0), NodeCount(7));
+        config.clock = Arc::new(clock.clone());
+        config.request_config.missing_parent_request_interval =
+            exponential_schedule(Duration::from_millis(100), 2.0, Duration::from_millis(400));
+        let (mut member, mut unit_messages) = member_with_config(config);
+        let coord = UnitCoord::new(4, NodeIndex(5));
+        let mut n_requests = || {
+            let mut n_requests = 0;
+            while let Ok((message, _)) = unit_messages.try_recv() {
+                assert!(matches!(message, UnitMessage::RequestCoord(_, c) if c == coord));
+                n_requests += 1;
+            }
+            n_requests
+        };
+
+        member.on_unit_message_from_units(RunwayNotificationOut::Request(
+            Request::Coord(coord),
+            Recipient::Node(coord.creator()),
+        ));
+        assert_eq!(n_requests(), 1);
+        // The responses never come, and every request waits twice as long as the previous one,
+        // until the delay reaches its maximum.
+        for delay in [100, 200, 400, 400] {
+            clock.advance(Duration::from_millis(delay - 1));
+            member.trigger_tasks();
+            assert_eq!(n_requests(), 0);
+            clock.advance(Duration::from_millis(1));
+            member.trigger_tasks();
+            assert_eq!(n_requests(), 1);
+        }
+
+        member.not_resolved_coords.remove(&coord);
+        clock.advance(Duration::from_millis(400));
+        member.trigger_tasks();
+        assert_eq!(n_requests(), 0);
+    }
+
+    #[test]
+    fn prefers_the_cause_of_stopping() {
+        let cause = SessionError::DuplicateNodeIndex { round: 3 };
+        assert_eq!(
+            SessionError::TaskStopped("member").or_cause(cause.clone()),
+            cause
+        );
+        assert_eq!(SessionError::Network.or_cause(cause.clone()), cause);
+        assert_eq!(
+            SessionError::Network.or_cause(SessionError::TaskStopped("runway")),
+            SessionError::Network
+        );
+        assert_eq!(
+            SessionError::Backup("the disk is gone".into()).or_cause(cause),
+            SessionError::Backup("the disk is gone".into())
+        );
+    }
 }
@@ -1,14 +1,43 @@
 use crate::{
     alerts::AlertMessage,
+    clock::Clock,
+    config::UnitBatchingConfig,
     member::UnitMessage,
     nodes::NodeIndex,
     signed::{PartialMultisignature, Signature},
-    Data, Hasher, Receiver, Sender,
+    terminator::Terminator,
+    units::{UncheckedSignedUnit, UnitCoord, UnitRejection, UnitRejectionReason},
+    Data, Hasher, Receiver, Round, Sender,
 };
 use codec::{Decode, Encode};
-use futures::{channel::oneshot, FutureExt, StreamExt};
-use log::{error, info, warn};
-use std::fmt::Debug;
+use futures::{
+    future::{BoxFuture, Fuse},
+    FutureExt, StreamExt,
+};
+use log::{debug, error, info, trace, warn};
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+#[cfg(feature = "compression")]
+mod compression;
+#[cfg(feature = "network-loopback")]
+mod loopback;
+mod message_codec;
+mod peer_status;
+mod rate_limit;
+mod sessions;
+#[cfg(feature = "network-tcp")]
+mod tcp;
+
+#[cfg(feature = "compression")]
+pub use compression::{compress_payload, decompress_payload, DecompressionError};
+#[cfg(feature = "network-loopback")]
+pub use loopback::LoopbackNetwork;
+pub use message_codec::{NetworkCodec, ScaleCodec};
+pub use peer_status::{PeerStatus, PeerStatusHandle, PeerTrackingNetwork};
+pub use rate_limit::RateLimitedNetwork;
+pub use sessions::{MultiplexedNetwork, SessionData, SessionMultiplexer, SessionNetwork};
+#[cfg(feature = "network-tcp")]
+pub use tcp::TcpNetwork;
 
 /// A recipient of a message, either a specific node or everyone.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -38,6 +67,9 @@ pub enum Recipient {
 ///
 /// We refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/aleph_bft_api.html
 /// Section 3.1.2 for a discussion of the required guarantees of this trait's implementation.
+///
+/// Implementations sending bytes might use a [`NetworkCodec`] to encode the data, so that the
+/// encoding can be chosen by the user, with the [`ScaleCodec`] as the default.
 #[async_trait::async_trait]
 pub trait Network<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature>: Send {
     /// Send a message to a single node or everyone, depending on the value of the recipient
@@ -54,6 +86,47 @@ pub trait Network<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature>:
 pub(crate) enum NetworkDataInner<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> {
     Units(UnitMessage<H, D, S>),
     Alert(AlertMessage<H, D, S, MS>),
+    // Several unit messages to the same recipient, see [`Config::unit_batching`](crate::Config::unit_batching).
+    // It comes last, so that the encodings of the other messages did not change.
+    UnitBatch(Vec<UnitMessage<H, D, S>>),
+}
+
+// The encoded indices of the variants carrying a single unit, see [`NetworkData::unit_header`].
+// They follow the order of the variants of `NetworkDataInner` and `UnitMessage`.
+const UNITS_INDEX: u8 = 0;
+const NEW_UNIT_INDEX: u8 = 0;
+const RESPONSE_COORD_INDEX: u8 = 2;
+
+/// The header of a unit carried by an encoded [`NetworkData`], read without decoding the data
+/// of the unit. It borrows from the received bytes, so checking it allocates nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnitHeader<'a> {
+    creator: NodeIndex,
+    round: Round,
+    broadcast: bool,
+    encoded: &'a [u8],
+}
+
+impl<'a> UnitHeader<'a> {
+    pub fn creator(&self) -> NodeIndex {
+        self.creator
+    }
+
+    pub fn round(&self) -> Round {
+        self.round
+    }
+
+    /// Whether the unit was broadcast by its creator, as opposed to sent in a response to
+    /// a request of ours.
+    pub fn is_broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// The encoding of the signed unit. Two messages carry the same unit with the same signature
+    /// if and only if these are equal.
+    pub fn encoded(&self) -> &'a [u8] {
+        self.encoded
+    }
 }
 
 impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> NetworkDataInner<H, D, S, MS> {
@@ -61,6 +134,26 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> NetworkDataInn
         match self {
             Self::Units(message) => message.included_data(),
             Self::Alert(message) => message.included_data(),
+            Self::UnitBatch(messages) => messages
+                .iter()
+                .flat_map(UnitMessage::included_data)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn sender(&self) -> Option<NodeIndex> {
+        match self {
+            Self::Units(message) => message.sender(),
+            Self::Alert(message) => message.sender(),
+            Self::UnitBatch(messages) => messages.iter().find_map(UnitMessage::sender),
+        }
+    }
+
+    pub(crate) fn units(&self) -> Vec<&UncheckedSignedUnit<H, D, S>> {
+        match self {
+            Self::Units(message) => message.units(),
+            Self::Alert(message) => message.units(),
+            Self::UnitBatch(messages) => messages.iter().flat_map(UnitMessage::units).collect(),
         }
     }
 }
@@ -107,9 +200,38 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> NetworkData<H,
     pub fn included_data(&self) -> Vec<D> {
         self.0.included_data()
     }
+
+    /// Reads the header of the unit in an encoded message carrying a single unit, without
+    /// decoding the whole message, so that networks can drop unwanted units cheaply. Returns
+    /// `None` for all the other messages. A header does not mean the message decodes correctly.
+    pub fn unit_header(encoded: &[u8]) -> Option<UnitHeader<'_>> {
+        let broadcast = match encoded {
+            [UNITS_INDEX, NEW_UNIT_INDEX, ..] => true,
+            [UNITS_INDEX, RESPONSE_COORD_INDEX, ..] => false,
+            _ => return None,
+        };
+        // The signed unit is the last field of these messages, and it starts with the coord.
+        let encoded = &encoded[2..];
+        let coord = UnitCoord::decode(&mut &encoded[..]).ok()?;
+        Some(UnitHeader {
+            creator: coord.creator(),
+            round: coord.round(),
+            broadcast,
+            encoded,
+        })
+    }
 }
 
-struct NetworkHub<
+/// The channels connecting the network hub to the rest of the session. They outlive the hub, so
+/// that it can be restarted with another network without the other tasks noticing.
+pub(crate) struct NetworkHubIO<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> {
+    pub(crate) units_to_send: Receiver<(UnitMessage<H, D, S>, Recipient)>,
+    pub(crate) units_received: Sender<UnitMessage<H, D, S>>,
+    pub(crate) alerts_to_send: Receiver<(AlertMessage<H, D, S, MS>, Recipient)>,
+    pub(crate) alerts_received: Sender<AlertMessage<H, D, S, MS>>,
+}
+
+pub(crate) struct NetworkHub<
     H: Hasher,
     D: Data,
     S: Signature,
@@ -121,17 +243,29 @@ struct NetworkHub<
     units_received: Sender<UnitMessage<H, D, S>>,
     alerts_to_send: Receiver<(AlertMessage<H, D, S, MS>, Recipient)>,
     alerts_received: Sender<AlertMessage<H, D, S, MS>>,
+    max_data_size: Option<usize>,
+    unit_rejection_sink: Option<Sender<UnitRejection>>,
+    unit_batching: Option<UnitBatchingConfig>,
+    clock: Arc<dyn Clock>,
+    // The unit messages waiting for the end of the batching window, by recipient.
+    pending_batches: HashMap<Recipient, Vec<UnitMessage<H, D, S>>>,
+    batching_window: Fuse<BoxFuture<'static, ()>>,
 }
 
 impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, N: Network<H, D, S, MS>>
     NetworkHub<H, D, S, MS, N>
 {
-    fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
         network: N,
         units_to_send: Receiver<(UnitMessage<H, D, S>, Recipient)>,
         units_received: Sender<UnitMessage<H, D, S>>,
         alerts_to_send: Receiver<(AlertMessage<H, D, S, MS>, Recipient)>,
         alerts_received: Sender<AlertMessage<H, D, S, MS>>,
+        max_data_size: Option<usize>,
+        unit_rejection_sink: Option<Sender<UnitRejection>>,
+        unit_batching: Option<UnitBatchingConfig>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
         NetworkHub {
             network,
@@ -139,6 +273,12 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, N: Network<H,
             units_received,
             alerts_to_send,
             alerts_received,
+            max_data_size,
+            unit_rejection_sink,
+            unit_batching,
+            clock,
+            pending_batches: HashMap::new(),
+            batching_window: Fuse::terminated(),
         }
     }
 
@@ -146,8 +286,79 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, N: Network<H,
         self.network.send(data, recipient);
     }
 
+    /// Sends the unit message, or adds it to the batch of its recipient if batching is enabled.
+    /// The first message waiting for a batch starts the window, at the end of which all the
+    /// batches are sent, and a batch which gets full is sent right away.
+    pub(crate) fn send_units(&mut self, message: UnitMessage<H, D, S>, recipient: Recipient) {
+        let unit_batching = match self.unit_batching {
+            Some(unit_batching) => unit_batching,
+            None => return self.send(NetworkData(NetworkDataInner::Units(message)), recipient),
+        };
+        if self.pending_batches.is_empty() {
+            self.batching_window = self.clock.sleep(unit_batching.window).fuse();
+        }
+        let batch = self.pending_batches.entry(recipient.clone()).or_default();
+        batch.push(message);
+        if batch.len() >= unit_batching.max_batch_size {
+            if let Some(batch) = self.pending_batches.remove(&recipient) {
+                self.send_batch(batch, recipient);
+            }
+        }
+    }
+
+    // A batch of a single message is sent as the message, so that it can be handled the same way
+    // as without batching, e.g. recognized by `NetworkData::unit_header`.
+    fn send_batch(&self, mut batch: Vec<UnitMessage<H, D, S>>, recipient: Recipient) {
+        let data = match batch.len() {
+            0 => return,
+            1 => NetworkDataInner::Units(batch.remove(0)),
+            _ => NetworkDataInner::UnitBatch(batch),
+        };
+        self.send(NetworkData(data), recipient);
+    }
+
+    /// Sends all the batches waiting for the end of the window.
+    pub(crate) fn flush_batches(&mut self) {
+        for (recipient, batch) in std::mem::take(&mut self.pending_batches) {
+            trace!(target: "AlephBFT-network-hub", "Sending a batch of {} unit messages to {:?}.", batch.len(), recipient);
+            self.send_batch(batch, recipient);
+        }
+    }
+
+    // Units with too much data are dropped before anyone spends time on them. Their creators
+    // signed them, so they misbehave, but the message might have been passed on by someone else.
+    fn is_oversized(&self, network_data: &NetworkDataInner<H, D, S, MS>) -> bool {
+        let max_data_size = match self.max_data_size {
+            Some(max_data_size) => max_data_size,
+            None => return false,
+        };
+        for uu in network_data.units() {
+            let full_unit = uu.as_signable();
+            let data_size = full_unit.data().encoded_size();
+            if data_size > max_data_size {
+                warn!(target: "AlephBFT-network-hub", "Dropping a message from {:?} with a unit of {:?} carrying {} bytes of data, more than the limit of {}.", network_data.sender(), full_unit.creator(), data_size, max_data_size);
+                if let Some(sink) = &self.unit_rejection_sink {
+                    let rejection = UnitRejection {
+                        creator: full_unit.creator(),
+                        round: full_unit.round(),
+                        sender: network_data.sender(),
+                        reason: UnitRejectionReason::TooLarge(data_size),
+                    };
+                    if sink.unbounded_send(rejection).is_err() {
+                        trace!(target: "AlephBFT-network-hub", "Unit rejection channel closed.");
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
     fn handle_incoming(&self, network_data: NetworkData<H, D, S, MS>) {
         let NetworkData(network_data) = network_data;
+        if self.is_oversized(&network_data) {
+            return;
+        }
         use NetworkDataInner::*;
         match network_data {
             Units(unit_message) => {
@@ -156,6 +367,15 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, N: Network<H,
                 }
             }
 
+            UnitBatch(unit_messages) => {
+                for unit_message in unit_messages {
+                    if let Err(e) = self.units_received.unbounded_send(unit_message) {
+                        warn!(target: "AlephBFT-network-hub", "Error when sending units to consensus {:?}", e);
+                        break;
+                    }
+                }
+            }
+
             Alert(alert_message) => {
                 if let Err(e) = self.alerts_received.unbounded_send(alert_message) {
                     warn!(target: "AlephBFT-network-hub", "Error when sending alerts to consensus {:?}", e);
@@ -164,16 +384,19 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, N: Network<H,
         }
     }
 
-    async fn run(mut self, mut exit: oneshot::Receiver<()>) {
+    /// Runs until told to stop or until any of its channels closes, and then gives the channels
+    /// back. The unit messages waiting for a batch and the queued alerts are sent first, so that
+    /// none of the messages taken from the channels are lost when the hub is replaced. Only the
+    /// outgoing units closing does not stop it, as the alerter might still have alerts to send.
+    async fn run(mut self, mut terminator: Terminator) -> NetworkHubIO<H, D, S, MS> {
         loop {
             use NetworkDataInner::*;
             futures::select! {
                 unit_message = self.units_to_send.next() => match unit_message {
-                    Some((unit_message, recipient)) => self.send(NetworkData(Units(unit_message)), recipient),
-                    None => {
-                        error!(target: "AlephBFT-network-hub", "Outgoing units stream closed.");
-                        break;
-                    }
+                    Some((unit_message, recipient)) => self.send_units(unit_message, recipient),
+                    // A member stopping on a fork closes it right after alerting the others, before
+                    // the alerter gets to send the alert.
+                    None => debug!(target: "AlephBFT-network-hub", "Outgoing units stream closed, only sending alerts from now on."),
                 },
                 alert_message = self.alerts_to_send.next() => match alert_message {
                     Some((alert_message, recipient)) => self.send(NetworkData(Alert(alert_message)), recipient),
@@ -189,10 +412,27 @@ impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, N: Network<H,
                         break;
                     }
                 },
-                _ = &mut exit => break,
+                _ = &mut self.batching_window => self.flush_batches(),
+                _ = terminator.get_exit() => break,
             }
         }
+        self.flush_batches();
+        // A member stopping on a fork stops right after alerting the others, so the alerts
+        // already queued have to go out as well.
+        while let Ok((alert_message, recipient)) = self.alerts_to_send.try_recv() {
+            self.send(
+                NetworkData(NetworkDataInner::Alert(alert_message)),
+                recipient,
+            );
+        }
+        terminator.terminate_sync().await;
         info!(target: "AlephBFT-network-hub", "Network ended.");
+        NetworkHubIO {
+            units_to_send: self.units_to_send,
+            units_received: self.units_received,
+            alerts_to_send: self.alerts_to_send,
+            alerts_received: self.alerts_received,
+        }
     }
 }
 
@@ -204,20 +444,25 @@ pub(crate) async fn run<
     N: Network<H, D, S, MS>,
 >(
     network: N,
-    units_to_send: Receiver<(UnitMessage<H, D, S>, Recipient)>,
-    units_received: Sender<UnitMessage<H, D, S>>,
-    alerts_to_send: Receiver<(AlertMessage<H, D, S, MS>, Recipient)>,
-    alerts_received: Sender<AlertMessage<H, D, S, MS>>,
-    exit: oneshot::Receiver<()>,
-) {
+    io: NetworkHubIO<H, D, S, MS>,
+    max_data_size: Option<usize>,
+    unit_rejection_sink: Option<Sender<UnitRejection>>,
+    unit_batching: Option<UnitBatchingConfig>,
+    clock: Arc<dyn Clock>,
+    terminator: Terminator,
+) -> NetworkHubIO<H, D, S, MS> {
     NetworkHub::new(
         network,
-        units_to_send,
-        units_received,
-        alerts_to_send,
-        alerts_received,
+        io.units_to_send,
+        io.units_received,
+        io.alerts_to_send,
+        io.alerts_received,
+        max_data_size,
+        unit_rejection_sink,
+        unit_batching,
+        clock,
     )
-    .run(exit)
+    .run(terminator)
     .await
 }
 
@@ -228,8 +473,11 @@ mod tests {
         nodes::NodeSubset,
         testing::mock::{self, Data, Hasher64, PartialMultisignature, Signature},
         units::{ControlHash, FullUnit, PreUnit, UncheckedSignedUnit, UnitCoord},
-        Round, UncheckedSigned,
+        MockClock, Round, UncheckedSigned,
     };
+    use futures::{channel::mpsc::unbounded, future::FusedFuture};
+    use parking_lot::Mutex;
+    use std::time::Duration;
 
     fn test_unchecked_unit(
         creator: NodeIndex,
@@ -274,6 +522,42 @@ mod tests {
         }
     }
 
+    fn encoded_units(message: UnitMessage<Hasher64, Data, Signature>) -> Vec<u8> {
+        NetworkData::<Hasher64, Data, Signature, PartialMultisignature>(NetworkDataInner::Units(
+            message,
+        ))
+        .encode()
+    }
+
+    #[test]
+    fn reads_unit_headers() {
+        use UnitMessage::{NewUnit, RequestCoord, ResponseCoord};
+
+        let uu = test_unchecked_unit(5.into(), 43, 1729);
+        let new_unit = encoded_units(NewUnit(uu.clone()));
+        let header = mock::NetworkData::unit_header(&new_unit).expect("the message carries a unit");
+        assert_eq!(header.creator(), 5.into());
+        assert_eq!(header.round(), 43);
+        assert!(header.is_broadcast());
+        assert_eq!(header.encoded(), &uu.encode()[..]);
+
+        let response = encoded_units(ResponseCoord(uu.clone()));
+        let header = mock::NetworkData::unit_header(&response).expect("the message carries a unit");
+        assert!(!header.is_broadcast());
+        assert_eq!(header.encoded(), &uu.encode()[..]);
+
+        let other = test_unchecked_unit(5.into(), 43, 1730);
+        let other = encoded_units(NewUnit(other));
+        assert_ne!(
+            mock::NetworkData::unit_header(&other).map(|header| header.encoded()),
+            mock::NetworkData::unit_header(&new_unit).map(|header| header.encoded())
+        );
+
+        let request = encoded_units(RequestCoord(1.into(), UnitCoord::new(3, 2.into())));
+        assert_eq!(mock::NetworkData::unit_header(&request), None);
+        assert_eq!(mock::NetworkData::unit_header(&new_unit[..5]), None);
+    }
+
     #[test]
     fn decoding_network_data_units_request_coord() {
         use NetworkDataInner::Units;
@@ -397,6 +681,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn decoding_network_data_unit_batch() {
+        use NetworkDataInner::UnitBatch;
+        use UnitMessage::{RequestCoord, ResponseCoord};
+
+        let uu = test_unchecked_unit(5.into(), 43, 1729);
+        let included_data = vec![uu.as_signable().data().clone()];
+        let nd = NetworkData::<Hasher64, Data, Signature, PartialMultisignature>(UnitBatch(vec![
+            RequestCoord(7.into(), UnitCoord::new(3, 13.into())),
+            ResponseCoord(uu.clone()),
+        ]));
+        let encoded = nd.encode();
+        assert_eq!(mock::NetworkData::unit_header(&encoded), None);
+        let decoded = mock::NetworkData::decode(&mut &encoded[..]);
+        assert!(decoded.is_ok(), "Bug in encode/decode for UnitBatch");
+        let decoded = decoded.unwrap();
+        assert_eq!(
+            decoded.included_data(),
+            included_data,
+            "data decoded incorrectly"
+        );
+        assert_eq!(decoded.0.sender(), Some(7.into()));
+        if let UnitBatch(messages) = decoded.0 {
+            assert_eq!(messages.len(), 2, "decoded should equal encoded");
+            match &messages[1] {
+                ResponseCoord(decoded_unchecked) => assert_eq!(
+                    uu.as_signable(),
+                    decoded_unchecked.as_signable(),
+                    "decoded should equal encoded"
+                ),
+                _ => panic!("Decoded ResponseCoord as something else"),
+            }
+        } else {
+            panic!("Decoded UnitBatch as something else");
+        }
+    }
+
     #[test]
     fn decoding_network_data_alert_fork_alert() {
         use AlertMessage::ForkAlert;
@@ -434,4 +755,299 @@ mod tests {
             panic!("Decoded ForkAlert as something else");
         }
     }
+
+    struct NoNetwork;
+
+    #[async_trait::async_trait]
+    impl Network<Hasher64, Data, Signature, PartialMultisignature> for NoNetwork {
+        fn send(&self, _: mock::NetworkData, _: Recipient) {}
+
+        async fn next_event(&mut self) -> Option<mock::NetworkData> {
+            futures::future::pending().await
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingNetwork(Arc<Mutex<Vec<(mock::NetworkData, Recipient)>>>);
+
+    #[async_trait::async_trait]
+    impl Network<Hasher64, Data, Signature, PartialMultisignature> for RecordingNetwork {
+        fn send(&self, data: mock::NetworkData, recipient: Recipient) {
+            self.0.lock().push((data, recipient));
+        }
+
+        async fn next_event(&mut self) -> Option<mock::NetworkData> {
+            futures::future::pending().await
+        }
+    }
+
+    type TestHub<N> = NetworkHub<Hasher64, Data, Signature, PartialMultisignature, N>;
+
+    fn hub<N: Network<Hasher64, Data, Signature, PartialMultisignature>>(
+        network: N,
+        unit_batching: Option<UnitBatchingConfig>,
+        clock: MockClock,
+    ) -> (TestHub<N>, Receiver<UnitMessage<Hasher64, Data, Signature>>) {
+        let (_, units_to_send) = unbounded();
+        let (units_received, units_rx) = unbounded();
+        let (_, alerts_to_send) = unbounded();
+        let (alerts_received, _) = unbounded();
+        let hub = NetworkHub::new(
+            network,
+            units_to_send,
+            units_received,
+            alerts_to_send,
+            alerts_received,
+            None,
+            None,
+            unit_batching,
+            Arc::new(clock),
+        );
+        (hub, units_rx)
+    }
+
+    #[test]
+    fn batches_unit_messages_to_the_same_recipient() {
+        use NetworkDataInner::{UnitBatch, Units};
+        use UnitMessage::{RequestCoord, ResponseCoord};
+
+        let network = RecordingNetwork::default();
+        let clock = MockClock::new();
+        let unit_batching = UnitBatchingConfig {
+            window: Duration::from_millis(10),
+            max_batch_size: 3,
+        };
+        let (mut hub, _) = hub(network.clone(), Some(unit_batching), clock.clone());
+        for round in 0..4 {
+            let uu = test_unchecked_unit(5.into(), round, 0);
+            hub.send_units(ResponseCoord(uu), Recipient::Node(1.into()));
+        }
+        let request = RequestCoord(0.into(), UnitCoord::new(3, 2.into()));
+        hub.send_units(request, Recipient::Node(2.into()));
+        {
+            let sent = network.0.lock();
+            assert_eq!(sent.len(), 1, "only the full batch is sent right away");
+            assert!(
+                matches!(&sent[0], (NetworkData(UnitBatch(messages)), Recipient::Node(node)) if messages.len() == 3 && *node == 1.into())
+            );
+        }
+
+        assert!((&mut hub.batching_window).now_or_never().is_none());
+        clock.advance(Duration::from_millis(10));
+        assert!((&mut hub.batching_window).now_or_never().is_some());
+        hub.flush_batches();
+        let sent = network.0.lock();
+        assert_eq!(sent.len(), 3);
+        // The remaining messages are alone in their batches, so they are sent as they are.
+        for (data, _) in &sent[1..] {
+            assert!(matches!(data, NetworkData(Units(_))));
+        }
+    }
+
+    #[test]
+    fn sends_unit_messages_right_away_without_batching() {
+        use UnitMessage::RequestCoord;
+
+        let network = RecordingNetwork::default();
+        let (mut hub, _) = hub(network.clone(), None, MockClock::new());
+        for round in 0..3 {
+            let request = RequestCoord(0.into(), UnitCoord::new(round, 2.into()));
+            hub.send_units(request, Recipient::Node(1.into()));
+        }
+        assert_eq!(network.0.lock().len(), 3);
+        assert!(hub.batching_window.is_terminated());
+    }
+
+    #[tokio::test]
+    async fn flushes_batches_and_gives_back_channels_when_stopped() {
+        use UnitMessage::RequestCoord;
+
+        let network = RecordingNetwork::default();
+        let unit_batching = UnitBatchingConfig {
+            window: Duration::from_millis(10),
+            max_batch_size: 3,
+        };
+        let (mut hub, _units_rx) = hub(network.clone(), Some(unit_batching), MockClock::new());
+        let request = RequestCoord(0.into(), UnitCoord::new(3, 2.into()));
+        hub.send_units(request, Recipient::Node(1.into()));
+        assert!(network.0.lock().is_empty());
+
+        let (exit_tx, exit) = futures::channel::oneshot::channel();
+        exit_tx
+            .send(())
+            .expect("the terminator should be listening");
+        let io = hub.run(Terminator::create_root(exit, "test")).await;
+        assert_eq!(network.0.lock().len(), 1);
+        assert!(!io.units_received.is_closed());
+    }
+
+    #[tokio::test]
+    async fn sends_queued_alerts_when_stopped() {
+        use crate::alerts::{Alert, AlertMessage::ForkAlert};
+
+        let network = RecordingNetwork::default();
+        let (_units_to_send_tx, units_to_send) = unbounded();
+        let (units_received, _units_rx) = unbounded();
+        let (alerts_to_send_tx, alerts_to_send) = unbounded();
+        let (alerts_received, _alerts_rx) = unbounded();
+        let hub: TestHub<_> = NetworkHub::new(
+            network.clone(),
+            units_to_send,
+            units_received,
+            alerts_to_send,
+            alerts_received,
+            None,
+            None,
+            None,
+            Arc::new(MockClock::new()),
+        );
+        let alert = Alert::new(
+            7.into(),
+            (
+                test_unchecked_unit(3.into(), 2, 0),
+                test_unchecked_unit(3.into(), 2, 1),
+            ),
+            Vec::new(),
+        );
+        alerts_to_send_tx
+            .unbounded_send((
+                ForkAlert(UncheckedSigned::new(alert, Signature {})),
+                Recipient::Everyone,
+            ))
+            .expect("the hub should be listening");
+        let (exit_tx, exit) = futures::channel::oneshot::channel();
+        exit_tx.send(()).expect("the hub should be listening");
+        hub.run(Terminator::create_root(exit, "test")).await;
+        assert_eq!(network.0.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_alerts_after_outgoing_units_close() {
+        use crate::alerts::{Alert, AlertMessage::ForkAlert};
+
+        let network = RecordingNetwork::default();
+        let (units_to_send_tx, units_to_send) = unbounded();
+        let (units_received, _units_rx) = unbounded();
+        let (alerts_to_send_tx, alerts_to_send) = unbounded();
+        let (alerts_received, _alerts_rx) = unbounded();
+        let hub: TestHub<_> = NetworkHub::new(
+            network.clone(),
+            units_to_send,
+            units_received,
+            alerts_to_send,
+            alerts_received,
+            None,
+            None,
+            None,
+            Arc::new(MockClock::new()),
+        );
+        // The member stopped on a fork, and the alerter is only about to send the alert.
+        drop(units_to_send_tx);
+        let (_exit_tx, exit) = futures::channel::oneshot::channel();
+        let hub = tokio::spawn(hub.run(Terminator::create_root(exit, "test")));
+        let alert = Alert::new(
+            7.into(),
+            (
+                test_unchecked_unit(3.into(), 2, 0),
+                test_unchecked_unit(3.into(), 2, 1),
+            ),
+            Vec::new(),
+        );
+        alerts_to_send_tx
+            .unbounded_send((
+                ForkAlert(UncheckedSigned::new(alert, Signature {})),
+                Recipient::Everyone,
+            ))
+            .expect("the hub should be listening");
+        // The hub stops once the alerter does.
+        drop(alerts_to_send_tx);
+        hub.await.expect("the hub should not panic");
+        assert_eq!(network.0.lock().len(), 1);
+    }
+
+    #[test]
+    fn passes_on_batched_and_single_unit_messages() {
+        use NetworkDataInner::{UnitBatch, Units};
+        use UnitMessage::{NewUnit, ResponseCoord};
+
+        let (hub, mut units_rx) = hub(NoNetwork, None, MockClock::new());
+        let uu = test_unchecked_unit(5.into(), 43, 0);
+        hub.handle_incoming(NetworkData(UnitBatch(vec![
+            NewUnit(uu.clone()),
+            ResponseCoord(uu.clone()),
+        ])));
+        hub.handle_incoming(NetworkData(Units(NewUnit(uu))));
+        let mut n_messages = 0;
+        while units_rx.try_recv().is_ok() {
+            n_messages += 1;
+        }
+        assert_eq!(n_messages, 3);
+    }
+
+    #[test]
+    fn drops_units_with_too_much_data() {
+        use crate::alerts::{Alert, AlertMessage::ForkAlert};
+        use NetworkDataInner::{Alert as AlertData, Units};
+
+        let uu = test_unchecked_unit(5.into(), 43, 0);
+        let data_size = uu.as_signable().data().encoded_size();
+        let alert = Alert::new(
+            7.into(),
+            (
+                test_unchecked_unit(3.into(), 2, 0),
+                test_unchecked_unit(3.into(), 2, 1),
+            ),
+            Vec::new(),
+        );
+        let messages = || {
+            vec![
+                NetworkData(Units(UnitMessage::NewUnit(uu.clone()))),
+                NetworkData(Units(UnitMessage::ResponseParents(
+                    [0; 8],
+                    vec![uu.clone(), uu.clone()],
+                ))),
+                NetworkData(AlertData(ForkAlert(UncheckedSigned::new(
+                    alert.clone(),
+                    Signature {},
+                )))),
+            ]
+        };
+        for (max_data_size, passed) in [(data_size - 1, 0), (data_size, 2)].iter() {
+            let (_, units_to_send) = unbounded();
+            let (units_received, mut units_rx) = unbounded();
+            let (_, alerts_to_send) = unbounded();
+            let (alerts_received, mut alerts_rx) = unbounded();
+            let (rejection_sink, mut rejections) = unbounded();
+            let hub = NetworkHub::new(
+                NoNetwork,
+                units_to_send,
+                units_received,
+                alerts_to_send,
+                alerts_received,
+                Some(*max_data_size),
+                Some(rejection_sink),
+                None,
+                Arc::new(MockClock::new()),
+            );
+            for message in messages() {
+                hub.handle_incoming(message);
+            }
+            let mut n_units = 0;
+            while units_rx.try_recv().is_ok() {
+                n_units += 1;
+            }
+            assert_eq!(n_units, *passed);
+            assert_eq!(alerts_rx.try_recv().is_ok(), *passed > 0);
+            let mut senders = Vec::new();
+            while let Ok(rejection) = rejections.try_recv() {
+                assert_eq!(rejection.reason, UnitRejectionReason::TooLarge(data_size));
+                senders.push(rejection.sender);
+            }
+            let expected = match passed {
+                0 => vec![Some(5.into()), None, None],
+                _ => Vec::new(),
+            };
+            assert_eq!(senders, expected);
+        }
+    }
 }
@@ -0,0 +1,117 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Marks compressed payloads. Encoded network data never starts with this byte, so payloads
+/// without it are passed through as they are, e.g. when coming from peers not compressing yet.
+const MAGIC: u8 = 0xa1;
+const LEVEL: i32 = 3;
+/// Payloads decompressing to more than this are rejected, so that a peer cannot make us
+/// allocate arbitrary amounts of memory.
+const MAX_DECOMPRESSED_LEN: usize = 32 * 1024 * 1024;
+
+/// A compressed payload that could not be restored.
+#[derive(Debug)]
+pub struct DecompressionError(std::io::Error);
+
+impl Display for DecompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "decompression failed: {}", self.0)
+    }
+}
+
+/// Compresses an encoded payload for sending over the wire using zstd. The payload is returned
+/// unchanged if compression would not make it any shorter.
+pub fn compress_payload(payload: Vec<u8>) -> Vec<u8> {
+    match zstd::block::compress(&payload, LEVEL) {
+        Ok(compressed) if compressed.len() + 1 < payload.len() => {
+            let mut frame = Vec::with_capacity(compressed.len() + 1);
+            frame.push(MAGIC);
+            frame.extend_from_slice(&compressed);
+            frame
+        }
+        _ => payload,
+    }
+}
+
+/// Restores a payload produced by [`compress_payload`]. Uncompressed payloads are returned as
+/// they are.
+pub fn decompress_payload(frame: Vec<u8>) -> Result<Vec<u8>, DecompressionError> {
+    match frame.split_first() {
+        Some((&MAGIC, compressed)) => {
+            zstd::block::decompress(compressed, MAX_DECOMPRESSED_LEN).map_err(DecompressionError)
+        }
+        _ => Ok(frame),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        member::UnitMessage,
+        network::{NetworkData, NetworkDataInner},
+        nodes::NodeSubset,
+        testing::mock::{Data, Hasher64, PartialMultisignature, Signature},
+        units::{ControlHash, FullUnit, PreUnit, UnitCoord},
+        Hasher, NodeIndex, UncheckedSigned,
+    };
+    use codec::Encode;
+
+    type TestNetworkData = NetworkData<Hasher64, Data, Signature, PartialMultisignature>;
+
+    // A response with the parents of a unit, typical for catching up with the DAG.
+    fn parents_response(n_members: usize) -> TestNetworkData {
+        let parents = (0..n_members)
+            .map(|creator| {
+                let control_hash = ControlHash {
+                    parents_mask: NodeSubset::with_size(n_members.into()),
+                    combined_hash: 0.using_encoded(Hasher64::hash),
+                };
+                let pu = PreUnit::new(NodeIndex(creator), 7, control_hash);
+                let data = Data::new(UnitCoord::new(7, NodeIndex(creator)), 0);
+                UncheckedSigned::new(FullUnit::new(pu, data, 0), Signature {})
+            })
+            .collect();
+        NetworkData(NetworkDataInner::Units(UnitMessage::ResponseParents(
+            [0; 8], parents,
+        )))
+    }
+
+    #[test]
+    fn compresses_repetitive_messages() {
+        let payload = parents_response(100).encode();
+        let frame = compress_payload(payload.clone());
+        assert!(frame.len() < payload.len() / 2);
+        assert_eq!(
+            decompress_payload(frame).expect("the frame is valid"),
+            payload
+        );
+    }
+
+    #[test]
+    fn passes_small_messages_uncompressed() {
+        let payload = NetworkData::<Hasher64, Data, Signature, PartialMultisignature>(
+            NetworkDataInner::Units(UnitMessage::RequestCoord(
+                NodeIndex(0),
+                UnitCoord::new(3, NodeIndex(1)),
+            )),
+        )
+        .encode();
+        assert_eq!(compress_payload(payload.clone()), payload);
+    }
+
+    #[test]
+    fn accepts_uncompressed_payloads() {
+        let payload = parents_response(4).encode();
+        assert_eq!(
+            decompress_payload(payload.clone()).expect("the payload is valid"),
+            payload
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_payloads() {
+        let mut frame = compress_payload(parents_response(100).encode());
+        frame.truncate(frame.len() / 2);
+        assert!(decompress_payload(frame).is_err());
+    }
+}
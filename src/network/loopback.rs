@@ -0,0 +1,112 @@
+use crate::{
+    network::{Network, NetworkData, Recipient},
+    nodes::{NodeCount, NodeIndex},
+    signed::{PartialMultisignature, Signature},
+    Data, Hasher,
+};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+
+/// The simplest [`Network`], connecting the members of a committee running in a single process
+/// through in-memory channels, available with the `network-loopback` feature. Every message is
+/// delivered immediately and exactly once, which makes it handy for examples and experiments,
+/// but not for testing the behavior under an unreliable network.
+pub struct LoopbackNetwork<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> {
+    node_ix: NodeIndex,
+    peers: Vec<UnboundedSender<NetworkData<H, D, S, MS>>>,
+    incoming: UnboundedReceiver<NetworkData<H, D, S, MS>>,
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> LoopbackNetwork<H, D, S, MS> {
+    /// Creates the connected networks of all the members of a committee of `n_members`, sorted
+    /// by their indices.
+    pub fn connected(n_members: NodeCount) -> Vec<Self> {
+        let (peers, incoming): (Vec<_>, Vec<_>) = (0..n_members.0).map(|_| unbounded()).unzip();
+        incoming
+            .into_iter()
+            .enumerate()
+            .map(|(ix, incoming)| LoopbackNetwork {
+                node_ix: NodeIndex(ix),
+                peers: peers.clone(),
+                incoming,
+            })
+            .collect()
+    }
+
+    /// The index of the member using the network.
+    pub fn index(&self) -> NodeIndex {
+        self.node_ix
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> Network<H, D, S, MS>
+    for LoopbackNetwork<H, D, S, MS>
+{
+    fn send(&self, data: NetworkData<H, D, S, MS>, recipient: Recipient) {
+        // A member which already stopped does not take messages anymore, which is fine.
+        match recipient {
+            Recipient::Node(peer) => {
+                if let Some(peer) = self.peers.get(peer.0) {
+                    let _ = peer.unbounded_send(data);
+                }
+            }
+            Recipient::Everyone => {
+                for (ix, peer) in self.peers.iter().enumerate() {
+                    if ix != self.node_ix.0 {
+                        let _ = peer.unbounded_send(data.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData<H, D, S, MS>> {
+        self.incoming.next().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoopbackNetwork;
+    use crate::{
+        alerts::AlertMessage,
+        network::NetworkDataInner,
+        testing::mock::{Data, Hasher64, NetworkData, PartialMultisignature, Signature},
+        Network, NodeCount, NodeIndex, Recipient,
+    };
+    use futures::FutureExt;
+
+    type TestNetwork = LoopbackNetwork<Hasher64, Data, Signature, PartialMultisignature>;
+
+    fn message(sender: usize) -> NetworkData {
+        crate::NetworkData(NetworkDataInner::Alert(AlertMessage::AlertRequest(
+            NodeIndex(sender),
+            [0; 8],
+        )))
+    }
+
+    fn senders(network: &mut TestNetwork) -> Vec<NodeIndex> {
+        let mut senders = Vec::new();
+        while let Some(Some(data)) = network.next_event().now_or_never() {
+            senders.extend(data.0.sender());
+        }
+        senders
+    }
+
+    #[test]
+    fn delivers_messages_to_recipients() {
+        let mut networks = TestNetwork::connected(NodeCount(3));
+        networks[0].send(message(0), Recipient::Everyone);
+        networks[1].send(message(1), Recipient::Node(NodeIndex(2)));
+        networks[2].send(message(2), Recipient::Node(NodeIndex(2)));
+        assert_eq!(senders(&mut networks[0]), vec![]);
+        assert_eq!(senders(&mut networks[1]), vec![NodeIndex(0)]);
+        assert_eq!(
+            senders(&mut networks[2]),
+            vec![NodeIndex(0), NodeIndex(1), NodeIndex(2)]
+        );
+    }
+}
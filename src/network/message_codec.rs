@@ -0,0 +1,81 @@
+use crate::{
+    network::{NetworkData, UnitHeader},
+    signed::{PartialMultisignature, Signature},
+    Data, Hasher,
+};
+use codec::{Decode, Encode};
+use std::fmt::{Debug, Display};
+
+/// Turns [`NetworkData`] into the bytes sent over the wire and back. Networks which send bytes,
+/// like [`TcpNetwork`](crate::TcpNetwork), use it for the payloads of the messages, so that the
+/// messages can be wrapped in an envelope imposed by some other system, e.g. a protobuf message
+/// of an existing message bus. The framing of the payloads is up to the network.
+///
+/// The encoded data should be passed to the codec decoding it as is, the messages of AlephBFT
+/// are not meant to be modified on the way.
+pub trait NetworkCodec<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature>:
+    Clone + Send + Sync + 'static
+{
+    type Error: Debug + Display + Send;
+
+    fn encode(&self, data: &NetworkData<H, D, S, MS>) -> Vec<u8>;
+
+    fn decode(&self, encoded: &[u8]) -> Result<NetworkData<H, D, S, MS>, Self::Error>;
+
+    /// The header of the unit in an encoded message carrying a single unit, if it can be read
+    /// without decoding the message, see [`NetworkData::unit_header`]. Networks use it to drop
+    /// repeated units cheaply, which they do not do if it returns `None`, as by default.
+    fn unit_header<'a>(&self, _encoded: &'a [u8]) -> Option<UnitHeader<'a>> {
+        None
+    }
+}
+
+/// The default [`NetworkCodec`], sending the SCALE encoding of the data as is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScaleCodec;
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> NetworkCodec<H, D, S, MS>
+    for ScaleCodec
+{
+    type Error = codec::Error;
+
+    fn encode(&self, data: &NetworkData<H, D, S, MS>) -> Vec<u8> {
+        data.encode()
+    }
+
+    fn decode(&self, encoded: &[u8]) -> Result<NetworkData<H, D, S, MS>, Self::Error> {
+        NetworkData::decode(&mut &encoded[..])
+    }
+
+    fn unit_header<'a>(&self, encoded: &'a [u8]) -> Option<UnitHeader<'a>> {
+        NetworkData::<H, D, S, MS>::unit_header(encoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NetworkCodec, ScaleCodec};
+    use crate::{
+        alerts::AlertMessage,
+        network::NetworkDataInner,
+        testing::mock::{Data, Hasher64, NetworkData, PartialMultisignature, Signature},
+        NodeIndex,
+    };
+    use codec::Encode;
+
+    fn codec() -> impl NetworkCodec<Hasher64, Data, Signature, PartialMultisignature> {
+        ScaleCodec
+    }
+
+    #[test]
+    fn scale_codec_roundtrips() {
+        let data: NetworkData = crate::NetworkData(NetworkDataInner::Alert(
+            AlertMessage::AlertRequest(NodeIndex(3), [7; 8]),
+        ));
+        let encoded = codec().encode(&data);
+        assert_eq!(encoded, data.encode());
+        let decoded = codec().decode(&encoded).expect("the encoding is valid");
+        assert_eq!(decoded.0.sender(), Some(NodeIndex(3)));
+        assert!(codec().decode(&encoded[..1]).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    network::{Network, NetworkData, Recipient},
+    nodes::{NodeCount, NodeIndex},
+    signed::{PartialMultisignature, Signature},
+    Data, Hasher,
+};
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Instant};
+
+/// What we know about the connectivity with a single peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeerStatus {
+    /// When we last passed a message for the peer to the network, including broadcasts.
+    pub last_sent: Option<Instant>,
+    /// When we last received a message identifying the peer as its sender. The sender is not
+    /// authenticated by the network, so this is only a hint.
+    pub last_received: Option<Instant>,
+}
+
+/// A handle for querying the statuses collected by a [`PeerTrackingNetwork`].
+#[derive(Clone)]
+pub struct PeerStatusHandle {
+    statuses: Arc<Mutex<Vec<PeerStatus>>>,
+}
+
+impl PeerStatusHandle {
+    /// The statuses of all the peers, sorted by their indices.
+    pub fn peer_status(&self) -> Vec<(NodeIndex, PeerStatus)> {
+        self.statuses
+            .lock()
+            .iter()
+            .enumerate()
+            .map(|(ix, status)| (NodeIndex(ix), *status))
+            .collect()
+    }
+}
+
+/// A [`Network`] wrapper recording when we last sent messages to and received messages from
+/// every peer, which can be queried through a [`PeerStatusHandle`].
+pub struct PeerTrackingNetwork<N> {
+    network: N,
+    node_ix: NodeIndex,
+    statuses: Arc<Mutex<Vec<PeerStatus>>>,
+}
+
+impl<N> PeerTrackingNetwork<N> {
+    /// Wraps the `network` of the node `node_ix` in a committee of `n_members`.
+    pub fn new(network: N, node_ix: NodeIndex, n_members: NodeCount) -> (Self, PeerStatusHandle) {
+        let statuses = Arc::new(Mutex::new(vec![PeerStatus::default(); n_members.0]));
+        let handle = PeerStatusHandle {
+            statuses: statuses.clone(),
+        };
+        let network = PeerTrackingNetwork {
+            network,
+            node_ix,
+            statuses,
+        };
+        (network, handle)
+    }
+}
+
+#[async_trait::async_trait]
+impl<H, D, S, MS, N> Network<H, D, S, MS> for PeerTrackingNetwork<N>
+where
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    N: Network<H, D, S, MS>,
+{
+    fn send(&self, data: NetworkData<H, D, S, MS>, recipient: Recipient) {
+        let now = Instant::now();
+        {
+            let mut statuses = self.statuses.lock();
+            match &recipient {
+                Recipient::Node(peer) => {
+                    if let Some(status) = statuses.get_mut(peer.0) {
+                        status.last_sent = Some(now);
+                    }
+                }
+                Recipient::Everyone => {
+                    for (ix, status) in statuses.iter_mut().enumerate() {
+                        if NodeIndex(ix) != self.node_ix {
+                            status.last_sent = Some(now);
+                        }
+                    }
+                }
+            }
+        }
+        self.network.send(data, recipient);
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData<H, D, S, MS>> {
+        let data = self.network.next_event().await?;
+        if let Some(sender) = data.0.sender() {
+            // Messages claiming to come from an unknown node are passed on without a trace.
+            if let Some(status) = self.statuses.lock().get_mut(sender.0) {
+                status.last_received = Some(Instant::now());
+            }
+        }
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        member::UnitMessage,
+        network::NetworkDataInner,
+        testing::mock::{Data, Hasher64, PartialMultisignature, Signature},
+        units::UnitCoord,
+    };
+    use futures::{
+        channel::mpsc::{unbounded, UnboundedReceiver},
+        StreamExt,
+    };
+
+    type TestNetworkData = NetworkData<Hasher64, Data, Signature, PartialMultisignature>;
+
+    struct ChannelNetwork {
+        incoming: UnboundedReceiver<TestNetworkData>,
+    }
+
+    #[async_trait::async_trait]
+    impl Network<Hasher64, Data, Signature, PartialMultisignature> for ChannelNetwork {
+        fn send(&self, _: TestNetworkData, _: Recipient) {}
+
+        async fn next_event(&mut self) -> Option<TestNetworkData> {
+            self.incoming.next().await
+        }
+    }
+
+    fn request_from(sender: NodeIndex) -> TestNetworkData {
+        NetworkData(NetworkDataInner::Units(UnitMessage::RequestCoord(
+            sender,
+            UnitCoord::new(3, NodeIndex(0)),
+        )))
+    }
+
+    #[tokio::test]
+    async fn tracks_sent_and_received_messages() {
+        let (incoming_tx, incoming) = unbounded();
+        let (mut network, handle) =
+            PeerTrackingNetwork::new(ChannelNetwork { incoming }, NodeIndex(0), NodeCount(4));
+        let before = Instant::now();
+        network.send(request_from(NodeIndex(0)), Recipient::Node(NodeIndex(2)));
+        incoming_tx
+            .unbounded_send(request_from(NodeIndex(3)))
+            .expect("the channel is open");
+        incoming_tx
+            .unbounded_send(request_from(NodeIndex(7)))
+            .expect("the channel is open");
+        assert!(network.next_event().await.is_some());
+        assert!(network.next_event().await.is_some());
+
+        let statuses = handle.peer_status();
+        assert_eq!(statuses.len(), 4);
+        assert_eq!(statuses[1].1, PeerStatus::default());
+        assert!(statuses[2].1.last_sent.expect("we sent to node 2") >= before);
+        assert_eq!(statuses[2].1.last_received, None);
+        assert!(statuses[3].1.last_received.expect("node 3 sent to us") >= before);
+
+        network.send(request_from(NodeIndex(0)), Recipient::Everyone);
+        let statuses = handle.peer_status();
+        assert_eq!(statuses[0].1.last_sent, None);
+        assert!(statuses[1].1.last_sent.is_some());
+    }
+}
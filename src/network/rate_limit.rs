@@ -0,0 +1,184 @@
+use crate::{
+    network::{Network, NetworkData, NetworkDataInner, Recipient},
+    signed::{PartialMultisignature, Signature},
+    Data, Hasher,
+};
+use log::trace;
+use parking_lot::Mutex;
+use std::{collections::HashMap, time::Instant};
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(burst: u32, now: Instant) -> Self {
+        TokenBucket {
+            tokens: burst as f64,
+            last_refill: now,
+        }
+    }
+
+    fn try_take(&mut self, rate: f64, burst: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(burst as f64);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A [`Network`] wrapper limiting the number of unit messages sent to every recipient.
+///
+/// Every recipient, with all broadcasts counted as a separate one, gets a token bucket holding up
+/// to `burst` messages and refilled at `messages_per_second`. Unit messages exceeding the limit are
+/// dropped, which AlephBFT tolerates by requesting missing units again. Alerts are never limited.
+pub struct RateLimitedNetwork<N> {
+    network: N,
+    messages_per_second: f64,
+    burst: u32,
+    buckets: Mutex<HashMap<Recipient, TokenBucket>>,
+}
+
+impl<N> RateLimitedNetwork<N> {
+    /// Wraps `network`, allowing `burst` unit messages at once to every recipient and
+    /// `messages_per_second` on average.
+    pub fn new(network: N, messages_per_second: f64, burst: u32) -> Self {
+        RateLimitedNetwork {
+            network,
+            messages_per_second,
+            burst,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn allow(&self, recipient: &Recipient, now: Instant) -> bool {
+        let burst = self.burst;
+        self.buckets
+            .lock()
+            .entry(recipient.clone())
+            .or_insert_with(|| TokenBucket::new(burst, now))
+            .try_take(self.messages_per_second, burst, now)
+    }
+}
+
+#[async_trait::async_trait]
+impl<H, D, S, MS, N> Network<H, D, S, MS> for RateLimitedNetwork<N>
+where
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    N: Network<H, D, S, MS>,
+{
+    fn send(&self, data: NetworkData<H, D, S, MS>, recipient: Recipient) {
+        if let NetworkDataInner::Units(_) | NetworkDataInner::UnitBatch(_) = data.0 {
+            if !self.allow(&recipient, Instant::now()) {
+                trace!(target: "AlephBFT-network-rate-limit", "Rate limit exceeded for {:?}, dropping a unit message.", recipient);
+                return;
+            }
+        }
+        self.network.send(data, recipient);
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData<H, D, S, MS>> {
+        self.network.next_event().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alerts::AlertMessage,
+        member::UnitMessage,
+        testing::mock::{Data, Hasher64, PartialMultisignature, Signature},
+        units::UnitCoord,
+        NodeIndex,
+    };
+    use std::{sync::Arc, time::Duration};
+
+    type TestNetworkData = NetworkData<Hasher64, Data, Signature, PartialMultisignature>;
+
+    #[derive(Clone, Default)]
+    struct RecordingNetwork {
+        sent: Arc<parking_lot::Mutex<Vec<Recipient>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Network<Hasher64, Data, Signature, PartialMultisignature> for RecordingNetwork {
+        fn send(&self, _: TestNetworkData, recipient: Recipient) {
+            self.sent.lock().push(recipient);
+        }
+
+        async fn next_event(&mut self) -> Option<TestNetworkData> {
+            None
+        }
+    }
+
+    fn unit_message() -> TestNetworkData {
+        NetworkData(NetworkDataInner::Units(UnitMessage::RequestCoord(
+            NodeIndex(0),
+            UnitCoord::new(3, NodeIndex(1)),
+        )))
+    }
+
+    fn alert_message() -> TestNetworkData {
+        NetworkData(NetworkDataInner::Alert(AlertMessage::AlertRequest(
+            NodeIndex(0),
+            [0; 8],
+        )))
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, start);
+        assert!(bucket.try_take(10.0, 2, start));
+        assert!(bucket.try_take(10.0, 2, start));
+        assert!(!bucket.try_take(10.0, 2, start));
+        assert!(bucket.try_take(10.0, 2, start + Duration::from_millis(100)));
+        assert!(!bucket.try_take(10.0, 2, start + Duration::from_millis(100)));
+        // The bucket never holds more than the burst.
+        let later = start + Duration::from_secs(10);
+        assert!(bucket.try_take(10.0, 2, later));
+        assert!(bucket.try_take(10.0, 2, later));
+        assert!(!bucket.try_take(10.0, 2, later));
+    }
+
+    #[test]
+    fn limits_unit_messages_per_recipient() {
+        let inner = RecordingNetwork::default();
+        let network = RateLimitedNetwork::new(inner.clone(), 0.001, 3);
+        for _ in 0..5 {
+            network.send(unit_message(), Recipient::Node(NodeIndex(1)));
+        }
+        network.send(unit_message(), Recipient::Node(NodeIndex(2)));
+        network.send(unit_message(), Recipient::Everyone);
+        let sent = inner.sent.lock().clone();
+        assert_eq!(
+            sent.iter()
+                .filter(|r| **r == Recipient::Node(NodeIndex(1)))
+                .count(),
+            3
+        );
+        assert_eq!(sent.len(), 5);
+    }
+
+    #[test]
+    fn never_limits_alerts() {
+        let inner = RecordingNetwork::default();
+        let network = RateLimitedNetwork::new(inner.clone(), 0.001, 1);
+        network.send(unit_message(), Recipient::Everyone);
+        for _ in 0..5 {
+            network.send(alert_message(), Recipient::Everyone);
+        }
+        network.send(unit_message(), Recipient::Everyone);
+        assert_eq!(inner.sent.lock().len(), 6);
+    }
+}
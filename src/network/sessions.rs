@@ -0,0 +1,249 @@
+use crate::{
+    network::{Network, NetworkData, Recipient},
+    signed::{PartialMultisignature, Signature},
+    Data, Hasher, Receiver, Sender, SessionId, SpawnHandle,
+};
+use codec::{Decode, Encode};
+use futures::{channel::mpsc::unbounded, FutureExt, StreamExt};
+use log::{trace, warn};
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc};
+
+/// The data of one of the sessions sharing a [`SessionNetwork`], tagged with the id of the
+/// session.
+#[derive(Clone, Debug, Encode, Decode)]
+pub struct SessionData<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> {
+    pub session_id: SessionId,
+    pub data: NetworkData<H, D, S, MS>,
+}
+
+/// A network shared by several sessions running at the same time, see [`SessionMultiplexer`].
+/// The requirements are the same as for a [`Network`], the session ids only have to be
+/// delivered along with the data.
+#[async_trait::async_trait]
+pub trait SessionNetwork<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature>:
+    Send
+{
+    /// Send a message of a session to a single node or everyone, depending on the value of the
+    /// recipient argument. It should not block, just like [`Network::send`].
+    fn send(&self, data: SessionData<H, D, S, MS>, recipient: Recipient);
+    /// Receive a message of any of the sessions from the network.
+    async fn next_event(&mut self) -> Option<SessionData<H, D, S, MS>>;
+}
+
+type Sessions<H, D, S, MS> =
+    Arc<Mutex<Option<HashMap<SessionId, Sender<NetworkData<H, D, S, MS>>>>>>;
+
+/// Splits a [`SessionNetwork`] into the [`Network`]s of the sessions, so that several calls to
+/// [`run_session`](crate::run_session) can run at the same time, e.g. while the next session
+/// is starting and the previous one is still finishing. The messages of every session are
+/// tagged with its id, and the received ones are passed only to the network of that session.
+///
+/// The messages of sessions we do not run at the moment are dropped, as the sessions recover
+/// from lost messages anyway. The routing stops once the shared network stops working, or
+/// the multiplexer and all the networks of the sessions are dropped.
+pub struct SessionMultiplexer<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> {
+    outgoing: Sender<(SessionData<H, D, S, MS>, Recipient)>,
+    sessions: Sessions<H, D, S, MS>,
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> SessionMultiplexer<H, D, S, MS> {
+    /// Wraps the shared `network`, spawning the task routing the messages of the sessions.
+    pub fn new<N: SessionNetwork<H, D, S, MS> + 'static>(
+        network: N,
+        spawn_handle: &impl SpawnHandle,
+    ) -> Self {
+        let (outgoing, outgoing_rx) = unbounded();
+        let sessions = Arc::new(Mutex::new(Some(HashMap::new())));
+        spawn_handle.spawn(
+            "network/sessions",
+            route(network, outgoing_rx, sessions.clone()),
+        );
+        SessionMultiplexer { outgoing, sessions }
+    }
+
+    /// The network of the session `session_id`, to be passed to the
+    /// [`run_session`](crate::run_session) with this session id in its config. Returns `None`
+    /// if the network of the session is still in use. It stops receiving messages once the
+    /// shared network stops working.
+    pub fn session_network(
+        &self,
+        session_id: SessionId,
+    ) -> Option<MultiplexedNetwork<H, D, S, MS>> {
+        let (incoming_tx, incoming) = unbounded();
+        if let Some(sessions) = self.sessions.lock().as_mut() {
+            if sessions.contains_key(&session_id) {
+                return None;
+            }
+            sessions.insert(session_id, incoming_tx);
+        }
+        Some(MultiplexedNetwork {
+            session_id,
+            outgoing: self.outgoing.clone(),
+            incoming,
+            sessions: self.sessions.clone(),
+        })
+    }
+}
+
+/// The [`Network`] of a single session, created by a [`SessionMultiplexer`]. Dropping it
+/// allows opening the network of the session again.
+pub struct MultiplexedNetwork<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> {
+    session_id: SessionId,
+    outgoing: Sender<(SessionData<H, D, S, MS>, Recipient)>,
+    incoming: Receiver<NetworkData<H, D, S, MS>>,
+    sessions: Sessions<H, D, S, MS>,
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> MultiplexedNetwork<H, D, S, MS> {
+    /// The id of the session using the network.
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> Drop
+    for MultiplexedNetwork<H, D, S, MS>
+{
+    fn drop(&mut self) {
+        if let Some(sessions) = self.sessions.lock().as_mut() {
+            sessions.remove(&self.session_id);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> Network<H, D, S, MS>
+    for MultiplexedNetwork<H, D, S, MS>
+{
+    fn send(&self, data: NetworkData<H, D, S, MS>, recipient: Recipient) {
+        let data = SessionData {
+            session_id: self.session_id,
+            data,
+        };
+        // The routing stops only when the shared network does, so there is nothing to do.
+        let _ = self.outgoing.unbounded_send((data, recipient));
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData<H, D, S, MS>> {
+        self.incoming.next().await
+    }
+}
+
+async fn route<H, D, S, MS, N>(
+    mut network: N,
+    mut outgoing: Receiver<(SessionData<H, D, S, MS>, Recipient)>,
+    sessions: Sessions<H, D, S, MS>,
+) where
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    N: SessionNetwork<H, D, S, MS>,
+{
+    loop {
+        futures::select! {
+            data = outgoing.next() => match data {
+                Some((data, recipient)) => network.send(data, recipient),
+                None => break,
+            },
+            data = network.next_event().fuse() => match data {
+                Some(SessionData { session_id, data }) => {
+                    match sessions.lock().as_ref().and_then(|sessions| sessions.get(&session_id)) {
+                        // The session might be just stopping, which is fine.
+                        Some(session) => {
+                            let _ = session.unbounded_send(data);
+                        }
+                        None => {
+                            trace!(target: "AlephBFT-network-sessions", "Dropping a message of session {}, which is not running.", session_id);
+                        }
+                    }
+                }
+                None => {
+                    warn!(target: "AlephBFT-network-sessions", "Shared network stopped working.");
+                    break;
+                }
+            },
+        }
+    }
+    // Closes the incoming messages of all the sessions.
+    *sessions.lock() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionData, SessionMultiplexer, SessionNetwork};
+    use crate::{
+        alerts::AlertMessage,
+        network::NetworkDataInner,
+        testing::mock::{Data, Hasher64, NetworkData, PartialMultisignature, Signature, Spawner},
+        Network, NodeIndex, Recipient,
+    };
+    use futures::{
+        channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        StreamExt,
+    };
+
+    type TestSessionData = SessionData<Hasher64, Data, Signature, PartialMultisignature>;
+
+    struct TestNetwork {
+        sent: UnboundedSender<TestSessionData>,
+        incoming: UnboundedReceiver<TestSessionData>,
+    }
+
+    #[async_trait::async_trait]
+    impl SessionNetwork<Hasher64, Data, Signature, PartialMultisignature> for TestNetwork {
+        fn send(&self, data: TestSessionData, _: Recipient) {
+            self.sent.unbounded_send(data).expect("the test is running");
+        }
+
+        async fn next_event(&mut self) -> Option<TestSessionData> {
+            self.incoming.next().await
+        }
+    }
+
+    fn message(sender: usize) -> NetworkData {
+        crate::NetworkData(NetworkDataInner::Alert(AlertMessage::AlertRequest(
+            NodeIndex(sender),
+            [0; 8],
+        )))
+    }
+
+    fn sender(data: &NetworkData) -> Option<NodeIndex> {
+        data.0.sender()
+    }
+
+    #[tokio::test]
+    async fn routes_messages_by_session() {
+        let (sent, mut sent_rx) = unbounded();
+        let (incoming_tx, incoming) = unbounded();
+        let multiplexer = SessionMultiplexer::new(TestNetwork { sent, incoming }, &Spawner::new());
+        let mut first = multiplexer.session_network(1).expect("the session is new");
+        let mut second = multiplexer.session_network(2).expect("the session is new");
+        assert!(multiplexer.session_network(1).is_none());
+
+        first.send(message(0), Recipient::Everyone);
+        let sent = sent_rx.next().await.expect("the message was sent");
+        assert_eq!(sent.session_id, 1);
+        assert_eq!(sender(&sent.data), Some(NodeIndex(0)));
+
+        for (session_id, sender) in [(3, 3), (2, 2), (1, 1)] {
+            let data = message(sender);
+            incoming_tx
+                .unbounded_send(SessionData { session_id, data })
+                .expect("the router is running");
+        }
+        let received = first.next_event().await.expect("the message was routed");
+        assert_eq!(sender(&received), Some(NodeIndex(1)));
+        let received = second.next_event().await.expect("the message was routed");
+        assert_eq!(sender(&received), Some(NodeIndex(2)));
+
+        drop(first);
+        let mut first = multiplexer
+            .session_network(1)
+            .expect("the previous network was dropped");
+        drop(incoming_tx);
+        assert!(first.next_event().await.is_none());
+        assert!(second.next_event().await.is_none());
+    }
+}
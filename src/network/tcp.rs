@@ -0,0 +1,384 @@
+use crate::{
+    network::{Network, NetworkCodec, NetworkData, Recipient, ScaleCodec},
+    nodes::NodeIndex,
+    runway::RecentHashes,
+    signed::{PartialMultisignature, Signature},
+    Data, Hasher,
+};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use futures_timer::Delay;
+use log::{debug, info, trace, warn};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// Frames longer than this are rejected, so that a peer cannot make us allocate arbitrary amounts
+/// of memory.
+const MAX_FRAME_LEN: usize = 32 * 1024 * 1024;
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+/// After this many failed attempts to connect to a peer in a row, we stop queueing messages for
+/// it until we connect again.
+const MAX_CONNECT_FAILURES: u32 = 5;
+/// How many of the most recently received broadcasts of units we remember.
+const RECENT_BROADCASTS: usize = 10_000;
+
+// The hashes of the encodings of the recently received broadcasts of units, shared by all the
+// connections.
+type RecentBroadcasts<H> = Arc<Mutex<RecentHashes<<H as Hasher>::Hash>>>;
+
+struct Peer {
+    frames: UnboundedSender<Vec<u8>>,
+    // False after connecting failed `MAX_CONNECT_FAILURES` times, until we connect again.
+    reachable: Arc<AtomicBool>,
+}
+
+/// A reference [`Network`] implementation sending length-prefixed frames over TCP, meant for
+/// demos and tests rather than production use.
+///
+/// Every peer is reached at a fixed address. Each of them gets its own outgoing connection,
+/// which is reestablished with an exponential backoff whenever it fails. Messages to a peer are
+/// queued while it is unreachable, until connecting to it failed a few times in a row. Then they
+/// are dropped, together with the queued ones, until a connection succeeds again, so that a
+/// peer which is down does not make us accumulate messages. The consensus sends everything
+/// important again, so nothing is lost for good. The incoming messages are not authenticated in
+/// any way. This is fine for consensus messages, which are signed, but the transport does not
+/// provide any rate limiting either.
+///
+/// The payloads of the frames are encoded by a [`NetworkCodec`], the [`ScaleCodec`] unless
+/// another one is passed to [`TcpNetwork::from_listener_with_codec`]. With the `compression`
+/// feature, they are compressed using [`compress_payload`](crate::compress_payload).
+/// Uncompressed frames are still accepted then.
+///
+/// Members broadcast their units repeatedly, so a unit broadcast again with the same signature
+/// is recognized by its [`UnitHeader`](crate::UnitHeader) and dropped without being decoded,
+/// as long as the codec can read the header.
+/// Units sent in responses are always decoded, as we asked for them.
+///
+/// The background tasks are spawned on the current tokio runtime and stopped when the network is
+/// dropped.
+pub struct TcpNetwork<
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    C: NetworkCodec<H, D, S, MS> = ScaleCodec,
+> {
+    node_ix: NodeIndex,
+    codec: C,
+    peers: HashMap<NodeIndex, Peer>,
+    loopback: UnboundedSender<NetworkData<H, D, S, MS>>,
+    incoming: UnboundedReceiver<NetworkData<H, D, S, MS>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature> TcpNetwork<H, D, S, MS> {
+    /// Listens at the address of `node_ix` in `addresses` and connects to all the other nodes in
+    /// there.
+    pub async fn new(
+        node_ix: NodeIndex,
+        addresses: HashMap<NodeIndex, SocketAddr>,
+    ) -> Result<Self, Error> {
+        Self::new_with_codec(node_ix, addresses, ScaleCodec).await
+    }
+
+    /// Like [`TcpNetwork::new`], but accepts connections on an already bound listener. The
+    /// address of `node_ix` in `addresses`, if any, is ignored.
+    pub fn from_listener(
+        node_ix: NodeIndex,
+        listener: TcpListener,
+        addresses: HashMap<NodeIndex, SocketAddr>,
+    ) -> Self {
+        Self::from_listener_with_codec(node_ix, listener, addresses, ScaleCodec)
+    }
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, C: NetworkCodec<H, D, S, MS>>
+    TcpNetwork<H, D, S, MS, C>
+{
+    /// Like [`TcpNetwork::new`], but encodes the messages with `codec`.
+    pub async fn new_with_codec(
+        node_ix: NodeIndex,
+        addresses: HashMap<NodeIndex, SocketAddr>,
+        codec: C,
+    ) -> Result<Self, Error> {
+        let address = addresses.get(&node_ix).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("no address for {:?}", node_ix),
+            )
+        })?;
+        let listener = TcpListener::bind(address).await?;
+        Ok(Self::from_listener_with_codec(
+            node_ix, listener, addresses, codec,
+        ))
+    }
+
+    /// Like [`TcpNetwork::from_listener`], but encodes the messages with `codec`.
+    pub fn from_listener_with_codec(
+        node_ix: NodeIndex,
+        listener: TcpListener,
+        addresses: HashMap<NodeIndex, SocketAddr>,
+        codec: C,
+    ) -> Self {
+        let (loopback, incoming) = unbounded();
+        let recent_broadcasts = Arc::new(Mutex::new(RecentHashes::new(RECENT_BROADCASTS)));
+        let mut tasks = vec![tokio::spawn(accept_connections(
+            listener,
+            loopback.clone(),
+            recent_broadcasts,
+            codec.clone(),
+        ))];
+        let mut peers = HashMap::new();
+        for (peer, address) in addresses {
+            if peer == node_ix {
+                continue;
+            }
+            let (frames, frames_rx) = unbounded();
+            let reachable = Arc::new(AtomicBool::new(true));
+            tasks.push(tokio::spawn(send_frames(
+                peer,
+                address,
+                frames_rx,
+                reachable.clone(),
+            )));
+            peers.insert(peer, Peer { frames, reachable });
+        }
+        TcpNetwork {
+            node_ix,
+            codec,
+            peers,
+            loopback,
+            incoming,
+            tasks,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_reachable(&self, peer: NodeIndex) -> bool {
+        self.peers[&peer].reachable.load(Ordering::Relaxed)
+    }
+
+    fn send_frame(&self, frame: Vec<u8>, peer: NodeIndex) {
+        match self.peers.get(&peer) {
+            Some(peer_connection) if !peer_connection.reachable.load(Ordering::Relaxed) => {
+                trace!(target: "AlephBFT-network-tcp", "{:?} {:?} is unreachable, dropping message.", self.node_ix, peer);
+            }
+            Some(peer_connection) => {
+                if peer_connection.frames.unbounded_send(frame).is_err() {
+                    warn!(target: "AlephBFT-network-tcp", "{:?} Connection task for {:?} stopped.", self.node_ix, peer);
+                }
+            }
+            None => {
+                warn!(target: "AlephBFT-network-tcp", "{:?} No address for {:?}, dropping message.", self.node_ix, peer);
+            }
+        }
+    }
+}
+
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, C: NetworkCodec<H, D, S, MS>> Drop
+    for TcpNetwork<H, D, S, MS, C>
+{
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<H: Hasher, D: Data, S: Signature, MS: PartialMultisignature, C: NetworkCodec<H, D, S, MS>>
+    Network<H, D, S, MS> for TcpNetwork<H, D, S, MS, C>
+{
+    fn send(&self, data: NetworkData<H, D, S, MS>, recipient: Recipient) {
+        match recipient {
+            Recipient::Node(peer) if peer == self.node_ix => {
+                // The receiver is owned by us, so this cannot fail.
+                let _ = self.loopback.unbounded_send(data);
+            }
+            Recipient::Node(peer) => self.send_frame(encode_frame(self.codec.encode(&data)), peer),
+            Recipient::Everyone => {
+                let frame = encode_frame(self.codec.encode(&data));
+                for peer in self.peers.keys() {
+                    self.send_frame(frame.clone(), *peer);
+                }
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData<H, D, S, MS>> {
+        self.incoming.next().await
+    }
+}
+
+fn encode_frame(payload: Vec<u8>) -> Vec<u8> {
+    #[cfg(feature = "compression")]
+    let payload = super::compress_payload(payload);
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&payload);
+    frame
+}
+
+async fn accept_connections<
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    C: NetworkCodec<H, D, S, MS>,
+>(
+    listener: TcpListener,
+    incoming: UnboundedSender<NetworkData<H, D, S, MS>>,
+    recent_broadcasts: RecentBroadcasts<H>,
+    codec: C,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, address)) => {
+                debug!(target: "AlephBFT-network-tcp", "Accepted connection from {}.", address);
+                tokio::spawn(receive_frames(
+                    stream,
+                    address,
+                    incoming.clone(),
+                    recent_broadcasts.clone(),
+                    codec.clone(),
+                ));
+            }
+            Err(e) => {
+                warn!(target: "AlephBFT-network-tcp", "Failed to accept a connection: {}.", e);
+            }
+        }
+    }
+}
+
+async fn receive_frames<
+    H: Hasher,
+    D: Data,
+    S: Signature,
+    MS: PartialMultisignature,
+    C: NetworkCodec<H, D, S, MS>,
+>(
+    mut stream: TcpStream,
+    address: SocketAddr,
+    incoming: UnboundedSender<NetworkData<H, D, S, MS>>,
+    recent_broadcasts: RecentBroadcasts<H>,
+    codec: C,
+) {
+    loop {
+        let len = match stream.read_u32().await {
+            Ok(len) => len as usize,
+            Err(e) => {
+                debug!(target: "AlephBFT-network-tcp", "Connection from {} closed: {}.", address, e);
+                return;
+            }
+        };
+        if len > MAX_FRAME_LEN {
+            warn!(target: "AlephBFT-network-tcp", "Frame of {} bytes from {} is too long, closing the connection.", len, address);
+            return;
+        }
+        let mut payload = vec![0; len];
+        if let Err(e) = stream.read_exact(&mut payload).await {
+            debug!(target: "AlephBFT-network-tcp", "Connection from {} closed: {}.", address, e);
+            return;
+        }
+        #[cfg(feature = "compression")]
+        let payload = match super::decompress_payload(payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!(target: "AlephBFT-network-tcp", "Invalid compressed message from {}: {}, closing the connection.", address, e);
+                return;
+            }
+        };
+        if let Some(header) = codec.unit_header(&payload) {
+            if header.is_broadcast() {
+                let hash = H::hash(header.encoded());
+                let mut recent_broadcasts = recent_broadcasts.lock();
+                if recent_broadcasts.contains(&hash) {
+                    trace!(target: "AlephBFT-network-tcp", "Dropping a repeated broadcast of the unit of {:?} at round {} from {}.", header.creator(), header.round(), address);
+                    continue;
+                }
+                recent_broadcasts.insert(hash);
+            }
+        }
+        match codec.decode(&payload) {
+            Ok(data) => {
+                if incoming.unbounded_send(data).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!(target: "AlephBFT-network-tcp", "Undecodable message from {}: {}, closing the connection.", address, e);
+                return;
+            }
+        }
+    }
+}
+
+async fn send_frames(
+    peer: NodeIndex,
+    address: SocketAddr,
+    mut frames: UnboundedReceiver<Vec<u8>>,
+    reachable: Arc<AtomicBool>,
+) {
+    let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+    let mut n_failures = 0;
+    // A frame we failed to send, retried once the connection is reestablished.
+    let mut pending = None;
+    loop {
+        let mut stream = match TcpStream::connect(address).await {
+            Ok(stream) => {
+                info!(target: "AlephBFT-network-tcp", "Connected to {:?} at {}.", peer, address);
+                reconnect_delay = INITIAL_RECONNECT_DELAY;
+                n_failures = 0;
+                reachable.store(true, Ordering::Relaxed);
+                stream
+            }
+            Err(e) => {
+                debug!(target: "AlephBFT-network-tcp", "Failed to connect to {:?} at {}: {}.", peer, address, e);
+                n_failures += 1;
+                if n_failures == MAX_CONNECT_FAILURES {
+                    info!(target: "AlephBFT-network-tcp", "{:?} at {} is unreachable, dropping messages for it until we connect.", peer, address);
+                    reachable.store(false, Ordering::Relaxed);
+                    pending = None;
+                    while frames.try_recv().is_ok() {}
+                }
+                Delay::new(reconnect_delay).await;
+                reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_nodelay(true) {
+            debug!(target: "AlephBFT-network-tcp", "Failed to disable Nagle's algorithm: {}.", e);
+        }
+        loop {
+            let frame = match pending.take() {
+                Some(frame) => frame,
+                None => match frames.next().await {
+                    Some(frame) => frame,
+                    None => return,
+                },
+            };
+            if let Err(e) = stream.write_all(&frame).await {
+                debug!(target: "AlephBFT-network-tcp", "Connection to {:?} at {} failed: {}, reconnecting.", peer, address, e);
+                pending = Some(frame);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{SpawnHandle, TaskHandle};
+use futures::{channel::oneshot, future::BoxFuture, Future, FutureExt};
+use log::error;
+use parking_lot::Mutex;
+use std::{any::Any, panic::AssertUnwindSafe, sync::Arc};
+
+/// A panic of a task of the session, caught with
+/// [`TaskPanicPolicy::Report`](crate::TaskPanicPolicy::Report).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TaskPanic {
+    pub(crate) task: &'static str,
+    pub(crate) message: String,
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "a panic without a message".to_string()
+    }
+}
+
+/// Wraps a [`SpawnHandle`], so that, if enabled, the panics of the tasks are caught within them
+/// instead of reaching the spawner. The first panic is kept, and the handle of an essential task
+/// which panicked resolves to an error, like with spawners reporting panics themselves.
+#[derive(Clone)]
+pub(crate) struct PanicCatchingSpawner<SH: SpawnHandle> {
+    spawn_handle: SH,
+    first_panic: Option<Arc<Mutex<Option<TaskPanic>>>>,
+}
+
+impl<SH: SpawnHandle> PanicCatchingSpawner<SH> {
+    pub(crate) fn new(spawn_handle: SH, catch_panics: bool) -> Self {
+        PanicCatchingSpawner {
+            spawn_handle,
+            first_panic: catch_panics.then(Default::default),
+        }
+    }
+
+    pub(crate) fn catches_panics(&self) -> bool {
+        self.first_panic.is_some()
+    }
+
+    /// The first panic caught so far.
+    pub(crate) fn first_panic(&self) -> Option<TaskPanic> {
+        self.first_panic.as_ref()?.lock().clone()
+    }
+
+    /// Logs the panic of the task, keeping it if it is the first one.
+    pub(crate) fn on_panic(&self, task: &'static str, payload: &(dyn Any + Send)) {
+        let message = panic_message(payload);
+        error!(target: "AlephBFT-spawner", "Task {} panicked: {}.", task, message);
+        if let Some(first_panic) = &self.first_panic {
+            first_panic
+                .lock()
+                .get_or_insert(TaskPanic { task, message });
+        }
+    }
+
+    // Resolves to whether the task panicked.
+    fn catching(
+        &self,
+        name: &'static str,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> impl Future<Output = bool> + Send + 'static {
+        let spawner = self.clone();
+        async move {
+            match AssertUnwindSafe(task).catch_unwind().await {
+                Ok(()) => false,
+                Err(payload) => {
+                    spawner.on_panic(name, &*payload);
+                    true
+                }
+            }
+        }
+    }
+}
+
+impl<SH: SpawnHandle> SpawnHandle for PanicCatchingSpawner<SH> {
+    fn spawn(&self, name: &'static str, task: impl Future<Output = ()> + Send + 'static) {
+        if !self.catches_panics() {
+            return self.spawn_handle.spawn(name, task);
+        }
+        self.spawn_handle
+            .spawn(name, self.catching(name, task).map(|_| ()));
+    }
+
+    fn spawn_essential(
+        &self,
+        name: &'static str,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> TaskHandle {
+        if !self.catches_panics() {
+            return self.spawn_handle.spawn_essential(name, task);
+        }
+        let (panicked_tx, panicked_rx) = oneshot::channel();
+        let task = self.catching(name, task);
+        let handle = self.spawn_handle.spawn_essential(name, async move {
+            let _ = panicked_tx.send(task.await);
+        });
+        Box::pin(async move {
+            handle.await?;
+            match panicked_rx.await {
+                Ok(false) => Ok(()),
+                _ => Err(()),
+            }
+        })
+    }
+
+    fn yield_now(&self) -> BoxFuture<'static, ()> {
+        self.spawn_handle.yield_now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PanicCatchingSpawner, TaskPanic};
+    use crate::{testing::mock::Spawner, SpawnHandle};
+
+    #[tokio::test]
+    async fn catches_panics_of_essential_tasks() {
+        let spawner = PanicCatchingSpawner::new(Spawner::new(), true);
+        let handle = spawner.spawn_essential("fine", async {});
+        assert_eq!(handle.await, Ok(()));
+        assert_eq!(spawner.first_panic(), None);
+        let handle = spawner.spawn_essential("first", async { panic!("the first task failed") });
+        assert_eq!(handle.await, Err(()));
+        let handle =
+            spawner.spawn_essential("second", async { panic!("the {} task failed", "second") });
+        assert_eq!(handle.await, Err(()));
+        assert_eq!(
+            spawner.first_panic(),
+            Some(TaskPanic {
+                task: "first",
+                message: "the first task failed".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_tasks_on_when_not_catching() {
+        let spawner = PanicCatchingSpawner::new(Spawner::new(), false);
+        let handle = spawner.spawn_essential("fine", async {});
+        assert_eq!(handle.await, Ok(()));
+        assert!(!spawner.catches_panics());
+        assert_eq!(spawner.first_panic(), None);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    backup::{self, NoBackup, Record},
+    creation::{AllAvailableParents, Creator},
+    extender::{Extender, ExtenderUnit},
+    nodes::{NodeIndex, NodeMap, NodeWeights},
+    units::{unit_order, Unit, UnitCoord},
+    BackupSource, Data, Hasher, SessionStatus, Signature,
+};
+use futures::{channel::mpsc, stream, Stream, StreamExt};
+use log::{debug, error, info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// Replays the units saved in a backup through the consensus, offline, and yields the units in
+/// the order the member which saved the backup finalized them, with their parents. Members
+/// finalize the same units in the same order, so comparing the outputs for the backups of two
+/// members shows whether they agree, up to the shorter of them.
+///
+/// The backup is only read, and the signatures of the units are not checked, so it should come
+/// from a member we trust. The units of forkers are replayed as the first of their variants
+/// which the backup contains, like when recovering from it, and the units whose parents cannot be
+/// found are skipped, together with their descendants. This happens for backups compacted with
+/// [`Config::backup_compaction_threshold`](crate::Config::backup_compaction_threshold), which lack
+/// old units of other members. If the backup cannot be loaded, the error is logged and nothing is
+/// yielded.
+///
+/// The members are assumed to have uniform weights, see [`replay_backup_with_weights`] otherwise.
+pub fn replay_backup<H: Hasher, D: Data, S: Signature>(
+    source: impl BackupSource,
+) -> impl Stream<Item = Unit<H>> {
+    stream::once(async move { finalized_units::<H, D, S>(&source, None).await })
+        .flat_map(stream::iter)
+}
+
+/// Like [`replay_backup`], for a committee with the given weights of the members, see
+/// [`Config::node_weights`](crate::Config::node_weights). The backup does not record them, and
+/// the consensus decides differently with different weights.
+pub fn replay_backup_with_weights<H: Hasher, D: Data, S: Signature>(
+    source: impl BackupSource,
+    node_weights: NodeWeights,
+) -> impl Stream<Item = Unit<H>> {
+    stream::once(async move { finalized_units::<H, D, S>(&source, Some(node_weights)).await })
+        .flat_map(stream::iter)
+}
+
+async fn finalized_units<H: Hasher, D: Data, S: Signature>(
+    source: &dyn BackupSource,
+    node_weights: Option<NodeWeights>,
+) -> Vec<Unit<H>> {
+    // The sink is only needed for upgrading and repairing the backup, which we do not want here.
+    let records = match backup::load_records::<H, D, S>(source, &NoBackup).await {
+        Ok((records, _)) => records,
+        Err(e) => {
+            error!(target: "AlephBFT-replay", "Unable to load the backup: {}.", e);
+            return Vec::new();
+        }
+    };
+    let mut coords = HashSet::new();
+    let mut units = Vec::new();
+    for record in records {
+        if let Record::Unit(uu) = record {
+            let full_unit = uu.as_signable();
+            if coords.insert(full_unit.coord()) {
+                units.push(full_unit.unit());
+            } else {
+                debug!(target: "AlephBFT-replay", "Skipping another variant of the unit of {:?} at round {}.", full_unit.creator(), full_unit.round());
+            }
+        }
+    }
+    let node_weights = match (node_weights, units.first()) {
+        (Some(node_weights), _) => node_weights,
+        (None, Some(unit)) => NodeWeights::uniform(unit.control_hash().n_members()),
+        (None, None) => return Vec::new(),
+    };
+    let n_members = node_weights.n_members();
+    info!(target: "AlephBFT-replay", "Replaying {} units of a committee of {} members.", units.len(), n_members.0);
+    // All the parents of a unit are of the previous round, so this adds them before the unit. The
+    // units of a round are added in the same order whatever the order of the backup.
+    units.sort_by(unit_order);
+
+    let (_, electors) = mpsc::unbounded();
+    let (batch_tx, mut batches) = mpsc::unbounded();
+    let (finalized_rounds_tx, _finalized_rounds) = mpsc::unbounded();
+    // The index is only used in the logs, the backup does not say whose it is.
+    let mut extender = Extender::<H>::new(
+        NodeIndex(0),
+        node_weights.clone(),
+        None,
+        electors,
+        batch_tx,
+        finalized_rounds_tx,
+        SessionStatus::new(),
+    );
+    let mut added = HashMap::new();
+    let mut pending = HashMap::new();
+    let mut finalized = Vec::new();
+    for unit in units {
+        let parents = match parents(&unit, &node_weights, &added) {
+            Some(parents) => parents,
+            None => {
+                warn!(target: "AlephBFT-replay", "Skipping the unit of {:?} at round {}, as its parents are not in the backup.", unit.creator(), unit.round());
+                continue;
+            }
+        };
+        added.insert(UnitCoord::new(unit.round(), unit.creator()), unit.hash());
+        extender.add_elector(ExtenderUnit::new(
+            unit.creator(),
+            unit.round(),
+            unit.hash(),
+            parents.clone(),
+        ));
+        pending.insert(
+            unit.hash(),
+            unit.with_parents(parents.into_values().collect()),
+        );
+        while let Ok((_, batch)) = batches.try_recv() {
+            finalized.extend(batch.iter().filter_map(|hash| pending.remove(hash)));
+        }
+    }
+    finalized
+}
+
+/// The parents of the unit among the units already added, if they are all there and the unit can
+/// be recreated exactly from them.
+fn parents<H: Hasher>(
+    unit: &Unit<H>,
+    node_weights: &NodeWeights,
+    added: &HashMap<UnitCoord, H::Hash>,
+) -> Option<NodeMap<H::Hash>> {
+    let control_hash = unit.control_hash();
+    let n_members = node_weights.n_members();
+    if control_hash.n_members() != n_members {
+        return None;
+    }
+    let mut parents = NodeMap::with_size(n_members);
+    for creator in control_hash.parents() {
+        let coord = UnitCoord::new(unit.round().checked_sub(1)?, creator);
+        parents.insert(creator, *added.get(&coord)?);
+    }
+    // This also checks that the parents are valid ones for the unit.
+    let creator = Creator::<H>::new(
+        unit.creator(),
+        node_weights.clone(),
+        Arc::new(AllAvailableParents),
+    );
+    let (preunit, _) = creator
+        .create_unit_with_parents(unit.round(), parents.clone())
+        .ok()?;
+    (preunit.control_hash() == control_hash).then_some(parents)
+}
@@ -1,8 +1,10 @@
 //! Reliable MultiCast - a primitive for Reliable Broadcast protocol.
 use crate::{
-    nodes::NodeCount,
+    clock::{Clock, RealClock},
+    config::DelaySchedule,
+    nodes::{NodeCount, NodeIndex, NodeSubset},
     signed::{PartiallyMultisigned, Signable, Signed, UncheckedSigned},
-    Indexed, MultiKeychain, Multisigned, PartialMultisignature, Signature,
+    Index, Indexed, MultiKeychain, Multisigned, PartialMultisignature, Signature,
 };
 use async_trait::async_trait;
 use codec::{Decode, Encode};
@@ -11,14 +13,14 @@ use futures::{
     channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
     FutureExt, StreamExt,
 };
-use futures_timer::Delay;
 use log::{debug, warn};
 use std::{
     cmp::Reverse,
-    collections::{BinaryHeap, HashMap},
+    collections::{BinaryHeap, HashMap, HashSet},
+    convert::TryInto,
     hash::Hash,
+    sync::Arc,
     time,
-    time::Duration,
 };
 
 /// An RMC message consisting of either a signed (indexed) hash, or a multisigned hash.
@@ -40,6 +42,23 @@ impl<H: Signable, S: Signature, M: PartialMultisignature> Message<H, S, M> {
     }
 }
 
+/// How far we are in collecting a multisignature under a hash, see
+/// [`ReliableMulticast::with_progress`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MultisignatureProgress<H> {
+    /// The hash being multisigned.
+    pub hash: H,
+    /// The number of members whose signatures we collected.
+    pub collected: NodeCount,
+    /// The number of signatures a complete multisignature usually needs, i.e. more than two
+    /// thirds of the committee. The keychain has the final word, see [`MultiKeychain::is_complete`].
+    pub needed: NodeCount,
+}
+
+fn signature_threshold(n_members: NodeCount) -> NodeCount {
+    NodeCount(n_members.0 * 2 / 3 + 1)
+}
+
 /// A task of brodcasting a message.
 #[derive(Clone)]
 pub enum Task<H: Signable, MK: MultiKeychain> {
@@ -61,44 +80,38 @@ pub trait TaskScheduler<T>: Send + Sync {
 #[derive(Clone, Debug, Eq, PartialEq)]
 struct ScheduledTask<T> {
     task: T,
-    delay: time::Duration,
+    performed: usize,
 }
 
 impl<T> ScheduledTask<T> {
-    fn new(task: T, delay: time::Duration) -> Self {
-        ScheduledTask { task, delay }
+    fn new(task: T) -> Self {
+        ScheduledTask { task, performed: 0 }
     }
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
 struct IndexedInstant(time::Instant, usize);
 
-impl IndexedInstant {
-    fn now(i: usize) -> Self {
-        let curr_time = time::Instant::now();
-        IndexedInstant(curr_time, i)
-    }
-}
-
-/// A basic task scheduler scheduling tasks with an exponential slowdown
+/// A task scheduler repeating the tasks according to a [`DelaySchedule`].
 ///
-/// A scheduler parameterized by a duration `initial_delay`. When a task is added to the scheduler
-/// it is first scheduled immediately, then it is scheduled indefinitely, where the first delay is
-/// `initial_delay`, and each following delay for that task is two times longer than the previous
-/// one.
-pub struct DoublingDelayScheduler<T> {
-    initial_delay: time::Duration,
+/// When a task is added to the scheduler it is first scheduled immediately, then it is scheduled
+/// indefinitely, where `schedule(k)` is the delay between the kth and the (k+1)th time the task is
+/// performed. The time is measured with the given [`Clock`].
+pub struct DelayScheduler<T> {
+    schedule: DelaySchedule,
+    clock: Arc<dyn Clock>,
     scheduled_instants: BinaryHeap<Reverse<IndexedInstant>>,
     scheduled_tasks: Vec<ScheduledTask<T>>,
     on_new_task_tx: UnboundedSender<T>,
     on_new_task_rx: UnboundedReceiver<T>,
 }
 
-impl<T> DoublingDelayScheduler<T> {
-    pub fn new(initial_delay: time::Duration) -> Self {
+impl<T> DelayScheduler<T> {
+    pub fn new(schedule: DelaySchedule, clock: Arc<dyn Clock>) -> Self {
         let (on_new_task_tx, on_new_task_rx) = unbounded();
-        DoublingDelayScheduler {
-            initial_delay,
+        DelayScheduler {
+            schedule,
+            clock,
             scheduled_instants: BinaryHeap::new(),
             scheduled_tasks: Vec::new(),
             on_new_task_tx,
@@ -108,7 +121,7 @@ impl<T> DoublingDelayScheduler<T> {
 }
 
 #[async_trait]
-impl<T: Send + Sync + Clone> TaskScheduler<T> for DoublingDelayScheduler<T> {
+impl<T: Send + Sync + Clone> TaskScheduler<T> for DelayScheduler<T> {
     fn add_task(&mut self, task: T) {
         self.on_new_task_tx
             .unbounded_send(task)
@@ -116,14 +129,12 @@ impl<T: Send + Sync + Clone> TaskScheduler<T> for DoublingDelayScheduler<T> {
     }
 
     async fn next_task(&mut self) -> Option<T> {
-        let mut delay: futures::future::Fuse<_> = match self.scheduled_instants.peek() {
+        let mut delay = match self.scheduled_instants.peek() {
             Some(&Reverse(IndexedInstant(instant, _))) => {
-                let now = time::Instant::now();
-                if now > instant {
-                    Delay::new(Duration::new(0, 0)).fuse()
-                } else {
-                    Delay::new(instant - now).fuse()
-                }
+                let now = self.clock.now();
+                self.clock
+                    .sleep(instant.saturating_duration_since(now))
+                    .fuse()
             }
             None => futures::future::Fuse::terminated(),
         };
@@ -133,10 +144,9 @@ impl<T: Send + Sync + Clone> TaskScheduler<T> for DoublingDelayScheduler<T> {
             task = self.on_new_task_rx.next() => {
                 if let Some(task) = task {
                     let i = self.scheduled_tasks.len();
-                    let indexed_instant = IndexedInstant::now(i);
+                    let indexed_instant = IndexedInstant(self.clock.now(), i);
                     self.scheduled_instants.push(Reverse(indexed_instant));
-                    let scheduled_task = ScheduledTask::new(task, self.initial_delay);
-                    self.scheduled_tasks.push(scheduled_task);
+                    self.scheduled_tasks.push(ScheduledTask::new(task));
                 } else {
                     return None;
                 }
@@ -149,15 +159,44 @@ impl<T: Send + Sync + Clone> TaskScheduler<T> for DoublingDelayScheduler<T> {
         let scheduled_task = &mut self.scheduled_tasks[i];
 
         let task = scheduled_task.task.clone();
-        self.scheduled_instants
-            .push(Reverse(IndexedInstant(instant + scheduled_task.delay, i)));
-
-        scheduled_task.delay *= 2;
+        let delay = (self.schedule)(scheduled_task.performed);
+        scheduled_task.performed += 1;
+        // A delay so long that the time overflows means the task is never performed again.
+        if let Some(next_instant) = instant.checked_add(delay) {
+            self.scheduled_instants
+                .push(Reverse(IndexedInstant(next_instant, i)));
+        }
 
         Some(task)
     }
 }
 
+/// A basic task scheduler scheduling tasks with an exponential slowdown
+///
+/// A [`DelayScheduler`] using the wall clock, where the first delay of a task is `initial_delay`,
+/// and each following delay for that task is two times longer than the previous one.
+pub struct DoublingDelayScheduler<T>(DelayScheduler<T>);
+
+impl<T> DoublingDelayScheduler<T> {
+    pub fn new(initial_delay: time::Duration) -> Self {
+        let schedule = Arc::new(move |k: usize| {
+            initial_delay.saturating_mul(2u32.saturating_pow(k.try_into().unwrap_or(u32::MAX)))
+        });
+        DoublingDelayScheduler(DelayScheduler::new(schedule, Arc::new(RealClock)))
+    }
+}
+
+#[async_trait]
+impl<T: Send + Sync + Clone> TaskScheduler<T> for DoublingDelayScheduler<T> {
+    fn add_task(&mut self, task: T) {
+        self.0.add_task(task)
+    }
+
+    async fn next_task(&mut self) -> Option<T> {
+        self.0.next_task().await
+    }
+}
+
 /// Reliable Multicast Box
 ///
 /// The instance of [`ReliableMulticast<'a, H, MK>`] reliably broadcasts hashes of type `H`,
@@ -176,6 +215,11 @@ impl<T: Send + Sync + Clone> TaskScheduler<T> for DoublingDelayScheduler<T> {
 /// for a high-level description of this protocol and how it is used for fork alerts.
 pub struct ReliableMulticast<'a, H: Signable + Hash, MK: MultiKeychain> {
     hash_states: HashMap<H, PartiallyMultisigned<'a, H, MK>>,
+    // The members whose signatures we collected for the incomplete multisignatures.
+    signers: HashMap<H, NodeSubset>,
+    // The hashes we no longer broadcast our signature of, see `stop_rmc`.
+    stopped: HashSet<H>,
+    progress_tx: Option<UnboundedSender<MultisignatureProgress<H>>>,
     network_rx: UnboundedReceiver<Message<H, MK::Signature, MK::PartialMultisignature>>,
     network_tx: UnboundedSender<Message<H, MK::Signature, MK::PartialMultisignature>>,
     keychain: &'a MK,
@@ -196,6 +240,9 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
         let (multisigned_hashes_tx, multisigned_hashes_rx) = unbounded();
         ReliableMulticast {
             hash_states: HashMap::new(),
+            signers: HashMap::new(),
+            stopped: HashSet::new(),
+            progress_tx: None,
             network_rx,
             network_tx,
             keychain,
@@ -205,6 +252,16 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
         }
     }
 
+    /// Sends the progress of collecting every multisignature to `progress_tx`, each time we get
+    /// the signature of another member, until the multisignature is complete.
+    pub fn with_progress(
+        mut self,
+        progress_tx: UnboundedSender<MultisignatureProgress<H>>,
+    ) -> Self {
+        self.progress_tx = Some(progress_tx);
+        self
+    }
+
     /// Initiate a new instance of RMC for `hash`.
     pub async fn start_rmc(&mut self, hash: H) {
         debug!(target: "AlephBFT-rmc", "starting rmc for {:?}", hash);
@@ -212,13 +269,24 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
 
         let message = Message::SignedHash(signed_hash.into_unchecked());
         self.handle_message(message.clone());
-        let task = Task::BroadcastMessage(message);
-        self.do_task(task.clone());
-        self.scheduler.add_task(task);
+        // The scheduler performs the task for the first time right away.
+        self.scheduler.add_task(Task::BroadcastMessage(message));
+    }
+
+    /// Stops broadcasting our signature under `hash`. Nothing changes if the multisignature is
+    /// already complete. Otherwise the signatures we collected are kept, and the multisignature
+    /// still completes, and is broadcast, if enough signatures or a multisignature reach us.
+    pub fn stop_rmc(&mut self, hash: &H) {
+        if self.get_multisigned(hash).is_none() {
+            debug!(target: "AlephBFT-rmc", "stopping rmc for {:?}", hash);
+            self.stopped.insert(hash.clone());
+        }
     }
 
     fn on_complete_multisignature(&mut self, multisigned: Multisigned<'a, H, MK>) {
         let hash = multisigned.as_signable().clone();
+        self.signers.remove(&hash);
+        self.stopped.remove(&hash);
         self.hash_states.insert(
             hash,
             PartiallyMultisigned::Complete {
@@ -230,7 +298,6 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
             .expect("We own the the rx, so this can't fail");
 
         let task = Task::BroadcastMessage(Message::MultisignedHash(multisigned.into_unchecked()));
-        self.do_task(task.clone());
         self.scheduler.add_task(task);
     }
 
@@ -256,6 +323,7 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
                         return;
                     }
                 };
+                self.on_signature(&hash, signed_hash.as_signable().index());
 
                 let new_state = match self.hash_states.remove(&hash) {
                     None => signed_hash.into_partially_multisigned(self.keychain),
@@ -273,8 +341,38 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
         }
     }
 
+    fn on_signature(&mut self, hash: &H, signer: NodeIndex) {
+        let n_members = self.keychain.node_count();
+        let signers = self
+            .signers
+            .entry(hash.clone())
+            .or_insert_with(|| NodeSubset::with_size(n_members));
+        if signer.0 >= signers.size() || signers[signer] {
+            return;
+        }
+        signers.insert(signer);
+        let progress = MultisignatureProgress {
+            hash: hash.clone(),
+            collected: NodeCount(signers.elements().count()),
+            needed: signature_threshold(n_members),
+        };
+        debug!(target: "AlephBFT-rmc", "collected {} of {} signatures for {:?}", progress.collected.0, progress.needed.0, hash);
+        if let Some(progress_tx) = &self.progress_tx {
+            // Nobody listening for the progress is fine.
+            let _ = progress_tx.unbounded_send(progress);
+        }
+    }
+
     fn do_task(&self, task: Task<H, MK>) {
         let Task::BroadcastMessage(message) = task;
+        // Once the multisignature is complete, only the multisigned hash is worth broadcasting.
+        if let Message::SignedHash(_) = message {
+            if self.get_multisigned(message.hash()).is_some()
+                || self.stopped.contains(message.hash())
+            {
+                return;
+            }
+        }
         self.network_tx
             .unbounded_send(message)
             .expect("Sending message should succeed");
@@ -306,6 +404,11 @@ impl<'a, H: Signable + Hash + Eq + Clone + Debug, MK: MultiKeychain> ReliableMul
 
                 task = self.scheduler.next_task().fuse() => {
                     if let Some(task) = task {
+                        // The messages that already arrived might complete the multisignature,
+                        // making the task unnecessary.
+                        while let Some(Some(incoming_message)) = self.network_rx.next().now_or_never() {
+                            self.handle_message(incoming_message);
+                        }
                         self.do_task(task);
                     } else {
                         debug!(target: "AlephBFT-rmc", "Tasks ended");
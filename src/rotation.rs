@@ -0,0 +1,431 @@
+use crate::{
+    terminator::Terminator, Index, KeyBox, MultiKeychain, NodeCount, NodeIndex, Receiver, Round,
+    SessionId,
+};
+use futures::StreamExt;
+use log::{info, warn};
+use parking_lot::RwLock;
+use std::{fmt::Debug, sync::Arc};
+
+/// A keychain to use from the given round of a session on, see
+/// [`run_session_with_key_rotation`](crate::run_session_with_key_rotation).
+///
+/// The units of `round` and all the later rounds are signed and verified with `keychain`, the
+/// units from before keep being verified with the keychains they were signed with. All the members
+/// have to switch to keychains with the same public keys at the same round, so that they agree on
+/// the validity of every unit.
+#[derive(Clone, Debug)]
+pub struct NewKeychain<MK> {
+    pub round: Round,
+    pub keychain: MK,
+}
+
+/// Why a [`NewKeychain`] was not scheduled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RotationError {
+    /// The keychain has a different index or number of members than the session.
+    WrongMembership,
+    /// The round is not after the round of the last scheduled keychain.
+    NotAfterLastSwitch(Round),
+    /// We have already signed a unit of this round with an older keychain.
+    AlreadySigned(Round),
+}
+
+struct Schedule<MK: MultiKeychain> {
+    // Sorted by the rounds, the first keychain starts at round 0.
+    keychains: Vec<(Round, MK)>,
+    // The highest round of a unit we signed so far.
+    signed_round: Option<Round>,
+    // The keychain of `signed_round`, used for the messages without a round.
+    active: usize,
+}
+
+impl<MK: MultiKeychain> Schedule<MK> {
+    fn position_for_round(&self, round: Round) -> usize {
+        self.keychains.partition_point(|(start, _)| *start <= round) - 1
+    }
+
+    fn for_round(&self, round: Round) -> &MK {
+        &self.keychains[self.position_for_round(round)].1
+    }
+
+    fn active(&self) -> &MK {
+        &self.keychains[self.active].1
+    }
+
+    /// The keychains accepted for the messages without a round. Members might switch at
+    /// somewhat different times, so apart from the active keychain we accept the one before it
+    /// and the scheduled ones.
+    fn accepted(&self) -> impl Iterator<Item = &MK> {
+        self.keychains[self.active.saturating_sub(1)..]
+            .iter()
+            .map(|(_, keychain)| keychain)
+    }
+}
+
+/// A [`MultiKeychain`] switching between keychains at the starts of the scheduled rounds.
+///
+/// Units are signed and verified with the keychain of their round. Other messages, like alerts
+/// and the responses to newest unit requests, are signed with the keychain of our newest unit.
+/// Note that a single multisignature cannot combine signatures made with different keychains,
+/// so the multicasts started just around a switch might have to be repeated after it.
+#[derive(Clone)]
+pub(crate) struct RotatingKeychain<MK: MultiKeychain> {
+    index: NodeIndex,
+    node_count: NodeCount,
+    schedule: Arc<RwLock<Schedule<MK>>>,
+}
+
+impl<MK: MultiKeychain> Debug for RotatingKeychain<MK> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let schedule = self.schedule.read();
+        let rounds: Vec<_> = schedule.keychains.iter().map(|(round, _)| round).collect();
+        f.debug_struct("RotatingKeychain")
+            .field("index", &self.index)
+            .field("switch rounds", &rounds)
+            .field("signed round", &schedule.signed_round)
+            .finish()
+    }
+}
+
+impl<MK: MultiKeychain> RotatingKeychain<MK> {
+    /// A keychain using `keychain` until another one is scheduled.
+    pub(crate) fn new(keychain: MK) -> Self {
+        RotatingKeychain {
+            index: keychain.index(),
+            node_count: keychain.node_count(),
+            schedule: Arc::new(RwLock::new(Schedule {
+                keychains: vec![(0, keychain)],
+                signed_round: None,
+                active: 0,
+            })),
+        }
+    }
+
+    /// Schedules switching to a new keychain. It has to come after all the scheduled switches, and
+    /// before we sign our unit of its round.
+    pub(crate) fn schedule(&self, new_keychain: NewKeychain<MK>) -> Result<(), RotationError> {
+        let NewKeychain { round, keychain } = new_keychain;
+        if keychain.index() != self.index || keychain.node_count() != self.node_count {
+            return Err(RotationError::WrongMembership);
+        }
+        let mut schedule = self.schedule.write();
+        let last_switch = schedule.keychains.last().map_or(0, |(start, _)| *start);
+        if round <= last_switch {
+            return Err(RotationError::NotAfterLastSwitch(last_switch));
+        }
+        if let Some(signed_round) = schedule.signed_round.filter(|signed| *signed >= round) {
+            return Err(RotationError::AlreadySigned(signed_round));
+        }
+        schedule.keychains.push((round, keychain));
+        Ok(())
+    }
+
+    fn active(&self) -> MK {
+        self.schedule.read().active().clone()
+    }
+
+    fn on_signing(&self, round: Round) -> MK {
+        let mut schedule = self.schedule.write();
+        if schedule.signed_round.is_none_or(|signed| signed < round) {
+            schedule.signed_round = Some(round);
+            schedule.active = schedule.position_for_round(round);
+        }
+        schedule.for_round(round).clone()
+    }
+}
+
+impl<MK: MultiKeychain> Index for RotatingKeychain<MK> {
+    fn index(&self) -> NodeIndex {
+        self.index
+    }
+}
+
+#[async_trait::async_trait]
+impl<MK: MultiKeychain> KeyBox for RotatingKeychain<MK> {
+    type Signature = MK::Signature;
+
+    fn node_count(&self) -> NodeCount {
+        self.node_count
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Self::Signature {
+        let keychain = self.active();
+        keychain.sign(msg).await
+    }
+
+    fn verify(&self, msg: &[u8], sgn: &Self::Signature, index: NodeIndex) -> bool {
+        self.schedule
+            .read()
+            .accepted()
+            .any(|keychain| keychain.verify(msg, sgn, index))
+    }
+
+    async fn sign_in_round(&self, msg: &[u8], round: Round) -> Self::Signature {
+        let keychain = self.on_signing(round);
+        keychain.sign_in_round(msg, round).await
+    }
+
+    fn verify_in_round(
+        &self,
+        msg: &[u8],
+        sgn: &Self::Signature,
+        index: NodeIndex,
+        round: Round,
+    ) -> bool {
+        self.schedule
+            .read()
+            .for_round(round)
+            .verify_in_round(msg, sgn, index, round)
+    }
+}
+
+impl<MK: MultiKeychain> MultiKeychain for RotatingKeychain<MK> {
+    type PartialMultisignature = MK::PartialMultisignature;
+
+    fn from_signature(
+        &self,
+        signature: &Self::Signature,
+        index: NodeIndex,
+    ) -> Self::PartialMultisignature {
+        self.schedule
+            .read()
+            .active()
+            .from_signature(signature, index)
+    }
+
+    fn is_complete(&self, msg: &[u8], partial: &Self::PartialMultisignature) -> bool {
+        self.schedule
+            .read()
+            .accepted()
+            .any(|keychain| keychain.is_complete(msg, partial))
+    }
+
+    fn verify_batch(&self, batch: &[(&[u8], &Self::Signature, NodeIndex)]) -> bool {
+        let schedule = self.schedule.read();
+        // Usually all the signatures come from a single keychain, so we try to batch first.
+        schedule
+            .accepted()
+            .any(|keychain| keychain.verify_batch(batch))
+            || batch.iter().all(|(msg, sgn, index)| {
+                schedule
+                    .accepted()
+                    .any(|keychain| keychain.verify(msg, sgn, *index))
+            })
+    }
+
+    fn verify_batch_in_rounds(
+        &self,
+        batch: &[(&[u8], &Self::Signature, NodeIndex, Round)],
+    ) -> bool {
+        let schedule = self.schedule.read();
+        let mut batches = vec![Vec::new(); schedule.keychains.len()];
+        for item in batch {
+            batches[schedule.position_for_round(item.3)].push(*item);
+        }
+        batches
+            .iter()
+            .zip(schedule.keychains.iter())
+            .filter(|(batch, _)| !batch.is_empty())
+            .all(|(batch, (_, keychain))| keychain.verify_batch_in_rounds(batch))
+    }
+}
+
+/// Schedules the keychains coming from `new_keychains` until told to stop.
+pub(crate) async fn run<MK: MultiKeychain>(
+    keychain: RotatingKeychain<MK>,
+    mut new_keychains: Receiver<NewKeychain<MK>>,
+    session_id: SessionId,
+    mut terminator: Terminator,
+) {
+    let index = keychain.index();
+    loop {
+        futures::select! {
+            new_keychain = new_keychains.next() => match new_keychain {
+                Some(new_keychain) => {
+                    let round = new_keychain.round;
+                    match keychain.schedule(new_keychain) {
+                        Ok(()) => info!(target: "AlephBFT-rotation", "{:?} Switching to a new keychain at round {} of session {}.", index, round, session_id),
+                        Err(e) => warn!(target: "AlephBFT-rotation", "{:?} Ignoring a new keychain for round {}: {:?}.", index, round, e),
+                    }
+                }
+                None => break,
+            },
+            _ = terminator.get_exit() => break,
+        }
+    }
+    terminator.terminate_sync().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NewKeychain, RotatingKeychain, RotationError};
+    use crate::{
+        nodes::NodeSubset,
+        testing::mock::{Data, Hasher64},
+        units::{ControlHash, FullUnit, PreUnit, UnitCoord},
+        DefaultMultiKeychain, Hasher, Index, KeyBox, NodeCount, NodeIndex, Signed, UncheckedSigned,
+    };
+    use async_trait::async_trait;
+    use codec::{Decode, Encode};
+
+    #[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+    struct VersionedSignature {
+        msg: Vec<u8>,
+        index: NodeIndex,
+        version: u8,
+    }
+
+    #[derive(Clone, Debug)]
+    struct VersionedKeyBox {
+        index: NodeIndex,
+        version: u8,
+    }
+
+    impl Index for VersionedKeyBox {
+        fn index(&self) -> NodeIndex {
+            self.index
+        }
+    }
+
+    #[async_trait]
+    impl KeyBox for VersionedKeyBox {
+        type Signature = VersionedSignature;
+
+        fn node_count(&self) -> NodeCount {
+            NodeCount(4)
+        }
+
+        async fn sign(&self, msg: &[u8]) -> Self::Signature {
+            VersionedSignature {
+                msg: msg.to_vec(),
+                index: self.index,
+                version: self.version,
+            }
+        }
+
+        fn verify(&self, msg: &[u8], sgn: &Self::Signature, index: NodeIndex) -> bool {
+            sgn.msg == msg && sgn.index == index && sgn.version == self.version
+        }
+    }
+
+    type Keychain = DefaultMultiKeychain<VersionedKeyBox>;
+
+    fn keychain(index: usize, version: u8) -> Keychain {
+        DefaultMultiKeychain::new(VersionedKeyBox {
+            index: NodeIndex(index),
+            version,
+        })
+    }
+
+    fn rotating(index: usize, switches: &[(u16, u8)]) -> RotatingKeychain<Keychain> {
+        let rotating = RotatingKeychain::new(keychain(index, 0));
+        for (round, version) in switches {
+            rotating
+                .schedule(NewKeychain {
+                    round: *round,
+                    keychain: keychain(index, *version),
+                })
+                .expect("the switches are in order");
+        }
+        rotating
+    }
+
+    fn full_unit(creator: usize, round: u16) -> FullUnit<Hasher64, Data> {
+        let control_hash = ControlHash {
+            parents_mask: NodeSubset::with_size(NodeCount(4)),
+            combined_hash: 0.using_encoded(Hasher64::hash),
+        };
+        let pu = PreUnit::new(NodeIndex(creator), round, control_hash);
+        let data = Data::new(UnitCoord::new(round, NodeIndex(creator)), 0);
+        FullUnit::new(pu, data, 0)
+    }
+
+    #[tokio::test]
+    async fn units_are_signed_and_verified_with_the_keychain_of_their_round() {
+        let signer = rotating(0, &[(5, 1)]);
+        let verifier = rotating(1, &[(5, 1)]);
+        for round in [0, 4, 5, 9] {
+            let signed = Signed::sign(full_unit(0, round), &signer).await;
+            let unchecked = signed.into_unchecked();
+            let expected_version = if round < 5 { 0 } else { 1 };
+            assert_eq!(unchecked.signature().version, expected_version);
+            assert!(unchecked.is_signature_valid(&verifier));
+        }
+        // A unit of a round after the switch signed with the old keychain is invalid.
+        let unchecked = Signed::sign(full_unit(0, 7), &keychain(0, 0))
+            .await
+            .into_unchecked();
+        assert_eq!(
+            unchecked.is_signature_valid(&verifier),
+            cfg!(feature = "trusted")
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_are_split_by_keychain() {
+        let verifier = rotating(1, &[(5, 1)]);
+        let mut units = Vec::new();
+        for (round, version) in [(3, 0), (6, 1)] {
+            let unchecked = Signed::sign(full_unit(0, round), &keychain(0, version))
+                .await
+                .into_unchecked();
+            units.push(unchecked);
+        }
+        assert!(UncheckedSigned::are_signatures_valid(&units, &verifier));
+        let unchecked = Signed::sign(full_unit(0, 8), &keychain(0, 0))
+            .await
+            .into_unchecked();
+        units.push(unchecked);
+        assert_eq!(
+            UncheckedSigned::are_signatures_valid(&units, &verifier),
+            cfg!(feature = "trusted")
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_without_rounds_follow_our_newest_unit() {
+        let rotating = rotating(0, &[(5, 1), (10, 2)]);
+        assert_eq!(rotating.sign(b"message").await.version, 0);
+        rotating.sign_in_round(b"unit", 6).await;
+        assert_eq!(rotating.sign(b"message").await.version, 1);
+        let verify = |version| {
+            let signature = VersionedSignature {
+                msg: b"message".to_vec(),
+                index: NodeIndex(1),
+                version,
+            };
+            rotating.verify(b"message", &signature, NodeIndex(1))
+        };
+        // The previous keychain and the scheduled ones are accepted.
+        assert!(verify(0));
+        assert!(verify(1));
+        assert!(verify(2));
+        rotating.sign_in_round(b"unit", 10).await;
+        assert!(!verify(0));
+        assert!(verify(1));
+    }
+
+    #[tokio::test]
+    async fn refuses_switches_it_cannot_make() {
+        let rotating = rotating(0, &[(5, 1)]);
+        let new_keychain = |round, index| NewKeychain {
+            round,
+            keychain: keychain(index, 2),
+        };
+        assert_eq!(
+            rotating.schedule(new_keychain(5, 0)),
+            Err(RotationError::NotAfterLastSwitch(5))
+        );
+        assert_eq!(
+            rotating.schedule(new_keychain(8, 1)),
+            Err(RotationError::WrongMembership)
+        );
+        rotating.sign_in_round(b"unit", 8).await;
+        assert_eq!(
+            rotating.schedule(new_keychain(8, 0)),
+            Err(RotationError::AlreadySigned(8))
+        );
+        assert_eq!(rotating.schedule(new_keychain(9, 0)), Ok(()));
+    }
+}
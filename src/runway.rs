@@ -1,29 +1,58 @@
-use std::{collections::HashSet, convert::TryFrom};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    iter,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use crate::{
-    alerts::{self, Alert, AlertConfig, AlertMessage, ForkProof, ForkingNotification},
+    alerts::{
+        self, Alert, AlertConfig, AlertMessage, ForkEvidence, ForkHandler, ForkProof,
+        ForkingNotification,
+    },
+    backup::{self, BackupSink, BackupSource, Record},
+    clock::{time_between, Clock},
     consensus,
-    member::{NewestUnitResponse, UnitMessage},
+    creation::CreatorCommand,
+    member::{NewestUnitResponse, SessionError, SessionOutcome, UnitMessage},
     network::Recipient,
-    nodes::NodeMap,
+    nodes::{NodeMap, NodeSubset, NodeWeights},
+    status::{CreatorStall, DagRequests, DagShape, MemberActivity},
+    terminator::Terminator,
+    trace::{in_span, unit_event},
     units::{
-        ControlHash, FullUnit, PreUnit, SignedUnit, UncheckedSignedUnit, Unit, UnitCoord, UnitStore,
+        ControlHash, FullUnit, PreUnit, SignedUnit, UncheckedSignedUnit, Unit, UnitCoord,
+        UnitRejection, UnitRejectionReason, UnitStore,
     },
-    Config, Data, DataIO, Hasher, Index, MultiKeychain, NodeCount, NodeIndex, OrderedBatch,
-    Receiver, Round, Sender, SessionId, Signature, Signed, SpawnHandle, UncheckedSigned,
+    CatchUpConfig, Config, Data, DataChannelPolicy, FinalizedUnit, ForkPolicy, Hasher, Index,
+    KeyBox, LivenessConfig, MaxRoundPolicy, MultiKeychain, NodeCount, NodeIndex, Receiver,
+    ResponseLimitConfig, Round, Sender, SessionId, SessionStatus, Signature, Signed, SpawnHandle,
+    UncheckedSigned, UnitFinalizationHandler,
 };
+use codec::DecodeAll;
 use futures::{
     channel::{mpsc, oneshot},
-    future::FusedFuture,
-    pin_mut, FutureExt, StreamExt,
+    future::{maybe_done, poll_fn, BoxFuture, Fuse, MaybeDone},
+    pin_mut, ready, Future, FutureExt, StreamExt,
 };
 use log::{debug, error, info, trace, warn};
 use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher as _},
-    time::Duration,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
+// For how long we have to stay close to the highest round before we consider ourselves caught up,
+// so that a short lull in a bulk catch up does not count.
+const CAUGHT_UP_DEBOUNCE: Duration = Duration::from_secs(1);
+// How many of the highest rounds of the Dag are summarized in the status.
+const DAG_SHAPE_ROUNDS: Round = 10;
+// How often we check whether we are wedged, unless the threshold is even shorter.
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Type for incoming notifications: Runway to Consensus.
 #[derive(Clone, PartialEq)]
 pub(crate) enum NotificationIn<H: Hasher> {
@@ -47,6 +76,31 @@ pub(crate) enum NotificationOut<H: Hasher> {
     WrongControlHash(H::Hash),
     /// Notification that a new unit has been added to the DAG, list of decoded parents provided
     AddedToDag(H::Hash, Vec<H::Hash>),
+    /// Notification that the creator still cannot create a unit of the given round, even though
+    /// the creation delay has passed. Contains the numbers of available and required parents.
+    CreatorStalled {
+        round: Round,
+        parents_collected: NodeCount,
+        parents_needed: NodeCount,
+    },
+    /// Notification that the creator received a unit while the given number of units were still
+    /// waiting to be received, which exceeds the configured high-water mark.
+    CreatorLagging { backlog: usize },
+    /// Notification that the creator cannot create any units, because it was supposed to start
+    /// at a round not below the maximum round. The creator exits after sending it.
+    StartingRoundTooHigh {
+        starting_round: Round,
+        max_round: Round,
+    },
+    /// Notification that the creator reached [`MAX_MAX_ROUND`](crate::MAX_MAX_ROUND), so it
+    /// cannot create any more units. The creator exits after sending it.
+    RoundLimitReached,
+    /// Notification that the creator got within `max_round_warning_margin` rounds of the given
+    /// maximum round.
+    MaxRoundApproached(Round),
+    /// Notification that the creator reached the given maximum round, so it does not create any
+    /// more units until the maximum round is extended.
+    MaxRoundReached(Round),
 }
 
 pub(crate) enum Request<H: Hasher> {
@@ -65,6 +119,16 @@ pub(crate) enum RunwayNotificationOut<H: Hasher, D: Data, S: Signature> {
     NewUnit(UncheckedSignedUnit<H, D, S>),
     Request(Request<H>, Recipient),
     Response(Response<H, D, S>, NodeIndex),
+    CreatorStalled {
+        round: Round,
+        parents_collected: NodeCount,
+        parents_needed: NodeCount,
+    },
+    CreatorLagging {
+        backlog: usize,
+    },
+    // Whether we are catching up, see `Config::catch_up`.
+    CatchingUp(bool),
 }
 
 pub(crate) enum RunwayNotificationIn<H: Hasher, D: Data, S: Signature> {
@@ -102,19 +166,826 @@ impl<H: Hasher, D: Data, S: Signature> TryFrom<UnitMessage<H, D, S>>
     }
 }
 
+/// Remembers up to a fixed number of the most recently inserted hashes.
+pub(crate) struct RecentHashes<T> {
+    capacity: usize,
+    hashes: HashSet<T>,
+    order: VecDeque<T>,
+}
+
+impl<T: Copy + Eq + std::hash::Hash> RecentHashes<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        RecentHashes {
+            capacity,
+            hashes: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn contains(&self, hash: &T) -> bool {
+        self.hashes.contains(hash)
+    }
+
+    pub(crate) fn insert(&mut self, hash: T) {
+        if self.capacity == 0 || !self.hashes.insert(hash) {
+            return;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Remembers the hashes of the correctly signed units of every creator and round, up to a fixed
+/// number of different ones. The units still being verified count towards the limit as well.
+pub(crate) struct UnitVariants<T> {
+    limit: usize,
+    variants: HashMap<UnitCoord, Vec<T>>,
+    verifying: HashMap<UnitCoord, Vec<T>>,
+}
+
+impl<T: Copy + Eq> UnitVariants<T> {
+    pub(crate) fn new(limit: usize) -> Self {
+        UnitVariants {
+            limit,
+            variants: HashMap::new(),
+            verifying: HashMap::new(),
+        }
+    }
+
+    /// Whether we already know, or are verifying, the limit of other units with the coord.
+    pub(crate) fn is_full(&self, coord: &UnitCoord, hash: &T) -> bool {
+        let variants = self.variants.get(coord).map_or(&[][..], Vec::as_slice);
+        let verifying = self.verifying.get(coord).map_or(&[][..], Vec::as_slice);
+        variants.len() + verifying.len() >= self.limit
+            && !variants.contains(hash)
+            && !verifying.contains(hash)
+    }
+
+    /// Counts the unit towards the limit while its signature is being verified.
+    pub(crate) fn start_verifying(&mut self, coord: UnitCoord, hash: T) {
+        let verifying = self.verifying.entry(coord).or_default();
+        if !verifying.contains(&hash) {
+            verifying.push(hash);
+        }
+    }
+
+    /// Stops counting the unit as being verified, a correctly signed one is inserted after.
+    pub(crate) fn finish_verifying(&mut self, coord: &UnitCoord, hash: &T) {
+        if let Some(verifying) = self.verifying.get_mut(coord) {
+            verifying.retain(|h| h != hash);
+            if verifying.is_empty() {
+                self.verifying.remove(coord);
+            }
+        }
+    }
+
+    pub(crate) fn insert(&mut self, coord: UnitCoord, hash: T) {
+        let variants = self.variants.entry(coord).or_default();
+        if variants.len() < self.limit && !variants.contains(&hash) {
+            variants.push(hash);
+        }
+    }
+
+    /// Forgets the coords not satisfying the predicate. The units being verified are still
+    /// counted until they are verified.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&UnitCoord) -> bool) {
+        self.variants.retain(|coord, _| keep(coord));
+    }
+}
+
+/// Counts the units we sent in responses to every member recently, so that we send at most the
+/// configured number of them per window. Without a configured limit everything is allowed.
+struct ResponseLimiter {
+    config: Option<ResponseLimitConfig>,
+    // The numbers of units of the responses within the window, in the order of sending, and
+    // their sum.
+    sent: HashMap<NodeIndex, (VecDeque<(Instant, usize)>, usize)>,
+}
+
+impl ResponseLimiter {
+    fn new(config: Option<ResponseLimitConfig>) -> Self {
+        ResponseLimiter {
+            config,
+            sent: HashMap::new(),
+        }
+    }
+
+    /// Whether a response with `n_units` units may be sent to `requester` at `now`. If so, it is
+    /// recorded as sent. The first response within a window is always allowed, even if it is
+    /// larger than the limit, so that every response can be sent eventually.
+    fn allow(&mut self, requester: NodeIndex, n_units: usize, now: Instant) -> bool {
+        let config = match self.config {
+            Some(config) => config,
+            None => return true,
+        };
+        let (sent, total) = self.sent.entry(requester).or_default();
+        while let Some((sent_at, n_sent)) = sent.front() {
+            // Like for resending alerts, after the clock went back we forget what we sent.
+            if *sent_at <= now && now.duration_since(*sent_at) < config.window {
+                break;
+            }
+            *total -= n_sent;
+            sent.pop_front();
+        }
+        if *total > 0 && *total + n_units > config.max_units {
+            return false;
+        }
+        sent.push_back((now, n_units));
+        *total += n_units;
+        true
+    }
+}
+
+/// The calls for the data of our next units, in the order they were made. All of them make
+/// progress while we wait for anything, but the data is taken out only in this order.
+struct DataPipeline<D> {
+    depth: usize,
+    requests: VecDeque<MaybeDone<BoxFuture<'static, D>>>,
+}
+
+impl<D> DataPipeline<D> {
+    fn new(depth: usize) -> Self {
+        DataPipeline {
+            depth,
+            requests: VecDeque::new(),
+        }
+    }
+
+    /// Makes calls with `request` until `depth` of them are outstanding.
+    fn fill(&mut self, mut request: impl FnMut() -> BoxFuture<'static, D>) {
+        while self.requests.len() < self.depth {
+            self.requests.push_back(maybe_done(request()));
+        }
+    }
+
+    fn poll_requests(&mut self, cx: &mut Context<'_>) {
+        for request in self.requests.iter_mut() {
+            let _ = Pin::new(request).poll(cx);
+        }
+    }
+
+    /// Resolves to the data of the oldest call if `wanted`, and then there has to be one.
+    /// Otherwise it never resolves, but makes the calls progress, so that the data is ready once
+    /// we need it.
+    fn next(&mut self, wanted: bool) -> impl Future<Output = D> + Unpin + '_ {
+        poll_fn(move |cx| {
+            self.poll_requests(cx);
+            if !wanted {
+                return Poll::Pending;
+            }
+            let oldest = self.requests.front_mut().expect("the data was requested");
+            match Pin::new(oldest).take_output() {
+                Some(data) => {
+                    self.requests.pop_front();
+                    Poll::Ready(data)
+                }
+                None => Poll::Pending,
+            }
+        })
+    }
+
+    /// Drops the oldest call, which we stopped waiting for.
+    fn abandon_oldest(&mut self) {
+        self.requests.pop_front();
+    }
+}
+
+/// The finalized batches waiting to be passed to the finalization handler. The handler takes one
+/// batch at a time, in the order of finalization, while we keep processing units.
+struct DeliveryQueue<H: Hasher, D, E> {
+    max_rounds: Round,
+    // With the round of their highest unit, and the number of units finalized up to them.
+    waiting: VecDeque<(Vec<FinalizedUnit<H, D>>, Round, u64)>,
+    in_flight: Option<InFlightBatch<H, E>>,
+}
+
+struct InFlightBatch<H: Hasher, E> {
+    round: Round,
+    count: u64,
+    last: H::Hash,
+    handled: BoxFuture<'static, Result<(), E>>,
+}
+
+impl<H: Hasher, D, E> DeliveryQueue<H, D, E> {
+    fn new(max_rounds: Round) -> Self {
+        DeliveryQueue {
+            max_rounds,
+            waiting: VecDeque::new(),
+            in_flight: None,
+        }
+    }
+
+    fn push(&mut self, units: Vec<FinalizedUnit<H, D>>, count: u64) {
+        if let Some(round) = units.iter().map(|unit| unit.round).max() {
+            self.waiting.push_back((units, round, count));
+        }
+    }
+
+    /// Passes the oldest waiting batch to `handle`, unless the handler is still busy.
+    fn start(
+        &mut self,
+        handle: impl FnOnce(Vec<FinalizedUnit<H, D>>) -> BoxFuture<'static, Result<(), E>>,
+    ) {
+        if self.in_flight.is_some() {
+            return;
+        }
+        if let Some((units, round, count)) = self.waiting.pop_front() {
+            let last = units.last().expect("only nonempty batches wait").hash;
+            self.in_flight = Some(InFlightBatch {
+                round,
+                count,
+                last,
+                handled: handle(units),
+            });
+        }
+    }
+
+    /// Resolves once the handler is done with the batch it was passed, to the number of units
+    /// finalized up to it, its last unit and the result of the handler. Never resolves while no
+    /// batch is passed.
+    fn delivered(&mut self) -> impl Future<Output = (u64, H::Hash, Result<(), E>)> + Unpin + '_ {
+        poll_fn(move |cx| {
+            let in_flight = match &mut self.in_flight {
+                Some(in_flight) => in_flight,
+                None => return Poll::Pending,
+            };
+            let result = ready!(in_flight.handled.poll_unpin(cx));
+            let (count, last) = (in_flight.count, in_flight.last);
+            self.in_flight = None;
+            Poll::Ready((count, last, result))
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.in_flight.is_none() && self.waiting.is_empty()
+    }
+
+    /// Whether the batches not handled yet span more than `max_rounds` rounds, the last of them
+    /// being `finalized_round`.
+    fn too_far_behind(&self, finalized_round: Option<Round>) -> bool {
+        let oldest_round = match &self.in_flight {
+            Some(in_flight) => Some(in_flight.round),
+            None => self.waiting.front().map(|(_, round, _)| *round),
+        };
+        match (oldest_round, finalized_round) {
+            (Some(oldest), Some(finalized)) => finalized.saturating_sub(oldest) >= self.max_rounds,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RoundShape {
+    creators: NodeSubset,
+    // The creators of the units of the round with our unit of the previous round as a parent.
+    including_own: NodeSubset,
+    // For every member, the number of units of the round with its unit of the previous round as
+    // a parent.
+    n_including: Vec<usize>,
+    n_units: usize,
+    n_parents: usize,
+}
+
+/// Summarizes the units added to the Dag in the recent rounds, see [`DagShape`].
+pub(crate) struct DagShapeRecorder {
+    node_ix: NodeIndex,
+    node_weights: NodeWeights,
+    rounds: BTreeMap<Round, RoundShape>,
+    highest_quorum_round: Option<Round>,
+}
+
+impl DagShapeRecorder {
+    pub(crate) fn new(node_ix: NodeIndex, node_weights: NodeWeights) -> Self {
+        DagShapeRecorder {
+            node_ix,
+            node_weights,
+            rounds: BTreeMap::new(),
+            highest_quorum_round: None,
+        }
+    }
+
+    pub(crate) fn add_unit(&mut self, round: Round, creator: NodeIndex, parents: &NodeSubset) {
+        if round < self.lowest_round() {
+            return;
+        }
+        let n_members = self.node_weights.n_members();
+        let shape = self.rounds.entry(round).or_insert_with(|| RoundShape {
+            creators: NodeSubset::with_size(n_members),
+            including_own: NodeSubset::with_size(n_members),
+            n_including: vec![0; n_members.as_usize()],
+            n_units: 0,
+            n_parents: 0,
+        });
+        shape.creators.insert(creator);
+        shape.n_units += 1;
+        for parent in parents.elements() {
+            shape.n_parents += 1;
+            if let Some(n_including) = shape.n_including.get_mut(parent.as_usize()) {
+                *n_including += 1;
+            }
+            if parent == self.node_ix {
+                shape.including_own.insert(creator);
+            }
+        }
+        if self.node_weights.is_quorum(shape.creators.elements()) {
+            self.highest_quorum_round = self.highest_quorum_round.max(Some(round));
+        }
+        self.rounds = self.rounds.split_off(&self.lowest_round());
+    }
+
+    /// In how many of the recent rounds with our unit followed by a quorum of units a quorum
+    /// included our unit, and how many such rounds there are.
+    pub(crate) fn own_unit_inclusion(&self) -> (usize, usize) {
+        let mut included = 0;
+        let mut evaluated = 0;
+        for (round, shape) in &self.rounds {
+            if !shape
+                .creators
+                .elements()
+                .any(|creator| creator == self.node_ix)
+            {
+                continue;
+            }
+            let next = match self.rounds.get(&(round + 1)) {
+                Some(next) if self.node_weights.is_quorum(next.creators.elements()) => next,
+                _ => continue,
+            };
+            evaluated += 1;
+            if self.node_weights.is_quorum(next.including_own.elements()) {
+                included += 1;
+            }
+        }
+        (included, evaluated)
+    }
+
+    /// For every member, how many of the units of the recent rounds following one of its units
+    /// used that unit as a parent, and how many such units there are.
+    pub(crate) fn parent_inclusion(&self) -> Vec<(usize, usize)> {
+        let mut inclusion = vec![(0, 0); self.node_weights.n_members().as_usize()];
+        for (round, shape) in &self.rounds {
+            let next = match self.rounds.get(&(round + 1)) {
+                Some(next) => next,
+                None => continue,
+            };
+            for creator in shape.creators.elements() {
+                if let Some((included, evaluated)) = inclusion.get_mut(creator.as_usize()) {
+                    *included += next.n_including[creator.as_usize()];
+                    *evaluated += next.n_units;
+                }
+            }
+        }
+        inclusion
+    }
+
+    fn highest_round(&self) -> Option<Round> {
+        self.rounds.keys().next_back().copied()
+    }
+
+    fn lowest_round(&self) -> Round {
+        self.highest_round()
+            .map_or(0, |highest| highest.saturating_sub(DAG_SHAPE_ROUNDS - 1))
+    }
+
+    pub(crate) fn shape(&self) -> DagShape {
+        let n_units: usize = self.rounds.values().map(|shape| shape.n_units).sum();
+        let n_parents: usize = self.rounds.values().map(|shape| shape.n_parents).sum();
+        DagShape {
+            widths: self
+                .rounds
+                .iter()
+                .map(|(round, shape)| (*round, NodeCount(shape.creators.elements().count())))
+                .collect(),
+            average_parents: match n_units {
+                0 => 0.0,
+                n_units => n_parents as f64 / n_units as f64,
+            },
+            highest_round: self.highest_round(),
+            highest_quorum_round: self.highest_quorum_round,
+            own_unit_inclusion: match self.own_unit_inclusion() {
+                (_, 0) => None,
+                (included, evaluated) => Some(included as f64 / evaluated as f64),
+            },
+            parent_inclusion: self
+                .parent_inclusion()
+                .into_iter()
+                .map(|(included, evaluated)| match evaluated {
+                    0 => None,
+                    evaluated => Some(included as f64 / evaluated as f64),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Distributes incoming units among the signature verification tasks, and puts them back in
+/// the original order once verified.
+struct VerificationQueue<H: Hasher, D: Data, S: Signature> {
+    workers: Vec<Sender<(u64, UncheckedSignedUnit<H, D, S>)>>,
+    next_submitted: u64,
+    next_processed: u64,
+    verified: BTreeMap<u64, (UncheckedSignedUnit<H, D, S>, bool)>,
+}
+
+impl<H: Hasher, D: Data, S: Signature> VerificationQueue<H, D, S> {
+    fn new(workers: Vec<Sender<(u64, UncheckedSignedUnit<H, D, S>)>>) -> Self {
+        VerificationQueue {
+            workers,
+            next_submitted: 0,
+            next_processed: 0,
+            verified: BTreeMap::new(),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.workers.is_empty()
+    }
+
+    fn submit(&mut self, uu: UncheckedSignedUnit<H, D, S>) -> bool {
+        let seq = self.next_submitted;
+        self.next_submitted += 1;
+        let worker = &self.workers[(seq % self.workers.len() as u64) as usize];
+        worker.unbounded_send((seq, uu)).is_ok()
+    }
+
+    fn on_verified(&mut self, seq: u64, uu: UncheckedSignedUnit<H, D, S>, valid: bool) {
+        self.verified.insert(seq, (uu, valid));
+    }
+
+    /// The next verified unit in the order of submission, if it is verified already.
+    fn next_ready(&mut self) -> Option<(UncheckedSignedUnit<H, D, S>, bool)> {
+        let result = self.verified.remove(&self.next_processed)?;
+        self.next_processed += 1;
+        Some(result)
+    }
+}
+
+/// Counts the signatures a task verified since it last let the other tasks run, see
+/// [`Config::verification_budget`], and reports the time spent verifying them.
+struct VerificationBudget {
+    budget: Option<usize>,
+    spent: usize,
+    yield_now: Box<dyn Fn() -> BoxFuture<'static, ()> + Send>,
+    status: SessionStatus,
+}
+
+impl VerificationBudget {
+    fn new<SH: SpawnHandle>(
+        budget: Option<usize>,
+        spawn_handle: SH,
+        status: SessionStatus,
+    ) -> Self {
+        VerificationBudget {
+            budget,
+            spent: 0,
+            yield_now: Box::new(move || spawn_handle.yield_now()),
+            status,
+        }
+    }
+
+    fn verify<T>(&mut self, verify: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = verify();
+        self.status.on_unit_verified(start.elapsed());
+        self.spent += 1;
+        result
+    }
+
+    /// Lets the other tasks run once the budget is spent, and starts a new one.
+    async fn yield_if_spent(&mut self) {
+        if self.budget.is_some_and(|budget| self.spent >= budget) {
+            self.spent = 0;
+            (self.yield_now)().await;
+        }
+    }
+}
+
+async fn verify_units<H: Hasher, D: Data, KB: KeyBox>(
+    keybox: KB,
+    mut units: Receiver<(u64, UncheckedSignedUnit<H, D, KB::Signature>)>,
+    verified: Sender<(u64, UncheckedSignedUnit<H, D, KB::Signature>, bool)>,
+    mut budget: VerificationBudget,
+) {
+    while let Some((seq, uu)) = units.next().await {
+        let valid = budget.verify(|| uu.is_signature_valid(&keybox));
+        if verified.unbounded_send((seq, uu, valid)).is_err() {
+            break;
+        }
+        budget.yield_if_spent().await;
+    }
+}
+
+// How recovering from the backup ended.
+enum Recovery {
+    // All the records were recovered, or as many as we had time for.
+    Done,
+    Failed,
+    Exited,
+}
+
+// Tells whether we are wedged, see `SessionStatus::wedged`.
+struct LivenessMonitor {
+    config: LivenessConfig,
+    node_ix: NodeIndex,
+    node_weights: NodeWeights,
+    last_finalized_at: Instant,
+}
+
+impl LivenessMonitor {
+    fn new(
+        config: LivenessConfig,
+        node_ix: NodeIndex,
+        node_weights: NodeWeights,
+        now: Instant,
+    ) -> Self {
+        LivenessMonitor {
+            config,
+            node_ix,
+            node_weights,
+            last_finalized_at: now,
+        }
+    }
+
+    fn check_interval(&self) -> Duration {
+        self.config.stall_threshold.min(LIVENESS_CHECK_INTERVAL)
+    }
+
+    fn on_finalized(&mut self, now: Instant) {
+        self.last_finalized_at = now;
+    }
+
+    // The number of the active other members, if they count as an active network while we have
+    // not finalized anything for too long.
+    fn wedged(
+        &self,
+        now: Instant,
+        activity: impl Fn(NodeIndex) -> Option<MemberActivity>,
+    ) -> Option<NodeCount> {
+        if time_between(self.last_finalized_at, now) < self.config.stall_threshold {
+            return None;
+        }
+        let active: Vec<_> = self
+            .node_weights
+            .n_members()
+            .all_indices()
+            .filter(|ix| *ix != self.node_ix)
+            .filter(|ix| {
+                activity(*ix).is_some_and(|activity| {
+                    time_between(activity.last_unit_at, now) <= self.config.activity_window
+                })
+            })
+            .collect();
+        let network_active = match self.config.min_active_members {
+            Some(min_active_members) => active.len() >= min_active_members.0,
+            None => self
+                .node_weights
+                .is_quorum(active.iter().copied().chain(iter::once(self.node_ix))),
+        };
+        network_active.then_some(NodeCount(active.len()))
+    }
+}
+
+// Why units cannot be the parents of a unit, see `check_parent_coords`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ParentsMismatch {
+    Count {
+        expected: usize,
+        got: usize,
+    },
+    Round {
+        parent: UnitCoord,
+        round: Round,
+    },
+    Creator {
+        parent: UnitCoord,
+        expected: NodeIndex,
+    },
+}
+
+impl From<ParentsMismatch> for UnitRejectionReason {
+    fn from(mismatch: ParentsMismatch) -> Self {
+        match mismatch {
+            ParentsMismatch::Count { expected, got } => {
+                UnitRejectionReason::ParentCountMismatch { expected, got }
+            }
+            ParentsMismatch::Round { parent, .. } => UnitRejectionReason::ParentRoundMismatch {
+                parent_round: parent.round(),
+            },
+            ParentsMismatch::Creator { parent, expected } => {
+                UnitRejectionReason::ParentCreatorMismatch {
+                    expected,
+                    got: parent.creator(),
+                }
+            }
+        }
+    }
+}
+
+// Whether units of the given coords, in order, can be the parents of a unit of `round` with the
+// parents of `creators`, which are distinct. Then every parent is of the previous round, and
+// their creators are distinct too.
+fn check_parent_coords(
+    round: Round,
+    creators: &[NodeIndex],
+    parents: &[UnitCoord],
+) -> Result<(), ParentsMismatch> {
+    if creators.len() != parents.len() {
+        return Err(ParentsMismatch::Count {
+            expected: creators.len(),
+            got: parents.len(),
+        });
+    }
+    for (parent, creator) in parents.iter().zip(creators) {
+        if round == 0 || parent.round() != round - 1 {
+            return Err(ParentsMismatch::Round {
+                parent: *parent,
+                round,
+            });
+        }
+        if parent.creator() != *creator {
+            return Err(ParentsMismatch::Creator {
+                parent: *parent,
+                expected: *creator,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Tells whether we are catching up, see `Config::catch_up`.
+struct CatchUpMonitor {
+    config: CatchUpConfig,
+    catching_up: bool,
+}
+
+impl CatchUpMonitor {
+    fn new(config: CatchUpConfig) -> Self {
+        CatchUpMonitor {
+            config,
+            catching_up: false,
+        }
+    }
+
+    // How many rounds the last finalized round is below the highest one, counting the rounds
+    // before the first finalized one as well.
+    fn lag(last_finalized_round: Option<Round>, highest_observed_round: Round) -> Round {
+        match last_finalized_round {
+            Some(finalized) => highest_observed_round.saturating_sub(finalized),
+            None => highest_observed_round.saturating_add(1),
+        }
+    }
+
+    fn is_close(&self, lag: Round) -> bool {
+        lag <= self.config.caught_up_rounds
+    }
+
+    // Whether we just started or stopped catching up, if so. The thresholds differ, so that we do
+    // not flap at the boundary.
+    fn update(&mut self, lag: Round) -> Option<bool> {
+        let catching_up = if self.catching_up {
+            !self.is_close(lag)
+        } else {
+            lag > self.config.behind_rounds
+        };
+        if catching_up == self.catching_up {
+            return None;
+        }
+        self.catching_up = catching_up;
+        Some(catching_up)
+    }
+}
+
+// Finds the rounds reaching a quorum of units in the Dag, see `Config::quorum_round_sink`. Units
+// are added to the Dag only after their parents, a quorum of units of the previous round, so the
+// rounds reach a quorum in order.
+struct QuorumRounds {
+    node_weights: NodeWeights,
+    next_round: Round,
+    creators: BTreeMap<Round, NodeSubset>,
+}
+
+impl QuorumRounds {
+    fn new(node_weights: NodeWeights) -> Self {
+        QuorumRounds {
+            node_weights,
+            next_round: 0,
+            creators: BTreeMap::new(),
+        }
+    }
+
+    // The rounds that reached a quorum with the unit added to the Dag, in order.
+    fn add_unit(&mut self, round: Round, creator: NodeIndex) -> Vec<Round> {
+        if round < self.next_round {
+            return Vec::new();
+        }
+        let n_members = self.node_weights.n_members();
+        self.creators
+            .entry(round)
+            .or_insert_with(|| NodeSubset::with_size(n_members))
+            .insert(creator);
+        let mut reached = Vec::new();
+        while self
+            .creators
+            .get(&self.next_round)
+            .is_some_and(|creators| self.node_weights.is_quorum(creators.elements()))
+        {
+            self.creators.remove(&self.next_round);
+            reached.push(self.next_round);
+            match self.next_round.checked_add(1) {
+                Some(next_round) => self.next_round = next_round,
+                None => break,
+            }
+        }
+        reached
+    }
+}
+
 struct Runway<'a, H, D, MK, DP>
 where
     H: Hasher,
     D: Data,
     MK: MultiKeychain,
-    DP: DataIO<D>,
+    DP: UnitFinalizationHandler<H, D>,
 {
     missing_coords: HashSet<UnitCoord>,
+    clock: Arc<dyn Clock>,
+    data_timeout: Option<Duration>,
+    data_pipeline: DataPipeline<D>,
+    // Our units waiting for data, oldest first. Only the oldest one waits for the pipeline.
+    units_waiting_for_data: VecDeque<PreUnit<H>>,
+    // Fires when the oldest of them waited for `data_timeout`.
+    data_deadline: Fuse<BoxFuture<'static, ()>>,
+    data_channel_policy: DataChannelPolicy,
+    max_round_policy: MaxRoundPolicy,
+    // Whether we noticed that the data provider is closed.
+    data_closed: bool,
+    fork_handler: Arc<dyn ForkHandler>,
+    fork_policy: ForkPolicy,
+    backup_sink: Arc<dyn BackupSink>,
+    backup_source: Arc<dyn BackupSource>,
+    backup_compaction_threshold: usize,
+    recovery_timeout: Option<Duration>,
+    // The size of the backup in bytes, and its size right after the last compaction.
+    backup_size: usize,
+    compacted_backup_size: usize,
+    // The fork handler learns about every forker once, also across restarts, as we save the
+    // proofs in the backup.
+    reported_forkers: HashSet<NodeIndex>,
+    fork_proofs_to_save: Vec<ForkProof<H, D, MK::Signature>>,
+    skip_delivered_after_recovery: bool,
+    truncate_inconsistent_backup: bool,
+    session_tag: Vec<u8>,
+    // The number of units finalized in this session, counting the ones we order again after a
+    // restart, and the last record of units passed to the finalization handler before it.
+    n_finalized_units: u64,
+    delivered_before_recovery: Option<(u64, H::Hash)>,
+    delivery: DeliveryQueue<H, D, DP::Error>,
+    // Whether we hold the units back from the consensus until the finalization handler catches up.
+    delivery_lagging: bool,
+    // The round up to which the batches are committed elsewhere, until we order a batch above it.
+    externally_finalized_round: Option<Round>,
+    max_stored_units: Option<usize>,
+    store_overloaded: Arc<AtomicBool>,
+    max_data_size: Option<usize>,
+    // The hashes of the units we created, in this run or recovered from the backup, by round.
+    own_units: HashMap<Round, H::Hash>,
+    // When we created the units of `own_units` created in this run, until they are finalized.
+    #[cfg(feature = "timestamps")]
+    creation_times: HashMap<Round, std::time::SystemTime>,
+    last_finalized_round: Option<Round>,
+    highest_observed_round: Option<Round>,
+    close_to_highest_since: Option<Instant>,
+    catch_up: CatchUpMonitor,
     missing_parents: HashSet<H::Hash>,
+    response_limiter: ResponseLimiter,
+    processed_units: RecentHashes<H::Hash>,
+    // The units known to be finalized, see `Config::known_unit_hashes`.
+    known_units: HashSet<H::Hash>,
+    unit_variants: UnitVariants<H::Hash>,
+    dag_shape: DagShapeRecorder,
+    quorum_rounds: QuorumRounds,
+    quorum_round_sink: Option<Sender<Round>>,
+    unit_rejection_sink: Option<Sender<UnitRejection>>,
+    liveness: LivenessMonitor,
+    min_own_unit_inclusion: Option<f64>,
+    // Whether we warned that our units are rarely included by others.
+    rarely_included: bool,
+    min_parent_inclusion_balance: Option<f64>,
+    // Whether we warned that the units of some members are used as parents much less than others.
+    unbalanced_parents: bool,
+    // The creators we caught sending more variants of a unit than we process.
+    flooders: HashSet<NodeIndex>,
+    verification: VerificationQueue<H, D, MK::Signature>,
+    verified_units: Receiver<(u64, UncheckedSignedUnit<H, D, MK::Signature>, bool)>,
+    // For the signatures verified inline, i.e. without the verification workers.
+    verification_budget: VerificationBudget,
     node_ix: NodeIndex,
     session_id: SessionId,
     n_members: NodeCount,
-    threshold: NodeCount,
+    node_weights: NodeWeights,
     store: UnitStore<'a, H, D, MK>,
     keybox: &'a MK,
     alerts_for_alerter: Sender<Alert<H, D, MK::Signature>>,
@@ -124,33 +995,87 @@ where
     resolved_requests: Sender<Request<H>>,
     tx_consensus: Sender<NotificationIn<H>>,
     rx_consensus: Receiver<NotificationOut<H>>,
-    ordered_batch_rx: Receiver<Vec<H::Hash>>,
+    // Bounded, so that the creator waits while we are behind with processing its notifications.
+    rx_creator: mpsc::Receiver<NotificationOut<H>>,
+    ordered_batch_rx: Receiver<(Round, Vec<H::Hash>)>,
     data_io: DP,
     after_catch_up_delay: bool,
     starting_round_sender: Option<oneshot::Sender<Round>>,
     starting_round_value: Round,
+    // The highest round of our units in the backup, seen while checking it.
+    own_backup_round: Option<Round>,
+    recovered_units_sender: Option<oneshot::Sender<Vec<Unit<H>>>>,
     newest_unit_responders: HashSet<NodeIndex>,
+    creator_control: Receiver<CreatorCommand>,
+    commands_for_creator: Sender<CreatorCommand>,
+    status: SessionStatus,
+    dag_requests: DagRequests,
     salt: u64,
+    // The creator checks the starting round against the initial maximum round.
+    max_round: Round,
+    // Whether we only follow the consensus, without a creator.
+    observer: bool,
     exiting: bool,
+    outcome: SessionOutcome,
+    error: Option<SessionError>,
 }
 
-struct RunwayConfig<'a, H: Hasher, D: Data, DP: DataIO<D>, MK: MultiKeychain> {
+struct RunwayConfig<'a, H: Hasher, D: Data, DP: UnitFinalizationHandler<H, D>, MK: MultiKeychain> {
     node_ix: NodeIndex,
     session_id: SessionId,
     n_members: NodeCount,
+    node_weights: NodeWeights,
     max_round: Round,
+    processed_units_cache_size: usize,
+    max_unit_variants: usize,
+    min_own_unit_inclusion: Option<f64>,
+    min_parent_inclusion_balance: Option<f64>,
+    quorum_round_sink: Option<Sender<Round>>,
+    unit_rejection_sink: Option<Sender<UnitRejection>>,
+    liveness: LivenessConfig,
+    catch_up: CatchUpConfig,
+    response_limit: Option<ResponseLimitConfig>,
+    clock: Arc<dyn Clock>,
+    data_timeout: Option<Duration>,
+    data_pipeline_depth: usize,
+    data_channel_policy: DataChannelPolicy,
+    max_round_policy: MaxRoundPolicy,
+    fork_handler: Arc<dyn ForkHandler>,
+    fork_policy: ForkPolicy,
+    backup_sink: Arc<dyn BackupSink>,
+    backup_source: Arc<dyn BackupSource>,
+    backup_compaction_threshold: usize,
+    recovery_timeout: Option<Duration>,
+    skip_delivered_after_recovery: bool,
+    externally_finalized_round: Option<Round>,
+    known_unit_hashes: Vec<Vec<u8>>,
+    max_undelivered_rounds: Round,
+    truncate_inconsistent_backup: bool,
+    session_tag: Vec<u8>,
+    max_stored_units: Option<usize>,
+    store_overloaded: Arc<AtomicBool>,
+    max_data_size: Option<usize>,
+    verification_workers: Vec<Sender<(u64, UncheckedSignedUnit<H, D, MK::Signature>)>>,
+    verified_units: Receiver<(u64, UncheckedSignedUnit<H, D, MK::Signature>, bool)>,
+    verification_budget: VerificationBudget,
     keychain: &'a MK,
     data_io: DP,
     alerts_for_alerter: Sender<Alert<H, D, MK::Signature>>,
     notifications_from_alerter: Receiver<ForkingNotification<H, D, MK::Signature>>,
     tx_consensus: Sender<NotificationIn<H>>,
     rx_consensus: Receiver<NotificationOut<H>>,
+    rx_creator: mpsc::Receiver<NotificationOut<H>>,
     unit_messages_from_network: Receiver<RunwayNotificationIn<H, D, MK::Signature>>,
     unit_messages_for_network: Sender<RunwayNotificationOut<H, D, MK::Signature>>,
-    ordered_batch_rx: Receiver<Vec<H::Hash>>,
+    ordered_batch_rx: Receiver<(Round, Vec<H::Hash>)>,
     resolved_requests: Sender<Request<H>>,
     starting_round_sender: oneshot::Sender<Round>,
+    recovered_units_sender: oneshot::Sender<Vec<Unit<H>>>,
+    creator_control: Receiver<CreatorCommand>,
+    commands_for_creator: Sender<CreatorCommand>,
+    status: SessionStatus,
     salt: u64,
+    observer: bool,
 }
 
 impl<'a, H, D, MK, DP> Runway<'a, H, D, MK, DP>
@@ -158,20 +1083,86 @@ where
     H: Hasher,
     D: Data,
     MK: MultiKeychain,
-    DP: DataIO<D>,
+    DP: UnitFinalizationHandler<H, D>,
 {
     fn new(config: RunwayConfig<'a, H, D, DP, MK>) -> Self {
         let n_members = config.n_members;
-        let threshold = (n_members * 2) / 3 + NodeCount(1);
         let max_round = config.max_round;
         let store = UnitStore::new(n_members, max_round);
+        let liveness = LivenessMonitor::new(
+            config.liveness,
+            config.node_ix,
+            config.node_weights.clone(),
+            config.clock.now(),
+        );
+
+        let known_units: HashSet<_> = config
+            .known_unit_hashes
+            .iter()
+            .filter_map(|hash| H::Hash::decode_all(hash).ok())
+            .collect();
+        if known_units.len() < config.known_unit_hashes.len() {
+            warn!(target: "AlephBFT-runway", "{:?} Ignoring {} known unit hashes which are not valid hashes or repeat.", config.node_ix, config.known_unit_hashes.len() - known_units.len());
+        }
 
         Runway {
-            threshold,
             store,
             keybox: config.keychain,
             missing_coords: HashSet::new(),
+            clock: config.clock,
+            data_timeout: config.data_timeout,
+            data_pipeline: DataPipeline::new(config.data_pipeline_depth),
+            units_waiting_for_data: VecDeque::new(),
+            data_deadline: Fuse::terminated(),
+            data_channel_policy: config.data_channel_policy,
+            max_round_policy: config.max_round_policy,
+            data_closed: false,
+            fork_handler: config.fork_handler,
+            fork_policy: config.fork_policy,
+            backup_sink: config.backup_sink,
+            backup_source: config.backup_source,
+            backup_compaction_threshold: config.backup_compaction_threshold,
+            recovery_timeout: config.recovery_timeout,
+            backup_size: 0,
+            compacted_backup_size: 0,
+            reported_forkers: HashSet::new(),
+            fork_proofs_to_save: Vec::new(),
+            skip_delivered_after_recovery: config.skip_delivered_after_recovery,
+            truncate_inconsistent_backup: config.truncate_inconsistent_backup,
+            session_tag: config.session_tag,
+            n_finalized_units: 0,
+            delivered_before_recovery: None,
+            delivery: DeliveryQueue::new(config.max_undelivered_rounds),
+            delivery_lagging: false,
+            externally_finalized_round: config.externally_finalized_round,
+            max_stored_units: config.max_stored_units,
+            store_overloaded: config.store_overloaded,
+            max_data_size: config.max_data_size,
+            own_units: HashMap::new(),
+            #[cfg(feature = "timestamps")]
+            creation_times: HashMap::new(),
+            last_finalized_round: None,
+            highest_observed_round: None,
+            close_to_highest_since: None,
+            catch_up: CatchUpMonitor::new(config.catch_up),
             missing_parents: HashSet::new(),
+            response_limiter: ResponseLimiter::new(config.response_limit),
+            processed_units: RecentHashes::new(config.processed_units_cache_size),
+            known_units,
+            unit_variants: UnitVariants::new(config.max_unit_variants),
+            dag_shape: DagShapeRecorder::new(config.node_ix, config.node_weights.clone()),
+            quorum_rounds: QuorumRounds::new(config.node_weights.clone()),
+            quorum_round_sink: config.quorum_round_sink,
+            unit_rejection_sink: config.unit_rejection_sink,
+            liveness,
+            min_own_unit_inclusion: config.min_own_unit_inclusion,
+            rarely_included: false,
+            min_parent_inclusion_balance: config.min_parent_inclusion_balance,
+            unbalanced_parents: false,
+            flooders: HashSet::new(),
+            verification: VerificationQueue::new(config.verification_workers),
+            verified_units: config.verified_units,
+            verification_budget: config.verification_budget,
             resolved_requests: config.resolved_requests,
             alerts_for_alerter: config.alerts_for_alerter,
             notifications_from_alerter: config.notifications_from_alerter,
@@ -179,17 +1170,29 @@ where
             unit_messages_for_network: config.unit_messages_for_network,
             tx_consensus: config.tx_consensus,
             rx_consensus: config.rx_consensus,
+            rx_creator: config.rx_creator,
             ordered_batch_rx: config.ordered_batch_rx,
             data_io: config.data_io,
             after_catch_up_delay: false,
             starting_round_sender: Some(config.starting_round_sender),
             starting_round_value: 0,
+            own_backup_round: None,
+            recovered_units_sender: Some(config.recovered_units_sender),
             node_ix: config.node_ix,
             session_id: config.session_id,
             n_members: config.n_members,
+            node_weights: config.node_weights,
             newest_unit_responders: HashSet::new(),
+            creator_control: config.creator_control,
+            commands_for_creator: config.commands_for_creator,
+            dag_requests: config.status.dag_requests(),
+            status: config.status,
             salt: config.salt,
+            max_round,
+            observer: config.observer,
             exiting: false,
+            outcome: SessionOutcome::Exited,
+            error: None,
         }
     }
 
@@ -197,12 +1200,23 @@ where
         self.node_ix
     }
 
+    // Makes the runway exit, the first error is the one the session ends with.
+    fn fail(&mut self, error: SessionError) {
+        self.exiting = true;
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
     async fn on_unit_message(&mut self, message: RunwayNotificationIn<H, D, MK::Signature>) {
         match message {
             RunwayNotificationIn::NewUnit(u) => {
                 trace!(target: "AlephBFT-runway", "{:?} New unit received {:?}.", self.index(), &u);
                 self.on_unit_received(u, false)
             }
+            RunwayNotificationIn::Request(_, node_id) if self.is_isolated(node_id) => {
+                trace!(target: "AlephBFT-runway", "{:?} Ignoring a request of forker {:?}.", self.index(), node_id);
+            }
             RunwayNotificationIn::Request(request, node_id) => match request {
                 Request::Coord(coord) => {
                     trace!(target: "AlephBFT-runway", "{:?} Coords request received {:?}.", self.index(), coord);
@@ -236,18 +1250,103 @@ where
     }
 
     fn on_unit_received(&mut self, uu: UncheckedSignedUnit<H, D, MK::Signature>, alert: bool) {
-        if let Some(su) = self.validate_unit(uu) {
-            self.resolve_missing_coord(&su.as_signable().coord());
-            if alert {
-                // Units from alerts explicitly come from forkers, and we want them anyway.
-                self.store.add_unit(su, true);
-            } else {
-                self.add_unit_to_store_unless_fork(su);
-            }
+        let hash = uu.as_signable().hash();
+        // The hash does not cover the signature, but we already verified this unit with a valid one.
+        if !alert && self.processed_units.contains(&hash) {
+            trace!(target: "AlephBFT-runway", "{:?} Ignoring a recently processed unit {:?}.", self.index(), hash);
+            return;
         }
-    }
-
-    fn resolve_missing_coord(&mut self, coord: &UnitCoord) {
+        // The ones we asked for are still needed, e.g. as parents after restarting without a backup.
+        if !alert
+            && self.known_units.contains(&hash)
+            && !self.missing_coords.contains(&uu.as_signable().coord())
+        {
+            trace!(target: "AlephBFT-runway", "{:?} Ignoring a unit known to be finalized {:?}.", self.index(), hash);
+            return;
+        }
+        // Malformed units are rejected before we count their variants or verify their signatures.
+        if !self.validate_unit_structure(&uu) {
+            return;
+        }
+        // Only the creator can sign its units, so the variants we count cannot be forged by others.
+        // Units from alerts are needed to resolve forks, however many there are.
+        let coord = uu.as_signable().coord();
+        if !alert && self.unit_variants.is_full(&coord, &hash) {
+            if self.flooders.insert(coord.creator()) {
+                warn!(target: "AlephBFT-runway", "{:?} Received too many different units of {:?} of round {}, dropping the excess ones.", self.index(), coord.creator(), coord.round());
+            }
+            return;
+        }
+        if !alert && self.verification.is_enabled() {
+            self.unit_variants.start_verifying(coord, hash);
+            if !self.verification.submit(uu) {
+                warn!(target: "AlephBFT-runway", "{:?} Channel for unit verification should be open", self.index());
+                self.fail(SessionError::TaskStopped("runway/verification"));
+            }
+            return;
+        }
+        if let Some(su) = self.check_signature(uu) {
+            self.on_unit_validated(su, alert);
+        }
+    }
+
+    fn on_unit_verified(
+        &mut self,
+        seq: u64,
+        uu: UncheckedSignedUnit<H, D, MK::Signature>,
+        valid: bool,
+    ) {
+        self.verification.on_verified(seq, uu, valid);
+        while let Some((uu, valid)) = self.verification.next_ready() {
+            self.unit_variants
+                .finish_verifying(&uu.as_signable().coord(), &uu.as_signable().hash());
+            if !valid {
+                self.reject_unit(uu.as_signable(), UnitRejectionReason::BadSignature);
+                continue;
+            }
+            // The structure was validated before submitting the unit.
+            self.on_unit_validated(uu.assume_checked(self.keybox), false);
+        }
+    }
+
+    fn on_unit_validated(&mut self, su: SignedUnit<'a, H, D, MK>, alert: bool) {
+        if self.is_impersonating(su.as_signable()) {
+            let round = su.as_signable().round();
+            error!(target: "AlephBFT-runway", "{:?} Received a unit of round {} with our index that we did not create, another node is running with our index. Exiting.", self.index(), round);
+            self.fail(SessionError::DuplicateNodeIndex { round });
+            return;
+        }
+        self.processed_units.insert(su.as_signable().hash());
+        self.unit_variants
+            .insert(su.as_signable().coord(), su.as_signable().hash());
+        let round = su.as_signable().round();
+        self.highest_observed_round = self.highest_observed_round.max(Some(round));
+        self.status
+            .on_unit_seen(su.as_signable().creator(), round, self.clock.now());
+        self.resolve_missing_coord(&su.as_signable().coord());
+        if alert {
+            // Units from alerts explicitly come from forkers, and we want them anyway.
+            self.store.add_unit(su, true);
+        } else {
+            self.add_unit_to_store_unless_fork(su);
+        }
+    }
+
+    // Our units from before a restart without a backup are legit, but they are below the starting
+    // round. The units of a process impersonating us before we know the starting round are
+    // caught when we create our unit of the same round.
+    fn is_impersonating(&self, full_unit: &FullUnit<H, D>) -> bool {
+        if full_unit.creator() != self.index() {
+            return false;
+        }
+        let round = full_unit.round();
+        match self.own_units.get(&round) {
+            Some(hash) => *hash != full_unit.hash(),
+            None => self.starting_round_sender.is_none() && round >= self.starting_round_value,
+        }
+    }
+
+    fn resolve_missing_coord(&mut self, coord: &UnitCoord) {
         if self.missing_coords.remove(coord) {
             self.send_resolved_request_notification(Request::Coord(*coord));
         }
@@ -255,36 +1354,110 @@ where
 
     // TODO: we should return an error and handle it outside
     fn validate_unit(
-        &self,
+        &mut self,
+        uu: UncheckedSignedUnit<H, D, MK::Signature>,
+    ) -> Option<SignedUnit<'a, H, D, MK>> {
+        if !self.validate_unit_structure(&uu) {
+            return None;
+        }
+        self.check_signature(uu)
+    }
+
+    // The units known to be finalized were signed correctly, so we skip verifying them again.
+    fn validate_recovered_unit(
+        &mut self,
+        uu: UncheckedSignedUnit<H, D, MK::Signature>,
+    ) -> Option<SignedUnit<'a, H, D, MK>> {
+        if !self.known_units.contains(&uu.as_signable().hash()) {
+            return self.validate_unit(uu);
+        }
+        self.validate_unit_structure(&uu)
+            .then(|| uu.assume_checked(self.keybox))
+    }
+
+    fn check_signature(
+        &mut self,
         uu: UncheckedSignedUnit<H, D, MK::Signature>,
     ) -> Option<SignedUnit<'a, H, D, MK>> {
-        let su = match uu.check(self.keybox) {
-            Ok(su) => su,
-            Err(uu) => {
-                warn!(target: "AlephBFT-runway", "{:?} Wrong signature received {:?}.", self.index(), &uu);
-                return None;
+        let keybox = self.keybox;
+        match self.verification_budget.verify(|| uu.check(keybox)) {
+            Ok(su) => Some(su),
+            Err(e) => {
+                self.reject_unit(e.unchecked.as_signable(), UnitRejectionReason::BadSignature);
+                None
             }
+        }
+    }
+
+    // Everything that can be checked without the signature, so that it is cheap. A unit with
+    // parents breaking the structure of the Dag flags its creator, if it is correctly signed.
+    fn validate_unit_structure(&self, uu: &UncheckedSignedUnit<H, D, MK::Signature>) -> bool {
+        let reason = match self.check_unit_structure(uu.as_signable()) {
+            Ok(()) => return true,
+            Err(reason) => reason,
         };
-        let full_unit = su.as_signable();
+        self.reject_unit(uu.as_signable(), reason);
+        if let UnitRejectionReason::MalformedParents(_) = reason {
+            self.flag_malformed_unit(uu);
+        }
+        false
+    }
+
+    fn check_unit_structure(&self, full_unit: &FullUnit<H, D>) -> Result<(), UnitRejectionReason> {
+        // Checked first, as everything below and the store index their state by the creator.
+        // There is no key of such a creator, so whoever sent the unit made it up.
+        if full_unit.creator().0 >= self.n_members.0 {
+            return Err(UnitRejectionReason::OutOfRangeCreator);
+        }
         if full_unit.session_id() != self.session_id {
             // NOTE: this implies malicious behavior as the unit's session_id
             // is incompatible with session_id of the message it arrived in.
-            warn!(target: "AlephBFT-runway", "{:?} A unit with incorrect session_id! {:?}", self.index(), full_unit);
-            return None;
+            return Err(UnitRejectionReason::WrongSession(full_unit.session_id()));
         }
         if full_unit.round() > self.store.limit_per_node() {
-            warn!(target: "AlephBFT-runway", "{:?} A unit with too high round {}! {:?}", self.index(), full_unit.round(), full_unit);
-            return None;
+            return Err(UnitRejectionReason::TooHighRound);
         }
-        if full_unit.creator().0 >= self.n_members.0 {
-            warn!(target: "AlephBFT-runway", "{:?} A unit with too high creator index {}! {:?}", self.index(), full_unit.creator().0, full_unit);
-            return None;
+        // NOTE: at this point we cannot validate correctness of the control hash, in principle it could be
+        // just a random hash, but we still would not be able to deduce that by looking at the unit only.
+        // The parents are a subset of the members, so they have distinct creators, and they are of
+        // the previous round by definition, which is checked for the actual units in parents responses.
+        full_unit
+            .as_pre_unit()
+            .check_parents(&self.node_weights)
+            .map_err(UnitRejectionReason::MalformedParents)
+    }
+
+    // The messages units come in to the runway do not tell who sent them.
+    fn reject_unit(&self, full_unit: &FullUnit<H, D>, reason: UnitRejectionReason) {
+        warn!(target: "AlephBFT-runway", "{:?} Rejecting a unit of {:?} of round {}: {:?}. {:?}", self.index(), full_unit.creator(), full_unit.round(), reason, full_unit);
+        self.report_rejection(UnitRejection {
+            creator: full_unit.creator(),
+            round: full_unit.round(),
+            sender: None,
+            reason,
+        });
+    }
+
+    fn report_rejection(&self, rejection: UnitRejection) {
+        if let Some(sink) = &self.unit_rejection_sink {
+            // Nobody listens anymore if the channel is closed, and a rejection is no reason to stop.
+            if sink.unbounded_send(rejection).is_err() {
+                trace!(target: "AlephBFT-runway", "{:?} Unit rejection channel closed.", self.index());
+            }
         }
-        if !self.validate_unit_parents(&su) {
-            warn!(target: "AlephBFT-runway", "{:?} A unit did not pass parents validation. {:?}", self.index(), full_unit);
-            return None;
+    }
+
+    // Only the creator can sign its units, so a correctly signed malformed unit proves the
+    // creator is malicious. The signature is only verified until the creator is flagged.
+    fn flag_malformed_unit(&self, uu: &UncheckedSignedUnit<H, D, MK::Signature>) {
+        let creator = uu.as_signable().creator();
+        if self.status.is_malformed_unit_creator(creator) {
+            return;
+        }
+        if uu.clone().check(self.keybox).is_ok() {
+            warn!(target: "AlephBFT-runway", "{:?} {:?} signed a malformed unit, flagging it.", self.index(), creator);
+            self.status.on_malformed_unit(creator);
         }
-        Some(su)
     }
 
     fn add_unit_to_store_unless_fork(&mut self, su: SignedUnit<'a, H, D, MK>) {
@@ -305,47 +1478,54 @@ where
             // There is no point in keeping this unit in any kind of buffer.
             return;
         }
+        unit_event!(
+            hash = ?full_unit.hash(),
+            round = full_unit.round(),
+            creator = full_unit.creator().0,
+            "unit received"
+        );
         self.store.add_unit(su, false);
     }
 
-    fn validate_unit_parents(&self, su: &SignedUnit<H, D, MK>) -> bool {
-        // NOTE: at this point we cannot validate correctness of the control hash, in principle it could be
-        // just a random hash, but we still would not be able to deduce that by looking at the unit only.
-        let pre_unit = su.as_signable().as_pre_unit();
-        if pre_unit.n_members() != self.n_members {
-            warn!(target: "AlephBFT-runway", "{:?} Unit with wrong length of parents map.", self.index());
-            return false;
-        }
-        let round = pre_unit.round();
-        let n_parents = pre_unit.n_parents();
-        if round == 0 && n_parents > NodeCount(0) {
-            warn!(target: "AlephBFT-runway", "{:?} Unit of round zero with non-zero number of parents.", self.index());
-            return false;
+    fn on_new_forker_detected(&mut self, forker: NodeIndex, proof: ForkProof<H, D, MK::Signature>) {
+        unit_event!(forker = forker.0, "forker detected");
+        let alerted_units = self.store.mark_forker(forker);
+        let new_forker = self.reported_forkers.insert(forker);
+        if new_forker {
+            self.fork_proofs_to_save.push(proof.clone());
         }
-        let threshold = self.threshold();
-        if round > 0 && n_parents < threshold {
-            warn!(target: "AlephBFT-runway", "{:?} Unit of non-zero round with only {:?} parents while at least {:?} are required.", self.index(), n_parents, threshold);
-            return false;
+        let evidence = ForkEvidence::from_proof(&proof);
+        let alert = self.form_alert(proof, alerted_units);
+        if self.alerts_for_alerter.unbounded_send(alert).is_err() {
+            warn!(target: "AlephBFT-runway", "{:?} Channel to alerter should be open", self.index());
+            self.fail(SessionError::TaskStopped("runway/alerter"));
         }
-        let control_hash = &pre_unit.control_hash();
-        if round > 0 && !control_hash.parents_mask[pre_unit.creator()] {
-            warn!(target: "AlephBFT-runway", "{:?} Unit does not have its creator's previous unit as parent.", self.index());
-            return false;
+        // The alert goes out first, so that the others learn about the fork even if we stop.
+        if new_forker {
+            self.on_fork_proven(evidence);
         }
-        true
     }
 
-    fn threshold(&self) -> NodeCount {
-        self.threshold
+    // Called once for every forker, also for the ones with proofs recovered from the backup.
+    fn on_fork_proven(&mut self, evidence: ForkEvidence) {
+        match self.fork_policy {
+            ForkPolicy::Alert => {}
+            ForkPolicy::Isolate => {
+                info!(target: "AlephBFT-runway", "{:?} {:?} forked, no longer answering its requests.", self.index(), evidence.forker);
+            }
+            ForkPolicy::Shutdown => {
+                error!(target: "AlephBFT-runway", "{:?} {:?} forked in round {}, stopping the session.", self.index(), evidence.forker, evidence.round);
+                self.fail(SessionError::ForkDetected {
+                    forker: evidence.forker,
+                    round: evidence.round,
+                });
+            }
+        }
+        self.fork_handler.on_fork(evidence);
     }
 
-    fn on_new_forker_detected(&mut self, forker: NodeIndex, proof: ForkProof<H, D, MK::Signature>) {
-        let alerted_units = self.store.mark_forker(forker);
-        let alert = self.form_alert(proof, alerted_units);
-        if self.alerts_for_alerter.unbounded_send(alert).is_err() {
-            warn!(target: "AlephBFT-runway", "{:?} Channel to alerter should be open", self.index());
-            self.exiting = true;
-        }
+    fn is_isolated(&self, node_id: NodeIndex) -> bool {
+        self.fork_policy == ForkPolicy::Isolate && self.reported_forkers.contains(&node_id)
     }
 
     fn form_alert(
@@ -365,6 +1545,10 @@ where
         let maybe_su = (self.store.unit_by_coord(coord)).cloned();
 
         if let Some(su) = maybe_su {
+            if !self.response_limiter.allow(node_id, 1, self.clock.now()) {
+                debug!(target: "AlephBFT-runway", "{:?} Not answering fetch request for coord {:?} from {:?}, we sent it enough units recently.", self.index(), coord, node_id);
+                return;
+            }
             trace!(target: "AlephBFT-runway", "{:?} Answering fetch request for coord {:?} from {:?}.", self.index(), coord, node_id);
             self.send_message_for_network(RunwayNotificationOut::Response(
                 Response::Coord(su.into()),
@@ -395,6 +1579,13 @@ where
                     return;
                 }
             }
+            if !self
+                .response_limiter
+                .allow(node_id, full_units.len(), self.clock.now())
+            {
+                debug!(target: "AlephBFT-runway", "{:?} Not answering parents request for hash {:?} from {:?}, we sent it enough units recently.", self.index(), u_hash, node_id);
+                return;
+            }
             self.send_message_for_network(RunwayNotificationOut::Response(
                 Response::Parents(u_hash, full_units),
                 node_id,
@@ -435,12 +1626,12 @@ where
             trace!(target: "AlephBFT-runway", "{:?} We got parents response but already know the parents.", self.index());
             return;
         }
-        let (u_round, u_control_hash, parent_ids) = match self.store.unit_by_hash(&u_hash) {
+        let (u_coord, u_control_hash, parent_ids) = match self.store.unit_by_hash(&u_hash) {
             Some(su) => {
                 let full_unit = su.as_signable();
                 let parent_ids: Vec<_> = full_unit.control_hash().parents().collect();
                 (
-                    full_unit.round(),
+                    full_unit.coord(),
                     full_unit.control_hash().combined_hash,
                     parent_ids,
                 )
@@ -451,48 +1642,64 @@ where
             }
         };
 
-        if parent_ids.len() != parents.len() {
-            warn!(target: "AlephBFT-runway", "{:?} In received parent response expected {} parents got {} for unit {:?}.", self.index(), parents.len(), parent_ids.len(), u_hash);
-            return;
-        }
-
-        let mut p_hashes_node_map = NodeMap::with_size(self.n_members);
-        for (i, uu) in parents.into_iter().enumerate() {
-            let su = match self.validate_unit(uu) {
-                None => {
-                    warn!(target: "AlephBFT-runway", "{:?} In received parent response received a unit that does not pass validation.", self.index());
-                    return;
+        let (parents, p_hashes) = match self.validate_parents(
+            u_coord.round(),
+            u_control_hash,
+            &parent_ids,
+            parents,
+        ) {
+            Ok(parents) => parents,
+            Err(reason) => {
+                warn!(target: "AlephBFT-runway", "{:?} Rejected a parents response for {:?}: {:?}.", self.index(), u_hash, reason);
+                // The units that fail validation themselves are reported on their own.
+                if let Some(reason) = reason {
+                    self.report_rejection(UnitRejection {
+                        creator: u_coord.creator(),
+                        round: u_coord.round(),
+                        sender: None,
+                        reason,
+                    });
                 }
-                Some(su) => su,
-            };
-            let full_unit = su.as_signable();
-            if full_unit.round() + 1 != u_round {
-                warn!(target: "AlephBFT-runway", "{:?} In received parent response received a unit with wrong round.", self.index());
                 return;
             }
-            if full_unit.creator() != parent_ids[i] {
-                warn!(target: "AlephBFT-runway", "{:?} In received parent response received a unit with wrong creator.", self.index());
-                return;
-            }
-            let p_hash = full_unit.hash();
-            let ix = full_unit.creator();
-            p_hashes_node_map.insert(ix, p_hash);
+        };
+        for su in parents {
             // There might be some optimization possible here to not validate twice, but overall
             // this piece of code should be executed extremely rarely.
             self.resolve_missing_coord(&su.as_signable().coord());
             self.add_unit_to_store_unless_fork(su);
         }
-
-        if ControlHash::<H>::combine_hashes(&p_hashes_node_map) != u_control_hash {
-            warn!(target: "AlephBFT-runway", "{:?} In received parent response the control hash is incorrect {:?}.", self.index(), p_hashes_node_map);
-            return;
-        }
-        let p_hashes: Vec<_> = p_hashes_node_map.into_values().collect();
         self.store.add_parents(u_hash, p_hashes.clone());
         trace!(target: "AlephBFT-runway", "{:?} Succesful parents response for {:?}.", self.index(), u_hash);
         self.send_consensus_notification(NotificationIn::UnitParents(u_hash, p_hashes));
     }
 
+    // The whole response is validated before any of the parents gets to the store. The cheap
+    // checks of the rounds and creators come before verifying the signatures. Fails without a
+    // reason if one of the parents fails validation as a unit.
+    fn validate_parents(
+        &mut self,
+        u_round: Round,
+        u_control_hash: H::Hash,
+        parent_ids: &[NodeIndex],
+        parents: Vec<UncheckedSignedUnit<H, D, MK::Signature>>,
+    ) -> Result<(Vec<SignedUnit<'a, H, D, MK>>, Vec<H::Hash>), Option<UnitRejectionReason>> {
+        let coords: Vec<_> = parents.iter().map(|uu| uu.as_signable().coord()).collect();
+        check_parent_coords(u_round, parent_ids, &coords)
+            .map_err(|mismatch| Some(mismatch.into()))?;
+        let mut p_hashes_node_map = NodeMap::with_size(self.n_members);
+        let mut checked = Vec::with_capacity(parents.len());
+        for uu in parents {
+            let su = self.validate_unit(uu).ok_or(None)?;
+            p_hashes_node_map.insert(su.as_signable().creator(), su.as_signable().hash());
+            checked.push(su);
+        }
+        if ControlHash::<H>::combine_hashes(&p_hashes_node_map) != u_control_hash {
+            return Err(Some(UnitRejectionReason::WrongControlHash));
+        }
+        Ok((checked, p_hashes_node_map.into_values().collect()))
+    }
+
     fn on_newest_response(
         &mut self,
         unchecked_response: UncheckedSigned<NewestUnitResponse<H, D, MK::Signature>, MK::Signature>,
@@ -531,7 +1738,7 @@ where
                 .unit_by_hash(&checked_unit.as_signable().hash())
                 .is_none()
             {
-                let starting_round_candidate = checked_unit.as_signable().round() + 1;
+                let starting_round_candidate = checked_unit.as_signable().round().saturating_add(1);
                 self.on_unit_received(checked_unit.into(), false);
                 if starting_round_candidate > self.starting_round_value {
                     self.starting_round_value = starting_round_candidate;
@@ -545,7 +1752,13 @@ where
     }
 
     fn is_starting_round_ready(&self) -> bool {
-        self.after_catch_up_delay && self.newest_unit_responders.len() + 1 >= self.threshold.0
+        self.after_catch_up_delay
+            && self.node_weights.is_quorum(
+                self.newest_unit_responders
+                    .iter()
+                    .copied()
+                    .chain(iter::once(self.index())),
+            )
     }
 
     fn resolve_starting_round(&mut self) {
@@ -556,14 +1769,24 @@ where
                 return;
             }
         };
-        if starting_round_sender
+        if self.observer {
+            debug!(target: "AlephBFT-runway", "{:?} Caught up at round {}, not creating units as an observer.", self.index(), self.starting_round_value);
+        } else if starting_round_sender
             .send(self.starting_round_value)
             .is_err()
         {
             error!(target: "AlephBFT-runway", "unable to send starting round to creator");
-            self.exiting = true;
+            self.fail(SessionError::TaskStopped("consensus/creation"));
             return;
         }
+        // The creator reports this and exits, but the consensus might stop before we hear about
+        // it, so we make sure the session ends with the right error.
+        if !self.observer && self.starting_round_value >= self.max_round {
+            self.fail(SessionError::StartingRoundTooHigh {
+                starting_round: self.starting_round_value,
+                max_round: self.max_round,
+            });
+        }
         if let Err(e) = self
             .resolved_requests
             .unbounded_send(Request::NewestUnit(self.salt))
@@ -578,10 +1801,143 @@ where
         }
     }
 
+    // Asks for the data of the oldest unit waiting for it. The select loop in `run` waits for
+    // the data, so that a slow data provider does not hold up anything else.
+    fn request_data(&mut self) {
+        if self.units_waiting_for_data.is_empty() {
+            return;
+        }
+        let data_io = &self.data_io;
+        self.data_pipeline.fill(|| data_io.get_data_async());
+        self.data_deadline = match self.data_timeout {
+            Some(timeout) => self.clock.sleep(timeout).fuse(),
+            None => Fuse::terminated(),
+        };
+    }
+
+    async fn on_data(&mut self, data: D) {
+        let round = self
+            .units_waiting_for_data
+            .front()
+            .expect("only the data of a waiting unit is taken")
+            .round();
+        let data = match self.data_io.finalize_data(data, round) {
+            Some(data) => data,
+            None => match self.data_io.fallback_data() {
+                Some(fallback) => {
+                    debug!(target: "AlephBFT-runway", "{:?} The data for our unit of round {} was rejected, using the fallback data.", self.index(), round);
+                    fallback
+                }
+                None => {
+                    debug!(target: "AlephBFT-runway", "{:?} The data for our unit of round {} was rejected, asking for new data.", self.index(), round);
+                    self.request_data();
+                    return;
+                }
+            },
+        };
+        self.create_waiting_unit(data).await;
+    }
+
+    async fn on_data_timeout(&mut self) {
+        let round = match self.units_waiting_for_data.front() {
+            Some(u) => u.round(),
+            None => return,
+        };
+        let timeout = self.data_timeout.unwrap_or_default();
+        match self.data_io.fallback_data() {
+            Some(fallback) => {
+                warn!(target: "AlephBFT-runway", "{:?} No data provided within {:?} for our unit of round {}, using the fallback data.", self.index(), timeout, round);
+                self.data_pipeline.abandon_oldest();
+                self.create_waiting_unit(fallback).await;
+            }
+            None => {
+                warn!(target: "AlephBFT-runway", "{:?} No data provided within {:?} for our unit of round {}, still waiting.", self.index(), timeout, round);
+            }
+        }
+    }
+
+    async fn create_waiting_unit(&mut self, data: D) {
+        let u = self
+            .units_waiting_for_data
+            .pop_front()
+            .expect("the data is for a waiting unit");
+        self.create_unit(u, data).await;
+        self.request_data();
+    }
+
     async fn on_create(&mut self, u: PreUnit<H>) {
         debug!(target: "AlephBFT-runway", "{:?} On create notification.", self.index());
-        let data = self.data_io.get_data();
+        let closed = self.data_io.is_closed();
+        if closed && !self.data_closed {
+            self.data_closed = true;
+            match self.data_channel_policy {
+                DataChannelPolicy::ProduceEmptyUnits => {
+                    info!(target: "AlephBFT-runway", "{:?} The data provider is closed, creating units with the fallback data.", self.index())
+                }
+                DataChannelPolicy::ShutdownSession => {
+                    info!(target: "AlephBFT-runway", "{:?} The data provider is closed, stopping the session.", self.index())
+                }
+            }
+        }
+        if closed && self.data_channel_policy == DataChannelPolicy::ShutdownSession {
+            self.outcome = SessionOutcome::DataClosed;
+            self.exiting = true;
+            return;
+        }
+        let fallback = match closed {
+            true => self.data_io.fallback_data(),
+            false => None,
+        };
+        match fallback {
+            Some(data) => {
+                // Our units are created in order, and the ones before it will not get data either.
+                self.data_deadline = Fuse::terminated();
+                while let Some(waiting) = self.units_waiting_for_data.pop_front() {
+                    self.create_unit(waiting, data.clone()).await;
+                }
+                self.create_unit(u, data).await;
+            }
+            None => {
+                self.units_waiting_for_data.push_back(u);
+                if self.units_waiting_for_data.len() == 1 {
+                    self.request_data();
+                }
+            }
+        }
+    }
+
+    async fn create_unit(&mut self, u: PreUnit<H>, data: D) {
+        if let Some(max_data_size) = self.max_data_size {
+            let data_size = data.encoded_size();
+            if data_size > max_data_size {
+                error!(target: "AlephBFT-runway", "{:?} The data for our unit of round {} has {} bytes, more than the limit of {}. Exiting.", self.index(), u.round(), data_size, max_data_size);
+                self.fail(SessionError::DataTooLarge {
+                    round: u.round(),
+                    data_size,
+                    max_data_size,
+                });
+                return;
+            }
+        }
         let full_unit = FullUnit::new(u, data, self.session_id);
+        unit_event!(
+            hash = ?full_unit.hash(),
+            round = full_unit.round(),
+            creator = full_unit.creator().0,
+            "unit created"
+        );
+        let coord = full_unit.coord();
+        if self.store.unit_by_coord(coord).is_some() {
+            error!(target: "AlephBFT-runway", "{:?} We already have a unit of round {} with our index that we did not create, another node is running with our index. Exiting.", self.index(), coord.round());
+            self.fail(SessionError::DuplicateNodeIndex {
+                round: coord.round(),
+            });
+            return;
+        }
+        self.own_units.insert(coord.round(), full_unit.hash());
+        #[cfg(feature = "timestamps")]
+        self.creation_times
+            .insert(coord.round(), std::time::SystemTime::now());
         let signed_unit = Signed::sign(full_unit, self.keybox).await;
         self.store.add_unit(signed_unit.clone(), false);
     }
@@ -606,6 +1962,10 @@ where
     async fn on_consensus_notification(&mut self, notification: NotificationOut<H>) {
         match notification {
             NotificationOut::CreatedPreUnit(pu, _) => {
+                self.status
+                    .on_unit_created(pu.round(), &pu.control_hash().parents_mask);
+                self.status
+                    .on_unit_seen(pu.creator(), pu.round(), self.clock.now());
                 self.on_create(pu).await;
             }
             NotificationOut::MissingUnits(coords) => {
@@ -618,6 +1978,7 @@ where
                 self.store.add_parents(h, p_hashes);
                 self.resolve_missing_parents(&h);
                 if let Some(su) = self.store.unit_by_hash(&h).cloned() {
+                    self.report_quorum_rounds(su.as_signable().round(), su.as_signable().creator());
                     if su.as_signable().creator() == self.index() {
                         trace!(target: "AlephBFT-runway", "{:?} Sending a unit {:?}.", self.index(), h);
                         self.send_message_for_network(RunwayNotificationOut::NewUnit(su.into()));
@@ -626,6 +1987,80 @@ where
                     error!(target: "AlephBFT-runway", "{:?} A unit already added to DAG is not in our store: {:?}.", self.index(), h);
                 }
             }
+            NotificationOut::CreatorStalled {
+                round,
+                parents_collected,
+                parents_needed,
+            } => {
+                debug!(target: "AlephBFT-runway", "{:?} Creator stalled at round {}.", self.index(), round);
+                self.status.on_creator_stalled(CreatorStall {
+                    round,
+                    parents_collected,
+                    parents_needed,
+                });
+                self.send_message_for_network(RunwayNotificationOut::CreatorStalled {
+                    round,
+                    parents_collected,
+                    parents_needed,
+                });
+            }
+            NotificationOut::CreatorLagging { backlog } => {
+                debug!(target: "AlephBFT-runway", "{:?} Creator lagging with backlog {}.", self.index(), backlog);
+                self.status.on_creator_lagging();
+                self.send_message_for_network(RunwayNotificationOut::CreatorLagging { backlog });
+            }
+            NotificationOut::StartingRoundTooHigh {
+                starting_round,
+                max_round,
+            } => {
+                error!(target: "AlephBFT-runway", "{:?} Creator cannot start at round {}, as the maximum round is {}.", self.index(), starting_round, max_round);
+                self.fail(SessionError::StartingRoundTooHigh {
+                    starting_round,
+                    max_round,
+                });
+            }
+            NotificationOut::RoundLimitReached => {
+                error!(target: "AlephBFT-runway", "{:?} Creator reached the highest possible round.", self.index());
+                self.fail(SessionError::RoundLimitReached);
+            }
+            NotificationOut::MaxRoundApproached(max_round) => {
+                self.status.on_max_round_approached(max_round);
+            }
+            NotificationOut::MaxRoundReached(max_round) => self.on_max_round_reached(max_round),
+        }
+    }
+
+    fn on_max_round_reached(&mut self, max_round: Round) {
+        self.status.on_max_round_reached(max_round);
+        match self.max_round_policy {
+            MaxRoundPolicy::Wait => {
+                info!(target: "AlephBFT-runway", "{:?} Creator reached the maximum round {}, waiting for it to be extended.", self.index(), max_round);
+            }
+            MaxRoundPolicy::Extend(rounds) => {
+                let new_max_round = max_round.saturating_add(rounds);
+                info!(target: "AlephBFT-runway", "{:?} Creator reached the maximum round {}, extending it to {}.", self.index(), max_round, new_max_round);
+                self.on_creator_command(CreatorCommand::ExtendMaxRound(new_max_round));
+            }
+            MaxRoundPolicy::ShutdownSession => {
+                info!(target: "AlephBFT-runway", "{:?} Creator reached the maximum round {}, stopping the session.", self.index(), max_round);
+                self.outcome = SessionOutcome::Completed;
+                self.exiting = true;
+            }
+        }
+    }
+
+    fn report_quorum_rounds(&mut self, round: Round, creator: NodeIndex) {
+        let sink = match &self.quorum_round_sink {
+            Some(sink) => sink,
+            None => return,
+        };
+        for round in self.quorum_rounds.add_unit(round, creator) {
+            trace!(target: "AlephBFT-runway", "{:?} Round {} reached a quorum.", self.index(), round);
+            if sink.unbounded_send(round).is_err() {
+                debug!(target: "AlephBFT-runway", "{:?} Quorum round channel closed, no longer reporting quorum rounds.", self.index());
+                self.quorum_round_sink = None;
+                return;
+            }
         }
     }
 
@@ -670,20 +2105,241 @@ where
         }
     }
 
-    fn on_ordered_batch(&mut self, batch: Vec<H::Hash>) {
-        let batch = batch
+    // The round is the one of the head of the batch. A batch split into a few parts lacks the
+    // head in all but the last one, so the highest round of their units might be lower.
+    fn on_ordered_batch(&mut self, round: Round, batch: Vec<H::Hash>) {
+        for hash in &batch {
+            self.store.mark_finalized(*hash);
+        }
+        #[cfg(feature = "timestamps")]
+        let finalized_at = std::time::SystemTime::now();
+        let units = batch
             .iter()
             .map(|h| {
-                self.store
+                let unit = self
+                    .store
                     .unit_by_hash(h)
                     .expect("Ordered units must be in store")
-                    .as_signable()
-                    .data()
-                    .clone()
+                    .as_signable();
+                unit_event!(
+                    hash = ?h,
+                    round = unit.round(),
+                    creator = unit.creator().0,
+                    "unit finalized"
+                );
+                FinalizedUnit {
+                    creator: unit.creator(),
+                    round: unit.round(),
+                    hash: *h,
+                    data: unit.data().clone(),
+                    #[cfg(feature = "timestamps")]
+                    finalized_at,
+                    #[cfg(feature = "timestamps")]
+                    created_at: match unit.creator() == self.node_ix {
+                        true => self.creation_times.remove(&unit.round()),
+                        false => None,
+                    },
+                }
             })
-            .collect::<OrderedBatch<D>>();
-        if let Err(e) = self.data_io.send_ordered_batch(batch) {
-            error!(target: "AlephBFT-runway", "{:?} Error when sending batch {:?}.", self.index(), e);
+            .collect::<Vec<_>>();
+        if !units.is_empty() {
+            // The hashes come first, so that they are there once the round is reported.
+            self.status.on_batch_finalized(round, &batch);
+            self.status.on_units_ordered(units.len(), round);
+            self.last_finalized_round = Some(round);
+            self.liveness.on_finalized(self.clock.now());
+            if self.status.is_wedged() {
+                info!(target: "AlephBFT-runway", "{:?} Finalizing units again, no longer wedged.", self.index());
+                self.status.on_wedged(false);
+            }
+        }
+        let units = self.skip_delivered(units);
+        let units = self.skip_externally_finalized(units);
+        self.delivery.push(units, self.n_finalized_units);
+        self.start_delivery();
+        self.prune_store_if_needed();
+    }
+
+    fn start_delivery(&mut self) {
+        let data_io = &mut self.data_io;
+        self.delivery
+            .start(|units| data_io.units_finalized_async(units));
+    }
+
+    async fn on_batch_delivered(
+        &mut self,
+        count: u64,
+        last: H::Hash,
+        result: Result<(), DP::Error>,
+    ) {
+        match result {
+            Ok(()) => self.save_delivered(count, last).await,
+            Err(e) => {
+                error!(target: "AlephBFT-runway", "{:?} Error when sending batch {:?}.", self.index(), e);
+            }
+        }
+        self.start_delivery();
+    }
+
+    // While the finalization handler is too far behind, we keep the units in the store, so that
+    // the consensus orders nothing new, and the creator gets no new parents.
+    fn check_delivery_lag(&mut self) -> bool {
+        let lagging = self.delivery.too_far_behind(self.last_finalized_round);
+        if lagging != self.delivery_lagging {
+            self.delivery_lagging = lagging;
+            self.status.on_finalization_lagging(lagging);
+            if lagging {
+                info!(target: "AlephBFT-runway", "{:?} The finalization handler is more than {} rounds behind, holding back the units from the consensus until it catches up.", self.index(), self.delivery.max_rounds);
+            } else {
+                info!(target: "AlephBFT-runway", "{:?} The finalization handler caught up, passing the units to the consensus again.", self.index());
+            }
+        }
+        lagging
+    }
+
+    // The units are finalized in the same order after a restart, so the ones passed to the
+    // finalization handler before it are the first ones we finalize again.
+    fn skip_delivered(&mut self, mut units: Vec<FinalizedUnit<H, D>>) -> Vec<FinalizedUnit<H, D>> {
+        let first = self.n_finalized_units;
+        self.n_finalized_units += units.len() as u64;
+        let (count, last) = match self.delivered_before_recovery {
+            Some(delivered) => delivered,
+            None => return units,
+        };
+        let n_skipped = count.saturating_sub(first).min(units.len() as u64) as usize;
+        if count <= self.n_finalized_units {
+            self.delivered_before_recovery = None;
+            if n_skipped > 0 && units[n_skipped - 1].hash != last {
+                error!(target: "AlephBFT-runway", "{:?} The units finalized after recovery differ from the ones passed before it.", self.index());
+            }
+            info!(target: "AlephBFT-runway", "{:?} Skipped {} finalized units passed before recovery.", self.index(), count);
+        }
+        units.drain(..n_skipped);
+        units
+    }
+
+    // The head of a batch is its unit of the highest round, so the batches committed elsewhere
+    // are the first ones we finalize, and we stop checking after the first one above them.
+    fn skip_externally_finalized(
+        &mut self,
+        mut units: Vec<FinalizedUnit<H, D>>,
+    ) -> Vec<FinalizedUnit<H, D>> {
+        let (finalized_round, head_round) = match (
+            self.externally_finalized_round,
+            units.iter().map(|unit| unit.round).max(),
+        ) {
+            (Some(finalized_round), Some(head_round)) => (finalized_round, head_round),
+            _ => return units,
+        };
+        if head_round <= finalized_round {
+            units.clear();
+        } else {
+            self.externally_finalized_round = None;
+            info!(target: "AlephBFT-runway", "{:?} Skipped the batches finalized externally up to round {}.", self.index(), finalized_round);
+        }
+        units
+    }
+
+    async fn save_delivered(&mut self, count: u64, last: H::Hash) {
+        if !self.skip_delivered_after_recovery {
+            return;
+        }
+        let record = Record::<H, D, MK::Signature>::Delivered { count, last };
+        match backup::save_records(&*self.backup_sink, &[record]).await {
+            Ok(size) => self.backup_size += size,
+            Err(e) => {
+                error!(target: "AlephBFT-runway", "{:?} Unable to save the delivered units to the backup: {}. Exiting.", self.index(), e);
+                self.fail(SessionError::Backup(e.to_string()));
+            }
+        }
+    }
+
+    // The finalized units are not needed for ordering anymore, and they are already in the
+    // backup, as we save units before passing them to the consensus.
+    fn prune_store_if_needed(&mut self) {
+        let max_stored_units = match self.max_stored_units {
+            Some(max_stored_units) => max_stored_units,
+            None => return,
+        };
+        if self.store.len() > max_stored_units {
+            let pruned = self.store.prune_finalized();
+            let store = &self.store;
+            self.unit_variants
+                .retain(|coord| store.contains_coord(coord));
+            debug!(target: "AlephBFT-runway", "{:?} Pruned {} finalized units, {} units left in store.", self.index(), pruned, self.store.len());
+        }
+    }
+
+    fn report_stored_units(&mut self) {
+        let n_units = self.store.len();
+        self.status.on_units_stored(n_units);
+        if let Some(max_stored_units) = self.max_stored_units {
+            let overloaded = n_units > max_stored_units;
+            if self.store_overloaded.swap(overloaded, Ordering::Relaxed) != overloaded {
+                if overloaded {
+                    info!(target: "AlephBFT-runway", "{:?} Storing {} units, more than the bound of {}, slowing down unit creation.", self.index(), n_units, max_stored_units);
+                    self.status.on_store_overloaded();
+                } else {
+                    info!(target: "AlephBFT-runway", "{:?} Storing {} units, back within the bound, resuming normal unit creation.", self.index(), n_units);
+                }
+            }
+        }
+    }
+
+    fn check_caught_up(&mut self) {
+        if self.status.is_caught_up() {
+            return;
+        }
+        // Before the starting round is known we are still learning where the others are.
+        let close_to_highest = self.starting_round_sender.is_none()
+            && self.last_finalized_round.is_some()
+            && self.highest_observed_round.is_some_and(|highest| {
+                self.catch_up
+                    .is_close(CatchUpMonitor::lag(self.last_finalized_round, highest))
+            });
+        if !close_to_highest {
+            self.close_to_highest_since = None;
+            return;
+        }
+        let now = self.clock.now();
+        let since = *self.close_to_highest_since.get_or_insert(now);
+        // Otherwise a clock going back would postpone catching up until it gets back to `since`.
+        if now < since {
+            self.close_to_highest_since = Some(now);
+        }
+        if time_between(since, now) >= CAUGHT_UP_DEBOUNCE {
+            info!(target: "AlephBFT-runway", "{:?} Caught up, ordered units up to round {:?} with the highest known round {:?}.", self.index(), self.last_finalized_round, self.highest_observed_round);
+            self.status.on_caught_up();
+        }
+    }
+
+    fn check_catching_up(&mut self) {
+        let highest = match self.highest_observed_round {
+            Some(highest) => highest,
+            None => return,
+        };
+        let lag = CatchUpMonitor::lag(self.last_finalized_round, highest);
+        match self.catch_up.update(lag) {
+            Some(true) => {
+                info!(target: "AlephBFT-runway", "{:?} Finalized rounds up to {:?}, {} rounds below the highest known round {}, catching up.", self.index(), self.last_finalized_round, lag, highest);
+            }
+            Some(false) => {
+                info!(target: "AlephBFT-runway", "{:?} Finalized rounds up to {:?}, close to the highest known round {}, stopped catching up.", self.index(), self.last_finalized_round, highest);
+            }
+            None => return,
+        }
+        self.send_message_for_network(RunwayNotificationOut::CatchingUp(self.catch_up.catching_up));
+    }
+
+    fn check_liveness(&mut self) {
+        if self.status.is_wedged() {
+            return;
+        }
+        let now = self.clock.now();
+        let status = &self.status;
+        if let Some(n_active) = self.liveness.wedged(now, |ix| status.member_activity(ix)) {
+            warn!(target: "AlephBFT-runway", "{:?} Finalized nothing for {:?}, even though {} other members are active, we are wedged.", self.index(), self.liveness.config.stall_threshold, n_active.0);
+            self.status.on_wedged(true);
         }
     }
 
@@ -697,47 +2353,443 @@ where
             .is_err()
         {
             warn!(target: "AlephBFT-runway", "{:?} unit_messages_for_network channel should be open", self.index());
-            self.exiting = true;
+            self.fail(SessionError::TaskStopped("member"));
         }
     }
 
     fn send_resolved_request_notification(&mut self, notification: Request<H>) {
         if self.resolved_requests.unbounded_send(notification).is_err() {
             warn!(target: "AlephBFT-runway", "{:?} resolved_requests channel should be open", self.index());
-            self.exiting = true;
+            self.fail(SessionError::TaskStopped("member"));
         }
     }
 
     fn send_consensus_notification(&mut self, notification: NotificationIn<H>) {
         if self.tx_consensus.unbounded_send(notification).is_err() {
             warn!(target: "AlephBFT-runway", "{:?} Channel to consensus should be open", self.index());
-            self.exiting = true;
+            self.fail(SessionError::TaskStopped("runway/consensus"));
         }
     }
 
-    fn move_units_to_consensus(&mut self) {
-        let units_to_move = self
-            .store
-            .yield_buffer_units()
+    // The units are saved before the consensus learns about them, so in particular our own units
+    // are saved before we send them to anyone.
+    async fn move_units_to_consensus(&mut self) {
+        let units = self.store.yield_buffer_units();
+        let records: Vec<_> = self
+            .fork_proofs_to_save
+            .drain(..)
+            .map(Record::ForkProof)
+            .chain(units.iter().cloned().map(|su| Record::Unit(su.into())))
+            .collect();
+        match backup::save_records(&*self.backup_sink, &records).await {
+            Ok(size) => self.backup_size += size,
+            Err(e) => {
+                error!(target: "AlephBFT-runway", "{:?} Unable to save units to the backup: {}. Exiting.", self.index(), e);
+                self.fail(SessionError::Backup(e.to_string()));
+                return;
+            }
+        }
+        self.send_units_to_consensus(units);
+        self.compact_backup_if_needed().await;
+    }
+
+    // We compact only once the backup doubles its size since the last compaction, so that we do
+    // not keep rewriting a backup consisting mostly of units we keep.
+    async fn compact_backup_if_needed(&mut self) {
+        let finalized_round = match self.last_finalized_round {
+            Some(round) => round,
+            None => return,
+        };
+        if self.backup_compaction_threshold == 0
+            || self.backup_size <= self.backup_compaction_threshold
+            || self.backup_size < 2 * self.compacted_backup_size
+        {
+            return;
+        }
+        match backup::compact_units::<H, D, MK::Signature>(
+            &*self.backup_source,
+            &*self.backup_sink,
+            self.index(),
+            finalized_round,
+        )
+        .await
+        {
+            Ok(size) => {
+                debug!(target: "AlephBFT-runway", "{:?} Compacted the backup from {} to {} bytes.", self.index(), self.backup_size, size);
+                self.backup_size = size;
+                self.compacted_backup_size = size;
+            }
+            Err(e) => {
+                error!(target: "AlephBFT-runway", "{:?} Unable to compact the backup: {}. Exiting.", self.index(), e);
+                self.fail(SessionError::Backup(e.to_string()));
+            }
+        }
+    }
+
+    fn send_units_to_consensus(&mut self, units: Vec<SignedUnit<'a, H, D, MK>>) {
+        for su in &units {
+            let unit = su.as_signable();
+            self.dag_shape.add_unit(
+                unit.round(),
+                unit.creator(),
+                &unit.control_hash().parents_mask,
+            );
+        }
+        if !units.is_empty() {
+            let shape = self.dag_shape.shape();
+            self.check_parent_inclusion_balance(&shape);
+            self.status.on_dag_shape(shape);
+            self.check_own_unit_inclusion();
+        }
+        let units_to_move = units
             .into_iter()
             .map(|su| su.as_signable().unit())
             .collect();
         self.send_consensus_notification(NotificationIn::NewUnits(units_to_move))
     }
 
-    async fn run(mut self, mut exit: oneshot::Receiver<()>) {
+    fn check_own_unit_inclusion(&mut self) {
+        let min_inclusion = match self.min_own_unit_inclusion {
+            Some(min_inclusion) => min_inclusion,
+            None => return,
+        };
+        let (included, evaluated) = self.dag_shape.own_unit_inclusion();
+        // Too few rounds say little, e.g. right after starting.
+        if evaluated < (DAG_SHAPE_ROUNDS / 2).into() {
+            return;
+        }
+        let rarely_included = (included as f64) < min_inclusion * evaluated as f64;
+        if rarely_included == self.rarely_included {
+            return;
+        }
+        self.rarely_included = rarely_included;
+        if rarely_included {
+            warn!(target: "AlephBFT-runway", "{:?} Only {} of the recent {} rounds had our unit included by a quorum of the next round, our units likely come too late for the others.", self.index(), included, evaluated);
+        } else {
+            info!(target: "AlephBFT-runway", "{:?} Our units are included by a quorum again, in {} of the recent {} rounds.", self.index(), included, evaluated);
+        }
+    }
+
+    fn check_parent_inclusion_balance(&mut self, shape: &DagShape) {
+        let min_balance = match self.min_parent_inclusion_balance {
+            Some(min_balance) => min_balance,
+            None => return,
+        };
+        // Too few rounds say little, e.g. right after starting.
+        if shape.widths.len() < (DAG_SHAPE_ROUNDS / 2).into() {
+            return;
+        }
+        let balance = match shape.parent_inclusion_balance() {
+            Some(balance) => balance,
+            None => return,
+        };
+        let unbalanced_parents = balance < min_balance;
+        if unbalanced_parents == self.unbalanced_parents {
+            return;
+        }
+        self.unbalanced_parents = unbalanced_parents;
+        if unbalanced_parents {
+            warn!(target: "AlephBFT-runway", "{:?} The units of some members are used as parents only {:.2} as often as the ones of others, the parents are chosen unfairly: {:?}.", self.index(), balance, shape.parent_inclusion);
+        } else {
+            info!(target: "AlephBFT-runway", "{:?} The units of the members are used as parents evenly again, with a balance of {:.2}.", self.index(), balance);
+        }
+    }
+
+    // The creator can use the recovered units right away, before they are added to the Dag.
+    fn send_recovered_units(&mut self, units: &[SignedUnit<'a, H, D, MK>]) {
+        if let Some(sender) = self.recovered_units_sender.take() {
+            let units = units.iter().map(|su| su.as_signable().unit()).collect();
+            if sender.send(units).is_err() {
+                debug!(target: "AlephBFT-runway", "{:?} Creator stopped before receiving the recovered units.", self.index());
+            }
+        }
+    }
+
+    // Checks the backup before anything is recovered from it, reading it once without keeping
+    // the records, and saves the session tag in an empty one. Returns the round from which the
+    // inconsistent backup was truncated, if it was.
+    async fn check_backup(&mut self) -> Result<Option<Round>, SessionError> {
+        let (source, sink) = (self.backup_source.clone(), self.backup_sink.clone());
+        let mut reader = backup::RecordReader::<H, D, MK::Signature>::new(&*source);
+        let mut checker = backup::UnitChecker::new(self.index());
+        let mut records = reader.records();
+        // A backup saved without a tag has the empty one.
+        let mut found_tag = None;
+        while let Some(record) = records.next().await {
+            match record {
+                Ok(record) => {
+                    if let Record::Unit(uu) = &record {
+                        let unit = uu.as_signable();
+                        if unit.creator() == self.index() {
+                            self.own_backup_round = self.own_backup_round.max(Some(unit.round()));
+                        }
+                    }
+                    if found_tag.is_none() {
+                        found_tag = Some(match &record {
+                            Record::SessionTag(tag) => tag.clone(),
+                            _ => Vec::new(),
+                        });
+                    }
+                    checker.add(&record)
+                }
+                Err(e) => {
+                    error!(target: "AlephBFT-runway", "{:?} Unable to load the backup: {}.", self.index(), e);
+                    return Err(SessionError::Backup(e.to_string()));
+                }
+            }
+        }
+        drop(records);
+        // Checked before the backup is repaired or upgraded, so that a backup of another
+        // session is left as it is.
+        if let Some(found) = found_tag
+            .as_ref()
+            .filter(|found| **found != self.session_tag)
+        {
+            error!(target: "AlephBFT-runway", "{:?} The backup belongs to the session tagged {:?}, not {:?}. Refusing to start.", self.index(), found, self.session_tag);
+            return Err(SessionError::BackupOfOtherSession {
+                expected: self.session_tag.clone(),
+                found: found.clone(),
+            });
+        }
+        match reader.finish(&*source, &*sink).await {
+            Ok(size) => self.backup_size = size,
+            Err(e) => {
+                error!(target: "AlephBFT-runway", "{:?} Unable to load the backup: {}.", self.index(), e);
+                return Err(SessionError::Backup(e.to_string()));
+            }
+        }
+        if found_tag.is_none() && !self.session_tag.is_empty() {
+            let record = Record::SessionTag(self.session_tag.clone());
+            match backup::save_records::<H, D, MK::Signature>(&*sink, &[record]).await {
+                Ok(size) => self.backup_size += size,
+                Err(e) => {
+                    error!(target: "AlephBFT-runway", "{:?} Unable to save the session tag in the backup: {}.", self.index(), e);
+                    return Err(SessionError::Backup(e.to_string()));
+                }
+            }
+        }
+        match checker.finish() {
+            Ok(()) => Ok(None),
+            Err(inconsistency) if self.truncate_inconsistent_backup => {
+                warn!(target: "AlephBFT-runway", "{:?} The backup is inconsistent from round {}: {}. Truncating it to the lower rounds.", self.index(), inconsistency.round, inconsistency.reason);
+                match backup::truncate_units::<H, D, MK::Signature>(
+                    &*source,
+                    &*sink,
+                    inconsistency.round,
+                )
+                .await
+                {
+                    Ok(size) => {
+                        self.backup_size = size;
+                        Ok(Some(inconsistency.round))
+                    }
+                    Err(e) => {
+                        error!(target: "AlephBFT-runway", "{:?} Unable to truncate the backup: {}.", self.index(), e);
+                        Err(SessionError::Backup(e.to_string()))
+                    }
+                }
+            }
+            Err(inconsistency) => {
+                error!(target: "AlephBFT-runway", "{:?} The backup is inconsistent from round {}: {}. Refusing to start.", self.index(), inconsistency.round, inconsistency.reason);
+                Err(SessionError::InconsistentBackup {
+                    round: inconsistency.round,
+                    reason: inconsistency.reason,
+                })
+            }
+        }
+    }
+
+    // The backup is read twice, first to check it and then to recover from it, as it might be
+    // too large to keep all its records in memory until they are checked.
+    async fn load_backup(&mut self, terminator: &mut Terminator) -> Recovery {
+        let mut timeout = match self.recovery_timeout {
+            Some(timeout) => self.clock.sleep(timeout).fuse(),
+            None => Fuse::terminated(),
+        };
+        let truncated_from = futures::select! {
+            checked = self.check_backup().fuse() => match checked {
+                Ok(truncated_from) => truncated_from,
+                Err(e) => {
+                    self.fail(e);
+                    return Recovery::Failed;
+                }
+            },
+            _ = terminator.get_exit() => {
+                info!(target: "AlephBFT-runway", "{:?} Received exit signal while checking the backup.", self.index());
+                return Recovery::Exited;
+            }
+        };
+        let source = self.backup_source.clone();
+        let mut records = backup::read_records::<H, D, MK::Signature>(&*source);
+        let mut n_records = 0;
+        let mut recovered_round = None;
+        loop {
+            let record = futures::select! {
+                record = records.next().fuse() => match record {
+                    Some(record) => record,
+                    None => break,
+                },
+                _ = terminator.get_exit() => {
+                    info!(target: "AlephBFT-runway", "{:?} Received exit signal after recovering {} records from the backup.", self.index(), n_records);
+                    return Recovery::Exited;
+                },
+                _ = timeout => {
+                    info!(target: "AlephBFT-runway", "{:?} Recovering from the backup timed out after {} records, with units up to round {:?}. Catching up with the others on the rest.", self.index(), n_records, recovered_round);
+                    self.status.on_recovery_timed_out();
+                    // We must not create units of the rounds we already created units for, even
+                    // though we did not recover all of them.
+                    if let Some(round) = self.own_backup_round {
+                        self.starting_round_value =
+                            self.starting_round_value.max(round.saturating_add(1));
+                    }
+                    break;
+                }
+            };
+            let record = match record {
+                Ok(record) => record,
+                Err(e) => {
+                    error!(target: "AlephBFT-runway", "{:?} Unable to load the backup: {}.", self.index(), e);
+                    self.fail(SessionError::Backup(e.to_string()));
+                    return Recovery::Failed;
+                }
+            };
+            // The session tag is already checked.
+            if matches!(record, Record::SessionTag(_)) {
+                continue;
+            }
+            // A source separate from the sink still has the records cut off the backup.
+            if truncated_from.is_some_and(|round| !backup::survives_truncation(&record, round)) {
+                continue;
+            }
+            if let Record::Unit(uu) = &record {
+                recovered_round = recovered_round.max(Some(uu.as_signable().round()));
+            }
+            if !self.recover_record(record) {
+                return Recovery::Failed;
+            }
+            n_records += 1;
+            self.verification_budget.yield_if_spent().await;
+        }
+        if n_records == 0 {
+            self.check_externally_finalized_round(None);
+            return Recovery::Done;
+        }
+        info!(target: "AlephBFT-runway", "{:?} Recovered {} records from the backup.", self.index(), n_records);
+        // These units are already in the backup.
+        let units = self.store.yield_buffer_units();
+        self.check_externally_finalized_round(
+            units.iter().map(|unit| unit.as_signable().round()).max(),
+        );
+        self.send_recovered_units(&units);
+        self.send_units_to_consensus(units);
+        Recovery::Done
+    }
+
+    // Batches above the recovered units cannot be checked against our Dag, so we pass them again
+    // rather than trusting that they are committed.
+    fn check_externally_finalized_round(&mut self, recovered_round: Option<Round>) {
+        let finalized_round = match self.externally_finalized_round {
+            Some(finalized_round) => finalized_round,
+            None => return,
+        };
+        let trusted_round = recovered_round.map(|round| round.min(finalized_round));
+        if trusted_round != Some(finalized_round) {
+            info!(target: "AlephBFT-runway", "{:?} Trusting the external finalization only up to round {:?}, the highest one recovered.", self.index(), trusted_round);
+        }
+        self.externally_finalized_round = trusted_round;
+    }
+
+    fn recover_record(&mut self, record: Record<H, D, MK::Signature>) -> bool {
+        let uu = match record {
+            Record::Unit(uu) => uu,
+            Record::ForkProof(proof) => {
+                let forker = proof.0.as_signable().creator();
+                if self.reported_forkers.insert(forker) {
+                    self.on_fork_proven(ForkEvidence::from_proof(&proof));
+                }
+                return true;
+            }
+            Record::Delivered { count, last } => {
+                if self.skip_delivered_after_recovery {
+                    self.delivered_before_recovery = Some((count, last));
+                }
+                return true;
+            }
+            Record::SessionTag(_) => return true,
+        };
+        let su = match self.validate_recovered_unit(uu) {
+            Some(su) => su,
+            None => {
+                error!(target: "AlephBFT-runway", "{:?} The backup contains an invalid unit.", self.index());
+                self.fail(SessionError::Backup(
+                    "the backup contains an invalid unit".to_string(),
+                ));
+                return false;
+            }
+        };
+        let full_unit = su.as_signable();
+        if full_unit.creator() == self.index() {
+            // We must not create units of the rounds we already created units for.
+            self.starting_round_value = self
+                .starting_round_value
+                .max(full_unit.round().saturating_add(1));
+            self.own_units.insert(full_unit.round(), full_unit.hash());
+        }
+        self.add_unit_to_store_unless_fork(su);
+        true
+    }
+
+    fn on_creator_command(&mut self, command: CreatorCommand) {
+        if let CreatorCommand::ExtendMaxRound(max_round) = command {
+            self.store.extend_max_round(max_round);
+        }
+        if self.observer {
+            debug!(target: "AlephBFT-runway", "{:?} Ignoring {:?} as an observer.", self.index(), command);
+            return;
+        }
+        if self.commands_for_creator.unbounded_send(command).is_err() {
+            warn!(target: "AlephBFT-runway", "{:?} Channel for creator commands should be open", self.index());
+            self.fail(SessionError::TaskStopped("consensus/creation"));
+        }
+    }
+
+    /// Returns an error unless it exits upon the exit signal.
+    async fn run(mut self, mut terminator: Terminator) -> Result<SessionOutcome, SessionError> {
         let index = self.index();
 
         info!(target: "AlephBFT-runway", "{:?} Runway starting.", index);
 
+        match self.load_backup(&mut terminator).await {
+            Recovery::Done => {}
+            Recovery::Failed => {
+                self.flush_ordered_batches().await;
+                terminator.terminate_sync().await;
+                return Err(self.error.take().expect("loading the backup failed"));
+            }
+            // Nothing was ordered yet, so there is nothing to flush.
+            Recovery::Exited => {
+                terminator.terminate_sync().await;
+                info!(target: "AlephBFT-runway", "{:?} Run ended during recovery.", index);
+                return Ok(self.outcome);
+            }
+        }
+
         let notification =
             RunwayNotificationOut::Request(Request::NewestUnit(self.salt), Recipient::Everyone);
         if let Err(e) = self.unit_messages_for_network.unbounded_send(notification) {
             error!(target: "AlephBFT-runway", "{:?} Unable to send the newest unit request: {}", index, e);
-            self.exiting = true
+            self.fail(SessionError::TaskStopped("member"));
         };
 
-        let mut catch_up_delay = futures_timer::Delay::new(Duration::from_secs(5)).fuse();
+        // A single member has nobody to ask about its newest unit, the backup is all there is.
+        let mut catch_up_delay = if self.node_weights.n_members() == NodeCount(1) {
+            self.after_catch_up_delay = true;
+            self.resolve_starting_round();
+            Fuse::terminated()
+        } else {
+            self.clock.sleep(Duration::from_secs(5)).fuse()
+        };
+        let mut liveness_check = self.clock.sleep(self.liveness.check_interval()).fuse();
 
         info!(target: "AlephBFT-runway", "{:?} Runway started.", index);
         loop {
@@ -746,16 +2798,24 @@ where
                         Some(notification) => self.on_consensus_notification(notification).await,
                         None => {
                             error!(target: "AlephBFT-runway", "{:?} Consensus notification stream closed.", index);
+                            self.fail(SessionError::TaskStopped("runway/consensus"));
                             break;
                         }
                 },
 
+                // The creator might stop on its own, e.g. when it cannot start, which the consensus
+                // reports.
+                notification = self.rx_creator.next() => if let Some(notification) = notification {
+                    self.on_consensus_notification(notification).await;
+                },
+
                 notification = self.notifications_from_alerter.next() => match notification {
                     Some(notification) => {
                         self.on_alert_notification(notification)
                     },
                     None => {
                         error!(target: "AlephBFT-runway", "{:?} Alert notification stream closed.", index);
+                        self.fail(SessionError::TaskStopped("runway/alerter"));
                         break;
                     }
                 },
@@ -764,18 +2824,42 @@ where
                     Some(event) => self.on_unit_message(event).await,
                     None => {
                         error!(target: "AlephBFT-runway", "{:?} Unit message stream closed.", index);
+                        self.fail(SessionError::TaskStopped("member"));
                         break;
                     }
                 },
 
                 batch = self.ordered_batch_rx.next() => match batch {
-                    Some(batch) => self.on_ordered_batch(batch),
+                    Some((round, batch)) => self.on_ordered_batch(round, batch),
                     None => {
                         error!(target: "AlephBFT-runway", "{:?} Ordered batch stream closed.", index);
+                        self.fail(SessionError::TaskStopped("runway/consensus"));
                         break;
                     }
                 },
 
+                command = self.creator_control.next() => if let Some(command) = command {
+                    self.on_creator_command(command);
+                },
+
+                verified = self.verified_units.next() => if let Some((seq, uu, valid)) = verified {
+                    self.on_unit_verified(seq, uu, valid);
+                },
+
+                response = self.dag_requests.next() => if let Some(response) = response {
+                    let _ = response.send(self.store.dag_to_dot());
+                },
+
+                data = self.data_pipeline.next(!self.units_waiting_for_data.is_empty()).fuse() => {
+                    self.on_data(data).await;
+                },
+
+                _ = &mut self.data_deadline => self.on_data_timeout().await,
+
+                (count, last, result) = self.delivery.delivered().fuse() => {
+                    self.on_batch_delivered(count, last, result).await;
+                },
+
                 _ = catch_up_delay => {
                     self.after_catch_up_delay = true;
                     if self.is_starting_round_ready() {
@@ -783,12 +2867,23 @@ where
                     }
                 }
 
-                _ = &mut exit => {
+                _ = liveness_check => {
+                    self.check_liveness();
+                    liveness_check = self.clock.sleep(self.liveness.check_interval()).fuse();
+                }
+
+                _ = terminator.get_exit() => {
                     info!(target: "AlephBFT-runway", "{:?} received exit signal", self.index());
                     self.exiting = true;
                 }
             };
-            self.move_units_to_consensus();
+            if !self.check_delivery_lag() {
+                self.move_units_to_consensus().await;
+            }
+            self.verification_budget.yield_if_spent().await;
+            self.report_stored_units();
+            self.check_caught_up();
+            self.check_catching_up();
 
             if self.exiting {
                 info!(target: "AlephBFT-runway", "{:?} Runway decided to exit.", index);
@@ -796,7 +2891,28 @@ where
             }
         }
 
+        self.flush_ordered_batches().await;
+        terminator.terminate_sync().await;
         info!(target: "AlephBFT-runway", "{:?} Run ended.", index);
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(self.outcome),
+        }
+    }
+
+    // The consensus is already stopped when we exit, so no more batches can be ordered. The
+    // handler still gets all the ones ordered so far.
+    async fn flush_ordered_batches(&mut self) {
+        while let Ok((round, batch)) = self.ordered_batch_rx.try_recv() {
+            self.on_ordered_batch(round, batch);
+        }
+        while !self.delivery.is_empty() {
+            let (count, last, result) = self.delivery.delivered().await;
+            self.on_batch_delivered(count, last, result).await;
+        }
+        if let Err(e) = self.data_io.flush() {
+            error!(target: "AlephBFT-runway", "{:?} Error when flushing the ordered data {:?}.", self.index(), e);
+        }
     }
 }
 
@@ -810,6 +2926,8 @@ pub(crate) struct RunwayIO<H: Hasher, D: Data, MK: MultiKeychain> {
     pub(crate) unit_messages_for_network: Sender<RunwayNotificationOut<H, D, MK::Signature>>,
     pub(crate) unit_messages_from_network: Receiver<RunwayNotificationIn<H, D, MK::Signature>>,
     pub(crate) resolved_requests: Sender<Request<H>>,
+    pub(crate) creator_control: Receiver<CreatorCommand>,
+    pub(crate) status: SessionStatus,
 }
 
 pub(crate) async fn run<H, D, MK, DP, SH>(
@@ -818,16 +2936,19 @@ pub(crate) async fn run<H, D, MK, DP, SH>(
     data_io: DP,
     spawn_handle: SH,
     runway_io: RunwayIO<H, D, MK>,
-    mut exit: oneshot::Receiver<()>,
-) where
+    salt: u64,
+    mut terminator: Terminator,
+) -> Result<SessionOutcome, SessionError>
+where
     H: Hasher,
     D: Data,
     MK: MultiKeychain,
-    DP: DataIO<D>,
+    DP: UnitFinalizationHandler<H, D>,
     SH: SpawnHandle,
 {
     let (tx_consensus, consensus_stream) = mpsc::unbounded();
     let (consensus_sink, rx_consensus) = mpsc::unbounded();
+    let (creator_sink, rx_creator) = mpsc::channel(config.creator_notification_capacity);
     let (ordered_batch_tx, ordered_batch_rx) = mpsc::unbounded();
 
     let (alert_notifications_for_units, notifications_from_alerter) = mpsc::unbounded();
@@ -835,52 +2956,100 @@ pub(crate) async fn run<H, D, MK, DP, SH>(
     let alert_config = AlertConfig {
         session_id: config.session_id,
         n_members: config.n_members,
+        resend_interval: config.delay_config.alert_resend_interval,
+        rmc_retry_delay: config.delay_config.rmc_retry_delay.clone(),
+        alert_timeout: config.delay_config.alert_timeout,
+        clock: config.clock.clone(),
+        status: runway_io.status.clone(),
     };
-    let (alerter_exit, exit_stream) = oneshot::channel();
+    let alerter_terminator = terminator.add_offspring_connection("runway/alerter");
     let alerter_keychain = keychain.clone();
     let alert_messages_for_network = runway_io.alert_messages_for_network;
     let alert_messages_from_network = runway_io.alert_messages_from_network;
-    let alerter_handle = spawn_handle.spawn_essential("runway/alerter", async move {
-        alerts::run(
-            alerter_keychain,
-            alert_messages_for_network,
-            alert_messages_from_network,
-            alert_notifications_for_units,
-            alerts_from_units,
-            alert_config,
-            exit_stream,
-        )
-        .await;
-    });
+    let alerter_handle = spawn_handle.spawn_essential(
+        "runway/alerter",
+        in_span(
+            "alerter",
+            config.node_ix,
+            config.session_id,
+            alerts::run(
+                alerter_keychain,
+                alert_messages_for_network,
+                alert_messages_from_network,
+                alert_notifications_for_units,
+                alerts_from_units,
+                alert_config,
+                alerter_terminator,
+            ),
+        ),
+    );
     let mut alerter_handle = alerter_handle.fuse();
 
-    let (consensus_exit, exit_stream) = oneshot::channel();
+    let consensus_terminator = terminator.add_offspring_connection("runway/consensus");
     let consensus_config = config.clone();
     let consensus_spawner = spawn_handle.clone();
     let (starting_round_sender, starting_round) = oneshot::channel();
-
-    let consensus_handle = spawn_handle.spawn_essential("runway/consensus", async move {
-        consensus::run(
-            consensus_config,
-            consensus_stream,
-            consensus_sink,
-            ordered_batch_tx,
-            consensus_spawner,
-            starting_round,
-            exit_stream,
-        )
-        .await
-    });
+    let (recovered_units_sender, recovered_units) = oneshot::channel();
+    let (commands_for_creator, creator_control) = mpsc::unbounded();
+    let store_overloaded = Arc::new(AtomicBool::new(false));
+    let consensus_store_overloaded = store_overloaded.clone();
+
+    let consensus_handle = spawn_handle.spawn_essential(
+        "runway/consensus",
+        in_span(
+            "consensus",
+            config.node_ix,
+            config.session_id,
+            consensus::run(
+                consensus_config,
+                consensus_stream,
+                consensus_sink,
+                creator_sink,
+                ordered_batch_tx,
+                consensus_spawner,
+                starting_round,
+                recovered_units,
+                creator_control,
+                consensus_store_overloaded,
+                runway_io.status.clone(),
+                consensus_terminator,
+            ),
+        ),
+    );
     let mut consensus_handle = consensus_handle.fuse();
 
     let index = config.node_ix;
 
-    let salt = {
-        let mut hasher = DefaultHasher::new();
-        std::time::Instant::now().hash(&mut hasher);
-        hasher.finish()
-    };
+    let (verified_units_tx, verified_units) = mpsc::unbounded();
+    let mut verification_workers = Vec::new();
+    for _ in 0..config.unit_verification_workers {
+        let (units_tx, units_rx) = mpsc::unbounded();
+        spawn_handle.spawn(
+            "runway/verification",
+            in_span(
+                "verification",
+                config.node_ix,
+                config.session_id,
+                verify_units(
+                    keychain.clone(),
+                    units_rx,
+                    verified_units_tx.clone(),
+                    VerificationBudget::new(
+                        config.verification_budget,
+                        spawn_handle.clone(),
+                        runway_io.status.clone(),
+                    ),
+                ),
+            ),
+        );
+        verification_workers.push(units_tx);
+    }
 
+    let verification_budget = VerificationBudget::new(
+        config.verification_budget,
+        spawn_handle.clone(),
+        runway_io.status.clone(),
+    );
     let runway_config = RunwayConfig {
         keychain: &keychain,
         data_io,
@@ -888,61 +3057,530 @@ pub(crate) async fn run<H, D, MK, DP, SH>(
         notifications_from_alerter,
         tx_consensus,
         rx_consensus,
+        rx_creator,
         unit_messages_from_network: runway_io.unit_messages_from_network,
         unit_messages_for_network: runway_io.unit_messages_for_network,
         ordered_batch_rx,
         resolved_requests: runway_io.resolved_requests,
         starting_round_sender,
+        recovered_units_sender,
+        creator_control: runway_io.creator_control,
+        commands_for_creator,
+        status: runway_io.status,
         node_ix: config.node_ix,
         session_id: config.session_id,
         n_members: config.n_members,
+        node_weights: config.node_weights.clone(),
         max_round: config.max_round,
+        observer: config.observer,
+        processed_units_cache_size: config.processed_units_cache_size,
+        max_unit_variants: config.max_unit_variants,
+        min_own_unit_inclusion: config.min_own_unit_inclusion,
+        min_parent_inclusion_balance: config.min_parent_inclusion_balance,
+        quorum_round_sink: config.quorum_round_sink.clone(),
+        unit_rejection_sink: config.unit_rejection_sink.clone(),
+        liveness: config.liveness,
+        catch_up: config.catch_up,
+        response_limit: config.response_limit,
+        clock: config.clock.clone(),
+        data_timeout: config.delay_config.data_timeout,
+        data_pipeline_depth: config.data_pipeline_depth,
+        data_channel_policy: config.data_channel_policy,
+        max_round_policy: config.max_round_policy,
+        fork_handler: config.fork_handler.clone(),
+        fork_policy: config.fork_policy,
+        backup_sink: config.backup_sink.clone(),
+        backup_source: config.backup_source.clone(),
+        backup_compaction_threshold: config.backup_compaction_threshold,
+        recovery_timeout: config.recovery_timeout,
+        skip_delivered_after_recovery: config.skip_delivered_after_recovery,
+        externally_finalized_round: config.externally_finalized_round,
+        known_unit_hashes: config.known_unit_hashes.clone(),
+        max_undelivered_rounds: config.max_undelivered_rounds,
+        truncate_inconsistent_backup: config.truncate_inconsistent_backup,
+        session_tag: config.session_tag.clone(),
+        max_stored_units: config.max_stored_units,
+        max_data_size: config.max_data_size,
+        store_overloaded: store_overloaded.clone(),
+        verification_workers,
+        verified_units,
+        verification_budget,
         salt,
     };
-    let (runway_exit, exit_stream) = oneshot::channel();
+    let runway_terminator = terminator.add_offspring_connection("runway/main");
     let runway = Runway::new(runway_config);
-    let runway_handle = runway.run(exit_stream).fuse();
+    let runway_handle = in_span(
+        "runway",
+        config.node_ix,
+        config.session_id,
+        runway.run(runway_terminator),
+    )
+    .fuse();
     pin_mut!(runway_handle);
 
-    futures::select! {
-        _ = runway_handle => {
+    let mut result = futures::select! {
+        result = runway_handle => {
             debug!(target: "AlephBFT-runway", "{:?} Runway task terminated early.", index);
+            result
         },
-        _ = alerter_handle => {
+        result = alerter_handle => {
             debug!(target: "AlephBFT-runway", "{:?} Alerter task terminated early.", index);
+            Err(SessionError::task_ended("runway/alerter", result))
         },
-        _ = consensus_handle => {
+        result = consensus_handle => {
             debug!(target: "AlephBFT-runway", "{:?} Consensus task terminated early.", index);
+            Err(SessionError::task_ended("runway/consensus", result))
         },
-        _ = &mut exit => {},
-    }
+        _ = terminator.get_exit() => Ok(SessionOutcome::Exited),
+    };
 
     info!(target: "AlephBFT-runway", "{:?} Ending run.", index);
 
-    if consensus_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-runway", "{:?} Consensus already stopped.", index);
+    // The consensus, with the creator, stops first, and the runway itself last, so that it
+    // still backs up and passes on everything the others produced.
+    if let Some(Err(())) = terminator
+        .terminate_offspring("runway/consensus", &mut consensus_handle)
+        .await
+    {
+        warn!(target: "AlephBFT-runway", "{:?} Consensus finished with an error", index);
+    }
+
+    if let Some(Err(())) = terminator
+        .terminate_offspring("runway/alerter", &mut alerter_handle)
+        .await
+    {
+        warn!(target: "AlephBFT-runway", "{:?} Alerter finished with an error", index);
+    }
+
+    // E.g. the creator exits because of its starting round, which the runway knows about.
+    if let Some(Err(error)) = terminator
+        .terminate_offspring("runway/main", &mut runway_handle)
+        .await
+    {
+        result = result.map_err(|e| e.or_cause(error));
+    }
+    terminator.terminate_sync().await;
+
+    info!(target: "AlephBFT-runway", "{:?} Runway ended.", index);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_parent_coords, verify_units, CatchUpMonitor, DagShapeRecorder, LivenessMonitor,
+        ParentsMismatch, QuorumRounds, RecentHashes, ResponseLimiter, UnitVariants,
+        VerificationBudget, VerificationQueue, DAG_SHAPE_ROUNDS,
+    };
+    use crate::{
+        nodes::{NodeSubset, NodeWeights},
+        status::MemberActivity,
+        testing::mock::{Data, Hasher64, KeyBox, Signature},
+        units::{ControlHash, FullUnit, PreUnit, UncheckedSignedUnit, UnitCoord},
+        CatchUpConfig, Hasher, LivenessConfig, NodeCount, NodeIndex, ResponseLimitConfig,
+        SessionStatus, UncheckedSigned,
+    };
+    use codec::Encode;
+    use futures::{channel::mpsc, StreamExt};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    fn unit(round: u16) -> UncheckedSignedUnit<Hasher64, Data, Signature> {
+        let control_hash = ControlHash {
+            parents_mask: NodeSubset::with_size(NodeCount(4)),
+            combined_hash: 0.using_encoded(Hasher64::hash),
+        };
+        let pu = PreUnit::new(NodeIndex(0), round, control_hash);
+        let data = Data::new(UnitCoord::new(round, NodeIndex(0)), 0);
+        UncheckedSigned::new(FullUnit::new(pu, data, 0), Signature {})
     }
-    if !consensus_handle.is_terminated() {
-        if let Err(()) = consensus_handle.await {
-            warn!(target: "AlephBFT-runway", "{:?} Consensus finished with an error", index);
+
+    #[tokio::test]
+    async fn verification_yields_once_the_budget_is_spent() {
+        let status = SessionStatus::new();
+        let yields = Arc::new(AtomicUsize::new(0));
+        let counted_yields = yields.clone();
+        let budget = VerificationBudget {
+            budget: Some(2),
+            spent: 0,
+            yield_now: Box::new(move || {
+                counted_yields.fetch_add(1, Ordering::Relaxed);
+                Box::pin(async {})
+            }),
+            status: status.clone(),
+        };
+        let (units_tx, units_rx) = mpsc::unbounded();
+        let (verified_tx, mut verified_rx) = mpsc::unbounded();
+        for round in 0..5 {
+            units_tx
+                .unbounded_send((round as u64, unit(round)))
+                .expect("the receiver is alive");
         }
+        drop(units_tx);
+        let keybox = KeyBox::new(NodeCount(4), NodeIndex(1));
+        verify_units(keybox, units_rx, verified_tx, budget).await;
+        for seq in 0..5 {
+            let (verified_seq, _, _) = verified_rx.next().await.expect("the unit was verified");
+            assert_eq!(verified_seq, seq);
+        }
+        assert_eq!(yields.load(Ordering::Relaxed), 2);
+        assert_eq!(status.n_verified_units(), 5);
+    }
+
+    #[tokio::test]
+    async fn verification_queue_keeps_the_order() {
+        let (first_tx, mut first_rx) = mpsc::unbounded();
+        let (second_tx, mut second_rx) = mpsc::unbounded();
+        let mut queue = VerificationQueue::new(vec![first_tx, second_tx]);
+        for round in 0..3 {
+            assert!(queue.submit(unit(round)));
+        }
+        let (seq_0, unit_0) = first_rx.next().await.expect("the unit was submitted");
+        let (seq_1, unit_1) = second_rx.next().await.expect("the unit was submitted");
+        let (seq_2, unit_2) = first_rx.next().await.expect("the unit was submitted");
+        queue.on_verified(seq_1, unit_1, false);
+        queue.on_verified(seq_2, unit_2, true);
+        assert!(queue.next_ready().is_none());
+        queue.on_verified(seq_0, unit_0, true);
+        let rounds: Vec<_> = std::iter::from_fn(|| queue.next_ready())
+            .map(|(uu, valid)| (uu.as_signable().round(), valid))
+            .collect();
+        assert_eq!(rounds, vec![(0, true), (1, false), (2, true)]);
+    }
+
+    #[test]
+    fn recent_hashes_forget_the_oldest() {
+        let mut recent = RecentHashes::new(2);
+        recent.insert(1);
+        recent.insert(2);
+        // Inserting a known hash does not refresh it.
+        recent.insert(1);
+        recent.insert(3);
+        assert!(!recent.contains(&1));
+        assert!(recent.contains(&2));
+        assert!(recent.contains(&3));
+    }
+
+    #[test]
+    fn recent_hashes_with_no_capacity_remember_nothing() {
+        let mut recent = RecentHashes::new(0);
+        recent.insert(1);
+        assert!(!recent.contains(&1));
     }
 
-    if alerter_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-runway", "{:?} Alerter already stopped.", index);
+    #[test]
+    fn response_limiter_caps_units_per_requester_within_window() {
+        let window = Duration::from_secs(1);
+        let mut limiter = ResponseLimiter::new(Some(ResponseLimitConfig {
+            max_units: 3,
+            window,
+        }));
+        let start = Instant::now();
+        assert!(limiter.allow(NodeIndex(1), 2, start));
+        assert!(limiter.allow(NodeIndex(1), 1, start));
+        assert!(!limiter.allow(NodeIndex(1), 1, start));
+        // Other requesters have their own limits.
+        assert!(limiter.allow(NodeIndex(2), 3, start));
+        // Once the earlier responses leave the window, we send again.
+        assert!(limiter.allow(NodeIndex(1), 1, start + window));
+        // A single response larger than the limit is sent when nothing else was.
+        assert!(limiter.allow(NodeIndex(2), 5, start + window));
+        assert!(!limiter.allow(NodeIndex(2), 1, start + window));
     }
-    if !alerter_handle.is_terminated() {
-        if let Err(()) = alerter_handle.await {
-            warn!(target: "AlephBFT-runway", "{:?} Alerter finished with an error", index);
+
+    #[test]
+    fn response_limiter_without_config_allows_everything() {
+        let mut limiter = ResponseLimiter::new(None);
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(limiter.allow(NodeIndex(1), 100, now));
         }
     }
 
-    if runway_exit.send(()).is_err() {
-        debug!(target: "AlephBFT-runway", "{:?} Runway already stopped.", index);
+    fn parents(indices: &[usize]) -> NodeSubset {
+        let mut parents = NodeSubset::with_size(NodeCount(4));
+        for ix in indices {
+            parents.insert(NodeIndex(*ix));
+        }
+        parents
     }
-    if !runway_handle.is_terminated() {
-        runway_handle.await;
+
+    #[test]
+    fn dag_shape_summarizes_recent_rounds() {
+        let mut recorder = DagShapeRecorder::new(NodeIndex(0), NodeWeights::uniform(NodeCount(4)));
+        assert_eq!(recorder.shape().highest_round, None);
+        for creator in 0..3 {
+            recorder.add_unit(0, NodeIndex(creator), &parents(&[]));
+        }
+        recorder.add_unit(1, NodeIndex(0), &parents(&[0, 1, 2]));
+        // A fork does not make the round any wider.
+        recorder.add_unit(1, NodeIndex(0), &parents(&[0, 1, 2]));
+        let shape = recorder.shape();
+        assert_eq!(shape.widths, vec![(0, NodeCount(3)), (1, NodeCount(1))]);
+        assert_eq!(shape.average_parents, 6.0 / 5.0);
+        assert_eq!(shape.highest_round, Some(1));
+        assert_eq!(shape.highest_quorum_round, Some(0));
+        assert_eq!(shape.quorum_gap(), Some(1));
+
+        recorder.add_unit(DAG_SHAPE_ROUNDS, NodeIndex(1), &parents(&[0, 1, 2]));
+        // Too old to count anymore.
+        recorder.add_unit(0, NodeIndex(3), &parents(&[]));
+        let shape = recorder.shape();
+        assert_eq!(
+            shape.widths,
+            vec![(1, NodeCount(1)), (DAG_SHAPE_ROUNDS, NodeCount(1))]
+        );
+        assert_eq!(shape.average_parents, 3.0);
+        assert_eq!(shape.quorum_gap(), Some(DAG_SHAPE_ROUNDS));
     }
 
-    info!(target: "AlephBFT-runway", "{:?} Runway ended.", index);
+    #[test]
+    fn dag_shape_tracks_inclusion_of_own_units() {
+        let mut recorder = DagShapeRecorder::new(NodeIndex(0), NodeWeights::uniform(NodeCount(4)));
+        for creator in 0..4 {
+            recorder.add_unit(0, NodeIndex(creator), &parents(&[]));
+        }
+        // The next round has no quorum yet, so it says nothing.
+        recorder.add_unit(1, NodeIndex(0), &parents(&[0, 1, 2]));
+        assert_eq!(recorder.own_unit_inclusion(), (0, 0));
+        assert_eq!(recorder.shape().own_unit_inclusion, None);
+        recorder.add_unit(1, NodeIndex(1), &parents(&[0, 1, 2]));
+        recorder.add_unit(1, NodeIndex(2), &parents(&[1, 2, 3]));
+        assert_eq!(recorder.own_unit_inclusion(), (0, 1));
+        recorder.add_unit(1, NodeIndex(3), &parents(&[0, 2, 3]));
+        assert_eq!(recorder.own_unit_inclusion(), (1, 1));
+        // Only two members include our unit of round 1.
+        for creator in 0..3 {
+            let own = if creator == 2 {
+                &[1, 2, 3][..]
+            } else {
+                &[0, 1, 2][..]
+            };
+            recorder.add_unit(2, NodeIndex(creator), &parents(own));
+        }
+        assert_eq!(recorder.own_unit_inclusion(), (1, 2));
+        assert_eq!(recorder.shape().own_unit_inclusion, Some(0.5));
+    }
+
+    #[test]
+    fn dag_shape_tracks_parent_inclusion() {
+        let mut recorder = DagShapeRecorder::new(NodeIndex(0), NodeWeights::uniform(NodeCount(4)));
+        for creator in 0..4 {
+            recorder.add_unit(0, NodeIndex(creator), &parents(&[]));
+        }
+        assert_eq!(recorder.shape().parent_inclusion, vec![None; 4]);
+        assert_eq!(recorder.shape().parent_inclusion_balance(), None);
+        // Nobody uses the unit of member 3.
+        for creator in 0..4 {
+            recorder.add_unit(1, NodeIndex(creator), &parents(&[0, 1, 2]));
+        }
+        assert_eq!(
+            recorder.parent_inclusion(),
+            vec![(4, 4), (4, 4), (4, 4), (0, 4)]
+        );
+        assert_eq!(recorder.shape().parent_inclusion_balance(), Some(0.0));
+        // Member 2 is not a candidate for round 3, as it has no unit of round 2.
+        for creator in 0..2 {
+            recorder.add_unit(2, NodeIndex(creator), &parents(&[0, 1, 3]));
+        }
+        recorder.add_unit(2, NodeIndex(3), &parents(&[0, 1, 3]));
+        recorder.add_unit(3, NodeIndex(0), &parents(&[0, 1, 3]));
+        recorder.add_unit(3, NodeIndex(1), &parents(&[0, 1, 3]));
+        assert_eq!(
+            recorder.parent_inclusion(),
+            vec![(9, 9), (9, 9), (4, 7), (5, 9)]
+        );
+        let shape = recorder.shape();
+        assert_eq!(shape.parent_inclusion[2], Some(4.0 / 7.0));
+        assert_eq!(shape.parent_inclusion_balance(), Some(5.0 / 9.0));
+    }
+
+    fn activity(last_unit_at: Instant) -> Option<MemberActivity> {
+        Some(MemberActivity {
+            highest_round: 5,
+            last_unit_at,
+        })
+    }
+
+    fn liveness_config(min_active_members: Option<NodeCount>) -> LivenessConfig {
+        LivenessConfig {
+            stall_threshold: Duration::from_secs(10),
+            activity_window: Duration::from_secs(5),
+            min_active_members,
+        }
+    }
+
+    #[test]
+    fn liveness_monitor_tells_wedged_from_quiet() {
+        let start = Instant::now();
+        let mut monitor = LivenessMonitor::new(
+            liveness_config(None),
+            NodeIndex(0),
+            NodeWeights::uniform(NodeCount(4)),
+            start,
+        );
+        let now = start + Duration::from_secs(20);
+        let active = |ix: NodeIndex| match ix.0 {
+            1 | 2 => activity(now - Duration::from_secs(1)),
+            3 => activity(start),
+            _ => None,
+        };
+        assert_eq!(monitor.wedged(start + Duration::from_secs(5), active), None);
+        // Together with us the active members form a quorum.
+        assert_eq!(monitor.wedged(now, active), Some(NodeCount(2)));
+        let quiet = |ix: NodeIndex| match ix.0 {
+            1 => activity(now - Duration::from_secs(1)),
+            _ => activity(start),
+        };
+        assert_eq!(monitor.wedged(now, quiet), None);
+        monitor.on_finalized(now);
+        assert_eq!(monitor.wedged(now + Duration::from_secs(1), active), None);
+    }
+
+    #[test]
+    fn liveness_monitor_counts_the_given_number_of_active_members() {
+        let start = Instant::now();
+        let monitor = LivenessMonitor::new(
+            liveness_config(Some(NodeCount(1))),
+            NodeIndex(0),
+            NodeWeights::uniform(NodeCount(4)),
+            start,
+        );
+        let now = start + Duration::from_secs(20);
+        // We do not count ourselves.
+        let only_us = |ix: NodeIndex| match ix.0 {
+            0 => activity(now),
+            _ => None,
+        };
+        assert_eq!(monitor.wedged(now, only_us), None);
+        let one_other = |ix: NodeIndex| match ix.0 {
+            2 => activity(now),
+            _ => None,
+        };
+        assert_eq!(monitor.wedged(now, one_other), Some(NodeCount(1)));
+    }
+
+    #[test]
+    fn unit_variants_reject_beyond_the_limit() {
+        let coord = UnitCoord::new(3, NodeIndex(1));
+        let other_coord = UnitCoord::new(3, NodeIndex(2));
+        let mut variants = UnitVariants::new(2);
+        variants.insert(coord, 1);
+        assert!(!variants.is_full(&coord, &2));
+        variants.insert(coord, 2);
+        assert!(variants.is_full(&coord, &3));
+        // The known variants are still processed, and so are the units of others.
+        assert!(!variants.is_full(&coord, &1));
+        assert!(!variants.is_full(&other_coord, &3));
+        variants.retain(|c| *c != coord);
+        assert!(!variants.is_full(&coord, &3));
+    }
+
+    #[test]
+    fn unit_variants_count_the_ones_being_verified() {
+        let coord = UnitCoord::new(3, NodeIndex(1));
+        let mut variants = UnitVariants::new(2);
+        variants.insert(coord, 1);
+        variants.start_verifying(coord, 2);
+        assert!(variants.is_full(&coord, &3));
+        assert!(!variants.is_full(&coord, &2));
+        // A badly signed unit frees its place.
+        variants.finish_verifying(&coord, &2);
+        assert!(!variants.is_full(&coord, &3));
+        variants.start_verifying(coord, 3);
+        variants.finish_verifying(&coord, &3);
+        variants.insert(coord, 3);
+        assert!(variants.is_full(&coord, &4));
+    }
+
+    #[test]
+    fn rounds_are_reported_once_they_reach_a_quorum() {
+        let mut quorum_rounds = QuorumRounds::new(NodeWeights::uniform(NodeCount(4)));
+        for creator in 0..2 {
+            assert!(quorum_rounds.add_unit(0, NodeIndex(creator)).is_empty());
+        }
+        assert_eq!(quorum_rounds.add_unit(0, NodeIndex(2)), vec![0]);
+        // The round is reported only once.
+        assert!(quorum_rounds.add_unit(0, NodeIndex(3)).is_empty());
+        for creator in 0..2 {
+            assert!(quorum_rounds.add_unit(1, NodeIndex(creator)).is_empty());
+        }
+        // A fork does not make a quorum.
+        assert!(quorum_rounds.add_unit(1, NodeIndex(1)).is_empty());
+        assert_eq!(quorum_rounds.add_unit(1, NodeIndex(3)), vec![1]);
+    }
+
+    #[test]
+    fn catch_up_monitor_switches_with_hysteresis() {
+        let mut monitor = CatchUpMonitor::new(CatchUpConfig {
+            behind_rounds: 10,
+            caught_up_rounds: 4,
+            fanout: 3,
+        });
+        assert_eq!(CatchUpMonitor::lag(None, 12), 13);
+        assert_eq!(CatchUpMonitor::lag(Some(12), 12), 0);
+        assert_eq!(monitor.update(CatchUpMonitor::lag(Some(2), 12)), None);
+        assert_eq!(monitor.update(CatchUpMonitor::lag(None, 12)), Some(true));
+        assert_eq!(monitor.update(13), None);
+        // Between the thresholds we keep doing what we did.
+        assert_eq!(monitor.update(7), None);
+        assert!(monitor.catching_up);
+        assert_eq!(monitor.update(4), Some(false));
+        assert_eq!(monitor.update(7), None);
+        assert_eq!(monitor.update(10), None);
+        assert!(!monitor.catching_up);
+        assert_eq!(monitor.update(11), Some(true));
+    }
+
+    #[test]
+    fn parents_have_to_be_of_the_previous_round_and_given_creators() {
+        let creators = [NodeIndex(0), NodeIndex(1), NodeIndex(3)];
+        let coord = |round, creator| UnitCoord::new(round, NodeIndex(creator));
+        assert_eq!(
+            check_parent_coords(5, &creators, &[coord(4, 0), coord(4, 1), coord(4, 3)]),
+            Ok(())
+        );
+        assert_eq!(
+            check_parent_coords(5, &creators, &[coord(4, 0), coord(4, 1)]),
+            Err(ParentsMismatch::Count {
+                expected: 3,
+                got: 2
+            })
+        );
+        // Skipping a round, in either direction.
+        assert_eq!(
+            check_parent_coords(5, &creators, &[coord(4, 0), coord(2, 1), coord(4, 3)]),
+            Err(ParentsMismatch::Round {
+                parent: coord(2, 1),
+                round: 5
+            })
+        );
+        assert_eq!(
+            check_parent_coords(5, &creators, &[coord(5, 0), coord(4, 1), coord(4, 3)]),
+            Err(ParentsMismatch::Round {
+                parent: coord(5, 0),
+                round: 5
+            })
+        );
+        assert_eq!(
+            check_parent_coords(0, &creators[..1], &[coord(0, 0)]),
+            Err(ParentsMismatch::Round {
+                parent: coord(0, 0),
+                round: 0
+            })
+        );
+        // Two parents of the same creator.
+        assert_eq!(
+            check_parent_coords(5, &creators, &[coord(4, 0), coord(4, 1), coord(4, 1)]),
+            Err(ParentsMismatch::Creator {
+                parent: coord(4, 1),
+                expected: NodeIndex(3)
+            })
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use crate::{
-    nodes::{NodeCount, NodeIndex, NodeMap},
-    Index,
+    nodes::{NodeCount, NodeIndex, NodeMap, NodeWeights},
+    Index, Round,
 };
 use async_trait::async_trait;
 use codec::{Decode, Encode};
@@ -30,6 +30,23 @@ pub trait KeyBox: Index + Clone + Send + Sync + 'static {
     async fn sign(&self, msg: &[u8]) -> Self::Signature;
     /// Verifies whether a node with `index` correctly signed the message `msg`.
     fn verify(&self, msg: &[u8], sgn: &Self::Signature, index: NodeIndex) -> bool;
+    /// Signs a message `msg` belonging to the given round, like a unit. Keychains whose keys
+    /// change during a session use the key valid in that round. By default it calls
+    /// [`KeyBox::sign`].
+    async fn sign_in_round(&self, msg: &[u8], _round: Round) -> Self::Signature {
+        self.sign(msg).await
+    }
+    /// Verifies a signature of a message belonging to the given round against the key the node
+    /// with `index` used in that round. By default it calls [`KeyBox::verify`].
+    fn verify_in_round(
+        &self,
+        msg: &[u8],
+        sgn: &Self::Signature,
+        index: NodeIndex,
+        _round: Round,
+    ) -> bool {
+        self.verify(msg, sgn, index)
+    }
 }
 
 /// A type to which signatures can be aggregated.
@@ -52,6 +69,7 @@ pub trait PartialMultisignature: Signature {
 pub trait MultiKeychain: KeyBox {
     type PartialMultisignature: PartialMultisignature<Signature = Self::Signature>;
     /// Transform a single signature to a multisignature consisting of the signature.
+    #[allow(clippy::wrong_self_convention)]
     fn from_signature(
         &self,
         signature: &Self::Signature,
@@ -59,6 +77,28 @@ pub trait MultiKeychain: KeyBox {
     ) -> Self::PartialMultisignature;
     /// Checks if enough signatures have beed added.
     fn is_complete(&self, msg: &[u8], partial: &Self::PartialMultisignature) -> bool;
+    /// Verifies a batch of signatures, each given with the signed message and the index of the
+    /// signer, and returns whether all of them are correct.
+    ///
+    /// The default implementation calls [`KeyBox::verify`] for every signature. Schemes
+    /// supporting batch verification should override it.
+    fn verify_batch(&self, batch: &[(&[u8], &Self::Signature, NodeIndex)]) -> bool {
+        batch
+            .iter()
+            .all(|(msg, sgn, index)| self.verify(msg, sgn, *index))
+    }
+    /// Verifies a batch like [`MultiKeychain::verify_batch`], where every signed message belongs
+    /// to the given round, see [`KeyBox::verify_in_round`]. By default the rounds are ignored.
+    fn verify_batch_in_rounds(
+        &self,
+        batch: &[(&[u8], &Self::Signature, NodeIndex, Round)],
+    ) -> bool {
+        let batch: Vec<_> = batch
+            .iter()
+            .map(|(msg, sgn, index, _)| (*msg, *sgn, *index))
+            .collect();
+        self.verify_batch(&batch)
+    }
 }
 
 /// Data which can be signed.
@@ -70,6 +110,41 @@ pub trait Signable {
     type Hash: AsRef<[u8]>;
     /// Return a hash for signing.
     fn hash(&self) -> Self::Hash;
+    /// The round the data belongs to, if any, which decides the key signing it when keys change
+    /// during a session.
+    fn signing_round(&self) -> Option<Round> {
+        None
+    }
+}
+
+async fn sign_signable<T: Signable, KB: KeyBox>(signable: &T, key_box: &KB) -> KB::Signature {
+    let hash = signable.hash();
+    match signable.signing_round() {
+        Some(round) => key_box.sign_in_round(hash.as_ref(), round).await,
+        None => key_box.sign(hash.as_ref()).await,
+    }
+}
+
+fn verify_signable<T: Signable, KB: KeyBox>(
+    signable: &T,
+    signature: &KB::Signature,
+    index: NodeIndex,
+    key_box: &KB,
+) -> bool {
+    // Keyboxes usually look the key up by the index, so they need not handle unknown members.
+    if index.0 >= key_box.node_count().0 {
+        return false;
+    }
+    // Only for measurements in environments where no member can misbehave, see the `trusted`
+    // feature.
+    if cfg!(feature = "trusted") {
+        return true;
+    }
+    let hash = signable.hash();
+    match signable.signing_round() {
+        Some(round) => key_box.verify_in_round(hash.as_ref(), signature, index, round),
+        None => key_box.verify(hash.as_ref(), signature, index),
+    }
 }
 
 impl<T: AsRef<[u8]> + Clone> Signable for T {
@@ -109,7 +184,7 @@ impl<T: Signable, S: Signature> UncheckedSigned<Indexed<T>, S> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "benchmarks"))]
 impl<T: Signable, S: Signature> UncheckedSigned<T, S> {
     pub(crate) fn new(signable: T, signature: S) -> Self {
         UncheckedSigned {
@@ -117,6 +192,10 @@ impl<T: Signable, S: Signature> UncheckedSigned<T, S> {
             signature,
         }
     }
+}
+
+#[cfg(test)]
+impl<T: Signable, S: Signature> UncheckedSigned<T, S> {
     pub(crate) fn new_with_index(
         signable: T,
         index: NodeIndex,
@@ -143,9 +222,9 @@ impl<T: Signable + Index, S: Signature> UncheckedSigned<T, S> {
     pub fn check<KB: KeyBox<Signature = S>>(
         self,
         key_box: &KB,
-    ) -> Result<Signed<T, KB>, SignatureError<T, S>> {
+    ) -> Result<Signed<'_, T, KB>, SignatureError<T, S>> {
         let index = self.signable.index();
-        if !key_box.verify(self.signable.hash().as_ref(), &self.signature, index) {
+        if !verify_signable(&self.signable, &self.signature, index, key_box) {
             return Err(SignatureError { unchecked: self });
         }
         Ok(Signed {
@@ -155,6 +234,64 @@ impl<T: Signable + Index, S: Signature> UncheckedSigned<T, S> {
     }
 }
 
+impl<T: Signable + Index, S: Signature> UncheckedSigned<T, S> {
+    /// Verifies the signature like [`UncheckedSigned::check`], without consuming the object.
+    pub(crate) fn is_signature_valid<KB: KeyBox<Signature = S>>(&self, key_box: &KB) -> bool {
+        verify_signable(
+            &self.signable,
+            &self.signature,
+            self.signable.index(),
+            key_box,
+        )
+    }
+
+    /// Verifies the signatures of all the objects with a single call to
+    /// [`MultiKeychain::verify_batch`].
+    pub(crate) fn are_signatures_valid<'b, MK: MultiKeychain<Signature = S>>(
+        unchecked: impl IntoIterator<Item = &'b Self>,
+        keychain: &MK,
+    ) -> bool
+    where
+        Self: 'b,
+    {
+        let unchecked: Vec<_> = unchecked.into_iter().collect();
+        let n_members = keychain.node_count();
+        if unchecked
+            .iter()
+            .any(|u| u.signable.index().0 >= n_members.0)
+        {
+            return false;
+        }
+        if cfg!(feature = "trusted") {
+            return true;
+        }
+        let hashes: Vec<_> = unchecked.iter().map(|u| u.signable.hash()).collect();
+        let mut batch = Vec::new();
+        let mut batch_in_rounds = Vec::new();
+        for (u, hash) in unchecked.iter().zip(hashes.iter()) {
+            let (msg, sgn, index) = (hash.as_ref(), &u.signature, u.signable.index());
+            match u.signable.signing_round() {
+                Some(round) => batch_in_rounds.push((msg, sgn, index, round)),
+                None => batch.push((msg, sgn, index)),
+            }
+        }
+        (batch.is_empty() || keychain.verify_batch(&batch))
+            && (batch_in_rounds.is_empty() || keychain.verify_batch_in_rounds(&batch_in_rounds))
+    }
+
+    /// Upgrades the object without verifying the signature again. It must have been already
+    /// verified with an equivalent key box, e.g. a clone of `key_box`.
+    pub(crate) fn assume_checked<KB: KeyBox<Signature = S>>(
+        self,
+        _key_box: &KB,
+    ) -> Signed<'_, T, KB> {
+        Signed {
+            unchecked: self,
+            marker: PhantomData,
+        }
+    }
+}
+
 impl<T: Signable + Index, S: Signature> Index for UncheckedSigned<T, S> {
     fn index(&self) -> NodeIndex {
         self.signable.index()
@@ -166,7 +303,7 @@ impl<T: Signable, S: PartialMultisignature> UncheckedSigned<T, S> {
     pub fn check_multi<MK: MultiKeychain<PartialMultisignature = S>>(
         self,
         keychain: &MK,
-    ) -> Result<Multisigned<T, MK>, SignatureError<T, S>> {
+    ) -> Result<Multisigned<'_, T, MK>, SignatureError<T, S>> {
         if !(keychain.is_complete(self.signable.hash().as_ref(), &self.signature)) {
             return Err(SignatureError { unchecked: self });
         }
@@ -215,7 +352,7 @@ impl<'a, T: Signable + Index, KB: KeyBox> Signed<'a, T, KB> {
     /// Create a signed object from a signable. The index of `signable` must match the index of the `key_box`.
     pub async fn sign(signable: T, key_box: &'a KB) -> Signed<'a, T, KB> {
         assert_eq!(signable.index(), key_box.index());
-        let signature = key_box.sign(signable.hash().as_ref()).await;
+        let signature = sign_signable(&signable, key_box).await;
         Signed {
             unchecked: UncheckedSigned {
                 signable,
@@ -307,6 +444,10 @@ impl<T: Signable> Signable for Indexed<T> {
     fn hash(&self) -> Self::Hash {
         self.signable.hash()
     }
+
+    fn signing_round(&self) -> Option<Round> {
+        self.signable.signing_round()
+    }
 }
 
 impl<T: Signable> Index for Indexed<T> {
@@ -447,22 +588,35 @@ impl<S: Signature> PartialMultisignature for SignatureSet<S> {
 }
 
 /// Keybox wrapper which implements MultiKeychain such that a partial multisignature is a list of
-/// signatures and a partial multisignature is considered complete if it contains more than 2N/3 signatures.
+/// signatures and a partial multisignature is considered complete if it contains more than 2N/3 signatures,
+/// or signatures of more than 2/3 of the total weight, see [`DefaultMultiKeychain::with_weights`].
 ///
 /// Note: this way of multisigning is very inefficient, and should be used only for testing.
 #[derive(Debug, Clone)]
 pub struct DefaultMultiKeychain<KB: KeyBox> {
     key_box: KB,
+    node_weights: NodeWeights,
 }
 
 impl<KB: KeyBox> DefaultMultiKeychain<KB> {
     // Create a new `DefaultMultiKeychain` using the provided `KeyBox`.
     pub fn new(key_box: KB) -> Self {
-        DefaultMultiKeychain { key_box }
+        let node_weights = NodeWeights::uniform(key_box.node_count());
+        DefaultMultiKeychain {
+            key_box,
+            node_weights,
+        }
     }
 
-    fn quorum(&self) -> usize {
-        2 * self.node_count().0 / 3 + 1
+    /// Counts the signatures with the given weights of the members.
+    pub fn with_weights(mut self, node_weights: NodeWeights) -> Self {
+        assert_eq!(
+            node_weights.n_members(),
+            self.node_count(),
+            "There should be a weight for every member."
+        );
+        self.node_weights = node_weights;
+        self
     }
 }
 
@@ -487,6 +641,20 @@ impl<KB: KeyBox> KeyBox for DefaultMultiKeychain<KB> {
     fn verify(&self, msg: &[u8], sgn: &Self::Signature, index: NodeIndex) -> bool {
         self.key_box.verify(msg, sgn, index)
     }
+
+    async fn sign_in_round(&self, msg: &[u8], round: Round) -> Self::Signature {
+        self.key_box.sign_in_round(msg, round).await
+    }
+
+    fn verify_in_round(
+        &self,
+        msg: &[u8],
+        sgn: &Self::Signature,
+        index: NodeIndex,
+        round: Round,
+    ) -> bool {
+        self.key_box.verify_in_round(msg, sgn, index, round)
+    }
 }
 
 impl<KB: KeyBox> MultiKeychain for DefaultMultiKeychain<KB> {
@@ -501,12 +669,19 @@ impl<KB: KeyBox> MultiKeychain for DefaultMultiKeychain<KB> {
     }
 
     fn is_complete(&self, msg: &[u8], partial: &Self::PartialMultisignature) -> bool {
-        let signature_count = partial.iter().count();
-        if signature_count < self.quorum() {
+        if !self.node_weights.is_quorum(partial.iter().map(|(i, _)| i)) {
             return false;
         }
-        partial
+        let batch: Vec<_> = partial.iter().map(|(i, sgn)| (msg, sgn, i)).collect();
+        self.verify_batch(&batch)
+    }
+
+    fn verify_batch_in_rounds(
+        &self,
+        batch: &[(&[u8], &Self::Signature, NodeIndex, Round)],
+    ) -> bool {
+        batch
             .iter()
-            .all(|(i, sgn)| self.key_box.verify(msg, sgn, i))
+            .all(|(msg, sgn, index, round)| self.verify_in_round(msg, sgn, *index, *round))
     }
 }
@@ -0,0 +1,107 @@
+use crate::{SpawnHandle, TaskHandle};
+use futures::{future::BoxFuture, Future};
+use log::{debug, error};
+use tokio::runtime::Handle;
+
+/// A [`SpawnHandle`] running the tasks on a tokio runtime, available with the `tokio` feature.
+///
+/// The spawner does not own the runtime, it only keeps a handle to it, so the runtime has to be
+/// kept alive by the application for as long as the session runs. All the tasks are spawned
+/// right away, regardless of whether anyone awaits the returned handles. If an essential task
+/// panics, or is cancelled because the runtime shuts down, its handle resolves to an error, which
+/// makes the session stop all its other tasks and exit.
+#[derive(Clone)]
+pub struct TokioSpawner {
+    handle: Handle,
+}
+
+impl TokioSpawner {
+    /// Creates a spawner for the runtime with the given handle.
+    pub fn new(handle: Handle) -> Self {
+        TokioSpawner { handle }
+    }
+
+    /// Creates a spawner for the runtime we are running in.
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn current() -> Self {
+        TokioSpawner::new(Handle::current())
+    }
+}
+
+impl SpawnHandle for TokioSpawner {
+    fn spawn(&self, _name: &'static str, task: impl Future<Output = ()> + Send + 'static) {
+        self.handle.spawn(task);
+    }
+
+    fn spawn_essential(
+        &self,
+        name: &'static str,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> TaskHandle {
+        let task = self.handle.spawn(task);
+        Box::pin(async move {
+            task.await.map_err(|e| {
+                if e.is_panic() {
+                    error!(target: "AlephBFT-spawner", "Essential task {} panicked.", name);
+                } else {
+                    debug!(target: "AlephBFT-spawner", "Essential task {} was cancelled.", name);
+                }
+            })
+        })
+    }
+
+    fn yield_now(&self) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::task::yield_now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokioSpawner;
+    use crate::SpawnHandle;
+    use futures::channel::oneshot;
+
+    #[tokio::test]
+    async fn essential_task_finishes() {
+        let spawner = TokioSpawner::current();
+        let handle = spawner.spawn_essential("task", async {});
+        assert_eq!(handle.await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn essential_task_panics() {
+        let spawner = TokioSpawner::current();
+        let handle = spawner.spawn_essential("task", async { panic!("the task failed") });
+        assert_eq!(handle.await, Err(()));
+    }
+
+    #[tokio::test]
+    async fn tasks_run_without_awaiting_handles() {
+        let spawner = TokioSpawner::current();
+        let (tx, rx) = oneshot::channel();
+        let _handle = spawner.spawn_essential("task", async move {
+            let _ = tx.send(());
+        });
+        assert_eq!(rx.await, Ok(()));
+        let (tx, rx) = oneshot::channel();
+        spawner.spawn("task", async move {
+            let _ = tx.send(());
+        });
+        assert_eq!(rx.await, Ok(()));
+    }
+
+    #[test]
+    fn essential_task_is_cancelled_with_runtime() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime builds");
+        let spawner = TokioSpawner::new(runtime.handle().clone());
+        let handle = spawner.spawn_essential("task", futures::future::pending());
+        drop(runtime);
+        let other_runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime builds");
+        assert_eq!(other_runtime.block_on(handle), Err(()));
+    }
+}
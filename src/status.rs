@@ -0,0 +1,874 @@
+use crate::{nodes::NodeSubset, rmc::MultisignatureProgress, NodeCount, NodeIndex, Round};
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+use std::{
+    ops::{Bound, RangeBounds},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "metrics")]
+mod prometheus;
+
+/// The requests for the Dag in the DOT format, only ever sent with the `debug` feature.
+#[cfg(feature = "debug")]
+pub(crate) type DagRequests = crate::Receiver<oneshot::Sender<String>>;
+#[cfg(not(feature = "debug"))]
+pub(crate) type DagRequests = futures::stream::Pending<oneshot::Sender<String>>;
+
+// The rounds are stored increased by one, so that zero means there is no such round yet.
+#[derive(Default)]
+struct Progress {
+    current_round: AtomicU32,
+    max_round_reached: AtomicU32,
+    max_round_approached: AtomicU32,
+    last_finalized_round: AtomicU32,
+    n_ordered_units: AtomicU64,
+    n_stored_units: AtomicU64,
+    n_unordered_units: AtomicUsize,
+    oldest_unordered_round: AtomicU32,
+    // Shared with the consensus, which counts the units passed to the creator.
+    parents_backlog: Arc<AtomicUsize>,
+    n_creator_lags: AtomicU64,
+    recovery_timed_out: AtomicBool,
+    n_store_overloads: AtomicU64,
+    finalization_lagging: AtomicBool,
+    n_forks_detected: AtomicU64,
+    n_alerts_originated: AtomicU64,
+    n_alerts_forwarded: AtomicU64,
+    n_multisignatures_collected: AtomicU64,
+    n_verified_units: AtomicU64,
+    verification_nanos: AtomicU64,
+    caught_up: AtomicBool,
+    // Only touched together with `caught_up`, so that no waiter misses the signal.
+    caught_up_waiters: Mutex<Vec<oneshot::Sender<()>>>,
+    wedged: AtomicBool,
+    // Only touched together with `wedged`, like `caught_up_waiters`.
+    wedged_waiters: Mutex<Vec<oneshot::Sender<()>>>,
+    member_activity: Mutex<Vec<Option<MemberActivity>>>,
+    created_unit_parents: Mutex<Option<CreatedUnitParents>>,
+    creator_stall: Mutex<Option<CreatorStall>>,
+    malformed_unit_creators: Mutex<Vec<NodeIndex>>,
+    multisignatures_in_progress: Mutex<Vec<MultisignatureProgress<Vec<u8>>>>,
+    dag_shape: Mutex<DagShape>,
+    finalized_units: Mutex<FinalizedUnits>,
+    #[cfg(feature = "debug")]
+    dag_requests: parking_lot::Mutex<Option<crate::Sender<oneshot::Sender<String>>>>,
+}
+
+// The hashes of the finalized units in the order of finalization, stored back to back, as they
+// all have the same length.
+#[derive(Default)]
+struct FinalizedUnits {
+    hashes: Vec<u8>,
+    hash_len: usize,
+    // The round of every batch, together with the number of units finalized before it.
+    batches: Vec<(Round, usize)>,
+}
+
+impl FinalizedUnits {
+    fn n_units(&self) -> usize {
+        self.hashes.len().checked_div(self.hash_len).unwrap_or(0)
+    }
+
+    // The first batch of a round at least, or above, `round`.
+    fn first_batch(&self, round: Bound<&Round>) -> usize {
+        match round {
+            Bound::Included(round) => self.batches.partition_point(|(r, _)| r < round),
+            Bound::Excluded(round) => self.batches.partition_point(|(r, _)| r <= round),
+            Bound::Unbounded => 0,
+        }
+    }
+
+    // The first batch after the ones of rounds at most, or below, `round`.
+    fn end_batch(&self, round: Bound<&Round>) -> usize {
+        match round {
+            Bound::Included(round) => self.batches.partition_point(|(r, _)| r <= round),
+            Bound::Excluded(round) => self.batches.partition_point(|(r, _)| r < round),
+            Bound::Unbounded => self.batches.len(),
+        }
+    }
+
+    // The number of units finalized before the batch.
+    fn units_before(&self, batch: usize) -> usize {
+        self.batches
+            .get(batch)
+            .map_or_else(|| self.n_units(), |(_, n_units)| *n_units)
+    }
+}
+
+/// The newest unit we have seen from a member, see [`SessionStatus::member_activity`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemberActivity {
+    /// The highest round of a unit of the member we have seen.
+    pub highest_round: Round,
+    /// When we first saw a unit of the member of that round, as measured by
+    /// [`Config::clock`](crate::Config::clock). With the default clock, `last_unit_at.elapsed()`
+    /// tells how long ago it was.
+    pub last_unit_at: Instant,
+}
+
+/// The parents of a unit we created, see [`SessionStatus::created_unit_parents`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CreatedUnitParents {
+    /// The round of the unit.
+    pub round: Round,
+    /// The creators of the parents, in the order of their indices.
+    pub parents: Vec<NodeIndex>,
+    /// The size of the committee, i.e. the number of parents the unit could have at most.
+    pub n_members: NodeCount,
+}
+
+impl CreatedUnitParents {
+    pub fn n_parents(&self) -> NodeCount {
+        NodeCount(self.parents.len())
+    }
+}
+
+/// Our creator waiting for the parents of a unit longer than expected, see
+/// [`SessionStatus::creator_stall`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CreatorStall {
+    /// The round of the unit.
+    pub round: Round,
+    /// The number of parents available for it when the creator last checked.
+    pub parents_collected: NodeCount,
+    /// The number of parents it needs.
+    pub parents_needed: NodeCount,
+}
+
+/// The shape of the recent rounds of the Dag, see [`SessionStatus::dag_shape`]. Only the units
+/// with all their parents in the Dag count.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DagShape {
+    /// For each of the recent rounds, from the lowest, the number of members with a unit of it.
+    pub widths: Vec<(Round, NodeCount)>,
+    /// The average number of parents of the units of the recent rounds.
+    pub average_parents: f64,
+    /// The highest round of a unit.
+    pub highest_round: Option<Round>,
+    /// The highest round with units of more than two thirds of the members.
+    pub highest_quorum_round: Option<Round>,
+    /// The fraction of the recent rounds with our unit in which more than two thirds of the
+    /// members used it as a parent in the next round, among the rounds followed by units of that
+    /// many members. A low one means our units come too late for the others.
+    pub own_unit_inclusion: Option<f64>,
+    /// For every member, indexed by their indices, the fraction of the units of the recent rounds
+    /// which used the unit of the member of the previous round as a parent, among the units
+    /// following one of its units. `None` for the members without such units.
+    pub parent_inclusion: Vec<Option<f64>>,
+}
+
+impl DagShape {
+    /// How many rounds the highest round is above the highest round with a quorum. A large gap
+    /// means some members create units much faster than a quorum of the committee can keep up.
+    pub fn quorum_gap(&self) -> Option<Round> {
+        Some(
+            self.highest_round?
+                .saturating_sub(self.highest_quorum_round?),
+        )
+    }
+
+    /// How evenly the units of the members are used as parents, i.e. the lowest
+    /// [`DagShape::parent_inclusion`] divided by the highest one. One means all the members are
+    /// used equally often, while a value close to zero means some members are systematically
+    /// left out in favour of others, which makes the committee depend on the favoured ones.
+    /// `None` until the inclusion of at least two members is known.
+    pub fn parent_inclusion_balance(&self) -> Option<f64> {
+        let mut inclusions = self.parent_inclusion.iter().flatten();
+        let first = *inclusions.next()?;
+        let (lowest, highest, n_members) = inclusions.fold(
+            (first, first, 1),
+            |(lowest, highest, n_members), inclusion| {
+                (
+                    lowest.min(*inclusion),
+                    highest.max(*inclusion),
+                    n_members + 1,
+                )
+            },
+        );
+        if n_members < 2 || highest == 0.0 {
+            return None;
+        }
+        Some(lowest / highest)
+    }
+}
+
+/// A cheap, read-only view of the progress of a running session, see [`spawn_session`](crate::spawn_session).
+#[derive(Clone, Default)]
+pub struct SessionStatus {
+    progress: Arc<Progress>,
+}
+
+fn load_round(round: &AtomicU32) -> Option<Round> {
+    match round.load(Ordering::Relaxed) {
+        0 => None,
+        round => Some((round - 1) as Round),
+    }
+}
+
+impl SessionStatus {
+    pub(crate) fn new() -> Self {
+        SessionStatus::default()
+    }
+
+    /// The round of the last unit we created.
+    pub fn current_round(&self) -> Option<Round> {
+        load_round(&self.progress.current_round)
+    }
+
+    /// The last maximum round our units reached, see
+    /// [`Config::max_round_policy`](crate::Config::max_round_policy). Unless it was extended
+    /// since, we are not creating units anymore.
+    pub fn max_round_reached(&self) -> Option<Round> {
+        load_round(&self.progress.max_round_reached)
+    }
+
+    /// The last maximum round our units got within
+    /// [`Config::max_round_warning_margin`](crate::Config::max_round_warning_margin) rounds of,
+    /// so that it should be extended soon, unless the session is meant to end there.
+    pub fn max_round_approached(&self) -> Option<Round> {
+        load_round(&self.progress.max_round_approached)
+    }
+
+    /// The parents of the last unit we created. Units with barely more than two thirds of the
+    /// committee as parents, round after round, mean we hear from few of the others in time.
+    pub fn created_unit_parents(&self) -> Option<CreatedUnitParents> {
+        self.progress.created_unit_parents.lock().clone()
+    }
+
+    /// The unit our creator is stuck at, for lack of parents, since
+    /// [`DelayConfig::stall_warning_delay`](crate::DelayConfig::stall_warning_delay) after it was
+    /// allowed to create it. `None` once the unit is created.
+    pub fn creator_stall(&self) -> Option<CreatorStall> {
+        *self.progress.creator_stall.lock()
+    }
+
+    /// The highest round of a unit ordered so far.
+    pub fn last_finalized_round(&self) -> Option<Round> {
+        load_round(&self.progress.last_finalized_round)
+    }
+
+    /// The number of units ordered so far.
+    pub fn n_ordered_units(&self) -> u64 {
+        self.progress.n_ordered_units.load(Ordering::Relaxed)
+    }
+
+    /// The number of units currently kept in memory, see [`Config::max_stored_units`](crate::Config::max_stored_units).
+    pub fn n_stored_units(&self) -> u64 {
+        self.progress.n_stored_units.load(Ordering::Relaxed)
+    }
+
+    /// The number of units in the Dag which the consensus has not ordered yet. They are ordered
+    /// once the head of a round above them is decided, so a number growing while
+    /// [`SessionStatus::last_finalized_round`] stays the same means the consensus sees the units,
+    /// but cannot decide on a head, e.g. because too few members vote in time.
+    pub fn n_unordered_units(&self) -> usize {
+        self.progress.n_unordered_units.load(Ordering::Relaxed)
+    }
+
+    /// The lowest round of a unit which the consensus has not ordered yet, see
+    /// [`SessionStatus::n_unordered_units`].
+    pub fn oldest_unordered_round(&self) -> Option<Round> {
+        load_round(&self.progress.oldest_unordered_round)
+    }
+
+    /// The number of units added to the Dag which the creator has not taken as candidates for
+    /// parents yet. A growing one means the creator cannot keep up, see
+    /// [`Config::parents_backlog_high_water`](crate::Config::parents_backlog_high_water).
+    pub fn parents_backlog(&self) -> usize {
+        self.progress.parents_backlog.load(Ordering::Relaxed)
+    }
+
+    /// How many times the creator fell more than
+    /// [`Config::parents_backlog_high_water`](crate::Config::parents_backlog_high_water) units
+    /// behind the Dag, counting again only after it caught up with most of them.
+    pub fn n_creator_lags(&self) -> u64 {
+        self.progress.n_creator_lags.load(Ordering::Relaxed)
+    }
+
+    /// How many times the units kept in memory grew above
+    /// [`Config::max_stored_units`](crate::Config::max_stored_units), which slows down unit
+    /// creation, counting again only after they got back within it.
+    pub fn n_store_overloads(&self) -> u64 {
+        self.progress.n_store_overloads.load(Ordering::Relaxed)
+    }
+
+    /// Whether the finalization handler is more than
+    /// [`Config::max_undelivered_rounds`](crate::Config::max_undelivered_rounds) behind at the
+    /// moment, so that we hold back the units from the consensus until it catches up.
+    pub fn is_finalization_lagging(&self) -> bool {
+        self.progress.finalization_lagging.load(Ordering::Relaxed)
+    }
+
+    /// Whether recovering from the backup took longer than
+    /// [`Config::recovery_timeout`](crate::Config::recovery_timeout), so that we fetched some
+    /// units of the other members from them again, rather than recovering them.
+    pub fn recovery_timed_out(&self) -> bool {
+        self.progress.recovery_timed_out.load(Ordering::Relaxed)
+    }
+
+    /// The number of forkers we know about, detected by us or learned from alerts.
+    pub fn n_forks_detected(&self) -> u64 {
+        self.progress.n_forks_detected.load(Ordering::Relaxed)
+    }
+
+    /// The members which signed units breaking the structure of the Dag, e.g. with parents not
+    /// carrying a quorum, sorted by their indices. Such units are rejected, and only their
+    /// creators could have signed them, so the members are certainly malicious.
+    pub fn malformed_unit_creators(&self) -> Vec<NodeIndex> {
+        self.progress.malformed_unit_creators.lock().clone()
+    }
+
+    /// The number of alerts about forks we sent.
+    pub fn n_alerts_originated(&self) -> u64 {
+        self.progress.n_alerts_originated.load(Ordering::Relaxed)
+    }
+
+    /// The number of correct alerts of other members we took part in multicasting.
+    pub fn n_alerts_forwarded(&self) -> u64 {
+        self.progress.n_alerts_forwarded.load(Ordering::Relaxed)
+    }
+
+    /// The number of alerts for which we collected a complete multisignature.
+    pub fn n_multisignatures_collected(&self) -> u64 {
+        self.progress
+            .n_multisignatures_collected
+            .load(Ordering::Relaxed)
+    }
+
+    /// The number of units with signatures we verified, including the invalid ones.
+    pub fn n_verified_units(&self) -> u64 {
+        self.progress.n_verified_units.load(Ordering::Relaxed)
+    }
+
+    /// The time spent verifying the signatures of units so far, summed over the verification
+    /// tasks, see [`Config::verification_budget`](crate::Config::verification_budget).
+    pub fn verification_time(&self) -> Duration {
+        Duration::from_nanos(self.progress.verification_nanos.load(Ordering::Relaxed))
+    }
+
+    /// How far we are in collecting the multisignatures of alerts which are not complete yet,
+    /// with the hashes as bytes. A multisignature stuck below the needed count for long means
+    /// some members do not sign, or their signatures do not reach us.
+    pub fn multisignatures_in_progress(&self) -> Vec<MultisignatureProgress<Vec<u8>>> {
+        self.progress.multisignatures_in_progress.lock().clone()
+    }
+
+    /// The shape of the recent rounds of the Dag, e.g. for studying how the
+    /// [`DelayConfig::unit_creation_delay`](crate::DelayConfig::unit_creation_delay) affects it.
+    pub fn dag_shape(&self) -> DagShape {
+        self.progress.dag_shape.lock().clone()
+    }
+
+    /// Whether we caught up with the rest of the committee, see [`SessionStatus::caught_up`].
+    pub fn is_caught_up(&self) -> bool {
+        self.progress.caught_up.load(Ordering::Acquire)
+    }
+
+    /// Resolves once we caught up with the rest of the committee, i.e. the units we ordered are
+    /// close to the highest round we have seen, see
+    /// [`CatchUpConfig::caught_up_rounds`](crate::CatchUpConfig::caught_up_rounds), and have
+    /// stayed so for a while after the initial catch up. This happens at most once per session and a node falling behind later does not
+    /// reset it. If the session ends before, it never resolves, so it should be awaited together
+    /// with the handle of the session.
+    pub async fn caught_up(&self) {
+        let receiver = {
+            let mut waiters = self.progress.caught_up_waiters.lock();
+            if self.is_caught_up() {
+                return;
+            }
+            let (sender, receiver) = oneshot::channel();
+            waiters.push(sender);
+            receiver
+        };
+        // The senders are kept as long as the status, so this cannot fail.
+        let _ = receiver.await;
+    }
+
+    /// Whether we are wedged at the moment, see [`SessionStatus::wedged`].
+    pub fn is_wedged(&self) -> bool {
+        self.progress.wedged.load(Ordering::Acquire)
+    }
+
+    /// Resolves once we are wedged, i.e. we finalized nothing for a while even though the other
+    /// members are active, see [`Config::liveness`](crate::Config::liveness). Unlike a quiet
+    /// network, this means that something is wrong with this node, so it is a liveness signal,
+    /// e.g. for restarting the node. [`SessionStatus::is_wedged`] turns false again once we
+    /// finalize a unit. If the session ends before, it never resolves, so it should be awaited
+    /// together with the handle of the session.
+    pub async fn wedged(&self) {
+        let receiver = {
+            let mut waiters = self.progress.wedged_waiters.lock();
+            if self.is_wedged() {
+                return;
+            }
+            let (sender, receiver) = oneshot::channel();
+            waiters.push(sender);
+            receiver
+        };
+        // The senders are kept as long as the status, so this cannot fail.
+        let _ = receiver.await;
+    }
+
+    /// The hashes of the units we finalized, as bytes, in the order of finalization, in the
+    /// batches of the given rounds. The round of a batch is the round of its head, i.e. the
+    /// highest round of its units, also for every part of a batch split because of
+    /// [`Config::max_batch_size`](crate::Config::max_batch_size), see
+    /// [`SessionStatus::last_finalized_round`]. All the honest members finalize the same units,
+    /// so e.g. `finalized_unit_hashes(..=round)` can be compared against other members, and
+    /// `finalized_unit_hashes(round..)` returns only the units finalized since.
+    pub fn finalized_unit_hashes(&self, rounds: impl RangeBounds<Round>) -> Vec<Vec<u8>> {
+        let finalized = self.progress.finalized_units.lock();
+        let first_batch = finalized.first_batch(rounds.start_bound());
+        let end_batch = finalized.end_batch(rounds.end_bound());
+        if first_batch >= end_batch || finalized.hash_len == 0 {
+            return Vec::new();
+        }
+        let hash_len = finalized.hash_len;
+        let first = finalized.units_before(first_batch) * hash_len;
+        let end = finalized.units_before(end_batch) * hash_len;
+        finalized.hashes[first..end]
+            .chunks(hash_len)
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+
+    /// The newest unit we have seen from the given member, including ourselves, or `None` if we
+    /// have not seen any unit of it yet. A member whose highest round stays far below the others
+    /// is not contributing, because it crashed or is cut off from us.
+    pub fn member_activity(&self, node_ix: NodeIndex) -> Option<MemberActivity> {
+        self.progress
+            .member_activity
+            .lock()
+            .get(node_ix.0)
+            .copied()
+            .flatten()
+    }
+
+    /// The Dag of the session in the GraphViz DOT format, with the finalized units filled.
+    /// Returns `None` if the session is not running.
+    #[cfg(feature = "debug")]
+    pub async fn dag_dot(&self) -> Option<String> {
+        let (response, dag) = oneshot::channel();
+        self.progress
+            .dag_requests
+            .lock()
+            .as_ref()?
+            .unbounded_send(response)
+            .ok()?;
+        dag.await.ok()
+    }
+
+    #[cfg(feature = "debug")]
+    pub(crate) fn dag_requests(&self) -> DagRequests {
+        let (requests_tx, requests_rx) = futures::channel::mpsc::unbounded();
+        *self.progress.dag_requests.lock() = Some(requests_tx);
+        requests_rx
+    }
+
+    #[cfg(not(feature = "debug"))]
+    pub(crate) fn dag_requests(&self) -> DagRequests {
+        futures::stream::pending()
+    }
+
+    pub(crate) fn parents_backlog_counter(&self) -> Arc<AtomicUsize> {
+        self.progress.parents_backlog.clone()
+    }
+
+    pub(crate) fn on_unit_created(&self, round: Round, parents: &NodeSubset) {
+        self.progress
+            .current_round
+            .fetch_max(round as u32 + 1, Ordering::Relaxed);
+        *self.progress.created_unit_parents.lock() = Some(CreatedUnitParents {
+            round,
+            parents: parents.elements().collect(),
+            n_members: NodeCount(parents.size()),
+        });
+        let mut stall = self.progress.creator_stall.lock();
+        if stall.is_some_and(|stall| stall.round <= round) {
+            *stall = None;
+        }
+    }
+
+    pub(crate) fn on_creator_stalled(&self, stall: CreatorStall) {
+        *self.progress.creator_stall.lock() = Some(stall);
+    }
+
+    pub(crate) fn on_creator_lagging(&self) {
+        self.progress.n_creator_lags.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_store_overloaded(&self) {
+        self.progress
+            .n_store_overloads
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_finalization_lagging(&self, lagging: bool) {
+        self.progress
+            .finalization_lagging
+            .store(lagging, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_recovery_timed_out(&self) {
+        self.progress
+            .recovery_timed_out
+            .store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_max_round_approached(&self, max_round: Round) {
+        self.progress
+            .max_round_approached
+            .store(max_round as u32 + 1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_max_round_reached(&self, max_round: Round) {
+        self.progress
+            .max_round_reached
+            .store(max_round as u32 + 1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_unit_seen(&self, creator: NodeIndex, round: Round, now: Instant) {
+        let mut activity = self.progress.member_activity.lock();
+        if activity.len() <= creator.0 {
+            activity.resize(creator.0 + 1, None);
+        }
+        let activity = &mut activity[creator.0];
+        if activity.is_none_or(|activity| activity.highest_round < round) {
+            *activity = Some(MemberActivity {
+                highest_round: round,
+                last_unit_at: now,
+            });
+        }
+    }
+
+    pub(crate) fn on_units_ordered(&self, n_units: usize, max_round: Round) {
+        self.progress
+            .n_ordered_units
+            .fetch_add(n_units as u64, Ordering::Relaxed);
+        self.progress
+            .last_finalized_round
+            .fetch_max(max_round as u32 + 1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_batch_finalized<H: AsRef<[u8]>>(&self, round: Round, hashes: &[H]) {
+        let mut finalized = self.progress.finalized_units.lock();
+        let n_units = finalized.n_units();
+        // The rounds have to stay sorted for looking the batches up.
+        let round = finalized
+            .batches
+            .last()
+            .map_or(round, |(last_round, _)| round.max(*last_round));
+        finalized.batches.push((round, n_units));
+        for hash in hashes {
+            let hash = hash.as_ref();
+            finalized.hash_len = hash.len();
+            finalized.hashes.extend_from_slice(hash);
+        }
+    }
+
+    pub(crate) fn on_unordered_units(&self, n_units: usize, oldest_round: Option<Round>) {
+        self.progress
+            .n_unordered_units
+            .store(n_units, Ordering::Relaxed);
+        self.progress.oldest_unordered_round.store(
+            oldest_round.map_or(0, |round| round as u32 + 1),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub(crate) fn on_units_stored(&self, n_units: usize) {
+        self.progress
+            .n_stored_units
+            .store(n_units as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_dag_shape(&self, shape: DagShape) {
+        *self.progress.dag_shape.lock() = shape;
+    }
+
+    pub(crate) fn on_fork_detected(&self) {
+        self.progress
+            .n_forks_detected
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_malformed_unit_creator(&self, creator: NodeIndex) -> bool {
+        self.progress
+            .malformed_unit_creators
+            .lock()
+            .binary_search_by_key(&creator.0, |ix| ix.0)
+            .is_ok()
+    }
+
+    pub(crate) fn on_malformed_unit(&self, creator: NodeIndex) {
+        let mut creators = self.progress.malformed_unit_creators.lock();
+        if let Err(position) = creators.binary_search_by_key(&creator.0, |ix| ix.0) {
+            creators.insert(position, creator);
+        }
+    }
+
+    pub(crate) fn on_unit_verified(&self, elapsed: Duration) {
+        self.progress
+            .n_verified_units
+            .fetch_add(1, Ordering::Relaxed);
+        self.progress
+            .verification_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_alert_originated(&self) {
+        self.progress
+            .n_alerts_originated
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_alert_forwarded(&self) {
+        self.progress
+            .n_alerts_forwarded
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_caught_up(&self) {
+        let mut waiters = self.progress.caught_up_waiters.lock();
+        self.progress.caught_up.store(true, Ordering::Release);
+        for waiter in waiters.drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+
+    pub(crate) fn on_wedged(&self, wedged: bool) {
+        let mut waiters = self.progress.wedged_waiters.lock();
+        self.progress.wedged.store(wedged, Ordering::Release);
+        if wedged {
+            for waiter in waiters.drain(..) {
+                let _ = waiter.send(());
+            }
+        }
+    }
+
+    pub(crate) fn on_multisignature_progress<H: AsRef<[u8]>>(
+        &self,
+        progress: MultisignatureProgress<H>,
+    ) {
+        let progress = MultisignatureProgress {
+            hash: progress.hash.as_ref().to_vec(),
+            collected: progress.collected,
+            needed: progress.needed,
+        };
+        let mut in_progress = self.progress.multisignatures_in_progress.lock();
+        match in_progress
+            .iter_mut()
+            .find(|other| other.hash == progress.hash)
+        {
+            Some(other) => *other = progress,
+            None => in_progress.push(progress),
+        }
+    }
+
+    pub(crate) fn on_multisignature_collected(&self, hash: &[u8]) {
+        self.progress
+            .n_multisignatures_collected
+            .fetch_add(1, Ordering::Relaxed);
+        self.progress
+            .multisignatures_in_progress
+            .lock()
+            .retain(|progress| progress.hash != hash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CreatedUnitParents, CreatorStall, MemberActivity, SessionStatus};
+    use crate::{nodes::NodeSubset, rmc::MultisignatureProgress, NodeCount, NodeIndex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn reports_progress() {
+        let status = SessionStatus::new();
+        let view = status.clone();
+        assert_eq!(view.current_round(), None);
+        assert_eq!(view.last_finalized_round(), None);
+        assert_eq!(view.n_ordered_units(), 0);
+        assert_eq!(view.created_unit_parents(), None);
+        let mut parents = NodeSubset::with_size(NodeCount(4));
+        status.on_unit_created(0, &parents);
+        parents.insert(NodeIndex(0));
+        parents.insert(NodeIndex(2));
+        parents.insert(NodeIndex(3));
+        status.on_unit_created(2, &parents);
+        status.on_units_ordered(3, 0);
+        status.on_units_ordered(4, 1);
+        assert_eq!(view.current_round(), Some(2));
+        assert_eq!(view.max_round_approached(), None);
+        status.on_max_round_approached(10);
+        assert_eq!(view.max_round_approached(), Some(10));
+        assert_eq!(view.max_round_reached(), None);
+        status.on_max_round_reached(10);
+        assert_eq!(view.max_round_reached(), Some(10));
+        let created_unit_parents = view.created_unit_parents().expect("we created units");
+        assert_eq!(
+            created_unit_parents,
+            CreatedUnitParents {
+                round: 2,
+                parents: vec![NodeIndex(0), NodeIndex(2), NodeIndex(3)],
+                n_members: NodeCount(4),
+            }
+        );
+        assert_eq!(created_unit_parents.n_parents(), NodeCount(3));
+        assert_eq!(view.last_finalized_round(), Some(1));
+        assert_eq!(view.n_ordered_units(), 7);
+        status.on_units_stored(12);
+        status.on_units_stored(5);
+        assert_eq!(view.n_stored_units(), 5);
+        assert_eq!(view.oldest_unordered_round(), None);
+        status.on_unordered_units(9, Some(0));
+        assert_eq!(view.n_unordered_units(), 9);
+        assert_eq!(view.oldest_unordered_round(), Some(0));
+        status.on_unordered_units(0, None);
+        assert_eq!(view.n_unordered_units(), 0);
+        assert_eq!(view.oldest_unordered_round(), None);
+        status.on_fork_detected();
+        status.on_alert_originated();
+        status.on_alert_forwarded();
+        status.on_alert_forwarded();
+        status.on_multisignature_collected(&[1]);
+        assert_eq!(view.n_forks_detected(), 1);
+        assert_eq!(view.n_alerts_originated(), 1);
+        assert_eq!(view.n_alerts_forwarded(), 2);
+        assert_eq!(view.n_multisignatures_collected(), 1);
+        status.on_creator_lagging();
+        assert_eq!(view.n_creator_lags(), 1);
+        status.on_store_overloaded();
+        assert_eq!(view.n_store_overloads(), 1);
+        status.on_finalization_lagging(true);
+        assert!(view.is_finalization_lagging());
+        status.on_finalization_lagging(false);
+        assert!(!view.is_finalization_lagging());
+        assert!(!view.recovery_timed_out());
+        status.on_recovery_timed_out();
+        assert!(view.recovery_timed_out());
+        status.on_unit_verified(Duration::from_micros(150));
+        status.on_unit_verified(Duration::from_micros(50));
+        assert_eq!(view.n_verified_units(), 2);
+        assert_eq!(view.verification_time(), Duration::from_micros(200));
+        status.on_malformed_unit(NodeIndex(3));
+        status.on_malformed_unit(NodeIndex(1));
+        status.on_malformed_unit(NodeIndex(3));
+        assert!(view.is_malformed_unit_creator(NodeIndex(1)));
+        assert!(!view.is_malformed_unit_creator(NodeIndex(2)));
+        assert_eq!(
+            view.malformed_unit_creators(),
+            vec![NodeIndex(1), NodeIndex(3)]
+        );
+    }
+
+    #[test]
+    fn reports_creator_stall_until_unit_created() {
+        let status = SessionStatus::new();
+        assert_eq!(status.creator_stall(), None);
+        let stall = CreatorStall {
+            round: 3,
+            parents_collected: NodeCount(1),
+            parents_needed: NodeCount(3),
+        };
+        status.on_creator_stalled(stall);
+        let parents = NodeSubset::with_size(NodeCount(4));
+        status.on_unit_created(2, &parents);
+        assert_eq!(status.creator_stall(), Some(stall));
+        status.on_unit_created(3, &parents);
+        assert_eq!(status.creator_stall(), None);
+    }
+
+    #[test]
+    fn reports_multisignatures_in_progress() {
+        let status = SessionStatus::new();
+        let progress = |hash: [u8; 1], collected| MultisignatureProgress {
+            hash,
+            collected: NodeCount(collected),
+            needed: NodeCount(3),
+        };
+        status.on_multisignature_progress(progress([1], 1));
+        status.on_multisignature_progress(progress([2], 1));
+        status.on_multisignature_progress(progress([1], 2));
+        assert_eq!(
+            status.multisignatures_in_progress(),
+            vec![
+                MultisignatureProgress {
+                    hash: vec![1],
+                    collected: NodeCount(2),
+                    needed: NodeCount(3),
+                },
+                MultisignatureProgress {
+                    hash: vec![2],
+                    collected: NodeCount(1),
+                    needed: NodeCount(3),
+                },
+            ]
+        );
+        status.on_multisignature_collected(&[1]);
+        assert_eq!(
+            status.multisignatures_in_progress(),
+            vec![MultisignatureProgress {
+                hash: vec![2],
+                collected: NodeCount(1),
+                needed: NodeCount(3),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_member_activity() {
+        let status = SessionStatus::new();
+        let start = Instant::now();
+        let later = start + Duration::from_secs(5);
+        assert_eq!(status.member_activity(NodeIndex(2)), None);
+        status.on_unit_seen(NodeIndex(2), 3, start);
+        // Older units, fetched when catching up, do not count as activity.
+        status.on_unit_seen(NodeIndex(2), 1, later);
+        assert_eq!(
+            status.member_activity(NodeIndex(2)),
+            Some(MemberActivity {
+                highest_round: 3,
+                last_unit_at: start,
+            })
+        );
+        status.on_unit_seen(NodeIndex(2), 4, later);
+        assert_eq!(
+            status.member_activity(NodeIndex(2)).map(|a| a.last_unit_at),
+            Some(later)
+        );
+        assert_eq!(status.member_activity(NodeIndex(0)), None);
+        assert_eq!(status.member_activity(NodeIndex(7)), None);
+    }
+
+    #[tokio::test]
+    async fn signals_catching_up() {
+        let status = SessionStatus::new();
+        let view = status.clone();
+        let waiting = tokio::spawn(async move { view.caught_up().await });
+        assert!(!status.is_caught_up());
+        status.on_caught_up();
+        waiting.await.expect("the waiter does not panic");
+        assert!(status.is_caught_up());
+        status.caught_up().await;
+    }
+
+    #[tokio::test]
+    async fn signals_being_wedged() {
+        let status = SessionStatus::new();
+        let view = status.clone();
+        let waiting = tokio::spawn(async move { view.wedged().await });
+        assert!(!status.is_wedged());
+        status.on_wedged(true);
+        waiting.await.expect("the waiter does not panic");
+        assert!(status.is_wedged());
+        status.wedged().await;
+        status.on_wedged(false);
+        assert!(!status.is_wedged());
+    }
+}
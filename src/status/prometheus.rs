@@ -0,0 +1,335 @@
+use super::SessionStatus;
+use crate::Round;
+use std::fmt::{Display, Write};
+
+const PREFIX: &str = "aleph_bft_";
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        }
+    }
+}
+
+// The metrics in the text exposition format, one family after another.
+struct Exposition(String);
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: Kind, help: &str) {
+        writeln!(self.0, "# HELP {PREFIX}{name} {help}").expect("writing to a string works");
+        writeln!(self.0, "# TYPE {PREFIX}{name} {}", kind.name())
+            .expect("writing to a string works");
+    }
+
+    fn sample(&mut self, name: &str, labels: &str, value: impl Display) {
+        writeln!(self.0, "{PREFIX}{name}{labels} {value}").expect("writing to a string works");
+    }
+
+    fn metric(&mut self, name: &str, kind: Kind, help: &str, value: impl Display) {
+        self.family(name, kind, help);
+        self.sample(name, "", value);
+    }
+
+    // Left out until the round is known, as there is no value meaning no round.
+    fn round(&mut self, name: &str, help: &str, round: Option<Round>) {
+        if let Some(round) = round {
+            self.metric(name, Kind::Gauge, help, round);
+        }
+    }
+}
+
+impl SessionStatus {
+    /// The counters and gauges of the session in the Prometheus text exposition format, ready to
+    /// be served by an HTTP handler of the application, available with the `metrics` feature. The
+    /// names of the metrics start with `aleph_bft_`, and the rounds we have not reached yet are
+    /// left out. The metrics carry no session label, so the ones of sessions running at the same
+    /// time have to be served separately.
+    pub fn prometheus_metrics(&self) -> String {
+        let mut output = Exposition(String::new());
+        output.round(
+            "current_round",
+            "The round of the last unit we created.",
+            self.current_round(),
+        );
+        output.round(
+            "max_round_approached",
+            "The last maximum round our units got close to.",
+            self.max_round_approached(),
+        );
+        output.round(
+            "max_round_reached",
+            "The last maximum round our units reached.",
+            self.max_round_reached(),
+        );
+        if let Some(parents) = self.created_unit_parents() {
+            output.metric(
+                "created_unit_parents",
+                Kind::Gauge,
+                "The number of parents of the last unit we created.",
+                parents.n_parents().0,
+            );
+            output.metric(
+                "committee_size",
+                Kind::Gauge,
+                "The number of members, i.e. the number of parents a unit can have at most.",
+                parents.n_members.0,
+            );
+        }
+        if let Some(stall) = self.creator_stall() {
+            output.metric(
+                "creator_stalled_round",
+                Kind::Gauge,
+                "The round of the unit our creator is stuck at for lack of parents.",
+                stall.round,
+            );
+        }
+        output.round(
+            "last_finalized_round",
+            "The highest round of a unit ordered so far.",
+            self.last_finalized_round(),
+        );
+        output.metric(
+            "ordered_units_total",
+            Kind::Counter,
+            "The number of units ordered so far.",
+            self.n_ordered_units(),
+        );
+        output.metric(
+            "stored_units",
+            Kind::Gauge,
+            "The number of units currently kept in memory.",
+            self.n_stored_units(),
+        );
+        output.metric(
+            "unordered_units",
+            Kind::Gauge,
+            "The number of units in the Dag not ordered yet.",
+            self.n_unordered_units(),
+        );
+        output.round(
+            "oldest_unordered_round",
+            "The lowest round of a unit in the Dag not ordered yet.",
+            self.oldest_unordered_round(),
+        );
+        output.metric(
+            "parents_backlog",
+            Kind::Gauge,
+            "The number of units added to the Dag not yet taken by the creator.",
+            self.parents_backlog(),
+        );
+        output.metric(
+            "creator_lags_total",
+            Kind::Counter,
+            "The number of times the creator fell too many units behind the Dag.",
+            self.n_creator_lags(),
+        );
+        output.metric(
+            "store_overloads_total",
+            Kind::Counter,
+            "The number of times the units kept in memory grew above the bound.",
+            self.n_store_overloads(),
+        );
+        output.metric(
+            "finalization_lagging",
+            Kind::Gauge,
+            "Whether the finalization handler is too far behind.",
+            u8::from(self.is_finalization_lagging()),
+        );
+        output.metric(
+            "recovery_timed_out",
+            Kind::Gauge,
+            "Whether recovering from the backup timed out.",
+            u8::from(self.recovery_timed_out()),
+        );
+        output.metric(
+            "forks_detected_total",
+            Kind::Counter,
+            "The number of forkers we know about.",
+            self.n_forks_detected(),
+        );
+        output.metric(
+            "malformed_unit_creators",
+            Kind::Gauge,
+            "The number of members which signed malformed units.",
+            self.malformed_unit_creators().len(),
+        );
+        output.metric(
+            "alerts_originated_total",
+            Kind::Counter,
+            "The number of alerts about forks we sent.",
+            self.n_alerts_originated(),
+        );
+        output.metric(
+            "alerts_forwarded_total",
+            Kind::Counter,
+            "The number of alerts of other members we took part in multicasting.",
+            self.n_alerts_forwarded(),
+        );
+        output.metric(
+            "multisignatures_collected_total",
+            Kind::Counter,
+            "The number of alerts with a complete multisignature.",
+            self.n_multisignatures_collected(),
+        );
+        output.metric(
+            "verified_units_total",
+            Kind::Counter,
+            "The number of units with signatures we verified.",
+            self.n_verified_units(),
+        );
+        output.metric(
+            "verification_seconds_total",
+            Kind::Counter,
+            "The time spent verifying the signatures of units.",
+            self.verification_time().as_secs_f64(),
+        );
+        output.metric(
+            "multisignatures_in_progress",
+            Kind::Gauge,
+            "The number of alerts with a multisignature not complete yet.",
+            self.multisignatures_in_progress().len(),
+        );
+        output.metric(
+            "caught_up",
+            Kind::Gauge,
+            "Whether we caught up with the rest of the committee.",
+            u8::from(self.is_caught_up()),
+        );
+        output.metric(
+            "wedged",
+            Kind::Gauge,
+            "Whether we finalize nothing even though the other members are active.",
+            u8::from(self.is_wedged()),
+        );
+        let shape = self.dag_shape();
+        output.round(
+            "dag_highest_round",
+            "The highest round of a unit in the Dag.",
+            shape.highest_round,
+        );
+        output.round(
+            "dag_highest_quorum_round",
+            "The highest round of the Dag with units of more than two thirds of the members.",
+            shape.highest_quorum_round,
+        );
+        output.metric(
+            "dag_average_parents",
+            Kind::Gauge,
+            "The average number of parents of the units of the recent rounds.",
+            shape.average_parents,
+        );
+        if let Some(inclusion) = shape.own_unit_inclusion {
+            output.metric(
+                "own_unit_inclusion",
+                Kind::Gauge,
+                "The fraction of the recent rounds in which a quorum used our unit as a parent.",
+                inclusion,
+            );
+        }
+        if let Some(balance) = shape.parent_inclusion_balance() {
+            output.metric(
+                "parent_inclusion_balance",
+                Kind::Gauge,
+                "The lowest fraction of units using the unit of a member as a parent divided by the highest one.",
+                balance,
+            );
+        }
+        output.family(
+            "member_parent_inclusion",
+            Kind::Gauge,
+            "The fraction of the recent units using the unit of the member as a parent.",
+        );
+        for (member, inclusion) in shape.parent_inclusion.iter().enumerate() {
+            if let Some(inclusion) = inclusion {
+                let labels = format!("{{member=\"{member}\"}}");
+                output.sample("member_parent_inclusion", &labels, inclusion);
+            }
+        }
+        let activity = self.progress.member_activity.lock().clone();
+        output.family(
+            "member_highest_round",
+            Kind::Gauge,
+            "The highest round of a unit of the member we have seen.",
+        );
+        for (member, activity) in activity.iter().enumerate() {
+            if let Some(activity) = activity {
+                let labels = format!("{{member=\"{member}\"}}");
+                output.sample("member_highest_round", &labels, activity.highest_round);
+            }
+        }
+        output.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{nodes::NodeSubset, CreatorStall, NodeCount, NodeIndex, SessionStatus};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn renders_metrics_in_text_format() {
+        let status = SessionStatus::new();
+        let metrics = status.prometheus_metrics();
+        assert!(!metrics.contains("aleph_bft_current_round"));
+        assert!(metrics.contains("\naleph_bft_ordered_units_total 0\n"));
+        assert!(metrics.contains("# TYPE aleph_bft_ordered_units_total counter\n"));
+
+        let mut parents = NodeSubset::with_size(NodeCount(4));
+        parents.insert(NodeIndex(1));
+        parents.insert(NodeIndex(2));
+        parents.insert(NodeIndex(3));
+        status.on_unit_created(3, &parents);
+        status.on_max_round_reached(4);
+        status.on_creator_stalled(CreatorStall {
+            round: 4,
+            parents_collected: NodeCount(2),
+            parents_needed: NodeCount(3),
+        });
+        status.on_units_ordered(5, 2);
+        status.on_unordered_units(6, Some(3));
+        status.on_fork_detected();
+        status.on_recovery_timed_out();
+        status.on_caught_up();
+        status.on_unit_verified(Duration::from_millis(250));
+        status.on_unit_seen(NodeIndex(2), 4, Instant::now());
+        status.on_unit_seen(NodeIndex(0), 3, Instant::now());
+        let metrics = status.prometheus_metrics();
+        for line in [
+            "# TYPE aleph_bft_current_round gauge",
+            "aleph_bft_current_round 3",
+            "aleph_bft_max_round_reached 4",
+            "aleph_bft_created_unit_parents 3",
+            "aleph_bft_committee_size 4",
+            "aleph_bft_creator_stalled_round 4",
+            "aleph_bft_last_finalized_round 2",
+            "aleph_bft_ordered_units_total 5",
+            "aleph_bft_unordered_units 6",
+            "aleph_bft_oldest_unordered_round 3",
+            "aleph_bft_finalization_lagging 0",
+            "aleph_bft_recovery_timed_out 1",
+            "aleph_bft_forks_detected_total 1",
+            "aleph_bft_caught_up 1",
+            "aleph_bft_verified_units_total 1",
+            "aleph_bft_verification_seconds_total 0.25",
+            "aleph_bft_wedged 0",
+            "aleph_bft_member_highest_round{member=\"0\"} 3",
+            "aleph_bft_member_highest_round{member=\"2\"} 4",
+        ] {
+            assert!(metrics.lines().any(|l| l == line), "missing {}", line);
+        }
+        assert!(!metrics.contains("member=\"1\""));
+        // Every sample follows the description of its family.
+        for line in metrics.lines().filter(|line| !line.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().expect("the line has a name");
+            assert!(metrics.contains(&format!("# TYPE {} ", name)), "{}", line);
+        }
+    }
+}
@@ -1,10 +1,12 @@
-use futures::{channel::oneshot, StreamExt};
+use futures::StreamExt;
 use std::collections::{hash_map::Entry, HashMap, VecDeque};
 
 use crate::{
     extender::ExtenderUnit,
     nodes::{NodeCount, NodeIndex, NodeMap},
     runway::{NotificationIn, NotificationOut},
+    terminator::Terminator,
+    trace::unit_event,
     units::{ControlHash, Unit, UnitCoord},
     Hasher, Receiver, Round, Sender,
 };
@@ -42,7 +44,8 @@ impl<H: Hasher> From<TerminalUnit<H>> for ExtenderUnit<H> {
 
 impl<H: Hasher> From<TerminalUnit<H>> for Unit<H> {
     fn from(u: TerminalUnit<H>) -> Unit<H> {
-        u.unit
+        let parents = u.parents.values().copied().collect();
+        u.unit.with_parents(parents)
     }
 }
 
@@ -91,9 +94,9 @@ type SyncClosure<X, Y> = Box<dyn Fn(X) -> Y + Sync + Send + 'static>;
 ///    list after hashing agrees with the control_hash in the unit.
 ///    a) If yes, then the unit u gets status WaitingParentsInDag
 ///    b) If no, then the unit gets status WrongControlHash and a notification is sent, requesting the list of parent
-///       hashes of u. At the moment when a response to this notification is received (which contains correct parent
-///       hashes of u), the parents field in the TerminalUnit object of u is updated accordingly, and the unit u
-///       gets status WaitingParentsInDag as in a)
+///    hashes of u. At the moment when a response to this notification is received (which contains correct parent
+///    hashes of u), the parents field in the TerminalUnit object of u is updated accordingly, and the unit u
+///    gets status WaitingParentsInDag as in a)
 /// 5) Now we wait for all the parents of unit u to be in Dag (in order to add it to Dag). Initially, right after u gets
 ///    gets this status, we mark all the parents that are already in the Dag and set appropriate triggers for when
 ///    the remaining parents are added to Dag.
@@ -101,7 +104,6 @@ type SyncClosure<X, Y> = Box<dyn Fn(X) -> Y + Sync + Send + 'static>;
 ///
 /// We also refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/internals.html
 /// Section 5.3 for a discussion of this component.
-
 pub(crate) struct Terminal<H: Hasher> {
     node_id: NodeIndex,
     // A channel for receiving notifications (units mainly)
@@ -332,6 +334,12 @@ impl<H: Hasher> Terminal<H> {
                     let u = self.unit_store.get_mut(&u_hash).unwrap();
                     u.status = UnitStatus::InDag;
                     trace!(target: "AlephBFT-terminal", "{:?} Adding to Dag {:?} round {:?} index {:?}.", self.node_id, u_hash, u.unit.round(), u.unit.creator());
+                    unit_event!(
+                        hash = ?u_hash,
+                        round = u.unit.round(),
+                        creator = u.unit.creator().0,
+                        "unit added to the dag"
+                    );
                     self.update_on_dag_add(&u_hash);
                 }
             }
@@ -349,7 +357,7 @@ impl<H: Hasher> Terminal<H> {
         }
     }
 
-    pub(crate) async fn run(&mut self, mut exit: oneshot::Receiver<()>) {
+    pub(crate) async fn run(&mut self, mut terminator: Terminator) {
         loop {
             futures::select! {
                 n = self.ntfct_rx.next() => {
@@ -367,7 +375,7 @@ impl<H: Hasher> Terminal<H> {
                         _ => {}
                     }
                 }
-                _ = &mut exit => {
+                _ = terminator.get_exit() => {
                     info!(target: "AlephBFT-terminal", "{:?} received exit signal", self.node_id);
                     self.exiting = true;
                 }
@@ -377,5 +385,6 @@ impl<H: Hasher> Terminal<H> {
                 break;
             }
         }
+        terminator.terminate_sync().await;
     }
 }
@@ -0,0 +1,176 @@
+use futures::{
+    channel::oneshot,
+    future::{Fuse, FusedFuture, Pending},
+};
+use log::debug;
+
+/// The connection of a [`Terminator`] to one of its offspring.
+struct Offspring {
+    name: &'static str,
+    exit: oneshot::Sender<()>,
+    done: oneshot::Receiver<()>,
+}
+
+/// Tells a task when to stop, and lets it tell its parent that it stopped. The terminators of the
+/// tasks form a tree, so that every task stops the tasks it started, one by one and in the order
+/// they were started, waiting for each to acknowledge, before acknowledging itself.
+pub(crate) struct Terminator {
+    name: &'static str,
+    exit: oneshot::Receiver<()>,
+    done: Option<oneshot::Sender<()>>,
+    offspring: Vec<Offspring>,
+}
+
+impl Terminator {
+    /// The terminator of a task with no parent, stopping upon `exit`.
+    pub(crate) fn create_root(exit: oneshot::Receiver<()>, name: &'static str) -> Self {
+        Terminator {
+            name,
+            exit,
+            done: None,
+            offspring: Vec::new(),
+        }
+    }
+
+    /// The terminator for a task started by ours.
+    pub(crate) fn add_offspring_connection(&mut self, name: &'static str) -> Self {
+        let (exit_tx, exit) = oneshot::channel();
+        let (done, done_rx) = oneshot::channel();
+        self.offspring.push(Offspring {
+            name,
+            exit: exit_tx,
+            done: done_rx,
+        });
+        Terminator {
+            name,
+            exit,
+            done: Some(done),
+            offspring: Vec::new(),
+        }
+    }
+
+    /// Resolves once the task should stop. Also resolves if the parent is gone.
+    pub(crate) fn get_exit(&mut self) -> &mut oneshot::Receiver<()> {
+        &mut self.exit
+    }
+
+    /// Whether the offspring `name` already stopped on its own. False once we told it to stop.
+    pub(crate) fn has_stopped(&self, name: &'static str) -> bool {
+        self.offspring
+            .iter()
+            .any(|o| o.name == name && o.exit.is_canceled())
+    }
+
+    /// Tells the offspring `name` to stop, and waits until it acknowledges, polling `task` in the
+    /// meantime, so that offspring running within our task can make progress. Returns its output,
+    /// unless it already finished.
+    pub(crate) async fn terminate_offspring<T: FusedFuture + Unpin>(
+        &mut self,
+        name: &'static str,
+        task: T,
+    ) -> Option<T::Output> {
+        let position = self.offspring.iter().position(|o| o.name == name);
+        let offspring = match position {
+            Some(position) => self.offspring.remove(position),
+            None => {
+                debug!(target: "AlephBFT-terminator", "{} has no offspring {}.", self.name, name);
+                return None;
+            }
+        };
+        self.stop(offspring, task).await
+    }
+
+    async fn stop<T: FusedFuture + Unpin>(
+        &self,
+        offspring: Offspring,
+        task: T,
+    ) -> Option<T::Output> {
+        let Offspring { name, exit, done } = offspring;
+        if exit.send(()).is_err() {
+            debug!(target: "AlephBFT-terminator", "{} already stopped.", name);
+        }
+        let result = if task.is_terminated() {
+            None
+        } else {
+            Some(task.await)
+        };
+        if done.await.is_err() {
+            debug!(target: "AlephBFT-terminator", "{} stopped without acknowledging to {}.", name, self.name);
+        }
+        result
+    }
+
+    /// Stops the remaining offspring running in separate tasks, in the order they were started,
+    /// and then acknowledges to the parent.
+    pub(crate) async fn terminate_sync(mut self) {
+        for offspring in std::mem::take(&mut self.offspring) {
+            self.stop(offspring, Fuse::<Pending<()>>::terminated())
+                .await;
+        }
+        if let Some(done) = self.done.take() {
+            if done.send(()).is_err() {
+                debug!(target: "AlephBFT-terminator", "The parent of {} already stopped.", self.name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Terminator;
+    use futures::{channel::oneshot, FutureExt};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    async fn task(mut terminator: Terminator, name: &'static str, log: Arc<Mutex<Vec<&str>>>) {
+        let _ = terminator.get_exit().await;
+        log.lock().push(name);
+        terminator.terminate_sync().await;
+    }
+
+    #[tokio::test]
+    async fn stops_offspring_in_order() {
+        let (exit_tx, exit) = oneshot::channel();
+        let mut root = Terminator::create_root(exit, "root");
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for name in ["first", "second", "third"] {
+            let terminator = root.add_offspring_connection(name);
+            handles.push(tokio::spawn(task(terminator, name, log.clone())));
+        }
+        let _ = exit_tx.send(());
+        let _ = root.get_exit().await;
+        let second = handles.remove(1).fuse();
+        let result = root.terminate_offspring("second", second).await;
+        assert!(matches!(result, Some(Ok(()))));
+        root.terminate_sync().await;
+        assert_eq!(*log.lock(), vec!["second", "first", "third"]);
+    }
+
+    #[tokio::test]
+    async fn knows_offspring_stopped_on_its_own() {
+        let (_exit_tx, exit) = oneshot::channel();
+        let mut root = Terminator::create_root(exit, "root");
+        let terminator = root.add_offspring_connection("offspring");
+        assert!(!root.has_stopped("offspring"));
+        terminator.terminate_sync().await;
+        assert!(root.has_stopped("offspring"));
+        root.terminate_sync().await;
+    }
+
+    #[tokio::test]
+    async fn waits_for_offspring_of_offspring() {
+        let (_exit_tx, exit) = oneshot::channel();
+        let mut root = Terminator::create_root(exit, "root");
+        let mut child = root.add_offspring_connection("child");
+        let grandchild = child.add_offspring_connection("grandchild");
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let grandchild = tokio::spawn(task(grandchild, "grandchild", log.clone()));
+        let child = tokio::spawn(task(child, "child", log.clone()));
+        root.terminate_sync().await;
+        // The child stops its offspring only after it was told to stop itself.
+        assert_eq!(*log.lock(), vec!["child", "grandchild"]);
+        assert!(grandchild.await.is_ok());
+        assert!(child.await.is_ok());
+    }
+}
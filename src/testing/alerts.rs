@@ -1,12 +1,15 @@
 use crate::{
     alerts::{run, Alert, AlertConfig, AlertMessage, ForkProof, ForkingNotification},
+    default_config,
     network::Recipient,
+    nodes::NodeMap,
     nodes::{NodeCount, NodeIndex},
     rmc::Message as RmcMessage,
     signed::KeyBox as _,
+    terminator::Terminator,
     testing::mock::{Data, Hasher64, KeyBox, PartialMultisignature, Signature},
     units::{ControlHash, FullUnit, PreUnit, UnitCoord},
-    Index, Indexed, NodeMap, Round, Signable, Signed, UncheckedSigned,
+    Index, Indexed, RealClock, Round, SessionStatus, Signable, Signed, UncheckedSigned,
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -17,6 +20,7 @@ use log::trace;
 use std::{
     collections::{HashMap, HashSet},
     hash::Hash,
+    sync::Arc,
     time::Duration,
 };
 
@@ -71,6 +75,7 @@ impl Segment {
 struct TestCase {
     keychains: Vec<KeyBox>,
     segments: Vec<Segment>,
+    status: SessionStatus,
 }
 
 impl TestCase {
@@ -82,6 +87,7 @@ impl TestCase {
         Self {
             keychains,
             segments: vec![Segment::new()],
+            status: SessionStatus::new(),
         }
     }
 
@@ -229,8 +235,16 @@ impl TestCase {
             AlertConfig {
                 n_members,
                 session_id: 0,
+                // Some of the tests query the same alert repeatedly.
+                resend_interval: Duration::ZERO,
+                rmc_retry_delay: default_config(n_members, 0.into(), 0)
+                    .delay_config
+                    .rmc_retry_delay,
+                alert_timeout: None,
+                clock: Arc::new(RealClock),
+                status: self.status.clone(),
             },
-            exit,
+            Terminator::create_root(exit, "runway/alerter"),
         ));
 
         use Input::*;
@@ -289,7 +303,11 @@ async fn distributes_alert_from_units() {
     test_case
         .incoming_alert(alert.clone())
         .outgoing_message(AlertMessage::ForkAlert(signed_alert), Recipient::Everyone);
+    let status = test_case.status.clone();
     test_case.run(own_index).await;
+    assert_eq!(status.n_forks_detected(), 1);
+    assert_eq!(status.n_alerts_originated(), 1);
+    assert_eq!(status.n_alerts_forwarded(), 0);
 }
 
 #[tokio::test]
@@ -345,7 +363,12 @@ async fn notifies_about_finished_alert() {
         ));
     }
     test_case.outgoing_notification(ForkingNotification::Units(Vec::new()));
+    let status = test_case.status.clone();
     test_case.run(own_index).await;
+    assert_eq!(status.n_forks_detected(), 1);
+    assert_eq!(status.n_alerts_originated(), 0);
+    assert_eq!(status.n_alerts_forwarded(), 1);
+    assert_eq!(status.n_multisignatures_collected(), 1);
 }
 
 #[tokio::test]
@@ -522,3 +545,142 @@ async fn notifies_only_about_multisigned_alert() {
         .unexpected_notification(ForkingNotification::Units(Vec::new()));
     test_case.run(own_index).await;
 }
+
+#[tokio::test]
+async fn limits_resending_alerts_under_a_flood() {
+    let n_members = NodeCount(7);
+    let own_index = NodeIndex(0);
+    let querier = NodeIndex(1);
+    let forker = NodeIndex(6);
+    let resend_interval = Duration::from_millis(200);
+    let test_case = TestCase::new(n_members);
+    let alert = test_case.alert(own_index, test_case.fork_proof(forker, 0).await);
+    let alert_hash = Signable::hash(&alert);
+    let unknown_alert = test_case.alert(querier, test_case.fork_proof(forker, 1).await);
+    let unknown_alert_hash = Signable::hash(&unknown_alert);
+    let signed_unknown_alert_hash = test_case
+        .indexed_unchecked_signed(unknown_alert_hash, querier)
+        .await;
+
+    let (messages_for_network, mut messages_from_alerter) = mpsc::unbounded();
+    let (messages_for_alerter, messages_from_network) = mpsc::unbounded();
+    let (notifications_for_units, _notifications_from_alerter) = mpsc::unbounded();
+    let (alerts_for_alerter, alerts_from_units) = mpsc::unbounded();
+    let (exit_alerter, exit) = oneshot::channel();
+    tokio::spawn(run(
+        test_case.keychain(own_index).clone(),
+        messages_for_network,
+        messages_from_network,
+        notifications_for_units,
+        alerts_from_units,
+        AlertConfig {
+            n_members,
+            session_id: 0,
+            resend_interval,
+            rmc_retry_delay: default_config(n_members, 0.into(), 0)
+                .delay_config
+                .rmc_retry_delay,
+            alert_timeout: None,
+            clock: Arc::new(RealClock),
+            status: SessionStatus::new(),
+        },
+        Terminator::create_root(exit, "runway/alerter"),
+    ));
+    alerts_for_alerter
+        .unbounded_send(alert)
+        .expect("the alert channel works");
+
+    let flood = |messages_for_alerter: &mpsc::UnboundedSender<TestMessage>| {
+        for _ in 0..100 {
+            let request = AlertMessage::AlertRequest(querier, alert_hash);
+            let rmc_message = AlertMessage::RmcMessage(
+                querier,
+                RmcMessage::SignedHash(signed_unknown_alert_hash.clone()),
+            );
+            for message in [request, rmc_message] {
+                messages_for_alerter
+                    .unbounded_send(message)
+                    .expect("the message channel works");
+            }
+        }
+    };
+    let count_replies = |messages_from_alerter: &mut mpsc::UnboundedReceiver<_>| {
+        let (mut alerts, mut requests) = (0, 0);
+        while let Ok((message, recipient)) = messages_from_alerter.try_recv() {
+            match (message, recipient) {
+                (AlertMessage::ForkAlert(_), Recipient::Node(node)) if node == querier => {
+                    alerts += 1
+                }
+                (AlertMessage::AlertRequest(_, hash), Recipient::Node(node))
+                    if node == querier && hash == unknown_alert_hash =>
+                {
+                    requests += 1
+                }
+                _ => {}
+            }
+        }
+        (alerts, requests)
+    };
+
+    flood(&messages_for_alerter);
+    Delay::new(resend_interval / 2).await;
+    assert_eq!(count_replies(&mut messages_from_alerter), (1, 1));
+    // Once the interval passes, we answer again.
+    Delay::new(resend_interval).await;
+    flood(&messages_for_alerter);
+    Delay::new(resend_interval / 2).await;
+    assert_eq!(count_replies(&mut messages_from_alerter), (1, 1));
+
+    exit_alerter
+        .send(())
+        .expect("exit channel shouldn't be closed");
+}
+
+#[tokio::test]
+async fn sends_own_alerts_when_exiting() {
+    let n_members = NodeCount(7);
+    let own_index = NodeIndex(0);
+    let forker = NodeIndex(6);
+    let test_case = TestCase::new(n_members);
+    let alert = test_case.alert(own_index, test_case.fork_proof(forker, 0).await);
+    let signed_alert = test_case.unchecked_signed(alert.clone(), own_index).await;
+
+    let (messages_for_network, mut messages_from_alerter) = mpsc::unbounded();
+    let (_messages_for_alerter, messages_from_network) = mpsc::unbounded();
+    let (notifications_for_units, _notifications_from_alerter) = mpsc::unbounded();
+    let (alerts_for_alerter, alerts_from_units) = mpsc::unbounded();
+    let (exit_alerter, exit) = oneshot::channel();
+    // The exit signal is already there when the alerter learns about the alert.
+    alerts_for_alerter
+        .unbounded_send(alert)
+        .expect("the alert channel works");
+    exit_alerter
+        .send(())
+        .expect("exit channel shouldn't be closed");
+    run(
+        test_case.keychain(own_index).clone(),
+        messages_for_network,
+        messages_from_network,
+        notifications_for_units,
+        alerts_from_units,
+        AlertConfig {
+            n_members,
+            session_id: 0,
+            resend_interval: Duration::from_secs(1),
+            rmc_retry_delay: default_config(n_members, 0.into(), 0)
+                .delay_config
+                .rmc_retry_delay,
+            alert_timeout: None,
+            clock: Arc::new(RealClock),
+            status: SessionStatus::new(),
+        },
+        Terminator::create_root(exit, "runway/alerter"),
+    )
+    .await;
+
+    let mut sent = Vec::new();
+    while let Ok(message) = messages_from_alerter.try_recv() {
+        sent.push(message);
+    }
+    assert!(sent.contains(&(AlertMessage::ForkAlert(signed_alert), Recipient::Everyone)));
+}
@@ -0,0 +1,558 @@
+use async_trait::async_trait;
+use futures::{
+    channel::{mpsc, oneshot},
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashSet,
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    replay_backup, run_session,
+    testing::mock::{
+        configure_network, gen_config, init_log, run_honest_member,
+        spawn_honest_member_with_config, AlertHook, Data, DataIO, Hasher64, KeyBox,
+        PartialMultisignature, Signature, Spawner,
+    },
+    units::UnitCoord,
+    BackupSink, BackupSource, Config, DataIO as DataIOT, Index, KeyBox as KeyBoxT, MemoryBackup,
+    MultiKeychain, NodeCount, NodeIndex, OrderedBatch, Round, SessionOutcome, SpawnHandle,
+};
+
+async fn run_members<B: BackupSink + BackupSource + Clone>(
+    backups: &[B],
+    n_batches: usize,
+    compaction_threshold: usize,
+    alert_hook: AlertHook,
+) -> Vec<Vec<OrderedBatch<Data>>> {
+    run_members_with_config(backups, n_batches, alert_hook, |config| {
+        config.backup_compaction_threshold = compaction_threshold;
+    })
+    .await
+}
+
+async fn run_members_with_config<B: BackupSink + BackupSource + Clone>(
+    backups: &[B],
+    n_batches: usize,
+    alert_hook: AlertHook,
+    configure: impl Fn(&mut Config),
+) -> Vec<Vec<OrderedBatch<Data>>> {
+    let n_members = NodeCount(backups.len());
+    let spawner = Spawner::new();
+    let (mut net_hub, networks) = configure_network(n_members, 1.0);
+    net_hub.add_hook(alert_hook);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    let mut handles = Vec::new();
+    for (network, backup) in networks.into_iter().zip(backups) {
+        let mut config = gen_config(network.index(), n_members);
+        config.backup_sink = Arc::new(backup.clone());
+        config.backup_source = Arc::new(backup.clone());
+        configure(&mut config);
+        let (batch_rx, exit_tx, handle) =
+            spawn_honest_member_with_config(spawner.clone(), config, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+        handles.push(handle);
+    }
+
+    let mut batches = Vec::new();
+    for rx in batch_rxs.iter_mut() {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..n_batches {
+            batches_per_ix.push(rx.next().await.expect("The member should not exit."));
+        }
+        batches.push(batches_per_ix);
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+    batches
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_members_recover_from_backup() {
+    init_log();
+    let n_batches = 5;
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    let alert_hook = AlertHook::new();
+
+    let before = run_members(&backups, n_batches, 0, alert_hook.clone()).await;
+    assert!(backups.iter().all(|backup| !backup.contents().is_empty()));
+    let after = run_members(&backups, 2 * n_batches, 0, alert_hook.clone()).await;
+
+    for (before, after) in before.iter().zip(after.iter()) {
+        assert_eq!(before[..], after[..n_batches]);
+    }
+    for batches in after.iter().skip(1) {
+        assert_eq!(batches, &after[0]);
+    }
+    for sender in NodeCount(4).all_indices() {
+        for recipient in NodeCount(4).all_indices() {
+            assert_eq!(alert_hook.count(sender, recipient), 0);
+        }
+    }
+}
+
+fn head_round(batch: &OrderedBatch<Data>) -> Round {
+    batch
+        .iter()
+        .map(|data| data.coord.round())
+        .max()
+        .expect("batches are not empty")
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_members_skip_externally_finalized_batches() {
+    init_log();
+    let n_batches = 10;
+    let n_finalized = 4;
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+
+    let before = run_members(&backups, n_batches, 0, AlertHook::new()).await;
+    let finalized_round = head_round(&before[0][n_finalized - 1]);
+    let after = run_members_with_config(&backups, n_batches, AlertHook::new(), |config| {
+        config.externally_finalized_round = Some(finalized_round);
+    })
+    .await;
+
+    for (before, after) in before.iter().zip(after.iter()) {
+        assert_eq!(before[n_finalized..], after[..n_batches - n_finalized]);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn externally_finalized_round_is_not_trusted_without_recovered_units() {
+    init_log();
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+
+    let batches = run_members_with_config(&backups, 1, AlertHook::new(), |config| {
+        config.externally_finalized_round = Some(5);
+    })
+    .await;
+
+    for batches in batches {
+        assert_eq!(head_round(&batches[0]), 0);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_members_recover_from_compacted_backup() {
+    init_log();
+    let n_batches = 40;
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    let uncompacted: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    let alert_hook = AlertHook::new();
+
+    let before = run_members(&backups, n_batches, 1, alert_hook.clone()).await;
+    run_members(&uncompacted, n_batches, 0, AlertHook::new()).await;
+    for (backup, uncompacted) in backups.iter().zip(uncompacted.iter()) {
+        assert!(backup.contents().len() < uncompacted.contents().len());
+    }
+
+    // Every member removed the old units of the others, so the Dag is recovered only thanks to
+    // the members keeping their own units.
+    let after = run_members(&backups, n_batches + 5, 1, alert_hook.clone()).await;
+    for (before, after) in before.iter().zip(after.iter()) {
+        assert_eq!(before[..], after[..n_batches]);
+    }
+    for sender in NodeCount(4).all_indices() {
+        for recipient in NodeCount(4).all_indices() {
+            assert_eq!(alert_hook.count(sender, recipient), 0);
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replayed_backups_reproduce_the_order() {
+    init_log();
+    let n_batches = 5;
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    let batches = run_members(&backups, n_batches, 0, AlertHook::new()).await;
+
+    for (backup, batches) in backups.iter().zip(batches) {
+        let replayed: Vec<_> = replay_backup::<Hasher64, Data, Signature>(backup.clone())
+            .map(|unit| UnitCoord::new(unit.round(), unit.creator()))
+            .collect()
+            .await;
+        // The units of the mock carry data with their own coords.
+        let ordered: Vec<_> = batches
+            .into_iter()
+            .flatten()
+            .map(|data| data.coord)
+            .collect();
+        // The member might have received more units before exiting than it finalized.
+        assert!(replayed.len() >= ordered.len());
+        assert_eq!(replayed[..ordered.len()], ordered[..]);
+    }
+}
+
+#[cfg(feature = "compression")]
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_members_recover_from_compressed_backup() {
+    use crate::CompressedBackup;
+
+    init_log();
+    let n_batches = 10;
+    let backups: Vec<_> = (0..4)
+        .map(|_| CompressedBackup::new(MemoryBackup::new()))
+        .collect();
+    let uncompressed: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    let alert_hook = AlertHook::new();
+
+    let before = run_members(&backups, n_batches, 0, alert_hook.clone()).await;
+    run_members(&uncompressed, n_batches, 0, AlertHook::new()).await;
+    let compressed_len: usize = backups
+        .iter()
+        .map(|backup| backup.inner().contents().len())
+        .sum();
+    let uncompressed_len: usize = uncompressed
+        .iter()
+        .map(|backup| backup.contents().len())
+        .sum();
+    assert!(compressed_len < uncompressed_len);
+
+    let after = run_members(&backups, 2 * n_batches, 0, alert_hook.clone()).await;
+    for (before, after) in before.iter().zip(after.iter()) {
+        assert_eq!(before[..], after[..n_batches]);
+    }
+    for sender in NodeCount(4).all_indices() {
+        for recipient in NodeCount(4).all_indices() {
+            assert_eq!(alert_hook.count(sender, recipient), 0);
+        }
+    }
+}
+
+// Marks the data with the number of the run, so that the data of units created after a restart
+// differs from the data created before it.
+struct RunDataIO {
+    data_io: DataIO,
+    run: u32,
+}
+
+impl DataIOT<Data> for RunDataIO {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        Data::new(self.data_io.get_data().coord, self.run)
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.data_io.send_ordered_batch(data)
+    }
+}
+
+// Runs the members until each of them passes on at least `n_units` units, and returns all the
+// data they pass on, also the data ordered while exiting.
+async fn run_members_skipping_delivered(
+    backups: &[MemoryBackup],
+    run: u32,
+    n_units: usize,
+) -> Vec<Vec<Data>> {
+    let n_members = NodeCount(backups.len());
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    let mut handles = Vec::new();
+    for (network, backup) in networks.into_iter().zip(backups) {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.backup_sink = Arc::new(backup.clone());
+        config.backup_source = Arc::new(backup.clone());
+        config.skip_delivered_after_recovery = true;
+        // Every unit is passed on separately, so that we exit in the middle of a round.
+        config.max_batch_size = Some(1);
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let data_io = RunDataIO { data_io, run };
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let keybox = KeyBox::new(n_members, ix);
+        let member_spawner = spawner.clone();
+        let handle = spawner.spawn_essential("member", async move {
+            let _ =
+                run_honest_member(config, network, data_io, keybox, member_spawner, exit_rx).await;
+        });
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+        handles.push(handle);
+    }
+
+    let mut ordered = vec![Vec::new(); backups.len()];
+    for (rx, ordered) in batch_rxs.iter_mut().zip(ordered.iter_mut()) {
+        while ordered.len() < n_units {
+            ordered.extend(rx.next().await.expect("The member should not exit."));
+        }
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+    for (rx, ordered) in batch_rxs.iter_mut().zip(ordered.iter_mut()) {
+        while let Some(batch) = rx.next().await {
+            ordered.extend(batch);
+        }
+    }
+    ordered
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restarted_members_skip_delivered_units() {
+    init_log();
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+
+    let before = run_members_skipping_delivered(&backups, 0, 13).await;
+    let after = run_members_skipping_delivered(&backups, 1, 10).await;
+
+    let ordered: Vec<Vec<Data>> = before
+        .into_iter()
+        .zip(after)
+        .map(|(before, after)| before.into_iter().chain(after).collect())
+        .collect();
+    // Nothing passed on before the restart is passed on again.
+    for ordered in &ordered {
+        let distinct: HashSet<_> = ordered.iter().collect();
+        assert_eq!(distinct.len(), ordered.len());
+    }
+    // Nor was anything missed.
+    let shortest = ordered
+        .iter()
+        .map(Vec::len)
+        .min()
+        .expect("there are members");
+    for other in ordered.iter().skip(1) {
+        assert_eq!(other[..shortest], ordered[0][..shortest]);
+    }
+}
+
+// Records the messages it verifies signatures of, which for units are their hashes.
+#[derive(Clone)]
+struct RecordingKeyBox {
+    keybox: KeyBox,
+    verified: Arc<Mutex<HashSet<Vec<u8>>>>,
+}
+
+impl Index for RecordingKeyBox {
+    fn index(&self) -> NodeIndex {
+        self.keybox.index()
+    }
+}
+
+#[async_trait]
+impl KeyBoxT for RecordingKeyBox {
+    type Signature = Signature;
+
+    fn node_count(&self) -> NodeCount {
+        self.keybox.node_count()
+    }
+
+    async fn sign(&self, msg: &[u8]) -> Signature {
+        self.keybox.sign(msg).await
+    }
+
+    fn verify(&self, msg: &[u8], sgn: &Signature, index: NodeIndex) -> bool {
+        self.verified.lock().insert(msg.to_vec());
+        self.keybox.verify(msg, sgn, index)
+    }
+}
+
+impl MultiKeychain for RecordingKeyBox {
+    type PartialMultisignature = PartialMultisignature;
+
+    fn from_signature(&self, signature: &Signature, index: NodeIndex) -> PartialMultisignature {
+        self.keybox.from_signature(signature, index)
+    }
+
+    fn is_complete(&self, msg: &[u8], partial: &PartialMultisignature) -> bool {
+        self.keybox.is_complete(msg, partial)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn known_units_are_not_verified_again() {
+    init_log();
+    let n_batches = 5;
+    let n_members = NodeCount(4);
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    run_members(&backups, n_batches, 0, AlertHook::new()).await;
+    let known: HashSet<_> = replay_backup::<Hasher64, Data, Signature>(backups[0].clone())
+        .map(|unit| unit.hash().to_vec())
+        .collect()
+        .await;
+
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let verified = Arc::new(Mutex::new(HashSet::new()));
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    let mut handles = Vec::new();
+    for (network, backup) in networks.into_iter().zip(&backups) {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.backup_sink = Arc::new(backup.clone());
+        config.backup_source = Arc::new(backup.clone());
+        let mut keybox = RecordingKeyBox {
+            keybox: KeyBox::new(n_members, ix),
+            verified: Arc::new(Mutex::new(HashSet::new())),
+        };
+        if ix == NodeIndex(0) {
+            config.known_unit_hashes = known.iter().cloned().collect();
+            keybox.verified = verified.clone();
+        }
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let member_spawner = spawner.clone();
+        let handle = spawner.spawn_essential("member", async move {
+            let (_, creator_control) = mpsc::unbounded();
+            let _ = run_session(
+                config,
+                network,
+                data_io,
+                keybox,
+                member_spawner,
+                creator_control,
+                exit_rx,
+            )
+            .await;
+        });
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+        handles.push(handle);
+    }
+
+    for rx in batch_rxs.iter_mut() {
+        for _ in 0..2 * n_batches {
+            rx.next().await.expect("The member should not exit.");
+        }
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+
+    let verified = verified.lock();
+    assert!(verified.is_disjoint(&known));
+    // Nothing is verified at all when only measuring.
+    if !cfg!(feature = "trusted") {
+        assert!(!verified.is_empty());
+    }
+}
+
+// Passes the backup on to be checked, but never finishes reading it again to recover the units.
+#[derive(Clone)]
+struct StallingBackup {
+    backup: MemoryBackup,
+    loads: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl BackupSource for StallingBackup {
+    async fn load(&self) -> io::Result<Vec<u8>> {
+        self.backup.load().await
+    }
+
+    fn load_chunks(&self) -> BoxStream<'_, io::Result<Vec<u8>>> {
+        match self.loads.fetch_add(1, Ordering::SeqCst) {
+            0 => self.backup.load_chunks(),
+            _ => stream::pending().boxed(),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn members_exit_while_recovering() {
+    init_log();
+    let n_members = NodeCount(4);
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    run_members(&backups, 3, 0, AlertHook::new()).await;
+
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let network = networks.remove(0);
+    let ix = network.index();
+    let backup = StallingBackup {
+        backup: backups[0].clone(),
+        loads: Arc::new(AtomicUsize::new(0)),
+    };
+    let mut config = gen_config(ix, n_members);
+    config.backup_sink = Arc::new(backups[0].clone());
+    config.backup_source = Arc::new(backup.clone());
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let keybox = KeyBox::new(n_members, ix);
+    let member = tokio::spawn(run_honest_member(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawner.clone(),
+        exit_rx,
+    ));
+
+    while backup.loads.load(Ordering::SeqCst) < 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    exit_tx.send(()).expect("the member is running");
+    let outcome = tokio::time::timeout(Duration::from_secs(10), member)
+        .await
+        .expect("the member should exit in the middle of recovering")
+        .expect("the member should not panic");
+    assert!(matches!(outcome, Ok(SessionOutcome::Exited)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn members_proceed_once_recovery_times_out() {
+    init_log();
+    let n_batches = 5;
+    let backups: Vec<_> = (0..4).map(|_| MemoryBackup::new()).collect();
+    let before = run_members(&backups, n_batches, 0, AlertHook::new()).await;
+
+    let stalling = StallingBackup {
+        backup: backups[0].clone(),
+        loads: Arc::new(AtomicUsize::new(0)),
+    };
+    let alert_hook = AlertHook::new();
+    let after = run_members_with_config(&backups, 2 * n_batches, alert_hook.clone(), |config| {
+        if config.node_ix == NodeIndex(0) {
+            config.backup_source = Arc::new(stalling.clone());
+            config.recovery_timeout = Some(Duration::from_millis(100));
+        }
+    })
+    .await;
+
+    // Without its own units, the member learns the Dag back from the others.
+    assert!(stalling.loads.load(Ordering::SeqCst) >= 2);
+    for (before, after) in before.iter().zip(after.iter()) {
+        assert_eq!(before[..], after[..n_batches]);
+    }
+    for batches in after.iter().skip(1) {
+        assert_eq!(batches, &after[0]);
+    }
+    // It creates units only above the rounds of its units in the backup, so it does not fork.
+    for sender in NodeCount(4).all_indices() {
+        for recipient in NodeCount(4).all_indices() {
+            assert_eq!(alert_hook.count(sender, recipient), 0);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    StreamExt,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    run_session,
+    testing::mock::{gen_config, init_log, Data, DataIO, Hasher64, KeyBox, Spawner},
+    BlsKeychain, BlsSecretKey, MultiKeychain, Network, NetworkData, NodeCount, NodeIndex,
+    OrderedBatch, Recipient, SpawnHandle,
+};
+
+type Message<MK> = NetworkData<
+    Hasher64,
+    Data,
+    <MK as crate::KeyBox>::Signature,
+    <MK as MultiKeychain>::PartialMultisignature,
+>;
+
+// A reliable network connecting the members directly, for any kind of signatures.
+struct ChannelNetwork<MK: MultiKeychain> {
+    index: NodeIndex,
+    peers: Vec<UnboundedSender<Message<MK>>>,
+    rx: UnboundedReceiver<Message<MK>>,
+}
+
+fn connect<MK: MultiKeychain>(n_members: NodeCount) -> Vec<ChannelNetwork<MK>> {
+    let (txs, rxs): (Vec<_>, Vec<_>) = n_members.all_indices().map(|_| unbounded()).unzip();
+    rxs.into_iter()
+        .enumerate()
+        .map(|(i, rx)| ChannelNetwork {
+            index: i.into(),
+            peers: txs.clone(),
+            rx,
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl<MK: MultiKeychain> Network<Hasher64, Data, MK::Signature, MK::PartialMultisignature>
+    for ChannelNetwork<MK>
+{
+    fn send(&self, data: Message<MK>, recipient: Recipient) {
+        match recipient {
+            Recipient::Node(node) => {
+                let _ = self.peers[node.0].unbounded_send(data);
+            }
+            Recipient::Everyone => {
+                for (i, peer) in self.peers.iter().enumerate() {
+                    if NodeIndex(i) != self.index {
+                        let _ = peer.unbounded_send(data.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<Message<MK>> {
+        self.rx.next().await
+    }
+}
+
+async fn run_members<MK: MultiKeychain>(
+    keychains: Vec<MK>,
+    n_batches: usize,
+) -> Vec<Vec<OrderedBatch<Data>>> {
+    let n_members = NodeCount(keychains.len());
+    let spawner = Spawner::new();
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    let mut handles = Vec::new();
+    for (network, keychain) in connect::<MK>(n_members).into_iter().zip(keychains) {
+        let config = gen_config(network.index, n_members);
+        let (data_io, batch_rx) = DataIO::new(network.index);
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (_, creator_control) = unbounded();
+        let session = run_session(
+            config,
+            network,
+            data_io,
+            keychain,
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        let handle = spawner.spawn_essential("member", async move {
+            let _ = session.await;
+        });
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+        handles.push(handle);
+    }
+
+    let mut batches = Vec::new();
+    for rx in batch_rxs.iter_mut() {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..n_batches {
+            batches_per_ix.push(rx.next().await.expect("The member should not exit."));
+        }
+        batches.push(batches_per_ix);
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+    batches
+}
+
+fn bls_keychains(n_members: NodeCount) -> Vec<BlsKeychain> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let secret_keys: Vec<_> = n_members
+        .all_indices()
+        .map(|_| BlsSecretKey::generate(&mut rng))
+        .collect();
+    let public_keys: Vec<_> = secret_keys.iter().map(BlsSecretKey::public_key).collect();
+    secret_keys
+        .into_iter()
+        .enumerate()
+        .map(|(i, secret_key)| BlsKeychain::new(i.into(), secret_key, public_keys.clone()))
+        .collect()
+}
+
+fn assert_finalized(batches: &[Vec<OrderedBatch<Data>>], n_members: NodeCount) {
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(batches_per_ix, &batches[0]);
+    }
+    // Every member contributes to the first rounds, so its data eventually gets finalized.
+    for creator in n_members.all_indices() {
+        assert!(batches[0]
+            .iter()
+            .flatten()
+            .any(|data| data.coord.creator() == creator));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bls_members_finalize_like_default_ones() {
+    init_log();
+    let n_members = NodeCount(4);
+    let n_batches = 10;
+
+    let default_keychains = n_members
+        .all_indices()
+        .map(|ix| KeyBox::new(n_members, ix))
+        .collect();
+    let default_batches = run_members(default_keychains, n_batches).await;
+    let bls_batches = run_members(bls_keychains(n_members), n_batches).await;
+
+    assert_finalized(&default_batches, n_members);
+    assert_finalized(&bls_batches, n_members);
+}
@@ -1,20 +1,28 @@
 use log::{debug, error, trace};
 
-use futures::{channel::oneshot, StreamExt};
+use futures::{
+    channel::{mpsc::unbounded, oneshot},
+    StreamExt,
+};
 
-use std::collections::HashMap;
+use futures_timer::Delay;
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use crate::{
     network::NetworkDataInner::Units,
     nodes::NodeMap,
+    run_session,
     signed::Signed,
     testing::mock::{
-        configure_network, init_log, spawn_honest_member, AlertHook, Data, Hash64, Hasher64,
-        KeyBox, Network, NetworkData, Spawner,
+        configure_network, gen_config, init_log, spawn_honest_member,
+        spawn_honest_member_with_config, AlertHook, Data, DataIO, Hash64, Hasher64, KeyBox,
+        Network, NetworkData, Spawner,
     },
     units::{ControlHash, FullUnit, PreUnit, SignedUnit, UnitCoord},
-    Hasher, Network as NetworkT, NetworkData as NetworkDataT, NodeCount, NodeIndex, Recipient,
-    Round, SessionId, SpawnHandle, TaskHandle,
+    ForkEvidence, ForkHandler, ForkPolicy, Hasher, MemoryBackup, Network as NetworkT,
+    NetworkData as NetworkDataT, NodeCount, NodeIndex, Recipient, Round, SessionBuilder,
+    SessionError, SessionEvent, SessionId, SpawnHandle, TaskHandle,
 };
 
 use crate::member::UnitMessage::NewUnit;
@@ -25,6 +33,7 @@ struct MaliciousMember<'a> {
     threshold: NodeCount,
     session_id: SessionId,
     forking_round: Round,
+    n_variants: u32,
     keybox: &'a KeyBox,
     network: Network,
     unit_store: HashMap<UnitCoord, SignedUnit<'a, Hasher64, Data, KeyBox>>,
@@ -38,6 +47,7 @@ impl<'a> MaliciousMember<'a> {
         n_members: NodeCount,
         session_id: SessionId,
         forking_round: Round,
+        n_variants: u32,
     ) -> Self {
         let threshold = (n_members * 2) / 3 + NodeCount(1);
         MaliciousMember {
@@ -46,6 +56,7 @@ impl<'a> MaliciousMember<'a> {
             threshold,
             session_id,
             forking_round,
+            n_variants,
             keybox,
             network,
             unit_store: HashMap::new(),
@@ -99,13 +110,20 @@ impl<'a> MaliciousMember<'a> {
         let message1 = Self::unit_to_data(su1);
         for ix in 0..self.n_members.0 {
             let node_ix = NodeIndex(ix);
-            let _ = if ix % 2 == 0 {
+            if ix % 2 == 0 {
                 self.network
                     .send(message0.clone(), Recipient::Node(node_ix))
             } else {
                 self.network
                     .send(message1.clone(), Recipient::Node(node_ix))
-            };
+            }
+        }
+    }
+
+    fn send_all_variants(&mut self, variants: Vec<SignedUnit<'a, Hasher64, Data, KeyBox>>) {
+        for su in variants {
+            self.network
+                .send(Self::unit_to_data(su), Recipient::Everyone);
         }
     }
 
@@ -125,13 +143,17 @@ impl<'a> MaliciousMember<'a> {
                 // FORKING HAPPENS HERE!
                 debug!(target: "malicious-member", "Creating forks for round {}.", round);
                 let mut variants = Vec::new();
-                for var in 0u32..2u32 {
+                for var in 0..self.n_variants {
                     let data = Data::new(coord, var);
                     let full_unit = FullUnit::new(new_preunit.clone(), data, self.session_id);
                     let signed = Signed::sign(full_unit, self.keybox).await;
                     variants.push(signed);
                 }
-                self.send_two_variants(variants[0].clone(), variants[1].clone());
+                if self.n_variants == 2 {
+                    self.send_two_variants(variants[0].clone(), variants[1].clone());
+                } else {
+                    self.send_all_variants(variants);
+                }
             }
             return true;
         }
@@ -188,6 +210,18 @@ fn spawn_malicious_member(
     n_members: NodeCount,
     round_to_fork: Round,
     network: Network,
+) -> (oneshot::Sender<()>, TaskHandle) {
+    spawn_flooding_member(spawner, node_index, n_members, round_to_fork, 2, network)
+}
+
+// Sends all the variants to everyone, unless there are just two.
+fn spawn_flooding_member(
+    spawner: Spawner,
+    node_index: NodeIndex,
+    n_members: NodeCount,
+    round_to_fork: Round,
+    n_variants: u32,
+    network: Network,
 ) -> (oneshot::Sender<()>, TaskHandle) {
     let (exit_tx, exit_rx) = oneshot::channel();
     let member_task = async move {
@@ -200,6 +234,7 @@ fn spawn_malicious_member(
             n_members,
             session_id,
             round_to_fork,
+            n_variants,
         );
         lesniak.run_session(exit_rx).await;
     };
@@ -227,7 +262,7 @@ async fn honest_members_agree_on_batches_byzantine(
 
     for network in networks {
         let ix = network.index();
-        let (exit_tx, handle) = if !n_honest.into_range().contains(&ix) {
+        let (exit_tx, handle) = if !n_honest.contains(ix) {
             spawn_malicious_member(spawner.clone(), ix, n_members, 2, network)
         } else {
             let (batch_rx, exit_tx, handle) =
@@ -250,10 +285,10 @@ async fn honest_members_agree_on_batches_byzantine(
     }
 
     let expected_forkers = n_members - n_honest;
-    for node_ix in n_honest.into_iterator().skip(1) {
+    for node_ix in n_honest.all_indices().skip(1) {
         debug!(target: "byzantine-test", "batch {:?} received", node_ix);
         assert_eq!(batches[0], batches[node_ix.0]);
-        for recipient_id in n_honest.into_iterator().skip(1) {
+        for recipient_id in n_honest.all_indices().skip(1) {
             if node_ix != recipient_id {
                 let alerts_sent = alert_hook.count(node_ix, recipient_id);
                 assert!(
@@ -289,3 +324,330 @@ async fn small_byzantine_two_forkers() {
 async fn medium_byzantine_ten_forkers() {
     honest_members_agree_on_batches_byzantine(31.into(), 21.into(), 5, 1.0).await;
 }
+
+#[derive(Default)]
+struct ForkRecorder(Mutex<Vec<ForkEvidence>>);
+
+impl ForkHandler for ForkRecorder {
+    fn on_fork(&self, evidence: ForkEvidence) {
+        self.0.lock().push(evidence);
+    }
+}
+
+#[tokio::test]
+async fn honest_members_report_fork_evidence() {
+    init_log();
+    let n_members = NodeCount(4);
+    let forker = NodeIndex(3);
+    let forking_round = 2;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut recorders = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        if ix == forker {
+            let (exit_tx, _) =
+                spawn_malicious_member(spawner.clone(), ix, n_members, forking_round, network);
+            exits.push(exit_tx);
+            continue;
+        }
+        let recorder = Arc::new(ForkRecorder::default());
+        let mut config = gen_config(ix, n_members);
+        config.fork_handler = recorder.clone();
+        let (batch_rx, exit_tx, _) =
+            spawn_honest_member_with_config(spawner.clone(), config, network);
+        batch_rxs.push(batch_rx);
+        recorders.push(recorder);
+        exits.push(exit_tx);
+    }
+
+    // The fork happens early, so it is known to everyone long before this many batches.
+    for rx in batch_rxs.iter_mut() {
+        for _ in 0..forking_round + 3 {
+            rx.next().await.expect("The member should not exit.");
+        }
+    }
+    for recorder in recorders {
+        let evidence = recorder.0.lock();
+        assert_eq!(evidence.len(), 1);
+        let evidence = &evidence[0];
+        assert_eq!(evidence.forker, forker);
+        assert_eq!(evidence.round, forking_round);
+        let [first, second] = &evidence.units;
+        assert_ne!(first.encoded_unit, second.encoded_unit);
+        assert_ne!(
+            Hasher64::hash(&first.encoded_unit),
+            Hasher64::hash(&second.encoded_unit)
+        );
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn session_events_cover_creation_quorums_forks_and_batches() {
+    init_log();
+    let n_members = NodeCount(4);
+    let forker = NodeIndex(3);
+    let forking_round = 2;
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let exit_tx = if ix == forker {
+            spawn_malicious_member(spawner.clone(), ix, n_members, forking_round, network).0
+        } else {
+            spawn_honest_member(spawner.clone(), ix, n_members, network).1
+        };
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let recorder = Arc::new(ForkRecorder::default());
+    let mut config = gen_config(ix, n_members);
+    config.fork_handler = recorder.clone();
+    let (data_io, mut batch_rx) = DataIO::new(ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let (builder, mut events) = SessionBuilder::new(config)
+        .network(network)
+        .data_io(data_io)
+        .keychain(KeyBox::new(n_members, ix))
+        .spawn_handle(spawner.clone())
+        .exit(exit_rx)
+        .events::<Hasher64, Data>();
+    let session = tokio::spawn(builder.run());
+
+    let (mut created, mut quorums, mut forks, mut batches) = (0, 0, Vec::new(), 0);
+    while forks.is_empty() || batches < forking_round + 3 {
+        match events.next().await.expect("The session should not exit.") {
+            SessionEvent::UnitCreated(_) => created += 1,
+            SessionEvent::QuorumRound(_) => quorums += 1,
+            SessionEvent::Fork(evidence) => forks.push(evidence),
+            SessionEvent::UnitRejected(_) => {}
+            SessionEvent::Finalized(units) => {
+                batches += 1;
+                let batch = batch_rx.next().await.expect("The member should not exit.");
+                let data: Vec<_> = units.into_iter().map(|unit| unit.data).collect();
+                assert_eq!(data, batch);
+            }
+        }
+    }
+    assert!(created > 0);
+    assert!(quorums > 0);
+    assert_eq!(forks.len(), 1);
+    assert_eq!(forks[0].forker, forker);
+    assert_eq!(forks[0].round, forking_round);
+    // The fork handler of the config still learns about the fork.
+    assert_eq!(*recorder.0.lock(), forks);
+
+    let _ = exit_tx.send(());
+    session
+        .await
+        .expect("The session should not panic.")
+        .expect("The session should exit cleanly.");
+    // The stream ends together with the session.
+    while events.next().await.is_some() {}
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn honest_members_survive_a_flood_of_variants() {
+    survive_a_flood_of_variants(0).await;
+}
+
+#[tokio::test]
+async fn honest_members_survive_a_flood_of_variants_with_parallel_verification() {
+    survive_a_flood_of_variants(2).await;
+}
+
+async fn survive_a_flood_of_variants(unit_verification_workers: usize) {
+    init_log();
+    let n_members = NodeCount(4);
+    let forker = NodeIndex(3);
+    let forking_round = 2;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut recorders = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        if ix == forker {
+            let (exit_tx, _) =
+                spawn_flooding_member(spawner.clone(), ix, n_members, forking_round, 50, network);
+            exits.push(exit_tx);
+            continue;
+        }
+        let recorder = Arc::new(ForkRecorder::default());
+        let mut config = gen_config(ix, n_members);
+        config.fork_handler = recorder.clone();
+        config.unit_verification_workers = unit_verification_workers;
+        let (batch_rx, exit_tx, _) =
+            spawn_honest_member_with_config(spawner.clone(), config, network);
+        batch_rxs.push(batch_rx);
+        recorders.push(recorder);
+        exits.push(exit_tx);
+    }
+
+    let mut batches = Vec::new();
+    for rx in batch_rxs.iter_mut() {
+        let mut member_batches = Vec::new();
+        for _ in 0..forking_round + 3 {
+            member_batches.push(rx.next().await.expect("The member should not exit."));
+        }
+        batches.push(member_batches);
+    }
+    for member_batches in &batches[1..] {
+        assert_eq!(member_batches, &batches[0]);
+    }
+    for recorder in recorders {
+        let evidence = recorder.0.lock();
+        assert_eq!(evidence.len(), 1);
+        assert_eq!(evidence[0].forker, forker);
+        assert_eq!(evidence[0].round, forking_round);
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn fork_evidence_survives_a_restart() {
+    init_log();
+    let n_members = NodeCount(4);
+    let forker = NodeIndex(3);
+    let forking_round = 2;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut handles = Vec::new();
+    let mut exits = Vec::new();
+    let backups: Vec<_> = (0..forker.0).map(|_| MemoryBackup::new()).collect();
+    for network in networks {
+        let ix = network.index();
+        if ix == forker {
+            let (exit_tx, _) =
+                spawn_malicious_member(spawner.clone(), ix, n_members, forking_round, network);
+            exits.push(exit_tx);
+            continue;
+        }
+        let mut config = gen_config(ix, n_members);
+        config.backup_sink = Arc::new(backups[ix.0].clone());
+        config.backup_source = Arc::new(backups[ix.0].clone());
+        let (batch_rx, exit_tx, handle) =
+            spawn_honest_member_with_config(spawner.clone(), config, network);
+        batch_rxs.push(batch_rx);
+        handles.push(handle);
+        exits.push(exit_tx);
+    }
+    for rx in batch_rxs.iter_mut() {
+        for _ in 0..forking_round + 3 {
+            rx.next().await.expect("The member should not exit.");
+        }
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+
+    // The evidence is loaded from the backup, before the member even hears from anyone.
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let recorder = Arc::new(ForkRecorder::default());
+    let mut config = gen_config(NodeIndex(0), n_members);
+    config.fork_handler = recorder.clone();
+    config.backup_sink = Arc::new(backups[0].clone());
+    config.backup_source = Arc::new(backups[0].clone());
+    let (_, exit_tx, handle) =
+        spawn_honest_member_with_config(spawner.clone(), config, networks.remove(0));
+    for _ in 0..100 {
+        if !recorder.0.lock().is_empty() {
+            break;
+        }
+        Delay::new(Duration::from_millis(10)).await;
+    }
+    let _ = exit_tx.send(());
+    let _ = handle.await;
+    let evidence = recorder.0.lock();
+    assert_eq!(evidence.len(), 1);
+    assert_eq!(evidence[0].forker, forker);
+    assert_eq!(evidence[0].round, forking_round);
+}
+
+#[tokio::test]
+async fn honest_members_stop_on_fork_with_shutdown_policy() {
+    init_log();
+    let n_members = NodeCount(4);
+    let forker = NodeIndex(3);
+    let forking_round = 2;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut results = Vec::new();
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        if ix == forker {
+            let (exit_tx, _) =
+                spawn_malicious_member(spawner.clone(), ix, n_members, forking_round, network);
+            exits.push(exit_tx);
+            continue;
+        }
+        let mut config = gen_config(ix, n_members);
+        config.fork_policy = ForkPolicy::Shutdown;
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (result_tx, result_rx) = oneshot::channel();
+        let session_spawner = spawner.clone();
+        spawner.spawn("member", async move {
+            let result = run_session(
+                config,
+                network,
+                data_io,
+                KeyBox::new(n_members, ix),
+                session_spawner,
+                creator_control,
+                exit_rx,
+            )
+            .await;
+            let _ = result_tx.send(result);
+        });
+        results.push(result_rx);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+
+    for result in results {
+        assert_eq!(
+            result.await,
+            Ok(Err(SessionError::ForkDetected {
+                forker,
+                round: forking_round
+            }))
+        );
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
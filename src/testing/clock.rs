@@ -0,0 +1,60 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    testing::mock::{configure_network, init_log, spawn_honest_member_with_config, Spawner},
+    Clock, ConfigBuilder, MockClock, NodeCount, SpawnHandle,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consensus_runs_on_simulated_time() {
+    init_log();
+    let n_members = NodeCount(4);
+    let n_batches = 10;
+    let clock = MockClock::new();
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks {
+        // The default delays, which would take many seconds of real time.
+        let config = ConfigBuilder::new(n_members, network.index())
+            .clock(Arc::new(clock.clone()))
+            .build()
+            .expect("the parameters are valid");
+        let (batch_rx, exit_tx, _) =
+            spawn_honest_member_with_config(spawner.clone(), config, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+
+    let started = clock.now();
+    let real_started = Instant::now();
+    let mut n_received = vec![0; n_members.0];
+    while n_received.iter().any(|n| *n < n_batches) {
+        let step = clock
+            .time_to_next_wakeup()
+            .unwrap_or(Duration::from_millis(10))
+            .min(Duration::from_millis(100));
+        clock.advance(step);
+        // Let the members react to the passing time.
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        for (n, batch_rx) in n_received.iter_mut().zip(batch_rxs.iter_mut()) {
+            while batch_rx.try_recv().is_ok() {
+                *n += 1;
+            }
+        }
+    }
+    let simulated = clock.now() - started;
+    // Creating the first units alone takes 5 seconds with the default delays.
+    assert!(simulated >= Duration::from_secs(5));
+    assert!(real_started.elapsed() < simulated);
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
@@ -1,9 +1,10 @@
 use crate::{
     consensus,
     runway::{NotificationIn, NotificationOut},
+    terminator::Terminator,
     testing::mock::{complete_oneshot, gen_config, Hasher64, HonestHub, Spawner},
     units::{ControlHash, PreUnit, Unit},
-    Hasher, NodeIndex, SpawnHandle,
+    Hasher, NodeIndex, SessionStatus, SpawnHandle,
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -11,6 +12,7 @@ use futures::{
     stream::StreamExt,
 };
 use log::trace;
+use std::sync::{atomic::AtomicBool, Arc};
 
 fn init_log() {
     let _ = env_logger::builder()
@@ -32,23 +34,29 @@ async fn agree_on_first_batch() {
     let mut handles = vec![];
 
     for node_ix in 0..n_members {
-        let (tx, rx) = hub.connect(NodeIndex(node_ix));
+        let (tx, creator_tx, rx) = hub.connect(NodeIndex(node_ix));
         let conf = gen_config(NodeIndex(node_ix), n_members.into());
         let (exit_tx, exit_rx) = oneshot::channel();
         exits.push(exit_tx);
         let (batch_tx, batch_rx) = mpsc::unbounded();
         batch_rxs.push(batch_rx);
         let starting_round = complete_oneshot(0);
+        let (_, creator_control) = mpsc::unbounded();
         handles.push(spawner.spawn_essential(
             "consensus",
             consensus::run(
                 conf,
                 rx,
                 tx,
+                creator_tx,
                 batch_tx,
                 spawner.clone(),
                 starting_round,
-                exit_rx,
+                complete_oneshot(Vec::new()),
+                creator_control,
+                Arc::new(AtomicBool::new(false)),
+                SessionStatus::new(),
+                Terminator::create_root(exit_rx, "runway/consensus"),
             ),
         ));
     }
@@ -57,7 +65,8 @@ async fn agree_on_first_batch() {
 
     let mut batches = vec![];
     for mut rx in batch_rxs.drain(..) {
-        let batch = rx.next().await.unwrap();
+        let (round, batch) = rx.next().await.unwrap();
+        assert_eq!(round, 0);
         assert!(!batch.is_empty());
         batches.push(batch);
     }
@@ -75,11 +84,14 @@ async fn catches_wrong_control_hash() {
     let node_ix = 0;
     let (mut tx_in, rx_in) = mpsc::unbounded();
     let (tx_out, mut rx_out) = mpsc::unbounded();
+    // Nothing processes the units we create, so the creator just waits.
+    let (creator_tx, _creator_rx) = mpsc::channel(0);
 
     let conf = gen_config(NodeIndex(node_ix), n_nodes.into());
     let (exit_tx, exit_rx) = oneshot::channel();
     let (batch_tx, _batch_rx) = mpsc::unbounded();
     let starting_round = complete_oneshot(0);
+    let (_, creator_control) = mpsc::unbounded();
 
     let consensus_handle = spawner.spawn_essential(
         "consensus",
@@ -87,10 +99,15 @@ async fn catches_wrong_control_hash() {
             conf,
             rx_in,
             tx_out,
+            creator_tx,
             batch_tx,
             spawner.clone(),
             starting_round,
-            exit_rx,
+            complete_oneshot(Vec::new()),
+            creator_control,
+            Arc::new(AtomicBool::new(false)),
+            SessionStatus::new(),
+            Terminator::create_root(exit_rx, "runway/consensus"),
         ),
     );
     let control_hash = ControlHash::new(&(vec![None; n_nodes]).into());
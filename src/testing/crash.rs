@@ -1,9 +1,12 @@
 use futures::StreamExt;
 
 use crate::{
-    testing::mock::{configure_network, init_log, spawn_honest_member, Spawner},
-    NodeCount, SpawnHandle,
+    testing::mock::{
+        configure_network, gen_config, init_log, spawn_honest_member_with_config, Data, Spawner,
+    },
+    Config, NodeCount, NodeIndex, NodeWeights, OrderedBatch, SpawnHandle, UnitBatchingConfig,
 };
+use std::time::Duration;
 
 async fn honest_members_agree_on_batches(
     n_members: NodeCount,
@@ -11,6 +14,40 @@ async fn honest_members_agree_on_batches(
     n_batches: usize,
     network_reliability: f64,
 ) {
+    honest_members_with_verification_workers_agree_on_batches(
+        n_members,
+        n_alive,
+        n_batches,
+        network_reliability,
+        0,
+    )
+    .await
+}
+
+async fn honest_members_with_verification_workers_agree_on_batches(
+    n_members: NodeCount,
+    n_alive: NodeCount,
+    n_batches: usize,
+    network_reliability: f64,
+    verification_workers: usize,
+) {
+    honest_members_with_config_agree_on_batches(
+        n_members,
+        n_alive,
+        n_batches,
+        network_reliability,
+        |config| config.unit_verification_workers = verification_workers,
+    )
+    .await;
+}
+
+async fn honest_members_with_config_agree_on_batches(
+    n_members: NodeCount,
+    n_alive: NodeCount,
+    n_batches: usize,
+    network_reliability: f64,
+    configure: impl Fn(&mut Config),
+) -> Vec<OrderedBatch<Data>> {
     init_log();
     let spawner = Spawner::new();
     let mut exits = Vec::new();
@@ -21,9 +58,11 @@ async fn honest_members_agree_on_batches(
 
     for network in networks {
         let ix = network.index();
-        if n_alive.into_range().contains(&ix) {
+        if n_alive.contains(ix) {
+            let mut config = gen_config(ix, n_members);
+            configure(&mut config);
             let (batch_rx, exit_tx, handle) =
-                spawn_honest_member(spawner.clone(), ix, n_members, network);
+                spawn_honest_member_with_config(spawner.clone(), config, network);
             batch_rxs.push(batch_rx);
             exits.push(exit_tx);
             handles.push(handle);
@@ -40,7 +79,7 @@ async fn honest_members_agree_on_batches(
         batches.push(batches_per_ix);
     }
 
-    for node_ix in n_alive.into_iterator().skip(1) {
+    for node_ix in n_alive.all_indices().skip(1) {
         assert_eq!(batches[0], batches[node_ix.0]);
     }
     for exit in exits {
@@ -49,6 +88,7 @@ async fn honest_members_agree_on_batches(
     for handle in handles {
         let _ = handle.await;
     }
+    batches.swap_remove(0)
 }
 
 #[tokio::test]
@@ -56,6 +96,27 @@ async fn small_honest_all_alive() {
     honest_members_agree_on_batches(4.into(), 4.into(), 5, 1.0).await;
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn small_honest_all_alive_parallel_verification() {
+    honest_members_with_verification_workers_agree_on_batches(4.into(), 4.into(), 5, 1.0, 3).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn medium_honest_ten_crashes_unreliable_network_parallel_verification() {
+    honest_members_with_verification_workers_agree_on_batches(31.into(), 21.into(), 5, 0.9, 3)
+        .await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn small_honest_all_alive_verification_budget() {
+    honest_members_with_config_agree_on_batches(4.into(), 4.into(), 5, 1.0, |config| {
+        // Half of the members verify the signatures inline.
+        config.unit_verification_workers = config.node_ix.0 % 2 * 2;
+        config.verification_budget = Some(1);
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn small_honest_one_crash() {
     honest_members_agree_on_batches(4.into(), 3.into(), 5, 1.0).await;
@@ -80,3 +141,41 @@ async fn medium_honest_ten_crashes() {
 async fn medium_honest_ten_crashes_unreliable_network() {
     honest_members_agree_on_batches(31.into(), 21.into(), 5, 0.9).await;
 }
+
+#[tokio::test]
+async fn heavy_members_alone_carry_the_consensus() {
+    // The two members alive have 6 of the 8 units of weight, which is a quorum.
+    honest_members_with_config_agree_on_batches(4.into(), 2.into(), 5, 1.0, |config| {
+        config.node_weights = NodeWeights::new(vec![5, 1, 1, 1])
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn members_batching_units_agree_with_the_others() {
+    // Like during a rolling upgrade, only some of the members send batches.
+    honest_members_with_config_agree_on_batches(7.into(), 6.into(), 5, 0.9, |config| {
+        if config.node_ix.0 % 2 == 0 {
+            config.unit_batching = Some(UnitBatchingConfig {
+                window: Duration::from_millis(5),
+                max_batch_size: 4,
+            });
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn observer_follows_without_creating_units() {
+    let observer = NodeIndex(3);
+    let batches =
+        honest_members_with_config_agree_on_batches(4.into(), 4.into(), 5, 1.0, |config| {
+            config.node_weights = NodeWeights::new(vec![1, 1, 1, 0]);
+            config.observer = config.node_ix == observer;
+        })
+        .await;
+    assert!(batches
+        .iter()
+        .flatten()
+        .all(|data| data.coord.creator() != observer));
+}
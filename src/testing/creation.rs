@@ -1,23 +1,44 @@
 use crate::{
-    creation::{run, IO},
+    constant_schedule,
+    creation::{run, CreationDelay, CreatorCommand, CreatorStatus, LatencyRecorder, IO},
+    nodes::NodeMap,
     runway::NotificationOut as GenericNotificationOut,
-    testing::mock::{gen_config, Data, Hasher64},
+    terminator::Terminator,
+    testing::mock::{complete_oneshot, gen_config, Data, Hash64, Hasher64},
     units::{
-        FullUnit as GenericFullUnit, PreUnit as GenericPreUnit, Unit as GenericUnit, UnitCoord,
+        ControlHash, FullUnit as GenericFullUnit, PreUnit as GenericPreUnit, Unit as GenericUnit,
+        UnitCoord,
     },
-    NodeCount, Receiver, Round, Sender,
+    MockClock, NodeCount, NodeIndex, Receiver, Round, Sender, MAX_MAX_ROUND,
 };
 
 use futures::{
     channel::{mpsc, oneshot},
     FutureExt, StreamExt,
 };
+use futures_timer::Delay;
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 type PreUnit = GenericPreUnit<Hasher64>;
 type Unit = GenericUnit<Hasher64>;
 type FullUnit = GenericFullUnit<Hasher64, Data>;
 type NotificationOut = GenericNotificationOut<Hasher64>;
 
+// Large enough for the creators not to wait for the tests, unless a test fills it on purpose.
+const NOTIFICATION_CAPACITY: usize = 1000;
+
+fn exit_channel() -> (oneshot::Sender<()>, Terminator) {
+    let (killer, exit) = oneshot::channel();
+    (killer, Terminator::create_root(exit, "consensus/creation"))
+}
+
 fn preunit_to_unit(preunit: PreUnit) -> Unit {
     FullUnit::new(
         preunit,
@@ -31,13 +52,13 @@ fn preunit_to_unit(preunit: PreUnit) -> Unit {
 struct TestController {
     max_round_per_creator: Vec<Round>,
     parents_for_creators: Sender<Unit>,
-    units_from_creators: Receiver<NotificationOut>,
+    units_from_creators: mpsc::Receiver<NotificationOut>,
 }
 
 impl TestController {
     fn new(
         parents_for_creators: Sender<Unit>,
-        units_from_creators: Receiver<NotificationOut>,
+        units_from_creators: mpsc::Receiver<NotificationOut>,
         n_members: NodeCount,
     ) -> Self {
         TestController {
@@ -80,7 +101,8 @@ fn setup_test(
     Receiver<Unit>,
     Vec<Sender<Unit>>,
 ) {
-    let (notifications_for_controller, notifications_from_creators) = mpsc::unbounded();
+    let (notifications_for_controller, notifications_from_creators) =
+        mpsc::channel(NOTIFICATION_CAPACITY);
     let (units_for_creators, units_from_controller) = mpsc::unbounded();
 
     let test_controller =
@@ -93,19 +115,31 @@ fn setup_test(
     for node_ix in 0..n_members.0 {
         let (parents_for_creator, parents_from_controller) = mpsc::unbounded();
 
+        let (_, finalized_rounds) = mpsc::unbounded();
+        let (_, control) = mpsc::unbounded();
+        let (_, delay_schedules) = mpsc::unbounded();
         let io = IO {
             incoming_parents: parents_from_controller,
             outgoing_units: notifications_for_controller.clone(),
+            finalized_rounds,
+            metrics: None,
+            latencies: None,
+            control,
+            delay_schedules,
+            state_sink: None,
+            parents_backlog: None,
+            store_overloaded: None,
+            recovered_units: None,
         };
         let config = gen_config(node_ix.into(), n_members);
         let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
 
         units_for_creators.push(parents_for_creator);
 
-        let (killer, exit) = oneshot::channel::<()>();
+        let (killer, exit) = exit_channel();
 
         let handle =
-            tokio::spawn(async move { run(config.into(), io, starting_round, exit).await });
+            tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
         starting_round_for_consensus
             .send(0)
             .expect("Sending the starting round should work.");
@@ -290,3 +324,1511 @@ async fn late_creators_should_create_dag() {
     );
     finish(killers, handles).await;
 }
+
+// This test checks that a creator which cannot gather enough parents for a long time, and thus
+// keeps hitting the stall warning, still creates its unit as soon as the parents arrive.
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn stalled_creator_creates_unit_when_parents_arrive() {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.delay_config.stall_warning_delay = Duration::from_millis(5);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let own_unit = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+        _ => panic!("Unexpected notification from creator."),
+    };
+    assert_eq!(own_unit.round(), 0);
+    parents_for_creator
+        .unbounded_send(own_unit)
+        .expect("Creator input channel isn't closed.");
+
+    // Only our own unit is available, so the creator will report being stalled many times in the meantime.
+    Delay::new(Duration::from_millis(100)).await;
+    let mut n_stalls = 0;
+    while let Some(Some(notification)) = units_from_creator.next().now_or_never() {
+        match notification {
+            NotificationOut::CreatorStalled {
+                round,
+                parents_collected,
+                parents_needed,
+            } => {
+                assert_eq!(round, 1);
+                assert_eq!(parents_collected, NodeCount(1));
+                assert_eq!(parents_needed, NodeCount(3));
+                n_stalls += 1;
+            }
+            _ => panic!("Unexpected notification from creator."),
+        }
+    }
+    assert!(n_stalls > 1);
+
+    for creator in 1..3 {
+        let preunit = PreUnit::new(
+            NodeIndex(creator),
+            0,
+            ControlHash::new(&NodeMap::with_size(n_members)),
+        );
+        parents_for_creator
+            .unbounded_send(preunit_to_unit(preunit))
+            .expect("Creator input channel isn't closed.");
+    }
+    loop {
+        match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, parent_hashes)) => {
+                assert_eq!(preunit.round(), 1);
+                assert_eq!(parent_hashes.len(), 3);
+                break;
+            }
+            Some(NotificationOut::CreatorStalled { .. }) => {}
+            _ => panic!("Unexpected notification from creator."),
+        }
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+// Runs a single creator with backpressure enabled and measures how long it takes to create units
+// up to the given round. If `finalize` is set, every created unit's round is immediately reported
+// as finalized, otherwise finalization stalls entirely. If `overloaded` is set, the store reports
+// too many units throughout.
+async fn time_to_create_rounds(
+    last_round: Round,
+    finalize: bool,
+    overloaded: bool,
+    factor: f64,
+) -> Duration {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (finalized_rounds_tx, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: Some(Arc::new(AtomicBool::new(overloaded))),
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.delay_config.creation_backpressure_threshold = Some(2);
+    config.delay_config.creation_backpressure_factor = factor;
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let start = Instant::now();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    loop {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        let round = unit.round();
+        if finalize {
+            finalized_rounds_tx
+                .unbounded_send(round)
+                .expect("Creator finalized rounds channel isn't closed.");
+        }
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+        if round == last_round {
+            break;
+        }
+    }
+    let elapsed = start.elapsed();
+    finish(vec![killer], vec![handle]).await;
+    elapsed
+}
+
+// With a creation delay of 50ms, rounds 0-2 take 150ms in total, and each of the rounds 3-5
+// is slowed down tenfold to 500ms if finalization does not progress.
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creation_slows_down_when_finalization_stalls() {
+    assert!(time_to_create_rounds(5, false, false, 10.0).await >= Duration::from_millis(1500));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creation_keeps_pace_when_finalization_progresses() {
+    assert!(time_to_create_rounds(5, true, false, 10.0).await < Duration::from_millis(1500));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creation_slows_down_when_too_many_units_are_stored() {
+    assert!(time_to_create_rounds(5, true, true, 10.0).await >= Duration::from_millis(1500));
+}
+
+// Factors which cannot scale a delay are brought into the allowed range instead of panicking,
+// with a factor of 1 not slowing the creation down at all.
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creation_survives_invalid_backpressure_factor() {
+    for factor in [-1.0, f64::NAN] {
+        time_to_create_rounds(5, false, true, factor).await;
+    }
+    assert!(time_to_create_rounds(5, false, true, 0.0).await < Duration::from_millis(1500));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_reports_status() {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (metrics, mut statuses) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: Some(metrics),
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let own_unit = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+        _ => panic!("Unexpected notification from creator."),
+    };
+    parents_for_creator
+        .unbounded_send(own_unit)
+        .expect("Creator input channel isn't closed.");
+
+    let expected = CreatorStatus {
+        round: 1,
+        parents_by_round: vec![NodeCount(1)],
+        parents_needed: NodeCount(3),
+        highest_creatable_round: 0,
+        is_behind: false,
+    };
+    loop {
+        let status = statuses
+            .next()
+            .await
+            .expect("Metrics channel isn't closed.");
+        if status == expected {
+            break;
+        }
+        assert!(status.round <= 1);
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_records_what_it_waited_for() {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (latency_sink, mut latencies) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: Some(LatencyRecorder::new(
+            latency_sink,
+            vec![Duration::from_millis(100)],
+        )),
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    // A unit of round 0 needs no parents, so only the delay matters.
+    let own_unit = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+        _ => panic!("Unexpected notification from creator."),
+    };
+    let round_0 = latencies.next().await.expect("Latencies are recorded.");
+    assert_eq!(round_0.round, 0);
+    assert_eq!(round_0.delay_bound.count(), 1);
+    assert_eq!(round_0.parent_bound.count(), 0);
+
+    // The rest of the parents of round 1 come well after the delay.
+    parents_for_creator
+        .unbounded_send(own_unit)
+        .expect("Creator input channel isn't closed.");
+    Delay::new(Duration::from_millis(200)).await;
+    for creator in 1..3 {
+        parents_for_creator
+            .unbounded_send(preunit_to_unit(PreUnit::new(
+                NodeIndex(creator),
+                0,
+                ControlHash::new(&NodeMap::with_size(n_members)),
+            )))
+            .expect("Creator input channel isn't closed.");
+    }
+    let round_1 = latencies.next().await.expect("Latencies are recorded.");
+    assert_eq!(round_1.round, 1);
+    assert_eq!(round_1.delay_bound.count(), 1);
+    assert_eq!(round_1.parent_bound.count(), 1);
+    assert!(round_1.parent_bound.sum() >= Duration::from_millis(200));
+    let buckets: Vec<_> = round_1.parent_bound.buckets().collect();
+    assert_eq!(
+        buckets,
+        vec![(Some(Duration::from_millis(100)), 0), (None, 1)]
+    );
+    assert_eq!(round_1.creation.count(), 2);
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn paused_creator_resumes_from_the_right_round() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (control_tx, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    for round in 0..3 {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        assert_eq!(unit.round(), round);
+        control_tx
+            .unbounded_send(CreatorCommand::Pause)
+            .expect("Creator control channel isn't closed.");
+        // The parent arrives while paused and should still be taken into account.
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+        // The creation delay is 50ms, so a unit would have been created in the meantime.
+        Delay::new(Duration::from_millis(200)).await;
+        assert!(units_from_creator.next().now_or_never().is_none());
+        control_tx
+            .unbounded_send(CreatorCommand::Resume)
+            .expect("Creator control channel isn't closed.");
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_waits_for_finalization_when_too_far_ahead() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (finalized_rounds_tx, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.max_rounds_ahead = Some(3);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let mut next_round = 0;
+    for (finalized_round, last_round) in [(None, 3), (Some(2), 5)] {
+        if let Some(finalized_round) = finalized_round {
+            finalized_rounds_tx
+                .unbounded_send(finalized_round)
+                .expect("Finalized rounds channel isn't closed.");
+        }
+        while next_round <= last_round {
+            let unit = match units_from_creator.next().await {
+                Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+                _ => panic!("Unexpected notification from creator."),
+            };
+            assert_eq!(unit.round(), next_round);
+            parents_for_creator
+                .unbounded_send(unit)
+                .expect("Creator input channel isn't closed.");
+            next_round += 1;
+        }
+        // The creation delay is 50ms, so more units would have been created in the meantime.
+        Delay::new(Duration::from_millis(200)).await;
+        assert!(units_from_creator.next().now_or_never().is_none());
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn paused_creator_exits() {
+    let n_members = NodeCount(1);
+    let (_parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) =
+        mpsc::channel::<NotificationOut>(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (control_tx, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    control_tx
+        .unbounded_send(CreatorCommand::Pause)
+        .expect("Creator control channel isn't closed.");
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    Delay::new(Duration::from_millis(200)).await;
+    assert!(units_from_creator.next().now_or_never().is_none());
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_resumes_from_state_after_exit() {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (state_sink, state_source) = oneshot::channel();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: Some(state_sink),
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let own_unit = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+        _ => panic!("Unexpected notification from creator."),
+    };
+    let other_units: Vec<_> = (1..3)
+        .map(|creator| {
+            preunit_to_unit(PreUnit::new(
+                NodeIndex(creator),
+                0,
+                ControlHash::new(&NodeMap::with_size(n_members)),
+            ))
+        })
+        .collect();
+    for unit in [own_unit, other_units[0].clone()] {
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+    }
+    // Two parents are not enough, so the creator waits for more at round 1.
+    Delay::new(Duration::from_millis(100)).await;
+    finish(vec![killer], vec![handle]).await;
+    let state = state_source
+        .await
+        .expect("The state should be sent on exit.");
+    assert_eq!(state.round(), 1);
+
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(
+            async move { run(config.into(), io, starting_round, Some(state), exit).await },
+        );
+    // The restored state takes precedence over a lower starting round.
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+    parents_for_creator
+        .unbounded_send(other_units[1].clone())
+        .expect("Creator input channel isn't closed.");
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, parent_hashes)) => {
+            assert_eq!(preunit.round(), 1);
+            assert_eq!(parent_hashes.len(), 3);
+        }
+        _ => panic!("Unexpected notification from creator."),
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_waits_for_notifications_to_be_processed() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    // With a single sender, there is room for a single notification.
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(0);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (state_sink, state_source) = oneshot::channel();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: Some(state_sink),
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+    // Our own unit of round 0 is enough to create the unit of round 1, and so on.
+    for round in 0..3 {
+        let preunit = PreUnit::new(
+            NodeIndex(0),
+            round,
+            ControlHash::new(&NodeMap::with_size(n_members)),
+        );
+        parents_for_creator
+            .unbounded_send(preunit_to_unit(preunit))
+            .expect("Creator input channel isn't closed.");
+    }
+
+    // The unit of round 0 fills the channel, so the creator waits with the unit of round 1.
+    Delay::new(Duration::from_millis(200)).await;
+    finish(vec![killer], vec![handle]).await;
+    let state = state_source
+        .await
+        .expect("The state should be sent on exit.");
+    assert_eq!(state.round(), 1);
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => assert_eq!(preunit.round(), 0),
+        _ => panic!("Unexpected notification from creator."),
+    }
+    assert!(units_from_creator.next().await.is_none());
+}
+
+#[tokio::test]
+async fn restarted_creator_uses_recovered_units() {
+    let n_members = NodeCount(4);
+    let recovered: Vec<_> = (0..2)
+        .flat_map(|round| {
+            (0..n_members.0).map(move |creator| {
+                preunit_to_unit(PreUnit::new(
+                    NodeIndex(creator),
+                    round,
+                    ControlHash::new(&NodeMap::with_size(n_members)),
+                ))
+            })
+        })
+        .collect();
+    // Nothing comes through the incoming parents, as if the Dag did not catch up yet.
+    let (_parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (recovered_units_for_creator, recovered_units) = oneshot::channel();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: Some(recovered_units),
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    recovered_units_for_creator
+        .send(recovered)
+        .expect("Sending the recovered units should work.");
+    starting_round_for_consensus
+        .send(2)
+        .expect("Sending the starting round should work.");
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, parent_hashes)) => {
+            assert_eq!(preunit.round(), 2);
+            assert_eq!(parent_hashes.len(), n_members.0);
+        }
+        _ => panic!("Unexpected notification from creator."),
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+// Runs a creator waiting for all the parents, with a creation delay of 100ms. After it creates its
+// round 0 unit, units of round 0 of creators 1 and 2 are delivered immediately, and the one of
+// creator 3 after `last_parent_delay`, if any. Returns the number of parents of the round 1 unit.
+async fn n_parents_with_min_parents(last_parent_delay: Option<Duration>) -> usize {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.delay_config.unit_creation_delay = Arc::new(|_| Duration::from_millis(100));
+    config.min_parents = Some(n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let own_unit = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+        _ => panic!("Unexpected notification from creator."),
+    };
+    let mut other_units: Vec<_> = (1..4)
+        .map(|creator| {
+            preunit_to_unit(PreUnit::new(
+                NodeIndex(creator),
+                0,
+                ControlHash::new(&NodeMap::with_size(n_members)),
+            ))
+        })
+        .collect();
+    let last_unit = other_units.pop().expect("There are three units.");
+    for unit in std::iter::once(own_unit).chain(other_units) {
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+    }
+    if let Some(last_parent_delay) = last_parent_delay {
+        Delay::new(last_parent_delay).await;
+        parents_for_creator
+            .unbounded_send(last_unit)
+            .expect("Creator input channel isn't closed.");
+    }
+    let n_parents = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, parent_hashes)) => {
+            assert_eq!(preunit.round(), 1);
+            parent_hashes.len()
+        }
+        _ => panic!("Unexpected notification from creator."),
+    };
+    finish(vec![killer], vec![handle]).await;
+    n_parents
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_waits_for_min_parents() {
+    // The last parent arrives after the creation delay, but before the creator stops waiting.
+    assert_eq!(
+        n_parents_with_min_parents(Some(Duration::from_millis(150))).await,
+        4
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_with_unachievable_min_parents_still_creates() {
+    assert_eq!(n_parents_with_min_parents(None).await, 3);
+}
+
+// Returns the parents of the unit of round 1 and the units of round 0, of which the last one
+// arrives after `last_parent_delay`, if at all.
+async fn parents_with_grace_period(
+    last_parent_delay: Option<Duration>,
+) -> (Vec<Hash64>, Vec<Unit>) {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.delay_config.unit_creation_delay = Arc::new(|_| Duration::from_millis(50));
+    config.delay_config.parents_grace_period = Duration::from_millis(300);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let own_unit = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+        _ => panic!("Unexpected notification from creator."),
+    };
+    let units: Vec<_> = std::iter::once(own_unit)
+        .chain((1..4).map(|creator| {
+            preunit_to_unit(PreUnit::new(
+                NodeIndex(creator),
+                0,
+                ControlHash::new(&NodeMap::with_size(n_members)),
+            ))
+        }))
+        .collect();
+    for unit in &units[..3] {
+        parents_for_creator
+            .unbounded_send(unit.clone())
+            .expect("Creator input channel isn't closed.");
+    }
+    if let Some(last_parent_delay) = last_parent_delay {
+        Delay::new(last_parent_delay).await;
+        parents_for_creator
+            .unbounded_send(units[3].clone())
+            .expect("Creator input channel isn't closed.");
+    }
+    // The grace period is far below the default stall warning delay, so the creator reports
+    // nothing but the unit, and it does not wait forever.
+    let parent_hashes = match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, parent_hashes)) => {
+            assert_eq!(preunit.round(), 1);
+            parent_hashes
+        }
+        _ => panic!("Unexpected notification from creator."),
+    };
+    finish(vec![killer], vec![handle]).await;
+    (parent_hashes, units)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_includes_parents_arriving_in_grace_period() {
+    let (parent_hashes, units) = parents_with_grace_period(Some(Duration::from_millis(150))).await;
+    let unit_hashes: Vec<_> = units.iter().map(|unit| unit.hash()).collect();
+    assert_eq!(parent_hashes, unit_hashes);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_creates_after_grace_period_without_missing_parents() {
+    let (parent_hashes, units) = parents_with_grace_period(None).await;
+    let unit_hashes: Vec<_> = units[..3].iter().map(|unit| unit.hash()).collect();
+    assert_eq!(parent_hashes, unit_hashes);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_continues_after_max_round_extension() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (control_tx, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.max_round = 2;
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    for round in 0..4 {
+        // The default margin is far above the maximum rounds here.
+        if round == 0 {
+            assert_eq!(
+                units_from_creator.next().await,
+                Some(NotificationOut::MaxRoundApproached(2))
+            );
+        }
+        if round == 3 {
+            assert_eq!(
+                units_from_creator.next().await,
+                Some(NotificationOut::MaxRoundApproached(4))
+            );
+        }
+        if round == 2 {
+            assert_eq!(
+                units_from_creator.next().await,
+                Some(NotificationOut::MaxRoundReached(2))
+            );
+            // The creation delay is 50ms, so a unit would have been created in the meantime.
+            Delay::new(Duration::from_millis(200)).await;
+            assert!(units_from_creator.next().now_or_never().is_none());
+            control_tx
+                .unbounded_send(CreatorCommand::ExtendMaxRound(4))
+                .expect("Creator control channel isn't closed.");
+        }
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        assert_eq!(unit.round(), round);
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+    }
+    assert_eq!(
+        units_from_creator.next().await,
+        Some(NotificationOut::MaxRoundReached(4))
+    );
+    Delay::new(Duration::from_millis(200)).await;
+    assert!(units_from_creator.next().now_or_never().is_none());
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_reports_parents_backlog() {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let parents_backlog = Arc::new(AtomicUsize::new(0));
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: Some(parents_backlog.clone()),
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.parents_backlog_high_water = 5;
+    for round in 0..5 {
+        for creator in 1..4 {
+            let parents = if round == 0 {
+                NodeMap::with_size(n_members)
+            } else {
+                vec![Some([0; 8]); n_members.0].into()
+            };
+            let preunit = PreUnit::new(NodeIndex(creator), round, ControlHash::new(&parents));
+            parents_backlog.fetch_add(1, Ordering::Relaxed);
+            parents_for_creator
+                .unbounded_send(preunit_to_unit(preunit))
+                .expect("Creator input channel isn't closed.");
+        }
+    }
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatorLagging { backlog }) => assert_eq!(backlog, 14),
+        _ => panic!("Unexpected notification from creator."),
+    }
+    // The creator reports lagging only once, until it catches up.
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => assert_eq!(preunit.round(), 0),
+        _ => panic!("Unexpected notification from creator."),
+    }
+    assert_eq!(parents_backlog.load(Ordering::Relaxed), 0);
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_applies_new_delay_schedule_from_next_round() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (delay_schedules_tx, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+
+    let mut created_at = Vec::new();
+    for round in 0..3 {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        created_at.push(Instant::now());
+        assert_eq!(unit.round(), round);
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+        if round == 0 {
+            // Arrives while the delay of round 1 is running.
+            delay_schedules_tx
+                .unbounded_send(CreationDelay::RoundOnly(constant_schedule(
+                    Duration::from_millis(500),
+                )))
+                .expect("Creator delay schedule channel isn't closed.");
+        }
+    }
+    // Round 1 keeps the old delay of 50ms, round 2 uses the new one.
+    assert!(created_at[1] - created_at[0] < Duration::from_millis(500));
+    assert!(created_at[2] - created_at[1] >= Duration::from_millis(500));
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_applies_new_context_aware_delay_schedule() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (delay_schedules_tx, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, complete_oneshot(0), None, exit).await });
+
+    let rounds = Arc::new(Mutex::new(Vec::new()));
+    let recorded = rounds.clone();
+    let mut created_at = Vec::new();
+    for round in 0..3 {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        created_at.push(Instant::now());
+        assert_eq!(unit.round(), round);
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+        if round == 0 {
+            let recorded = recorded.clone();
+            delay_schedules_tx
+                .unbounded_send(CreationDelay::ContextAware(Arc::new(move |context| {
+                    recorded.lock().push(context.round);
+                    Duration::from_millis(500)
+                })))
+                .expect("Creator delay schedule channel isn't closed.");
+        }
+    }
+    // Round 1 keeps the round-only delay of the config, round 2 uses the context-aware one.
+    assert!(created_at[1] - created_at[0] < Duration::from_millis(500));
+    assert!(created_at[2] - created_at[1] >= Duration::from_millis(500));
+    assert_eq!(rounds.lock().first(), Some(&2));
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_uses_context_aware_delay_schedule() {
+    let n_members = NodeCount(1);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let contexts = Arc::new(Mutex::new(Vec::new()));
+    let mut config = gen_config(0.into(), n_members);
+    let recorded = contexts.clone();
+    config.delay_config.context_creation_delay = Some(Arc::new(move |context| {
+        recorded.lock().push(context);
+        // Slow while nobody seems active, the round-only schedule of the config is much faster.
+        match context.active_members {
+            NodeCount(0) => Duration::from_millis(300),
+            _ => Duration::from_millis(10),
+        }
+    }));
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, complete_oneshot(0), None, exit).await });
+
+    let started = Instant::now();
+    for round in 0..3 {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        assert_eq!(unit.round(), round);
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+    }
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    let contexts = contexts.lock().clone();
+    assert!(contexts
+        .iter()
+        .all(|context| context.n_members == n_members));
+    assert_eq!(
+        contexts
+            .first()
+            .map(|context| (context.round, context.active_members)),
+        Some((0, NodeCount(0)))
+    );
+    assert!(contexts.iter().any(|context| context.round == 2));
+    finish(vec![killer], vec![handle]).await;
+}
+
+async fn start_at_round(starting_round: Round, max_round: Round) -> Option<NotificationOut> {
+    let n_members = NodeCount(4);
+    let (_parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.max_round = max_round;
+    let (starting_round_for_consensus, starting_round_rx) = oneshot::channel::<Round>();
+    let (_killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round_rx, None, exit).await });
+    starting_round_for_consensus
+        .send(starting_round)
+        .expect("Sending the starting round should work.");
+    // The creator should exit on its own.
+    handle.await.expect("The creator should not panic.");
+    units_from_creator.next().await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_reports_starting_round_equal_to_max_round() {
+    assert_eq!(
+        start_at_round(5, 5).await,
+        Some(NotificationOut::StartingRoundTooHigh {
+            starting_round: 5,
+            max_round: 5
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_reports_starting_round_above_max_round() {
+    assert_eq!(
+        start_at_round(7, 5).await,
+        Some(NotificationOut::StartingRoundTooHigh {
+            starting_round: 7,
+            max_round: 5
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_reports_reaching_round_limit() {
+    let n_members = NodeCount(1);
+    let first_round = MAX_MAX_ROUND - 2;
+    let recovered = vec![preunit_to_unit(PreUnit::new(
+        NodeIndex(0),
+        first_round - 1,
+        ControlHash::new(&NodeMap::with_size(n_members)),
+    ))];
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (control_tx, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (recovered_units_for_creator, recovered_units) = oneshot::channel();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: Some(recovered_units),
+    };
+    let mut config = gen_config(0.into(), n_members);
+    config.max_round = first_round + 1;
+    config.max_round_warning_margin = 0;
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (_killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    recovered_units_for_creator
+        .send(recovered)
+        .expect("Sending the recovered units should work.");
+    starting_round_for_consensus
+        .send(first_round)
+        .expect("Sending the starting round should work.");
+    // Extending the maximum round past the limit only raises it to the limit.
+    control_tx
+        .unbounded_send(CreatorCommand::ExtendMaxRound(Round::MAX))
+        .expect("Creator control channel isn't closed.");
+
+    for round in first_round..MAX_MAX_ROUND {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        assert_eq!(unit.round(), round);
+        // After the last unit the creator might exit before receiving it.
+        let _ = parents_for_creator.unbounded_send(unit);
+    }
+    assert_eq!(
+        units_from_creator.next().await,
+        Some(NotificationOut::RoundLimitReached)
+    );
+    // The creator should exit on its own.
+    handle.await.expect("The creator should not panic.");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_exits_before_starting_round() {
+    let n_members = NodeCount(4);
+    let (_parents_for_creator, incoming_parents) = mpsc::unbounded::<Unit>();
+    let (outgoing_units, mut units_from_creator) =
+        mpsc::channel::<NotificationOut>(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (state_sink, state_source) = oneshot::channel();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: Some(state_sink),
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (_starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    Delay::new(Duration::from_millis(100)).await;
+    finish(vec![killer], vec![handle]).await;
+    let state = state_source
+        .await
+        .expect("The state should be sent on exit.");
+    assert_eq!(state.round(), 0);
+    assert_eq!(units_from_creator.next().await, None);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn creator_exits_when_starting_round_is_dropped() {
+    let n_members = NodeCount(4);
+    let (_parents_for_creator, incoming_parents) = mpsc::unbounded::<Unit>();
+    let (outgoing_units, mut units_from_creator) =
+        mpsc::channel::<NotificationOut>(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (_killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    drop(starting_round_for_consensus);
+    // The creator should exit on its own, without creating anything.
+    handle.await.expect("The creator should not panic.");
+    assert_eq!(units_from_creator.next().await, None);
+}
+
+// Closes the channel of the incoming parents while the creator waits for them, at the same time
+// as the exit signal if `with_exit`, and returns the snapshot of the creator, if any.
+async fn close_parents_channel(with_exit: bool) -> Option<Round> {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded::<Unit>();
+    let (outgoing_units, mut units_from_creator) =
+        mpsc::channel::<NotificationOut>(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let (state_sink, state_source) = oneshot::channel();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: Some(state_sink),
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let config = gen_config(0.into(), n_members);
+    let (starting_round_for_consensus, starting_round) = oneshot::channel::<Round>();
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, starting_round, None, exit).await });
+    starting_round_for_consensus
+        .send(0)
+        .expect("Sending the starting round should work.");
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => assert_eq!(preunit.round(), 0),
+        _ => panic!("Unexpected notification from creator."),
+    }
+    // Without the parents of round 0, the creator waits at round 1.
+    Delay::new(Duration::from_millis(100)).await;
+    // The creator cannot run in between, as the test is single threaded, so both are ready
+    // at once.
+    let _killer = if with_exit {
+        killer.send(()).expect("The creator should be running.");
+        None
+    } else {
+        Some(killer)
+    };
+    drop(parents_for_creator);
+    handle.await.expect("The creator should not panic.");
+    state_source.await.ok().map(|state| state.round())
+}
+
+#[tokio::test]
+async fn creator_exits_when_parents_channel_closes_on_shutdown() {
+    // Whichever of the two the creator notices first, it exits as upon the exit signal.
+    for _ in 0..10 {
+        assert_eq!(close_parents_channel(true).await, Some(1));
+    }
+}
+
+#[tokio::test]
+async fn creator_exits_without_state_when_parents_channel_closes_unexpectedly() {
+    assert_eq!(close_parents_channel(false).await, None);
+}
+
+#[tokio::test]
+async fn zero_creation_delay_is_raised() {
+    let n_members = NodeCount(4);
+    let (_parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    let clock = MockClock::new();
+    let mut config = gen_config(0.into(), n_members);
+    config.delay_config.unit_creation_delay = Arc::new(|_| Duration::ZERO);
+    config.clock = Arc::new(clock.clone());
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, complete_oneshot(0), None, exit).await });
+
+    // With a zero delay the unit would be created without the clock moving at all.
+    Delay::new(Duration::from_millis(50)).await;
+    assert!(units_from_creator.try_recv().is_err());
+    clock.advance(Duration::from_millis(1));
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => assert_eq!(preunit.round(), 0),
+        _ => panic!("Unexpected notification from creator."),
+    }
+    finish(vec![killer], vec![handle]).await;
+}
+
+#[tokio::test]
+async fn lagging_creator_catches_up_without_delays() {
+    let n_members = NodeCount(4);
+    let (parents_for_creator, incoming_parents) = mpsc::unbounded();
+    let (outgoing_units, mut units_from_creator) = mpsc::channel(NOTIFICATION_CAPACITY);
+    let (_, finalized_rounds) = mpsc::unbounded();
+    let (_, control) = mpsc::unbounded();
+    let (_, delay_schedules) = mpsc::unbounded();
+    let io = IO {
+        incoming_parents,
+        outgoing_units,
+        finalized_rounds,
+        metrics: None,
+        latencies: None,
+        control,
+        delay_schedules,
+        state_sink: None,
+        parents_backlog: None,
+        store_overloaded: None,
+        recovered_units: None,
+    };
+    // The clock only moves when the test says so, so no delay passes on its own.
+    let clock = MockClock::new();
+    let mut config = gen_config(0.into(), n_members);
+    config.delay_config.unit_creation_delay = constant_schedule(Duration::from_secs(1));
+    config.clock = Arc::new(clock.clone());
+    let (killer, exit) = exit_channel();
+    let handle =
+        tokio::spawn(async move { run(config.into(), io, complete_oneshot(0), None, exit).await });
+
+    // The others got to round 10 while the creator waited for the delay of round 0.
+    Delay::new(Duration::from_millis(50)).await;
+    assert!(units_from_creator.try_recv().is_err());
+    let last_round = 10;
+    for round in 0..=last_round {
+        for creator in 1..n_members.0 {
+            let preunit = PreUnit::new(
+                NodeIndex(creator),
+                round,
+                ControlHash::new(&NodeMap::with_size(n_members)),
+            );
+            parents_for_creator
+                .unbounded_send(preunit_to_unit(preunit))
+                .expect("Creator input channel isn't closed.");
+        }
+    }
+    for round in 0..last_round {
+        let unit = match units_from_creator.next().await {
+            Some(NotificationOut::CreatedPreUnit(preunit, _)) => preunit_to_unit(preunit),
+            _ => panic!("Unexpected notification from creator."),
+        };
+        assert_eq!(unit.round(), round);
+        parents_for_creator
+            .unbounded_send(unit)
+            .expect("Creator input channel isn't closed.");
+    }
+
+    // Caught up with the others, so the unit of the last round waits for the delay again.
+    Delay::new(Duration::from_millis(50)).await;
+    assert!(units_from_creator.try_recv().is_err());
+    clock.advance(Duration::from_secs(1));
+    match units_from_creator.next().await {
+        Some(NotificationOut::CreatedPreUnit(preunit, _)) => {
+            assert_eq!(preunit.round(), last_round)
+        }
+        _ => panic!("Unexpected notification from creator."),
+    }
+    finish(vec![killer], vec![handle]).await;
+}
@@ -2,9 +2,10 @@ use crate::{
     consensus,
     nodes::{NodeCount, NodeIndex, NodeMap, NodeSubset},
     runway::{NotificationIn, NotificationOut},
+    terminator::Terminator,
     testing::mock::{gen_config, Hash64, Hasher64, Spawner},
     units::{ControlHash, PreUnit, Unit},
-    Receiver, Round, Sender, SpawnHandle,
+    Receiver, Round, Sender, SessionStatus, SpawnHandle,
 };
 use futures::{
     channel::{mpsc, oneshot},
@@ -14,7 +15,11 @@ use futures::{
 use futures_timer::Delay;
 use log::{debug, error, trace};
 use rand::{distributions::Open01, prelude::*};
-use std::{cmp, time::Duration};
+use std::{
+    cmp,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
 use crate::testing::mock::complete_oneshot;
 use std::collections::HashMap;
@@ -131,18 +136,26 @@ async fn run_consensus_on_dag(
     let conf = gen_config(NodeIndex(0), n_members);
     let (_exit_tx, exit_rx) = oneshot::channel();
     let (batch_tx, mut batch_rx) = mpsc::unbounded();
+    // The units we create are not part of the Dag, so nothing processes them.
+    let (creator_tx, _creator_rx) = mpsc::channel(0);
     let spawner = Spawner::new();
     let starting_round = complete_oneshot(0);
+    let (_, creator_control) = mpsc::unbounded();
     spawner.spawn(
         "consensus",
         consensus::run(
             conf,
             rx_in,
             tx_out,
+            creator_tx,
             batch_tx,
             spawner.clone(),
             starting_round,
-            exit_rx,
+            complete_oneshot(Vec::new()),
+            creator_control,
+            Arc::new(AtomicBool::new(false)),
+            SessionStatus::new(),
+            Terminator::create_root(exit_rx, "runway/consensus"),
         ),
     );
     spawner.spawn("feeder", feeder.run());
@@ -151,7 +164,8 @@ async fn run_consensus_on_dag(
     loop {
         futures::select! {
             batch = batch_rx.next() => {
-                batches.push(batch.unwrap());
+                let (_, batch) = batch.unwrap();
+                batches.push(batch);
             },
             _ = &mut delay_fut => {
                 break;
@@ -173,7 +187,7 @@ fn generate_random_dag(n_members: NodeCount, height: Round, seed: u64) -> Vec<Un
     let mut forker_bitmap = NodeSubset::with_size(n_members);
     // below we select n_forkers forkers at random
     for forker_ix in n_members
-        .into_iterator()
+        .all_indices()
         .choose_multiple(&mut rng, n_forkers.into())
     {
         forker_bitmap.insert(forker_ix);
@@ -190,10 +204,10 @@ fn generate_random_dag(n_members: NodeCount, height: Round, seed: u64) -> Vec<Un
         vec![vec![vec![]; n_members.into()]; height.into()];
     // dag is a (height x n_members)-dimensional array consisting of empty vectors.
 
-    let mut all_ixs: Vec<_> = n_members.into_iterator().collect();
+    let mut all_ixs: Vec<_> = n_members.all_indices().collect();
 
     for r in 0..height {
-        for node_ix in n_members.into_iterator() {
+        for node_ix in n_members.all_indices() {
             let mut n_variants = if forker_bitmap[node_ix] {
                 rng.gen_range(1..=max_variants)
             } else {
@@ -213,7 +227,7 @@ fn generate_random_dag(n_members: NodeCount, height: Round, seed: u64) -> Vec<Un
                         break;
                     }
                     let mut n_max_parents = NodeCount(0);
-                    for p_ix in n_members.into_iterator() {
+                    for p_ix in n_members.all_indices() {
                         if !dag[previous_round_index][p_ix.0].is_empty() {
                             n_max_parents += 1.into();
                         }
@@ -226,7 +240,7 @@ fn generate_random_dag(n_members: NodeCount, height: Round, seed: u64) -> Vec<Un
                     all_ixs.shuffle(&mut rng);
                     // The loop below makes the first element of all_ixs equal to node_ix (the currently considered creator)
                     // This is to make sure that it will be chosen as a parent
-                    for i in n_members.into_iterator() {
+                    for i in n_members.all_indices() {
                         if all_ixs[i.0] == node_ix {
                             all_ixs.swap(0, i.0);
                             break;
@@ -0,0 +1,572 @@
+use futures::{channel::oneshot, future::BoxFuture, FutureExt, StreamExt};
+use futures_timer::Delay;
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    testing::mock::{
+        configure_network, gen_config, init_log, run_honest_member, spawn_honest_member, Data,
+        DataIO, KeyBox, Spawner,
+    },
+    units::UnitCoord,
+    DataChannelPolicy, DataIO as DataIOT, NodeCount, NodeIndex, OrderedBatch, Round,
+    SessionOutcome, SpawnHandle,
+};
+
+const FALLBACK_VARIANT: u32 = 1;
+const FINALIZED_VARIANT: u32 = 2;
+
+// Never provides data in time, so that only the fallback data ends up in the units.
+struct HangingDataIO(DataIO);
+
+impl DataIOT<Data> for HangingDataIO {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        panic!("The data should be requested asynchronously.");
+    }
+
+    fn get_data_async(&self) -> BoxFuture<'static, Data> {
+        futures::future::pending().boxed()
+    }
+
+    fn fallback_data(&self) -> Option<Data> {
+        let mut data = self.0.get_data();
+        data.variant = FALLBACK_VARIANT;
+        Some(data)
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.0.send_ordered_batch(data)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hanging_data_provider_does_not_stall_creation() {
+    init_log();
+    let n_members = NodeCount(4);
+    let hanging_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let hanging_network = networks.remove(0);
+    let mut config = gen_config(hanging_ix, n_members);
+    config.delay_config.data_timeout = Some(Duration::from_millis(10));
+    let (data_io, _) = DataIO::new(hanging_ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = run_honest_member(
+        config,
+        hanging_network,
+        HangingDataIO(data_io),
+        KeyBox::new(n_members, hanging_ix),
+        spawner.clone(),
+        exit_rx,
+    );
+    spawner.spawn("hanging member", async move {
+        let _ = session.await;
+    });
+    let mut exits = vec![exit_tx];
+
+    let mut batch_rxs = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (batch_rx, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+
+    let mut n_fallback_units = 0;
+    while n_fallback_units < 3 {
+        let batch = batch_rxs[0]
+            .next()
+            .await
+            .expect("The member should not exit.");
+        n_fallback_units += batch
+            .iter()
+            .filter(|data| data.coord.creator() == hanging_ix)
+            .inspect(|data| assert_eq!(data.variant, FALLBACK_VARIANT))
+            .count();
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+// Never provides data and has no fallback, so that our units never get created.
+struct StuckDataIO(DataIO);
+
+impl DataIOT<Data> for StuckDataIO {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        panic!("The data should be requested asynchronously.");
+    }
+
+    fn get_data_async(&self) -> BoxFuture<'static, Data> {
+        futures::future::pending().boxed()
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.0.send_ordered_batch(data)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stuck_data_provider_does_not_stall_the_session() {
+    init_log();
+    let n_members = NodeCount(4);
+    let stuck_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let stuck_network = networks.remove(0);
+    let mut config = gen_config(stuck_ix, n_members);
+    config.delay_config.data_timeout = Some(Duration::from_millis(10));
+    let (data_io, mut stuck_batch_rx) = DataIO::new(stuck_ix);
+    let (stuck_exit_tx, exit_rx) = oneshot::channel();
+    let session = tokio::spawn(run_honest_member(
+        config,
+        stuck_network,
+        StuckDataIO(data_io),
+        KeyBox::new(n_members, stuck_ix),
+        spawner.clone(),
+        exit_rx,
+    ));
+
+    let mut exits = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+
+    // While waiting for the data, the member keeps following the others.
+    for _ in 0..3 {
+        let batch = stuck_batch_rx
+            .next()
+            .await
+            .expect("The member should not exit.");
+        assert!(batch.iter().all(|data| data.coord.creator() != stuck_ix));
+    }
+    stuck_exit_tx.send(()).expect("the member is running");
+    let outcome = tokio::time::timeout(Duration::from_secs(10), session)
+        .await
+        .expect("the member should exit while waiting for the data")
+        .expect("the member should not panic");
+    assert!(matches!(outcome, Ok(SessionOutcome::Exited)));
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+// Rejects the data of odd rounds, and puts the round of the unit in the data of the others.
+struct FinalizingDataIO(DataIO, NodeIndex);
+
+impl DataIOT<Data> for FinalizingDataIO {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        self.0.get_data()
+    }
+
+    fn fallback_data(&self) -> Option<Data> {
+        Some(Data::new(UnitCoord::new(0, self.1), FALLBACK_VARIANT))
+    }
+
+    fn finalize_data(&self, _: Data, round: Round) -> Option<Data> {
+        match round % 2 {
+            0 => Some(Data::new(UnitCoord::new(round, self.1), FINALIZED_VARIANT)),
+            _ => None,
+        }
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.0.send_ordered_batch(data)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn finalized_data_ends_up_in_units() {
+    init_log();
+    let n_members = NodeCount(4);
+    let finalizing_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let finalizing_network = networks.remove(0);
+    let (data_io, _) = DataIO::new(finalizing_ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = run_honest_member(
+        gen_config(finalizing_ix, n_members),
+        finalizing_network,
+        FinalizingDataIO(data_io, finalizing_ix),
+        KeyBox::new(n_members, finalizing_ix),
+        spawner.clone(),
+        exit_rx,
+    );
+    spawner.spawn("finalizing member", async move {
+        let _ = session.await;
+    });
+    let mut exits = vec![exit_tx];
+
+    let mut batch_rxs = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (batch_rx, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+
+    let (mut n_fallback_units, mut n_finalized_units) = (0, 0);
+    while n_fallback_units < 2 || n_finalized_units < 2 {
+        let batch = batch_rxs[0]
+            .next()
+            .await
+            .expect("The member should not exit.");
+        for data in batch
+            .iter()
+            .filter(|data| data.coord.creator() == finalizing_ix)
+        {
+            match data.variant {
+                FALLBACK_VARIANT => n_fallback_units += 1,
+                FINALIZED_VARIANT => {
+                    assert_eq!(data.coord.round() % 2, 0);
+                    n_finalized_units += 1;
+                }
+                variant => panic!("Unexpected variant {} of the data.", variant),
+            }
+        }
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Event {
+    Batch,
+    Flush,
+}
+
+struct FlushRecorder {
+    data_io: DataIO,
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl DataIOT<Data> for FlushRecorder {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        self.data_io.get_data()
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.events.lock().push(Event::Batch);
+        self.data_io.send_ordered_batch(data)
+    }
+
+    fn flush(&mut self) -> Result<(), ()> {
+        self.events.lock().push(Event::Flush);
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_flushes_data_before_returning() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    let mut recorded = None;
+    for network in networks {
+        let ix = network.index();
+        if ix != NodeIndex(0) {
+            let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+            exits.push(exit_tx);
+            continue;
+        }
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = FlushRecorder {
+            data_io,
+            events: events.clone(),
+        };
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let session = run_honest_member(
+            gen_config(ix, n_members),
+            network,
+            recorder,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            exit_rx,
+        );
+        let session = spawner.spawn_essential("recorded member", async move {
+            assert_eq!(session.await, Ok(SessionOutcome::Exited));
+        });
+        recorded = Some((session, batch_rx, events, exit_tx));
+    }
+
+    let (session, mut batch_rx, events, exit_tx) = recorded.expect("node 0 is in the committee");
+    for _ in 0..3 {
+        batch_rx.next().await.expect("The member should not exit.");
+    }
+    let _ = exit_tx.send(());
+    assert_eq!(session.await, Ok(()));
+    let events = events.lock();
+    assert_eq!(events.last(), Some(&Event::Flush));
+    assert_eq!(events.iter().filter(|e| **e == Event::Flush).count(), 1);
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+// Provides a few data items and then reports being closed, falling back to the empty variant.
+struct ClosingDataIO {
+    data_io: DataIO,
+    remaining: AtomicUsize,
+}
+
+impl ClosingDataIO {
+    fn new(data_io: DataIO, n_items: usize) -> Self {
+        ClosingDataIO {
+            data_io,
+            remaining: AtomicUsize::new(n_items),
+        }
+    }
+}
+
+impl DataIOT<Data> for ClosingDataIO {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        assert!(
+            !self.is_closed(),
+            "The data should not be requested when closed."
+        );
+        self.remaining.fetch_sub(1, Ordering::Relaxed);
+        self.data_io.get_data()
+    }
+
+    fn fallback_data(&self) -> Option<Data> {
+        let mut data = self.data_io.get_data();
+        data.variant = FALLBACK_VARIANT;
+        Some(data)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.remaining.load(Ordering::Relaxed) == 0
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.data_io.send_ordered_batch(data)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_shuts_down_when_data_closes() {
+    init_log();
+    let n_members = NodeCount(4);
+    let closing_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+
+    let mut config = gen_config(closing_ix, n_members);
+    config.data_channel_policy = DataChannelPolicy::ShutdownSession;
+    let (data_io, _) = DataIO::new(closing_ix);
+    // The sender is kept alive, so that only closing the data can stop the session.
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let outcome = run_honest_member(
+        config,
+        networks.remove(0),
+        ClosingDataIO::new(data_io, 3),
+        KeyBox::new(n_members, closing_ix),
+        spawner.clone(),
+        exit_rx,
+    )
+    .await;
+    assert_eq!(outcome, Ok(SessionOutcome::DataClosed));
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_produces_empty_units_when_data_closes() {
+    init_log();
+    let n_members = NodeCount(4);
+    let closing_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let closing_network = networks.remove(0);
+    let mut config = gen_config(closing_ix, n_members);
+    config.data_channel_policy = DataChannelPolicy::ProduceEmptyUnits;
+    let (data_io, _) = DataIO::new(closing_ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = run_honest_member(
+        config,
+        closing_network,
+        ClosingDataIO::new(data_io, 3),
+        KeyBox::new(n_members, closing_ix),
+        spawner.clone(),
+        exit_rx,
+    );
+    spawner.spawn("closing member", async move {
+        let _ = session.await;
+    });
+    let mut exits = vec![exit_tx];
+
+    let mut batch_rxs = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (batch_rx, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+
+    let mut n_fallback_units = 0;
+    while n_fallback_units < 3 {
+        let batch = batch_rxs[0]
+            .next()
+            .await
+            .expect("The member should not exit.");
+        n_fallback_units += batch
+            .iter()
+            .filter(|data| data.coord.creator() == closing_ix && data.variant == FALLBACK_VARIANT)
+            .count();
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+// Numbers the calls for the data in the variant, and answers the later calls of every few sooner
+// than the earlier ones, counting how many of them are outstanding at once.
+struct SlowerEarlierDataIO {
+    data_io: DataIO,
+    creator: NodeIndex,
+    n_calls: Arc<AtomicUsize>,
+    outstanding: Arc<AtomicUsize>,
+    max_outstanding: Arc<AtomicUsize>,
+}
+
+impl DataIOT<Data> for SlowerEarlierDataIO {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        panic!("The data should be requested asynchronously.");
+    }
+
+    fn get_data_async(&self) -> BoxFuture<'static, Data> {
+        let call = self.n_calls.fetch_add(1, Ordering::SeqCst);
+        let outstanding = self.outstanding.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_outstanding
+            .fetch_max(outstanding, Ordering::SeqCst);
+        let delay = Duration::from_millis(30 * (3 - call as u64 % 3));
+        let data = Data::new(UnitCoord::new(0, self.creator), call as u32);
+        let outstanding = self.outstanding.clone();
+        async move {
+            Delay::new(delay).await;
+            outstanding.fetch_sub(1, Ordering::SeqCst);
+            data
+        }
+        .boxed()
+    }
+
+    fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
+        self.data_io.send_ordered_batch(data)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pipelined_data_ends_up_in_units_in_the_order_of_calls() {
+    init_log();
+    let n_members = NodeCount(4);
+    let depth = 3;
+    let pipelined_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let pipelined_network = networks.remove(0);
+    let mut config = gen_config(pipelined_ix, n_members);
+    config.data_pipeline_depth = depth;
+    let (data_io, _) = DataIO::new(pipelined_ix);
+    let max_outstanding = Arc::new(AtomicUsize::new(0));
+    let data_io = SlowerEarlierDataIO {
+        data_io,
+        creator: pipelined_ix,
+        n_calls: Arc::new(AtomicUsize::new(0)),
+        outstanding: Arc::new(AtomicUsize::new(0)),
+        max_outstanding: max_outstanding.clone(),
+    };
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = run_honest_member(
+        config,
+        pipelined_network,
+        data_io,
+        KeyBox::new(n_members, pipelined_ix),
+        spawner.clone(),
+        exit_rx,
+    );
+    spawner.spawn("pipelined member", async move {
+        let _ = session.await;
+    });
+    let mut exits = vec![exit_tx];
+
+    let mut batch_rxs = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (batch_rx, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+
+    // Our units are finalized in the order of their rounds.
+    let mut calls = Vec::new();
+    while calls.len() < 6 {
+        let batch = batch_rxs[0]
+            .next()
+            .await
+            .expect("The member should not exit.");
+        calls.extend(
+            batch
+                .iter()
+                .filter(|data| data.coord.creator() == pipelined_ix)
+                .map(|data| data.variant),
+        );
+    }
+    assert_eq!(calls, (0..calls.len() as u32).collect::<Vec<_>>());
+    assert_eq!(max_outstanding.load(Ordering::SeqCst), depth);
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
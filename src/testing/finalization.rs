@@ -0,0 +1,403 @@
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    future::BoxFuture,
+    FutureExt, StreamExt,
+};
+use futures_timer::Delay;
+use std::time::Duration;
+
+use crate::{
+    run_session_with_unit_handler,
+    testing::mock::{
+        configure_network, gen_config, init_log, Data, DataIO, Hasher64, KeyBox, Spawner,
+    },
+    units::UnitCoord,
+    DataIO as DataIOT, FinalizedUnit, NodeCount, NodeIndex, OrderedBatch, SessionBuilder,
+    SpawnHandle, UnitFinalizationHandler,
+};
+
+type Unit = FinalizedUnit<Hasher64, Data>;
+
+struct UnitRecorder {
+    data_io: DataIO,
+    tx: UnboundedSender<Vec<Unit>>,
+}
+
+impl DataIOT<Data> for UnitRecorder {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        self.data_io.get_data()
+    }
+
+    fn send_ordered_batch(&mut self, _: OrderedBatch<Data>) -> Result<(), ()> {
+        panic!("Batches should be passed with their units.");
+    }
+}
+
+impl UnitFinalizationHandler<Hasher64, Data> for UnitRecorder {
+    fn units_finalized(&mut self, units: Vec<Unit>) -> Result<(), ()> {
+        self.tx.unbounded_send(units).map_err(|_| ())
+    }
+}
+
+fn spawn_recording_member(
+    spawner: Spawner,
+    node_ix: NodeIndex,
+    n_members: NodeCount,
+    network: crate::testing::mock::Network,
+) -> (UnboundedReceiver<Vec<Unit>>, oneshot::Sender<()>) {
+    let (data_io, _) = DataIO::new(node_ix);
+    let (tx, rx) = unbounded();
+    let recorder = UnitRecorder { data_io, tx };
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let spawner_inner = spawner.clone();
+    spawner.spawn("member", async move {
+        let (_, creator_control) = unbounded();
+        let _ = run_session_with_unit_handler(
+            gen_config(node_ix, n_members),
+            network,
+            recorder,
+            KeyBox::new(n_members, node_ix),
+            spawner_inner,
+            creator_control,
+            exit_rx,
+        )
+        .await;
+    });
+    (rx, exit_tx)
+}
+
+#[tokio::test]
+async fn finalized_units_carry_their_provenance() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut receivers = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (rx, exit) = spawn_recording_member(spawner.clone(), ix, n_members, network);
+        receivers.push(rx);
+        exits.push(exit);
+    }
+
+    let mut batches = Vec::new();
+    for rx in receivers.iter_mut() {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..3 {
+            batches_per_ix.push(rx.next().await.expect("The member should not exit."));
+        }
+        batches.push(batches_per_ix);
+    }
+    for units in batches[0].iter() {
+        for unit in units {
+            // The mock data identifies the unit that carries it.
+            let coord = UnitCoord::new(unit.round, unit.creator);
+            assert_eq!(unit.data, Data::new(coord, 0));
+        }
+    }
+    // The timestamps, if any, are local, so only the units themselves have to agree.
+    let provenance = |batches: &Vec<Vec<Unit>>| -> Vec<Vec<_>> {
+        batches
+            .iter()
+            .map(|units| {
+                units
+                    .iter()
+                    .map(|unit| (unit.creator, unit.round, unit.hash, unit.data.clone()))
+                    .collect()
+            })
+            .collect()
+    };
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(provenance(&batches[0]), provenance(batches_per_ix));
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[cfg(feature = "timestamps")]
+#[tokio::test]
+async fn finalized_units_carry_their_timestamps() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut receivers = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (rx, exit) = spawn_recording_member(spawner.clone(), ix, n_members, network);
+        receivers.push(rx);
+        exits.push(exit);
+    }
+
+    let started = std::time::SystemTime::now();
+    let mut n_own_units = 0;
+    for _ in 0..3 {
+        let units = receivers[0]
+            .next()
+            .await
+            .expect("The member should not exit.");
+        for unit in units {
+            assert!(unit.finalized_at >= started);
+            // Only our own units have a known creation time.
+            match unit.creator {
+                NodeIndex(0) => {
+                    let created_at = unit.created_at.expect("We created the unit.");
+                    assert!(created_at <= unit.finalized_at);
+                    assert!(unit.finalization_latency().is_some());
+                    n_own_units += 1;
+                }
+                _ => assert_eq!(unit.created_at, None),
+            }
+        }
+    }
+    assert!(n_own_units > 0);
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+// Passes every batch to the test, and finishes handling it only once the test says so.
+struct SlowHandler {
+    data_io: DataIO,
+    tx: UnboundedSender<(Vec<Unit>, oneshot::Sender<()>)>,
+}
+
+impl DataIOT<Data> for SlowHandler {
+    type Error = ();
+
+    fn get_data(&self) -> Data {
+        self.data_io.get_data()
+    }
+
+    fn send_ordered_batch(&mut self, _: OrderedBatch<Data>) -> Result<(), ()> {
+        panic!("Batches should be passed with their units.");
+    }
+}
+
+impl UnitFinalizationHandler<Hasher64, Data> for SlowHandler {
+    fn units_finalized(&mut self, _: Vec<Unit>) -> Result<(), ()> {
+        panic!("Batches should be passed asynchronously.");
+    }
+
+    fn units_finalized_async(&mut self, units: Vec<Unit>) -> BoxFuture<'static, Result<(), ()>> {
+        let (done_tx, done_rx) = oneshot::channel();
+        let sent = self.tx.unbounded_send((units, done_tx));
+        async move {
+            sent.map_err(|_| ())?;
+            done_rx.await.map_err(|_| ())
+        }
+        .boxed()
+    }
+}
+
+#[tokio::test]
+async fn slow_finalization_handler_holds_back_the_next_batch() {
+    init_log();
+    let n_members = NodeCount(4);
+    let slow_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit) = spawn_recording_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit);
+    }
+
+    let (data_io, _) = DataIO::new(slow_ix);
+    let (tx, mut batches) = unbounded();
+    let handler = SlowHandler { data_io, tx };
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let network = networks.remove(0);
+    let spawner_inner = spawner.clone();
+    spawner.spawn("slow member", async move {
+        let (_, creator_control) = unbounded();
+        let _ = run_session_with_unit_handler(
+            gen_config(slow_ix, n_members),
+            network,
+            handler,
+            KeyBox::new(n_members, slow_ix),
+            spawner_inner,
+            creator_control,
+            exit_rx,
+        )
+        .await;
+    });
+    exits.push(exit_tx);
+
+    let (_, done) = batches.next().await.expect("The member should not exit.");
+    // The other members keep ordering units, but we do not get them before finishing the batch.
+    Delay::new(Duration::from_millis(300)).await;
+    assert!(batches.try_recv().is_err());
+    let _ = done.send(());
+    let (_, done) = batches.next().await.expect("The member should not exit.");
+    let _ = done.send(());
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn stalled_finalization_handler_stops_ordering_and_creation() {
+    init_log();
+    let n_members = NodeCount(4);
+    let slow_ix = NodeIndex(0);
+    let max_undelivered_rounds = 5;
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    let mut other_batches = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (batches, exit) = spawn_recording_member(spawner.clone(), ix, n_members, network);
+        other_batches.push(batches);
+        exits.push(exit);
+    }
+
+    let (data_io, _) = DataIO::new(slow_ix);
+    let (tx, mut batches) = unbounded();
+    let mut config = gen_config(slow_ix, n_members);
+    config.max_undelivered_rounds = max_undelivered_rounds;
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let builder = SessionBuilder::new(config)
+        .network(networks.remove(0))
+        .unit_finalization_handler(SlowHandler { data_io, tx })
+        .keychain(KeyBox::new(n_members, slow_ix))
+        .spawn_handle(spawner.clone())
+        .exit(exit_rx);
+    let status = builder.status();
+    spawner.spawn("slow member", async move {
+        let _ = builder.run::<Hasher64, Data>().await;
+    });
+    exits.push(exit_tx);
+
+    let (units, done) = batches.next().await.expect("The member should not exit.");
+    let stalled_round = units
+        .iter()
+        .map(|unit| unit.round)
+        .max()
+        .expect("batches are not empty");
+    // The others go on without us, far beyond the bound.
+    let mut others_round = 0;
+    while others_round < stalled_round + 4 * max_undelivered_rounds {
+        let units = other_batches[0]
+            .next()
+            .await
+            .expect("The member should not exit.");
+        others_round = units
+            .iter()
+            .map(|unit| unit.round)
+            .max()
+            .unwrap_or(others_round);
+    }
+    // The units passed to the consensus before we noticed the lag may still be ordered, and
+    // our creator may still use them as parents.
+    let margin = 10;
+    let finalized_round = status
+        .last_finalized_round()
+        .expect("a batch was finalized");
+    assert!(finalized_round < stalled_round + max_undelivered_rounds + margin);
+    let current_round = status.current_round().expect("units were created");
+    assert!(current_round < stalled_round + max_undelivered_rounds + margin);
+    assert!(status.is_finalization_lagging());
+
+    // Once the handler is done, we catch up with the others.
+    let _ = done.send(());
+    loop {
+        let (units, done) = batches.next().await.expect("The member should not exit.");
+        let _ = done.send(());
+        if units.iter().any(|unit| unit.round >= others_round) {
+            break;
+        }
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn split_batches_are_finalized_in_the_same_order() {
+    init_log();
+    let n_members = NodeCount(4);
+    let split_ix = NodeIndex(0);
+    let max_batch_size = 2;
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut receivers = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (rx, exit) = spawn_recording_member(spawner.clone(), ix, n_members, network);
+        receivers.push(rx);
+        exits.push(exit);
+    }
+
+    let mut config = gen_config(split_ix, n_members);
+    config.max_batch_size = Some(max_batch_size);
+    let (data_io, _) = DataIO::new(split_ix);
+    let (tx, mut split_batches) = unbounded();
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let builder = SessionBuilder::new(config)
+        .network(networks.remove(0))
+        .unit_finalization_handler(UnitRecorder { data_io, tx })
+        .keychain(KeyBox::new(n_members, split_ix))
+        .spawn_handle(spawner.clone())
+        .exit(exit_rx);
+    let status = builder.status();
+    spawner.spawn("splitting member", async move {
+        let _ = builder.run::<Hasher64, Data>().await;
+    });
+    exits.push(exit_tx);
+
+    let mut whole = Vec::new();
+    for _ in 0..5 {
+        whole.extend(
+            receivers[0]
+                .next()
+                .await
+                .expect("The member should not exit."),
+        );
+    }
+    assert!(whole.len() > max_batch_size);
+    let mut split = Vec::new();
+    let mut last_finalized_round = None;
+    while split.len() < whole.len() {
+        let units = split_batches
+            .next()
+            .await
+            .expect("The member should not exit.");
+        assert!(!units.is_empty() && units.len() <= max_batch_size);
+        // The parts of a batch without its head still count as finalizing the round of the head.
+        let round = status.last_finalized_round();
+        assert!(round >= last_finalized_round);
+        assert!(units.iter().all(|unit| Some(unit.round) <= round));
+        last_finalized_round = round;
+        split.extend(units);
+    }
+    let hashes = |units: &[Unit]| -> Vec<_> { units.iter().map(|unit| unit.hash).collect() };
+    assert_eq!(hashes(&split[..whole.len()]), hashes(&whole));
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
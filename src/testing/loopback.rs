@@ -0,0 +1,42 @@
+use futures::StreamExt;
+
+use crate::{
+    testing::mock::{init_log, spawn_honest_member, Spawner},
+    LoopbackNetwork, NodeCount,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn honest_members_agree_over_loopback() {
+    init_log();
+    let n_members = NodeCount(4);
+    let n_batches = 5;
+    let spawner = Spawner::new();
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    let mut handles = Vec::new();
+    for network in LoopbackNetwork::connected(n_members) {
+        let (batch_rx, exit_tx, handle) =
+            spawn_honest_member(spawner.clone(), network.index(), n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+        handles.push(handle);
+    }
+
+    let mut batches = Vec::new();
+    for mut rx in batch_rxs {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..n_batches {
+            batches_per_ix.push(rx.next().await.expect("the member should not exit"));
+        }
+        batches.push(batches_per_ix);
+    }
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(&batches[0], batches_per_ix);
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+}
@@ -0,0 +1,222 @@
+use futures::{
+    channel::{mpsc::unbounded, oneshot},
+    StreamExt,
+};
+
+use crate::{
+    member::UnitMessage,
+    network::NetworkDataInner,
+    nodes::NodeSubset,
+    spawn_session,
+    testing::mock::{
+        configure_network, gen_config, init_log, DataIO, KeyBox, NetworkData, NetworkHook, Spawner,
+    },
+    NodeCount, NodeIndex, ParentsError, Round, SpawnHandle, UnitRejectionReason,
+};
+
+#[derive(Clone, Copy)]
+enum Malformation {
+    TooFewParents,
+    NoOwnParent,
+    ParentsInRoundZero,
+}
+
+impl Malformation {
+    fn error(&self) -> ParentsError {
+        match self {
+            Malformation::TooFewParents => ParentsError::NotQuorum,
+            Malformation::NoOwnParent => ParentsError::MissingOwnParent,
+            Malformation::ParentsInRoundZero => ParentsError::ParentsOfInitialUnit,
+        }
+    }
+}
+
+// Breaks the parents of the unit of the creator of the given round, as it is broadcast. The
+// others can still get the intact unit by requesting it.
+struct MalformUnit {
+    creator: NodeIndex,
+    round: Round,
+    n_members: NodeCount,
+    malformation: Malformation,
+}
+
+impl NetworkHook for MalformUnit {
+    fn update_state(&mut self, data: &mut NetworkData, _: NodeIndex, _: NodeIndex) {
+        let full_unit = match data {
+            crate::NetworkData(NetworkDataInner::Units(UnitMessage::NewUnit(uu))) => {
+                uu.as_signable_mut()
+            }
+            _ => return,
+        };
+        if full_unit.creator() != self.creator || full_unit.round() != self.round {
+            return;
+        }
+        let mut parents = NodeSubset::with_size(self.n_members);
+        match self.malformation {
+            Malformation::TooFewParents => parents.insert(self.creator),
+            Malformation::NoOwnParent => {
+                for ix in self.n_members.all_indices() {
+                    if ix != self.creator {
+                        parents.insert(ix);
+                    }
+                }
+            }
+            Malformation::ParentsInRoundZero => {
+                for ix in self.n_members.all_indices() {
+                    parents.insert(ix);
+                }
+            }
+        }
+        full_unit.set_parents(parents);
+    }
+}
+
+async fn members_flag_creator_of_malformed_unit(malformation: Malformation, round: Round) {
+    init_log();
+    let n_members = NodeCount(4);
+    let creator = NodeIndex(3);
+    let spawner = Spawner::new();
+    let (mut net_hub, networks) = configure_network(n_members, 1.0);
+    net_hub.add_hook(MalformUnit {
+        creator,
+        round,
+        n_members,
+        malformation,
+    });
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    let mut rejections = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (rejection_sink, rejections_rx) = unbounded();
+        let mut config = gen_config(ix, n_members);
+        config.unit_rejection_sink = Some(rejection_sink);
+        let (handle, status) = spawn_session(
+            config,
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        members.push((handle, status, batch_rx, exit_tx));
+        rejections.push(rejections_rx);
+    }
+
+    let mut batches = Vec::new();
+    for (_, _, batch_rx, _) in members.iter_mut() {
+        let mut member_batches = Vec::new();
+        for _ in 0..round + 3 {
+            member_batches.push(batch_rx.next().await.expect("The member should not exit."));
+        }
+        batches.push(member_batches);
+    }
+    for member_batches in &batches[1..] {
+        assert_eq!(member_batches, &batches[0]);
+    }
+    for (_, status, _, _) in members.iter().take(creator.0) {
+        assert_eq!(status.malformed_unit_creators(), vec![creator]);
+    }
+    for rejections in rejections.iter_mut().take(creator.0) {
+        let rejection = rejections
+            .try_recv()
+            .expect("The malformed unit should be reported.");
+        assert_eq!(rejection.creator, creator);
+        assert_eq!(rejection.round, round);
+        assert_eq!(
+            rejection.reason,
+            UnitRejectionReason::MalformedParents(malformation.error())
+        );
+    }
+
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn unit_with_too_few_parents_is_rejected() {
+    members_flag_creator_of_malformed_unit(Malformation::TooFewParents, 2).await;
+}
+
+#[tokio::test]
+async fn unit_without_own_parent_is_rejected() {
+    members_flag_creator_of_malformed_unit(Malformation::NoOwnParent, 2).await;
+}
+
+#[tokio::test]
+async fn unit_of_round_zero_with_parents_is_rejected() {
+    members_flag_creator_of_malformed_unit(Malformation::ParentsInRoundZero, 0).await;
+}
+
+// Moves all the units of the given creator to a creator out of range, in any message.
+struct OutOfRangeCreator(NodeIndex);
+
+impl NetworkHook for OutOfRangeCreator {
+    fn update_state(&mut self, data: &mut NetworkData, _: NodeIndex, _: NodeIndex) {
+        let units = match data {
+            crate::NetworkData(NetworkDataInner::Units(UnitMessage::NewUnit(uu)))
+            | crate::NetworkData(NetworkDataInner::Units(UnitMessage::ResponseCoord(uu))) => {
+                vec![uu]
+            }
+            crate::NetworkData(NetworkDataInner::Units(UnitMessage::ResponseParents(_, units))) => {
+                units.iter_mut().collect()
+            }
+            _ => return,
+        };
+        for uu in units {
+            if uu.as_signable().creator() == self.0 {
+                uu.as_signable_mut().set_creator(NodeIndex(7));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn units_of_out_of_range_creator_are_rejected() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (mut net_hub, networks) = configure_network(n_members, 1.0);
+    net_hub.add_hook(OutOfRangeCreator(NodeIndex(3)));
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            gen_config(ix, n_members),
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+    let mut batches = Vec::new();
+    for (_, _, batch_rx, _) in members.iter_mut() {
+        let mut member_batches = Vec::new();
+        for _ in 0..5 {
+            member_batches.push(batch_rx.next().await.expect("The member should not exit."));
+        }
+        batches.push(member_batches);
+    }
+    for member_batches in &batches[1..] {
+        assert_eq!(member_batches, &batches[0]);
+    }
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
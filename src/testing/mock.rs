@@ -5,10 +5,10 @@ use parking_lot::Mutex;
 
 use futures::{
     channel::{
-        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        mpsc::{self, unbounded, UnboundedReceiver, UnboundedSender},
         oneshot,
     },
-    Future, StreamExt,
+    Future, Stream, StreamExt,
 };
 
 use std::{
@@ -18,16 +18,16 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::Duration,
 };
 
 use crate::{
-    exponential_slowdown, run_session,
+    run_session,
     runway::{NotificationIn, NotificationOut},
     units::{Unit, UnitCoord},
-    Config, DataIO as DataIOT, DelayConfig, Hasher, Index, KeyBox as KeyBoxT,
-    MultiKeychain as MultiKeychainT, Network as NetworkT, NodeCount, NodeIndex, OrderedBatch,
-    PartialMultisignature as PartialMultisignatureT, Recipient, Round, SpawnHandle, TaskHandle,
+    Config, DataIO as DataIOT, Hasher, Index, KeyBox as KeyBoxT, MultiKeychain as MultiKeychainT,
+    Network as NetworkT, NodeCount, NodeIndex, OrderedBatch,
+    PartialMultisignature as PartialMultisignatureT, Profile, Recipient, Round, SessionError,
+    SessionOutcome, SpawnHandle, TaskHandle,
 };
 
 pub fn init_log() {
@@ -38,21 +38,7 @@ pub fn init_log() {
 }
 
 pub fn gen_config(node_ix: NodeIndex, n_members: NodeCount) -> Config {
-    let delay_config = DelayConfig {
-        tick_interval: Duration::from_millis(5),
-        requests_interval: Duration::from_millis(50),
-        unit_broadcast_delay: Arc::new(|t| exponential_slowdown(t, 100.0, 1, 3.0)),
-        //100, 100, 300, 900, 2700, ...
-        unit_creation_delay: Arc::new(|t| exponential_slowdown(t, 50.0, usize::MAX, 1.000)),
-        //50, 50, 50, 50, ...
-    };
-    Config {
-        node_ix,
-        session_id: 0,
-        n_members,
-        delay_config,
-        max_round: 5000,
-    }
+    Config::with_profile(node_ix, n_members, Profile::Testing)
 }
 
 // A hasher from the standard library that hashes to u64, should be enough to
@@ -81,6 +67,7 @@ pub(crate) type Hash64 = <Hasher64 as Hasher>::Hash;
 pub(crate) struct HonestHub {
     n_members: usize,
     ntfct_out_rxs: HashMap<NodeIndex, UnboundedReceiver<NotificationOut<Hasher64>>>,
+    creator_rxs: HashMap<NodeIndex, mpsc::Receiver<NotificationOut<Hasher64>>>,
     ntfct_in_txs: HashMap<NodeIndex, UnboundedSender<NotificationIn<Hasher64>>>,
     units_by_coord: HashMap<UnitCoord, Unit<Hasher64>>,
 }
@@ -90,6 +77,7 @@ impl HonestHub {
         HonestHub {
             n_members,
             ntfct_out_rxs: HashMap::new(),
+            creator_rxs: HashMap::new(),
             ntfct_in_txs: HashMap::new(),
             units_by_coord: HashMap::new(),
         }
@@ -100,13 +88,16 @@ impl HonestHub {
         node_ix: NodeIndex,
     ) -> (
         UnboundedSender<NotificationOut<Hasher64>>,
+        mpsc::Sender<NotificationOut<Hasher64>>,
         UnboundedReceiver<NotificationIn<Hasher64>>,
     ) {
         let (tx_in, rx_in) = unbounded();
         let (tx_out, rx_out) = unbounded();
+        let (creator_tx, creator_rx) = mpsc::channel(0);
         self.ntfct_in_txs.insert(node_ix, tx_in);
         self.ntfct_out_rxs.insert(node_ix, rx_out);
-        (tx_out, rx_in)
+        self.creator_rxs.insert(node_ix, creator_rx);
+        (tx_out, creator_tx, rx_in)
     }
 
     fn send_to_all(&mut self, ntfct: NotificationIn<Hasher64>) {
@@ -158,38 +149,60 @@ impl HonestHub {
                 // Safe to ignore in testing.
                 // Normally this is used in Member to answer parents requests.
             }
+            NotificationOut::CreatorStalled { .. }
+            | NotificationOut::CreatorLagging { .. }
+            | NotificationOut::MaxRoundApproached(_)
+            | NotificationOut::MaxRoundReached(_) => {
+                // Safe to ignore in testing.
+            }
+            NotificationOut::StartingRoundTooHigh { .. } => {
+                panic!("The starting round should be below the maximum round in testing.");
+            }
+            NotificationOut::RoundLimitReached => {
+                panic!("The round limit should not be reached in testing.");
+            }
+        }
+    }
+}
+
+// Moves all the ready notifications to the buffer, and forgets the closed receivers.
+fn poll_notifications<S: Stream<Item = NotificationOut<Hasher64>> + Unpin>(
+    rxs: &mut HashMap<NodeIndex, S>,
+    buffer: &mut Vec<(NodeIndex, NotificationOut<Hasher64>)>,
+    cx: &mut Context<'_>,
+) {
+    let mut ready_ixs: Vec<NodeIndex> = Vec::new();
+    for (ix, rx) in rxs.iter_mut() {
+        loop {
+            match rx.poll_next_unpin(cx) {
+                Poll::Ready(Some(ntfct)) => {
+                    buffer.push((*ix, ntfct));
+                }
+                Poll::Ready(None) => {
+                    ready_ixs.push(*ix);
+                    break;
+                }
+                Poll::Pending => {
+                    break;
+                }
+            }
         }
     }
+    for ix in ready_ixs {
+        rxs.remove(&ix);
+    }
 }
 
 impl Future for HonestHub {
     type Output = ();
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut ready_ixs: Vec<NodeIndex> = Vec::new();
         let mut buffer = Vec::new();
-        for (ix, rx) in self.ntfct_out_rxs.iter_mut() {
-            loop {
-                match rx.poll_next_unpin(cx) {
-                    Poll::Ready(Some(ntfct)) => {
-                        buffer.push((*ix, ntfct));
-                    }
-                    Poll::Ready(None) => {
-                        ready_ixs.push(*ix);
-                        break;
-                    }
-                    Poll::Pending => {
-                        break;
-                    }
-                }
-            }
-        }
+        poll_notifications(&mut self.ntfct_out_rxs, &mut buffer, cx);
+        poll_notifications(&mut self.creator_rxs, &mut buffer, cx);
         for (ix, ntfct) in buffer {
             self.on_notification(ix, ntfct);
         }
-        for ix in ready_ixs {
-            self.ntfct_out_rxs.remove(&ix);
-        }
-        if self.ntfct_out_rxs.is_empty() {
+        if self.ntfct_out_rxs.is_empty() && self.creator_rxs.is_empty() {
             return Poll::Ready(());
         }
         Poll::Pending
@@ -299,7 +312,7 @@ impl UnreliableRouter {
 
     pub(crate) fn connect_peer(&mut self, peer: NodeIndex) -> Network {
         assert!(
-            self.peer_list.iter().any(|p| *p == peer),
+            self.peer_list.contains(&peer),
             "Must connect a peer in the list."
         );
         assert!(
@@ -415,8 +428,8 @@ impl NetworkHook for AlertHook {
 
 #[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Hash)]
 pub struct Data {
-    coord: UnitCoord,
-    variant: u32,
+    pub(crate) coord: UnitCoord,
+    pub(crate) variant: u32,
 }
 
 impl Data {
@@ -450,6 +463,7 @@ impl PartialMultisignatureT for PartialMultisignature {
 
 pub(crate) struct DataIO {
     ix: NodeIndex,
+    variant: u32,
     round_counter: Cell<Round>,
     tx: UnboundedSender<OrderedBatch<Data>>,
 }
@@ -459,7 +473,10 @@ impl DataIOT<Data> for DataIO {
     fn get_data(&self) -> Data {
         let coord = UnitCoord::new(self.round_counter.get(), self.ix);
         self.round_counter.set(self.round_counter.get() + 1);
-        Data { coord, variant: 0 }
+        Data {
+            coord,
+            variant: self.variant,
+        }
     }
     fn send_ordered_batch(&mut self, data: OrderedBatch<Data>) -> Result<(), ()> {
         self.tx.unbounded_send(data).map_err(|e| {
@@ -470,9 +487,18 @@ impl DataIOT<Data> for DataIO {
 
 impl DataIO {
     pub(crate) fn new(ix: NodeIndex) -> (Self, UnboundedReceiver<OrderedBatch<Data>>) {
+        Self::with_variant(ix, 0)
+    }
+
+    // Proposes data with the given variant, to tell apart the data of several sessions.
+    pub(crate) fn with_variant(
+        ix: NodeIndex,
+        variant: u32,
+    ) -> (Self, UnboundedReceiver<OrderedBatch<Data>>) {
         let (tx, rx) = unbounded();
         let data_io = DataIO {
             ix,
+            variant,
             round_counter: Cell::new(0),
             tx,
         };
@@ -527,26 +553,37 @@ impl MultiKeychainT for KeyBox {
 }
 
 pub(crate) async fn run_honest_member<
+    DP: DataIOT<Data> + Send + 'static,
     N: 'static + NetworkT<Hasher64, Data, Signature, PartialMultisignature>,
 >(
     config: Config,
     network: N,
-    data_io: DataIO,
+    data_io: DP,
     keybox: KeyBox,
     spawn_handle: Spawner,
     exit: oneshot::Receiver<()>,
-) {
-    run_session(config, network, data_io, keybox, spawn_handle, exit).await
+) -> Result<SessionOutcome, SessionError> {
+    let (_, creator_control) = unbounded();
+    run_session(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawn_handle,
+        creator_control,
+        exit,
+    )
+    .await
 }
 
 pub fn configure_network(
     n_members: NodeCount,
     reliability: f64,
 ) -> (UnreliableRouter, Vec<Network>) {
-    let peer_list = n_members.into_iterator().collect();
+    let peer_list = n_members.all_indices().collect();
     let mut router = UnreliableRouter::new(peer_list, reliability);
     let mut networks = Vec::new();
-    for ix in n_members.into_iterator() {
+    for ix in n_members.all_indices() {
         let network = router.connect_peer(ix);
         networks.push(network);
     }
@@ -563,13 +600,26 @@ pub fn spawn_honest_member(
     oneshot::Sender<()>,
     TaskHandle,
 ) {
+    spawn_honest_member_with_config(spawner, gen_config(node_index, n_members), network)
+}
+
+pub fn spawn_honest_member_with_config(
+    spawner: Spawner,
+    config: Config,
+    network: impl 'static + NetworkT<Hasher64, Data, Signature, PartialMultisignature>,
+) -> (
+    UnboundedReceiver<OrderedBatch<Data>>,
+    oneshot::Sender<()>,
+    TaskHandle,
+) {
+    let node_index = config.node_ix;
+    let n_members = config.n_members;
     let (data_io, rx_batch) = DataIO::new(node_index);
-    let config = gen_config(node_index, n_members);
     let (exit_tx, exit_rx) = oneshot::channel();
     let spawner_inner = spawner.clone();
     let member_task = async move {
         let keybox = KeyBox::new(n_members, node_index);
-        run_honest_member(
+        let _ = run_honest_member(
             config,
             network,
             data_io,
@@ -577,7 +627,7 @@ pub fn spawn_honest_member(
             spawner_inner.clone(),
             exit_rx,
         )
-        .await
+        .await;
     };
     let handle = spawner.spawn_essential("member", member_task);
     (rx_batch, exit_tx, handle)
@@ -1,11 +1,27 @@
 #![cfg(test)]
 mod alerts;
+mod backup;
+#[cfg(feature = "bls")]
+mod bls;
 mod byzantine;
+mod clock;
 mod consensus;
 mod crash;
 mod creation;
 mod dag;
+mod data;
+mod finalization;
+#[cfg(feature = "network-loopback")]
+mod loopback;
+mod malformed;
 pub(crate) mod mock;
+mod network;
 mod rmc;
+mod session;
+mod sessions;
 pub(crate) mod signed;
+pub(crate) mod simulation;
+mod status;
+#[cfg(feature = "network-tcp")]
+mod tcp;
 mod unreliable;
@@ -0,0 +1,264 @@
+use futures::{channel::mpsc::UnboundedReceiver, StreamExt};
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use crate::{
+    testing::mock::{Data, Hasher64, NetworkData, PartialMultisignature, Signature},
+    Network as NetworkT, NodeCount, NodeIndex, Recipient,
+};
+
+/// The distribution of the latency of a link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Latency {
+    /// Every message takes exactly this long. Deliveries might then coincide with timers, in
+    /// which case the members handle them in an arbitrary order.
+    Constant(Duration),
+    /// Latencies uniformly distributed between the bounds, with nanosecond resolution.
+    Uniform(Duration, Duration),
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut StdRng) -> Duration {
+        match *self {
+            Latency::Constant(latency) => latency,
+            Latency::Uniform(min, max) => {
+                let (min, max) = (min.as_nanos() as u64, max.as_nanos() as u64);
+                Duration::from_nanos(rng.gen_range(min..=max))
+            }
+        }
+    }
+}
+
+/// How the messages sent over a link behave.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkConfig {
+    pub latency: Latency,
+    /// The probability of silently dropping a message.
+    pub drop_probability: f64,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        LinkConfig {
+            latency: Latency::Uniform(Duration::from_millis(1), Duration::from_millis(50)),
+            drop_probability: 0.0,
+        }
+    }
+}
+
+/// The numbers of messages delivered and dropped by a link.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub delivered: usize,
+    pub dropped: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LinkEvent {
+    Cut,
+    Heal,
+}
+
+/// The behaviour of the links between the members, together with a script of cutting and healing
+/// them at given times since the start of a [`Simulation`](crate::testing::simulation::Simulation).
+/// A cut link drops all the messages, including the ones sent before the cut, which would be
+/// delivered after it.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkModel {
+    default_link: LinkConfig,
+    links: HashMap<(NodeIndex, NodeIndex), LinkConfig>,
+    // Kept sorted by time, the later event for a link wins on ties.
+    events: Vec<(Duration, NodeIndex, NodeIndex, LinkEvent)>,
+}
+
+impl NetworkModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the behaviour of the links without their own configuration.
+    pub fn default_link(mut self, config: LinkConfig) -> Self {
+        self.default_link = config;
+        self
+    }
+
+    /// Sets the behaviour of the link from `sender` to `recipient`.
+    pub fn link(mut self, sender: NodeIndex, recipient: NodeIndex, config: LinkConfig) -> Self {
+        self.links.insert((sender, recipient), config);
+        self
+    }
+
+    /// Cuts the link from `sender` to `recipient` at time `at`.
+    pub fn cut(self, at: Duration, sender: NodeIndex, recipient: NodeIndex) -> Self {
+        self.event(at, sender, recipient, LinkEvent::Cut)
+    }
+
+    /// Heals the link from `sender` to `recipient` at time `at`.
+    pub fn heal(self, at: Duration, sender: NodeIndex, recipient: NodeIndex) -> Self {
+        self.event(at, sender, recipient, LinkEvent::Heal)
+    }
+
+    /// Cuts all the links in both directions between `group` and the rest of the `n_members`
+    /// members for the time between `from` and `until`.
+    pub fn partition(
+        mut self,
+        n_members: NodeCount,
+        group: &[NodeIndex],
+        from: Duration,
+        until: Duration,
+    ) -> Self {
+        for inside in group.iter().copied() {
+            for outside in n_members.all_indices().filter(|ix| !group.contains(ix)) {
+                self = self
+                    .cut(from, inside, outside)
+                    .cut(from, outside, inside)
+                    .heal(until, inside, outside)
+                    .heal(until, outside, inside);
+            }
+        }
+        self
+    }
+
+    fn event(
+        mut self,
+        at: Duration,
+        sender: NodeIndex,
+        recipient: NodeIndex,
+        event: LinkEvent,
+    ) -> Self {
+        let position = self.events.partition_point(|(time, ..)| *time <= at);
+        self.events.insert(position, (at, sender, recipient, event));
+        self
+    }
+
+    pub(crate) fn link_config(&self, sender: NodeIndex, recipient: NodeIndex) -> LinkConfig {
+        self.links
+            .get(&(sender, recipient))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+
+    /// Whether the link from `sender` to `recipient` is cut at time `at`.
+    pub(crate) fn is_cut(&self, at: Duration, sender: NodeIndex, recipient: NodeIndex) -> bool {
+        self.events
+            .iter()
+            .take_while(|(time, ..)| *time <= at)
+            .filter(|(_, from, to, _)| (*from, *to) == (sender, recipient))
+            .last()
+            .is_some_and(|(.., event)| *event == LinkEvent::Cut)
+    }
+
+    /// When a message sent at `now` over the link from `sender` to `recipient` should be
+    /// delivered, or `None` if it should be dropped.
+    pub(crate) fn delivery_time(
+        &self,
+        now: Duration,
+        sender: NodeIndex,
+        recipient: NodeIndex,
+        rng: &mut StdRng,
+    ) -> Option<Duration> {
+        let config = self.link_config(sender, recipient);
+        // Sampling both values every time keeps the random choices of a link independent of
+        // whether it is cut.
+        let latency = config.latency.sample(rng);
+        let dropped = rng.gen_bool(config.drop_probability);
+        if dropped || self.is_cut(now, sender, recipient) {
+            return None;
+        }
+        Some(now + latency)
+    }
+}
+
+pub(crate) type Outbox = Arc<Mutex<Vec<(NodeIndex, NodeIndex, NetworkData)>>>;
+
+/// The [`Network`](crate::Network) of a single member in a
+/// [`Simulation`](crate::testing::simulation::Simulation). The messages only leave it when the
+/// simulation routes them according to its [`NetworkModel`].
+pub struct MockNetwork {
+    index: NodeIndex,
+    n_members: NodeCount,
+    outbox: Outbox,
+    incoming: UnboundedReceiver<NetworkData>,
+}
+
+impl MockNetwork {
+    pub(crate) fn new(
+        index: NodeIndex,
+        n_members: NodeCount,
+        outbox: Outbox,
+        incoming: UnboundedReceiver<NetworkData>,
+    ) -> Self {
+        MockNetwork {
+            index,
+            n_members,
+            outbox,
+            incoming,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl NetworkT<Hasher64, Data, Signature, PartialMultisignature> for MockNetwork {
+    fn send(&self, data: NetworkData, recipient: Recipient) {
+        let mut outbox = self.outbox.lock();
+        match recipient {
+            Recipient::Node(node) => outbox.push((self.index, node, data)),
+            Recipient::Everyone => {
+                for node in self.n_members.all_indices() {
+                    if node != self.index {
+                        outbox.push((self.index, node, data.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData> {
+        self.incoming.next().await
+    }
+}
+
+#[test]
+fn partitions_cut_and_heal_links() {
+    let model = NetworkModel::new().partition(
+        NodeCount(3),
+        &[NodeIndex(0)],
+        Duration::from_secs(1),
+        Duration::from_secs(2),
+    );
+    let at = Duration::from_millis;
+    assert!(!model.is_cut(at(999), NodeIndex(0), NodeIndex(1)));
+    assert!(model.is_cut(at(1000), NodeIndex(0), NodeIndex(1)));
+    assert!(model.is_cut(at(1500), NodeIndex(2), NodeIndex(0)));
+    assert!(!model.is_cut(at(1500), NodeIndex(1), NodeIndex(2)));
+    assert!(!model.is_cut(at(2000), NodeIndex(0), NodeIndex(1)));
+}
+
+#[test]
+fn links_follow_their_config() {
+    let lossy = LinkConfig {
+        latency: Latency::Constant(Duration::from_millis(10)),
+        drop_probability: 1.0,
+    };
+    let slow = LinkConfig {
+        latency: Latency::Uniform(Duration::from_millis(100), Duration::from_millis(200)),
+        drop_probability: 0.0,
+    };
+    let model = NetworkModel::new()
+        .default_link(slow)
+        .link(NodeIndex(0), NodeIndex(1), lossy);
+    let mut rng = StdRng::seed_from_u64(0);
+    let now = Duration::from_secs(1);
+    for _ in 0..100 {
+        assert_eq!(
+            model.delivery_time(now, NodeIndex(0), NodeIndex(1), &mut rng),
+            None
+        );
+        let delivery = model
+            .delivery_time(now, NodeIndex(1), NodeIndex(0), &mut rng)
+            .expect("the link is reliable");
+        assert!(delivery >= now + Duration::from_millis(100));
+        assert!(delivery <= now + Duration::from_millis(200));
+    }
+}
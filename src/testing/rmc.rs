@@ -1,11 +1,12 @@
 use crate::{
+    exponential_schedule,
     nodes::NodeCount,
     rmc::*,
     signed::*,
     testing::signed::{
         test_multi_keychain, TestMultiKeychain, TestPartialMultisignature, TestSignature,
     },
-    NodeIndex,
+    Clock, MockClock, NodeIndex,
 };
 use futures::{
     channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
@@ -14,7 +15,7 @@ use futures::{
     StreamExt,
 };
 use rand::Rng;
-use std::{collections::HashMap, pin::Pin, time::Duration};
+use std::{collections::HashMap, pin::Pin, sync::Arc, time::Duration};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Ord, PartialOrd)]
 struct Hash {
@@ -214,6 +215,34 @@ async fn node_hearing_only_multisignatures() {
     }
 }
 
+/// A node reports every new signature it collects, starting with its own, until the
+/// multisignature is complete.
+#[tokio::test]
+async fn reports_collection_progress() {
+    let node_count = NodeCount(10);
+    let keychains = prepare_keychains(node_count);
+    let mut data = TestData::new(node_count, &keychains, |_, _| true);
+    let (progress_tx, mut progress_rx) = unbounded();
+    let rmc = data.rmcs.remove(0).with_progress(progress_tx);
+    data.rmcs.insert(0, rmc);
+
+    let hash = Hash { byte: 56 };
+    for i in 0..node_count.0 {
+        data.rmcs[i].start_rmc(hash).await;
+    }
+    data.collect_multisigned_hashes(node_count.0).await;
+
+    let mut collected = Vec::new();
+    while let Ok(progress) = progress_rx.try_recv() {
+        assert_eq!(progress.hash, hash);
+        assert_eq!(progress.needed, NodeCount(7));
+        collected.push(progress.collected.0);
+    }
+    assert_eq!(collected.first(), Some(&1));
+    assert!(collected.windows(2).all(|pair| pair[0] + 1 == pair[1]));
+    assert!(collected.last() <= Some(&7));
+}
+
 fn bad_signature() -> TestSignature {
     TestSignature {
         msg: Vec::new(),
@@ -258,3 +287,152 @@ async fn bad_signatures_and_multisignatures_are_ignored() {
         assert_eq!(multisignatures[0].as_signable(), &hash);
     }
 }
+
+fn scheduler_with_mock_clock(clock: &MockClock) -> DelayScheduler<Task<Hash, TestMultiKeychain>> {
+    DelayScheduler::new(
+        exponential_schedule(Duration::from_millis(100), 2.0, Duration::from_secs(10)),
+        Arc::new(clock.clone()),
+    )
+}
+
+/// A single node retransmits its signed hash with the configured backoff.
+#[tokio::test]
+async fn retransmits_with_backoff() {
+    let node_count = NodeCount(4);
+    let keychains = prepare_keychains(node_count);
+    let clock = MockClock::new();
+    let (_incoming_tx, incoming_rx) = unbounded();
+    let (outgoing_tx, mut outgoing_rx) = unbounded();
+    let mut rmc = ReliableMulticast::new(
+        incoming_rx,
+        outgoing_tx,
+        &keychains[0],
+        node_count,
+        scheduler_with_mock_clock(&clock),
+    );
+    let hash = Hash { byte: 56 };
+    rmc.start_rmc(hash).await;
+
+    let start = clock.now();
+    let mut sent_at = Vec::new();
+    let multisigned = rmc.next_multisigned_hash();
+    futures::pin_mut!(multisigned);
+    for _ in 0..200 {
+        assert!(futures::poll!(&mut multisigned).is_pending());
+        while let Ok(message) = outgoing_rx.try_recv() {
+            assert!(matches!(message, Message::SignedHash(_)));
+            sent_at.push(clock.now() - start);
+        }
+        clock.advance(Duration::from_millis(10));
+    }
+    let expected: Vec<_> = [0, 100, 300, 700, 1500]
+        .iter()
+        .map(|ms| Duration::from_millis(*ms))
+        .collect();
+    assert_eq!(sent_at, expected);
+}
+
+/// A complete multisignature arriving together with a due retransmission stops the
+/// retransmissions of the signed hash.
+#[tokio::test]
+async fn completion_racing_with_retransmission() {
+    let node_count = NodeCount(4);
+    let keychains = prepare_keychains(node_count);
+    let hash = Hash { byte: 56 };
+    for _ in 0..20 {
+        let clock = MockClock::new();
+        let (incoming_tx, incoming_rx) = unbounded();
+        let (outgoing_tx, mut outgoing_rx) = unbounded();
+        let mut rmc = ReliableMulticast::new(
+            incoming_rx,
+            outgoing_tx,
+            &keychains[0],
+            node_count,
+            scheduler_with_mock_clock(&clock),
+        );
+        rmc.start_rmc(hash).await;
+        {
+            let multisigned = rmc.next_multisigned_hash();
+            futures::pin_mut!(multisigned);
+            assert!(futures::poll!(&mut multisigned).is_pending());
+        }
+        assert!(matches!(outgoing_rx.try_recv(), Ok(Message::SignedHash(_))));
+
+        let mut partial = PartiallyMultisigned::sign(hash, &keychains[1]).await;
+        for keychain in keychains.iter().skip(2) {
+            let signed = Signed::sign_with_index(hash, keychain).await;
+            partial = partial.add_signature(signed, keychain);
+        }
+        let multisigned = match partial {
+            PartiallyMultisigned::Complete { multisigned } => multisigned,
+            _ => panic!("three signatures should form a complete multisignature"),
+        };
+        incoming_tx
+            .unbounded_send(Message::MultisignedHash(multisigned.into_unchecked()))
+            .expect("the rmc should be listening");
+        clock.advance(Duration::from_millis(100));
+
+        let multisigned = rmc.next_multisigned_hash().await;
+        assert_eq!(multisigned.as_signable(), &hash);
+        clock.advance(Duration::from_secs(10));
+        let multisigned = rmc.next_multisigned_hash();
+        futures::pin_mut!(multisigned);
+        assert!(futures::poll!(&mut multisigned).is_pending());
+        while let Ok(message) = outgoing_rx.try_recv() {
+            assert!(matches!(message, Message::MultisignedHash(_)));
+        }
+    }
+}
+
+/// A stopped rmc no longer retransmits the signed hash, but still accepts a complete
+/// multisignature of it.
+#[tokio::test]
+async fn stopped_rmc_stops_retransmitting() {
+    let node_count = NodeCount(4);
+    let keychains = prepare_keychains(node_count);
+    let clock = MockClock::new();
+    let (incoming_tx, incoming_rx) = unbounded();
+    let (outgoing_tx, mut outgoing_rx) = unbounded();
+    let mut rmc = ReliableMulticast::new(
+        incoming_rx,
+        outgoing_tx,
+        &keychains[0],
+        node_count,
+        scheduler_with_mock_clock(&clock),
+    );
+    let hash = Hash { byte: 56 };
+    rmc.start_rmc(hash).await;
+    {
+        let multisigned = rmc.next_multisigned_hash();
+        futures::pin_mut!(multisigned);
+        assert!(futures::poll!(&mut multisigned).is_pending());
+    }
+    assert!(matches!(outgoing_rx.try_recv(), Ok(Message::SignedHash(_))));
+
+    rmc.stop_rmc(&hash);
+    {
+        let multisigned = rmc.next_multisigned_hash();
+        futures::pin_mut!(multisigned);
+        for _ in 0..100 {
+            assert!(futures::poll!(&mut multisigned).is_pending());
+            clock.advance(Duration::from_millis(100));
+        }
+    }
+    assert!(outgoing_rx.try_recv().is_err());
+
+    let mut partial = PartiallyMultisigned::sign(hash, &keychains[1]).await;
+    for keychain in keychains.iter().skip(2) {
+        let signed = Signed::sign_with_index(hash, keychain).await;
+        partial = partial.add_signature(signed, keychain);
+    }
+    let multisigned = match partial {
+        PartiallyMultisigned::Complete { multisigned } => multisigned,
+        _ => panic!("three signatures should form a complete multisignature"),
+    };
+    incoming_tx
+        .unbounded_send(Message::MultisignedHash(multisigned.into_unchecked()))
+        .expect("the rmc should be listening");
+    let multisigned = rmc.next_multisigned_hash().await;
+    assert_eq!(multisigned.as_signable(), &hash);
+    assert!(rmc.get_multisigned(&hash).is_some());
+}
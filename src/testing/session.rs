@@ -0,0 +1,858 @@
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    FutureExt, StreamExt,
+};
+use std::{io, sync::Arc, time::Duration};
+
+use crate::{
+    backup::{self, Record},
+    member::UnitMessage,
+    network::NetworkDataInner,
+    nodes::NodeMap,
+    run_session,
+    testing::mock::{
+        self, configure_network, gen_config, init_log, spawn_honest_member, Data, DataIO, Hasher64,
+        KeyBox, NetworkData, PartialMultisignature, Signature, Spawner,
+    },
+    units::{ControlHash, FullUnit, PreUnit, UnitCoord},
+    BackupSource, ConfigBuilder, MaxRoundPolicy, MemoryBackup, Network, NodeCount, NodeIndex,
+    ParentSelector, Recipient, Round, SessionBuilder, SessionError, SessionOutcome, Signed,
+    SpawnHandle, TaskPanicPolicy,
+};
+
+// Stops working right away.
+struct DeadNetwork;
+
+#[async_trait::async_trait]
+impl Network<Hasher64, Data, Signature, PartialMultisignature> for DeadNetwork {
+    fn send(&self, _data: NetworkData, _recipient: Recipient) {}
+
+    async fn next_event(&mut self) -> Option<NetworkData> {
+        None
+    }
+}
+
+// Passes the unit of another process using our index after some messages.
+struct ImpersonatedNetwork {
+    network: mock::Network,
+    forged: Option<NetworkData>,
+    n_events: usize,
+}
+
+#[async_trait::async_trait]
+impl Network<Hasher64, Data, Signature, PartialMultisignature> for ImpersonatedNetwork {
+    fn send(&self, data: NetworkData, recipient: Recipient) {
+        self.network.send(data, recipient)
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData> {
+        self.n_events += 1;
+        if self.n_events > 30 {
+            if let Some(forged) = self.forged.take() {
+                return Some(forged);
+            }
+        }
+        self.network.next_event().await
+    }
+}
+
+// One of the connections of a member to the mock network, which the member can replace with a
+// new one mid-session.
+struct Connection {
+    outgoing: UnboundedSender<(NetworkData, Recipient)>,
+    incoming: UnboundedReceiver<NetworkData>,
+}
+
+#[async_trait::async_trait]
+impl Network<Hasher64, Data, Signature, PartialMultisignature> for Connection {
+    fn send(&self, data: NetworkData, recipient: Recipient) {
+        let _ = self.outgoing.unbounded_send((data, recipient));
+    }
+
+    async fn next_event(&mut self) -> Option<NetworkData> {
+        self.incoming.next().await
+    }
+}
+
+// Passes the messages between the mock network and the latest connection. The earlier
+// connections are kept open, so that they only stop working once the member drops them.
+async fn relay(
+    mut network: mock::Network,
+    mut outgoing: UnboundedReceiver<(NetworkData, Recipient)>,
+    mut connections: UnboundedReceiver<UnboundedSender<NetworkData>>,
+) {
+    let mut incoming: Vec<UnboundedSender<NetworkData>> = Vec::new();
+    loop {
+        futures::select! {
+            message = outgoing.next() => match message {
+                Some((data, recipient)) => network.send(data, recipient),
+                None => return,
+            },
+            connection = connections.next() => match connection {
+                Some(connection) => incoming.push(connection),
+                None => return,
+            },
+            data = network.next_event().fuse() => match (data, incoming.last()) {
+                (Some(data), Some(connection)) => {
+                    let _ = connection.unbounded_send(data);
+                }
+                (Some(_), None) => {}
+                (None, _) => return,
+            },
+        }
+    }
+}
+
+struct UnreadableBackup;
+
+#[async_trait::async_trait]
+impl BackupSource for UnreadableBackup {
+    async fn load(&self) -> io::Result<Vec<u8>> {
+        Err(io::Error::other("the disk is gone"))
+    }
+}
+
+#[tokio::test]
+async fn session_exits_cleanly() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let (data_io, mut batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = tokio::spawn(run_session(
+        gen_config(ix, n_members),
+        network,
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner.clone(),
+        creator_control,
+        exit_rx,
+    ));
+    batch_rx.next().await.expect("The member should not exit.");
+    let _ = exit_tx.send(());
+    assert_eq!(
+        session.await.expect("The session should not panic."),
+        Ok(SessionOutcome::Exited)
+    );
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn session_fails_without_network() {
+    init_log();
+    let n_members = NodeCount(4);
+    let ix = NodeIndex(0);
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        gen_config(ix, n_members),
+        DeadNetwork,
+        data_io,
+        KeyBox::new(n_members, ix),
+        Spawner::new(),
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    assert_eq!(result, Err(SessionError::Network));
+}
+
+#[tokio::test]
+async fn session_from_builder_exits_cleanly() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let (data_io, mut batch_rx) = DataIO::new(ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let builder = SessionBuilder::new(gen_config(ix, n_members))
+        .network(network)
+        .data_io(data_io)
+        .keychain(KeyBox::new(n_members, ix))
+        .spawn_handle(spawner.clone())
+        .exit(exit_rx);
+    let status = builder.status();
+    let session = tokio::spawn(builder.run());
+    batch_rx.next().await.expect("The member should not exit.");
+    assert!(status.current_round().is_some());
+    let _ = exit_tx.send(());
+    assert_eq!(
+        session.await.expect("The session should not panic."),
+        Ok(SessionOutcome::Exited)
+    );
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn session_from_builder_runs_without_exit_channel() {
+    init_log();
+    let n_members = NodeCount(4);
+    let ix = NodeIndex(0);
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let result = SessionBuilder::new(gen_config(ix, n_members))
+        .network(DeadNetwork)
+        .data_io(data_io)
+        .keychain(KeyBox::new(n_members, ix))
+        .spawn_handle(Spawner::new())
+        .run()
+        .await;
+    // Without the exit channel being kept open, the session would exit before the network fails.
+    assert_eq!(result, Err(SessionError::Network));
+}
+
+#[tokio::test]
+async fn session_keeps_finalizing_after_replacing_the_network() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (batch_rx, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let (outgoing_tx, outgoing_rx) = unbounded();
+    let (connections_tx, connections_rx) = unbounded();
+    spawner.spawn("relay", relay(network, outgoing_rx, connections_rx));
+    let connect = || {
+        let (incoming_tx, incoming) = unbounded();
+        connections_tx
+            .unbounded_send(incoming_tx)
+            .expect("The relay should be running.");
+        Connection {
+            outgoing: outgoing_tx.clone(),
+            incoming,
+        }
+    };
+
+    let (data_io, batch_rx) = DataIO::new(ix);
+    batch_rxs.insert(0, batch_rx);
+    let (new_networks_tx, new_networks) = unbounded();
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = tokio::spawn(
+        SessionBuilder::new(gen_config(ix, n_members))
+            .network(connect())
+            .network_replacements(new_networks)
+            .data_io(data_io)
+            .keychain(KeyBox::new(n_members, ix))
+            .spawn_handle(spawner.clone())
+            .exit(exit_rx)
+            .run(),
+    );
+
+    let mut batches = vec![Vec::new(); n_members.0];
+    for n_replacements in 0..=3 {
+        if n_replacements > 0 {
+            new_networks_tx
+                .unbounded_send(connect())
+                .expect("The session should be running.");
+        }
+        for (batch_rx, batches) in batch_rxs.iter_mut().zip(batches.iter_mut()) {
+            for _ in 0..5 {
+                batches.push(batch_rx.next().await.expect("The member should not exit."));
+            }
+        }
+    }
+    for member_batches in &batches[1..] {
+        assert_eq!(member_batches, &batches[0]);
+    }
+
+    let _ = exit_tx.send(());
+    assert_eq!(
+        session.await.expect("The session should not panic."),
+        Ok(SessionOutcome::Exited)
+    );
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn single_node_orders_its_units_right_away() {
+    init_log();
+    let n_members = NodeCount(1);
+    let ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let config = ConfigBuilder::single_node()
+        .allow_small_committee(true)
+        .delay_config(gen_config(ix, n_members).delay_config)
+        .build()
+        .expect("A single node should be allowed.");
+    let (data_io, mut batch_rx) = DataIO::new(ix);
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = tokio::spawn(
+        SessionBuilder::new(config)
+            .network(networks.remove(0))
+            .data_io(data_io)
+            .keychain(KeyBox::new(n_members, ix))
+            .spawn_handle(spawner.clone())
+            .exit(exit_rx)
+            .run(),
+    );
+
+    // Well before the others could have told us about our newest unit, if there were any.
+    let batches = tokio::time::timeout(Duration::from_secs(2), async {
+        let mut batches = Vec::new();
+        for _ in 0..10 {
+            batches.push(batch_rx.next().await.expect("The member should not exit."));
+        }
+        batches
+    })
+    .await
+    .expect("The units should be ordered right away.");
+    for (round, batch) in batches.into_iter().enumerate() {
+        let coords: Vec<_> = batch.into_iter().map(|data| data.coord).collect();
+        assert_eq!(coords, vec![UnitCoord::new(round as Round, ix)]);
+    }
+
+    let _ = exit_tx.send(());
+    assert_eq!(
+        session.await.expect("The session should not panic."),
+        Ok(SessionOutcome::Exited)
+    );
+}
+
+#[tokio::test]
+async fn session_fails_with_unreadable_backup() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let network = networks.remove(0);
+    let ix = network.index();
+    let mut config = gen_config(ix, n_members);
+    config.backup_source = Arc::new(UnreadableBackup);
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        config,
+        network,
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner,
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    match result {
+        Err(SessionError::Backup(e)) => assert!(e.contains("the disk is gone")),
+        result => panic!("Unexpected result {:?}.", result),
+    }
+}
+
+#[tokio::test]
+async fn session_fails_when_impersonated() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let config = gen_config(ix, n_members);
+    let keybox = KeyBox::new(n_members, ix);
+    // Our unit of round zero, but with different data.
+    let full_unit = FullUnit::new(
+        PreUnit::new(ix, 0, ControlHash::new(&NodeMap::with_size(n_members))),
+        Data::new(UnitCoord::new(0, ix), 1),
+        config.session_id,
+    );
+    let forged = Signed::sign(full_unit, &keybox).await.into();
+    let network = ImpersonatedNetwork {
+        network,
+        forged: Some(crate::NetworkData(NetworkDataInner::Units(
+            UnitMessage::NewUnit(forged),
+        ))),
+        n_events: 0,
+    };
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawner,
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    assert_eq!(result, Err(SessionError::DuplicateNodeIndex { round: 0 }));
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn session_fails_with_too_much_data() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let mut config = gen_config(ix, n_members);
+    config.max_data_size = Some(1);
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        config,
+        network,
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner,
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    match result {
+        Err(SessionError::DataTooLarge {
+            round: 0,
+            max_data_size: 1,
+            ..
+        }) => {}
+        result => panic!("Unexpected result {:?}.", result),
+    }
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+// A backup with two different units of ours of round zero, which we would never save.
+async fn backup_with_own_fork(
+    ix: NodeIndex,
+    n_members: NodeCount,
+    keybox: &KeyBox,
+) -> MemoryBackup {
+    let backup = MemoryBackup::new();
+    // Loading an empty backup writes its header.
+    backup::load_records::<Hasher64, Data, Signature>(&backup, &backup)
+        .await
+        .expect("loading works");
+    let mut records = Vec::new();
+    for variant in 0..2 {
+        let full_unit = FullUnit::<Hasher64, Data>::new(
+            PreUnit::new(ix, 0, ControlHash::new(&NodeMap::with_size(n_members))),
+            Data::new(UnitCoord::new(0, ix), variant),
+            0,
+        );
+        records.push(Record::Unit(Signed::sign(full_unit, keybox).await.into()));
+    }
+    backup::save_records(&backup, &records)
+        .await
+        .expect("saving works");
+    backup
+}
+
+#[tokio::test]
+async fn session_refuses_inconsistent_backup() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let network = networks.remove(0);
+    let ix = network.index();
+    let keybox = KeyBox::new(n_members, ix);
+    let backup = Arc::new(backup_with_own_fork(ix, n_members, &keybox).await);
+    let mut config = gen_config(ix, n_members);
+    config.backup_sink = backup.clone();
+    config.backup_source = backup;
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawner,
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    match result {
+        Err(SessionError::InconsistentBackup { round: 0, reason }) => {
+            assert!(reason.contains("two different units of ours"))
+        }
+        result => panic!("Unexpected result {:?}.", result),
+    }
+}
+
+// Panics when choosing the parents of the unit of the given round.
+struct PanickingParentSelector(Round);
+
+impl ParentSelector for PanickingParentSelector {
+    fn select(&self, candidates: &[NodeIndex], round: Round) -> Vec<NodeIndex> {
+        if round == self.0 {
+            panic!("no parents for round {}", round);
+        }
+        candidates.to_vec()
+    }
+}
+
+#[tokio::test]
+async fn session_reports_panic_of_its_task() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let mut config = gen_config(ix, n_members);
+    config.parent_selector = Arc::new(PanickingParentSelector(2));
+    config.task_panic_policy = TaskPanicPolicy::Report;
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        config,
+        network,
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner,
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    match result {
+        Err(SessionError::TaskPanicked { task, message }) => {
+            assert_eq!(task, "consensus/creation");
+            assert_eq!(message, "no parents for round 2");
+        }
+        result => panic!("Unexpected result {:?}.", result),
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn session_truncates_inconsistent_backup() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let keybox = KeyBox::new(n_members, ix);
+    let backup = backup_with_own_fork(ix, n_members, &keybox).await;
+    let mut config = gen_config(ix, n_members);
+    config.backup_sink = Arc::new(backup.clone());
+    config.backup_source = Arc::new(backup.clone());
+    config.truncate_inconsistent_backup = true;
+    let (data_io, mut batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = tokio::spawn(run_session(
+        config,
+        network,
+        data_io,
+        keybox,
+        spawner.clone(),
+        creator_control,
+        exit_rx,
+    ));
+    batch_rx.next().await.expect("The member should not exit.");
+    let _ = exit_tx.send(());
+    assert_eq!(
+        session.await.expect("The session should not panic."),
+        Ok(SessionOutcome::Exited)
+    );
+    let (records, _) = backup::load_records::<Hasher64, Data, Signature>(&backup, &backup)
+        .await
+        .expect("loading works");
+    assert_eq!(backup::check_units(&records, ix), Ok(()));
+
+    for exit in exits {
+        let _ = exit.send(());
+    }
+}
+
+#[tokio::test]
+async fn session_refuses_backup_of_other_session() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut exits = Vec::new();
+    for network in networks.split_off(1) {
+        let ix = network.index();
+        let (_, exit_tx, _) = spawn_honest_member(spawner.clone(), ix, n_members, network);
+        exits.push(exit_tx);
+    }
+    let network = networks.remove(0);
+    let ix = network.index();
+    let backup = MemoryBackup::new();
+    let mut config = gen_config(ix, n_members);
+    config.backup_sink = Arc::new(backup.clone());
+    config.backup_source = Arc::new(backup.clone());
+    config.session_tag = b"epoch 1".to_vec();
+    let (data_io, mut batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let session = tokio::spawn(run_session(
+        config,
+        network,
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner.clone(),
+        creator_control,
+        exit_rx,
+    ));
+    batch_rx.next().await.expect("The member should not exit.");
+    let _ = exit_tx.send(());
+    assert_eq!(
+        session.await.expect("The session should not panic."),
+        Ok(SessionOutcome::Exited)
+    );
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    let (records, _) = backup::load_records::<Hasher64, Data, Signature>(&backup, &backup)
+        .await
+        .expect("loading works");
+    assert_eq!(records[0], Record::SessionTag(b"epoch 1".to_vec()));
+    assert!(records.len() > 1);
+
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let contents = backup.contents();
+    let mut config = gen_config(ix, n_members);
+    config.backup_sink = Arc::new(backup.clone());
+    config.backup_source = Arc::new(backup.clone());
+    config.session_tag = b"epoch 2".to_vec();
+    let (data_io, _batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (_exit_tx, exit_rx) = oneshot::channel();
+    let result = run_session(
+        config,
+        networks.remove(0),
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner,
+        creator_control,
+        exit_rx,
+    )
+    .await;
+    assert_eq!(
+        result,
+        Err(SessionError::BackupOfOtherSession {
+            expected: b"epoch 2".to_vec(),
+            found: b"epoch 1".to_vec(),
+        })
+    );
+    assert_eq!(backup.contents(), contents);
+}
+
+#[tokio::test]
+async fn sessions_complete_at_max_round_with_shutdown_policy() {
+    init_log();
+    let n_members = NodeCount(4);
+    let max_round = 10;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut sessions = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.max_round = max_round;
+        config.max_round_warning_margin = 3;
+        config.max_round_policy = MaxRoundPolicy::ShutdownSession;
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let builder = SessionBuilder::new(config)
+            .network(network)
+            .data_io(data_io)
+            .keychain(KeyBox::new(n_members, ix))
+            .spawn_handle(spawner.clone());
+        let status = builder.status();
+        sessions.push((tokio::spawn(builder.run()), status, batch_rx));
+    }
+
+    for (session, status, mut batch_rx) in sessions {
+        assert_eq!(
+            session.await.expect("The session should not panic."),
+            Ok(SessionOutcome::Completed)
+        );
+        assert_eq!(status.max_round_approached(), Some(max_round));
+        assert_eq!(status.max_round_reached(), Some(max_round));
+        assert_eq!(status.current_round(), Some(max_round - 1));
+        // The rounds well below the maximum one are finalized before stopping.
+        let mut n_batches = 0;
+        while batch_rx.next().await.is_some() {
+            n_batches += 1;
+        }
+        assert!(n_batches > 0);
+    }
+}
+
+#[tokio::test]
+async fn sessions_wait_at_max_round_with_wait_policy() {
+    init_log();
+    let n_members = NodeCount(4);
+    let max_round = 5;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.max_round = max_round;
+        config.max_round_policy = MaxRoundPolicy::Wait;
+        let (data_io, _) = DataIO::new(ix);
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let builder = SessionBuilder::new(config)
+            .network(network)
+            .data_io(data_io)
+            .keychain(KeyBox::new(n_members, ix))
+            .spawn_handle(spawner.clone())
+            .exit(exit_rx);
+        let status = builder.status();
+        members.push((tokio::spawn(builder.run()), status, exit_tx));
+    }
+
+    for (session, status, _) in &members {
+        while status.max_round_reached().is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(status.max_round_reached(), Some(max_round));
+        assert_eq!(status.current_round(), Some(max_round - 1));
+        assert!(!session.is_finished());
+    }
+    for (session, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(
+            session.await.expect("The session should not panic."),
+            Ok(SessionOutcome::Exited)
+        );
+    }
+}
+
+#[tokio::test]
+async fn sessions_go_on_past_max_round_with_extend_policy() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.max_round = 5;
+        config.max_round_policy = MaxRoundPolicy::Extend(5);
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let builder = SessionBuilder::new(config)
+            .network(network)
+            .data_io(data_io)
+            .keychain(KeyBox::new(n_members, ix))
+            .spawn_handle(spawner.clone())
+            .exit(exit_rx);
+        let status = builder.status();
+        members.push((tokio::spawn(builder.run()), status, batch_rx, exit_tx));
+    }
+
+    for (_, status, batch_rx, _) in members.iter_mut() {
+        // Units above round 12 need the maximum round to have been extended twice.
+        while status
+            .last_finalized_round()
+            .is_none_or(|round| round <= 12)
+        {
+            batch_rx.next().await.expect("The member should not exit.");
+        }
+        assert!(status.max_round_reached() >= Some(10));
+    }
+    for (session, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(
+            session.await.expect("The session should not panic."),
+            Ok(SessionOutcome::Exited)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    testing::mock::{
+        gen_config, init_log, run_honest_member, Data, DataIO, Hasher64, KeyBox,
+        PartialMultisignature, Signature, Spawner,
+    },
+    FileBackup, NodeCount, NodeIndex, OrderedBatch, Recipient, SessionData, SessionId,
+    SessionMultiplexer, SessionNetwork, SpawnHandle, TaskHandle,
+};
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    StreamExt,
+};
+use std::{path::Path, sync::Arc};
+
+type TestSessionData = SessionData<Hasher64, Data, Signature, PartialMultisignature>;
+
+// Connects the members of a committee in memory, carrying the messages of all the sessions.
+struct SharedNetwork {
+    node_ix: NodeIndex,
+    peers: Vec<UnboundedSender<TestSessionData>>,
+    incoming: UnboundedReceiver<TestSessionData>,
+}
+
+impl SharedNetwork {
+    fn connected(n_members: NodeCount) -> Vec<Self> {
+        let (peers, incoming): (Vec<_>, Vec<_>) = (0..n_members.0).map(|_| unbounded()).unzip();
+        incoming
+            .into_iter()
+            .enumerate()
+            .map(|(ix, incoming)| SharedNetwork {
+                node_ix: NodeIndex(ix),
+                peers: peers.clone(),
+                incoming,
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionNetwork<Hasher64, Data, Signature, PartialMultisignature> for SharedNetwork {
+    fn send(&self, data: TestSessionData, recipient: Recipient) {
+        for (ix, peer) in self.peers.iter().enumerate() {
+            let addressed = match recipient {
+                Recipient::Node(peer) => peer.0 == ix,
+                Recipient::Everyone => ix != self.node_ix.0,
+            };
+            if addressed {
+                let _ = peer.unbounded_send(data.clone());
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<TestSessionData> {
+        self.incoming.next().await
+    }
+}
+
+struct RunningSession {
+    batches: UnboundedReceiver<OrderedBatch<Data>>,
+    exit: oneshot::Sender<()>,
+    handle: TaskHandle,
+}
+
+fn start_session(
+    spawner: &Spawner,
+    multiplexer: &SessionMultiplexer<Hasher64, Data, Signature, PartialMultisignature>,
+    node_ix: NodeIndex,
+    n_members: NodeCount,
+    session_id: SessionId,
+    backup_dir: &Path,
+) -> RunningSession {
+    let network = multiplexer
+        .session_network(session_id)
+        .expect("the session is not running yet");
+    let backup = Arc::new(FileBackup::for_session(backup_dir, session_id));
+    let mut config = gen_config(node_ix, n_members);
+    config.session_id = session_id;
+    config.backup_sink = backup.clone();
+    config.backup_source = backup;
+    let (data_io, batches) = DataIO::with_variant(node_ix, session_id as u32);
+    let (exit, exit_rx) = oneshot::channel();
+    let keybox = KeyBox::new(n_members, node_ix);
+    let spawner_inner = spawner.clone();
+    let handle = spawner.spawn_essential("member", async move {
+        let _ = run_honest_member(config, network, data_io, keybox, spawner_inner, exit_rx).await;
+    });
+    RunningSession {
+        batches,
+        exit,
+        handle,
+    }
+}
+
+async fn next_batches(sessions: &mut [RunningSession], n_batches: usize) -> Vec<Vec<Data>> {
+    let mut batches = Vec::new();
+    for session in sessions {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..n_batches {
+            let batch = session
+                .batches
+                .next()
+                .await
+                .expect("the member should not exit");
+            batches_per_ix.extend(batch);
+        }
+        batches.push(batches_per_ix);
+    }
+    batches
+}
+
+fn assert_agree(batches: &[Vec<Data>], session_id: SessionId) {
+    for batches_per_ix in &batches[1..] {
+        assert_eq!(&batches[0], batches_per_ix);
+    }
+    assert!(batches[0]
+        .iter()
+        .all(|data| data.variant == session_id as u32));
+}
+
+async fn stop(sessions: Vec<RunningSession>) {
+    for session in &sessions {
+        assert!(!session.exit.is_canceled());
+    }
+    for RunningSession { exit, handle, .. } in sessions {
+        let _ = exit.send(());
+        let _ = handle.await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn overlapping_sessions_finalize_independently() {
+    init_log();
+    let n_members = NodeCount(4);
+    let n_batches = 3;
+    let spawner = Spawner::new();
+    let backup_root =
+        std::env::temp_dir().join(format!("aleph-bft-sessions-{}", std::process::id()));
+    let backup_dirs: Vec<_> = n_members
+        .all_indices()
+        .map(|ix| backup_root.join(format!("member{}", ix.0)))
+        .collect();
+    for dir in &backup_dirs {
+        std::fs::create_dir_all(dir).expect("the temporary directory is writable");
+    }
+    let multiplexers: Vec<_> = SharedNetwork::connected(n_members)
+        .into_iter()
+        .map(|network| SessionMultiplexer::new(network, &spawner))
+        .collect();
+    let start_sessions = |session_id| -> Vec<_> {
+        multiplexers
+            .iter()
+            .zip(&backup_dirs)
+            .enumerate()
+            .map(|(ix, (multiplexer, dir))| {
+                start_session(
+                    &spawner,
+                    multiplexer,
+                    NodeIndex(ix),
+                    n_members,
+                    session_id,
+                    dir,
+                )
+            })
+            .collect()
+    };
+
+    let mut first = start_sessions(7);
+    let first_batches = next_batches(&mut first, n_batches).await;
+    assert_agree(&first_batches, 7);
+
+    // The next session starts while the previous one is still running.
+    let mut second = start_sessions(8);
+    let second_batches = next_batches(&mut second, n_batches).await;
+    assert_agree(&second_batches, 8);
+    let first_batches = next_batches(&mut first, n_batches).await;
+    assert_agree(&first_batches, 7);
+
+    stop(first).await;
+    let second_batches = next_batches(&mut second, n_batches).await;
+    assert_agree(&second_batches, 8);
+    stop(second).await;
+
+    for dir in &backup_dirs {
+        for session_id in [7, 8] {
+            let backup = dir.join(format!("session{}.backup", session_id));
+            let len = std::fs::metadata(&backup)
+                .expect("every session keeps its own backup")
+                .len();
+            assert!(len > 0);
+        }
+    }
+    let _ = std::fs::remove_dir_all(&backup_root);
+}
@@ -106,12 +106,32 @@ async fn test_invalid_signatures() {
     let mut unchecked_msg = signed_msg.into_unchecked();
     unchecked_msg.signature_mut().index = 1.into();
 
-    assert!(
+    // With the `trusted` feature every signature is accepted.
+    assert_eq!(
         unchecked_msg.check(&keychain).is_err(),
+        !cfg!(feature = "trusted"),
         "wrong index makes wrong signature"
     );
 }
 
+#[tokio::test]
+async fn test_signatures_of_unknown_members() {
+    let msg = test_message();
+    let signer = test_multi_keychain(7.into(), 5.into());
+    let keychain = test_multi_keychain(4.into(), 0.into());
+    let unchecked_msg = Signed::sign_with_index(msg, &signer).await.into_unchecked();
+
+    // Also with the `trusted` feature.
+    assert!(!UncheckedSigned::are_signatures_valid(
+        [&unchecked_msg],
+        &keychain
+    ));
+    assert!(
+        unchecked_msg.check(&keychain).is_err(),
+        "a member out of range cannot sign"
+    );
+}
+
 #[tokio::test]
 async fn test_incomplete_multisignature() {
     let msg = test_message();
@@ -146,3 +166,28 @@ async fn test_multisignatures() {
         partial
     );
 }
+
+#[tokio::test]
+async fn test_verify_batch() {
+    let node_count: NodeCount = 4.into();
+    let keychains: Vec<TestMultiKeychain> = (0..node_count.0)
+        .map(|i| test_multi_keychain(node_count, i.into()))
+        .collect();
+    let msg = test_message();
+    let mut unchecked = Vec::new();
+    for keychain in keychains.iter() {
+        let signed = Signed::sign_with_index(msg.clone(), keychain).await;
+        unchecked.push(signed.into_unchecked());
+    }
+    assert!(
+        UncheckedSigned::are_signatures_valid(&unchecked, &keychains[0]),
+        "All signatures in the batch are valid"
+    );
+
+    unchecked[2].signature_mut().index = 3.into();
+    assert_eq!(
+        UncheckedSigned::are_signatures_valid(&unchecked, &keychains[0]),
+        cfg!(feature = "trusted"),
+        "One wrong signature invalidates the batch"
+    );
+}
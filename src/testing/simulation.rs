@@ -0,0 +1,414 @@
+use codec::Encode;
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    future::BoxFuture,
+    stream::FuturesUnordered,
+    task::{waker_ref, ArcWake},
+    Future, FutureExt, StreamExt,
+};
+use parking_lot::Mutex;
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    run_session,
+    testing::{
+        mock::{gen_config, Data, DataIO, KeyBox, NetworkData},
+        network::{LinkConfig, LinkStats, MockNetwork, NetworkModel, Outbox},
+    },
+    Clock, MockClock, NodeCount, NodeIndex, OrderedBatch, SpawnHandle, TaskHandle,
+};
+
+// Gives up on runs which do not finish, instead of looping forever.
+const MAX_STEPS: usize = 1_000_000;
+
+// Collects the spawned tasks, which the simulation polls on its own.
+#[derive(Clone, Default)]
+struct SimulationSpawner {
+    spawned: Arc<Mutex<Vec<BoxFuture<'static, ()>>>>,
+}
+
+impl SpawnHandle for SimulationSpawner {
+    fn spawn(&self, _name: &str, task: impl Future<Output = ()> + Send + 'static) {
+        self.spawned.lock().push(task.boxed());
+    }
+
+    fn spawn_essential(
+        &self,
+        name: &'static str,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> TaskHandle {
+        let (res_tx, res_rx) = oneshot::channel();
+        self.spawn(name, async move {
+            task.await;
+            let _ = res_tx.send(());
+        });
+        Box::pin(async move { res_rx.await.map_err(|_| ()) })
+    }
+}
+
+#[derive(Default)]
+struct WakeFlag(AtomicBool);
+
+impl ArcWake for WakeFlag {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+// The time of delivery, the sender, the recipient, a hash of the content and the number of
+// identical messages sent before. It does not depend on the order in which the members send
+// their messages, and orders the deliveries happening at the same time.
+type DeliveryKey = (Duration, usize, usize, u64, usize);
+
+/// Runs honest members over a simulated network, with all the tasks polled in a single thread and
+/// the time given by a [`MockClock`]. The fate of the messages is decided by a [`NetworkModel`],
+/// with the random choices derived from the seed, so that a run is reproducible given the seed.
+pub struct Simulation {
+    seed: u64,
+    model: NetworkModel,
+    stats: HashMap<(NodeIndex, NodeIndex), LinkStats>,
+    clock: MockClock,
+    start: Instant,
+    spawner: SimulationSpawner,
+    tasks: FuturesUnordered<BoxFuture<'static, ()>>,
+    wake_flag: Arc<WakeFlag>,
+    outbox: Outbox,
+    in_flight: BTreeMap<DeliveryKey, NetworkData>,
+    sent: HashMap<(usize, usize, u64), usize>,
+    incoming_txs: Vec<UnboundedSender<NetworkData>>,
+    batch_rxs: Vec<UnboundedReceiver<OrderedBatch<Data>>>,
+    batches: Vec<Vec<OrderedBatch<Data>>>,
+}
+
+impl Simulation {
+    pub fn new(n_members: NodeCount, seed: u64) -> Self {
+        Self::with_network(n_members, seed, NetworkModel::new())
+    }
+
+    pub fn with_network(n_members: NodeCount, seed: u64, model: NetworkModel) -> Self {
+        let clock = MockClock::new();
+        let spawner = SimulationSpawner::default();
+        let outbox = Outbox::default();
+        let mut incoming_txs = Vec::new();
+        let mut batch_rxs = Vec::new();
+        for node_ix in n_members.all_indices() {
+            let (incoming_tx, incoming) = unbounded();
+            let network = MockNetwork::new(node_ix, n_members, outbox.clone(), incoming);
+            let mut config = gen_config(node_ix, n_members);
+            config.clock = Arc::new(clock.clone());
+            config.random_seed = Some(seed);
+            let (data_io, batch_rx) = DataIO::new(node_ix);
+            let keybox = KeyBox::new(n_members, node_ix);
+            let (_, creator_control) = unbounded();
+            // The members run until the simulation is dropped.
+            let (exit_tx, exit_rx) = oneshot::channel();
+            let session_spawner = spawner.clone();
+            spawner.spawn("member", async move {
+                let _exit_tx = exit_tx;
+                let _ = run_session(
+                    config,
+                    network,
+                    data_io,
+                    keybox,
+                    session_spawner,
+                    creator_control,
+                    exit_rx,
+                )
+                .await;
+            });
+            incoming_txs.push(incoming_tx);
+            batch_rxs.push(batch_rx);
+        }
+        Simulation {
+            seed,
+            model,
+            stats: HashMap::new(),
+            start: clock.now(),
+            clock,
+            spawner,
+            tasks: FuturesUnordered::new(),
+            wake_flag: Arc::new(WakeFlag::default()),
+            outbox,
+            in_flight: BTreeMap::new(),
+            sent: HashMap::new(),
+            incoming_txs,
+            batches: batch_rxs.iter().map(|_| Vec::new()).collect(),
+            batch_rxs,
+        }
+    }
+
+    /// The time since the start of the simulation.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now() - self.start
+    }
+
+    /// Moves the time forward to the next delivery of a message or to the next timer, and runs
+    /// all the members until none of them can make progress. Returns `false` if nothing is left
+    /// to happen.
+    pub fn step(&mut self) -> bool {
+        self.run_until_stalled();
+        let now = self.elapsed();
+        let next_delivery = self.in_flight.keys().next().map(|key| key.0);
+        let next_wakeup = self.clock.time_to_next_wakeup().map(|delay| now + delay);
+        let next = match (next_delivery, next_wakeup) {
+            (Some(delivery), Some(wakeup)) => delivery.min(wakeup),
+            (Some(time), None) | (None, Some(time)) => time,
+            (None, None) => return false,
+        };
+        if next > now {
+            self.clock.advance(next - now);
+        }
+        while let Some(entry) = self.in_flight.first_entry() {
+            if entry.key().0 > next {
+                break;
+            }
+            let ((time, sender, recipient, _, _), data) = entry.remove_entry();
+            let (sender, recipient) = (NodeIndex(sender), NodeIndex(recipient));
+            let stats = self.stats.entry((sender, recipient)).or_default();
+            if self.model.is_cut(time, sender, recipient) {
+                stats.dropped += 1;
+                continue;
+            }
+            stats.delivered += 1;
+            // The member might have exited, then the message is lost.
+            let _ = self.incoming_txs[recipient.0].unbounded_send(data);
+        }
+        self.run_until_stalled();
+        true
+    }
+
+    /// Steps until `duration` passes since the start of the simulation.
+    pub fn run_until(&mut self, duration: Duration) {
+        while self.elapsed() < duration {
+            if !self.step() {
+                return;
+            }
+        }
+    }
+
+    /// The batches ordered by each of the members so far.
+    pub fn batches(&self) -> &[Vec<OrderedBatch<Data>>] {
+        &self.batches
+    }
+
+    /// The numbers of messages sent from `sender` to `recipient` which were delivered and dropped.
+    pub fn link_stats(&self, sender: NodeIndex, recipient: NodeIndex) -> LinkStats {
+        self.stats
+            .get(&(sender, recipient))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Steps until every member ordered at least `n_batches` batches, and returns the first
+    /// `n_batches` of each of them.
+    pub fn run_until_batches(&mut self, n_batches: usize) -> Vec<Vec<OrderedBatch<Data>>> {
+        for _ in 0..MAX_STEPS {
+            if self
+                .batches
+                .iter()
+                .all(|batches| batches.len() >= n_batches)
+            {
+                return self
+                    .batches
+                    .iter()
+                    .map(|batches| batches[..n_batches].to_vec())
+                    .collect();
+            }
+            assert!(
+                self.step(),
+                "the members stopped before ordering enough batches"
+            );
+        }
+        panic!(
+            "the members did not order enough batches in {} steps",
+            MAX_STEPS
+        );
+    }
+
+    fn run_until_stalled(&mut self) {
+        loop {
+            let spawned: Vec<_> = self.spawner.spawned.lock().drain(..).collect();
+            let any_spawned = !spawned.is_empty();
+            self.tasks.extend(spawned);
+            self.wake_flag.0.store(false, Ordering::SeqCst);
+            let waker = waker_ref(&self.wake_flag);
+            let mut cx = Context::from_waker(&waker);
+            while let Poll::Ready(Some(())) = self.tasks.poll_next_unpin(&mut cx) {}
+            self.collect_outputs();
+            if !any_spawned
+                && !self.wake_flag.0.load(Ordering::SeqCst)
+                && self.spawner.spawned.lock().is_empty()
+            {
+                return;
+            }
+        }
+    }
+
+    fn collect_outputs(&mut self) {
+        for (rx, batches) in self.batch_rxs.iter_mut().zip(self.batches.iter_mut()) {
+            while let Ok(batch) = rx.try_recv() {
+                batches.push(batch);
+            }
+        }
+        let outgoing: Vec<_> = self.outbox.lock().drain(..).collect();
+        let now = self.elapsed();
+        for (sender, recipient, data) in outgoing {
+            match self.delivery_key(now, sender, recipient, &data) {
+                Some(key) => {
+                    self.in_flight.insert(key, data);
+                }
+                None => self.stats.entry((sender, recipient)).or_default().dropped += 1,
+            }
+        }
+    }
+
+    fn delivery_key(
+        &mut self,
+        now: Duration,
+        sender: NodeIndex,
+        recipient: NodeIndex,
+        data: &NetworkData,
+    ) -> Option<DeliveryKey> {
+        let mut hasher = DefaultHasher::new();
+        (self.seed, sender, recipient, data.encode()).hash(&mut hasher);
+        let content = hasher.finish();
+        let repetition = self
+            .sent
+            .entry((sender.0, recipient.0, content))
+            .or_insert(0);
+        *repetition += 1;
+        let mut rng = StdRng::seed_from_u64(content ^ *repetition as u64);
+        let time = self.model.delivery_time(now, sender, recipient, &mut rng)?;
+        Some((time, sender.0, recipient.0, content, *repetition))
+    }
+}
+
+#[test]
+fn runs_are_reproducible() {
+    let n_members = NodeCount(4);
+    let n_batches = 10;
+    for seed in 0..3 {
+        let first = Simulation::new(n_members, seed).run_until_batches(n_batches);
+        let second = Simulation::new(n_members, seed).run_until_batches(n_batches);
+        assert_eq!(first.encode(), second.encode());
+    }
+}
+
+#[test]
+fn honest_members_agree_under_any_schedule() {
+    let n_members = NodeCount(7);
+    let n_batches = 10;
+    let mut outcomes = Vec::new();
+    for seed in 0..10 {
+        let batches = Simulation::new(n_members, seed).run_until_batches(n_batches);
+        for batches_per_ix in batches.iter().skip(1) {
+            assert_eq!(
+                batches_per_ix, &batches[0],
+                "members disagree for seed {}",
+                seed
+            );
+        }
+        outcomes.push(batches[0].clone());
+    }
+    // Otherwise the seed would not influence the schedule at all.
+    assert!(outcomes.iter().any(|outcome| outcome != &outcomes[0]));
+}
+
+fn assert_prefix_consistent(batches: &[Vec<OrderedBatch<Data>>]) {
+    for (first, second) in batches.iter().zip(batches.iter().skip(1)) {
+        let common = first.len().min(second.len());
+        assert_eq!(first[..common], second[..common]);
+    }
+}
+
+#[test]
+fn members_recover_after_partition_without_quorum() {
+    let n_members = NodeCount(7);
+    // The members start ordering after waiting for the catch up to finish.
+    let (from, until) = (Duration::from_secs(6), Duration::from_secs(10));
+    let group: Vec<_> = (0..3).map(NodeIndex).collect();
+    let model = NetworkModel::new().partition(n_members, &group, from, until);
+    let mut simulation = Simulation::with_network(n_members, 0, model);
+
+    simulation.run_until(from + Duration::from_secs(1));
+    let ordered_before: Vec<_> = simulation.batches().iter().map(Vec::len).collect();
+    assert!(ordered_before.iter().all(|ordered| *ordered > 0));
+    simulation.run_until(until);
+    assert_prefix_consistent(simulation.batches());
+    // Neither side has a quorum, so nothing gets finalized once the messages sent before the
+    // partition are delivered.
+    let ordered_during: Vec<_> = simulation.batches().iter().map(Vec::len).collect();
+    assert_eq!(ordered_before, ordered_during);
+    assert!(simulation.link_stats(NodeIndex(0), NodeIndex(3)).dropped > 0);
+
+    let n_batches = ordered_during.iter().max().expect("there are members") + 10;
+    let batches = simulation.run_until_batches(n_batches);
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(batches_per_ix, &batches[0]);
+    }
+}
+
+#[test]
+fn majority_progresses_during_partition() {
+    let n_members = NodeCount(7);
+    // The members start ordering after waiting for the catch up to finish.
+    let (from, until) = (Duration::from_secs(6), Duration::from_secs(10));
+    let minority: Vec<_> = (0..2).map(NodeIndex).collect();
+    let model = NetworkModel::new().partition(n_members, &minority, from, until);
+    let mut simulation = Simulation::with_network(n_members, 0, model);
+
+    simulation.run_until(from + Duration::from_secs(1));
+    let ordered_before: Vec<_> = simulation.batches().iter().map(Vec::len).collect();
+    assert!(ordered_before.iter().all(|ordered| *ordered > 0));
+    simulation.run_until(until);
+    assert_prefix_consistent(simulation.batches());
+    let ordered_during = simulation.batches().iter().map(Vec::len);
+    for (ix, (before, during)) in ordered_before.iter().zip(ordered_during).enumerate() {
+        if minority.contains(&NodeIndex(ix)) {
+            assert_eq!(during, *before);
+        } else {
+            assert!(during > *before);
+        }
+    }
+
+    let n_batches = simulation.batches()[2].len() + 5;
+    let batches = simulation.run_until_batches(n_batches);
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(batches_per_ix, &batches[0]);
+    }
+}
+
+#[test]
+fn members_agree_over_lossy_links() {
+    let n_members = NodeCount(7);
+    let n_batches = 10;
+    let lossy = LinkConfig {
+        drop_probability: 0.2,
+        ..LinkConfig::default()
+    };
+    let model = NetworkModel::new().default_link(lossy);
+    let mut simulation = Simulation::with_network(n_members, 0, model);
+
+    let batches = simulation.run_until_batches(n_batches);
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(batches_per_ix, &batches[0]);
+    }
+    for (sender, recipient) in [(0, 1), (3, 5), (6, 2)] {
+        let stats = simulation.link_stats(NodeIndex(sender), NodeIndex(recipient));
+        assert!(stats.dropped > 0);
+        assert!(stats.delivered > stats.dropped);
+    }
+}
@@ -0,0 +1,472 @@
+use futures::{
+    channel::{mpsc::unbounded, oneshot},
+    StreamExt,
+};
+use std::time::Duration;
+
+use crate::{
+    member::UnitMessage,
+    network::NetworkDataInner,
+    spawn_session,
+    testing::mock::{
+        configure_network, gen_config, init_log, DataIO, KeyBox, NetworkData, NetworkHook, Spawner,
+    },
+    Config, LivenessConfig, NodeCount, NodeIndex, OrderedBatch, ResponseLimitConfig, Round,
+    SpawnHandle,
+};
+
+#[tokio::test]
+async fn session_status_follows_the_progress() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            gen_config(ix, n_members),
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        assert_eq!(status.current_round(), None);
+        assert_eq!(status.last_finalized_round(), None);
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+
+    for (_, status, batch_rx, _) in members.iter_mut() {
+        for _ in 0..3 {
+            batch_rx.next().await.expect("The member should not exit.");
+        }
+        let current_round = status.current_round().expect("Units have been created.");
+        let last_finalized_round = status
+            .last_finalized_round()
+            .expect("Batches have been ordered.");
+        assert!(last_finalized_round <= current_round);
+        assert!(status.n_ordered_units() > 0);
+        assert!(status.n_stored_units() > 0);
+        // Three batches need units of a few rounds from a quorum at least.
+        let n_active = n_members
+            .all_indices()
+            .filter_map(|node_ix| status.member_activity(node_ix))
+            .filter(|activity| activity.highest_round > 0)
+            .count();
+        assert!(n_active >= 3);
+        // Ordering a round needs a quorum of units in the rounds above it.
+        let shape = status.dag_shape();
+        let highest_quorum_round = shape
+            .highest_quorum_round
+            .expect("Batches have been ordered.");
+        assert!(highest_quorum_round > last_finalized_round);
+        assert!(shape.quorum_gap().is_some());
+        assert!(!shape.widths.is_empty());
+        assert!(shape.widths.iter().all(|(_, width)| *width <= n_members));
+        assert!(shape.average_parents > 0.0);
+        assert!(shape.own_unit_inclusion.is_some());
+        #[cfg(feature = "debug")]
+        {
+            let dag = status.dag_dot().await.expect("The session is running.");
+            assert!(dag.starts_with("digraph dag {"));
+            assert!(dag.contains("style=filled"));
+        }
+    }
+
+    // The members finalized the same units, as far as all of them got.
+    let round = members
+        .iter()
+        .filter_map(|(_, status, ..)| status.last_finalized_round())
+        .min()
+        .expect("Batches have been ordered.");
+    let finalized = members[0].1.finalized_unit_hashes(..=round);
+    assert!(!finalized.is_empty());
+    for (_, status, ..) in &members {
+        assert_eq!(status.finalized_unit_hashes(..=round), finalized);
+        let all = status.finalized_unit_hashes(..);
+        assert!(all.starts_with(&finalized));
+    }
+
+    for (handle, _status, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+        #[cfg(feature = "debug")]
+        assert_eq!(_status.dag_dot().await, None);
+    }
+}
+
+#[tokio::test]
+async fn stored_units_stay_near_the_bound() {
+    init_log();
+    let n_members = NodeCount(4);
+    let max_stored_units = 40;
+    let n_batches = 30;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.max_stored_units = Some(max_stored_units);
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            config,
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+
+    let mut batches: Vec<Vec<OrderedBatch<_>>> = Vec::new();
+    for (_, status, batch_rx, _) in members.iter_mut() {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..n_batches {
+            batches_per_ix.push(batch_rx.next().await.expect("The member should not exit."));
+            // The bound is soft, the units which are not finalized yet are never pruned.
+            assert!(status.n_stored_units() <= 2 * max_stored_units as u64);
+        }
+        batches.push(batches_per_ix);
+    }
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(batches_per_ix, &batches[0]);
+    }
+    // Far more units were ordered, than the members keep.
+    for (_, status, _, _) in members.iter() {
+        assert!(status.n_ordered_units() > 2 * max_stored_units as u64);
+    }
+
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn finalized_unit_hashes_agree_with_split_batches() {
+    init_log();
+    let n_members = NodeCount(4);
+    let target_round = 4;
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        // Half of the members split every batch into single units.
+        if ix.0 % 2 == 0 {
+            config.max_batch_size = Some(1);
+        }
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            config,
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+
+    for (_, status, batch_rx, _) in members.iter_mut() {
+        let mut last_finalized_round = None;
+        while last_finalized_round < Some(target_round) {
+            batch_rx.next().await.expect("The member should not exit.");
+            let round = status.last_finalized_round();
+            assert!(round >= last_finalized_round);
+            last_finalized_round = round;
+        }
+    }
+
+    for round in 0..target_round {
+        let finalized = members[0].1.finalized_unit_hashes(..=round);
+        for (_, status, ..) in &members {
+            assert_eq!(status.finalized_unit_hashes(..=round), finalized);
+            let mut all = status.finalized_unit_hashes(..=round);
+            all.extend(status.finalized_unit_hashes(round + 1..));
+            assert_eq!(all, status.finalized_unit_hashes(..));
+        }
+    }
+
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn rounds_reaching_quorum_are_reported_ahead_of_finalization() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (quorum_round_sink, quorum_rounds) = unbounded();
+        let mut config = gen_config(ix, n_members);
+        config.quorum_round_sink = Some(quorum_round_sink);
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            config,
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        members.push((handle, status, batch_rx, quorum_rounds, exit_tx));
+    }
+
+    for (_, status, batch_rx, quorum_rounds, _) in members.iter_mut() {
+        for _ in 0..5 {
+            batch_rx.next().await.expect("The member should not exit.");
+        }
+        let last_finalized_round = status
+            .last_finalized_round()
+            .expect("Batches have been ordered.");
+        let mut reported: Vec<Round> = Vec::new();
+        while let Ok(round) = quorum_rounds.try_recv() {
+            reported.push(round);
+        }
+        // Every round is reported once, in order, and before it is finalized.
+        assert_eq!(reported, (0..reported.len() as Round).collect::<Vec<_>>());
+        assert!(reported.len() as Round > last_finalized_round);
+    }
+
+    for (handle, _, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn members_signal_catching_up() {
+    init_log();
+    let n_members = NodeCount(4);
+    let spawner = Spawner::new();
+    let (net_hub, networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            gen_config(ix, n_members),
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        assert!(!status.is_caught_up());
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+
+    for (_, status, _, _) in members.iter() {
+        status.caught_up().await;
+        assert!(status.is_caught_up());
+        assert!(status.last_finalized_round().is_some());
+    }
+
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
+
+// Starts the last member once the others ordered a number of batches, and checks that it orders
+// everything they ordered before considering itself caught up.
+async fn late_member_catches_up(configure: impl Fn(&mut Config)) {
+    init_log();
+    let n_members = NodeCount(4);
+    let n_batches = 20;
+    let spawner = Spawner::new();
+    let (net_hub, mut networks) = configure_network(n_members, 1.0);
+    spawner.spawn("network-hub", net_hub);
+    let late_network = networks.pop().expect("there are members");
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let mut config = gen_config(ix, n_members);
+        configure(&mut config);
+        let (handle, status) = spawn_session(
+            config,
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+    for (_, _, batch_rx, _) in members.iter_mut() {
+        for _ in 0..n_batches {
+            batch_rx.next().await.expect("The member should not exit.");
+        }
+    }
+    let ordered_before = members
+        .iter()
+        .filter_map(|(_, status, _, _)| status.last_finalized_round())
+        .min()
+        .expect("Batches have been ordered.");
+
+    let ix = late_network.index();
+    let (data_io, batch_rx) = DataIO::new(ix);
+    let (_, creator_control) = unbounded();
+    let (exit_tx, exit_rx) = oneshot::channel();
+    let mut config = gen_config(ix, n_members);
+    configure(&mut config);
+    let (handle, status) = spawn_session(
+        config,
+        late_network,
+        data_io,
+        KeyBox::new(n_members, ix),
+        spawner.clone(),
+        creator_control,
+        exit_rx,
+    );
+    status.caught_up().await;
+    let ordered_late = status
+        .last_finalized_round()
+        .expect("Batches have been ordered.");
+    assert!(ordered_late >= ordered_before);
+    members.push((handle, status, batch_rx, exit_tx));
+
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
+
+#[tokio::test]
+async fn late_member_catches_up_after_ordering_the_backlog() {
+    late_member_catches_up(|_| {}).await;
+}
+
+#[tokio::test]
+async fn late_member_catches_up_with_limited_responses() {
+    late_member_catches_up(|config| {
+        config.response_limit = Some(ResponseLimitConfig {
+            max_units: 8,
+            window: Duration::from_millis(100),
+        });
+    })
+    .await;
+}
+
+// Makes the units of the creator sent to the recipient invalid, so that it never gets them.
+struct CensorUnits {
+    recipient: NodeIndex,
+    creator: NodeIndex,
+}
+
+impl NetworkHook for CensorUnits {
+    fn update_state(&mut self, data: &mut NetworkData, _sender: NodeIndex, recipient: NodeIndex) {
+        if recipient != self.recipient {
+            return;
+        }
+        let units = match data {
+            crate::NetworkData(NetworkDataInner::Units(
+                UnitMessage::NewUnit(uu) | UnitMessage::ResponseCoord(uu),
+            )) => vec![uu],
+            crate::NetworkData(NetworkDataInner::Units(UnitMessage::ResponseParents(_, units))) => {
+                units.iter_mut().collect()
+            }
+            _ => return,
+        };
+        for uu in units {
+            let full_unit = uu.as_signable_mut();
+            if full_unit.creator() == self.creator {
+                // Above the maximum round, so it does not pass the validation.
+                full_unit.set_round(Round::MAX);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn member_cut_off_from_a_creator_is_wedged() {
+    init_log();
+    let n_members = NodeCount(4);
+    let wedged_ix = NodeIndex(0);
+    let spawner = Spawner::new();
+    let (mut net_hub, networks) = configure_network(n_members, 1.0);
+    net_hub.add_hook(CensorUnits {
+        recipient: wedged_ix,
+        creator: NodeIndex(3),
+    });
+    spawner.spawn("network-hub", net_hub);
+
+    let mut members = Vec::new();
+    for network in networks {
+        let ix = network.index();
+        let mut config = gen_config(ix, n_members);
+        config.liveness = LivenessConfig {
+            stall_threshold: Duration::from_secs(2),
+            activity_window: Duration::from_secs(2),
+            min_active_members: None,
+        };
+        let (data_io, batch_rx) = DataIO::new(ix);
+        let (_, creator_control) = unbounded();
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let (handle, status) = spawn_session(
+            config,
+            network,
+            data_io,
+            KeyBox::new(n_members, ix),
+            spawner.clone(),
+            creator_control,
+            exit_rx,
+        );
+        assert!(!status.is_wedged());
+        members.push((handle, status, batch_rx, exit_tx));
+    }
+
+    // The others keep making progress, but we lack the parents of their units.
+    members[wedged_ix.0].1.wedged().await;
+    assert!(members[wedged_ix.0].1.is_wedged());
+    for (_, status, batch_rx, _) in members.iter_mut().skip(1) {
+        batch_rx.next().await.expect("The member should not exit.");
+        assert!(!status.is_wedged());
+    }
+
+    for (handle, _, _, exit_tx) in members {
+        let _ = exit_tx.send(());
+        assert_eq!(handle.await, Ok(()));
+    }
+}
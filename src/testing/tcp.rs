@@ -0,0 +1,264 @@
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use futures_timer::Delay;
+use std::{collections::HashMap, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    member::UnitMessage,
+    network::NetworkDataInner,
+    nodes::NodeSubset,
+    testing::mock::{
+        init_log, spawn_honest_member, Data, Hasher64, NetworkData, PartialMultisignature,
+        Signature, Spawner,
+    },
+    units::{ControlHash, FullUnit, PreUnit, UncheckedSignedUnit, UnitCoord},
+    Hasher, Network, NetworkCodec, NodeCount, NodeIndex, Recipient, TcpNetwork, UncheckedSigned,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn honest_members_agree_over_tcp() {
+    init_log();
+    let n_members = NodeCount(4);
+    let n_batches = 5;
+    let mut listeners = Vec::new();
+    let mut addresses = HashMap::new();
+    for ix in n_members.all_indices() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("binding to a free port should work");
+        addresses.insert(
+            ix,
+            listener.local_addr().expect("the listener has an address"),
+        );
+        listeners.push(listener);
+    }
+
+    let spawner = Spawner::new();
+    let mut batch_rxs = Vec::new();
+    let mut exits = Vec::new();
+    let mut handles = Vec::new();
+    for (ix, listener) in listeners.into_iter().enumerate() {
+        let network = TcpNetwork::from_listener(NodeIndex(ix), listener, addresses.clone());
+        let (batch_rx, exit_tx, handle) =
+            spawn_honest_member(spawner.clone(), NodeIndex(ix), n_members, network);
+        batch_rxs.push(batch_rx);
+        exits.push(exit_tx);
+        handles.push(handle);
+    }
+
+    let mut batches = Vec::new();
+    for mut rx in batch_rxs {
+        let mut batches_per_ix = Vec::new();
+        for _ in 0..n_batches {
+            batches_per_ix.push(rx.next().await.expect("the member should not exit"));
+        }
+        batches.push(batches_per_ix);
+    }
+    for batches_per_ix in batches.iter().skip(1) {
+        assert_eq!(&batches[0], batches_per_ix);
+    }
+    for exit in exits {
+        let _ = exit.send(());
+    }
+    for handle in handles {
+        let _ = handle.await;
+    }
+}
+
+fn unit(round: u16) -> UncheckedSignedUnit<Hasher64, Data, Signature> {
+    let control_hash = ControlHash {
+        parents_mask: NodeSubset::with_size(NodeCount(2)),
+        combined_hash: 0.using_encoded(Hasher64::hash),
+    };
+    let pu = PreUnit::new(NodeIndex(1), round, control_hash);
+    let data = Data::new(UnitCoord::new(round, NodeIndex(1)), 0);
+    UncheckedSigned::new(FullUnit::new(pu, data, 0), Signature {})
+}
+
+fn frame(message: UnitMessage<Hasher64, Data, Signature>) -> Vec<u8> {
+    let payload = crate::NetworkData::<Hasher64, Data, Signature, PartialMultisignature>(
+        NetworkDataInner::Units(message),
+    )
+    .encode();
+    let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+    frame.extend(payload);
+    frame
+}
+
+fn round_of(data: NetworkData) -> Option<u16> {
+    match data.0 {
+        NetworkDataInner::Units(UnitMessage::NewUnit(uu))
+        | NetworkDataInner::Units(UnitMessage::ResponseCoord(uu)) => Some(uu.as_signable().round()),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn repeated_broadcasts_are_dropped() {
+    init_log();
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binding to a free port should work");
+    let address = listener.local_addr().expect("the listener has an address");
+    let mut network: TcpNetwork<Hasher64, Data, Signature, _> =
+        TcpNetwork::from_listener(NodeIndex(0), listener, HashMap::new());
+
+    let mut peer = TcpStream::connect(address)
+        .await
+        .expect("the network accepts connections");
+    for message in [
+        UnitMessage::NewUnit(unit(0)),
+        UnitMessage::NewUnit(unit(0)),
+        UnitMessage::NewUnit(unit(1)),
+        UnitMessage::ResponseCoord(unit(0)),
+        UnitMessage::ResponseCoord(unit(0)),
+        UnitMessage::NewUnit(unit(2)),
+    ] {
+        peer.write_all(&frame(message))
+            .await
+            .expect("the connection is open");
+    }
+
+    let mut rounds = Vec::new();
+    for _ in 0..5 {
+        let data = network.next_event().await.expect("the network is running");
+        rounds.push(round_of(data).expect("only units were sent"));
+    }
+    assert_eq!(rounds, vec![0, 1, 0, 0, 2]);
+}
+
+async fn wait_until(condition: impl Fn() -> bool) {
+    for _ in 0..400 {
+        if condition() {
+            return;
+        }
+        Delay::new(Duration::from_millis(50)).await;
+    }
+    panic!("the condition should hold within 20 seconds");
+}
+
+#[tokio::test]
+async fn drops_messages_for_unreachable_peer() {
+    init_log();
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binding to a free port should work");
+    let peer_listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binding to a free port should work");
+    let peer_address = peer_listener
+        .local_addr()
+        .expect("the listener has an address");
+    // Nobody listens at the address of the peer for now.
+    drop(peer_listener);
+    let addresses = HashMap::from([(NodeIndex(1), peer_address)]);
+    let network: TcpNetwork<Hasher64, Data, Signature, PartialMultisignature> =
+        TcpNetwork::from_listener(NodeIndex(0), listener, addresses);
+    let send = |round| {
+        network.send(
+            crate::NetworkData(NetworkDataInner::Units(UnitMessage::NewUnit(unit(round)))),
+            Recipient::Node(NodeIndex(1)),
+        )
+    };
+
+    send(0);
+    wait_until(|| !network.is_reachable(NodeIndex(1))).await;
+    send(1);
+    let peer_listener = TcpListener::bind(peer_address)
+        .await
+        .expect("the address of the peer should be free");
+    let (mut stream, _) = peer_listener
+        .accept()
+        .await
+        .expect("the network connects to the peer");
+    wait_until(|| network.is_reachable(NodeIndex(1))).await;
+    send(2);
+
+    let len = stream.read_u32().await.expect("a frame is sent");
+    let mut payload = vec![0; len as usize];
+    stream
+        .read_exact(&mut payload)
+        .await
+        .expect("the frame is complete");
+    #[cfg(feature = "compression")]
+    let payload = crate::decompress_payload(payload).expect("the frame is compressed");
+    let data = NetworkData::decode(&mut payload.as_slice()).expect("the frame is a message");
+    assert_eq!(round_of(data), Some(2));
+}
+
+// Wraps the default encoding in an envelope of some other system, here a fixed tag.
+#[derive(Clone)]
+struct EnvelopeCodec;
+
+const ENVELOPE_TAG: &[u8] = b"envelope";
+
+impl NetworkCodec<Hasher64, Data, Signature, PartialMultisignature> for EnvelopeCodec {
+    type Error = String;
+
+    fn encode(&self, data: &NetworkData) -> Vec<u8> {
+        let mut encoded = ENVELOPE_TAG.to_vec();
+        encoded.extend(data.encode());
+        encoded
+    }
+
+    fn decode(&self, encoded: &[u8]) -> Result<NetworkData, Self::Error> {
+        let encoded = encoded
+            .strip_prefix(ENVELOPE_TAG)
+            .ok_or_else(|| "no envelope".to_string())?;
+        NetworkData::decode(&mut &encoded[..]).map_err(|e| e.to_string())
+    }
+}
+
+#[tokio::test]
+async fn custom_codec_encodes_payloads() {
+    init_log();
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binding to a free port should work");
+    let address = listener.local_addr().expect("the listener has an address");
+    let peer_listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("binding to a free port should work");
+    let addresses = HashMap::from([(
+        NodeIndex(1),
+        peer_listener
+            .local_addr()
+            .expect("the listener has an address"),
+    )]);
+    let mut network =
+        TcpNetwork::from_listener_with_codec(NodeIndex(0), listener, addresses, EnvelopeCodec);
+
+    network.send(
+        crate::NetworkData(NetworkDataInner::Units(UnitMessage::NewUnit(unit(3)))),
+        Recipient::Node(NodeIndex(1)),
+    );
+    let (mut stream, _) = peer_listener
+        .accept()
+        .await
+        .expect("the network connects to the peer");
+    let len = stream.read_u32().await.expect("a frame is sent");
+    let mut payload = vec![0; len as usize];
+    stream
+        .read_exact(&mut payload)
+        .await
+        .expect("the frame is complete");
+    #[cfg(feature = "compression")]
+    let payload = crate::decompress_payload(payload).expect("the frame is compressed");
+    assert!(payload.starts_with(ENVELOPE_TAG));
+
+    // The received messages are decoded with the codec as well.
+    let mut peer = TcpStream::connect(address)
+        .await
+        .expect("the network accepts connections");
+    let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+    frame.extend(&payload);
+    peer.write_all(&frame)
+        .await
+        .expect("the connection is open");
+    let data = network.next_event().await.expect("the network is running");
+    assert_eq!(round_of(data), Some(3));
+}
@@ -101,7 +101,7 @@ async fn request_missing_coord() {
         }
         batches.push(batches_per_ix);
     }
-    for node_ix in n_members.into_iterator().skip(1) {
+    for node_ix in n_members.all_indices().skip(1) {
         assert_eq!(batches[0], batches[node_ix.0]);
     }
     for exit in exits {
@@ -0,0 +1,140 @@
+//! Optional integration with [`tracing`](https://docs.rs/tracing), enabled by the `tracing`
+//! feature. The main tasks of a session run inside spans carrying the index of the node and the
+//! session id, and the milestones in the lifecycle of a unit are emitted as structured events, so
+//! a unit can be followed across tasks. Without the feature this compiles to nothing and only the
+//! `log` output remains.
+
+use futures::Future;
+
+use crate::{NodeIndex, SessionId};
+
+/// Runs `task` inside a span named after the task, carrying the index of the node and the
+/// session id.
+#[cfg(feature = "tracing")]
+pub(crate) fn in_span<F: Future>(
+    task: &'static str,
+    node_ix: NodeIndex,
+    session_id: SessionId,
+    future: F,
+) -> impl Future<Output = F::Output> {
+    use tracing::Instrument;
+    future.instrument(tracing::info_span!(
+        "AlephBFT",
+        task,
+        node_ix = node_ix.0,
+        session_id
+    ))
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn in_span<F: Future>(
+    _task: &'static str,
+    _node_ix: NodeIndex,
+    _session_id: SessionId,
+    future: F,
+) -> F {
+    future
+}
+
+/// Emits a structured `tracing` event at the debug level, taking the same arguments as
+/// `tracing::debug!`. The arguments are not evaluated without the `tracing` feature.
+#[cfg(feature = "tracing")]
+macro_rules! unit_event {
+    ($($arg:tt)*) => {
+        tracing::debug!(target: "AlephBFT-units", $($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! unit_event {
+    ($($arg:tt)*) => {};
+}
+
+pub(crate) use unit_event;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use parking_lot::Mutex;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    #[derive(Default)]
+    struct Fields(Vec<String>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    // Remembers the fields of the spans and, for every event, the fields of the entered spans.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<Vec<Vec<String>>>>,
+        entered: Arc<Mutex<Vec<u64>>>,
+        events: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut fields = Fields::default();
+            span.record(&mut fields);
+            self.spans.lock().push(fields.0);
+            span::Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::default();
+            for id in self.entered.lock().iter() {
+                fields.0.extend(self.spans.lock()[*id as usize - 1].clone());
+            }
+            event.record(&mut fields);
+            self.events.lock().push(fields.0);
+        }
+
+        fn enter(&self, span: &span::Id) {
+            self.entered.lock().push(span.into_u64());
+        }
+
+        fn exit(&self, _: &span::Id) {
+            self.entered.lock().pop();
+        }
+    }
+
+    #[test]
+    fn events_carry_the_fields_of_the_task() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            block_on(in_span("creator", NodeIndex(3), 7, async {
+                unit_event!(round = 2, "unit created");
+            }))
+        });
+        assert_eq!(
+            *recorder.events.lock(),
+            vec![vec![
+                "task=\"creator\"".to_string(),
+                "node_ix=3".to_string(),
+                "session_id=7".to_string(),
+                "message=unit created".to_string(),
+                "round=2".to_string(),
+            ]]
+        );
+    }
+}
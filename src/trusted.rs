@@ -0,0 +1,111 @@
+//! A keychain for measuring the throughput of the protocol alone, without the cost of any
+//! cryptography, available with the `trusted` feature.
+//!
+//! Enabling the feature also makes the verification of all signatures a no-op, so any member
+//! or anyone else with access to the network can impersonate other members. It must never be
+//! enabled in a real network, only in benchmarks and deployments in which all the members are
+//! run by a single operator.
+use crate::{
+    nodes::{NodeCount, NodeIndex, NodeSubset, NodeWeights},
+    Index, KeyBox, MultiKeychain, PartialMultisignature,
+};
+use async_trait::async_trait;
+use codec::{Decode, Encode};
+
+/// An empty signature of a [`TrustedKeychain`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct TrustedSignature;
+
+/// A multisignature of a [`TrustedKeychain`], just the set of members that signed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct TrustedMultisignature {
+    signers: NodeSubset,
+}
+
+impl PartialMultisignature for TrustedMultisignature {
+    type Signature = TrustedSignature;
+
+    fn add_signature(mut self, _: &TrustedSignature, index: NodeIndex) -> Self {
+        if index.0 < self.signers.size() {
+            self.signers.insert(index);
+        }
+        self
+    }
+}
+
+/// A [`MultiKeychain`] whose signatures are empty and always valid. A multisignature is
+/// complete once more than 2/3 of the members are among its signers, as with real keychains.
+///
+/// It does not protect against anything and is only meant for measurements, see the
+/// [module documentation](self).
+#[derive(Clone, Debug)]
+pub struct TrustedKeychain {
+    index: NodeIndex,
+    node_weights: NodeWeights,
+}
+
+impl TrustedKeychain {
+    /// Creates the keychain of the member with the given index in a committee of `n_members`.
+    pub fn new(n_members: NodeCount, index: NodeIndex) -> Self {
+        TrustedKeychain {
+            index,
+            node_weights: NodeWeights::uniform(n_members),
+        }
+    }
+}
+
+impl Index for TrustedKeychain {
+    fn index(&self) -> NodeIndex {
+        self.index
+    }
+}
+
+#[async_trait]
+impl KeyBox for TrustedKeychain {
+    type Signature = TrustedSignature;
+
+    fn node_count(&self) -> NodeCount {
+        self.node_weights.n_members()
+    }
+
+    async fn sign(&self, _: &[u8]) -> TrustedSignature {
+        TrustedSignature
+    }
+
+    fn verify(&self, _: &[u8], _: &TrustedSignature, _: NodeIndex) -> bool {
+        true
+    }
+}
+
+impl MultiKeychain for TrustedKeychain {
+    type PartialMultisignature = TrustedMultisignature;
+
+    fn from_signature(&self, _: &TrustedSignature, index: NodeIndex) -> TrustedMultisignature {
+        let mut signers = NodeSubset::with_size(self.node_count());
+        signers.insert(index);
+        TrustedMultisignature { signers }
+    }
+
+    fn is_complete(&self, _: &[u8], partial: &TrustedMultisignature) -> bool {
+        partial.signers.size() == self.node_count().0
+            && self.node_weights.is_quorum(partial.signers.elements())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrustedKeychain, TrustedSignature};
+    use crate::{MultiKeychain, NodeCount, NodeIndex, PartialMultisignature};
+
+    #[test]
+    fn multisignatures_need_a_quorum_of_signers() {
+        let keychain = TrustedKeychain::new(NodeCount(4), NodeIndex(0));
+        let mut partial = keychain.from_signature(&TrustedSignature, NodeIndex(0));
+        for ix in [1, 1, 7] {
+            partial = partial.add_signature(&TrustedSignature, NodeIndex(ix));
+            assert!(!keychain.is_complete(b"message", &partial));
+        }
+        partial = partial.add_signature(&TrustedSignature, NodeIndex(3));
+        assert!(keychain.is_complete(b"message", &partial));
+    }
+}
@@ -1,105 +1,14 @@
 use crate::{
-    nodes::NodeSubset,
     signed::{Signable, Signed, UncheckedSigned},
-    Data, Hasher, Index, KeyBox, NodeCount, NodeIndex, NodeMap, Round, SessionId,
+    Data, Hasher, Index, KeyBox, NodeIndex, Round, SessionId,
 };
+pub use aleph_bft_types::ParentsError;
+pub use aleph_bft_types::{unit_order, Unit};
+pub(crate) use aleph_bft_types::{ControlHash, PreUnit, UnitCoord};
 use codec::{Decode, Encode};
 use derivative::Derivative;
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Encode, Decode, Hash)]
-pub(crate) struct UnitCoord {
-    round: Round,
-    creator: NodeIndex,
-}
-
-impl UnitCoord {
-    pub fn new(round: Round, creator: NodeIndex) -> Self {
-        Self {
-            creator,
-            round: round as u16,
-        }
-    }
-
-    pub fn creator(&self) -> NodeIndex {
-        self.creator
-    }
-
-    pub fn round(&self) -> Round {
-        self.round
-    }
-}
-
-/// Combined hashes of the parents of a unit together with the set of indices of creators of the
-/// parents
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
-pub(crate) struct ControlHash<H: Hasher> {
-    pub(crate) parents_mask: NodeSubset,
-    pub(crate) combined_hash: H::Hash,
-}
-
-impl<H: Hasher> ControlHash<H> {
-    pub(crate) fn new(parent_map: &NodeMap<H::Hash>) -> Self {
-        ControlHash {
-            parents_mask: parent_map.to_subset(),
-            combined_hash: Self::combine_hashes(parent_map),
-        }
-    }
-
-    pub(crate) fn combine_hashes(parent_map: &NodeMap<H::Hash>) -> H::Hash {
-        parent_map.using_encoded(H::hash)
-    }
-
-    pub(crate) fn parents(&self) -> impl Iterator<Item = NodeIndex> + '_ {
-        self.parents_mask.elements()
-    }
-
-    pub(crate) fn n_parents(&self) -> NodeCount {
-        NodeCount(self.parents().count())
-    }
-
-    pub(crate) fn n_members(&self) -> NodeCount {
-        NodeCount(self.parents_mask.size())
-    }
-}
-
-/// The simplest type representing a unit, consisting of coordinates and a control hash
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
-pub(crate) struct PreUnit<H: Hasher> {
-    coord: UnitCoord,
-    control_hash: ControlHash<H>,
-}
-
-impl<H: Hasher> PreUnit<H> {
-    pub(crate) fn new(creator: NodeIndex, round: Round, control_hash: ControlHash<H>) -> Self {
-        PreUnit {
-            coord: UnitCoord::new(round, creator),
-            control_hash,
-        }
-    }
-
-    pub(crate) fn n_parents(&self) -> NodeCount {
-        self.control_hash.n_parents()
-    }
-
-    pub(crate) fn n_members(&self) -> NodeCount {
-        self.control_hash.n_members()
-    }
-
-    pub(crate) fn creator(&self) -> NodeIndex {
-        self.coord.creator()
-    }
-
-    pub(crate) fn round(&self) -> Round {
-        self.coord.round()
-    }
-
-    pub(crate) fn control_hash(&self) -> &ControlHash<H> {
-        &self.control_hash
-    }
-}
-
-///
 #[derive(Debug, Encode, Decode, Derivative)]
 #[derivative(PartialEq, Eq, Hash)]
 pub(crate) struct FullUnit<H: Hasher, D: Data> {
@@ -146,7 +55,7 @@ impl<H: Hasher, D: Data> FullUnit<H, D> {
         self.pre_unit.control_hash()
     }
     pub(crate) fn coord(&self) -> UnitCoord {
-        self.pre_unit.coord
+        self.pre_unit.coord()
     }
     pub(crate) fn data(&self) -> &D {
         &self.data
@@ -165,12 +74,31 @@ impl<H: Hasher, D: Data> FullUnit<H, D> {
             }
         }
     }
+    /// The key of the unit in the order of [`unit_order`].
+    pub(crate) fn order_key(&self) -> (Round, usize, H::Hash) {
+        (self.round(), self.creator().0, self.hash())
+    }
     pub(crate) fn unit(&self) -> Unit<H> {
         Unit::new(self.pre_unit.clone(), self.hash())
     }
     #[cfg(test)]
     pub(crate) fn set_round(&mut self, round: Round) {
-        self.pre_unit.coord.round = round
+        let control_hash = self.pre_unit.control_hash().clone();
+        self.pre_unit = PreUnit::new(self.creator(), round, control_hash);
+    }
+    #[cfg(test)]
+    pub(crate) fn set_creator(&mut self, creator: NodeIndex) {
+        let control_hash = self.pre_unit.control_hash().clone();
+        self.pre_unit = PreUnit::new(creator, self.round(), control_hash);
+        *self.hash.write() = None;
+    }
+    #[cfg(test)]
+    pub(crate) fn set_parents(&mut self, parents: crate::nodes::NodeSubset) {
+        let mut control_hash = self.pre_unit.control_hash().clone();
+        control_hash.parents_mask = parents;
+        self.pre_unit = PreUnit::new(self.creator(), self.round(), control_hash);
+        // Otherwise the unit would pass for the one it was made from.
+        *self.hash.write() = None;
     }
 }
 
@@ -179,6 +107,10 @@ impl<H: Hasher, D: Data> Signable for FullUnit<H, D> {
     fn hash(&self) -> H::Hash {
         self.hash()
     }
+
+    fn signing_round(&self) -> Option<Round> {
+        Some(self.round())
+    }
 }
 
 impl<H: Hasher, D: Data> Index for FullUnit<H, D> {
@@ -191,28 +123,43 @@ pub(crate) type UncheckedSignedUnit<H, D, S> = UncheckedSigned<FullUnit<H, D>, S
 
 pub(crate) type SignedUnit<'a, H, D, KB> = Signed<'a, FullUnit<H, D>, KB>;
 
-#[derive(Clone, Debug, PartialEq, Encode, Decode)]
-pub(crate) struct Unit<H: Hasher> {
-    pre_unit: PreUnit<H>,
-    hash: H::Hash,
+/// Why an incoming unit, or the parents sent for a unit, did not make it to the Dag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnitRejectionReason {
+    /// The creator index is not one of the members of the committee.
+    OutOfRangeCreator,
+    /// The unit belongs to the session with this id instead of ours.
+    WrongSession(SessionId),
+    /// The round is higher than any unit we accept can have.
+    TooHighRound,
+    /// The parents of the unit break the structure of the Dag.
+    MalformedParents(ParentsError),
+    /// The signature does not match the creator of the unit.
+    BadSignature,
+    /// The unit carries this many bytes of data, more than [`crate::Config::max_data_size`].
+    TooLarge(usize),
+    /// A parents response holds a different number of units than the unit has parents.
+    ParentCountMismatch { expected: usize, got: usize },
+    /// A unit in a parents response is not of the round just below the unit.
+    ParentRoundMismatch { parent_round: Round },
+    /// A unit in a parents response is not by the creator of the parent at its position, which
+    /// includes repeating the creator of another parent.
+    ParentCreatorMismatch { expected: NodeIndex, got: NodeIndex },
+    /// The units in a parents response do not combine to the control hash of the unit.
+    WrongControlHash,
 }
 
-impl<H: Hasher> Unit<H> {
-    pub(crate) fn new(pre_unit: PreUnit<H>, hash: H::Hash) -> Self {
-        Unit { pre_unit, hash }
-    }
-    pub(crate) fn creator(&self) -> NodeIndex {
-        self.pre_unit.creator()
-    }
-    pub(crate) fn round(&self) -> Round {
-        self.pre_unit.round()
-    }
-    pub(crate) fn control_hash(&self) -> &ControlHash<H> {
-        self.pre_unit.control_hash()
-    }
-    pub(crate) fn hash(&self) -> H::Hash {
-        self.hash
-    }
+/// An incoming unit we rejected, see [`crate::Config::unit_rejection_sink`]. Rejected parents
+/// responses are reported with the unit the parents were sent for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnitRejection {
+    /// The creator of the unit, as claimed by the unit.
+    pub creator: NodeIndex,
+    /// The round of the unit, as claimed by the unit.
+    pub round: Round,
+    /// The member that passed the unit to us, if the message it came in tells.
+    pub sender: Option<NodeIndex>,
+    pub reason: UnitRejectionReason,
 }
 
 mod store;
@@ -238,15 +185,6 @@ mod tests {
         assert_eq!(full_unit.hash(), hash);
     }
 
-    #[test]
-    fn test_control_hash_codec() {
-        let ch = ControlHash::<Hasher64>::new(&vec![Some([0; 8]), None, Some([1; 8])].into());
-        let encoded = ch.encode();
-        let decoded =
-            ControlHash::decode(&mut encoded.as_slice()).expect("should decode correctly");
-        assert_eq!(decoded, ch);
-    }
-
     #[test]
     fn test_full_unit_codec() {
         let ch = ControlHash::<Hasher64>::new(&vec![].into());
@@ -1,14 +1,22 @@
 use super::*;
+use crate::{
+    nodes::{NodeCount, NodeSubset},
+    MAX_MAX_ROUND,
+};
 use log::{trace, warn};
+use std::{collections::HashSet, fmt::Write};
 
 /// A component for temporarily storing units before they are declared "legit" and sent
 /// to the Terminal. We refer to the documentation https://cardinal-cryptography.github.io/AlephBFT/internals.html
 /// Section 5.4 for a discussion of this component and the notion of "legit" units.
-
 pub(crate) struct UnitStore<'a, H: Hasher, D: Data, KB: KeyBox> {
     by_coord: HashMap<UnitCoord, SignedUnit<'a, H, D, KB>>,
     by_hash: HashMap<H::Hash, SignedUnit<'a, H, D, KB>>,
     parents: HashMap<H::Hash, Vec<H::Hash>>,
+    finalized: HashSet<H::Hash>,
+    // The coords of the units we forgot, we ignore any units with them. We no longer have the
+    // forgotten unit to prove a fork with, so another variant is ignored as well.
+    pruned: HashSet<UnitCoord>,
     //the number of unique nodes that we hold units for a given round
     is_forker: NodeSubset,
     legit_buffer: Vec<SignedUnit<'a, H, D, KB>>,
@@ -21,6 +29,8 @@ impl<'a, H: Hasher, D: Data, KB: KeyBox> UnitStore<'a, H, D, KB> {
             by_coord: HashMap::new(),
             by_hash: HashMap::new(),
             parents: HashMap::new(),
+            finalized: HashSet::new(),
+            pruned: HashSet::new(),
             // is_forker is initialized with default values for bool, i.e., false
             is_forker: NodeSubset::with_size(n_nodes),
             legit_buffer: Vec::new(),
@@ -40,8 +50,14 @@ impl<'a, H: Hasher, D: Data, KB: KeyBox> UnitStore<'a, H, D, KB> {
         self.by_hash.contains_key(hash)
     }
 
+    /// Also true for the coords of the pruned units, so that we do not request them again.
     pub(crate) fn contains_coord(&self, coord: &UnitCoord) -> bool {
-        self.by_coord.contains_key(coord)
+        self.by_coord.contains_key(coord) || self.pruned.contains(coord)
+    }
+
+    /// The number of units in the store.
+    pub(crate) fn len(&self) -> usize {
+        self.by_hash.len()
     }
 
     pub(crate) fn newest_unit(
@@ -52,7 +68,7 @@ impl<'a, H: Hasher, D: Data, KB: KeyBox> UnitStore<'a, H, D, KB> {
             self.by_coord
                 .values()
                 .filter(|su| su.as_signable().creator() == index)
-                .max_by_key(|su| su.as_signable().round())?
+                .max_by_key(|su| su.as_signable().order_key())?
                 .clone()
                 .into_unchecked(),
         )
@@ -98,6 +114,10 @@ impl<'a, H: Hasher, D: Data, KB: KeyBox> UnitStore<'a, H, D, KB> {
                 "The forker must be marked before adding alerted units."
             );
         }
+        if self.pruned.contains(&su.as_signable().coord()) {
+            trace!(target: "AlephBFT-unit-store", "A unit ignored as it was already pruned {:?}.", su.as_signable());
+            return;
+        }
         if self.contains_hash(&hash) {
             // Ignoring a duplicate.
             trace!(target: "AlephBFT-unit-store", "A unit ignored as a duplicate {:?}.", su.as_signable());
@@ -119,9 +139,95 @@ impl<'a, H: Hasher, D: Data, KB: KeyBox> UnitStore<'a, H, D, KB> {
         self.parents.get(&hash)
     }
 
+    pub(crate) fn mark_finalized(&mut self, hash: H::Hash) {
+        self.finalized.insert(hash);
+    }
+
+    /// Removes the finalized units, which are no longer needed for ordering, apart from the
+    /// newest unit of every creator, which we might still have to send to a recovering member.
+    /// Returns the number of removed units.
+    pub(crate) fn prune_finalized(&mut self) -> usize {
+        let mut newest: HashMap<NodeIndex, Round> = HashMap::new();
+        for coord in self.by_coord.keys() {
+            let round = newest.entry(coord.creator()).or_insert(coord.round());
+            *round = (*round).max(coord.round());
+        }
+        let prunable: Vec<_> = self
+            .finalized
+            .iter()
+            .filter(|hash| match self.by_hash.get(hash) {
+                Some(su) => {
+                    let coord = su.as_signable().coord();
+                    newest.get(&coord.creator()) != Some(&coord.round())
+                }
+                None => false,
+            })
+            .copied()
+            .collect();
+        for hash in &prunable {
+            let su = self.by_hash.remove(hash).expect("the unit was just found");
+            let coord = su.as_signable().coord();
+            if self.by_coord.get(&coord).map(|su| su.as_signable().hash()) == Some(*hash) {
+                self.by_coord.remove(&coord);
+            }
+            self.parents.remove(hash);
+            self.finalized.remove(hash);
+            self.pruned.insert(coord);
+        }
+        prunable.len()
+    }
+
+    /// Renders the units added to the Dag in the GraphViz DOT format, labeled with their creators
+    /// and rounds, with edges pointing to their parents. Finalized units are filled.
+    pub(crate) fn dag_to_dot(&self) -> String {
+        let mut units: Vec<_> = self
+            .parents
+            .keys()
+            .filter_map(|hash| Some(self.unit_by_hash(hash)?.as_signable().order_key()))
+            .collect();
+        // In the order of `unit_order`, so that the ids are the same on every node.
+        units.sort();
+        let ids: HashMap<_, _> = units
+            .iter()
+            .enumerate()
+            .map(|(id, (_, _, hash))| (*hash, id))
+            .collect();
+
+        let mut dot = String::from("digraph dag {\n    rankdir=BT;\n");
+        for (id, (round, creator, hash)) in units.iter().enumerate() {
+            let style = if self.finalized.contains(hash) {
+                ", style=filled"
+            } else {
+                ""
+            };
+            writeln!(
+                dot,
+                "    u{} [label=\"({}, {})\"{}];",
+                id, creator, round, style
+            )
+            .expect("writing to a string works");
+        }
+        for (id, (_, _, hash)) in units.iter().enumerate() {
+            for parent in &self.parents[hash] {
+                if let Some(parent_id) = ids.get(parent) {
+                    writeln!(dot, "    u{} -> u{};", id, parent_id)
+                        .expect("writing to a string works");
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
     pub(crate) fn limit_per_node(&self) -> Round {
         self.max_round
     }
+
+    /// Raises the limit of rounds of accepted units, lower values are ignored. It is never raised
+    /// above [`MAX_MAX_ROUND`].
+    pub(crate) fn extend_max_round(&mut self, max_round: Round) {
+        self.max_round = self.max_round.max(max_round.min(MAX_MAX_ROUND));
+    }
 }
 
 #[cfg(test)]
@@ -130,10 +236,10 @@ mod tests {
         nodes::NodeMap,
         testing::mock::{Data, Hasher64, KeyBox},
         units::{ControlHash, FullUnit, PreUnit, SignedUnit, UnitCoord, UnitStore},
-        NodeCount, NodeIndex, Round, Signed,
+        NodeCount, NodeIndex, Round, Signed, MAX_MAX_ROUND,
     };
 
-    async fn create_unit<'a>(
+    async fn create_unit(
         round: Round,
         node_idx: NodeIndex,
         count: NodeCount,
@@ -210,4 +316,84 @@ mod tests {
             assert!(store.by_hash.contains_key(hash));
         }
     }
+
+    #[tokio::test]
+    async fn prunes_finalized_units_apart_from_newest() {
+        let n_nodes = NodeCount(2);
+        let mut store = UnitStore::<Hasher64, Data, KeyBox>::new(n_nodes, 100);
+        let keyboxes: Vec<_> = (0..2).map(|i| KeyBox::new(n_nodes, NodeIndex(i))).collect();
+
+        let mut units = Vec::new();
+        for round in 0..3 {
+            for (i, keybox) in keyboxes.iter().enumerate() {
+                let unit = create_unit(round, NodeIndex(i), n_nodes, 0, keybox).await;
+                store.add_unit(unit.clone(), false);
+                units.push(unit);
+            }
+        }
+        // Everything up to the newest unit of node 0.
+        for unit in &units[..5] {
+            store.mark_finalized(unit.as_signable().hash());
+        }
+
+        assert_eq!(store.prune_finalized(), 4);
+        assert_eq!(store.len(), 2);
+        for unit in &units[..4] {
+            let coord = unit.as_signable().coord();
+            assert!(store.unit_by_coord(coord).is_none());
+            assert!(store.contains_coord(&coord));
+        }
+        for unit in &units[4..] {
+            assert!(store.contains_hash(&unit.as_signable().hash()));
+        }
+
+        // A pruned unit arriving again is ignored.
+        store.yield_buffer_units();
+        store.add_unit(units[0].clone(), false);
+        assert_eq!(store.len(), 2);
+        assert!(store.yield_buffer_units().is_empty());
+        assert_eq!(store.prune_finalized(), 0);
+
+        // So is another variant of it, as there is nothing to prove the fork with.
+        let variant = create_unit(0, NodeIndex(0), n_nodes, 1, &keyboxes[0]).await;
+        assert!(store.is_new_fork(variant.as_signable()).is_none());
+        store.add_unit(variant, false);
+        assert_eq!(store.len(), 2);
+        assert!(store.yield_buffer_units().is_empty());
+    }
+
+    #[tokio::test]
+    async fn renders_dag_to_dot() {
+        let n_nodes = NodeCount(2);
+        let mut store = UnitStore::<Hasher64, Data, KeyBox>::new(n_nodes, 100);
+        let keyboxes: Vec<_> = (0..2).map(|i| KeyBox::new(n_nodes, NodeIndex(i))).collect();
+
+        let mut hashes = Vec::new();
+        for round in 0..2 {
+            for (i, keybox) in keyboxes.iter().enumerate() {
+                let unit = create_unit(round, NodeIndex(i), n_nodes, 0, keybox).await;
+                hashes.push(unit.as_signable().hash());
+                store.add_unit(unit, false);
+            }
+        }
+        store.add_parents(hashes[0], vec![]);
+        store.add_parents(hashes[1], vec![]);
+        store.add_parents(hashes[2], vec![hashes[0], hashes[1]]);
+        store.mark_finalized(hashes[0]);
+        // The last unit is not in the Dag yet.
+
+        assert_eq!(
+            store.dag_to_dot(),
+            "digraph dag {\n    rankdir=BT;\n    u0 [label=\"(0, 0)\", style=filled];\n    u1 [label=\"(1, 0)\"];\n    u2 [label=\"(0, 1)\"];\n    u2 -> u0;\n    u2 -> u1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn max_round_is_not_extended_past_the_limit() {
+        let mut store = UnitStore::<Hasher64, Data, KeyBox>::new(NodeCount(4), 100);
+        store.extend_max_round(50);
+        assert_eq!(store.limit_per_node(), 100);
+        store.extend_max_round(Round::MAX);
+        assert_eq!(store.limit_per_node(), MAX_MAX_ROUND);
+    }
 }
@@ -0,0 +1,35 @@
+//! The units of the AlephBFT Dag and the checks of their structure, for light clients and
+//! verifiers which cannot run the full member. Depends only on `alloc`, so it works with `no_std`.
+#![no_std]
+
+extern crate alloc;
+
+use codec::{Decode, Encode};
+use core::{fmt::Debug, hash::Hash as StdHash};
+
+mod nodes;
+mod units;
+
+pub use nodes::{NodeCount, NodeIndex, NodeMap, NodeSubset, NodeWeights};
+pub use units::{unit_order, ControlHash, ParentsError, PreUnit, Unit, UnitCoord};
+
+/// A hasher, used for creating identifiers for blocks or units.
+pub trait Hasher: Eq + Clone + Send + Sync + Debug + 'static {
+    /// A hash, as an identifier for a block or unit.
+    type Hash: AsRef<[u8]>
+        + Eq
+        + Ord
+        + Copy
+        + Clone
+        + Send
+        + Sync
+        + Debug
+        + StdHash
+        + Encode
+        + Decode;
+
+    fn hash(s: &[u8]) -> Self::Hash;
+}
+
+/// An asynchronous round of the protocol.
+pub type Round = u16;
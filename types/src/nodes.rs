@@ -0,0 +1,482 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use codec::{Decode, Encode, Error, Input, Output};
+use core::{
+    convert::TryFrom,
+    num::TryFromIntError,
+    ops::{Div, Index, Mul, Range},
+};
+use derive_more::{Add, AddAssign, From, Into, Sub, SubAssign, Sum};
+
+/// The index of a node
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, PartialOrd, Hash, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeIndex(pub usize);
+
+impl NodeIndex {
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+}
+
+// The indices and counts are usually sent as, or received from, fixed width integers, and these
+// need not fit in a `usize`, nor the other way around.
+macro_rules! impl_int_conversions {
+    ($name:ident) => {
+        impl_int_conversions!($name, from u32, u64);
+        impl_int_conversions!($name, into u16, u32, u64);
+    };
+    ($name:ident, from $($int:ty),*) => {
+        $(
+            impl TryFrom<$int> for $name {
+                type Error = TryFromIntError;
+
+                fn try_from(value: $int) -> Result<Self, Self::Error> {
+                    usize::try_from(value).map($name)
+                }
+            }
+        )*
+    };
+    ($name:ident, into $($int:ty),*) => {
+        $(
+            impl TryFrom<$name> for $int {
+                type Error = TryFromIntError;
+
+                fn try_from(value: $name) -> Result<Self, Self::Error> {
+                    <$int>::try_from(value.0)
+                }
+            }
+        )*
+    };
+}
+
+impl_int_conversions!(NodeIndex);
+
+impl Encode for NodeIndex {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        let val = self.0 as u64;
+        let bytes = val.to_le_bytes();
+        dest.write(&bytes);
+    }
+}
+
+impl Decode for NodeIndex {
+    fn decode<I: Input>(value: &mut I) -> Result<Self, Error> {
+        let mut arr = [0u8; 8];
+        value.read(&mut arr)?;
+        let val: u64 = u64::from_le_bytes(arr);
+        NodeIndex::try_from(val).map_err(|_| Error::from("Node index out of range."))
+    }
+}
+
+/// Node count. Right now it doubles as node weight in many places in the code, in the future we
+/// might need a new type for that.
+#[derive(
+    Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Add, Sub, AddAssign, SubAssign, Sum, From, Into,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeCount(pub usize);
+
+impl_int_conversions!(NodeCount);
+
+// deriving Mul and Div is somehow cumbersome
+impl Mul<usize> for NodeCount {
+    type Output = Self;
+    fn mul(self, rhs: usize) -> Self::Output {
+        NodeCount(self.0 * rhs)
+    }
+}
+
+impl Div<usize> for NodeCount {
+    type Output = Self;
+    fn div(self, rhs: usize) -> Self::Output {
+        NodeCount(self.0 / rhs)
+    }
+}
+
+impl NodeCount {
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+
+    /// The indices of all the members of a committee of this size, in increasing order.
+    pub fn all_indices(self) -> impl Iterator<Item = NodeIndex> {
+        (0..self.0).map(NodeIndex)
+    }
+
+    /// The indices of the members of a committee of this size within the given range of
+    /// `usize`s, the rest of the range is skipped.
+    pub fn indices_in(self, range: Range<usize>) -> impl Iterator<Item = NodeIndex> {
+        (range.start..range.end.min(self.0)).map(NodeIndex)
+    }
+
+    /// Whether the given index is one of a member of a committee of this size.
+    pub fn contains(self, node_ix: NodeIndex) -> bool {
+        node_ix.0 < self.0
+    }
+
+    /// The index of the member at the given position, if there is such a member.
+    pub fn index(self, position: usize) -> Option<NodeIndex> {
+        (position < self.0).then_some(NodeIndex(position))
+    }
+
+    pub fn into_range(self) -> Range<NodeIndex> {
+        Range {
+            start: 0.into(),
+            end: self.0.into(),
+        }
+    }
+
+    #[deprecated(note = "use `NodeCount::all_indices` instead")]
+    pub fn into_iterator(self) -> impl Iterator<Item = NodeIndex> {
+        self.all_indices()
+    }
+}
+
+/// A container keeping items indexed by NodeIndex.
+#[derive(Clone, Debug, Eq, PartialEq, From, Encode, Decode)]
+pub struct NodeMap<T>(Vec<Option<T>>);
+
+impl<T> NodeMap<T> {
+    /// Constructs a new node map with a given length.
+    pub fn with_size(len: NodeCount) -> Self
+    where
+        T: Clone,
+    {
+        let v = vec![None; len.into()];
+        NodeMap(v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeIndex, &T)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, maybe_value)| Some((NodeIndex(idx), maybe_value.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (NodeIndex, &mut T)> {
+        self.0
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, maybe_value)| Some((NodeIndex(idx), maybe_value.as_mut()?)))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter(self) -> impl Iterator<Item = (NodeIndex, T)>
+    where
+        T: 'static,
+    {
+        self.0
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, maybe_value)| Some((NodeIndex(idx), maybe_value?)))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn into_values(self) -> impl Iterator<Item = T>
+    where
+        T: 'static,
+    {
+        self.into_iter().map(|(_, value)| value)
+    }
+
+    pub fn get(&self, node_id: NodeIndex) -> Option<&T> {
+        self.0[node_id.0].as_ref()
+    }
+
+    pub fn insert(&mut self, node_id: NodeIndex, value: T) {
+        self.0[node_id.0] = Some(value)
+    }
+
+    pub fn to_subset(&self) -> NodeSubset {
+        NodeSubset(self.0.iter().map(Option::is_some).collect())
+    }
+}
+
+impl<T: 'static> IntoIterator for NodeMap<T> {
+    type Item = (NodeIndex, T);
+    type IntoIter = Box<dyn Iterator<Item = (NodeIndex, T)>>;
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.into_iter())
+    }
+}
+
+impl<'a, T> IntoIterator for &'a NodeMap<T> {
+    type Item = (NodeIndex, &'a T);
+    type IntoIter = Box<dyn Iterator<Item = (NodeIndex, &'a T)> + 'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut NodeMap<T> {
+    type Item = (NodeIndex, &'a mut T);
+    type IntoIter = Box<dyn Iterator<Item = (NodeIndex, &'a mut T)> + 'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter_mut())
+    }
+}
+
+/// The weights of the members of the committee, e.g. their stakes. A set of members is a quorum
+/// when their weights add up to more than two thirds of the total weight. With uniform weights,
+/// which is the default, this is the usual requirement of more than 2N/3 members.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeWeights(Vec<u64>);
+
+impl NodeWeights {
+    /// The weights of the members, in the order of their indices.
+    pub fn new(weights: Vec<u64>) -> Self {
+        NodeWeights(weights)
+    }
+
+    /// Every member of the committee has the same weight.
+    pub fn uniform(n_members: NodeCount) -> Self {
+        NodeWeights(vec![1; n_members.0])
+    }
+
+    pub fn n_members(&self) -> NodeCount {
+        NodeCount(self.0.len())
+    }
+
+    /// The weight of the member, zero for indices outside of the committee.
+    pub fn weight(&self, node_ix: NodeIndex) -> u64 {
+        self.0.get(node_ix.0).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u128 {
+        self.0.iter().map(|weight| *weight as u128).sum()
+    }
+
+    /// The total weight of the given members, counting every member once.
+    pub fn weight_of(&self, members: impl IntoIterator<Item = NodeIndex>) -> u128 {
+        let mut counted = NodeSubset::with_size(self.n_members());
+        members
+            .into_iter()
+            .filter(|member| member.0 < self.0.len())
+            .map(|member| {
+                if counted[member] {
+                    return 0;
+                }
+                counted.insert(member);
+                self.weight(member) as u128
+            })
+            .sum()
+    }
+
+    /// Whether the given members carry more than two thirds of the total weight.
+    pub fn is_quorum(&self, members: impl IntoIterator<Item = NodeIndex>) -> bool {
+        3 * self.weight_of(members) > 2 * self.total()
+    }
+
+    /// The smallest number of members forming a quorum, i.e. the number of the heaviest members
+    /// needed for it.
+    pub fn min_quorum_size(&self) -> NodeCount {
+        let mut weights = self.0.clone();
+        weights.sort_unstable_by(|a, b| b.cmp(a));
+        let mut collected = 0u128;
+        for (count, weight) in weights.into_iter().enumerate() {
+            if 3 * collected > 2 * self.total() {
+                return NodeCount(count);
+            }
+            collected += weight as u128;
+        }
+        self.n_members()
+    }
+}
+
+/// A set of members of the committee, e.g. the creators of the parents of a unit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NodeSubset(bit_vec::BitVec<u32>);
+
+impl NodeSubset {
+    pub fn with_size(capacity: NodeCount) -> Self {
+        NodeSubset(bit_vec::BitVec::from_elem(capacity.0, false))
+    }
+
+    pub fn insert(&mut self, i: NodeIndex) {
+        self.0.set(i.0, true);
+    }
+
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| if b { Some(i.into()) } else { None })
+    }
+}
+
+impl Encode for NodeSubset {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        (self.0.len() as u32).encode_to(dest);
+        self.0.to_bytes().encode_to(dest);
+    }
+}
+
+impl Decode for NodeSubset {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, Error> {
+        let capacity = u32::decode(input)? as usize;
+        let bytes = Vec::decode(input)?;
+        let mut bv = bit_vec::BitVec::from_bytes(&bytes);
+        // Length should be capacity rounded up to the closest multiple of 8
+        if bv.len() != 8 * capacity.div_ceil(8) {
+            return Err(Error::from(
+                "Length of bitvector inconsistent with encoded capacity.",
+            ));
+        }
+        while bv.len() > capacity {
+            if bv.pop() != Some(false) {
+                return Err(Error::from(
+                    "Non-canonical encoding. Trailing bits should be all 0.",
+                ));
+            }
+        }
+        bv.truncate(capacity);
+        Ok(NodeSubset(bv))
+    }
+}
+
+impl Index<NodeIndex> for NodeSubset {
+    type Output = bool;
+
+    fn index(&self, vidx: NodeIndex) -> &bool {
+        &self.0[vidx.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nodes::{NodeCount, NodeIndex, NodeSubset, NodeWeights};
+    use alloc::{vec, vec::Vec};
+    use codec::{Decode, Encode};
+    use core::convert::TryFrom;
+
+    #[test]
+    fn decoding_node_index_works() {
+        for i in 0..1000 {
+            let node_index = NodeIndex(i);
+            let mut encoded: &[u8] = &node_index.encode();
+            let decoded = NodeIndex::decode(&mut encoded);
+            assert_eq!(node_index, decoded.unwrap());
+        }
+    }
+
+    #[test]
+    fn bool_node_map_decoding_works() {
+        for len in 0..12 {
+            for mask in 0..(1 << len) {
+                let mut bnm = NodeSubset::with_size(len.into());
+                for i in 0..len {
+                    if (1 << i) & mask != 0 {
+                        bnm.insert(i.into());
+                    }
+                }
+                let encoded: Vec<_> = bnm.encode();
+                let decoded =
+                    NodeSubset::decode(&mut encoded.as_slice()).expect("decode should work");
+                assert!(decoded == bnm);
+            }
+        }
+    }
+
+    #[test]
+    fn bool_node_map_decoding_deals_with_trailing_zeros() {
+        let mut encoded = vec![1, 0, 0, 0];
+        encoded.extend(vec![128u8].encode());
+        //128 encodes bit-vec 10000000
+        let decoded = NodeSubset::decode(&mut encoded.as_slice()).expect("decode should work");
+        assert_eq!(decoded, NodeSubset([true].iter().cloned().collect()));
+
+        let mut encoded = vec![1, 0, 0, 0];
+        encoded.extend(vec![129u8].encode());
+        //129 encodes bit-vec 10000001
+        assert!(NodeSubset::decode(&mut encoded.as_slice()).is_err());
+    }
+
+    #[test]
+    fn bool_node_map_decoding_deals_with_too_long_bitvec() {
+        let mut encoded = vec![1, 0, 0, 0];
+        encoded.extend(vec![128u8, 0].encode());
+        //[128, 0] encodes bit-vec 1000000000000000
+        assert!(NodeSubset::decode(&mut encoded.as_slice()).is_err());
+    }
+
+    #[test]
+    fn decoding_bool_node_map_works() {
+        let bool_node_map = NodeSubset([true, false, true, true, true].iter().cloned().collect());
+        let encoded: Vec<_> = bool_node_map.encode();
+        let decoded = NodeSubset::decode(&mut encoded.as_slice()).expect("decode should work");
+        assert_eq!(decoded, bool_node_map);
+    }
+
+    #[test]
+    fn uniform_weights_need_more_than_two_thirds_of_members() {
+        for n_members in 1..20 {
+            let weights = NodeWeights::uniform(NodeCount(n_members));
+            let threshold = (2 * n_members) / 3 + 1;
+            assert!(weights.is_quorum((0..threshold).map(NodeIndex)));
+            assert!(!weights.is_quorum((0..threshold - 1).map(NodeIndex)));
+            assert_eq!(weights.min_quorum_size(), NodeCount(threshold));
+        }
+    }
+
+    #[test]
+    fn weighted_quorum_depends_on_weights() {
+        let weights = NodeWeights::new(vec![5, 1, 1, 1]);
+        assert!(weights.is_quorum([NodeIndex(0), NodeIndex(1)]));
+        assert!(!weights.is_quorum([NodeIndex(0)]));
+        assert!(!weights.is_quorum([NodeIndex(1), NodeIndex(2), NodeIndex(3)]));
+        // Repeated and unknown members add nothing.
+        assert!(!weights.is_quorum([NodeIndex(0), NodeIndex(0), NodeIndex(7)]));
+        assert_eq!(weights.min_quorum_size(), NodeCount(2));
+    }
+
+    #[test]
+    fn node_index_rejects_encoding_out_of_range() {
+        let encoded = u64::MAX.encode();
+        let decoded = NodeIndex::decode(&mut encoded.as_slice());
+        assert_eq!(decoded.is_ok(), usize::try_from(u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn converts_to_and_from_integers() {
+        assert_eq!(NodeIndex::try_from(7u32), Ok(NodeIndex(7)));
+        assert_eq!(NodeCount::try_from(7u64), Ok(NodeCount(7)));
+        assert_eq!(u16::try_from(NodeIndex(7)), Ok(7));
+        assert_eq!(u64::try_from(NodeCount(7)), Ok(7));
+        assert!(u16::try_from(NodeIndex(1 << 16)).is_err());
+        assert_eq!(NodeIndex::from(3usize).as_usize(), 3);
+        assert_eq!(usize::from(NodeCount(3)), NodeCount(3).as_usize());
+    }
+
+    #[test]
+    fn iterates_over_committee_indices() {
+        let n_members = NodeCount(4);
+        let all: Vec<_> = n_members.all_indices().collect();
+        assert_eq!(all, (0..4).map(NodeIndex).collect::<Vec<_>>());
+        assert_eq!(NodeCount(0).all_indices().count(), 0);
+        assert_eq!(
+            n_members.indices_in(2..7).collect::<Vec<_>>(),
+            vec![NodeIndex(2), NodeIndex(3)]
+        );
+        assert_eq!(n_members.indices_in(5..7).count(), 0);
+        assert!(n_members.contains(NodeIndex(3)));
+        assert!(!n_members.contains(NodeIndex(4)));
+        assert_eq!(n_members.index(3), Some(NodeIndex(3)));
+        assert_eq!(n_members.index(4), None);
+    }
+
+    #[test]
+    fn test_bool_node_map_has_efficient_encoding() {
+        let mut bnm = NodeSubset::with_size(100.into());
+        for i in 0..50 {
+            bnm.insert(i.into())
+        }
+        assert!(bnm.encode().len() < 20);
+    }
+}
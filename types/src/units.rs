@@ -0,0 +1,315 @@
+use crate::{
+    nodes::{NodeCount, NodeIndex, NodeMap, NodeSubset, NodeWeights},
+    Hasher, Round,
+};
+use alloc::vec::Vec;
+use codec::{Decode, Encode};
+use core::cmp::Ordering;
+
+/// The place of a unit in the Dag, i.e. its round and creator.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Encode, Decode, Hash)]
+pub struct UnitCoord {
+    round: Round,
+    creator: NodeIndex,
+}
+
+impl UnitCoord {
+    pub fn new(round: Round, creator: NodeIndex) -> Self {
+        Self { creator, round }
+    }
+
+    pub fn creator(&self) -> NodeIndex {
+        self.creator
+    }
+
+    pub fn round(&self) -> Round {
+        self.round
+    }
+}
+
+/// Combined hashes of the parents of a unit together with the set of indices of creators of the
+/// parents
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct ControlHash<H: Hasher> {
+    pub parents_mask: NodeSubset,
+    pub combined_hash: H::Hash,
+}
+
+impl<H: Hasher> ControlHash<H> {
+    pub fn new(parent_map: &NodeMap<H::Hash>) -> Self {
+        ControlHash {
+            parents_mask: parent_map.to_subset(),
+            combined_hash: Self::combine_hashes(parent_map),
+        }
+    }
+
+    pub fn combine_hashes(parent_map: &NodeMap<H::Hash>) -> H::Hash {
+        parent_map.using_encoded(H::hash)
+    }
+
+    pub fn parents(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.parents_mask.elements()
+    }
+
+    pub fn n_parents(&self) -> NodeCount {
+        NodeCount(self.parents().count())
+    }
+
+    pub fn n_members(&self) -> NodeCount {
+        NodeCount(self.parents_mask.size())
+    }
+}
+
+/// Why the parents of a unit break the structure of the Dag, see [`PreUnit::check_parents`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParentsError {
+    /// The parents are of a committee of this size, instead of ours.
+    WrongCommitteeSize(NodeCount),
+    /// Units of round 0 have no parents.
+    ParentsOfInitialUnit,
+    /// The unit of the creator of the previous round is not among the parents.
+    MissingOwnParent,
+    /// The parents carry at most two thirds of the total weight.
+    NotQuorum,
+}
+
+/// The simplest type representing a unit, consisting of coordinates and a control hash
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct PreUnit<H: Hasher> {
+    coord: UnitCoord,
+    control_hash: ControlHash<H>,
+}
+
+impl<H: Hasher> PreUnit<H> {
+    pub fn new(creator: NodeIndex, round: Round, control_hash: ControlHash<H>) -> Self {
+        PreUnit {
+            coord: UnitCoord::new(round, creator),
+            control_hash,
+        }
+    }
+
+    pub fn n_parents(&self) -> NodeCount {
+        self.control_hash.n_parents()
+    }
+
+    pub fn n_members(&self) -> NodeCount {
+        self.control_hash.n_members()
+    }
+
+    pub fn creator(&self) -> NodeIndex {
+        self.coord.creator()
+    }
+
+    pub fn round(&self) -> Round {
+        self.coord.round()
+    }
+
+    pub fn coord(&self) -> UnitCoord {
+        self.coord
+    }
+
+    pub fn control_hash(&self) -> &ControlHash<H> {
+        &self.control_hash
+    }
+
+    /// Checks that the parents satisfy the constraints of the protocol for the committee with the
+    /// given weights: units of round 0 have none, and the other ones have the unit of their
+    /// creator among them and carry more than two thirds of the total weight. Whether the parents
+    /// are the units of the previous round with the combined hash cannot be told from the unit
+    /// alone.
+    pub fn check_parents(&self, node_weights: &NodeWeights) -> Result<(), ParentsError> {
+        let control_hash = &self.control_hash;
+        if control_hash.n_members() != node_weights.n_members() {
+            return Err(ParentsError::WrongCommitteeSize(control_hash.n_members()));
+        }
+        if self.round() == 0 {
+            if control_hash.n_parents() > NodeCount(0) {
+                return Err(ParentsError::ParentsOfInitialUnit);
+            }
+        } else if !control_hash.parents().any(|id| id == self.creator()) {
+            return Err(ParentsError::MissingOwnParent);
+        } else if !node_weights.is_quorum(control_hash.parents()) {
+            return Err(ParentsError::NotQuorum);
+        }
+        Ok(())
+    }
+}
+
+/// A unit of the Dag, as seen by the application. It carries no data, only its place in the Dag.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct Unit<H: Hasher> {
+    pre_unit: PreUnit<H>,
+    hash: H::Hash,
+    // Empty until the parents are reconstructed.
+    parents: Vec<H::Hash>,
+}
+
+impl<H: Hasher> Unit<H> {
+    pub fn new(pre_unit: PreUnit<H>, hash: H::Hash) -> Self {
+        Unit {
+            pre_unit,
+            hash,
+            parents: Vec::new(),
+        }
+    }
+
+    pub fn with_parents(self, parents: Vec<H::Hash>) -> Self {
+        Unit { parents, ..self }
+    }
+
+    /// The member that created the unit.
+    pub fn creator(&self) -> NodeIndex {
+        self.pre_unit.creator()
+    }
+
+    /// The round of the unit.
+    pub fn round(&self) -> Round {
+        self.pre_unit.round()
+    }
+
+    /// The hash of the unit.
+    pub fn hash(&self) -> H::Hash {
+        self.hash
+    }
+
+    /// The hashes of the parents of the unit, in the order of their creators. The parents are
+    /// known once the unit is added to the Dag, which holds for all the units AlephBFT passes out.
+    pub fn parents(&self) -> impl Iterator<Item = H::Hash> + '_ {
+        self.parents.iter().copied()
+    }
+
+    pub fn control_hash(&self) -> &ControlHash<H> {
+        self.pre_unit.control_hash()
+    }
+
+    /// The key of the unit in the order of [`unit_order`].
+    pub fn order_key(&self) -> (Round, usize, H::Hash) {
+        (self.round(), self.creator().0, self.hash)
+    }
+}
+
+/// The total order of units used wherever ties between units are broken, the same on every node
+/// whatever order the units were received in: by round, then by the index of the creator, then
+/// by hash, which only decides between different variants of a unit of a forker.
+///
+/// The only exception is the consensus choosing the head of a round, which considers the units of
+/// the round by hash alone. That order is a part of the protocol, and it does not favor the
+/// members with low indices.
+pub fn unit_order<H: Hasher>(a: &Unit<H>, b: &Unit<H>) -> Ordering {
+    a.order_key().cmp(&b.order_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        nodes::{NodeCount, NodeIndex, NodeWeights},
+        units::{unit_order, ControlHash, ParentsError, PreUnit, Unit},
+        Hasher,
+    };
+    use alloc::{vec, vec::Vec};
+    use codec::{Decode, Encode};
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct XorHasher;
+
+    impl Hasher for XorHasher {
+        type Hash = [u8; 8];
+
+        fn hash(s: &[u8]) -> Self::Hash {
+            let mut hash = [0; 8];
+            for (i, byte) in s.iter().enumerate() {
+                hash[i % 8] ^= byte.wrapping_add(i as u8);
+            }
+            hash
+        }
+    }
+
+    #[test]
+    fn unit_order_does_not_depend_on_insertion_order() {
+        let ch = ControlHash::<XorHasher>::new(&vec![None; 3].into());
+        let mut units = Vec::new();
+        for round in 0..3 {
+            for creator in 0..3 {
+                let pre_unit = PreUnit::new(NodeIndex(creator), round, ch.clone());
+                units.push(Unit::new(pre_unit, [(9 - creator) as u8; 8]));
+            }
+        }
+        // Another variant of the unit of a forker.
+        let pre_unit = PreUnit::new(NodeIndex(1), 1, ch.clone());
+        units.insert(4, Unit::new(pre_unit, [0; 8]));
+        let expected: Vec<_> = units
+            .iter()
+            .map(|unit| (unit.round(), unit.creator().0, unit.hash()))
+            .collect();
+        let mut sorted_by_hash = units.clone();
+        sorted_by_hash.sort_by_key(|unit| unit.hash());
+        let mut reversed = units.clone();
+        reversed.reverse();
+        let mut interleaved = units.clone();
+        interleaved.rotate_left(5);
+        for mut units in [units, sorted_by_hash, reversed, interleaved] {
+            units.sort_by(unit_order);
+            let order: Vec<_> = units
+                .iter()
+                .map(|unit| (unit.round(), unit.creator().0, unit.hash()))
+                .collect();
+            assert_eq!(order, expected);
+        }
+    }
+
+    #[test]
+    fn test_unit_exposes_its_place_in_the_dag() {
+        let parents = vec![Some([0; 8]), None, Some([1; 8])].into();
+        let pre_unit = PreUnit::new(NodeIndex(1), 1, ControlHash::<XorHasher>::new(&parents));
+        let unit = Unit::new(pre_unit, [2; 8]);
+        assert_eq!(unit.creator(), NodeIndex(1));
+        assert_eq!(unit.round(), 1);
+        assert_eq!(unit.hash(), [2; 8]);
+        assert_eq!(unit.parents().count(), 0);
+        let unit = unit.with_parents(vec![[0; 8], [1; 8]]);
+        assert_eq!(unit.parents().collect::<Vec<_>>(), vec![[0; 8], [1; 8]]);
+    }
+
+    #[test]
+    fn test_control_hash_codec() {
+        let ch = ControlHash::<XorHasher>::new(&vec![Some([0; 8]), None, Some([1; 8])].into());
+        let encoded = ch.encode();
+        let decoded =
+            ControlHash::decode(&mut encoded.as_slice()).expect("should decode correctly");
+        assert_eq!(decoded, ch);
+    }
+
+    #[test]
+    fn checks_parents() {
+        let weights = NodeWeights::uniform(NodeCount(4));
+        let pre_unit = |creator, round, parents: &[usize]| {
+            let mut parent_map = vec![None; 4];
+            for parent in parents {
+                parent_map[*parent] = Some([*parent as u8; 8]);
+            }
+            PreUnit::new(
+                NodeIndex(creator),
+                round,
+                ControlHash::<XorHasher>::new(&parent_map.into()),
+            )
+        };
+        assert_eq!(pre_unit(0, 0, &[]).check_parents(&weights), Ok(()));
+        assert_eq!(pre_unit(0, 1, &[0, 1, 2]).check_parents(&weights), Ok(()));
+        assert_eq!(
+            pre_unit(0, 0, &[0]).check_parents(&weights),
+            Err(ParentsError::ParentsOfInitialUnit)
+        );
+        assert_eq!(
+            pre_unit(3, 1, &[0, 1, 2]).check_parents(&weights),
+            Err(ParentsError::MissingOwnParent)
+        );
+        assert_eq!(
+            pre_unit(0, 1, &[0, 1]).check_parents(&weights),
+            Err(ParentsError::NotQuorum)
+        );
+        assert_eq!(
+            pre_unit(0, 1, &[0, 1, 2]).check_parents(&NodeWeights::uniform(NodeCount(5))),
+            Err(ParentsError::WrongCommitteeSize(NodeCount(4)))
+        );
+    }
+}